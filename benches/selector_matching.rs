@@ -0,0 +1,21 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use pentas::bench::BenchDocument;
+
+const FIXTURES: [(&str, &str); 2] = [
+    ("article", include_str!("fixtures/article.html")),
+    ("listing", include_str!("fixtures/listing.html")),
+];
+
+/// Matches all the selectors of the style sheets, including the UA style sheet, against all the elements.
+fn selector_matching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("selector_matching");
+    for (name, html) in FIXTURES {
+        let matching = BenchDocument::parse(html).unwrap().selector_matching();
+        group.bench_function(name, |b| b.iter(|| black_box(matching.run())));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, selector_matching);
+criterion_main!(benches);
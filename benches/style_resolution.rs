@@ -0,0 +1,23 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use pentas::bench::BenchDocument;
+
+const FIXTURES: [(&str, &str); 2] = [
+    ("article", include_str!("fixtures/article.html")),
+    ("listing", include_str!("fixtures/listing.html")),
+];
+
+/// Computes the styles of all the nodes of the parsed documents from scratch.
+fn style_resolution(c: &mut Criterion) {
+    let mut group = c.benchmark_group("style_resolution");
+    for (name, html) in FIXTURES {
+        let document = BenchDocument::parse(html).unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(document.resolve_styles().unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, style_resolution);
+criterion_main!(benches);
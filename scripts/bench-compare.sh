@@ -0,0 +1,40 @@
+#!/bin/sh
+# Compares the benchmarks of the working tree with the ones of a git revision, and fails if any of them
+# regressed beyond the noise threshold.
+#
+# Usage: scripts/bench-compare.sh [<base revision>] [<noise threshold>]
+#   e.g. scripts/bench-compare.sh main 0.05
+#
+# The base revision must have the benchmarks too. The results, including the HTML reports, are left in
+# target/criterion.
+
+set -eu
+
+BASE=${1:-main}
+THRESHOLD=${2:-0.05}
+ROOT=$(git rev-parse --show-toplevel)
+WORKTREE=$(mktemp -d)
+LOG=$(mktemp)
+
+cleanup() {
+    git -C "$ROOT" worktree remove --force "$WORKTREE" >/dev/null 2>&1 || true
+    rm -f "$LOG"
+}
+trap cleanup EXIT
+
+git -C "$ROOT" worktree add --detach "$WORKTREE" "$BASE" >/dev/null
+
+# Both runs share the target directory, in which criterion keeps the baselines.
+export CARGO_TARGET_DIR="$ROOT/target"
+
+echo "Benchmarking $BASE..."
+(cd "$WORKTREE" && cargo bench --benches -- --save-baseline base --noise-threshold "$THRESHOLD")
+
+echo "Benchmarking the working tree..."
+(cd "$ROOT" && cargo bench --benches -- --baseline base --noise-threshold "$THRESHOLD") | tee "$LOG"
+
+if grep -q "Performance has regressed" "$LOG"; then
+    echo "Some benchmarks regressed against $BASE." >&2
+    exit 1
+fi
+echo "No benchmarks regressed against $BASE."
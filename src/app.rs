@@ -1,15 +1,60 @@
-use anyhow::{Ok, Result};
+use anyhow::Context;
 use gtk4::prelude::WidgetExt;
-use gtk4::{self, DrawingArea};
+use gtk4::{self, pango, DrawingArea};
+use tracing_chrome::ChromeLayerBuilder;
+use tracing_subscriber::prelude::*;
 
-use crate::renderer::{print_box_tree, print_style_sheet};
-use crate::ui::show_ui;
+use crate::devtools::DevToolsServer;
+use crate::error::{Error, Result};
+use crate::renderer::{
+    decode_html, get_computed_styles, get_css_parse_errors, get_html_parse_errors, get_line_dump,
+    get_live_announcements, get_page_metadata, get_pipeline_stats, get_serialized_dom,
+    print_box_tree, print_style_sheet, render_for_print, set_dictionary,
+    set_text_rendering_options, Dictionary, MediaEnvironment, TextRenderingOptions,
+};
+use crate::ui::{print_pages, set_font_options, Browser};
 
 #[derive(Debug)]
 pub struct Config {
     pub no_window_html: Option<String>,
     pub no_window_css: Option<String>,
     pub verbosity: VerbosityLevel,
+    pub trace: Option<String>,
+    pub dump: Option<DumpTarget>,
+    /// Whether the pages are rendered with the system colors instead of their own colors.
+    pub forced_colors: bool,
+    /// The initial size of the window, which is the viewport against which the media queries are evaluated.
+    /// The pages are laid out in the viewport of this size in CLI mode.
+    pub window_size: (i32, i32),
+    /// The local port on which the remote debugging protocol is served in window mode, if any.
+    pub devtools_port: Option<u16>,
+    /// The selectors of the elements to click in order before the box tree is printed in CLI mode.
+    pub clicks: Vec<String>,
+    /// The PDF file to which the HTML input is printed instead of printing the box tree in CLI mode.
+    pub print_pdf: Option<String>,
+    /// How the text is rasterized and which fonts are used, which can be changed later on the settings page
+    /// in window mode.
+    pub text_rendering: TextRenderingOptions,
+}
+
+/// What to print instead of the default output in CLI mode.
+#[derive(Debug, Clone, Copy)]
+pub enum DumpTarget {
+    /// The parse errors of the input, one per line.
+    Errors,
+    /// The metadata of an HTML input, such as the icons, the canonical URL and the Open Graph properties.
+    Metadata,
+    /// The DOM tree of an HTML input serialized back into HTML, which can be parsed again into the same tree.
+    DomHtml,
+    /// The announcements of the `aria-live` regions of an HTML input while it is loaded, one per line.
+    Announcements,
+    /// The statistics of the parsing, the styling and the layout of an HTML input.
+    Stats,
+    /// The computed style of each rendered element of an HTML input, one per line.
+    ComputedStyle,
+    /// The lines into which the texts of an HTML input are wrapped, with their break opportunities and the
+    /// advance widths of their runs, grouped by the blocks they are in.
+    Lines,
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -30,25 +75,184 @@ impl Runner {
         Self { config }
     }
 
+    /// Runs in the mode given by the configuration. The errors are classified by the failures so that the
+    /// CLI can exit with a status for each kind.
     pub fn run(&self) -> Result<()> {
+        // The trace file is written when the guard is dropped, so it must live until the end of this function.
+        // Tracing fails if another subscriber has been set globally, e.g. by the application embedding this.
+        let _trace_guard = self
+            .config
+            .trace
+            .as_ref()
+            .map(|p| {
+                let (chrome_layer, guard) =
+                    ChromeLayerBuilder::new().file(p).include_args(true).build();
+                tracing_subscriber::registry()
+                    .with(chrome_layer)
+                    .try_init()
+                    .map_err(|e| Error::Config(format!("Failed to trace to {}: {}", p, e)))?;
+                Ok(guard)
+            })
+            .transpose()?;
+        set_text_rendering_options(self.config.text_rendering.clone());
+        let media = MediaEnvironment {
+            forced_colors: self.config.forced_colors,
+            viewport_width: self.config.window_size.0,
+            viewport_height: self.config.window_size.1,
+            ..Default::default()
+        };
+
         match (&self.config.no_window_html, &self.config.no_window_css) {
+            (Some(p), None) if self.config.dump.is_some() => {
+                let html = decode_html(&read_file(p)?, None);
+                match self.config.dump.unwrap() {
+                    DumpTarget::Errors => get_html_parse_errors(&html)?
+                        .iter()
+                        .for_each(|e| println!("{}", e)),
+                    DumpTarget::Metadata => print!("{}", get_page_metadata(&html)?),
+                    DumpTarget::DomHtml => println!("{}", get_serialized_dom(&html)?),
+                    DumpTarget::Announcements => get_live_announcements(&html)
+                        .map_err(|e| Error::classify(e, |e| Error::html_parse(Vec::new(), e)))?
+                        .iter()
+                        .for_each(|a| println!("{}", a)),
+                    DumpTarget::Stats => {
+                        init_gtk()?;
+                        print!(
+                            "{}",
+                            get_pipeline_stats(&html, &media, &self.create_draw_context())
+                                .map_err(|e| Error::classify(e, Error::layout))?
+                        );
+                    }
+                    DumpTarget::ComputedStyle => get_computed_styles(&html, &media)
+                        .map_err(|e| Error::classify(e, Error::layout))?
+                        .iter()
+                        .for_each(|style| println!("{}", style)),
+                    DumpTarget::Lines => {
+                        init_gtk()?;
+                        print!(
+                            "{}",
+                            get_line_dump(&html, &media, &self.create_draw_context())
+                                .map_err(|e| Error::classify(e, Error::layout))?
+                        );
+                    }
+                }
+            }
+            (None, Some(p)) if self.config.dump.is_some() => {
+                let css = read_css_file(p)?;
+                match self.config.dump.unwrap() {
+                    DumpTarget::Errors => get_css_parse_errors(&css)?
+                        .iter()
+                        .for_each(|e| println!("{}", e)),
+                    DumpTarget::Metadata => return Err(requires_html_input("metadata")),
+                    DumpTarget::DomHtml => return Err(requires_html_input("dom-html")),
+                    DumpTarget::Announcements => return Err(requires_html_input("announcements")),
+                    DumpTarget::Stats => return Err(requires_html_input("stats")),
+                    DumpTarget::ComputedStyle => return Err(requires_html_input("computed-style")),
+                    DumpTarget::Lines => return Err(requires_html_input("lines")),
+                }
+            }
+            (Some(p), None) if self.config.print_pdf.is_some() => {
+                init_gtk()?;
+                let document_url = get_file_url(p)?;
+                let (page_settings, objects) = render_for_print(
+                    &decode_html(&read_file(p)?, None),
+                    &document_url,
+                    &media,
+                    &self.create_draw_context(),
+                    self.config.verbosity,
+                )
+                .map_err(|e| Error::classify(e, Error::layout))?;
+                print_pages(
+                    &objects,
+                    &page_settings,
+                    None,
+                    self.config.print_pdf.as_deref(),
+                )
+                .map_err(|e| Error::classify(e, Error::layout))?;
+            }
             (Some(p), None) => {
-                gtk4::init()?;
+                init_gtk()?;
+                // The external style sheets are resolved relative to the file.
+                let document_url = get_file_url(p)?;
                 print_box_tree(
-                    &std::fs::read_to_string(p)?,
-                    &DrawingArea::new().pango_context(),
+                    &decode_html(&read_file(p)?, None),
+                    &document_url,
+                    &media,
+                    &self.config.clicks,
+                    &self.create_draw_context(),
                     self.config.verbosity,
-                )?;
+                )
+                .map_err(|e| Error::classify(e, Error::layout))?;
             }
             (None, Some(p)) => {
-                print_style_sheet(&std::fs::read_to_string(p)?)?;
+                print_style_sheet(&read_css_file(p)?)
+                    .map_err(|e| Error::classify(e, |e| Error::css_parse(Vec::new(), e)))?;
             }
             (None, None) => {
-                show_ui(self.config.verbosity);
+                if self.config.dump.is_some() {
+                    return Err(Error::Config(
+                        "--dump requires --no-window-html or --no-window-css".to_string(),
+                    ));
+                }
+                let devtools = self
+                    .config
+                    .devtools_port
+                    .map(DevToolsServer::start)
+                    .transpose()
+                    .map_err(|e| Error::classify(e, Error::io))?;
+                if let Some(devtools) = &devtools {
+                    println!("DevTools listening on 127.0.0.1:{}", devtools.get_port());
+                }
+                // The spelling is checked only in window mode, so that the output in CLI mode doesn't depend
+                // on the dictionaries installed on the system.
+                match Dictionary::load_system() {
+                    Ok(dictionary) => set_dictionary(dictionary),
+                    Err(e) => eprintln!("The spelling is not checked: {:#}", e),
+                }
+                Browser::new(self.config.verbosity, media, devtools)
+                    .run()
+                    .map_err(|e| Error::classify(e, Error::io))?;
             }
             _ => unreachable!(),
         }
 
         Ok(())
     }
+
+    /// Returns the context with which the text is laid out in CLI mode. GTK must be initialized.
+    fn create_draw_context(&self) -> pango::Context {
+        let draw_ctx = DrawingArea::new().pango_context();
+        set_font_options(&draw_ctx, &self.config.text_rendering);
+        draw_ctx
+    }
+}
+
+fn init_gtk() -> Result<()> {
+    gtk4::init()
+        .context("Failed to initialize GTK")
+        .map_err(Error::layout)
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>> {
+    std::fs::read(path)
+        .with_context(|| format!("Failed to read {}", path))
+        .map_err(Error::io)
+}
+
+fn read_css_file(path: &str) -> Result<String> {
+    std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path))
+        .map_err(Error::io)
+}
+
+/// Returns the URL of the file, against which the relative URLs in it are resolved.
+fn get_file_url(path: &str) -> Result<String> {
+    let path = std::fs::canonicalize(path)
+        .with_context(|| format!("Failed to resolve {}", path))
+        .map_err(Error::io)?;
+    Ok(format!("file://{}", path.display()))
+}
+
+fn requires_html_input(dump: &str) -> Error {
+    Error::Config(format!("--dump {} requires --no-window-html", dump))
 }
@@ -1,8 +1,13 @@
-use clap::{Parser, ValueEnum};
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     #[arg(long, value_name = "HTML", help = "The HTML file to parse in CLI mode")]
     pub no_window_html: Option<String>,
 
@@ -22,6 +27,171 @@ pub struct Args {
         help = "Set the verbosity level"
     )]
     pub verbose: VerbosityLevel,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Record a trace of the rendering pipeline in Chrome trace-event format"
+    )]
+    pub trace: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "KIND",
+        help = "Print the given information about the input file instead of the tree in CLI mode"
+    )]
+    pub dump: Option<DumpTarget>,
+
+    #[arg(
+        long,
+        help = "Render the pages with the high-contrast system colors instead of their own colors"
+    )]
+    pub forced_colors: bool,
+
+    #[arg(
+        long,
+        default_value = "1200x800",
+        value_name = "WIDTHxHEIGHT",
+        value_parser = parse_window_size,
+        help = "Set the initial size of the window, which is the viewport of the pages in CLI mode"
+    )]
+    pub window_size: (i32, i32),
+
+    #[arg(
+        long,
+        value_name = "PORT",
+        help = "Serve the remote debugging protocol on the local port in window mode"
+    )]
+    pub devtools_port: Option<u16>,
+
+    #[arg(
+        long,
+        value_name = "SELECTOR",
+        requires = "no_window_html",
+        help = "Click the first element matching the selector before the tree is printed in CLI mode (repeatable)"
+    )]
+    pub click: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "no_window_html",
+        conflicts_with = "dump",
+        help = "Print the HTML file with the print media type to a PDF file instead of printing the tree in CLI mode"
+    )]
+    pub print_pdf: Option<String>,
+
+    #[arg(
+        long,
+        default_value_t = Antialiasing::System,
+        value_name = "MODE",
+        help = "Set how the edges of the glyphs are smoothed"
+    )]
+    pub antialiasing: Antialiasing,
+
+    #[arg(
+        long,
+        default_value_t = Hinting::System,
+        value_name = "LEVEL",
+        help = "Set how strongly the glyphs are fitted to the pixel grid"
+    )]
+    pub hinting: Hinting,
+
+    #[arg(
+        long,
+        value_name = "FAMILY",
+        help = "Set the font family used for the generic serif family"
+    )]
+    pub serif_font: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FAMILY",
+        help = "Set the font family used for the generic sans-serif family"
+    )]
+    pub sans_serif_font: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FAMILY",
+        help = "Set the font family used for the generic monospace family"
+    )]
+    pub monospace_font: Option<String>,
+
+    #[arg(
+        long,
+        default_value_t = 0.0,
+        value_name = "PX",
+        value_parser = parse_minimum_font_size,
+        help = "Enlarge the text smaller than the font size in pixels"
+    )]
+    pub minimum_font_size: f32,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Manage the disk cache of the fetched resources
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Render the built-in test pages into PNG files, and compare them with the reference screenshots
+    Gallery {
+        #[arg(
+            long,
+            default_value = "gallery",
+            value_name = "DIR",
+            help = "The directory to write the screenshots and the differences to"
+        )]
+        output: PathBuf,
+        #[arg(
+            long,
+            value_name = "DIR",
+            help = "The directory of the reference screenshots, which fails the command if they differ or are missing"
+        )]
+        reference: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheAction {
+    /// Remove all the cached resources
+    Clear,
+}
+
+fn parse_window_size(value: &str) -> Result<(i32, i32), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, found {}", value))?;
+    match (width.parse::<i32>(), height.parse::<i32>()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(format!("invalid window size: {}", value)),
+    }
+}
+
+fn parse_minimum_font_size(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(size) if (0.0..=72.0).contains(&size) => Ok(size),
+        _ => Err(format!("expected a size between 0 and 72, found {}", value)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DumpTarget {
+    /// The parse errors of the input file
+    Errors,
+    /// The metadata links, the description and the Open Graph properties of the input HTML file
+    Metadata,
+    /// The DOM tree of the input HTML file serialized back into HTML
+    DomHtml,
+    /// The announcements of the aria-live regions of the input HTML file while it is loaded
+    Announcements,
+    /// The token, rule, selector matching and layout counts of the input HTML file
+    Stats,
+    /// The computed style of each rendered element of the input HTML file
+    ComputedStyle,
+    /// The lines into which the texts of the input HTML file are wrapped, with the break opportunities and the glyph runs
+    Lines,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -40,3 +210,43 @@ impl std::fmt::Display for VerbosityLevel {
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Antialiasing {
+    System,
+    None,
+    Grayscale,
+    Subpixel,
+}
+
+impl std::fmt::Display for Antialiasing {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Antialiasing::System => write!(f, "system"),
+            Antialiasing::None => write!(f, "none"),
+            Antialiasing::Grayscale => write!(f, "grayscale"),
+            Antialiasing::Subpixel => write!(f, "subpixel"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Hinting {
+    System,
+    None,
+    Slight,
+    Medium,
+    Full,
+}
+
+impl std::fmt::Display for Hinting {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Hinting::System => write!(f, "system"),
+            Hinting::None => write!(f, "none"),
+            Hinting::Slight => write!(f, "slight"),
+            Hinting::Medium => write!(f, "medium"),
+            Hinting::Full => write!(f, "full"),
+        }
+    }
+}
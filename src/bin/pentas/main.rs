@@ -6,6 +6,24 @@ use pentas::{self, Config, Runner};
 
 fn main() {
     let args = cli::Args::parse();
+    if let Some(cli::Command::Cache {
+        action: cli::CacheAction::Clear,
+    }) = args.command
+    {
+        match pentas::clear_default_cache() {
+            Ok(dir) => println!("Cleared the cache in {}", dir.display()),
+            Err(e) => exit_with_error(&e),
+        }
+        return;
+    }
+    if let Some(cli::Command::Gallery { output, reference }) = &args.command {
+        match pentas::render_gallery(output, reference.as_deref()) {
+            Ok(results) => report_gallery(&results),
+            Err(e) => exit_with_error(&e),
+        }
+        return;
+    }
+
     let config = Config {
         no_window_html: args.no_window_html,
         no_window_css: args.no_window_css,
@@ -14,10 +32,90 @@ fn main() {
             cli::VerbosityLevel::Normal => pentas::VerbosityLevel::Normal,
             cli::VerbosityLevel::Verbose => pentas::VerbosityLevel::Verbose,
         },
+        trace: args.trace,
+        dump: args.dump.map(|d| match d {
+            cli::DumpTarget::Errors => pentas::DumpTarget::Errors,
+            cli::DumpTarget::Metadata => pentas::DumpTarget::Metadata,
+            cli::DumpTarget::DomHtml => pentas::DumpTarget::DomHtml,
+            cli::DumpTarget::Announcements => pentas::DumpTarget::Announcements,
+            cli::DumpTarget::Stats => pentas::DumpTarget::Stats,
+            cli::DumpTarget::ComputedStyle => pentas::DumpTarget::ComputedStyle,
+            cli::DumpTarget::Lines => pentas::DumpTarget::Lines,
+        }),
+        forced_colors: args.forced_colors,
+        window_size: args.window_size,
+        devtools_port: args.devtools_port,
+        clicks: args.click,
+        print_pdf: args.print_pdf,
+        text_rendering: pentas::TextRenderingOptions {
+            antialiasing: match args.antialiasing {
+                cli::Antialiasing::System => pentas::Antialiasing::System,
+                cli::Antialiasing::None => pentas::Antialiasing::None,
+                cli::Antialiasing::Grayscale => pentas::Antialiasing::Grayscale,
+                cli::Antialiasing::Subpixel => pentas::Antialiasing::Subpixel,
+            },
+            hinting: match args.hinting {
+                cli::Hinting::System => pentas::Hinting::System,
+                cli::Hinting::None => pentas::Hinting::None,
+                cli::Hinting::Slight => pentas::Hinting::Slight,
+                cli::Hinting::Medium => pentas::Hinting::Medium,
+                cli::Hinting::Full => pentas::Hinting::Full,
+            },
+            serif_family: args.serif_font,
+            sans_serif_family: args.sans_serif_font,
+            monospace_family: args.monospace_font,
+            minimum_font_size: args.minimum_font_size,
+        },
     };
 
     if let Err(e) = Runner::new(config).run() {
-        eprintln!("Error: {}", e);
+        exit_with_error(&e);
+    }
+}
+
+/// Prints the status of each page of the gallery, and exits with 1 if any of them differs from its reference
+/// or has no reference.
+fn report_gallery(results: &[pentas::GalleryResult]) {
+    for result in results {
+        let status = match result.status {
+            pentas::GalleryStatus::Rendered => "rendered".to_string(),
+            pentas::GalleryStatus::Matched => "matched".to_string(),
+            pentas::GalleryStatus::MissingReference => "NO REFERENCE".to_string(),
+            pentas::GalleryStatus::Mismatched { different_pixels } => {
+                format!("MISMATCHED ({} pixels)", different_pixels)
+            }
+        };
+        println!(
+            "{:<12} {:<24} {}",
+            result.name,
+            status,
+            result.path.display()
+        );
+    }
+    if results.iter().any(|result| result.status.is_failure()) {
         std::process::exit(1);
     }
 }
+
+/// Prints the error with its sources, and exits with the status for its kind, which follows the
+/// conventions of `sysexits.h`.
+fn exit_with_error(error: &pentas::Error) -> ! {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        message.push_str(&format!(": {}", e));
+        source = e.source();
+    }
+    eprintln!("Error: {}", message);
+
+    let code = match error {
+        pentas::Error::Config(_) => 64,
+        pentas::Error::HtmlParse { .. } | pentas::Error::CssParse { .. } => 65,
+        pentas::Error::Network { .. } | pentas::Error::Tls { .. } => 69,
+        pentas::Error::Layout { .. } => 70,
+        pentas::Error::Io { .. } => 74,
+        pentas::Error::Timeout { .. } => 75,
+        _ => 1,
+    };
+    std::process::exit(code);
+}
@@ -0,0 +1,307 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+
+/// A command sent by a remote debugging client, which is run on the page shown in the browser.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DevToolsCommand {
+    /// Navigates to the URL as if it is entered in the URL bar. The navigation continues after the reply.
+    Navigate { url: String },
+    /// Returns the DOM tree of the current page serialized into HTML, and its title.
+    GetDocument,
+    /// Sets the title of the current page, which is `document.title` in the page. This is not in the Chrome
+    /// DevTools Protocol, where it's set by evaluating a script.
+    SetTitle { title: String },
+    /// Returns the elements of the current page which match the selector list, serialized into HTML.
+    QuerySelectorAll { selector: String },
+    /// Clicks the first element of the current page which matches the selector, as if it is clicked by the
+    /// user. This is not in the Chrome DevTools Protocol, where the clicks are dispatched as input events.
+    Click { selector: String },
+    /// Returns the visible area of the current page as a PNG image encoded in Base64.
+    CaptureScreenshot,
+    /// Returns the computed style of the first element of the current page which matches the selector. The
+    /// element is given by its node ID in the Chrome DevTools Protocol.
+    GetComputedStyle { selector: String },
+    /// Adds a history entry for the current page with the state and the URL, which is `history.pushState()`
+    /// in the page. This is not in the Chrome DevTools Protocol, where it's called by evaluating a script.
+    PushState {
+        state: Option<String>,
+        url: Option<String>,
+    },
+    /// Replaces the state and the URL of the current history entry, which is `history.replaceState()` in
+    /// the page.
+    ReplaceState {
+        state: Option<String>,
+        url: Option<String>,
+    },
+}
+
+impl DevToolsCommand {
+    /// Parses the method and the parameters of a request into a command.
+    pub fn from_method(method: &str, params: &Value) -> Result<Self> {
+        let get_param = |name: &str| {
+            params
+                .get(name)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+                .with_context(|| format!("{} requires the string parameter \"{}\"", method, name))
+        };
+        let get_optional_param =
+            |name: &str| params.get(name).and_then(Value::as_str).map(str::to_string);
+        Ok(match method {
+            "Page.navigate" => Self::Navigate {
+                url: get_param("url")?,
+            },
+            "Page.captureScreenshot" => Self::CaptureScreenshot,
+            "DOM.getDocument" => Self::GetDocument,
+            "DOM.setTitle" => Self::SetTitle {
+                title: get_param("title")?,
+            },
+            "DOM.querySelectorAll" => Self::QuerySelectorAll {
+                selector: get_param("selector")?,
+            },
+            "DOM.click" => Self::Click {
+                selector: get_param("selector")?,
+            },
+            "CSS.getComputedStyleForNode" => Self::GetComputedStyle {
+                selector: get_param("selector")?,
+            },
+            "History.pushState" => Self::PushState {
+                state: get_optional_param("state"),
+                url: get_optional_param("url"),
+            },
+            "History.replaceState" => Self::ReplaceState {
+                state: get_optional_param("state"),
+                url: get_optional_param("url"),
+            },
+            _ => bail!("Unknown method: {}", method),
+        })
+    }
+}
+
+/// A command waiting for the browser to run it, which must be replied to.
+#[derive(Debug)]
+pub struct DevToolsRequest {
+    pub command: DevToolsCommand,
+    reply_tx: Sender<Result<Value>>,
+}
+
+impl DevToolsRequest {
+    /// Sends the result of the command back to the client.
+    pub fn reply(self, result: Result<Value>) {
+        // The client may have disconnected.
+        let _ = self.reply_tx.send(result);
+    }
+}
+
+/// A server of the remote debugging protocol on a local TCP port, through which external tools drive the
+/// browser. The protocol is a small subset of the Chrome DevTools Protocol with its messages in JSON, one
+/// per line instead of in WebSocket frames, e.g.
+/// `{"id": 1, "method": "DOM.querySelectorAll", "params": {"selector": "a"}}`, to which the server replies
+/// with `{"id": 1, "result": {"nodes": [...]}}` or `{"id": 1, "error": {"message": "..."}}`.
+/// Only the connections from the same host are accepted, since the pages can be read through them. The
+/// connections which start with an HTTP request are closed, since they may be sent by a web page, e.g. with
+/// `fetch()` from another origin or through DNS rebinding, whose body would be taken as a request.
+/// https://chromedevtools.github.io/devtools-protocol/
+#[derive(Debug)]
+pub struct DevToolsServer {
+    port: u16,
+    request_rx: Receiver<DevToolsRequest>,
+}
+
+impl DevToolsServer {
+    /// Starts listening on the loopback address, where the port is chosen by the OS if `port` is 0.
+    pub fn start(port: u16) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .with_context(|| format!("Failed to listen on port {}", port))?;
+        let port = listener.local_addr()?.port();
+        let (request_tx, request_rx) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let request_tx = request_tx.clone();
+                        thread::spawn(move || {
+                            if let Err(e) = handle_connection(stream, &request_tx) {
+                                eprintln!("{:#}", e);
+                            }
+                        });
+                    }
+                    Err(e) => eprintln!("Failed to accept a DevTools connection: {}", e),
+                }
+            }
+        });
+        Ok(Self { port, request_rx })
+    }
+
+    pub fn get_port(&self) -> u16 {
+        self.port
+    }
+
+    /// Returns the next request of the clients if any, without blocking.
+    pub fn try_recv(&self) -> Option<DevToolsRequest> {
+        self.request_rx.try_recv().ok()
+    }
+}
+
+/// Reads the requests of a client line by line, and writes the replies in the order of the requests.
+fn handle_connection(stream: TcpStream, request_tx: &Sender<DevToolsRequest>) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut is_first_line = true;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if is_first_line && is_http_request_line(&line) {
+            bail!("Closed a DevTools connection which started with an HTTP request");
+        }
+        is_first_line = false;
+        let (id, command) = parse_message(&line);
+        let result = command.and_then(|command| send_command(command, request_tx));
+        let reply = match result {
+            Ok(result) => json!({ "id": id, "result": result }),
+            Err(e) => json!({ "id": id, "error": { "message": format!("{:#}", e) } }),
+        };
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(())
+}
+
+/// Returns whether the line is the request line of HTTP, e.g. `POST / HTTP/1.1`.
+/// https://datatracker.ietf.org/doc/html/rfc9112#section-3
+fn is_http_request_line(line: &str) -> bool {
+    line.split_ascii_whitespace()
+        .last()
+        .is_some_and(|version| version.starts_with("HTTP/"))
+}
+
+/// Parses a request into its ID and command. The ID is null if the request is not valid JSON.
+fn parse_message(line: &str) -> (Value, Result<DevToolsCommand>) {
+    let message = match serde_json::from_str::<Value>(line) {
+        Ok(message) => message,
+        Err(e) => return (Value::Null, Err(e).context("Invalid JSON message")),
+    };
+    let id = message.get("id").cloned().unwrap_or(Value::Null);
+    let command = message
+        .get("method")
+        .and_then(Value::as_str)
+        .context("The message has no method")
+        .and_then(|method| {
+            DevToolsCommand::from_method(method, message.get("params").unwrap_or(&json!({})))
+        });
+    (id, command)
+}
+
+/// Sends the command to the browser, and waits for its result.
+fn send_command(command: DevToolsCommand, request_tx: &Sender<DevToolsRequest>) -> Result<Value> {
+    let (reply_tx, reply_rx) = mpsc::channel();
+    request_tx
+        .send(DevToolsRequest { command, reply_tx })
+        .map_err(|_| anyhow!("The browser is closed"))?;
+    reply_rx
+        .recv()
+        .map_err(|_| anyhow!("The browser dropped the request"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serve_commands() {
+        let server = DevToolsServer::start(0).unwrap();
+        let stream = TcpStream::connect((Ipv4Addr::LOCALHOST, server.get_port())).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        let mut lines = BufReader::new(stream).lines();
+        let mut send = |message: &str| {
+            writeln!(writer, "{}", message).unwrap();
+        };
+
+        send(r#"{"id": 1, "method": "DOM.querySelectorAll", "params": {"selector": "p"}}"#);
+        // The browser runs the commands on its own thread.
+        let request = loop {
+            if let Some(request) = server.try_recv() {
+                break request;
+            }
+            thread::yield_now();
+        };
+        assert_eq!(
+            request.command,
+            DevToolsCommand::QuerySelectorAll {
+                selector: "p".to_string()
+            }
+        );
+        request.reply(Ok(json!({ "nodes": ["<p>a</p>"] })));
+        assert_eq!(
+            serde_json::from_str::<Value>(&lines.next().unwrap().unwrap()).unwrap(),
+            json!({ "id": 1, "result": { "nodes": ["<p>a</p>"] } })
+        );
+
+        // The invalid requests are replied to with errors without reaching the browser.
+        send(r#"{"id": 2, "method": "Page.navigate"}"#);
+        send(r#"{"id": 3, "method": "Runtime.evaluate", "params": {}}"#);
+        send("{");
+        let errors = (0..3)
+            .map(|_| serde_json::from_str::<Value>(&lines.next().unwrap().unwrap()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors.iter().map(|e| e["id"].clone()).collect::<Vec<_>>(),
+            [json!(2), json!(3), Value::Null]
+        );
+        assert_eq!(
+            errors[0]["error"]["message"],
+            "Page.navigate requires the string parameter \"url\""
+        );
+        assert!(server.try_recv().is_none());
+    }
+
+    #[test]
+    fn reject_http_requests() {
+        let server = DevToolsServer::start(0).unwrap();
+        let stream = TcpStream::connect((Ipv4Addr::LOCALHOST, server.get_port())).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        // A page may send this with `fetch()`, whose body is a valid message.
+        write!(
+            writer,
+            "POST / HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Type: text/plain\r\n\r\n\
+             {{\"id\": 1, \"method\": \"Page.navigate\", \"params\": {{\"url\": \"a\"}}}}\n"
+        )
+        .unwrap();
+        // The connection is closed without any reply.
+        assert_eq!(BufReader::new(stream).lines().count(), 0);
+        assert!(server.try_recv().is_none());
+
+        assert!(is_http_request_line("GET /json HTTP/1.0"));
+        assert!(!is_http_request_line(
+            r#"{"id": 1, "method": "DOM.getDocument"}"#
+        ));
+    }
+
+    #[test]
+    fn parse_history_commands() {
+        assert_eq!(
+            DevToolsCommand::from_method(
+                "History.pushState",
+                &json!({ "state": "{\"page\": 2}", "url": "?page=2" })
+            )
+            .unwrap(),
+            DevToolsCommand::PushState {
+                state: Some("{\"page\": 2}".to_string()),
+                url: Some("?page=2".to_string()),
+            }
+        );
+        // The state and the URL are optional, as in `history.replaceState(null, "")`.
+        assert_eq!(
+            DevToolsCommand::from_method("History.replaceState", &json!({})).unwrap(),
+            DevToolsCommand::ReplaceState {
+                state: None,
+                url: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::io;
+
+use crate::net::fetch::FetchError;
+use crate::renderer::parse_error::ParseErrorReport;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// The errors of the public API, classified by the kind of the failure so that the callers can handle them
+/// differently, e.g. the CLI exits with a different status for each kind. The message of each kind is
+/// short, and the details are in the chain of its sources.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The resource could not be fetched, e.g. the host was unreachable or the response was malformed.
+    #[error("Failed to fetch {url}")]
+    Network {
+        url: String,
+        #[source]
+        source: BoxError,
+    },
+    /// The resource was requested over HTTPS, which is not supported yet.
+    #[error("Failed to establish a secure connection to {url}")]
+    Tls {
+        url: String,
+        #[source]
+        source: BoxError,
+    },
+    /// The server didn't respond in time.
+    #[error("Timed out while fetching {url}")]
+    Timeout {
+        url: String,
+        #[source]
+        source: BoxError,
+    },
+    /// The HTML could not be parsed. `diagnostics` are the parse errors reported before the failure.
+    #[error("Failed to parse the HTML")]
+    HtmlParse {
+        diagnostics: Vec<ParseErrorReport>,
+        #[source]
+        source: BoxError,
+    },
+    /// The CSS, including the selectors given to the API, could not be parsed. `diagnostics` are the parse
+    /// errors reported before the failure.
+    #[error("Failed to parse the CSS")]
+    CssParse {
+        diagnostics: Vec<ParseErrorReport>,
+        #[source]
+        source: BoxError,
+    },
+    /// The document could not be styled, laid out, painted or printed.
+    #[error("Failed to render the document")]
+    Layout {
+        #[source]
+        source: BoxError,
+    },
+    /// A file or a socket could not be read or written.
+    #[error("I/O error")]
+    Io {
+        #[source]
+        source: BoxError,
+    },
+    /// The configuration is invalid, e.g. the options given on the command line contradict each other.
+    #[error("{0}")]
+    Config(String),
+}
+
+impl Error {
+    pub(crate) fn html_parse(diagnostics: Vec<ParseErrorReport>, source: anyhow::Error) -> Self {
+        Self::HtmlParse {
+            diagnostics,
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn css_parse(diagnostics: Vec<ParseErrorReport>, source: anyhow::Error) -> Self {
+        Self::CssParse {
+            diagnostics,
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn layout(source: anyhow::Error) -> Self {
+        Self::Layout {
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn io(source: anyhow::Error) -> Self {
+        Self::Io {
+            source: source.into(),
+        }
+    }
+
+    /// Classifies the internal error by the failures in its chain, i.e. the ones of fetching resources and
+    /// the I/O errors, or with `otherwise` if there are none of them.
+    pub(crate) fn classify(
+        error: anyhow::Error,
+        otherwise: impl FnOnce(anyhow::Error) -> Self,
+    ) -> Self {
+        let is_timeout = error.chain().any(|cause| {
+            cause.downcast_ref::<io::Error>().is_some_and(|e| {
+                matches!(
+                    e.kind(),
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                )
+            })
+        });
+        match error.downcast_ref::<FetchError>() {
+            Some(FetchError::Tls(url)) => Self::Tls {
+                url: url.clone(),
+                source: error.into(),
+            },
+            Some(FetchError::Network(url)) if is_timeout => Self::Timeout {
+                url: url.clone(),
+                source: error.into(),
+            },
+            Some(FetchError::Network(url)) => Self::Network {
+                url: url.clone(),
+                source: error.into(),
+            },
+            Some(FetchError::LocalFile(url)) => Self::Network {
+                url: url.clone(),
+                source: error.into(),
+            },
+            Some(FetchError::Cancelled(_)) => otherwise(error),
+            None if error.chain().any(|cause| cause.is::<io::Error>()) => Self::io(error),
+            None => otherwise(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context};
+
+    use super::*;
+
+    #[test]
+    fn classify_errors() {
+        let timeout = Err::<(), _>(io::Error::from(io::ErrorKind::TimedOut))
+            .context("Failed to read the response")
+            .context(FetchError::Network("http://example.com/".to_string()))
+            .context("Failed to load the style sheet")
+            .unwrap_err();
+        let error = Error::classify(timeout, Error::layout);
+        assert!(matches!(&error, Error::Timeout { url, .. } if url == "http://example.com/"));
+        // The whole chain is kept as the sources.
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(source.to_string(), "Failed to load the style sheet");
+        assert_eq!(
+            source.source().unwrap().to_string(),
+            "Failed to fetch http://example.com/"
+        );
+
+        let refused = anyhow!(io::Error::from(io::ErrorKind::ConnectionRefused))
+            .context(FetchError::Network("http://localhost/".to_string()));
+        assert!(matches!(
+            Error::classify(refused, Error::layout),
+            Error::Network { .. }
+        ));
+        let tls = anyhow!(FetchError::Tls("https://example.com/".to_string()));
+        assert!(matches!(
+            Error::classify(tls, Error::layout),
+            Error::Tls { .. }
+        ));
+        let not_found = Err::<(), _>(io::Error::from(io::ErrorKind::NotFound))
+            .context("Failed to read a.html")
+            .unwrap_err();
+        assert!(matches!(
+            Error::classify(not_found, Error::layout),
+            Error::Io { .. }
+        ));
+        assert!(matches!(
+            Error::classify(anyhow!("No root element"), Error::layout),
+            Error::Layout { .. }
+        ));
+    }
+}
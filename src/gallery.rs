@@ -0,0 +1,209 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use gtk4::prelude::WidgetExt;
+use gtk4::DrawingArea;
+
+use crate::error::{Error, Result};
+use crate::renderer::{
+    render_page, set_text_rendering_options, Antialiasing, Hinting, MediaEnvironment,
+    TextRenderingOptions,
+};
+use crate::ui::{load_png, paint_to_pixels, save_png, set_font_options};
+
+/// The size of the viewport in which the pages are laid out, which is also the size of the screenshots.
+pub const GALLERY_VIEWPORT: (i32, i32) = (800, 600);
+
+/// The pixels whose channels differ from the reference by at most this are taken as the same, which absorbs
+/// the small differences of the glyph rasterization between the versions of the font libraries.
+const CHANNEL_TOLERANCE: u8 = 24;
+
+/// The ratio of the different pixels up to which a screenshot still matches its reference.
+const MAX_DIFFERENT_RATIO: f64 = 0.001;
+
+/// A built-in test page, which exercises a part of the rendering in a way that is easy to check by eye.
+#[derive(Debug, Clone, Copy)]
+pub struct GalleryPage {
+    pub name: &'static str,
+    pub html: &'static str,
+}
+
+/// The built-in test pages, which are rendered with `pentas gallery`.
+pub const GALLERY_PAGES: [GalleryPage; 4] = [
+    GalleryPage {
+        name: "layout",
+        html: include_str!("../resources/gallery/layout.html"),
+    },
+    GalleryPage {
+        name: "typography",
+        html: include_str!("../resources/gallery/typography.html"),
+    },
+    GalleryPage {
+        name: "forms",
+        html: include_str!("../resources/gallery/forms.html"),
+    },
+    GalleryPage {
+        name: "floats",
+        html: include_str!("../resources/gallery/floats.html"),
+    },
+];
+
+/// How the screenshot of a page compares with its reference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GalleryStatus {
+    /// No reference directory is given.
+    Rendered,
+    Matched,
+    /// The reference directory has no screenshot of the page, e.g. the page is new, which fails the comparison
+    /// so that the references are not left out silently.
+    MissingReference,
+    /// The screenshot differs from the reference in more pixels than allowed, or in its size.
+    Mismatched {
+        different_pixels: usize,
+    },
+}
+
+impl GalleryStatus {
+    /// Returns whether the screenshot fails the comparison with the reference directory.
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Self::MissingReference | Self::Mismatched { .. })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GalleryResult {
+    pub name: &'static str,
+    /// The path of the screenshot.
+    pub path: PathBuf,
+    pub status: GalleryStatus,
+}
+
+/// Returns the text rendering options of the gallery, which are all set explicitly so that the screenshots
+/// don't depend on the font settings of the system. The DejaVu fonts must be installed.
+pub fn get_gallery_text_rendering_options() -> TextRenderingOptions {
+    TextRenderingOptions {
+        antialiasing: Antialiasing::Grayscale,
+        hinting: Hinting::None,
+        serif_family: Some("DejaVu Serif".to_string()),
+        sans_serif_family: Some("DejaVu Sans".to_string()),
+        monospace_family: Some("DejaVu Sans Mono".to_string()),
+        minimum_font_size: 0.0,
+    }
+}
+
+/// Renders the built-in test pages into `<name>.png` in `output_dir`. If `reference_dir` is given, each
+/// screenshot is compared with the one of the same name in it, and the differences are written to
+/// `<name>.diff.png`, where the different pixels are red.
+pub fn render_gallery(
+    output_dir: &Path,
+    reference_dir: Option<&Path>,
+) -> Result<Vec<GalleryResult>> {
+    gtk4::init()
+        .context("Failed to initialize GTK")
+        .map_err(Error::layout)?;
+    let text_rendering = get_gallery_text_rendering_options();
+    set_text_rendering_options(text_rendering.clone());
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("Failed to create {}", output_dir.display()))
+        .map_err(Error::io)?;
+
+    let (width, height) = GALLERY_VIEWPORT;
+    let media = MediaEnvironment {
+        viewport_width: width,
+        viewport_height: height,
+        ..Default::default()
+    };
+    let mut results = Vec::new();
+    for page in GALLERY_PAGES {
+        let draw_ctx = DrawingArea::new().pango_context();
+        set_font_options(&draw_ctx, &text_rendering);
+        let pixels = render_page(page.html, "about:blank", &media, &draw_ctx)
+            .and_then(|objects| paint_to_pixels(&objects, width, height))
+            .with_context(|| format!("Failed to render the gallery page {}", page.name))
+            .map_err(|e| Error::classify(e, Error::layout))?;
+        let path = output_dir.join(format!("{}.png", page.name));
+        save_png(&pixels, width, height, &path).map_err(Error::io)?;
+
+        let status = match reference_dir.map(|dir| dir.join(format!("{}.png", page.name))) {
+            None => GalleryStatus::Rendered,
+            Some(reference) if !reference.exists() => GalleryStatus::MissingReference,
+            Some(reference) => {
+                let (expected, expected_size) = load_png(&reference).map_err(Error::io)?;
+                if expected_size != (width, height) {
+                    GalleryStatus::Mismatched {
+                        different_pixels: (width * height) as usize,
+                    }
+                } else {
+                    let (different_pixels, diff) = compare_pixels(&expected, &pixels);
+                    if different_pixels as f64 > (width * height) as f64 * MAX_DIFFERENT_RATIO {
+                        let diff_path = output_dir.join(format!("{}.diff.png", page.name));
+                        save_png(&diff, width, height, &diff_path).map_err(Error::io)?;
+                        GalleryStatus::Mismatched { different_pixels }
+                    } else {
+                        GalleryStatus::Matched
+                    }
+                }
+            }
+        };
+        results.push(GalleryResult {
+            name: page.name,
+            path,
+            status,
+        });
+    }
+    Ok(results)
+}
+
+/// Compares two images of the same size in 4-byte BGRA pixels, and returns the number of the pixels which
+/// differ beyond the tolerance, and an image in which they are red and the others are faded.
+fn compare_pixels(expected: &[u8], actual: &[u8]) -> (usize, Vec<u8>) {
+    let mut different_pixels = 0;
+    let mut diff = Vec::with_capacity(actual.len());
+    for (e, a) in expected.chunks_exact(4).zip(actual.chunks_exact(4)) {
+        if e.iter()
+            .zip(a)
+            .any(|(e, a)| e.abs_diff(*a) > CHANNEL_TOLERANCE)
+        {
+            different_pixels += 1;
+            diff.extend([0, 0, 255, 255]);
+        } else {
+            diff.extend(a[..3].iter().map(|c| 255 - (255 - c) / 4));
+            diff.push(255);
+        }
+    }
+    (different_pixels, diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::get_computed_styles;
+
+    #[test]
+    fn gallery_pages_and_pixel_diff() {
+        // The pages are styled without errors, and none of them is empty.
+        for page in GALLERY_PAGES {
+            let styles = get_computed_styles(page.html, &MediaEnvironment::default()).unwrap();
+            assert!(styles.len() > 5, "{}", page.name);
+        }
+
+        let white = [255, 255, 255, 255];
+        let expected = [white, white, [0, 0, 0, 255]].concat();
+        let actual = [white, [250, 250, 250, 255], [0, 0, 200, 255]].concat();
+        let (different_pixels, diff) = compare_pixels(&expected, &actual);
+        // Only the last pixel differs beyond the tolerance.
+        assert_eq!(different_pixels, 1);
+        assert_eq!(&diff[..4], white);
+        assert_eq!(&diff[8..], [0, 0, 255, 255]);
+
+        // The pages without references fail once the references are given.
+        assert!(!GalleryStatus::Rendered.is_failure());
+        assert!(!GalleryStatus::Matched.is_failure());
+        assert!(GalleryStatus::MissingReference.is_failure());
+        assert!(GalleryStatus::Mismatched {
+            different_pixels: 1
+        }
+        .is_failure());
+    }
+}
@@ -1,9 +1,33 @@
+use anyhow::{ensure, Result};
+
+use crate::net::url::resolve_url;
 use crate::renderer::RenderObjects;
+use crate::settings::get_origin;
 
 #[derive(Debug, Clone)]
 pub struct HistoryEntry {
     pub query: String,
     pub objects: RenderObjects,
+    /// The serialized state associated with the entry by `pushState()` or `replaceState()`.
+    /// https://html.spec.whatwg.org/multipage/browsing-the-web.html#she-classic-history-api-state
+    pub state: Option<String>,
+    /// The document of the entry. The entries added by `pushState()` share the document of the entry from
+    /// which they were added, and traversing between them doesn't load the page again.
+    pub document_id: u64,
+}
+
+impl HistoryEntry {
+    /// Returns the title of the entry shown in the window, which is the title of its document, the title with
+    /// which it's shared, or its URL in this order.
+    pub fn get_title(&self) -> String {
+        let metadata = &self.objects.metadata;
+        metadata
+            .title
+            .clone()
+            .filter(|title| !title.is_empty())
+            .or_else(|| metadata.get_share_preview().title)
+            .unwrap_or_else(|| self.query.clone())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -12,6 +36,7 @@ pub struct History {
     back_stack: Vec<HistoryEntry>,
     forward_stack: Vec<HistoryEntry>,
     unreachable_stack: Vec<HistoryEntry>,
+    next_document_id: u64,
 }
 
 impl Default for History {
@@ -27,6 +52,7 @@ impl History {
             back_stack: Vec::new(),
             forward_stack: Vec::new(),
             unreachable_stack: Vec::new(),
+            next_document_id: 0,
         }
     }
 
@@ -41,15 +67,81 @@ impl History {
     }
 
     pub fn add(&mut self, query: &str, objects: &RenderObjects) {
-        let query = query.to_owned();
-        let objects = objects.to_owned();
+        let document_id = self.next_document_id;
+        self.next_document_id += 1;
+        self.push(HistoryEntry {
+            query: query.to_owned(),
+            objects: objects.to_owned(),
+            state: None,
+            document_id,
+        });
+    }
+
+    /// Adds an entry for the same document with `state`, whose URL is `url` resolved against the current
+    /// one, or the current one if it is `None`. The URL must have the same origin as the current one.
+    /// https://html.spec.whatwg.org/multipage/nav-history-apis.html#dom-history-pushstate
+    pub fn push_state(&mut self, state: Option<&str>, url: Option<&str>) -> Result<()> {
+        let entry = self.create_state_entry(state, url)?;
+        self.push(entry);
+        Ok(())
+    }
+
+    /// Replaces the state and the URL of the current entry in the same way as `push_state()`.
+    /// https://html.spec.whatwg.org/multipage/nav-history-apis.html#dom-history-replacestate
+    pub fn replace_state(&mut self, state: Option<&str>, url: Option<&str>) -> Result<()> {
+        let entry = self.create_state_entry(state, url)?;
+        self.current = Some(entry);
+        Ok(())
+    }
+
+    /// Replaces the objects of the current entry with the ones of its document rendered again, e.g. after the
+    /// window is resized, and returns whether the title of the entry changes.
+    pub fn set_current_objects(&mut self, objects: &RenderObjects) -> bool {
+        let Some(current) = &mut self.current else {
+            return false;
+        };
+        let old_title = current.get_title();
+        current.objects = objects.to_owned();
+        current.get_title() != old_title
+    }
+
+    /// https://html.spec.whatwg.org/multipage/nav-history-apis.html#shared-history-push/replace-state-steps
+    fn create_state_entry(&self, state: Option<&str>, url: Option<&str>) -> Result<HistoryEntry> {
+        let Some(current) = &self.current else {
+            anyhow::bail!("There is no document whose history can be changed");
+        };
+        let query = match url {
+            Some(url) => {
+                let query = resolve_url(&current.query, url);
+                // Documents can't impersonate other origins, nor change the URLs of the opaque ones.
+                ensure!(
+                    query == current.query
+                        || get_origin(&current.query)
+                            .is_some_and(|origin| get_origin(&query) == Some(origin)),
+                    "The URL {} can't be used in the history of {}",
+                    query,
+                    current.query
+                );
+                query
+            }
+            None => current.query.clone(),
+        };
+        Ok(HistoryEntry {
+            query,
+            objects: current.objects.clone(),
+            state: state.map(|s| s.to_owned()),
+            document_id: current.document_id,
+        })
+    }
+
+    fn push(&mut self, entry: HistoryEntry) {
         while let Some(e) = self.forward_stack.pop() {
             self.unreachable_stack.push(e);
         }
         if let Some(current) = self.current.take() {
             self.back_stack.push(current);
         }
-        self.current = Some(HistoryEntry { query, objects });
+        self.current = Some(entry);
     }
 
     pub fn forward(&mut self) -> Option<&HistoryEntry> {
@@ -81,6 +173,16 @@ impl History {
     pub fn is_forwardable(&self) -> bool {
         !self.forward_stack.is_empty()
     }
+
+    /// Returns the state with which the `popstate` event is fired after traversing from an entry of the
+    /// document `from_document_id`, or `None` if the traversal loaded another document.
+    /// https://html.spec.whatwg.org/multipage/browsing-the-web.html#updating-the-document
+    pub fn get_pop_state(&self, from_document_id: u64) -> Option<Option<&str>> {
+        self.current
+            .as_ref()
+            .filter(|current| current.document_id == from_document_id)
+            .map(|current| current.state.as_deref())
+    }
 }
 
 #[cfg(test)]
@@ -95,6 +197,24 @@ mod tests {
         assert!(!history.is_forwardable());
     }
 
+    #[test]
+    fn update_title() {
+        let mut history = History::with_initial_page("p1", &RenderObjects::default());
+        assert_eq!(history.get_current().unwrap().get_title(), "p1");
+
+        let mut objects = RenderObjects::default();
+        objects.metadata.title = Some("Title".to_string());
+        assert!(history.set_current_objects(&objects));
+        assert!(!history.set_current_objects(&objects));
+        assert_eq!(history.get_current().unwrap().get_title(), "Title");
+
+        // An empty title falls back to the one with which the page is shared.
+        objects.metadata.title = Some(String::new());
+        objects.metadata.open_graph = vec![("og:title".to_string(), "Shared".to_string())];
+        assert!(history.set_current_objects(&objects));
+        assert_eq!(history.get_current().unwrap().get_title(), "Shared");
+    }
+
     #[test]
     fn add_page() {
         let mut history = History::with_initial_page("p1", &RenderObjects::default());
@@ -159,4 +279,54 @@ mod tests {
         assert!(!history.is_forwardable());
         assert!(history.is_rewindable());
     }
+
+    #[test]
+    fn push_and_replace_states() {
+        let mut history =
+            History::with_initial_page("http://example.com/app", &RenderObjects::default());
+        let document_id = history.get_current().unwrap().document_id;
+        history
+            .push_state(Some("{\"page\":1}"), Some("/app/1"))
+            .unwrap();
+        assert_eq!(
+            history.get_current().unwrap().query,
+            "http://example.com/app/1"
+        );
+        history
+            .replace_state(Some("{\"page\":2}"), Some("2"))
+            .unwrap();
+        assert_eq!(
+            history.get_current().unwrap().query,
+            "http://example.com/app/2"
+        );
+        assert!(history
+            .push_state(None, Some("http://example.org/"))
+            .is_err());
+        history.push_state(None, None).unwrap();
+        assert_eq!(
+            history.get_current().unwrap().query,
+            "http://example.com/app/2"
+        );
+
+        // Traversing within the document fires popstate events with the states.
+        history.rewind();
+        assert_eq!(
+            history.get_pop_state(document_id),
+            Some(Some("{\"page\":2}"))
+        );
+        history.rewind();
+        assert_eq!(history.get_pop_state(document_id), Some(None));
+        assert!(!history.is_rewindable());
+
+        // Traversing to another document doesn't.
+        history.add("http://example.org/", &RenderObjects::default());
+        history.rewind();
+        let other_document_id = history.get_current().unwrap().document_id + 1;
+        history.forward();
+        assert_eq!(
+            history.get_current().unwrap().document_id,
+            other_document_id
+        );
+        assert_eq!(history.get_pop_state(document_id), None);
+    }
 }
@@ -1,8 +1,29 @@
 mod app;
+mod devtools;
+mod error;
+mod gallery;
 mod history;
 mod net;
 mod renderer;
+mod settings;
 mod ui;
 mod utils;
 
-pub use app::{Config, Runner, VerbosityLevel};
+pub use app::{Config, DumpTarget, Runner, VerbosityLevel};
+pub use error::{Error, Result};
+pub use gallery::{render_gallery, GalleryResult, GalleryStatus};
+pub use net::cache::clear_default_cache;
+#[doc(hidden)]
+pub use renderer::bench;
+pub use renderer::parse_error::ParseErrorReport;
+pub use renderer::source::{SourcePosition, SourceSpan};
+pub use renderer::{
+    get_css_parse_errors, get_html_parse_errors, get_page_metadata, get_serialized_dom,
+    query_selector_all,
+};
+pub use renderer::{AccessibilityBackend, Announcement, Politeness};
+pub use renderer::{
+    AlternateLink, IconLink, IconSize, PageMetadata, Refresh, ResourceHint, ResourceHintKind,
+    SharePreview, StylesheetLink,
+};
+pub use renderer::{Antialiasing, Hinting, TextRenderingOptions};
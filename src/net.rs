@@ -1 +1,5 @@
+pub mod cache;
+pub mod fetch;
 pub mod http;
+pub mod scheduler;
+pub mod url;
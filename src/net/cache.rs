@@ -0,0 +1,521 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once, OnceLock, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, fs, io};
+
+use anyhow::{Context, Result};
+
+use crate::renderer::escape_string;
+
+/// The URL of the page which lists the cached resources.
+pub const CACHE_PAGE_URL: &str = "about:cache";
+/// The disk space which the cached resources of all kinds can take up in total.
+pub const DEFAULT_CACHE_BUDGET: u64 = 64 * 1024 * 1024;
+
+const INDEX_FILE_NAME: &str = "index";
+const ENTRIES_DIR_NAME: &str = "entries";
+
+/// The kind of a cached resource, which share the same disk budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CacheKind {
+    /// The HTTP responses of the documents and the style sheets.
+    Http,
+    /// todo: Cache the images once they are fetched over HTTP.
+    #[allow(dead_code)]
+    Image,
+    /// todo: Cache the web fonts once `@font-face` is supported.
+    #[allow(dead_code)]
+    Font,
+}
+
+impl CacheKind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "http" => Some(Self::Http),
+            "image" => Some(Self::Image),
+            "font" => Some(Self::Font),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CacheKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Http => write!(f, "http"),
+            Self::Image => write!(f, "image"),
+            Self::Font => write!(f, "font"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CacheEntry {
+    /// The name of the file in the entries directory which holds the resource.
+    file: String,
+    size: u64,
+    /// The logical time of the last use, by which the least recently used entries are evicted first.
+    last_used: u64,
+    /// The Unix time in seconds after which the resource is stale and not used.
+    expires: Option<u64>,
+}
+
+/// A cache of resources on disk, whose entries are evicted in least recently used order when they exceed the
+/// budget.
+///
+/// The resources are stored in files in the `entries` directory, which are listed in the `index` file with
+/// a line per entry, e.g. `http 1024 1700000000000 1700000600 6b0f3e2a19c4d8e7 http://example.com/a.css`,
+/// where the fields are the kind, the size, the time of the last use, the expiration time (`-` if none),
+/// the file name and the URL. The times of use are written with the next change or compaction, or when the
+/// cache is closed.
+#[derive(Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+    budget: u64,
+    entries: BTreeMap<(CacheKind, String), CacheEntry>,
+    /// The logical clock, which is the current time in milliseconds unless entries are used more than once
+    /// in a millisecond.
+    clock: u64,
+    /// Whether the cache is closed, after which the resources are no longer stored.
+    is_closed: bool,
+}
+
+impl DiskCache {
+    /// Opens the cache in `dir`, which is created when a resource is first stored. A broken index is
+    /// discarded, since the resources can be fetched again.
+    pub fn open(dir: &Path, budget: u64) -> Result<Self> {
+        let index_path = dir.join(INDEX_FILE_NAME);
+        let entries = match fs::read_to_string(&index_path) {
+            Ok(text) => parse_index(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", index_path.display()))
+            }
+        };
+        let clock = entries.values().map(|e| e.last_used).max().unwrap_or(0);
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            budget,
+            entries,
+            clock,
+            is_closed: false,
+        })
+    }
+
+    /// Returns the default location of the cache, which is `$XDG_CACHE_HOME/pentas` or
+    /// `$HOME/.cache/pentas`.
+    pub fn get_default_dir() -> Option<PathBuf> {
+        let cache_dir = env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(cache_dir.join("pentas"))
+    }
+
+    /// Returns the total size of the cached resources in bytes.
+    pub fn get_total_size(&self) -> u64 {
+        self.entries.values().map(|e| e.size).sum()
+    }
+
+    /// Returns the resource if it is cached and fresh, and marks it as used.
+    pub fn get(&mut self, kind: CacheKind, url: &str) -> Option<Vec<u8>> {
+        let key = (kind, url.to_string());
+        let entry = self.entries.get(&key)?;
+        if entry
+            .expires
+            .is_some_and(|expires| expires <= get_unix_time() / 1000)
+        {
+            return None;
+        }
+        match fs::read(self.get_entries_dir().join(&entry.file)) {
+            Ok(body) if body.len() as u64 == entry.size => {
+                let now = self.tick();
+                if let Some(entry) = self.entries.get_mut(&key) {
+                    entry.last_used = now;
+                }
+                Some(body)
+            }
+            // The file was removed or changed outside the browser.
+            _ => {
+                self.entries.remove(&key);
+                None
+            }
+        }
+    }
+
+    /// Stores the resource, which is stale after `expires` (a Unix time in seconds) if it is given, and
+    /// evicts the least recently used resources if the cache exceeds the budget. The resources larger than
+    /// the budget are not stored.
+    pub fn put(
+        &mut self,
+        kind: CacheKind,
+        url: &str,
+        body: &[u8],
+        expires: Option<u64>,
+    ) -> Result<()> {
+        if self.is_closed || body.len() as u64 > self.budget {
+            return Ok(());
+        }
+        let entries_dir = self.get_entries_dir();
+        fs::create_dir_all(&entries_dir)
+            .with_context(|| format!("Failed to create {}", entries_dir.display()))?;
+        let file = format!("{:016x}", hash_key(kind, url));
+        let path = entries_dir.join(&file);
+        fs::write(&path, body).with_context(|| format!("Failed to write {}", path.display()))?;
+        let last_used = self.tick();
+        self.entries.insert(
+            (kind, url.to_string()),
+            CacheEntry {
+                file,
+                size: body.len() as u64,
+                last_used,
+                expires,
+            },
+        );
+        self.evict(self.budget);
+        self.save_index()
+    }
+
+    /// Removes all the cached resources.
+    pub fn clear(&mut self) -> Result<()> {
+        self.entries.clear();
+        let entries_dir = self.get_entries_dir();
+        match fs::remove_dir_all(&entries_dir) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                return Err(e)
+                    .with_context(|| format!("Failed to remove {}", entries_dir.display()))
+            }
+            _ => {}
+        }
+        self.save_index()
+    }
+
+    /// Drops the stale entries and the entries whose files are lost, removes the files which no entry
+    /// refers to (e.g. those left by a crash while storing a resource), evicts the entries over the budget,
+    /// and writes the times of use to the index. Returns the number of the removed files.
+    pub fn compact(&mut self) -> Result<usize> {
+        let now = get_unix_time() / 1000;
+        let entries_dir = self.get_entries_dir();
+        let mut removed = 0;
+        self.entries.retain(|_, entry| {
+            let is_stale = entry.expires.is_some_and(|expires| expires <= now);
+            let is_intact = fs::metadata(entries_dir.join(&entry.file))
+                .is_ok_and(|metadata| metadata.len() == entry.size);
+            if is_stale && is_intact {
+                let _ = fs::remove_file(entries_dir.join(&entry.file));
+                removed += 1;
+            }
+            !is_stale && is_intact
+        });
+        removed += self.evict(self.budget);
+
+        let files = self
+            .entries
+            .values()
+            .map(|entry| entry.file.as_str())
+            .collect::<HashSet<_>>();
+        if let Ok(dir) = fs::read_dir(&entries_dir) {
+            for file in dir.flatten() {
+                if !files.contains(file.file_name().to_string_lossy().as_ref())
+                    && fs::remove_file(file.path()).is_ok()
+                {
+                    removed += 1;
+                }
+            }
+        }
+        self.save_index()?;
+        Ok(removed)
+    }
+
+    /// Returns the HTML of the cache page, which lists the cached resources from the most recently used
+    /// one.
+    pub fn to_html(&self) -> String {
+        let mut html = String::from("<html><head><title>Cache</title></head><body><h1>Cache</h1>");
+        let _ = write!(
+            html,
+            "<p>{} of {} used in {}.</p>",
+            format_size(self.get_total_size()),
+            format_size(self.budget),
+            escape_string(&self.dir.display().to_string(), false)
+        );
+        if self.entries.is_empty() {
+            html.push_str("<p>No resource is cached.</p>");
+        } else {
+            html.push_str("<table><tr><th>Kind</th><th>URL</th><th>Size</th></tr>");
+            let mut entries = self.entries.iter().collect::<Vec<_>>();
+            entries.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.last_used));
+            for ((kind, url), entry) in entries {
+                let _ = write!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    kind,
+                    escape_string(url, false),
+                    format_size(entry.size)
+                );
+            }
+            html.push_str("</table>");
+        }
+        let _ = write!(
+            html,
+            "<p>Open {}?clear to clear the cache.</p></body></html>",
+            CACHE_PAGE_URL
+        );
+        html
+    }
+
+    /// Writes the times of use to the index and stops storing the resources, so that the index is not
+    /// changed by the fetches which are still running while the browser exits.
+    pub fn close(&mut self) -> Result<()> {
+        self.is_closed = true;
+        self.save_index()
+    }
+
+    /// Evicts the least recently used entries until their total size is within `budget`, and returns the
+    /// number of the evicted entries.
+    fn evict(&mut self, budget: u64) -> usize {
+        let mut total_size = self.get_total_size();
+        let mut evicted = 0;
+        while total_size > budget {
+            let Some(key) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                let _ = fs::remove_file(self.get_entries_dir().join(&entry.file));
+                total_size -= entry.size;
+                evicted += 1;
+            }
+        }
+        evicted
+    }
+
+    /// Writes the index to a temporary file first, so that the index is not broken by a crash while writing it.
+    fn save_index(&self) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let mut text = String::new();
+        for ((kind, url), entry) in &self.entries {
+            let _ = writeln!(
+                text,
+                "{} {} {} {} {} {}",
+                kind,
+                entry.size,
+                entry.last_used,
+                entry
+                    .expires
+                    .map_or_else(|| "-".to_string(), |expires| expires.to_string()),
+                entry.file,
+                url
+            );
+        }
+        let path = self.dir.join(INDEX_FILE_NAME);
+        let temp_path = self.dir.join(format!("{}.tmp", INDEX_FILE_NAME));
+        fs::write(&temp_path, text)
+            .and_then(|_| fs::rename(&temp_path, &path))
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn get_entries_dir(&self) -> PathBuf {
+        self.dir.join(ENTRIES_DIR_NAME)
+    }
+
+    /// Advances the logical clock, which never goes backwards even if the system time does.
+    fn tick(&mut self) -> u64 {
+        self.clock = get_unix_time().max(self.clock + 1);
+        self.clock
+    }
+}
+
+static SHARED_CACHE: OnceLock<Option<Mutex<DiskCache>>> = OnceLock::new();
+static COMPACTION: Once = Once::new();
+/// The thread compacting the shared cache, which is joined when the cache is closed.
+static COMPACTION_THREAD: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+/// Returns the cache at the default location shared by the whole browser, which is opened and compacted in
+/// the background on the first call. This is `None` if the cache can't be opened or is already closed.
+pub fn get_shared_cache() -> Option<&'static Mutex<DiskCache>> {
+    let cache = SHARED_CACHE
+        .get_or_init(|| {
+            let dir = DiskCache::get_default_dir()?;
+            match DiskCache::open(&dir, DEFAULT_CACHE_BUDGET) {
+                Ok(cache) => Some(Mutex::new(cache)),
+                Err(e) => {
+                    eprintln!("{:#}", e);
+                    None
+                }
+            }
+        })
+        .as_ref()?;
+    COMPACTION.call_once(|| {
+        let thread = thread::spawn(|| {
+            if let Err(e) = lock(cache).compact() {
+                eprintln!("Failed to compact the cache: {:#}", e);
+            }
+        });
+        *COMPACTION_THREAD
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(thread);
+    });
+    Some(cache)
+}
+
+/// Waits for the compaction of the shared cache, and closes the cache if it was opened. The cache is
+/// disabled for the rest of the process even if it wasn't opened.
+pub fn close_shared_cache() -> Result<()> {
+    // The compaction is not started after this.
+    COMPACTION.call_once(|| {});
+    let thread = COMPACTION_THREAD
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    if let Some(thread) = thread {
+        // The panic has already been reported, and the cache is consistent between its operations.
+        let _ = thread.join();
+    }
+    match SHARED_CACHE.get_or_init(|| None) {
+        Some(cache) => lock(cache).close(),
+        None => Ok(()),
+    }
+}
+
+/// Removes all the resources in the cache at the default location, and returns the location.
+pub fn clear_default_cache() -> crate::Result<PathBuf> {
+    let dir = DiskCache::get_default_dir()
+        .ok_or_else(|| crate::Error::Config("The location of the cache is unknown".to_string()))?;
+    DiskCache::open(&dir, DEFAULT_CACHE_BUDGET)
+        .and_then(|mut cache| cache.clear())
+        .map_err(crate::Error::io)?;
+    Ok(dir)
+}
+
+/// Locks the cache, ignoring the panics of the other threads while they held the lock since the cache is
+/// consistent between its operations.
+pub fn lock(cache: &Mutex<DiskCache>) -> std::sync::MutexGuard<'_, DiskCache> {
+    cache.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn parse_index(text: &str) -> BTreeMap<(CacheKind, String), CacheEntry> {
+    let mut entries = BTreeMap::new();
+    for line in text.lines() {
+        let mut fields = line.splitn(6, ' ');
+        let mut next = || fields.next();
+        let (Some(kind), Some(size), Some(last_used), Some(expires), Some(file), Some(url)) =
+            (next(), next(), next(), next(), next(), next())
+        else {
+            continue;
+        };
+        let (Some(kind), Ok(size), Ok(last_used)) =
+            (CacheKind::from_name(kind), size.parse(), last_used.parse())
+        else {
+            continue;
+        };
+        let expires = match expires {
+            "-" => None,
+            expires => match expires.parse() {
+                Ok(expires) => Some(expires),
+                Err(_) => continue,
+            },
+        };
+        // The file names are generated from the keys, so other names are not trusted.
+        if file.is_empty() || !file.bytes().all(|c| c.is_ascii_hexdigit()) {
+            continue;
+        }
+        entries.insert(
+            (kind, url.to_string()),
+            CacheEntry {
+                file: file.to_string(),
+                size,
+                last_used,
+                expires,
+            },
+        );
+    }
+    entries
+}
+
+/// Returns the 64-bit FNV-1a hash of the key, which is stable across builds unlike the `Hash` trait.
+/// http://www.isthe.com/chongo/tech/comp/fnv/
+fn hash_key(kind: CacheKind, url: &str) -> u64 {
+    format!("{} {}", kind, url)
+        .bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// Returns the current Unix time in milliseconds.
+fn get_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+fn format_size(size: u64) -> String {
+    match size {
+        0..1024 => format!("{} B", size),
+        1024..1048576 => format!("{:.1} KiB", size as f64 / 1024.0),
+        _ => format!("{:.1} MiB", size as f64 / 1048576.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evict_and_compact() {
+        let dir = env::temp_dir().join(format!("pentas-cache-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut cache = DiskCache::open(&dir, 10).unwrap();
+        cache
+            .put(CacheKind::Http, "http://a/", b"aaaa", None)
+            .unwrap();
+        cache
+            .put(CacheKind::Image, "http://b/", b"bbbb", None)
+            .unwrap();
+        // Using the first resource makes the second one the least recently used.
+        assert_eq!(cache.get(CacheKind::Http, "http://a/").unwrap(), b"aaaa");
+        cache
+            .put(CacheKind::Font, "http://c/", b"cccc", None)
+            .unwrap();
+        assert!(cache.get(CacheKind::Image, "http://b/").is_none());
+        assert_eq!(cache.get_total_size(), 8);
+        // Too large resources and stale ones are not used.
+        cache
+            .put(CacheKind::Http, "http://d/", &[0; 11], None)
+            .unwrap();
+        assert!(cache.get(CacheKind::Http, "http://d/").is_none());
+        cache
+            .put(CacheKind::Http, "http://a/", b"a", Some(1))
+            .unwrap();
+        assert!(cache.get(CacheKind::Http, "http://a/").is_none());
+
+        // The index survives reopening, and the compaction removes the stale entries and the stray files.
+        fs::write(dir.join(ENTRIES_DIR_NAME).join("stray"), b"x").unwrap();
+        let mut cache = DiskCache::open(&dir, 10).unwrap();
+        assert_eq!(cache.get_total_size(), 5);
+        assert_eq!(cache.compact().unwrap(), 2);
+        assert_eq!(cache.get(CacheKind::Font, "http://c/").unwrap(), b"cccc");
+        assert!(cache
+            .to_html()
+            .contains("<td>font</td><td>http://c/</td><td>4 B</td>"));
+
+        // The closed cache keeps the entries but doesn't store any more.
+        cache.close().unwrap();
+        cache.put(CacheKind::Http, "http://e/", b"e", None).unwrap();
+        assert!(cache.get(CacheKind::Http, "http://e/").is_none());
+        let mut cache = DiskCache::open(&dir, 10).unwrap();
+        assert_eq!(cache.get_total_size(), 4);
+
+        cache.clear().unwrap();
+        assert_eq!(DiskCache::open(&dir, 10).unwrap().get_total_size(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+use std::fmt;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::net::cache::{get_shared_cache, lock, CacheKind};
+use crate::net::http::{HttpClient, HttpResponse};
+use crate::net::url::split_http_url;
+
+/// The context of the errors of fetching a resource, from which the kind of the failure is told when the
+/// error is returned from the public API.
+#[derive(Debug, thiserror::Error)]
+pub enum FetchError {
+    #[error("Failed to fetch {0}")]
+    Network(String),
+    /// todo: Support TLS.
+    #[error("HTTPS is not supported yet: {0}")]
+    Tls(String),
+    #[error("The fetch was canceled: {0}")]
+    Cancelled(String),
+    /// The local files can be fetched only by the local documents, so that the remote pages can't read them.
+    #[error("A remote document can't fetch the local file {0}")]
+    LocalFile(String),
+}
+
+/// A flag shared with the fetches running on another thread, with which they are canceled, e.g. when the
+/// navigation is canceled or the browser shuts down. A request already sent is not interrupted, but its
+/// response is discarded.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellable(Arc<AtomicBool>);
+
+impl Cancellable {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Fetches the subresources referenced by documents, such as external style sheets.
+pub trait ResourceFetcher: fmt::Debug {
+    /// Returns the body of the resource at the absolute URL.
+    fn fetch(&self, url: &str) -> Result<Vec<u8>>;
+}
+
+/// Fetches resources over HTTP, or from the file system for `file://` URLs if the document which fetches
+/// them is a `file://` one too. No more resources are fetched once the cancellable is canceled.
+/// https://fetch.spec.whatwg.org/#scheme-fetch
+#[derive(Debug)]
+pub struct DefaultFetcher {
+    document_url: String,
+    cancellable: Cancellable,
+}
+
+impl DefaultFetcher {
+    pub fn new(document_url: &str, cancellable: Cancellable) -> Self {
+        Self {
+            document_url: document_url.to_string(),
+            cancellable,
+        }
+    }
+
+    fn ensure_not_cancelled(&self, url: &str) -> Result<()> {
+        if self.cancellable.is_cancelled() {
+            bail!(FetchError::Cancelled(url.to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl ResourceFetcher for DefaultFetcher {
+    fn fetch(&self, url: &str) -> Result<Vec<u8>> {
+        self.ensure_not_cancelled(url)?;
+        if let Some(path) = url.strip_prefix("file://") {
+            if !self.document_url.starts_with("file://") {
+                bail!(FetchError::LocalFile(url.to_string()));
+            }
+            return fs::read(path).with_context(|| format!("Failed to read {}", path));
+        }
+        let response = fetch_http(url)?;
+        self.ensure_not_cancelled(url)?;
+        // Error pages are not used as the resources.
+        let status = response.status_line.split_whitespace().nth(1);
+        ensure!(
+            status.is_some_and(|status| status.starts_with('2')),
+            "Failed to fetch {}: {}",
+            url,
+            response.status_line
+        );
+        Ok(response.body)
+    }
+}
+
+/// Sends a GET request to the HTTP URL, or returns the response from the disk cache if a fresh one is
+/// cached. The responses which are allowed to be reused for a while with `Cache-Control: max-age` are
+/// cached.
+/// todo: Revalidate the stale responses with `ETag` and `Last-Modified`, and support `Expires`.
+/// https://www.rfc-editor.org/rfc/rfc9111
+pub fn fetch_http(url: &str) -> Result<HttpResponse> {
+    let cache = get_shared_cache();
+    if let Some(bytes) = cache.and_then(|cache| lock(cache).get(CacheKind::Http, url)) {
+        return HttpResponse::from_bytes(&bytes);
+    }
+
+    if url.starts_with("https://") {
+        bail!(FetchError::Tls(url.to_string()));
+    }
+    let (host, port, path) =
+        split_http_url(url).with_context(|| format!("Unsupported URL: {}", url))?;
+    let headers = [
+        // HTTP/1.1 client must contain Host header.
+        // https://datatracker.ietf.org/doc/html/rfc9112#section-3.2
+        ("Host", host.as_str()),
+        // todo: Remove this header and handle Content-Length in the client.
+        ("Connection", "close"),
+    ];
+    let response = HttpClient::new(&host, port)
+        .send_request("GET", &path, &headers, None)
+        .with_context(|| FetchError::Network(url.to_string()))?;
+
+    if let (Some(cache), Some(max_age)) = (cache, get_max_age(&response)) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        if let Err(e) = lock(cache).put(
+            CacheKind::Http,
+            url,
+            &response.to_bytes(),
+            Some(now + max_age),
+        ) {
+            eprintln!("Failed to cache {}: {:#}", url, e);
+        }
+    }
+    Ok(response)
+}
+
+/// Sends a POST request with the body to the HTTP URL, e.g. when a form is submitted. The responses to POST
+/// requests are not cached.
+/// https://www.rfc-editor.org/rfc/rfc9110#section-9.3.3
+pub fn post_http(url: &str, content_type: &str, body: &[u8]) -> Result<HttpResponse> {
+    if url.starts_with("https://") {
+        bail!(FetchError::Tls(url.to_string()));
+    }
+    let (host, port, path) =
+        split_http_url(url).with_context(|| format!("Unsupported URL: {}", url))?;
+    let content_length = body.len().to_string();
+    let headers = [
+        ("Host", host.as_str()),
+        ("Connection", "close"),
+        ("Content-Type", content_type),
+        ("Content-Length", content_length.as_str()),
+    ];
+    HttpClient::new(&host, port)
+        .send_request("POST", &path, &headers, Some(body))
+        .with_context(|| FetchError::Network(url.to_string()))
+}
+
+/// Returns the number of seconds for which the response can be reused without revalidation, or `None` if
+/// it must not be stored. Only the successful responses are cached.
+/// https://www.rfc-editor.org/rfc/rfc9111#section-5.2.2.1
+fn get_max_age(response: &HttpResponse) -> Option<u64> {
+    if response.status_line.split_whitespace().nth(1) != Some("200") {
+        return None;
+    }
+    let directives = response
+        .get_header("Cache-Control")?
+        .split(',')
+        .map(|directive| directive.trim().to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    if directives
+        .iter()
+        .any(|directive| directive == "no-store" || directive == "no-cache")
+    {
+        return None;
+    }
+    directives
+        .iter()
+        .find_map(|directive| directive.strip_prefix("max-age=")?.parse().ok())
+        .filter(|max_age| *max_age > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuse_local_files_to_remote_documents() {
+        let path = std::env::temp_dir().join(format!("pentas-fetch-{}.css", std::process::id()));
+        fs::write(&path, "p {}").unwrap();
+        let url = format!("file://{}", path.display());
+
+        let fetcher = DefaultFetcher::new("http://example.com/", Cancellable::default());
+        let error = fetcher.fetch(&url).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<FetchError>(),
+            Some(FetchError::LocalFile(u)) if *u == url
+        ));
+        let fetcher = DefaultFetcher::new("file:///index.html", Cancellable::default());
+        assert_eq!(fetcher.fetch(&url).unwrap(), b"p {}");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cacheable_responses() {
+        let get =
+            |response: &str| get_max_age(&HttpResponse::from_bytes(response.as_bytes()).unwrap());
+        assert_eq!(
+            get("HTTP/1.1 200 OK\r\nCache-Control: public, max-age=600\r\n\r\nbody"),
+            Some(600)
+        );
+        assert_eq!(get("HTTP/1.1 200 OK\r\n\r\nbody"), None);
+        assert_eq!(
+            get("HTTP/1.1 200 OK\r\nCache-Control: max-age=600, no-store\r\n\r\n"),
+            None
+        );
+        assert_eq!(
+            get("HTTP/1.1 404 Not Found\r\nCache-Control: max-age=600\r\n\r\n"),
+            None
+        );
+    }
+}
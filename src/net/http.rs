@@ -1,8 +1,12 @@
 use std::io::{Read, Write};
 use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
 
+/// The time after which connecting to the server, or each read or write on the connection, fails.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
 /// HTTP/1.1 Request
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -11,7 +15,7 @@ pub struct HttpRequest {
     path: String,
     host: String,
     headers: Vec<(String, String)>,
-    body: Option<String>,
+    body: Option<Vec<u8>>,
 }
 
 impl HttpRequest {
@@ -25,14 +29,16 @@ impl HttpRequest {
     //                  CRLF
     //                  [ message-body ]
     /// https://datatracker.ietf.org/doc/html/rfc9112#section-2.1
-    pub fn to_http_format(&self) -> String {
+    pub fn to_http_format(&self) -> Vec<u8> {
         let mut request = format!("{} {} HTTP/1.1\r\n", self.method, self.path);
         for (key, value) in &self.headers {
             request.push_str(&format!("{}: {}\r\n", key, value));
         }
         request.push_str("\r\n");
+        // The body may be in any encoding, or binary, e.g. the files submitted by a form.
+        let mut request = request.into_bytes();
         if let Some(body) = &self.body {
-            request.push_str(body);
+            request.extend(body);
         }
         request
     }
@@ -44,7 +50,8 @@ impl HttpRequest {
 pub struct HttpResponse {
     pub status_line: String,
     pub headers: Vec<(String, String)>,
-    pub body: String,
+    /// The body is kept as bytes since its encoding is determined by the consumer, e.g. the HTML parser.
+    pub body: Vec<u8>,
 }
 
 impl HttpResponse {
@@ -53,8 +60,14 @@ impl HttpResponse {
     //                  CRLF
     //                  [ message-body ]
     /// https://datatracker.ietf.org/doc/html/rfc9112#section-2.1
-    pub fn from_str(response_text: &str) -> Result<Self> {
-        let mut lines = response_text.split("\r\n");
+    pub fn from_bytes(response: &[u8]) -> Result<Self> {
+        // The start line and the header fields are ASCII, while the body may be in any encoding.
+        let head_len = response
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .map_or(response.len(), |i| i + 4);
+        let head = String::from_utf8_lossy(&response[..head_len]);
+        let mut lines = head.split("\r\n");
         let status_line = lines.next().context(anyhow!("No status line"))?.to_string();
 
         let mut headers = Vec::new();
@@ -67,7 +80,7 @@ impl HttpResponse {
             }
         }
 
-        let body = lines.collect::<Vec<&str>>().join("\r\n");
+        let body = response[head_len..].to_vec();
 
         Ok(Self {
             status_line,
@@ -75,6 +88,25 @@ impl HttpResponse {
             body,
         })
     }
+
+    /// Serializes the response back into an HTTP message, from which `from_bytes()` restores it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut head = format!("{}\r\n", self.status_line);
+        for (key, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", key, value));
+        }
+        head.push_str("\r\n");
+        [head.into_bytes(), self.body.clone()].concat()
+    }
+
+    /// Returns the value of the header field. Field names are case-insensitive.
+    /// https://datatracker.ietf.org/doc/html/rfc9110#section-5.1
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
 }
 
 /// HTTP/1.1 Client
@@ -97,7 +129,7 @@ impl HttpClient {
         method: &str,
         path: &str,
         headers: &[(&str, &str)],
-        body: Option<&str>,
+        body: Option<&[u8]>,
     ) -> Result<HttpResponse> {
         let addr = format!("{}:{}", self.host, self.port)
             // This is where the DNS resolution takes place.
@@ -105,7 +137,9 @@ impl HttpClient {
             .to_socket_addrs()?
             .next()
             .context(anyhow!("Failed to resolve address"))?;
-        let mut stream = TcpStream::connect((addr.ip(), addr.port()))?;
+        let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
         let request = HttpRequest {
             method: method.to_string(),
             path: path.to_string(),
@@ -114,14 +148,14 @@ impl HttpClient {
                 .iter()
                 .map(|(k, v)| (k.to_string(), v.to_string()))
                 .collect(),
-            body: body.map(|s| s.to_string()),
+            body: body.map(<[u8]>::to_vec),
         };
-        stream.write_all(request.to_http_format().as_bytes())?;
+        stream.write_all(&request.to_http_format())?;
         stream.flush()?;
 
-        let mut response_text = String::new();
-        stream.read_to_string(&mut response_text)?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
 
-        HttpResponse::from_str(&response_text)
+        HttpResponse::from_bytes(&response)
     }
 }
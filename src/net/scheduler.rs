@@ -0,0 +1,292 @@
+use std::collections::{BinaryHeap, HashSet};
+
+use anyhow::Result;
+
+use crate::renderer::{RenderObject, ResourceHint, ResourceHintKind};
+
+/// The priority of a resource fetch. Resources which block the first paint are fetched first, and those
+/// which are not needed for the current page are fetched last.
+/// https://fetch.spec.whatwg.org/#request-priority
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ResourcePriority {
+    /// Resources for a likely future navigation, e.g. `<link rel=prefetch>`.
+    Prefetch,
+    /// Images which are not in the viewport.
+    BelowTheFoldImage,
+    /// Images which are in the viewport.
+    InViewportImage,
+    /// Fonts which are used by the rendered text.
+    Font,
+    /// Stylesheets which block rendering until they are loaded.
+    /// https://html.spec.whatwg.org/multipage/dom.html#render-blocking
+    RenderBlockingStyle,
+}
+
+impl ResourcePriority {
+    /// Returns the priority of the resource fetched by the hint, or `None` if the hint doesn't fetch any.
+    /// todo: Resolve the hosts of `dns-prefetch` and open the connections of `preconnect` once
+    /// connections are reused.
+    pub fn from_hint(kind: &ResourceHintKind) -> Option<Self> {
+        match kind {
+            ResourceHintKind::DnsPrefetch | ResourceHintKind::Preconnect => None,
+            ResourceHintKind::Prefetch => Some(Self::Prefetch),
+            ResourceHintKind::Preload(destination) => Some(match destination.as_str() {
+                "style" => Self::RenderBlockingStyle,
+                "font" => Self::Font,
+                // The priority is raised when layout finds the image in the viewport.
+                "image" => Self::BelowTheFoldImage,
+                // Scripts are not executed and the other destinations are not used yet, so they are
+                // fetched only when nothing else is needed.
+                _ => Self::Prefetch,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct PendingRequest {
+    priority: ResourcePriority,
+    /// The order in which the request was queued, which keeps the requests with the same priority FIFO.
+    sequence: u64,
+    url: String,
+}
+
+impl Ord for PendingRequest {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for PendingRequest {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Decides the order in which the subresources of a page are fetched, limiting the number of fetches in
+/// flight so that the higher-priority ones are not slowed down by the others.
+/// The external style sheets and the resources requested by the resource hints are fetched with this once
+/// the document is parsed, and the images once it's laid out, whose priorities are raised if they are in the
+/// viewport.
+/// todo: Fetch the subresources concurrently, for which `max_in_flight` is kept.
+#[derive(Debug)]
+pub struct ResourceScheduler {
+    queue: BinaryHeap<PendingRequest>,
+    in_flight: HashSet<String>,
+    max_in_flight: usize,
+    next_sequence: u64,
+}
+
+impl ResourceScheduler {
+    /// The number of connections per host which browsers commonly open.
+    pub const DEFAULT_MAX_IN_FLIGHT: usize = 6;
+
+    pub fn new(max_in_flight: usize) -> Self {
+        Self {
+            queue: BinaryHeap::new(),
+            in_flight: HashSet::new(),
+            max_in_flight: max_in_flight.max(1),
+            next_sequence: 0,
+        }
+    }
+
+    /// Queues the fetch of `url`. If it is already queued, its priority is raised instead, and it is
+    /// ignored if it is already being fetched.
+    pub fn enqueue(&mut self, url: &str, priority: ResourcePriority) {
+        if self.in_flight.contains(url) {
+            return;
+        }
+        if self.queue.iter().any(|request| request.url == url) {
+            self.raise_priority(url, priority);
+            return;
+        }
+        self.queue.push(PendingRequest {
+            priority,
+            sequence: self.next_sequence,
+            url: url.to_string(),
+        });
+        self.next_sequence += 1;
+    }
+
+    /// Raises the priority of the queued fetch of `url`. Priorities are never lowered, so that an image
+    /// scrolled out of the viewport doesn't fall behind the ones which have never been visible.
+    pub fn raise_priority(&mut self, url: &str, priority: ResourcePriority) {
+        let mut requests = std::mem::take(&mut self.queue).into_vec();
+        for request in &mut requests {
+            if request.url == url && request.priority < priority {
+                request.priority = priority;
+            }
+        }
+        self.queue = BinaryHeap::from(requests);
+    }
+
+    /// Queues the fetches requested by the resource hints of a page.
+    pub fn enqueue_hints(&mut self, hints: &[ResourceHint]) {
+        for hint in hints {
+            if let Some(priority) = ResourcePriority::from_hint(&hint.kind) {
+                self.enqueue(&hint.href, priority);
+            }
+        }
+    }
+
+    /// Raises the priorities of the images which layout has placed in `viewport` (x, y, width, height).
+    pub fn prioritize_visible_images(
+        &mut self,
+        objects: &[RenderObject],
+        viewport: (f64, f64, f64, f64),
+    ) {
+        let (vx, vy, vw, vh) = viewport;
+        for object in objects {
+            if let RenderObject::Image {
+                x,
+                y,
+                width,
+                height,
+                src: Some(src),
+                ..
+            } = object
+            {
+                if x + width >= vx && *x <= vx + vw && y + height >= vy && *y <= vy + vh {
+                    self.raise_priority(src, ResourcePriority::InViewportImage);
+                }
+            }
+        }
+    }
+
+    /// Returns the URL of the next resource to fetch, or `None` if nothing is queued or the limit of the
+    /// fetches in flight is reached. The fetch is in flight until `complete` is called.
+    pub fn next(&mut self) -> Option<String> {
+        if self.in_flight.len() >= self.max_in_flight {
+            return None;
+        }
+        let request = self.queue.pop()?;
+        self.in_flight.insert(request.url.clone());
+        Some(request.url)
+    }
+
+    /// Marks the fetch of `url` as finished, whether it succeeded or not.
+    pub fn complete(&mut self, url: &str) {
+        self.in_flight.remove(url);
+    }
+
+    /// Fetches all the queued resources one by one in the order of their priorities with `fetch`, and
+    /// returns the responses in that order.
+    pub fn fetch_all(
+        &mut self,
+        mut fetch: impl FnMut(&str) -> Result<Vec<u8>>,
+    ) -> Vec<(String, Result<Vec<u8>>)> {
+        let mut responses = Vec::new();
+        while let Some(url) = self.next() {
+            let response = fetch(&url);
+            self.complete(&url);
+            responses.push((url, response));
+        }
+        responses
+    }
+
+    #[cfg(test)]
+    pub fn is_idle(&self) -> bool {
+        self.queue.is_empty() && self.in_flight.is_empty()
+    }
+}
+
+impl Default for ResourceScheduler {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX_IN_FLIGHT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(y: f64, src: &str) -> RenderObject {
+        RenderObject::Image {
+            x: 0.0,
+            y,
+            width: 100.0,
+            height: 100.0,
+            src: Some(src.to_string()),
+            alt: String::new(),
+            areas: vec![],
+            lazy: false,
+        }
+    }
+
+    #[test]
+    fn schedule_by_priority() {
+        let mut scheduler = ResourceScheduler::new(2);
+        scheduler.enqueue("below.png", ResourcePriority::BelowTheFoldImage);
+        scheduler.enqueue("next.html", ResourcePriority::Prefetch);
+        scheduler.enqueue("top.png", ResourcePriority::BelowTheFoldImage);
+        scheduler.enqueue("style.css", ResourcePriority::RenderBlockingStyle);
+        scheduler.enqueue("font.woff", ResourcePriority::Font);
+        // Priorities are not lowered by queueing the same resource again.
+        scheduler.enqueue("style.css", ResourcePriority::Prefetch);
+
+        // Layout finds that `top.png` is in the viewport.
+        let objects = [image(0.0, "top.png"), image(2000.0, "below.png")];
+        scheduler.prioritize_visible_images(&objects, (0.0, 0.0, 800.0, 600.0));
+
+        assert_eq!(scheduler.next().as_deref(), Some("style.css"));
+        assert_eq!(scheduler.next().as_deref(), Some("font.woff"));
+        // The number of fetches in flight is limited.
+        assert_eq!(scheduler.next(), None);
+        scheduler.complete("style.css");
+        assert_eq!(scheduler.next().as_deref(), Some("top.png"));
+        scheduler.complete("font.woff");
+        scheduler.complete("top.png");
+        // Resources being fetched are not queued again.
+        assert_eq!(scheduler.next().as_deref(), Some("below.png"));
+        scheduler.enqueue("below.png", ResourcePriority::InViewportImage);
+        assert_eq!(scheduler.next().as_deref(), Some("next.html"));
+        scheduler.complete("below.png");
+        scheduler.complete("next.html");
+        assert_eq!(scheduler.next(), None);
+        assert!(scheduler.is_idle());
+    }
+
+    #[test]
+    fn schedule_resource_hints() {
+        let hint = |kind, href: &str| ResourceHint {
+            kind,
+            href: href.to_string(),
+        };
+        let mut scheduler = ResourceScheduler::default();
+        scheduler.enqueue_hints(&[
+            hint(ResourceHintKind::Prefetch, "next.html"),
+            hint(ResourceHintKind::Preconnect, "https://cdn.example.com"),
+            hint(ResourceHintKind::Preload("image".to_string()), "hero.png"),
+            hint(ResourceHintKind::Preload("style".to_string()), "style.css"),
+            hint(ResourceHintKind::DnsPrefetch, "https://cdn.example.com"),
+            hint(ResourceHintKind::Preload("font".to_string()), "font.woff2"),
+        ]);
+        let mut urls = Vec::new();
+        while let Some(url) = scheduler.next() {
+            urls.push(url);
+        }
+        assert_eq!(urls, ["style.css", "font.woff2", "hero.png", "next.html"]);
+    }
+
+    #[test]
+    fn fetch_all_by_priority() {
+        let mut scheduler = ResourceScheduler::new(1);
+        scheduler.enqueue("a.png", ResourcePriority::BelowTheFoldImage);
+        scheduler.enqueue("missing.css", ResourcePriority::RenderBlockingStyle);
+        scheduler.enqueue("b.css", ResourcePriority::RenderBlockingStyle);
+        let responses = scheduler.fetch_all(|url| match url {
+            "missing.css" => anyhow::bail!("Not found: {}", url),
+            _ => Ok(url.as_bytes().to_vec()),
+        });
+        assert_eq!(
+            responses
+                .iter()
+                .map(|(url, response)| (url.as_str(), response.is_ok()))
+                .collect::<Vec<_>>(),
+            [("missing.css", false), ("b.css", true), ("a.png", true)]
+        );
+        assert!(scheduler.is_idle());
+    }
+}
@@ -0,0 +1,233 @@
+use std::fmt;
+
+use encoding_rs::{Encoding, UTF_8};
+
+/// Resolves a URL referenced by a document against the URL of the document. URLs without a scheme are
+/// treated as HTTP ones, as the toolbar does.
+/// todo: Use a proper URL parser.
+pub fn resolve_url(base: &str, url: &str) -> String {
+    if url.contains("://") {
+        return url.to_string();
+    }
+    let (scheme, rest) = match base.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, base),
+    };
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let resolved = if let Some(path) = url.strip_prefix('/') {
+        format!("{}/{}", host, path)
+    } else {
+        match path.rsplit_once('/') {
+            Some((dir, _)) => format!("{}/{}/{}", host, dir, url),
+            None => format!("{}/{}", host, url),
+        }
+    };
+    match scheme {
+        Some(scheme) => format!("{}://{}", scheme, resolved),
+        None => resolved,
+    }
+}
+
+/// Splits an HTTP URL into the host, the port and the path. URLs without a scheme are treated as HTTP
+/// ones. Returns `None` if the URL has another scheme or an invalid port.
+pub fn split_http_url(url: &str) -> Option<(String, u16, String)> {
+    let url = match url.split_once("://") {
+        Some(("http", rest)) => rest,
+        Some(_) => return None,
+        None => url,
+    };
+    let (authority, path) = match url.find('/') {
+        Some(i) => (&url[..i], url[i..].to_string()),
+        None => (url, "/".to_string()),
+    };
+    let (host, port) = match authority.split_once(':') {
+        Some((host, port)) => (host, port.parse().ok()?),
+        None => (authority, 80),
+    };
+    (!host.is_empty()).then(|| (host.to_string(), port, path))
+}
+
+/// Returns the URL with its query replaced, keeping the fragment.
+/// https://url.spec.whatwg.org/#dom-url-search
+pub fn set_query(url: &str, query: &str) -> String {
+    let (rest, fragment) = match url.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (url, None),
+    };
+    let path = rest.split_once('?').map_or(rest, |(path, _)| path);
+    let mut url = format!("{}?{}", path, query);
+    if let Some(fragment) = fragment {
+        url.push('#');
+        url.push_str(fragment);
+    }
+    url
+}
+
+/// The list of name-value pairs in the `application/x-www-form-urlencoded` format, which is used by the
+/// queries of URLs and the bodies of form submissions, e.g. `a=1&b=x+y`.
+/// https://url.spec.whatwg.org/#concept-urlencoded
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QueryString {
+    pairs: Vec<(String, String)>,
+}
+
+impl QueryString {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the query, whose bytes are decoded as UTF-8.
+    /// https://url.spec.whatwg.org/#concept-urlencoded-parser
+    pub fn parse(query: &str) -> Self {
+        let query = query.strip_prefix('?').unwrap_or(query);
+        let pairs = query
+            .split('&')
+            .filter(|sequence| !sequence.is_empty())
+            .map(|sequence| {
+                let (name, value) = sequence.split_once('=').unwrap_or((sequence, ""));
+                (percent_decode_plus(name), percent_decode_plus(value))
+            })
+            .collect();
+        Self { pairs }
+    }
+
+    pub fn append(&mut self, name: &str, value: &str) -> &mut Self {
+        self.pairs.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Returns the value of the first pair with the name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_pairs(&self) -> &[(String, String)] {
+        &self.pairs
+    }
+
+    /// Serializes the pairs with the names and the values encoded in the encoding. Characters which the
+    /// encoding can't represent are written as numeric character references, e.g. `&#12354;`.
+    /// https://url.spec.whatwg.org/#concept-urlencoded-serializer
+    pub fn serialize(&self, encoding: &'static Encoding) -> String {
+        let encode = |s: &str| {
+            let (bytes, _, _) = encoding.output_encoding().encode(s);
+            let mut output = String::new();
+            for &b in bytes.iter() {
+                match b {
+                    b' ' => output.push('+'),
+                    b'*' | b'-' | b'.' | b'_' => output.push(b as char),
+                    _ if b.is_ascii_alphanumeric() => output.push(b as char),
+                    _ => output.push_str(&format!("%{:02X}", b)),
+                }
+            }
+            output
+        };
+        self.pairs
+            .iter()
+            .map(|(name, value)| format!("{}={}", encode(name), encode(value)))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+}
+
+impl fmt::Display for QueryString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.serialize(UTF_8))
+    }
+}
+
+/// Decodes the percent-encoded bytes and the plus signs, which stand for spaces, as UTF-8.
+/// https://url.spec.whatwg.org/#percent-decode
+fn percent_decode_plus(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => output.push(b' '),
+            b'%' => match input
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(b) => {
+                    output.push(b);
+                    i += 2;
+                }
+                None => output.push(b'%'),
+            },
+            b => output.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_urls() {
+        assert_eq!(
+            resolve_url("example.com/a/b.html", "c.css"),
+            "example.com/a/c.css"
+        );
+        assert_eq!(
+            resolve_url("http://example.com/a/b.html", "/c.css"),
+            "http://example.com/c.css"
+        );
+        assert_eq!(resolve_url("example.com", "c.css"), "example.com/c.css");
+        assert_eq!(
+            resolve_url("file:///home/a/b.html", "c.css"),
+            "file:///home/a/c.css"
+        );
+        assert_eq!(
+            resolve_url("example.com/a/", "http://example.org/c.css"),
+            "http://example.org/c.css"
+        );
+
+        assert_eq!(
+            split_http_url("http://localhost:8000/a/b?c"),
+            Some(("localhost".to_string(), 8000, "/a/b?c".to_string()))
+        );
+        assert_eq!(
+            split_http_url("example.com"),
+            Some(("example.com".to_string(), 80, "/".to_string()))
+        );
+        assert_eq!(split_http_url("https://example.com/"), None);
+        assert_eq!(split_http_url("example.com:x/"), None);
+    }
+
+    #[test]
+    fn build_query_strings() {
+        let mut query = QueryString::new();
+        query
+            .append("q", "a b&c")
+            .append("lang", "日本")
+            .append("", "*-._~");
+        assert_eq!(
+            query.to_string(),
+            "q=a+b%26c&lang=%E6%97%A5%E6%9C%AC&=*-._%7E"
+        );
+        assert_eq!(QueryString::parse(&query.to_string()), query);
+        // Characters which are not in the encoding are written as character references.
+        assert_eq!(
+            query.serialize(encoding_rs::WINDOWS_1252),
+            "q=a+b%26c&lang=%26%2326085%3B%26%2326412%3B&=*-._%7E"
+        );
+
+        let query = QueryString::parse("?a=1&&b&a=%zz+%41");
+        assert_eq!(query.get("a"), Some("1"));
+        assert_eq!(query.get("b"), Some(""));
+        assert_eq!(query.get_pairs()[2].1, "%zz A");
+
+        assert_eq!(
+            set_query("http://example.com/a?b=1#c", "d=2"),
+            "http://example.com/a?d=2#c"
+        );
+        assert_eq!(set_query("example.com/", "q="), "example.com/?q=");
+    }
+}
@@ -1,20 +1,80 @@
+#[doc(hidden)]
+pub mod bench;
+pub mod color_management;
 mod css;
 mod html;
 mod layout;
+pub mod parse_error;
+pub mod source;
+pub mod stats;
 mod style;
+mod svg;
+mod text_rendering;
 
-use anyhow::Result;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use anyhow::{Context, Result};
 use gtk4::pango;
 
 use crate::app::VerbosityLevel;
-use crate::ui::{DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_WIDTH};
+use crate::error::{self, Error};
+use crate::net::fetch::{Cancellable, DefaultFetcher};
 use crate::utils::PrintableTree as _;
+use css::cssom::{ComponentValue, StyleSheet};
 use css::get_ua_style_sheet;
 use css::parser::CssParser;
 use css::token::CssTokenizer;
-use html::dom::DocumentTree;
+use html::dom::{DocumentTree, DomNode, NodeType};
+use html::image_map::ImageMapArea;
+use html::interaction::{
+    activate, get_focusable_ancestor, move_user_action, set_target, UserAction,
+};
+use html::live_region::{AnnouncementLog, LiveRegionTracker};
+use html::metadata::get_document_title;
+use html::mutation::{invalidate_style, set_document_title};
+use html::parse_thread::ParserThread;
 use html::parser::HtmlParser;
+use html::serializer::{serialize_document, serialize_outer};
+use html::spellcheck::{get_dictionary, replace_misspelled_word};
 use html::token::HtmlTokenizer;
+use layout::box_model::{take_layout_count, BoxTree, HitRegions};
+use layout::geometry::Rect;
+use layout::intersection::IntersectionObserver;
+use layout::lines::dump_lines;
+use layout::overflow::ScrollBox;
+use layout::positioned::ScrollLayer;
+use layout::resize::{
+    ResizeObserver, ResizeObserverBoxOptions, ResizeObserverCallback, ResizeObserverEntry,
+    ResizeObservers,
+};
+use layout::text::{EditableText, MisspelledWord};
+use parse_error::{ParseErrorCollector, ParseErrorReport};
+use stats::PipelineStats;
+use style::invalidation::UserActionInvalidationMap;
+use style::style_model::{RenderTree, StyleCache};
+
+pub use css::media::{ColorScheme, MediaEnvironment, MediaType};
+pub use css::page::PageSettings;
+pub use html::editing::{is_caret_visible, Editor, CARET_BLINK_TIMEOUT};
+pub use html::form::FormSubmission;
+pub use html::image_map::AreaShape;
+pub use html::interaction::Activation;
+pub use html::lifecycle::{get_timer_delay, VisibilityState};
+pub use html::live_region::{AccessibilityBackend, Announcement, Politeness};
+pub use html::metadata::{
+    AlternateLink, IconLink, IconSize, PageMetadata, Refresh, ResourceHint, ResourceHintKind,
+    SharePreview, StylesheetLink,
+};
+pub use html::parse_thread::cancel_parser_threads;
+pub use html::serializer::escape_string;
+pub use html::spellcheck::{set_dictionary, Dictionary};
+pub use svg::path::PathSegment;
+pub use text_rendering::{
+    get_text_rendering_options, set_text_rendering_options, Antialiasing, Hinting,
+    TextRenderingOptions,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum RenderObject {
@@ -25,72 +85,853 @@ pub enum RenderObject {
         font_family: Vec<String>,
         font_size: f64,
         font_weight: String,
-        /// 0.0 <= (r, g, b) <= 1.0
-        color: (f64, f64, f64),
-        /// 0.0 <= (r, g, b) <= 1.0
-        decoration_color: (f64, f64, f64),
-        decoration_line: Vec<String>,
-        decoration_style: String,
+        /// The style and the stretch options of the font description of pango, e.g. `Italic Condensed`,
+        /// which are empty for the normal ones.
+        font_style: String,
+        /// 0.0 <= (r, g, b, a) <= 1.0
+        color: (f64, f64, f64, f64),
     },
     Rect {
         x: f64,
         y: f64,
         width: f64,
         height: f64,
-        /// 0.0 <= (r, g, b) <= 1.0
-        color: (f64, f64, f64),
+        /// 0.0 <= (r, g, b, a) <= 1.0
+        color: (f64, f64, f64, f64),
         /// (top-left, top-right, bottom-right, bottom-left)
         border_radius: (f64, f64, f64, f64),
     },
+    /// A placeholder of a media element, since media playback is not supported yet.
+    Media {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        is_video: bool,
+        /// The URL of the poster frame of the video.
+        poster: Option<String>,
+        controls: bool,
+    },
+    Image {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        src: Option<String>,
+        alt: String,
+        /// The areas of the image map associated with the image, in tree order.
+        areas: Vec<ImageMapArea>,
+        /// Whether the image is fetched only when it approaches the viewport.
+        lazy: bool,
+    },
+    /// A shape of an inline SVG image, whose coordinates are on the canvas.
+    Path {
+        segments: Vec<PathSegment>,
+        /// 0.0 <= (r, g, b, a) <= 1.0, or `None` if the path is not filled.
+        fill: Option<(f64, f64, f64, f64)>,
+        /// Whether the fill rule is `evenodd` instead of `nonzero`.
+        even_odd: bool,
+        /// 0.0 <= (r, g, b, a) <= 1.0, or `None` if the path is not stroked.
+        stroke: Option<(f64, f64, f64, f64)>,
+        stroke_width: f64,
+        /// The area (x, y, width, height) of the SVG viewport or the MathML formula which the path is
+        /// clipped to.
+        clip: (f64, f64, f64, f64),
+    },
+    /// Clips the following objects to the area until the matching `EndClip`, e.g. to the padding box of
+    /// a box whose overflow is not visible. The clips are nested.
+    /// https://drafts.csswg.org/css-overflow-3/#overflow-properties
+    Clip {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+    EndClip,
+}
+
+/// The distance from the viewport within which the lazily loaded images are fetched, so that they are
+/// likely to be ready when they are scrolled into view.
+/// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#lazy-load-root-margin
+pub const LAZY_LOAD_MARGIN: f64 = 1250.0;
+
+impl RenderObject {
+    /// Moves the object on the canvas.
+    pub fn translate(&mut self, dx: f64, dy: f64) {
+        match self {
+            RenderObject::Text { x, y, .. }
+            | RenderObject::Rect { x, y, .. }
+            | RenderObject::Media { x, y, .. }
+            | RenderObject::Image { x, y, .. }
+            | RenderObject::Clip { x, y, .. } => {
+                *x += dx;
+                *y += dy;
+            }
+            RenderObject::Path { segments, clip, .. } => {
+                for segment in segments.iter_mut() {
+                    *segment = segment.map_points(|x, y| (x + dx, y + dy));
+                }
+                clip.0 += dx;
+                clip.1 += dy;
+            }
+            RenderObject::EndClip => {}
+        }
+    }
+
+    /// Makes the object translucent by multiplying the alpha of its colors. The images and the media are
+    /// kept opaque.
+    pub fn apply_opacity(&mut self, alpha: f64) {
+        match self {
+            RenderObject::Text { color, .. } | RenderObject::Rect { color, .. } => color.3 *= alpha,
+            RenderObject::Path { fill, stroke, .. } => {
+                for color in fill.iter_mut().chain(stroke.iter_mut()) {
+                    color.3 *= alpha;
+                }
+            }
+            RenderObject::Media { .. }
+            | RenderObject::Image { .. }
+            | RenderObject::Clip { .. }
+            | RenderObject::EndClip => {}
+        }
+    }
+
+    /// Returns whether the resource of the object should be fetched. The ones of the lazily loaded images
+    /// are deferred until they are resumed by `ParsedDocument::update_lazy_loading()`.
+    /// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#will-lazy-load-element-steps
+    pub fn should_fetch(&self) -> bool {
+        !matches!(self, RenderObject::Image { lazy: true, .. })
+    }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct RenderObjects {
     pub list: Vec<RenderObject>,
+    /// The ranges of `list` painted by the fixed and sticky positioned boxes, which are moved when the
+    /// viewport is scrolled.
+    pub scroll_layers: Vec<ScrollLayer>,
     pub max_width: f32,
     pub max_height: f32,
+    /// The metadata of the page, which is kept with the page for bookmarking and sharing it.
+    pub metadata: PageMetadata,
 }
 
-pub fn get_render_objects(
-    html: &str,
-    viewport_width: i32,
-    viewport_height: i32,
-    draw_ctx: &pango::Context,
+/// The position of an image map area, which is the index of the image in the render objects and the index
+/// of the area in the image map.
+pub type AreaIndex = (usize, usize);
+
+impl RenderObjects {
+    /// Returns the objects as they are painted when `viewport` (x, y, width, height) is the visible area
+    /// of the canvas, where the fixed and sticky positioned boxes are moved by the scroll position.
+    /// todo: Find the image map areas and the elements under the pointer in the moved boxes.
+    pub fn get_scrolled_list(&self, viewport: (f64, f64, f64, f64)) -> Cow<'_, [RenderObject]> {
+        if self.scroll_layers.is_empty() {
+            return Cow::Borrowed(&self.list);
+        }
+        let mut list = self.list.clone();
+        for layer in &self.scroll_layers {
+            let (dx, dy) = layer.get_offset(viewport);
+            for object in &mut list[layer.range.clone()] {
+                object.translate(dx, dy);
+            }
+        }
+        Cow::Owned(list)
+    }
+
+    /// Returns the image map area at the point, if any. The topmost image is hit, and the first area
+    /// in tree order is chosen if the areas overlap.
+    /// https://html.spec.whatwg.org/multipage/image-maps.html#image-map-processing-model
+    pub fn find_area_at(&self, x: f64, y: f64) -> Option<AreaIndex> {
+        self.list.iter().enumerate().rev().find_map(|(i, object)| {
+            let RenderObject::Image {
+                x: img_x,
+                y: img_y,
+                width,
+                height,
+                areas,
+                ..
+            } = object
+            else {
+                return None;
+            };
+            let (local_x, local_y) = (x - img_x, y - img_y);
+            if !(0.0..=*width).contains(&local_x) || !(0.0..=*height).contains(&local_y) {
+                return None;
+            }
+            areas
+                .iter()
+                .position(|area| area.shape.contains(local_x, local_y, *width, *height))
+                .map(|j| (i, j))
+        })
+    }
+
+    /// Returns the area at the index.
+    pub fn get_area(&self, (i, j): AreaIndex) -> Option<&ImageMapArea> {
+        match self.list.get(i)? {
+            RenderObject::Image { areas, .. } => areas.get(j),
+            _ => None,
+        }
+    }
+
+    /// Returns the focusable areas in the sequential focus navigation order, which is the tree order.
+    pub fn get_focusable_areas(&self) -> Vec<AreaIndex> {
+        self.list
+            .iter()
+            .enumerate()
+            .filter_map(|(i, object)| match object {
+                RenderObject::Image { areas, .. } => Some(
+                    areas
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, area)| area.is_focusable())
+                        .map(move |(j, _)| (i, j)),
+                ),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+}
+
+/// A document which is parsed on a worker thread, so that the window keeps responding while a large
+/// document is parsed.
+#[derive(Debug)]
+pub struct PendingDocument {
+    thread: ParserThread,
+}
+
+impl PendingDocument {
+    /// Starts parsing the document. The external style sheets are fetched relative to `document_url`, and
+    /// the ones whose `media` attributes don't match the media are skipped.
+    pub fn parse(html: &str, document_url: &str, media: MediaEnvironment) -> Result<Self> {
+        Ok(Self {
+            thread: ParserThread::spawn_with_input(document_url, html, media)?,
+        })
+    }
+
+    /// Returns the document once it is parsed. Returns `None` while it is being parsed, so this is polled
+    /// until it returns the result.
+    pub fn poll(&self) -> Option<Result<ParsedDocument>> {
+        self.thread.try_recv().map(|result| {
+            result.and_then(|(document, style_sheets)| {
+                ParsedDocument::new(document.into_dom(), style_sheets)
+            })
+        })
+    }
+}
+
+/// The editing hosts which have been resized while they are rendered, which are recorded by the resize
+/// observer of the focused editor.
+#[derive(Debug, Default)]
+struct EditingHostResizes {
+    hosts: Vec<Rc<RefCell<DomNode>>>,
+}
+
+impl ResizeObserverCallback for EditingHostResizes {
+    fn on_resize(&mut self, entries: &[ResizeObserverEntry]) {
+        // The host which is no longer rendered has no caret to be moved.
+        self.hosts.extend(
+            entries
+                .iter()
+                .filter(|entry| entry.content_box_size.inline_size > 0.0)
+                .map(|entry| Rc::clone(&entry.target)),
+        );
+    }
+}
+
+/// A parsed document with its style sheets, which is rendered again without parsing it when the environment
+/// changes, e.g. when the window is resized.
+/// todo: Evaluate the `media` attributes of the style sheets again too, which are evaluated while parsing.
+#[derive(Debug)]
+pub struct ParsedDocument {
+    root: Rc<RefCell<DomNode>>,
+    style_sheets: Vec<StyleSheet>,
+    /// The border boxes of the elements laid out by the last rendering, with which the element under the
+    /// pointer is found.
+    hit_regions: RefCell<HitRegions>,
+    /// The scroll containers laid out by the last rendering, with which the one under the pointer is
+    /// scrolled.
+    scroll_boxes: RefCell<Vec<ScrollBox>>,
+    /// The misspelled words laid out by the last rendering, whose suggestions are offered at the pointer.
+    misspelled_words: RefCell<Vec<MisspelledWord>>,
+    /// The fragments of the editable texts laid out by the last rendering, in which the caret is placed.
+    editable_texts: RefCell<Vec<EditableText>>,
+    hovered: RefCell<Option<Rc<RefCell<DomNode>>>>,
+    active: RefCell<Option<Rc<RefCell<DomNode>>>>,
+    focused: RefCell<Option<Rc<RefCell<DomNode>>>>,
+    /// The styles computed by the last rendering for the screen with its media, which are reused for the
+    /// elements not invalidated since.
+    styles: RefCell<Option<(MediaEnvironment, StyleCache)>>,
+    /// Used to find the elements to be restyled when the states change.
+    invalidation_maps: Vec<(UserAction, UserActionInvalidationMap)>,
+    /// Whether the style sheets or the `style` attributes have lengths relative to the viewport, in which
+    /// case the document needs to be rendered again when the viewport is resized.
+    has_viewport_units: bool,
+    /// Observes the lazily loaded images which are not resumed yet.
+    /// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#lazy-load-intersection-observer
+    lazy_load_observer: RefCell<IntersectionObserver>,
+    /// The border boxes of the images observed by `lazy_load_observer`, laid out by the last rendering.
+    lazy_load_targets: RefCell<Vec<(Rc<RefCell<DomNode>>, Rect)>>,
+    /// The resize observers of the document, which are notified at the end of each rendering.
+    resize_observers: RefCell<ResizeObservers>,
+    /// Observes the editing host of the focused editor, whose caret moves when the host is resized.
+    editing_host_observer: Rc<RefCell<ResizeObserver>>,
+    editing_host_resizes: Rc<RefCell<EditingHostResizes>>,
+    /// The rendered texts of the live regions at the last update, whose changes are announced.
+    live_regions: RefCell<LiveRegionTracker>,
+}
+
+impl ParsedDocument {
+    fn new(root: Rc<RefCell<DomNode>>, style_sheets: Vec<StyleSheet>) -> Result<Self> {
+        let all_style_sheets = std::iter::once(get_ua_style_sheet()?)
+            .chain(style_sheets.iter().cloned())
+            .collect::<Vec<_>>();
+        let invalidation_maps = UserAction::ALL
+            .into_iter()
+            .map(|action| {
+                (
+                    action,
+                    UserActionInvalidationMap::build(&all_style_sheets, action.get_pseudo_class()),
+                )
+            })
+            .collect();
+        let has_viewport_units = style_sheets.iter().any(StyleSheet::has_viewport_units)
+            || DomNode::get_descendants(&root).any(|node| {
+                let NodeType::Element(elm) = &node.borrow().node_type else {
+                    return false;
+                };
+                elm.get_attribute("style")
+                    .and_then(|style| CssTokenizer::new(style).tokenize().ok())
+                    .is_some_and(|tokens| {
+                        tokens
+                            .into_iter()
+                            .any(|token| ComponentValue::PreservedToken(token).has_viewport_units())
+                    })
+            });
+        let editing_host_resizes = Rc::new(RefCell::new(EditingHostResizes::default()));
+        let editing_host_observer = Rc::new(RefCell::new(ResizeObserver::new(Rc::clone(
+            &editing_host_resizes,
+        ) as _)));
+        let mut resize_observers = ResizeObservers::default();
+        resize_observers.register(&editing_host_observer);
+        // The live regions in the loaded document are not changes to be announced.
+        let mut live_regions = LiveRegionTracker::default();
+        live_regions.update(&root, &AnnouncementLog::default());
+        Ok(Self {
+            root,
+            style_sheets,
+            hit_regions: RefCell::default(),
+            scroll_boxes: RefCell::default(),
+            misspelled_words: RefCell::default(),
+            editable_texts: RefCell::default(),
+            hovered: RefCell::default(),
+            active: RefCell::default(),
+            focused: RefCell::default(),
+            styles: RefCell::default(),
+            invalidation_maps,
+            has_viewport_units,
+            lazy_load_observer: RefCell::new(IntersectionObserver::new(
+                &format!("{}px", LAZY_LOAD_MARGIN),
+                &[],
+            )?),
+            lazy_load_targets: RefCell::default(),
+            resize_observers: RefCell::new(resize_observers),
+            editing_host_observer,
+            editing_host_resizes,
+            live_regions: RefCell::new(live_regions),
+        })
+    }
+
+    /// Renders the document into the objects to paint for the media. Only the elements invalidated since
+    /// the last rendering are restyled unless the media changes.
+    pub fn render(
+        &self,
+        media: &MediaEnvironment,
+        viewport_width: i32,
+        viewport_height: i32,
+        draw_ctx: &pango::Context,
+        verbosity: VerbosityLevel,
+    ) -> Result<RenderObjects> {
+        let mut styles = self
+            .styles
+            .take()
+            .filter(|(styled_media, _)| styled_media == media)
+            .map(|(_, styles)| styles)
+            .unwrap_or_default();
+        let render_tree = build_render_tree(
+            Rc::clone(&self.root),
+            self.style_sheets.clone(),
+            media,
+            verbosity,
+            Some(&mut styles),
+        )?;
+        let (objects, box_tree) = render_document(
+            render_tree,
+            viewport_width,
+            viewport_height,
+            draw_ctx,
+            verbosity,
+        )?;
+        *self.styles.borrow_mut() = Some((*media, styles));
+        *self.hit_regions.borrow_mut() = box_tree.get_hit_regions();
+        *self.scroll_boxes.borrow_mut() = box_tree.get_scroll_boxes();
+        *self.misspelled_words.borrow_mut() = box_tree.get_misspelled_words();
+        *self.editable_texts.borrow_mut() = box_tree.get_editable_texts();
+        self.observe_lazy_loading(&box_tree);
+        // todo: Pass the scale factor of the canvas, which only matters to the observations of the
+        // device-pixel-content-box.
+        self.resize_observers.borrow().notify(&box_tree, 1.0);
+        Ok(objects)
+    }
+
+    /// Announces the changes of the live regions since the last update through `backend`, which is called
+    /// each time the document is rendered again after it is mutated.
+    pub fn update_live_regions(&self, backend: &dyn AccessibilityBackend) {
+        self.live_regions.borrow_mut().update(&self.root, backend);
+    }
+
+    /// Starts observing the size of the editing host of the focused editor in place of the last one, or
+    /// stops observing it if no editor is focused.
+    pub fn observe_editing_host(&self, editor: Option<&Editor>) {
+        let mut observer = self.editing_host_observer.borrow_mut();
+        observer.disconnect();
+        if let Some(editor) = editor {
+            observer.observe(editor.get_host(), ResizeObserverBoxOptions::ContentBox);
+        }
+        self.editing_host_resizes.borrow_mut().hosts.clear();
+    }
+
+    /// Returns whether the editing host observed by `observe_editing_host()` has been resized by the
+    /// renderings since this was last called, in which case its caret may have moved, e.g. since the text
+    /// wraps differently.
+    pub fn take_editing_host_resize(&self) -> bool {
+        !std::mem::take(&mut self.editing_host_resizes.borrow_mut().hosts).is_empty()
+    }
+
+    /// Starts observing the lazily loaded images laid out by the rendering which are not resumed yet, in
+    /// place of the ones observed for the last rendering.
+    /// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#start-intersection-observing-a-lazy-loading-element
+    fn observe_lazy_loading(&self, box_tree: &BoxTree) {
+        let targets = DomNode::get_descendants(&self.root)
+            .filter(|node| {
+                let node = node.borrow();
+                !node.lazy_load_resumed
+                    && matches!(&node.node_type, NodeType::Element(elm) if elm.tag_name == "img" && elm.is_lazy_loading())
+            })
+            .filter_map(|node| box_tree.get_border_box(&node).map(|rect| (node, rect)))
+            .collect::<Vec<_>>();
+        let mut observer = self.lazy_load_observer.borrow_mut();
+        observer.disconnect();
+        for (target, _) in &targets {
+            observer.observe(target);
+        }
+        *self.lazy_load_targets.borrow_mut() = targets;
+    }
+
+    /// Updates the intersections of the lazily loaded images with `viewport` (x, y, width, height), which is
+    /// run each time the document is painted after it is rendered or scrolled. The images which are within
+    /// `LAZY_LOAD_MARGIN` of the viewport are resumed, and this returns whether any is, in which case the
+    /// document needs to be rendered again to fetch them.
+    /// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#lazy-load-intersection-observer
+    pub fn update_lazy_loading(&self, viewport: (f64, f64, f64, f64)) -> bool {
+        let (x, y, width, height) = viewport;
+        let targets = self.lazy_load_targets.borrow();
+        let mut observer = self.lazy_load_observer.borrow_mut();
+        let entries = observer.update(Rect::new(x, y, width, height), |target| {
+            targets
+                .iter()
+                .find(|(t, _)| Rc::ptr_eq(t, target))
+                .map(|(_, rect)| *rect)
+        });
+        let mut is_resumed = false;
+        for entry in entries.into_iter().filter(|entry| entry.is_intersecting) {
+            // https://html.spec.whatwg.org/multipage/urls-and-fetching.html#stop-intersection-observing-a-lazy-loading-element
+            observer.unobserve(&entry.target);
+            entry.target.borrow_mut().lazy_load_resumed = true;
+            is_resumed = true;
+        }
+        is_resumed
+    }
+
+    /// Renders the document for printing, where the `print` media type applies and the document is laid
+    /// out in the page area given by its `@page` rules. The objects are split into the pages of the returned
+    /// settings when they are painted.
+    /// https://drafts.csswg.org/css-page-3/#page-model
+    pub fn render_for_print(
+        &self,
+        media: &MediaEnvironment,
+        draw_ctx: &pango::Context,
+        verbosity: VerbosityLevel,
+    ) -> Result<(PageSettings, RenderObjects)> {
+        let media = MediaEnvironment {
+            media_type: MediaType::Print,
+            ..*media
+        };
+        let page_settings = PageSettings::from_style_sheets(&self.style_sheets, &media);
+        let (width, height) = page_settings.get_page_area();
+        let media = MediaEnvironment {
+            viewport_width: width.round() as i32,
+            viewport_height: height.round() as i32,
+            ..media
+        };
+        // The hit regions and the scroll containers are left for the screen.
+        let render_tree = build_render_tree(
+            Rc::clone(&self.root),
+            self.style_sheets.clone(),
+            &media,
+            verbosity,
+            None,
+        )?;
+        let (objects, ..) = render_document(
+            render_tree,
+            media.viewport_width,
+            media.viewport_height,
+            draw_ctx,
+            verbosity,
+        )?;
+        Ok((page_settings, objects))
+    }
+
+    /// Moves the hover state to the element under the pointer at the position on the canvas, or clears it
+    /// if the pointer leaves the canvas. Returns whether the document needs to be rendered again.
+    pub fn on_pointer_move(&self, pos: Option<(f64, f64)>) -> bool {
+        let target = pos.and_then(|(x, y)| self.hit_test(x, y));
+        self.move_user_action(UserAction::Hover, &self.hovered, target)
+    }
+
+    /// Makes the element under the pointer active, and moves the focus to it or to its nearest focusable
+    /// ancestor, as the primary button is pressed at the position. Returns whether the document needs to
+    /// be rendered again.
+    /// https://html.spec.whatwg.org/multipage/interaction.html#focus-update-steps
+    pub fn on_pointer_down(&self, x: f64, y: f64) -> bool {
+        let target = self.hit_test(x, y);
+        let focus = target.as_ref().and_then(get_focusable_ancestor);
+        let active_changed = self.move_user_action(UserAction::Active, &self.active, target);
+        let focus_changed = self.move_user_action(UserAction::Focus, &self.focused, focus);
+        active_changed || focus_changed
+    }
+
+    /// Clears the active state as the primary button is released. Returns whether the document needs to be
+    /// rendered again.
+    pub fn on_pointer_up(&self) -> bool {
+        self.move_user_action(UserAction::Active, &self.active, None)
+    }
+
+    /// Scrolls the innermost scroll container under the pointer at the position on the canvas by (dx, dy),
+    /// and returns whether it's scrolled, in which case the document needs to be rendered again. The
+    /// scroll containers which can't be scrolled further in the direction are skipped, so that the ones
+    /// outside them or the page are scrolled instead.
+    /// https://drafts.csswg.org/cssom-view/#scrolling-events
+    pub fn scroll_by(&self, x: f64, y: f64, dx: f64, dy: f64) -> bool {
+        let scroll_boxes = self.scroll_boxes.borrow();
+        for scroll_box in scroll_boxes.iter().rev() {
+            if !scroll_box
+                .visible_scrollport
+                .is_some_and(|rect| rect.contains(x, y))
+            {
+                continue;
+            }
+            let offset = scroll_box.get_scrolled_offset(dx as f32, dy as f32);
+            if offset != scroll_box.offset {
+                scroll_box.node.borrow_mut().scroll_offset = offset;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns the suggestions for the misspelled word at the position on the canvas, or `None` if there is
+    /// no misspelled word.
+    pub fn get_spelling_suggestions(&self, x: f64, y: f64) -> Option<Vec<String>> {
+        let dictionary = get_dictionary()?;
+        let word = self.find_misspelled_word(x, y)?;
+        Some(dictionary.suggest(&word.word))
+    }
+
+    /// Replaces the misspelled word at the position on the canvas with the replacement, and returns whether
+    /// it's replaced, in which case the document needs to be rendered again.
+    pub fn replace_misspelled_word(&self, x: f64, y: f64, replacement: &str) -> Result<bool> {
+        let (Some(dictionary), Some(word)) = (get_dictionary(), self.find_misspelled_word(x, y))
+        else {
+            return Ok(false);
+        };
+        replace_misspelled_word(&dictionary, &word.node, word.index, replacement)
+    }
+
+    /// Returns the text node and the offset in its data of the caret placed at the position on the canvas,
+    /// which is in the editable fragment nearest to it in the line containing it, or `None` if no editable
+    /// text is there.
+    pub fn get_caret_position(&self, x: f64, y: f64) -> Option<(Rc<RefCell<DomNode>>, usize)> {
+        let texts = self.editable_texts.borrow();
+        let text = texts
+            .iter()
+            .filter(|text| (text.rect.y..text.rect.y + text.rect.height).contains(&y))
+            .min_by(|a, b| {
+                let distance = |rect: &Rect| (rect.x - x).max(x - rect.x - rect.width).max(0.0);
+                distance(&a.rect).total_cmp(&distance(&b.rect))
+            })?;
+        Some((Rc::clone(&text.node), text.get_offset_at(x)))
+    }
+
+    /// Returns the objects drawing the caret of the editor and the text being composed at it, which are
+    /// painted over the page.
+    pub fn get_caret_objects(
+        &self,
+        editor: &Editor,
+        is_caret_visible: bool,
+        draw_ctx: &pango::Context,
+    ) -> Vec<RenderObject> {
+        let Some(caret) = editor.get_caret() else {
+            return Vec::new();
+        };
+        self.editable_texts
+            .borrow()
+            .iter()
+            .find(|text| text.contains(&caret.node, caret.offset))
+            .map(|text| {
+                text.get_caret_objects(
+                    caret.offset,
+                    editor.get_composition(),
+                    is_caret_visible,
+                    draw_ctx,
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the area of the line at the caret of the editor on the canvas, next to which the candidates
+    /// of the input method are shown.
+    pub fn get_caret_area(&self, editor: &Editor) -> Option<Rect> {
+        let caret = editor.get_caret()?;
+        self.editable_texts
+            .borrow()
+            .iter()
+            .find(|text| text.contains(&caret.node, caret.offset))
+            .map(|text| {
+                let x = text.caret_positions[caret.offset - text.offset];
+                Rect::new(x, text.rect.y, 0.0, text.rect.height)
+            })
+    }
+
+    fn find_misspelled_word(&self, x: f64, y: f64) -> Option<MisspelledWord> {
+        self.misspelled_words
+            .borrow()
+            .iter()
+            .find(|word| word.rect.contains(x, y))
+            .cloned()
+    }
+
+    /// Returns the topmost element whose border box contains the position.
+    /// todo: Respect the positioned boxes and `pointer-events`.
+    fn hit_test(&self, x: f64, y: f64) -> Option<Rc<RefCell<DomNode>>> {
+        self.hit_regions
+            .borrow()
+            .iter()
+            .rev()
+            .find(|(rect, _)| rect.contains(x, y))
+            .map(|(_, node)| Rc::clone(node))
+    }
+
+    /// Moves the state from the element in `current` to `new`, and invalidates the styles of the elements
+    /// found by the invalidation map of the action, which are restyled by the next rendering. Returns
+    /// whether any element has to be restyled for the change.
+    fn move_user_action(
+        &self,
+        action: UserAction,
+        current: &RefCell<Option<Rc<RefCell<DomNode>>>>,
+        new: Option<Rc<RefCell<DomNode>>>,
+    ) -> bool {
+        let old = current.replace(new.clone());
+        if old.as_ref().map(Rc::as_ptr) == new.as_ref().map(Rc::as_ptr) {
+            return false;
+        }
+        let changed = move_user_action(action, old.as_ref(), new.as_ref());
+        let invalidated = self
+            .invalidation_maps
+            .iter()
+            .filter(|(a, _)| *a == action)
+            .flat_map(|(_, map)| map.get_invalidated_nodes_by_changes(&changed))
+            .collect::<Vec<_>>();
+        for node in &invalidated {
+            invalidate_style(node);
+        }
+        !invalidated.is_empty()
+    }
+
+    /// Returns the title of the document, or `None` if it has no `title` element.
+    /// https://html.spec.whatwg.org/multipage/dom.html#document.title
+    pub fn get_title(&self) -> Option<String> {
+        get_document_title(&self.root)
+    }
+
+    /// Sets the title of the document, which is reflected in the metadata of the objects once the document
+    /// is rendered again.
+    pub fn set_title(&self, title: &str) -> Result<()> {
+        set_document_title(&self.root, title)
+    }
+
+    /// Serializes the DOM tree of the document into HTML.
+    pub fn serialize(&self) -> Result<String> {
+        Ok(serialize_document(&DocumentTree::build(Rc::clone(
+            &self.root,
+        ))?))
+    }
+
+    /// Returns the elements which match the selector list, serialized into HTML in tree order.
+    pub fn query_selector_all(&self, selectors: &str) -> Result<Vec<String>> {
+        Ok(DomNode::query_selector_all(&self.root, selectors)?
+            .iter()
+            .map(serialize_outer)
+            .collect())
+    }
+
+    /// Returns the names and the values of the computed style of the first element which matches the selector
+    /// list, which is styled for the media.
+    /// https://drafts.csswg.org/cssom/#dom-window-getcomputedstyle
+    pub fn get_computed_style(
+        &self,
+        selectors: &str,
+        media: &MediaEnvironment,
+    ) -> Result<Vec<(&'static str, String)>> {
+        let node = DomNode::query_selector(&self.root, selectors)?
+            .with_context(|| format!("No element matches \"{}\"", selectors))?;
+        let style_sheets = std::iter::once(get_ua_style_sheet()?)
+            .chain(self.style_sheets.iter().cloned())
+            .collect::<Vec<_>>();
+        DocumentTree::build(Rc::clone(&self.root))?
+            .to_render_tree(style_sheets, media)?
+            .get_element_styles()
+            .into_iter()
+            .find(|(element, _)| Rc::ptr_eq(element, &node))
+            .map(|(_, style)| style.get_declarations())
+            .with_context(|| format!("The element matching \"{}\" is not rendered", selectors))
+    }
+
+    /// Clicks the first element which matches the selector list, and returns what the browser must do next.
+    pub fn activate(&self, selectors: &str) -> Result<Activation> {
+        let node = DomNode::query_selector(&self.root, selectors)?
+            .with_context(|| format!("No element matches \"{}\"", selectors))?;
+        Ok(activate(&node))
+    }
+
+    /// Makes the element indicated by the fragment of the URL the target element, and returns whether it
+    /// is found.
+    pub fn set_target(&self, fragment: &str) -> bool {
+        set_target(&self.root, fragment)
+    }
+
+    /// Returns whether the `@media` rules which apply to the document or the lengths relative to the viewport
+    /// change when the environment changes from `old` to `new`, in which case the document needs to be
+    /// rendered again.
+    pub fn is_affected_by_media_change(
+        &self,
+        old: &MediaEnvironment,
+        new: &MediaEnvironment,
+    ) -> bool {
+        let is_resized =
+            (old.viewport_width, old.viewport_height) != (new.viewport_width, new.viewport_height);
+        (self.has_viewport_units && is_resized)
+            || self
+                .style_sheets
+                .iter()
+                .any(|style_sheet| style_sheet.is_affected_by_media_change(old, new))
+    }
+}
+
+/// Builds the render tree of the parsed document with its style sheets, which follow the UA style sheet. If
+/// `styles` are given, the styles kept in them are reused for the elements not invalidated since they are
+/// kept.
+fn build_render_tree(
+    doc_root: Rc<RefCell<DomNode>>,
+    style_sheets: Vec<StyleSheet>,
+    media: &MediaEnvironment,
     verbosity: VerbosityLevel,
-) -> Result<RenderObjects> {
-    let (doc_root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse()?;
+    styles: Option<&mut StyleCache>,
+) -> Result<RenderTree> {
     let style_sheets = std::iter::once(get_ua_style_sheet()?)
         .chain(style_sheets)
         .collect::<Vec<_>>();
+    let is_printed = matches!(verbosity, VerbosityLevel::Normal | VerbosityLevel::Verbose);
+    let mut document_tree = DocumentTree::build(doc_root)?;
+    if is_printed {
+        document_tree.print_in_chain(verbosity);
+    }
+    let mut render_tree = match styles {
+        Some(styles) => document_tree.restyle_render_tree(style_sheets, media, styles)?,
+        None => document_tree.to_render_tree(style_sheets, media)?,
+    };
+    if is_printed {
+        render_tree.print_in_chain(verbosity);
+    }
+    Ok(render_tree)
+}
 
+/// Renders the render tree of the parsed document, and returns the laid out box tree with the objects.
+#[tracing::instrument(skip_all)]
+fn render_document(
+    render_tree: RenderTree,
+    viewport_width: i32,
+    viewport_height: i32,
+    draw_ctx: &pango::Context,
+    verbosity: VerbosityLevel,
+) -> Result<(RenderObjects, BoxTree)> {
+    let metadata = PageMetadata::from_document(&render_tree.root.borrow().dom_node);
+    let mut box_tree = render_tree.to_box_tree(draw_ctx)?;
     match verbosity {
-        VerbosityLevel::Quiet => Ok(DocumentTree::build(doc_root)?
-            .to_render_tree(style_sheets)?
-            .to_box_tree(draw_ctx)?
+        VerbosityLevel::Quiet => box_tree
             .clean_up()?
-            .layout(viewport_width, viewport_height)?
-            .to_render_objects(viewport_width, viewport_height)),
-        VerbosityLevel::Normal | VerbosityLevel::Verbose => Ok(DocumentTree::build(doc_root)?
-            .print_in_chain(verbosity)
-            .to_render_tree(style_sheets)?
-            .print_in_chain(verbosity)
-            .to_box_tree(draw_ctx)?
+            .layout(viewport_width, viewport_height)?,
+        VerbosityLevel::Normal | VerbosityLevel::Verbose => box_tree
             .print_in_chain(verbosity)
             .clean_up()?
             .print_in_chain(verbosity)
             .layout(viewport_width, viewport_height)?
-            .print_in_chain(verbosity)
-            .to_render_objects(viewport_width, viewport_height)),
-    }
+            .print_in_chain(verbosity),
+    };
+    let objects = box_tree.to_render_objects(viewport_width, viewport_height);
+    Ok((
+        RenderObjects {
+            metadata,
+            ..objects
+        },
+        box_tree,
+    ))
 }
 
-/// Prints an HTML document as a box tree.
+/// Prints an HTML document as a box tree. The external style sheets are fetched relative to `document_url`,
+/// and the document is styled for the media after the elements matching `clicks` are clicked in order.
+#[tracing::instrument(skip_all)]
 pub fn print_box_tree(
     html: &str,
+    document_url: &str,
+    media: &MediaEnvironment,
+    clicks: &[String],
     draw_ctx: &pango::Context,
     verbosity: VerbosityLevel,
 ) -> Result<()> {
-    let (doc_root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse()?;
+    let (doc_root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html))
+        .set_resource_fetcher(
+            document_url,
+            Rc::new(DefaultFetcher::new(document_url, Cancellable::default())),
+        )
+        .set_media_environment(*media)
+        .parse()?;
+
+    for selectors in clicks {
+        let node = DomNode::query_selector(&doc_root, selectors)?
+            .with_context(|| format!("No element matches \"{}\"", selectors))?;
+        match activate(&node) {
+            Activation::FollowHyperlink(href) => {
+                eprintln!("The hyperlink to {} is not followed in CLI mode.", href);
+            }
+            Activation::SubmitForm(submission) => {
+                eprintln!(
+                    "The form to {} is not submitted in CLI mode.",
+                    submission.get_action()
+                );
+            }
+            Activation::None | Activation::Restyle => {}
+        }
+    }
 
     let style_sheets = std::iter::once(get_ua_style_sheet()?)
         .chain(style_sheets)
@@ -99,22 +940,22 @@ pub fn print_box_tree(
     match verbosity {
         VerbosityLevel::Quiet => {
             DocumentTree::build(doc_root)?
-                .to_render_tree(style_sheets)?
+                .to_render_tree(style_sheets, media)?
                 .to_box_tree(draw_ctx)?
                 .clean_up()?
-                .layout(DEFAULT_WINDOW_WIDTH, DEFAULT_WINDOW_HEIGHT)?
+                .layout(media.viewport_width, media.viewport_height)?
                 .print(verbosity);
         }
         VerbosityLevel::Normal | VerbosityLevel::Verbose => {
             DocumentTree::build(doc_root)?
                 .print_in_chain(verbosity)
-                .to_render_tree(style_sheets)?
+                .to_render_tree(style_sheets, media)?
                 .print_in_chain(verbosity)
                 .to_box_tree(draw_ctx)?
                 .print_in_chain(verbosity)
                 .clean_up()?
                 .print_in_chain(verbosity)
-                .layout(DEFAULT_WINDOW_WIDTH, DEFAULT_WINDOW_HEIGHT)?
+                .layout(media.viewport_width, media.viewport_height)?
                 .print(verbosity);
         }
     }
@@ -122,10 +963,240 @@ pub fn print_box_tree(
     Ok(())
 }
 
+/// Parses an HTML document, and renders it into the objects to paint in the viewport of the media. The
+/// external style sheets are fetched relative to `document_url`.
+pub fn render_page(
+    html: &str,
+    document_url: &str,
+    media: &MediaEnvironment,
+    draw_ctx: &pango::Context,
+) -> Result<RenderObjects> {
+    let (doc_root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html))
+        .set_resource_fetcher(
+            document_url,
+            Rc::new(DefaultFetcher::new(document_url, Cancellable::default())),
+        )
+        .set_media_environment(*media)
+        .parse()?;
+    ParsedDocument::new(doc_root, style_sheets)?.render(
+        media,
+        media.viewport_width,
+        media.viewport_height,
+        draw_ctx,
+        VerbosityLevel::Quiet,
+    )
+}
+
+/// Parses an HTML document with the `print` media type, and renders it for printing. The external style
+/// sheets are fetched relative to `document_url`.
+pub fn render_for_print(
+    html: &str,
+    document_url: &str,
+    media: &MediaEnvironment,
+    draw_ctx: &pango::Context,
+    verbosity: VerbosityLevel,
+) -> Result<(PageSettings, RenderObjects)> {
+    let media = MediaEnvironment {
+        media_type: MediaType::Print,
+        ..*media
+    };
+    let (doc_root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html))
+        .set_resource_fetcher(
+            document_url,
+            Rc::new(DefaultFetcher::new(document_url, Cancellable::default())),
+        )
+        .set_media_environment(media)
+        .parse()?;
+    ParsedDocument::new(doc_root, style_sheets)?.render_for_print(&media, draw_ctx, verbosity)
+}
+
 /// Prints a CSS document as a style sheet.
+#[tracing::instrument(skip_all)]
 pub fn print_style_sheet(css: &str) -> Result<()> {
     CssParser::new(&CssTokenizer::new(css).tokenize()?)
         .parse()?
         .print();
     Ok(())
 }
+
+/// Decodes the bytes of an HTML document into a string. The encoding is determined by the byte order mark,
+/// the `Content-Type` header with which the document was transferred, or the `meta` element declaring it.
+#[tracing::instrument(skip_all)]
+pub fn decode_html(bytes: &[u8], content_type: Option<&str>) -> String {
+    html::encoding::decode(bytes, content_type)
+}
+
+/// Parses an HTML document and returns the parse errors, including the ones in the style sheets of the document.
+#[tracing::instrument(skip_all)]
+pub fn get_html_parse_errors(html: &str) -> error::Result<Vec<ParseErrorReport>> {
+    let collector = Rc::new(RefCell::new(ParseErrorCollector::default()));
+    let mut tokenizer = HtmlTokenizer::new(html);
+    tokenizer.set_error_handler(Rc::clone(&collector) as _);
+    let result = HtmlParser::new(tokenizer).parse();
+    let errors = collector.borrow_mut().take_errors();
+    match result {
+        Ok(_) => Ok(errors),
+        Err(e) => Err(Error::html_parse(errors, e)),
+    }
+}
+
+/// Parses an HTML document and returns the metadata declared by the `link` and `meta` elements in its head,
+/// such as the icons, the canonical URL and the Open Graph properties.
+#[tracing::instrument(skip_all)]
+pub fn get_page_metadata(html: &str) -> error::Result<PageMetadata> {
+    let (document, _) = parse_html(html)?;
+    Ok(PageMetadata::from_document(&document))
+}
+
+/// Parses, styles and lays out an HTML document for the media, and returns the statistics of the stages.
+#[tracing::instrument(skip_all)]
+pub fn get_pipeline_stats(
+    html: &str,
+    media: &MediaEnvironment,
+    draw_ctx: &pango::Context,
+) -> Result<PipelineStats> {
+    let mut parser = HtmlParser::new(HtmlTokenizer::new(html));
+    parser.set_media_environment(*media);
+    let (document, style_sheets) = parser.parse()?;
+    let style_sheets = std::iter::once(get_ua_style_sheet()?)
+        .chain(style_sheets)
+        .collect::<Vec<_>>();
+    let mut stats =
+        PipelineStats::collect(&document, parser.get_token_counts(), &style_sheets, media)?;
+
+    take_layout_count();
+    DocumentTree::build(document)?
+        .to_render_tree(style_sheets, media)?
+        .to_box_tree(draw_ctx)?
+        .clean_up()?
+        .layout(media.viewport_width, media.viewport_height)?;
+    stats.box_layouts = take_layout_count();
+    Ok(stats)
+}
+
+/// Parses, styles and lays out an HTML document for the media, and returns the lines into which its texts are
+/// wrapped, grouped by the blocks they are in.
+#[tracing::instrument(skip_all)]
+pub fn get_line_dump(
+    html: &str,
+    media: &MediaEnvironment,
+    draw_ctx: &pango::Context,
+) -> Result<String> {
+    let mut parser = HtmlParser::new(HtmlTokenizer::new(html));
+    parser.set_media_environment(*media);
+    let (document, style_sheets) = parser.parse()?;
+    let style_sheets = std::iter::once(get_ua_style_sheet()?)
+        .chain(style_sheets)
+        .collect::<Vec<_>>();
+    let mut box_tree = DocumentTree::build(document)?
+        .to_render_tree(style_sheets, media)?
+        .to_box_tree(draw_ctx)?;
+    box_tree
+        .clean_up()?
+        .layout(media.viewport_width, media.viewport_height)?;
+    Ok(dump_lines(&box_tree))
+}
+
+/// Parses and styles an HTML document for the media, and returns the computed style of each rendered element
+/// in tree order, one per line, e.g. `p#intro.note { color: black; ... }`.
+#[tracing::instrument(skip_all)]
+pub fn get_computed_styles(html: &str, media: &MediaEnvironment) -> Result<Vec<String>> {
+    let mut parser = HtmlParser::new(HtmlTokenizer::new(html));
+    parser.set_media_environment(*media);
+    let (document, style_sheets) = parser.parse()?;
+    let style_sheets = std::iter::once(get_ua_style_sheet()?)
+        .chain(style_sheets)
+        .collect::<Vec<_>>();
+    Ok(DocumentTree::build(document)?
+        .to_render_tree(style_sheets, media)?
+        .get_element_styles()
+        .iter()
+        .map(|(node, style)| format!("{} {{ {} }}", get_element_label(node), style))
+        .collect())
+}
+
+/// Returns the tag name of the element followed by its ID and its classes like a compound selector.
+pub(crate) fn get_element_label(node: &Rc<RefCell<DomNode>>) -> String {
+    let NodeType::Element(elm) = &node.borrow().node_type else {
+        return String::new();
+    };
+    let mut label = elm.tag_name.clone();
+    if let Some(id) = elm.get_attribute("id").filter(|id| !id.is_empty()) {
+        label.push('#');
+        label.push_str(id);
+    }
+    for class in elm
+        .get_attribute("class")
+        .unwrap_or_default()
+        .split_whitespace()
+    {
+        label.push('.');
+        label.push_str(class);
+    }
+    label
+}
+
+/// Parses an HTML document and serializes the resulting DOM tree back into HTML text, which can be
+/// parsed again into the same tree.
+#[tracing::instrument(skip_all)]
+pub fn get_serialized_dom(html: &str) -> error::Result<String> {
+    let (document, _) = parse_html(html)?;
+    let tree = DocumentTree::build(document).map_err(|e| Error::html_parse(Vec::new(), e))?;
+    Ok(serialize_document(&tree))
+}
+
+/// Parses an HTML document line by line as if it arrived over the network, and returns the changes of its
+/// `aria-live` regions which are announced to screen readers while it is loaded.
+#[tracing::instrument(skip_all)]
+pub fn get_live_announcements(html: &str) -> Result<Vec<Announcement>> {
+    let log = AnnouncementLog::default();
+    let mut tracker = LiveRegionTracker::default();
+    let mut parser = HtmlParser::new(HtmlTokenizer::new_streaming());
+    for line in html.split_inclusive('\n') {
+        parser.feed(line.as_bytes())?;
+        tracker.update(&parser.get_document(), &log);
+    }
+    let (document, _) = parser.finish()?;
+    tracker.update(&document, &log);
+    Ok(log.take_announcements())
+}
+
+/// Parses an HTML document and returns the elements which match the selector list, serialized into HTML
+/// in tree order.
+#[tracing::instrument(skip_all)]
+pub fn query_selector_all(html: &str, selectors: &str) -> error::Result<Vec<String>> {
+    let (document, _) = parse_html(html)?;
+    Ok(DomNode::query_selector_all(&document, selectors)
+        .map_err(|e| Error::css_parse(Vec::new(), e))?
+        .iter()
+        .map(serialize_outer)
+        .collect())
+}
+
+/// Parses a CSS document and returns the parse errors.
+#[tracing::instrument(skip_all)]
+pub fn get_css_parse_errors(css: &str) -> error::Result<Vec<ParseErrorReport>> {
+    let collector = Rc::new(RefCell::new(ParseErrorCollector::default()));
+    let result = CssTokenizer::new(css)
+        .set_error_handler(Rc::clone(&collector) as _)
+        .tokenize_with_spans()
+        .and_then(|tokens| {
+            let (tokens, spans): (Vec<_>, Vec<_>) = tokens.into_iter().unzip();
+            CssParser::new(&tokens)
+                .set_error_handler(Rc::clone(&collector) as _)
+                .set_source_spans(spans)
+                .parse()
+        });
+    let errors = collector.borrow_mut().take_errors();
+    match result {
+        Ok(_) => Ok(errors),
+        Err(e) => Err(Error::css_parse(errors, e)),
+    }
+}
+
+/// Parses an HTML document for the public API, which reports the failure as an HTML parse error.
+fn parse_html(html: &str) -> error::Result<(Rc<RefCell<DomNode>>, Vec<StyleSheet>)> {
+    HtmlParser::new(HtmlTokenizer::new(html))
+        .parse()
+        .map_err(|e| Error::html_parse(Vec::new(), e))
+}
@@ -0,0 +1,101 @@
+//! The entry points into the internals for the benchmarks in `benches`, which are not a part of the API and
+//! change with the internals.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use anyhow::Result;
+
+use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::css::get_ua_style_sheet;
+use crate::renderer::css::media::MediaEnvironment;
+use crate::renderer::css::selector::Selector;
+use crate::renderer::html::dom::{DocumentTree, DomNode, NodeType};
+use crate::renderer::html::parser::HtmlParser;
+use crate::renderer::html::token::HtmlTokenizer;
+
+/// A parsed document with its style sheets, which follow the UA style sheet.
+pub struct BenchDocument {
+    root: Rc<RefCell<DomNode>>,
+    style_sheets: Vec<StyleSheet>,
+}
+
+impl BenchDocument {
+    pub fn parse(html: &str) -> Result<Self> {
+        let (root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse()?;
+        let style_sheets = std::iter::once(get_ua_style_sheet()?)
+            .chain(style_sheets)
+            .collect();
+        Ok(Self { root, style_sheets })
+    }
+
+    /// Returns the selector matching of all the selectors of the style rules which apply to the default
+    /// environment against all the elements.
+    pub fn selector_matching(&self) -> SelectorMatching {
+        let elements = DomNode::get_descendants(&self.root)
+            .filter(|node| matches!(node.borrow().node_type, NodeType::Element(_)))
+            .collect();
+        let selectors = self
+            .style_sheets
+            .iter()
+            .flat_map(|style_sheet| style_sheet.get_style_rules(&MediaEnvironment::default()))
+            .flat_map(|rule| rule.selectors.iter().cloned())
+            .collect();
+        SelectorMatching {
+            elements,
+            selectors,
+        }
+    }
+
+    /// Computes the styles of all the nodes from scratch in the default environment, as the document is
+    /// rendered, and returns the number of the render nodes.
+    pub fn resolve_styles(&self) -> Result<usize> {
+        let render_tree = DocumentTree::build(Rc::clone(&self.root))?
+            .to_render_tree(self.style_sheets.clone(), &MediaEnvironment::default())?;
+        let mut count = 0;
+        let mut stack = vec![render_tree.root];
+        while let Some(node) = stack.pop() {
+            count += 1;
+            stack.extend(node.borrow().children.iter().cloned());
+        }
+        Ok(count)
+    }
+}
+
+pub struct SelectorMatching {
+    elements: Vec<Rc<RefCell<DomNode>>>,
+    selectors: Vec<Selector>,
+}
+
+impl SelectorMatching {
+    /// Matches every selector against every element with `Selector::matches`, and returns the number of the
+    /// matches.
+    pub fn run(&self) -> usize {
+        self.selectors
+            .iter()
+            .map(|selector| {
+                self.elements
+                    .iter()
+                    .filter(|node| selector.matches(node))
+                    .count()
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_fixtures() {
+        for html in [
+            include_str!("../../benches/fixtures/article.html"),
+            include_str!("../../benches/fixtures/listing.html"),
+        ] {
+            let document = BenchDocument::parse(html).unwrap();
+            assert!(document.selector_matching().run() > 0);
+            assert!(document.resolve_styles().unwrap() > 100);
+        }
+    }
+}
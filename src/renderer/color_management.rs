@@ -0,0 +1,521 @@
+use anyhow::{bail, ensure, Context, Result};
+
+/// A 3x3 matrix in row-major order.
+type Matrix = [[f64; 3]; 3];
+
+/// Converts the linear-light sRGB values to XYZ with the D65 white point.
+/// https://www.w3.org/TR/css-color-4/#color-conversion-code
+const SRGB_TO_XYZ: Matrix = [
+    [0.41239079926595934, 0.357584339383878, 0.1804807884018343],
+    [0.21263900587151027, 0.715168678767756, 0.07219231536073371],
+    [0.01933081871559182, 0.11919477979462598, 0.9505321522496607],
+];
+
+/// Converts the linear-light Display P3 values to XYZ with the D65 white point.
+const DISPLAY_P3_TO_XYZ: Matrix = [
+    [0.4865709486482162, 0.26566769316909306, 0.1982172852343625],
+    [0.2289745640697488, 0.6917385218365064, 0.079286914093745],
+    [0.0, 0.04511338185890264, 1.043944368900976],
+];
+
+/// The Bradford chromatic adaptation from the D50 white point of the ICC profile connection space to D65.
+const D50_TO_D65: Matrix = [
+    [
+        0.9554734527042182,
+        -0.023098536874261423,
+        0.0632593086610217,
+    ],
+    [
+        -0.028369706963208136,
+        1.0099954580058226,
+        0.021041398966943008,
+    ],
+    [
+        0.012314001688319899,
+        -0.020507696433477912,
+        1.3303659366080753,
+    ],
+];
+
+fn multiply(m: &Matrix, n: &Matrix) -> Matrix {
+    let mut product = [[0.0; 3]; 3];
+    for (i, row) in product.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| m[i][k] * n[k][j]).sum();
+        }
+    }
+    product
+}
+
+fn apply(m: &Matrix, v: [f64; 3]) -> [f64; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+fn invert(m: &Matrix) -> Result<Matrix> {
+    let [[a, b, c], [d, e, f], [g, h, i]] = *m;
+    let det = a * (e * i - f * h) - b * (d * i - f * g) + c * (d * h - e * g);
+    ensure!(
+        det.abs() > f64::EPSILON,
+        "The color space matrix is not invertible."
+    );
+    Ok([
+        [
+            (e * i - f * h) / det,
+            (c * h - b * i) / det,
+            (b * f - c * e) / det,
+        ],
+        [
+            (f * g - d * i) / det,
+            (a * i - c * g) / det,
+            (c * d - a * f) / det,
+        ],
+        [
+            (d * h - e * g) / det,
+            (b * g - a * h) / det,
+            (a * e - b * d) / det,
+        ],
+    ])
+}
+
+/// The curve which converts the encoded values of a channel to linear light.
+/// https://www.color.org/specification/ICC.1-2022-05.pdf (10.18 parametricCurveType)
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransferFunction {
+    /// Y = (aX + b)^g + e if X >= d, otherwise Y = cX + f.
+    Parametric {
+        g: f64,
+        a: f64,
+        b: f64,
+        c: f64,
+        d: f64,
+        e: f64,
+        f: f64,
+    },
+    /// The values sampled at the evenly spaced points in [0, 1], which are interpolated linearly.
+    Table(Vec<f64>),
+}
+
+impl TransferFunction {
+    pub const SRGB: Self = Self::Parametric {
+        g: 2.4,
+        a: 1.0 / 1.055,
+        b: 0.055 / 1.055,
+        c: 1.0 / 12.92,
+        d: 0.04045,
+        e: 0.0,
+        f: 0.0,
+    };
+
+    pub const LINEAR: Self = Self::gamma(1.0);
+
+    const fn gamma(g: f64) -> Self {
+        Self::Parametric {
+            g,
+            a: 1.0,
+            b: 0.0,
+            c: 0.0,
+            d: 0.0,
+            e: 0.0,
+            f: 0.0,
+        }
+    }
+
+    pub fn to_linear(&self, x: f64) -> f64 {
+        match self {
+            Self::Parametric {
+                g,
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+            } => {
+                if x >= *d {
+                    (a * x + b).max(0.0).powf(*g) + e
+                } else {
+                    c * x + f
+                }
+            }
+            Self::Table(table) => {
+                let position = x.clamp(0.0, 1.0) * (table.len() - 1) as f64;
+                let i = (position.floor() as usize).min(table.len() - 2);
+                table[i] + (table[i + 1] - table[i]) * (position - i as f64)
+            }
+        }
+    }
+
+    pub fn to_encoded(&self, y: f64) -> f64 {
+        match self {
+            Self::Parametric {
+                g,
+                a,
+                b,
+                c,
+                d,
+                e,
+                f,
+            } => {
+                if y >= c * d + f && *a != 0.0 {
+                    ((y - e).max(0.0).powf(1.0 / g) - b) / a
+                } else if *c != 0.0 {
+                    (y - f) / c
+                } else {
+                    0.0
+                }
+            }
+            // The tables of the curves are monotonically increasing.
+            Self::Table(table) => {
+                let i = table.partition_point(|v| *v < y).clamp(1, table.len() - 1);
+                let (low, high) = (table[i - 1], table[i]);
+                let t = if high > low {
+                    ((y - low) / (high - low)).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                (i - 1) as f64 / (table.len() - 1) as f64 + t / (table.len() - 1) as f64
+            }
+        }
+    }
+}
+
+/// An RGB color space, defined by its primaries and the transfer functions of its channels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbColorSpace {
+    /// Converts the linear-light values to XYZ with the D65 white point.
+    to_xyz: Matrix,
+    transfer_functions: [TransferFunction; 3],
+}
+
+impl RgbColorSpace {
+    /// https://www.w3.org/TR/css-color-4/#predefined-sRGB
+    pub const SRGB: Self = Self {
+        to_xyz: SRGB_TO_XYZ,
+        transfer_functions: [
+            TransferFunction::SRGB,
+            TransferFunction::SRGB,
+            TransferFunction::SRGB,
+        ],
+    };
+
+    /// https://www.w3.org/TR/css-color-4/#predefined-sRGB-linear
+    pub const SRGB_LINEAR: Self = Self {
+        to_xyz: SRGB_TO_XYZ,
+        transfer_functions: [
+            TransferFunction::LINEAR,
+            TransferFunction::LINEAR,
+            TransferFunction::LINEAR,
+        ],
+    };
+
+    /// https://www.w3.org/TR/css-color-4/#predefined-display-p3
+    pub const DISPLAY_P3: Self = Self {
+        to_xyz: DISPLAY_P3_TO_XYZ,
+        transfer_functions: [
+            TransferFunction::SRGB,
+            TransferFunction::SRGB,
+            TransferFunction::SRGB,
+        ],
+    };
+
+    /// The color space in which the CSS colors are painted, while the images are converted to the color
+    /// space of the monitor when they're painted.
+    /// todo: Convert the CSS colors to the color space of the monitor too.
+    pub const OUTPUT: Self = Self::SRGB;
+
+    /// Returns the predefined color space which can be used in the CSS `color()` function.
+    /// https://www.w3.org/TR/css-color-4/#predefined
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "srgb" => Some(Self::SRGB),
+            "srgb-linear" => Some(Self::SRGB_LINEAR),
+            "display-p3" => Some(Self::DISPLAY_P3),
+            _ => None,
+        }
+    }
+
+    /// Reads the color space of an ICC profile embedded in an image. Only the RGB profiles with the
+    /// matrix/TRC model are supported, which most of the profiles of photos, such as Display P3 and
+    /// Adobe RGB, are.
+    /// todo: Support the profiles with lookup tables and the rendering intents.
+    /// https://www.color.org/specification/ICC.1-2022-05.pdf
+    pub fn from_icc_profile(profile: &[u8]) -> Result<Self> {
+        ensure!(
+            profile.get(36..40) == Some(b"acsp"),
+            "The data is not an ICC profile."
+        );
+        ensure!(
+            &profile[16..20] == b"RGB ",
+            "Only RGB ICC profiles are supported."
+        );
+        ensure!(
+            &profile[20..24] == b"XYZ ",
+            "Only ICC profiles whose connection space is XYZ are supported."
+        );
+
+        let tag_count = read_u32(profile, 128)? as usize;
+        let find_tag = |signature: &[u8; 4]| -> Result<&[u8]> {
+            for i in 0..tag_count {
+                let entry = 132 + i * 12;
+                if profile.get(entry..entry + 4) == Some(signature) {
+                    let offset = read_u32(profile, entry + 4)? as usize;
+                    let size = read_u32(profile, entry + 8)? as usize;
+                    return profile
+                        .get(offset..offset + size)
+                        .context("The ICC profile is truncated.");
+                }
+            }
+            bail!(
+                "The ICC profile has no {} tag.",
+                String::from_utf8_lossy(signature)
+            );
+        };
+
+        // The colorants are the columns of the matrix which converts the linear values to XYZ.
+        let mut to_xyz_d50 = [[0.0; 3]; 3];
+        for (column, signature) in [b"rXYZ", b"gXYZ", b"bXYZ"].into_iter().enumerate() {
+            let xyz = read_xyz(find_tag(signature)?)?;
+            for (row, value) in xyz.into_iter().enumerate() {
+                to_xyz_d50[row][column] = value;
+            }
+        }
+        let [r, g, b] =
+            [b"rTRC", b"gTRC", b"bTRC"].map(|signature| find_tag(signature).and_then(read_curve));
+
+        Ok(Self {
+            to_xyz: multiply(&D50_TO_D65, &to_xyz_d50),
+            transfer_functions: [r?, g?, b?],
+        })
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .context("The ICC profile is truncated.")?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .context("The ICC profile is truncated.")?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Reads an s15Fixed16Number.
+fn read_fixed(data: &[u8], offset: usize) -> Result<f64> {
+    Ok(read_u32(data, offset)? as i32 as f64 / 65536.0)
+}
+
+/// https://www.color.org/specification/ICC.1-2022-05.pdf (10.31 XYZType)
+fn read_xyz(tag: &[u8]) -> Result<[f64; 3]> {
+    ensure!(tag.starts_with(b"XYZ "), "Invalid XYZ tag.");
+    Ok([
+        read_fixed(tag, 8)?,
+        read_fixed(tag, 12)?,
+        read_fixed(tag, 16)?,
+    ])
+}
+
+/// https://www.color.org/specification/ICC.1-2022-05.pdf (10.6 curveType, 10.18 parametricCurveType)
+fn read_curve(tag: &[u8]) -> Result<TransferFunction> {
+    if tag.starts_with(b"curv") {
+        let count = read_u32(tag, 8)? as usize;
+        return Ok(match count {
+            0 => TransferFunction::LINEAR,
+            // The gamma is a u8Fixed8Number.
+            1 => TransferFunction::gamma(read_u16(tag, 12)? as f64 / 256.0),
+            _ => TransferFunction::Table(
+                (0..count)
+                    .map(|i| Ok(read_u16(tag, 12 + i * 2)? as f64 / 65535.0))
+                    .collect::<Result<_>>()?,
+            ),
+        });
+    }
+
+    ensure!(tag.starts_with(b"para"), "Invalid curve tag.");
+    let function_type = read_u16(tag, 8)?;
+    let param_count = match function_type {
+        0 => 1,
+        1 => 3,
+        2 => 4,
+        3 => 5,
+        4 => 7,
+        _ => bail!("Invalid parametric curve type: {}", function_type),
+    };
+    let params = (0..param_count)
+        .map(|i| read_fixed(tag, 12 + i * 4))
+        .collect::<Result<Vec<_>>>()?;
+    // All the types are the special cases of the type 4.
+    let [g, a, b, c, d, e, f] = match params[..] {
+        [g] => [g, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        [g, a, b] => [g, a, b, 0.0, -b / a, 0.0, 0.0],
+        [g, a, b, c] => [g, a, b, 0.0, -b / a, c, c],
+        [g, a, b, c, d] => [g, a, b, c, d, 0.0, 0.0],
+        [g, a, b, c, d, e, f] => [g, a, b, c, d, e, f],
+        _ => unreachable!(),
+    };
+    Ok(TransferFunction::Parametric {
+        g,
+        a,
+        b,
+        c,
+        d,
+        e,
+        f,
+    })
+}
+
+/// Converts the colors in a color space to another. The colors out of the gamut of the destination are
+/// clipped.
+/// todo: Map the colors to the gamut by reducing the chroma in OKLCh.
+/// https://www.w3.org/TR/css-color-4/#gamut-mapping
+#[derive(Debug)]
+pub struct ColorTransform {
+    source: RgbColorSpace,
+    destination: RgbColorSpace,
+    /// Converts the linear values in the source to the linear values in the destination.
+    matrix: Matrix,
+}
+
+impl ColorTransform {
+    pub fn new(source: &RgbColorSpace, destination: &RgbColorSpace) -> Result<Self> {
+        Ok(Self {
+            source: source.clone(),
+            destination: destination.clone(),
+            matrix: multiply(&invert(&destination.to_xyz)?, &source.to_xyz),
+        })
+    }
+
+    /// Converts the color whose channels are in [0, 1].
+    pub fn convert(&self, rgb: [f64; 3]) -> [f64; 3] {
+        let linear = apply(
+            &self.matrix,
+            [0, 1, 2].map(|i| self.source.transfer_functions[i].to_linear(rgb[i])),
+        );
+        [0, 1, 2].map(|i| {
+            self.destination.transfer_functions[i]
+                .to_encoded(linear[i].clamp(0.0, 1.0))
+                .clamp(0.0, 1.0)
+        })
+    }
+
+    /// Converts the 8-bit RGB(A) pixels in place. The alpha channel, if any, is kept as is.
+    pub fn transform_pixels(
+        &self,
+        pixels: &mut [u8],
+        width: usize,
+        n_channels: usize,
+        rowstride: usize,
+    ) {
+        if self.source == self.destination {
+            return;
+        }
+        // The transfer functions are applied through lookup tables, since they are slow to evaluate.
+        const OUTPUT_STEPS: usize = 4096;
+        let to_linear = [0, 1, 2].map(|i| {
+            (0..=255)
+                .map(|v| self.source.transfer_functions[i].to_linear(v as f64 / 255.0))
+                .collect::<Vec<_>>()
+        });
+        let from_linear = [0, 1, 2].map(|i| {
+            (0..=OUTPUT_STEPS)
+                .map(|v| {
+                    let encoded = self.destination.transfer_functions[i]
+                        .to_encoded(v as f64 / OUTPUT_STEPS as f64);
+                    (encoded.clamp(0.0, 1.0) * 255.0).round() as u8
+                })
+                .collect::<Vec<_>>()
+        });
+
+        for row in pixels.chunks_mut(rowstride) {
+            for pixel in row.chunks_exact_mut(n_channels).take(width) {
+                let linear = apply(
+                    &self.matrix,
+                    [0, 1, 2].map(|i| to_linear[i][pixel[i] as usize]),
+                );
+                for i in 0..3 {
+                    let step = (linear[i].clamp(0.0, 1.0) * OUTPUT_STEPS as f64).round();
+                    pixel[i] = from_linear[i][step as usize];
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an ICC profile with the sRGB primaries and the curve tag used for all the channels.
+    fn build_icc_profile(curve: &[u8]) -> Vec<u8> {
+        let fixed = |v: f64| ((v * 65536.0).round() as i32).to_be_bytes();
+        let xyz = |x: f64, y: f64, z: f64| {
+            [b"XYZ \0\0\0\0".as_slice(), &fixed(x), &fixed(y), &fixed(z)].concat()
+        };
+        let tags = [
+            (b"rXYZ", xyz(0.4361, 0.2225, 0.0139)),
+            (b"gXYZ", xyz(0.3851, 0.7169, 0.0971)),
+            (b"bXYZ", xyz(0.1431, 0.0606, 0.7141)),
+            (b"rTRC", curve.to_vec()),
+            (b"gTRC", curve.to_vec()),
+            (b"bTRC", curve.to_vec()),
+        ];
+
+        let mut profile = vec![0; 128];
+        profile[16..20].copy_from_slice(b"RGB ");
+        profile[20..24].copy_from_slice(b"XYZ ");
+        profile[36..40].copy_from_slice(b"acsp");
+        profile.extend((tags.len() as u32).to_be_bytes());
+        let mut offset = 132 + tags.len() * 12;
+        let mut data = Vec::<u8>::new();
+        for (signature, tag) in &tags {
+            profile.extend(signature.as_slice());
+            profile.extend((offset as u32).to_be_bytes());
+            profile.extend((tag.len() as u32).to_be_bytes());
+            offset += tag.len();
+            data.extend(tag);
+        }
+        profile.extend(data);
+        profile
+    }
+
+    #[test]
+    fn transform_colors() {
+        let p3_to_srgb =
+            ColorTransform::new(&RgbColorSpace::DISPLAY_P3, &RgbColorSpace::SRGB).unwrap();
+        let to_u8 = |rgb: [f64; 3]| rgb.map(|v| (v * 255.0).round() as u8);
+        // The white point is the same, and the most saturated red of Display P3 is clipped.
+        assert_eq!(to_u8(p3_to_srgb.convert([0.5, 0.5, 0.5])), [128, 128, 128]);
+        assert_eq!(to_u8(p3_to_srgb.convert([1.0, 0.0, 0.0])), [255, 0, 0]);
+        assert_eq!(to_u8(p3_to_srgb.convert([0.8, 0.4, 0.3])), [219, 94, 68]);
+
+        // The sRGB curve (parametric type 3).
+        let para = [
+            b"para\0\0\0\0\0\x03\0\0".as_slice(),
+            &[2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045]
+                .map(|v: f64| ((v * 65536.0).round() as i32).to_be_bytes())
+                .concat(),
+        ]
+        .concat();
+        let srgb = RgbColorSpace::from_icc_profile(&build_icc_profile(&para)).unwrap();
+        let transform = ColorTransform::new(&srgb, &RgbColorSpace::SRGB).unwrap();
+        let mut pixels = vec![10, 128, 250, 255, 0, 64, 200, 128];
+        transform.transform_pixels(&mut pixels, 2, 4, 8);
+        assert_eq!(pixels, [10, 128, 250, 255, 0, 64, 200, 128]);
+
+        // The gamma of 1.0 (curve with a single entry), whose midtones are darker in the linear encoding.
+        let linear =
+            RgbColorSpace::from_icc_profile(&build_icc_profile(b"curv\0\0\0\0\0\0\0\x01\x01\x00"))
+                .unwrap();
+        let transform = ColorTransform::new(&linear, &RgbColorSpace::SRGB).unwrap();
+        // The padding at the end of the row is not touched.
+        let mut pixels = vec![128, 128, 128, 7];
+        transform.transform_pixels(&mut pixels, 1, 3, 4);
+        assert_eq!(pixels, [188, 188, 188, 7]);
+
+        assert!(RgbColorSpace::from_icc_profile(&[0; 64]).is_err());
+        assert!(RgbColorSpace::from_icc_profile(&build_icc_profile(b"curv")).is_err());
+    }
+}
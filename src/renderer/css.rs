@@ -1,18 +1,24 @@
 pub mod cssom;
+pub mod loader;
+pub mod media;
+pub mod page;
 pub mod parser;
 pub mod selector;
 pub mod token;
 
 use anyhow::Result;
 
-use cssom::StyleSheet;
+use cssom::{CascadeOrigin, StyleSheet};
 use parser::CssParser;
 use token::CssTokenizer;
 
 const UA_CSS_PATH: &str = "src/renderer/style/ua.css";
 
 /// Returns the user agent style sheet.
+#[tracing::instrument(skip_all)]
 pub fn get_ua_style_sheet() -> Result<StyleSheet> {
     let css = std::fs::read_to_string(UA_CSS_PATH)?;
-    CssParser::new(&CssTokenizer::new(&css).tokenize()?).parse()
+    let mut style_sheet = CssParser::new(&CssTokenizer::new(&css).tokenize()?).parse()?;
+    style_sheet.origin = CascadeOrigin::UserAgent;
+    Ok(style_sheet)
 }
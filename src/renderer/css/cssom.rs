@@ -1,62 +1,200 @@
 use std::cell::RefCell;
+use std::fmt;
 use std::rc::Rc;
 
+use crate::renderer::css::media::{MediaEnvironment, MediaQueryEvaluator};
 use crate::renderer::css::selector::Selector;
-use crate::renderer::css::token::CssToken;
+use crate::renderer::css::token::{CssToken, NumericType};
 use crate::renderer::html::dom::DomNode;
+use crate::renderer::source::SourceSpan;
 
 /// https://www.w3.org/TR/cssom-1/#cssstylesheet
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct StyleSheet {
     pub rules: Vec<Rule>,
+    pub origin: CascadeOrigin,
 }
 
 impl StyleSheet {
+    /// Creates an author style sheet.
     pub fn new(rules: Vec<Rule>) -> Self {
-        Self { rules }
+        Self {
+            rules,
+            origin: CascadeOrigin::Author,
+        }
     }
 
     pub fn print(&self) {
         println!("{:#?}", self);
     }
+
+    /// Returns the style rules in the order of appearance, including the ones in the `@media` rules and the
+    /// imported style sheets whose conditions match the environment. The rules of an imported style sheet
+    /// precede the following rules of the importing one in the cascade.
+    /// https://drafts.csswg.org/css-cascade-5/#at-import
+    pub fn get_style_rules(&self, media: &MediaEnvironment) -> Vec<&StyleRule> {
+        let evaluator = MediaQueryEvaluator::new(media);
+        let mut style_rules = Vec::new();
+        collect_rules(
+            &self.rules,
+            &|media| evaluator.matches(media),
+            &mut |rule| {
+                if let Rule::QualifiedRule(rule) = rule {
+                    style_rules.push(rule);
+                }
+            },
+        );
+        style_rules
+    }
+
+    /// Returns the `@page` rules in the order of appearance, including the ones in the `@media` rules and
+    /// the imported style sheets whose conditions match the environment.
+    /// https://drafts.csswg.org/css-page-3/#at-page-rule
+    pub fn get_page_rules(&self, media: &MediaEnvironment) -> Vec<&PageRule> {
+        let evaluator = MediaQueryEvaluator::new(media);
+        let mut page_rules = Vec::new();
+        collect_rules(
+            &self.rules,
+            &|media| evaluator.matches(media),
+            &mut |rule| {
+                if let Rule::PageRule(rule) = rule {
+                    page_rules.push(rule);
+                }
+            },
+        );
+        page_rules
+    }
+
+    /// Returns the style rules in the order of appearance, including the ones in all the `@media` rules and
+    /// the imported style sheets.
+    pub fn get_all_style_rules(&self) -> Vec<&StyleRule> {
+        let mut style_rules = Vec::new();
+        collect_rules(&self.rules, &|_| true, &mut |rule| {
+            if let Rule::QualifiedRule(rule) = rule {
+                style_rules.push(rule);
+            }
+        });
+        style_rules
+    }
+
+    /// Returns whether any declaration of the style rules has a length relative to the viewport, whose
+    /// computed value changes when the viewport is resized.
+    pub fn has_viewport_units(&self) -> bool {
+        self.get_all_style_rules().iter().any(|rule| {
+            rule.declarations.iter().any(|declaration| {
+                declaration
+                    .value
+                    .iter()
+                    .any(ComponentValue::has_viewport_units)
+            })
+        })
+    }
+
+    /// Returns whether the change of the environment changes the `@media` and `@import` rules whose
+    /// conditions match, in which case the style rules that apply change.
+    pub fn is_affected_by_media_change(
+        &self,
+        old: &MediaEnvironment,
+        new: &MediaEnvironment,
+    ) -> bool {
+        fn is_affected(
+            rules: &[Rule],
+            old: &MediaQueryEvaluator,
+            new: &MediaQueryEvaluator,
+        ) -> bool {
+            rules.iter().any(|rule| match rule {
+                Rule::MediaRule(rule) => {
+                    old.matches(&rule.media) != new.matches(&rule.media)
+                        || is_affected(&rule.rules, old, new)
+                }
+                Rule::ImportRule(rule) => {
+                    old.matches(&rule.media) != new.matches(&rule.media)
+                        || rule
+                            .style_sheet
+                            .as_ref()
+                            .is_some_and(|style_sheet| is_affected(&style_sheet.rules, old, new))
+                }
+                _ => false,
+            })
+        }
+        is_affected(
+            &self.rules,
+            &MediaQueryEvaluator::new(old),
+            &MediaQueryEvaluator::new(new),
+        )
+    }
+}
+
+/// Calls `f` with the rules other than the `@media` and `@import` rules in the order of appearance, including
+/// the ones in the `@media` rules and the imported style sheets whose media query lists pass the filter.
+fn collect_rules<'a>(
+    rules: &'a [Rule],
+    filter: &dyn Fn(&str) -> bool,
+    f: &mut dyn FnMut(&'a Rule),
+) {
+    for rule in rules {
+        match rule {
+            Rule::MediaRule(rule) => {
+                if filter(&rule.media) {
+                    collect_rules(&rule.rules, filter, f)
+                }
+            }
+            Rule::ImportRule(ImportRule {
+                media, style_sheet, ..
+            }) => {
+                if let Some(style_sheet) = style_sheet.as_ref().filter(|_| filter(media)) {
+                    collect_rules(&style_sheet.rules, filter, f)
+                }
+            }
+            _ => f(rule),
+        }
+    }
 }
 
 /// A CSS document is a series of style rules and at-rules.
 /// - https://www.w3.org/TR/css-syntax-3/#syntax-description
 /// - https://www.w3.org/TR/cssom-1/#cssrule
-#[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Rule {
     QualifiedRule(QualifiedRule),
+    ImportRule(ImportRule),
+    MediaRule(MediaRule),
+    PageRule(PageRule),
     AtRule(AtRule),
 }
 
-impl Rule {
-    pub fn get_matched_selectors(&self, dom_node: Rc<RefCell<DomNode>>) -> Option<Vec<Selector>> {
-        match self {
-            Rule::QualifiedRule(rule) => rule.get_matched_selectors(dom_node),
-            _ => None,
-        }
-    }
-}
-
 /// https://www.w3.org/TR/css-syntax-3/#qualified-rule
 pub type QualifiedRule = StyleRule;
 
 /// - https://www.w3.org/TR/css-syntax-3/#style-rules
 /// - https://www.w3.org/TR/cssom-1/#the-cssstylerule-interface
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct StyleRule {
     pub selectors: Vec<Selector>,
     pub declarations: Vec<Declaration>,
+    /// The span of the source text of the rule, from the start of the prelude to the end of the block.
+    /// This is `None` if the parser is not given the spans of the tokens.
+    pub source_span: Option<SourceSpan>,
 }
 
 impl StyleRule {
-    pub fn get_matched_selectors(&self, dom_node: Rc<RefCell<DomNode>>) -> Option<Vec<Selector>> {
+    /// Returns the selectors of the rule which match the node, or its pseudo-element if `pseudo_element` is
+    /// given.
+    pub fn get_matched_selectors(
+        &self,
+        node: &Rc<RefCell<DomNode>>,
+        pseudo_element: Option<&str>,
+    ) -> Option<Vec<Selector>> {
         // The matched selectors can be multiple, separated by commas.
         let mut matched_selectors = Vec::new();
 
         for selector in &self.selectors {
-            if selector.matches(&dom_node) {
+            let is_matched = match pseudo_element {
+                Some(name) => selector.matches_pseudo_element(node, name),
+                None => selector.matches(node),
+            };
+            if is_matched {
                 matched_selectors.push(selector.clone());
             }
         }
@@ -68,12 +206,43 @@ impl StyleRule {
     }
 }
 
+/// The origin of a style sheet, which takes precedence over the specificity in the cascade. The origins are
+/// ordered from the one of the user agent, which `revert` rolls back to last.
+/// https://drafts.csswg.org/css-cascade-5/#cascading-origins
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CascadeOrigin {
+    UserAgent,
+    /// todo: Load the user style sheets.
+    User,
+    Author,
+}
+
+impl CascadeOrigin {
+    /// Returns the precedence of the declarations of the origin with the importance, where the greater one wins.
+    /// The order of the important declarations is the reverse of the normal ones.
+    /// https://drafts.csswg.org/css-cascade-5/#cascade-origin
+    pub fn get_precedence(&self, important: bool) -> u8 {
+        match (self, important) {
+            (CascadeOrigin::UserAgent, false) => 0,
+            (CascadeOrigin::User, false) => 1,
+            (CascadeOrigin::Author, false) => 2,
+            (CascadeOrigin::Author, true) => 3,
+            (CascadeOrigin::User, true) => 4,
+            (CascadeOrigin::UserAgent, true) => 5,
+        }
+    }
+}
+
 /// - https://www.w3.org/TR/css-syntax-3/#declaration
 /// - https://www.w3.org/TR/cssom-1/#css-declarations
 #[derive(Clone, Debug, PartialEq)]
 pub struct Declaration {
     pub name: String,
     pub value: Vec<ComponentValue>,
+    /// Whether the declaration is followed by `!important`.
+    /// https://drafts.csswg.org/css-cascade-5/#importance
+    pub important: bool,
 }
 
 /// https://www.w3.org/TR/css-syntax-3/#component-value
@@ -90,8 +259,110 @@ pub enum ComponentValue {
     },
 }
 
+/// Serializes the component values in the way close to the source text, e.g. to get the text of the prelude of
+/// an at-rule.
+/// https://www.w3.org/TR/cssom-1/#serialize-a-css-component-value
+impl fmt::Display for ComponentValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let number = |n: &NumericType| match n {
+            NumericType::Integer(i) => i.to_string(),
+            NumericType::Number(n) => n.to_string(),
+        };
+        match self {
+            ComponentValue::PreservedToken(token) => match token {
+                CssToken::Ident(s) => write!(f, "{}", s),
+                CssToken::AtKeyword(s) => write!(f, "@{}", s),
+                CssToken::Hash(s, _) => write!(f, "#{}", s),
+                CssToken::String(s) => write!(f, "{:?}", s),
+                CssToken::Url(s) => write!(f, "url({})", s),
+                CssToken::Delim(c) => write!(f, "{}", c),
+                CssToken::Number(n) => write!(f, "{}", number(n)),
+                CssToken::Percentage(n) => write!(f, "{}%", n),
+                CssToken::Dimension(n, unit) => write!(f, "{}{}", number(n), unit),
+                CssToken::Whitespace => write!(f, " "),
+                CssToken::Colon => write!(f, ":"),
+                CssToken::Semicolon => write!(f, ";"),
+                CssToken::Comma => write!(f, ","),
+                _ => Ok(()),
+            },
+            ComponentValue::Function { name, values } => {
+                write!(f, "{}(", name)?;
+                values.iter().try_for_each(|v| write!(f, "{}", v))?;
+                write!(f, ")")
+            }
+            ComponentValue::SimpleBlock {
+                associated_token,
+                values,
+            } => {
+                let (open, close) = match associated_token {
+                    CssToken::OpenParenthesis => ("(", ")"),
+                    CssToken::OpenSquareBracket => ("[", "]"),
+                    _ => ("{", "}"),
+                };
+                write!(f, "{}", open)?;
+                values.iter().try_for_each(|v| write!(f, "{}", v))?;
+                write!(f, "{}", close)
+            }
+        }
+    }
+}
+
+impl ComponentValue {
+    /// Returns whether the value is or contains a dimension in `vw`, `vh`, `vmin` or `vmax`.
+    /// https://drafts.csswg.org/css-values-4/#viewport-relative-lengths
+    pub fn has_viewport_units(&self) -> bool {
+        match self {
+            ComponentValue::PreservedToken(CssToken::Dimension(_, unit)) => {
+                ["vw", "vh", "vmin", "vmax"]
+                    .iter()
+                    .any(|u| unit.eq_ignore_ascii_case(u))
+            }
+            ComponentValue::PreservedToken(_) => false,
+            ComponentValue::Function { values, .. }
+            | ComponentValue::SimpleBlock { values, .. } => {
+                values.iter().any(ComponentValue::has_viewport_units)
+            }
+        }
+    }
+}
+
+/// An `@media` rule, whose rules apply only if the media query list matches the environment.
+/// - https://drafts.csswg.org/css-conditional-3/#at-media
+/// - https://drafts.csswg.org/css-conditional-3/#the-cssmediarule-interface
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaRule {
+    /// The serialized media query list in the prelude.
+    pub media: String,
+    pub rules: Vec<Rule>,
+}
+
+/// An `@import` rule, whose style sheet applies only if the media query list matches the environment.
+/// todo: Support the `layer` and `supports()` conditions.
+/// - https://drafts.csswg.org/css-cascade-5/#at-import
+/// - https://www.w3.org/TR/cssom-1/#the-cssimportrule-interface
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRule {
+    /// The URL of the imported style sheet, which is not resolved yet.
+    pub href: String,
+    /// The serialized media query list in the prelude, which is empty if it is omitted.
+    pub media: String,
+    /// The imported style sheet, which is `None` until it is loaded or if it fails to load.
+    pub style_sheet: Option<Box<StyleSheet>>,
+}
+
+/// An `@page` rule, whose declarations apply to the pages when the document is printed.
+/// todo: Support the margin rules, e.g. `@top-center`.
+/// - https://drafts.csswg.org/css-page-3/#at-page-rule
+/// - https://www.w3.org/TR/cssom-1/#the-csspagerule-interface
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageRule {
+    /// The serialized page selector list in the prelude, e.g. `:first`, which is empty if it is omitted.
+    pub selectors: String,
+    pub declarations: Vec<Declaration>,
+}
+
 /// https://www.w3.org/TR/css-syntax-3/#at-rules
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct AtRule {
     pub name: String,
     pub prelude: Vec<ComponentValue>,
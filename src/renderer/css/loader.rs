@@ -0,0 +1,145 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use anyhow::Result;
+
+use crate::net::fetch::ResourceFetcher;
+use crate::net::url::resolve_url;
+use crate::renderer::css::cssom::{Rule, StyleSheet};
+use crate::renderer::css::parser::CssParser;
+use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::parse_error::ParseErrorCollector;
+
+/// Parses the external style sheet whose body is fetched from the absolute URL, and loads the style sheets
+/// it imports with the fetcher.
+/// https://html.spec.whatwg.org/multipage/semantics.html#fetching-and-processing-a-resource-from-a-link-element
+pub fn load_style_sheet(
+    url: &str,
+    bytes: &[u8],
+    fetcher: &dyn ResourceFetcher,
+) -> Result<StyleSheet> {
+    parse_style_sheet_in_chain(url, bytes, fetcher, &mut Vec::new())
+}
+
+/// Loads the style sheets imported by the `@import` rules of the style sheet recursively, resolving their
+/// URLs against `base_url`. The style sheets which fail to load are skipped, as browsers do.
+/// https://drafts.csswg.org/css-cascade-5/#fetch-an-import
+pub fn load_imports(style_sheet: &mut StyleSheet, base_url: &str, fetcher: &dyn ResourceFetcher) {
+    load_imports_in_chain(style_sheet, base_url, fetcher, &mut Vec::new());
+}
+
+/// `chain` is the URLs of the style sheets which import the style sheet directly or indirectly, which are
+/// not imported again to stop the import cycles.
+fn fetch_style_sheet_in_chain(
+    url: &str,
+    fetcher: &dyn ResourceFetcher,
+    chain: &mut Vec<String>,
+) -> Result<StyleSheet> {
+    let bytes = fetcher.fetch(url)?;
+    parse_style_sheet_in_chain(url, &bytes, fetcher, chain)
+}
+
+fn parse_style_sheet_in_chain(
+    url: &str,
+    bytes: &[u8],
+    fetcher: &dyn ResourceFetcher,
+    chain: &mut Vec<String>,
+) -> Result<StyleSheet> {
+    let css = String::from_utf8_lossy(bytes);
+    // The parse errors in the style sheet are not reported with the ones in the document, as the positions
+    // are in another source.
+    let error_handler = Rc::new(RefCell::new(ParseErrorCollector::default()));
+    let tokens = CssTokenizer::new(css.trim_start_matches('\u{FEFF}'))
+        .set_error_handler(Rc::clone(&error_handler) as _)
+        .tokenize()?;
+    let mut style_sheet = CssParser::new(&tokens)
+        .set_error_handler(error_handler)
+        .parse()?;
+    chain.push(url.to_string());
+    load_imports_in_chain(&mut style_sheet, url, fetcher, chain);
+    chain.pop();
+    Ok(style_sheet)
+}
+
+fn load_imports_in_chain(
+    style_sheet: &mut StyleSheet,
+    base_url: &str,
+    fetcher: &dyn ResourceFetcher,
+    chain: &mut Vec<String>,
+) {
+    // The `@import` rules are only at the top level, since the ones in the other rules are dropped by the parser.
+    for rule in &mut style_sheet.rules {
+        let Rule::ImportRule(rule) = rule else {
+            continue;
+        };
+        let url = resolve_url(base_url, &rule.href);
+        if chain.contains(&url) {
+            eprintln!("Import cycle detected: {}", url);
+            continue;
+        }
+        match fetch_style_sheet_in_chain(&url, fetcher, chain) {
+            Ok(imported) => rule.style_sheet = Some(Box::new(imported)),
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::bail;
+
+    use super::*;
+    use crate::renderer::css::media::MediaEnvironment;
+
+    #[derive(Debug)]
+    struct MockFetcher;
+
+    impl ResourceFetcher for MockFetcher {
+        fn fetch(&self, url: &str) -> Result<Vec<u8>> {
+            let css = match url {
+                "http://example.com/css/main.css" => {
+                    "@charset \"utf-8\"; @import url(base.css); @import \"/print.css\" print;\
+                    @import url(\"missing.css\"); p { color: blue; }"
+                }
+                "http://example.com/css/base.css" => {
+                    "@import \"main.css\"; @import \"base.css\"; a { color: red; } @import \"late.css\";"
+                }
+                "http://example.com/print.css" => "div { color: black; }",
+                _ => bail!("Not found: {}", url),
+            };
+            Ok(css.as_bytes().to_vec())
+        }
+    }
+
+    #[test]
+    fn import_style_sheets() {
+        let url = "http://example.com/css/main.css";
+        let style_sheet =
+            load_style_sheet(url, &MockFetcher.fetch(url).unwrap(), &MockFetcher).unwrap();
+        let get_selectors = |media: &MediaEnvironment| {
+            style_sheet
+                .get_style_rules(media)
+                .iter()
+                .map(|rule| rule.selectors[0].to_string())
+                .collect::<Vec<_>>()
+        };
+        // The imported rules precede the ones of the importing style sheet, the import cycles are stopped,
+        // and the misplaced `@import` rule is dropped.
+        assert_eq!(get_selectors(&MediaEnvironment::default()), ["a", "p"]);
+        assert_eq!(style_sheet.get_all_style_rules().len(), 3);
+
+        let Rule::ImportRule(base) = &style_sheet.rules[1] else {
+            panic!();
+        };
+        let base = base.style_sheet.as_ref().unwrap();
+        assert_eq!(base.rules.len(), 3);
+        assert!(base.rules[..2]
+            .iter()
+            .all(|rule| matches!(rule, Rule::ImportRule(rule) if rule.style_sheet.is_none())));
+        let Rule::ImportRule(print) = &style_sheet.rules[2] else {
+            panic!();
+        };
+        assert_eq!(print.media, "print");
+        assert!(print.style_sheet.is_some());
+    }
+}
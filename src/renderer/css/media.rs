@@ -0,0 +1,285 @@
+use crate::renderer::style::property::{parse_length_px, LengthContext};
+use crate::ui::{DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_WIDTH};
+
+/// The state of the output device and the user preferences against which the media queries are evaluated.
+/// https://drafts.csswg.org/mediaqueries/#media-feature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MediaEnvironment {
+    /// Whether the colors of the pages are replaced with the limited palette chosen by the user, e.g. in a
+    /// high-contrast mode.
+    /// https://drafts.csswg.org/mediaqueries-5/#forced-colors
+    pub forced_colors: bool,
+    /// The size of the viewport in CSS pixels.
+    /// https://drafts.csswg.org/mediaqueries/#width
+    pub viewport_width: i32,
+    pub viewport_height: i32,
+    /// https://drafts.csswg.org/mediaqueries-5/#prefers-color-scheme
+    pub color_scheme: ColorScheme,
+    /// Whether the pages are shown on the screen or printed, in which case the viewport is the page area.
+    /// https://drafts.csswg.org/mediaqueries/#media-types
+    pub media_type: MediaType,
+}
+
+impl Default for MediaEnvironment {
+    fn default() -> Self {
+        Self {
+            forced_colors: false,
+            viewport_width: DEFAULT_WINDOW_WIDTH,
+            viewport_height: DEFAULT_WINDOW_HEIGHT,
+            color_scheme: ColorScheme::default(),
+            media_type: MediaType::default(),
+        }
+    }
+}
+
+/// The color scheme preferred by the user.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScheme {
+    #[default]
+    Light,
+    Dark,
+}
+
+/// The media types which the browser supports.
+/// https://drafts.csswg.org/mediaqueries/#media-types
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MediaType {
+    #[default]
+    Screen,
+    Print,
+}
+
+impl MediaType {
+    /// Returns whether the media type in a media query matches this one.
+    fn matches(&self, name: &str) -> bool {
+        match name {
+            "all" => true,
+            "screen" => *self == Self::Screen,
+            "print" => *self == Self::Print,
+            _ => false,
+        }
+    }
+}
+
+/// Evaluates the media queries, e.g. the conditions of the `@media` rules and the values of the `media`
+/// attributes, against the environment in which the pages are rendered.
+/// https://drafts.csswg.org/mediaqueries/#evaluating
+#[derive(Debug, Clone, Copy)]
+pub struct MediaQueryEvaluator<'a> {
+    environment: &'a MediaEnvironment,
+}
+
+impl<'a> MediaQueryEvaluator<'a> {
+    pub fn new(environment: &'a MediaEnvironment) -> Self {
+        Self { environment }
+    }
+
+    /// Returns whether the media query list matches the environment. An empty list matches all media.
+    /// https://drafts.csswg.org/mediaqueries/#mq-list
+    pub fn matches(&self, list: &str) -> bool {
+        if list.trim().is_empty() {
+            return true;
+        }
+        list.split(',').any(|query| self.matches_media_query(query))
+    }
+
+    /// Evaluates a query of an optional media type followed by media features joined with `and`. A query with
+    /// unknown media features never matches, even if it is negated.
+    /// todo: Support `or`, the negation of the features and the range syntax.
+    /// https://drafts.csswg.org/mediaqueries/#media-query
+    fn matches_media_query(&self, query: &str) -> bool {
+        let query = query.to_ascii_lowercase();
+        let query = query.replace('(', " (").replace(')', ") ");
+        let mut words = query.split_ascii_whitespace().peekable();
+        let negated = words.next_if_eq(&"not").is_some();
+        if !negated {
+            // `only` hides the query from legacy user agents, and doesn't change its meaning.
+            words.next_if_eq(&"only");
+        }
+        let words = words.collect::<Vec<_>>();
+        if words.is_empty() {
+            // An empty query in a list is the same as `not all`.
+            return false;
+        }
+
+        // https://drafts.csswg.org/mediaqueries/#media-types
+        let (mut matches, features) = match &words[..] {
+            // The media type is `all` if it is omitted.
+            [first, ..] if first.starts_with('(') => (true, &words[..]),
+            [media_type] => (self.environment.media_type.matches(media_type), &[][..]),
+            [media_type, "and", features @ ..] if !features.is_empty() => {
+                (self.environment.media_type.matches(media_type), features)
+            }
+            _ => return false,
+        };
+        let features = features.join(" ");
+        for feature in features.split(" and ").filter(|f| !f.is_empty()) {
+            let Some(feature) = feature.strip_prefix('(').and_then(|f| f.strip_suffix(')')) else {
+                return false;
+            };
+            match self.evaluate_media_feature(feature.trim()) {
+                Some(result) => matches &= result,
+                None => return false,
+            }
+        }
+        matches != negated
+    }
+
+    /// Evaluates a media feature in the form of `name: value`, or `name` in a boolean context. Returns `None` if
+    /// the feature or its value is unknown.
+    /// https://drafts.csswg.org/mediaqueries/#mq-features
+    fn evaluate_media_feature(&self, feature: &str) -> Option<bool> {
+        let environment = self.environment;
+        let (name, value) = match feature.split_once(':') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (feature, None),
+        };
+        // The `min-` and `max-` prefixes of the range features mean `>=` and `<=`.
+        // https://drafts.csswg.org/mediaqueries/#mq-min-max
+        let compare = |actual: i32, value: Option<&str>, prefix: &str| {
+            // The font-relative units are relative to the initial font size, since the media queries are not
+            // evaluated against any element.
+            // https://drafts.csswg.org/mediaqueries/#units
+            let expected = parse_length_px(value?, &LengthContext::new(environment))?;
+            let actual = actual as f32;
+            Some(match prefix {
+                "min-" => actual >= expected,
+                "max-" => actual <= expected,
+                _ => actual == expected,
+            })
+        };
+        let (prefix, base_name) = ["min-", "max-"]
+            .into_iter()
+            .find_map(|prefix| Some((prefix, name.strip_prefix(prefix)?)))
+            .unwrap_or(("", name));
+        match (base_name, value) {
+            // https://drafts.csswg.org/mediaqueries-5/#forced-colors
+            ("forced-colors", None | Some("active")) if prefix.is_empty() => {
+                Some(environment.forced_colors)
+            }
+            ("forced-colors", Some("none")) if prefix.is_empty() => {
+                Some(!environment.forced_colors)
+            }
+            // A viewport is never zero-sized in a boolean context, since it is always shown.
+            // https://drafts.csswg.org/mediaqueries/#width
+            ("width" | "height", None) if prefix.is_empty() => Some(true),
+            ("width", value) => compare(environment.viewport_width, value, prefix),
+            ("height", value) => compare(environment.viewport_height, value, prefix),
+            // The square viewport is portrait.
+            // https://drafts.csswg.org/mediaqueries/#orientation
+            ("orientation", value) if prefix.is_empty() => {
+                let portrait = environment.viewport_height >= environment.viewport_width;
+                match value {
+                    None => Some(true),
+                    Some("portrait") => Some(portrait),
+                    Some("landscape") => Some(!portrait),
+                    _ => None,
+                }
+            }
+            // https://drafts.csswg.org/mediaqueries-5/#prefers-color-scheme
+            ("prefers-color-scheme", Some("light")) if prefix.is_empty() => {
+                Some(environment.color_scheme == ColorScheme::Light)
+            }
+            ("prefers-color-scheme", Some("dark")) if prefix.is_empty() => {
+                Some(environment.color_scheme == ColorScheme::Dark)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_media_query_lists() {
+        let env = MediaEnvironment::default();
+        let evaluator = MediaQueryEvaluator::new(&env);
+        assert!(evaluator.matches(""));
+        assert!(evaluator.matches("all"));
+        assert!(evaluator.matches("Only Screen"));
+        assert!(evaluator.matches("print, screen"));
+        assert!(evaluator.matches("not print"));
+        assert!(!evaluator.matches("print"));
+        assert!(!evaluator.matches("not screen"));
+        assert!(!evaluator.matches("tv"));
+        assert!(!evaluator.matches("print,"));
+        assert!(!evaluator.matches("screen and (min-resolution: 2dppx)"));
+
+        let print = MediaEnvironment {
+            media_type: MediaType::Print,
+            ..Default::default()
+        };
+        let evaluator = MediaQueryEvaluator::new(&print);
+        assert!(evaluator.matches("print"));
+        assert!(evaluator.matches("all and (min-width: 100px)"));
+        assert!(evaluator.matches("not screen"));
+        assert!(!evaluator.matches("screen"));
+    }
+
+    #[test]
+    fn match_forced_colors() {
+        let forced = MediaEnvironment {
+            forced_colors: true,
+            ..Default::default()
+        };
+        let normal = MediaEnvironment::default();
+        for (query, matches_forced, matches_normal) in [
+            ("(forced-colors)", true, false),
+            ("(forced-colors: active)", true, false),
+            ("(FORCED-COLORS:none)", false, true),
+            ("screen and (forced-colors: active)", true, false),
+            ("not screen and (forced-colors: none)", true, false),
+            ("print and (forced-colors)", false, false),
+            ("(forced-colors: unknown)", false, false),
+            ("screen and", false, false),
+        ] {
+            assert_eq!(
+                MediaQueryEvaluator::new(&forced).matches(query),
+                matches_forced,
+                "{}",
+                query
+            );
+            assert_eq!(
+                MediaQueryEvaluator::new(&normal).matches(query),
+                matches_normal,
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn match_viewport_features() {
+        let env = MediaEnvironment {
+            viewport_width: 800,
+            viewport_height: 600,
+            color_scheme: ColorScheme::Dark,
+            ..Default::default()
+        };
+        for (query, matches) in [
+            ("(min-width: 800px)", true),
+            ("(min-width: 801px)", false),
+            ("screen and (max-width: 50em)", true),
+            ("(width: 800px) and (height: 600px)", true),
+            ("(max-height: 599.5px)", false),
+            ("(width)", true),
+            ("(min-width: 600)", false),
+            ("(min-orientation: portrait)", false),
+            ("(orientation: landscape)", true),
+            ("(orientation: portrait)", false),
+            ("(prefers-color-scheme: dark)", true),
+            ("(prefers-color-scheme: light)", false),
+            ("not all and (prefers-color-scheme: light)", true),
+            ("(max-width: 600px), (orientation: landscape)", true),
+        ] {
+            assert_eq!(
+                MediaQueryEvaluator::new(&env).matches(query),
+                matches,
+                "{}",
+                query
+            );
+        }
+    }
+}
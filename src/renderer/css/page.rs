@@ -0,0 +1,245 @@
+use anyhow::{bail, Context, Result};
+
+use crate::renderer::css::cssom::{ComponentValue, Declaration, StyleSheet};
+use crate::renderer::css::media::MediaEnvironment;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{
+    parse_length_percentage_type, CssProperty, CssValue, LengthUnit, MarginProp,
+};
+
+/// The size of the A4 paper, which is the default page size, in millimeters.
+const A4_SIZE_MM: (f32, f32) = (210.0, 297.0);
+/// The default margins of the pages, which leave the space where the printers can't print.
+const DEFAULT_MARGIN_PX: f32 = 96.0 / 2.54;
+
+/// The size and the margins of the pages in CSS pixels, into which the document is split when it is printed.
+/// The document is laid out in the page area, which is the page box without the margins.
+/// https://drafts.csswg.org/css-page-3/#page-model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSettings {
+    pub width: f32,
+    pub height: f32,
+    /// The margins of the top, right, bottom and left edges.
+    pub margin: (f32, f32, f32, f32),
+}
+
+impl Default for PageSettings {
+    fn default() -> Self {
+        let (width, height) = get_named_page_size("a4").unwrap();
+        Self {
+            width,
+            height,
+            margin: (
+                DEFAULT_MARGIN_PX,
+                DEFAULT_MARGIN_PX,
+                DEFAULT_MARGIN_PX,
+                DEFAULT_MARGIN_PX,
+            ),
+        }
+    }
+}
+
+impl PageSettings {
+    /// Applies the `size` and margin declarations of the `@page` rules which apply to the media, in the order
+    /// of appearance. The invalid declarations are ignored.
+    /// todo: Support the page selectors, e.g. `:first`, and the other page properties.
+    /// https://drafts.csswg.org/css-page-3/#page-properties
+    pub fn from_style_sheets(style_sheets: &[StyleSheet], media: &MediaEnvironment) -> Self {
+        let mut settings = Self::default();
+        let declarations = style_sheets
+            .iter()
+            .flat_map(|style_sheet| style_sheet.get_page_rules(media))
+            .filter(|rule| rule.selectors.is_empty())
+            .flat_map(|rule| &rule.declarations);
+        for declaration in declarations {
+            // The invalid declarations are ignored as in the style rules.
+            let _ = settings.apply_declaration(declaration);
+        }
+        settings
+    }
+
+    /// Returns the width and the height of the page area, which are at least 1px.
+    pub fn get_page_area(&self) -> (f32, f32) {
+        let (top, right, bottom, left) = self.margin;
+        (
+            (self.width - left - right).max(1.0),
+            (self.height - top - bottom).max(1.0),
+        )
+    }
+
+    /// Returns the number of the pages into which the document of the height is split.
+    /// todo: Avoid breaking the lines and the boxes across the pages, and support `break-before` and
+    /// `break-after`.
+    /// https://drafts.csswg.org/css-break-3/#breaking-rules
+    pub fn get_page_count(&self, document_height: f32) -> usize {
+        let (_, page_height) = self.get_page_area();
+        ((document_height / page_height).ceil() as usize).max(1)
+    }
+
+    fn apply_declaration(&mut self, declaration: &Declaration) -> Result<()> {
+        match declaration.name.as_str() {
+            "size" => {
+                (self.width, self.height) = parse_page_size(&declaration.value)?;
+            }
+            // The percentages of the margins are relative to the width or the height of the page box.
+            // https://drafts.csswg.org/css-page-3/#margin-dimension
+            "margin" => {
+                let margin = MarginProp::parse(&declaration.value)?;
+                self.margin = (
+                    resolve_length(&margin.top, self.height)?,
+                    resolve_length(&margin.right, self.width)?,
+                    resolve_length(&margin.bottom, self.height)?,
+                    resolve_length(&margin.left, self.width)?,
+                );
+            }
+            "margin-top" | "margin-right" | "margin-bottom" | "margin-left" => {
+                let value = parse_length_percentage_type(
+                    &mut declaration
+                        .value
+                        .iter()
+                        .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+                        .cloned()
+                        .peekable(),
+                )?;
+                match declaration.name.as_str() {
+                    "margin-top" => self.margin.0 = resolve_length(&value, self.height)?,
+                    "margin-right" => self.margin.1 = resolve_length(&value, self.width)?,
+                    "margin-bottom" => self.margin.2 = resolve_length(&value, self.height)?,
+                    _ => self.margin.3 = resolve_length(&value, self.width)?,
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+// size =
+//   <length [0,∞]>{1,2} | auto | [ <page-size> || [ portrait | landscape ] ]
+fn parse_page_size(values: &[ComponentValue]) -> Result<(f32, f32)> {
+    let values = values
+        .iter()
+        .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+        .collect::<Vec<_>>();
+    let idents = values
+        .iter()
+        .map(|v| match v {
+            ComponentValue::PreservedToken(CssToken::Ident(ident)) => {
+                Some(ident.to_ascii_lowercase())
+            }
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>();
+    let Some(idents) = idents else {
+        let lengths = values
+            .into_iter()
+            .map(|v| {
+                let value =
+                    parse_length_percentage_type(&mut std::iter::once(v.clone()).peekable())?;
+                resolve_length(&value, 0.0)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        return match lengths[..] {
+            [size] => Ok((size, size)),
+            [width, height] => Ok((width, height)),
+            _ => bail!("Invalid page size: {:?}", lengths),
+        };
+    };
+
+    let default = PageSettings::default();
+    let mut size = None;
+    let mut orientation = None;
+    for ident in &idents {
+        match ident.as_str() {
+            "auto" if idents.len() == 1 => return Ok((default.width, default.height)),
+            "portrait" | "landscape" if orientation.is_none() => orientation = Some(ident),
+            _ if size.is_none() => {
+                size = Some(
+                    get_named_page_size(ident)
+                        .with_context(|| format!("Unknown page size: {}", ident))?,
+                )
+            }
+            _ => bail!("Invalid page size: {:?}", idents),
+        }
+    }
+    let (width, height) = size.unwrap_or((default.width, default.height));
+    Ok(match orientation.map(String::as_str) {
+        Some("landscape") => (width.max(height), width.min(height)),
+        _ => (width.min(height), width.max(height)),
+    })
+}
+
+/// Returns the size of the paper in the portrait orientation in CSS pixels.
+/// https://drafts.csswg.org/css-page-3/#typedef-page-size-page-size
+fn get_named_page_size(name: &str) -> Option<(f32, f32)> {
+    let mm = |(width, height): (f32, f32)| (width * 96.0 / 25.4, height * 96.0 / 25.4);
+    let inches = |(width, height): (f32, f32)| (width * 96.0, height * 96.0);
+    Some(match name {
+        "a5" => mm((148.0, 210.0)),
+        "a4" => mm(A4_SIZE_MM),
+        "a3" => mm((297.0, 420.0)),
+        "b5" => mm((176.0, 250.0)),
+        "b4" => mm((250.0, 353.0)),
+        "jis-b5" => mm((182.0, 257.0)),
+        "jis-b4" => mm((257.0, 364.0)),
+        "letter" => inches((8.5, 11.0)),
+        "legal" => inches((8.5, 14.0)),
+        "ledger" => inches((11.0, 17.0)),
+        _ => return None,
+    })
+}
+
+/// Converts the absolute length or the percentage of the basis to pixels. The pages have no font, so the
+/// relative lengths are not supported.
+fn resolve_length(value: &CssValue, percentage_basis: f32) -> Result<f32> {
+    match value {
+        CssValue::Length(value, LengthUnit::AbsoluteLengthUnit(unit)) if *value >= 0.0 => {
+            Ok(unit.to_px(*value))
+        }
+        CssValue::Percentage(value) if *value >= 0.0 => Ok(value / 100.0 * percentage_basis),
+        _ => bail!("Unsupported page length: {:?}", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::media::MediaType;
+    use crate::renderer::css::parser::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+
+    #[test]
+    fn apply_page_rules() {
+        let css = "@page { size: A5 landscape; margin: 1in 10% }\
+            @media print { @page { margin-top: 0; } }\
+            @page :first { size: 10cm; }\
+            @page { size: 1em; }";
+        let style_sheet = CssParser::new(&CssTokenizer::new(css).tokenize().unwrap())
+            .parse()
+            .unwrap();
+        let print = MediaEnvironment {
+            media_type: MediaType::Print,
+            ..Default::default()
+        };
+        let round = |settings: PageSettings| {
+            [
+                settings.width,
+                settings.height,
+                settings.margin.0,
+                settings.margin.1,
+                settings.margin.2,
+                settings.margin.3,
+            ]
+            .map(f32::round)
+        };
+
+        let settings = PageSettings::from_style_sheets(std::slice::from_ref(&style_sheet), &print);
+        assert_eq!(round(settings), [794.0, 559.0, 0.0, 79.0, 96.0, 79.0]);
+        assert_eq!(settings.get_page_count(0.0), 1);
+        assert_eq!(settings.get_page_count(463.0 * 2.5), 3);
+        // The `@page` rules in `@media print` don't apply to the screen.
+        let screen = PageSettings::from_style_sheets(&[style_sheet], &MediaEnvironment::default());
+        assert_eq!(round(screen)[2], 96.0);
+        assert_eq!(round(PageSettings::default())[..2], [794.0, 1123.0]);
+    }
+}
@@ -3,44 +3,143 @@ use std::collections::VecDeque;
 use anyhow::{ensure, Result};
 
 use crate::renderer::css::cssom::{
-    AtRule, ComponentValue, Declaration, QualifiedRule, Rule, StyleSheet,
+    AtRule, ComponentValue, Declaration, ImportRule, MediaRule, PageRule, QualifiedRule, Rule,
+    StyleSheet,
 };
 use crate::renderer::css::selector::SelectorParser;
 use crate::renderer::css::token::CssToken;
+use crate::renderer::parse_error::{
+    ParseErrorReport, SharedParseErrorHandler, StderrParseErrorHandler,
+};
+use crate::renderer::source::SourceSpan;
 use crate::utils::TokenIterator;
 
 #[derive(Debug)]
 pub struct CssParser {
     input: TokenIterator<CssToken>,
+    error_handler: SharedParseErrorHandler,
+    /// The spans of the input tokens in the source text, if known.
+    spans: Vec<SourceSpan>,
 }
 
 impl CssParser {
     pub fn new(tokens: &[CssToken]) -> Self {
         Self {
             input: TokenIterator::new(tokens),
+            error_handler: StderrParseErrorHandler::new_shared(),
+            spans: Vec::new(),
         }
     }
 
+    pub fn set_error_handler(&mut self, handler: SharedParseErrorHandler) -> &mut Self {
+        self.error_handler = handler;
+        self
+    }
+
+    /// Sets the spans of the input tokens, which are returned by `CssTokenizer::tokenize_with_spans()`.
+    pub fn set_source_spans(&mut self, spans: Vec<SourceSpan>) -> &mut Self {
+        self.spans = spans;
+        self
+    }
+
+    /// Returns the span from the start of the token at `start` to the end of the last consumed token.
+    fn get_span_from(&self, start: usize) -> Option<SourceSpan> {
+        let end = self.spans.get(self.input.get_pos().checked_sub(1)?)?;
+        Some(SourceSpan {
+            start: self.spans.get(start)?.start,
+            end: end.end,
+        })
+    }
+
+    /// Reports a parse error at the last consumed token. If the spans of the tokens are not given,
+    /// the position in the source text is unknown.
+    /// https://www.w3.org/TR/css-syntax-3/#error-handling
+    fn report_error(&self, code: &str, detail: Option<String>) {
+        let position = self
+            .input
+            .get_pos()
+            .checked_sub(1)
+            .and_then(|i| self.spans.get(i))
+            .map(|span| span.start);
+        self.error_handler.borrow_mut().handle(ParseErrorReport {
+            code: code.to_string(),
+            detail,
+            position,
+            context: None,
+        });
+    }
+
     /// Returns a stylesheet using the `Parse a stylesheet` entry point (normal parser entry point).
     /// https://www.w3.org/TR/css-syntax-3/#parse-stylesheet
+    #[tracing::instrument(skip_all)]
     pub fn parse(&mut self) -> Result<StyleSheet> {
-        Ok(StyleSheet::new(self.consume_list_of_rules()?))
+        Ok(StyleSheet::new(self.consume_list_of_rules(true)?))
+    }
+
+    /// Returns the component values using the `Parse a list of component values` entry point, which is used
+    /// to parse the values given outside style sheets, e.g. the selectors of `querySelector()`.
+    /// https://www.w3.org/TR/css-syntax-3/#parse-list-of-component-values
+    pub fn parse_list_of_component_values(&mut self) -> Vec<ComponentValue> {
+        let mut values = Vec::new();
+        loop {
+            match self.consume_component_value() {
+                ComponentValue::PreservedToken(CssToken::Eof) => return values,
+                value => values.push(value),
+            }
+        }
+    }
+
+    /// Returns the declarations using the `Parse a list of declarations` entry point, which is used to parse
+    /// the `style` attributes.
+    /// https://www.w3.org/TR/css-syntax-3/#parse-list-of-declarations
+    pub fn parse_list_of_declarations(&mut self) -> Vec<Declaration> {
+        self.consume_declarations_until(None)
     }
 
+    /// Consumes the rules of the style sheet if `top_level` is true, or the ones in the block of an at-rule
+    /// up to its closing brace otherwise.
     /// https://www.w3.org/TR/css-syntax-3/#consume-list-of-rules
-    fn consume_list_of_rules(&mut self) -> Result<Vec<Rule>> {
+    fn consume_list_of_rules(&mut self, top_level: bool) -> Result<Vec<Rule>> {
         let mut rules = Vec::new();
 
         loop {
             match self.input.next() {
                 Some(CssToken::Whitespace) => continue,
-                Some(CssToken::Eof) | None => return Ok(rules),
+                Some(CssToken::Eof) | None => {
+                    if !top_level {
+                        self.report_error("eof-in-at-rule", None);
+                    }
+                    return Ok(rules);
+                }
+                Some(CssToken::CloseCurlyBrace) if !top_level => return Ok(rules),
                 Some(CssToken::Cdo | CssToken::Cdc) => {
                     unimplemented!();
                 }
                 Some(CssToken::AtKeyword(_)) => {
                     self.input.rewind(1);
-                    rules.push(Rule::AtRule(self.consume_at_rule()?.unwrap()));
+                    match self.consume_at_rule()? {
+                        // `@import` rules are valid only at the top of the style sheet, where only `@charset`
+                        // and `@layer` statements and other `@import` rules precede them.
+                        // https://drafts.csswg.org/css-cascade-5/#at-import
+                        Some(Rule::ImportRule(_))
+                            if !top_level
+                                || !rules.iter().all(|rule| match rule {
+                                    Rule::ImportRule(_) => true,
+                                    Rule::AtRule(rule) => {
+                                        rule.block.is_none()
+                                            && matches!(
+                                                rule.name.to_ascii_lowercase().as_str(),
+                                                "charset" | "layer"
+                                            )
+                                    }
+                                    _ => false,
+                                }) =>
+                        {
+                            self.report_error("misplaced-import-rule", None);
+                        }
+                        Some(rule) => rules.push(rule),
+                        None => {}
+                    }
                 }
                 _ => {
                     self.input.rewind(1);
@@ -50,8 +149,10 @@ impl CssParser {
         }
     }
 
+    /// Consumes an at-rule. The block of an `@media` rule is a list of rules, the block of an `@page` rule is
+    /// a list of declarations, and the block of the other at-rules is a style rule for now. `None` is returned if the at-rule is invalid.
     /// https://www.w3.org/TR/css-syntax-3/#consume-an-at-rule
-    fn consume_at_rule(&mut self) -> Result<Option<AtRule>> {
+    fn consume_at_rule(&mut self) -> Result<Option<Rule>> {
         self.input.next();
         let CssToken::AtKeyword(name) = self.input.get_last_consumed().unwrap() else {
             unreachable!();
@@ -64,10 +165,39 @@ impl CssParser {
 
         loop {
             match self.input.next() {
-                Some(CssToken::Semicolon) => return Ok(Some(at_rule)),
+                Some(CssToken::Semicolon) if at_rule.name.eq_ignore_ascii_case("import") => {
+                    return Ok(self
+                        .parse_import_prelude(&at_rule.prelude)
+                        .map(Rule::ImportRule));
+                }
+                Some(CssToken::Semicolon) => return Ok(Some(Rule::AtRule(at_rule))),
                 Some(CssToken::Eof) => {
-                    eprintln!("parse error in consume_at_rule");
-                    return Ok(Some(at_rule));
+                    self.report_error("eof-in-at-rule", None);
+                    return Ok(Some(Rule::AtRule(at_rule)));
+                }
+                // https://drafts.csswg.org/css-conditional-3/#at-media
+                Some(CssToken::OpenCurlyBrace) if at_rule.name.eq_ignore_ascii_case("media") => {
+                    let media = at_rule
+                        .prelude
+                        .iter()
+                        .map(|value| value.to_string())
+                        .collect::<String>();
+                    return Ok(Some(Rule::MediaRule(MediaRule {
+                        media: media.trim().to_string(),
+                        rules: self.consume_list_of_rules(false)?,
+                    })));
+                }
+                // https://drafts.csswg.org/css-page-3/#syntax-page-selector
+                Some(CssToken::OpenCurlyBrace) if at_rule.name.eq_ignore_ascii_case("page") => {
+                    let selectors = at_rule
+                        .prelude
+                        .iter()
+                        .map(|value| value.to_string())
+                        .collect::<String>();
+                    return Ok(Some(Rule::PageRule(PageRule {
+                        selectors: selectors.trim().to_string(),
+                        declarations: self.consume_list_of_declarations(),
+                    })));
                 }
                 Some(CssToken::OpenCurlyBrace) => {
                     while let Some(CssToken::Whitespace) = self.input.peek() {
@@ -80,7 +210,7 @@ impl CssParser {
                         self.input.next();
                     }
                     ensure!(matches!(self.input.next(), Some(CssToken::CloseCurlyBrace)));
-                    return Ok(Some(at_rule));
+                    return Ok(Some(Rule::AtRule(at_rule)));
                 }
                 _ => {
                     self.input.rewind(1);
@@ -90,19 +220,60 @@ impl CssParser {
         }
     }
 
+    /// Parses the prelude of an `@import` rule, which is a URL followed by an optional media query list.
+    /// https://drafts.csswg.org/css-cascade-5/#at-import
+    fn parse_import_prelude(&self, prelude: &[ComponentValue]) -> Option<ImportRule> {
+        let mut values = prelude
+            .iter()
+            .skip_while(|v| **v == ComponentValue::PreservedToken(CssToken::Whitespace));
+        let href = match values.next() {
+            Some(ComponentValue::PreservedToken(CssToken::Url(url) | CssToken::String(url))) => {
+                url.clone()
+            }
+            // A quoted URL in `url()` is a function token followed by a string.
+            Some(ComponentValue::Function { name, values }) if name.eq_ignore_ascii_case("url") => {
+                match values
+                    .iter()
+                    .find(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+                {
+                    Some(ComponentValue::PreservedToken(CssToken::String(url))) => url.clone(),
+                    _ => {
+                        self.report_error("invalid-import-rule", None);
+                        return None;
+                    }
+                }
+            }
+            _ => {
+                self.report_error("invalid-import-rule", None);
+                return None;
+            }
+        };
+        Some(ImportRule {
+            href,
+            media: values
+                .map(|v| v.to_string())
+                .collect::<String>()
+                .trim()
+                .to_string(),
+            style_sheet: None,
+        })
+    }
+
     /// https://www.w3.org/TR/css-syntax-3/#consume-a-qualified-rule
     fn consume_qualified_rule(&mut self) -> Result<Option<QualifiedRule>> {
+        let start = self.input.get_pos();
         let mut qualified_rule = QualifiedRule {
             // The prelude of the qualified rule is parsed as a <selector-list>.
             selectors: Vec::new(),
             declarations: Vec::new(),
+            source_span: None,
         };
         let mut selectors_buf = Vec::new();
 
         loop {
             match self.input.next() {
                 Some(CssToken::Eof) | None => {
-                    eprintln!("parse error in consume_qualified_rule");
+                    self.report_error("eof-in-qualified-rule", None);
                     return Ok(None);
                 }
                 Some(CssToken::OpenCurlyBrace) => {
@@ -125,7 +296,8 @@ impl CssParser {
                     // This implementation stops parsing the CSS and returns an error in this case, instead of ignoring the rule.
                     qualified_rule
                         .selectors
-                        .extend(SelectorParser::new(selectors_buf).parse()?);
+                        .extend(SelectorParser::new(&selectors_buf).parse()?);
+                    qualified_rule.source_span = self.get_span_from(start);
 
                     return Ok(Some(qualified_rule));
                 }
@@ -160,7 +332,7 @@ impl CssParser {
             match self.input.next() {
                 Some(t) if t == ending_token => return block,
                 Some(CssToken::Eof) | None => {
-                    eprintln!("parse error in consume_simple_block");
+                    self.report_error("eof-in-simple-block", None);
                     return block;
                 }
                 _ => {
@@ -179,7 +351,6 @@ impl CssParser {
             t,
             CssToken::OpenCurlyBrace | CssToken::OpenParenthesis | CssToken::OpenSquareBracket
         )));
-        let mut declarations = Vec::new();
 
         // Partially follows the consume-simple-block algorithm.
         let ending_token = match self.input.get_last_consumed().unwrap() {
@@ -190,12 +361,23 @@ impl CssParser {
                 unreachable!();
             }
         };
+        self.consume_declarations_until(Some(ending_token))
+    }
+
+    /// Consumes the declarations up to the ending token, or up to the end of the input if it is `None`.
+    fn consume_declarations_until(&mut self, ending_token: Option<CssToken>) -> Vec<Declaration> {
+        let mut declarations = Vec::new();
 
         loop {
             match self.input.next() {
-                Some(t) if t == ending_token => return declarations,
+                Some(t) if ending_token.as_ref() == Some(&t) => return declarations,
                 Some(CssToken::Whitespace) | Some(CssToken::Semicolon) => {}
-                Some(CssToken::Eof) | None => return declarations,
+                Some(CssToken::Eof) | None => {
+                    if ending_token.is_some() {
+                        self.report_error("eof-in-simple-block", None);
+                    }
+                    return declarations;
+                }
                 Some(CssToken::AtKeyword(_)) => {
                     unimplemented!();
                 }
@@ -203,26 +385,29 @@ impl CssParser {
                     let mut tmp_token_list = vec![ComponentValue::PreservedToken(
                         self.input.get_last_consumed().unwrap().clone(),
                     )];
+                    // The last declaration in the block may not be followed by a semicolon.
                     while !matches!(
                         self.input.peek(),
                         Some(CssToken::Semicolon) | Some(CssToken::Eof)
-                    ) {
+                    ) && self.input.peek() != ending_token.as_ref()
+                    {
                         tmp_token_list.push(self.consume_component_value());
                     }
-                    if let Some(declaration) = Self::consume_declaration(tmp_token_list) {
+                    if let Some(declaration) = self.consume_declaration(tmp_token_list) {
                         declarations.push(declaration);
                     }
                 }
                 _ => {
-                    eprintln!(
-                        "parse error in consume_list_of_declarations: {:?}",
-                        self.input.get_last_consumed()
+                    self.report_error(
+                        "invalid-declaration",
+                        Some(format!("{:?}", self.input.get_last_consumed())),
                     );
                     self.input.rewind(1);
                     while !matches!(
                         self.input.peek(),
                         Some(CssToken::Semicolon) | Some(CssToken::Eof)
-                    ) {
+                    ) && self.input.peek() != ending_token.as_ref()
+                    {
                         self.consume_component_value();
                     }
                 }
@@ -232,7 +417,7 @@ impl CssParser {
 
     /// This function is intended to be called for a given list of component values, not for default input stream.
     /// https://www.w3.org/TR/css-syntax-3/#consume-declaration
-    fn consume_declaration(component_values: Vec<ComponentValue>) -> Option<Declaration> {
+    fn consume_declaration(&self, component_values: Vec<ComponentValue>) -> Option<Declaration> {
         let mut component_values = VecDeque::from(component_values);
         assert!(component_values
             .front()
@@ -245,6 +430,7 @@ impl CssParser {
         let mut declaration = Declaration {
             name,
             value: Vec::new(),
+            important: false,
         };
 
         while component_values.front()
@@ -253,7 +439,7 @@ impl CssParser {
             component_values.pop_front();
         }
         if component_values.front() != Some(&ComponentValue::PreservedToken(CssToken::Colon)) {
-            eprintln!("parse error in consume_declaration");
+            self.report_error("missing-colon-in-declaration", None);
             return None;
         } else {
             component_values.pop_front();
@@ -271,23 +457,38 @@ impl CssParser {
             declaration.value.push(t);
         }
 
-        if (declaration.value.len() >= 2)
-            && declaration.value.get(declaration.value.len() - 2)
-                == Some(&ComponentValue::PreservedToken(CssToken::Delim('!')))
-        {
-            if let Some(&ComponentValue::PreservedToken(CssToken::Ident(s))) =
-                declaration.value.last().as_ref()
-            {
-                if s.eq_ignore_ascii_case("important") {
-                    unimplemented!();
-                }
-            }
-        }
         while declaration.value.last()
             == Some(&ComponentValue::PreservedToken(CssToken::Whitespace))
         {
             declaration.value.pop();
         }
+        // If the last two non-whitespace tokens are `!` and `important`, they are removed and the declaration
+        // is marked as important.
+        let non_whitespace_indices = declaration
+            .value
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, v)| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+            .map(|(i, _)| i)
+            .take(2)
+            .collect::<Vec<_>>();
+        if let [important_index, delim_index] = non_whitespace_indices[..] {
+            if matches!(
+                &declaration.value[important_index],
+                ComponentValue::PreservedToken(CssToken::Ident(s)) if s.eq_ignore_ascii_case("important")
+            ) && declaration.value[delim_index]
+                == ComponentValue::PreservedToken(CssToken::Delim('!'))
+            {
+                declaration.value.truncate(delim_index);
+                declaration.important = true;
+                while declaration.value.last()
+                    == Some(&ComponentValue::PreservedToken(CssToken::Whitespace))
+                {
+                    declaration.value.pop();
+                }
+            }
+        }
         Some(declaration)
     }
 
@@ -321,7 +522,7 @@ impl CssParser {
             match self.input.next() {
                 Some(CssToken::CloseParenthesis) => return function,
                 Some(CssToken::Eof) | None => {
-                    eprintln!("parse error in consume_function");
+                    self.report_error("eof-in-function", None);
                     return function;
                 }
                 _ => {
@@ -339,6 +540,7 @@ impl CssParser {
 mod tests {
     use super::*;
     use crate::renderer::css::cssom::{ComponentValue, Declaration, QualifiedRule, Rule};
+    use crate::renderer::css::media::{ColorScheme, MediaEnvironment};
     use crate::renderer::css::selector::{Combinator, Selector, SimpleSelector};
     use crate::renderer::css::token::{CssToken, CssTokenizer, NumericType};
 
@@ -369,6 +571,7 @@ mod tests {
                         value: vec![ComponentValue::PreservedToken(CssToken::Ident(
                             "red".to_string(),
                         ))],
+                        important: false,
                     },
                     Declaration {
                         name: "grid-template-columns".to_string(),
@@ -383,8 +586,10 @@ mod tests {
                                 "fr".to_string(),
                             )),
                         ],
+                        important: false,
                     },
                 ],
+                source_span: None,
             }),
             Rule::QualifiedRule(QualifiedRule {
                 selectors: vec![Selector::Simple(vec![SimpleSelector::Type {
@@ -396,7 +601,9 @@ mod tests {
                     value: vec![ComponentValue::PreservedToken(CssToken::Ident(
                         "blue".to_string(),
                     ))],
+                    important: false,
                 }],
+                source_span: None,
             }),
         ];
 
@@ -439,7 +646,9 @@ mod tests {
                     value: vec![ComponentValue::PreservedToken(CssToken::Ident(
                         "red".to_string(),
                     ))],
+                    important: false,
                 }],
+                source_span: None,
             }),
             Rule::QualifiedRule(QualifiedRule {
                 selectors: vec![Selector::Complex(
@@ -479,6 +688,7 @@ mod tests {
                         value: vec![ComponentValue::PreservedToken(CssToken::Ident(
                             "blue".to_string(),
                         ))],
+                        important: false,
                     },
                     Declaration {
                         name: "font-size".to_string(),
@@ -486,11 +696,60 @@ mod tests {
                             NumericType::Integer(16),
                             "px".to_string(),
                         ))],
+                        important: false,
                     },
                 ],
+                source_span: None,
             }),
         ];
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn parse_media_rules() {
+        let css = r#"
+            p { color: red; }
+            @media screen and (max-width: 600px) {
+                p { color: blue; }
+                @media (prefers-color-scheme: dark) { a { color: white; } }
+            }
+            @media print { p { color: black; } }
+            div { color: green; }
+        "#;
+        let style_sheet = CssParser::new(&CssTokenizer::new(css).tokenize().unwrap())
+            .parse()
+            .unwrap();
+        let Rule::MediaRule(rule) = &style_sheet.rules[1] else {
+            panic!();
+        };
+        assert_eq!(rule.media, "screen and (max-width: 600px)");
+        assert_eq!(rule.rules.len(), 2);
+        assert_eq!(style_sheet.rules.len(), 4);
+
+        let get_selectors = |media: &MediaEnvironment| {
+            style_sheet
+                .get_style_rules(media)
+                .iter()
+                .map(|rule| rule.selectors[0].to_string())
+                .collect::<Vec<_>>()
+        };
+        let wide = MediaEnvironment::default();
+        let narrow = MediaEnvironment {
+            viewport_width: 600,
+            color_scheme: ColorScheme::Dark,
+            ..Default::default()
+        };
+        assert_eq!(get_selectors(&wide), ["p", "div"]);
+        assert_eq!(get_selectors(&narrow), ["p", "p", "a", "div"]);
+        assert_eq!(style_sheet.get_all_style_rules().len(), 5);
+        assert!(style_sheet.is_affected_by_media_change(&wide, &narrow));
+        assert!(!style_sheet.is_affected_by_media_change(
+            &narrow,
+            &MediaEnvironment {
+                viewport_width: 300,
+                ..narrow
+            }
+        ));
+    }
 }
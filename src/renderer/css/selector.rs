@@ -1,14 +1,14 @@
 use std::cell::RefCell;
-use std::iter::Peekable;
-use std::ops::Deref;
+use std::fmt;
+use std::ops::{Add, Deref};
 use std::rc::Rc;
-use std::vec::IntoIter;
 
 use anyhow::{bail, ensure, Ok, Result};
 
 use crate::renderer::css::cssom::ComponentValue;
-use crate::renderer::css::token::CssToken;
-use crate::renderer::html::dom::{DomNode, NodeType};
+use crate::renderer::css::parser::CssParser;
+use crate::renderer::css::token::{CssToken, CssTokenizer, NumericType};
+use crate::renderer::html::dom::{Directionality, DomNode, Element, NodeType};
 
 /// - https://www.w3.org/TR/selectors-3/#simple-selectors
 /// - https://www.w3.org/TR/selectors-3/#grammar
@@ -28,13 +28,45 @@ pub enum SimpleSelector {
     Class(String),
     Id(String),
     PseudoClass(String),
-    // PseudoElement(String),
+    /// The `:lang()` pseudo-class with a list of language ranges.
+    /// https://www.w3.org/TR/selectors-4/#the-lang-pseudo
+    Lang(Vec<String>),
+    /// The `:dir()` pseudo-class.
+    /// https://www.w3.org/TR/selectors-4/#the-dir-pseudo
+    Dir(String),
+    /// The `:nth-child()`, `:nth-last-child()`, `:nth-of-type()` and `:nth-last-of-type()` pseudo-classes,
+    /// which match the elements whose indices among their siblings are `an+b` for some `n >= 0`.
+    /// https://www.w3.org/TR/selectors-4/#child-index
+    Nth {
+        a: i32,
+        b: i32,
+        of_type: bool,
+        from_last: bool,
+    },
+    /// The logical pseudo-classes `:not()`, `:is()` and `:where()`, and the relational pseudo-class `:has()`,
+    /// which take selector lists. The relative selectors of `:has()` are absolutized with `:scope`, e.g.
+    /// `:has(> p)` takes `:scope > p`.
+    /// - https://www.w3.org/TR/selectors-4/#logical-combination
+    /// - https://www.w3.org/TR/selectors-4/#relational
+    PseudoClassFunction {
+        name: String,
+        selectors: Vec<Selector>,
+    },
+    /// The pseudo-element, e.g. `::before`, which must be the last simple selector of the selector. The
+    /// selector matches the originating element, and the declarations apply to the pseudo-element.
+    /// https://www.w3.org/TR/selectors-4/#pseudo-elements
+    PseudoElement(String),
 }
 
 impl SimpleSelector {
-    pub fn matches(&self, dom_node: &Rc<RefCell<DomNode>>) -> bool {
-        let dom_node = dom_node.borrow();
+    pub fn matches(&self, node: &Rc<RefCell<DomNode>>) -> bool {
+        match &node.borrow().node_type {
+            NodeType::Element(elm) => self.matches_element(node, elm),
+            _ => false,
+        }
+    }
 
+    fn matches_element(&self, node: &Rc<RefCell<DomNode>>, elm: &Element) -> bool {
         match self {
             SimpleSelector::Type {
                 namespace_prefix,
@@ -43,47 +75,372 @@ impl SimpleSelector {
                 if namespace_prefix.is_some() {
                     unimplemented!();
                 }
-
-                if let NodeType::Element(elm) = &dom_node.node_type {
-                    elm.tag_name == *name
-                } else {
+                elm.tag_name == *name
+            }
+            SimpleSelector::Class(class_name) => elm
+                .attributes
+                .iter()
+                // e.g) p.class2 matches <p class="class1 class2 class3">
+                .any(|(k, v)| k == "class" && v.split(' ').any(|c| c == class_name)),
+            SimpleSelector::Id(id) => elm.attributes.iter().any(|(k, v)| k == "id" && v == id),
+            SimpleSelector::PseudoClass(class_name) => match class_name.as_str() {
+                // https://developer.mozilla.org/en-US/docs/Web/CSS/:link
+                "link" => elm.attributes.iter().any(|(k, _)| k == "href"),
+                // `:scope` is the same as `:root` in style sheets.
+                // - https://www.w3.org/TR/selectors-4/#the-root-pseudo
+                // - https://www.w3.org/TR/selectors-4/#the-scope-pseudo
+                "root" | "scope" => DomNode::get_parent(node).is_some_and(|parent| {
+                    matches!(parent.borrow().node_type, NodeType::Document(_))
+                }),
+                // https://html.spec.whatwg.org/multipage/semantics-other.html#selector-defined
+                "defined" => elm.is_defined(),
+                // The checkedness of the checkboxes and the radio buttons is their `checked` attribute, which
+                // is toggled when they are activated.
+                // https://html.spec.whatwg.org/multipage/semantics-other.html#selector-checked
+                "checked" => match elm.tag_name.as_str() {
+                    "input" => {
+                        matches!(
+                            elm.get_attribute("type")
+                                .map(str::to_ascii_lowercase)
+                                .as_deref(),
+                            Some("checkbox" | "radio")
+                        ) && elm.get_attribute("checked").is_some()
+                    }
+                    "option" => elm.get_attribute("selected").is_some(),
+                    _ => false,
+                },
+                // https://html.spec.whatwg.org/multipage/semantics-other.html#selector-target
+                "target" => node.borrow().state.target,
+                // https://www.w3.org/TR/selectors-4/#useraction-pseudos
+                "hover" => node.borrow().state.hover,
+                "active" => node.borrow().state.active,
+                "focus" => node.borrow().state.focus,
+                // https://www.w3.org/TR/selectors-4/#child-index
+                "first-child" => get_child_index(node, false).0 == 1,
+                "last-child" => get_child_index(node, false).1 == 1,
+                "only-child" => get_child_index(node, false) == (1, 1),
+                "first-of-type" => get_child_index(node, true).0 == 1,
+                "last-of-type" => get_child_index(node, true).1 == 1,
+                "only-of-type" => get_child_index(node, true) == (1, 1),
+                _ => {
+                    // todo
                     false
                 }
+            },
+            SimpleSelector::Lang(ranges) => DomNode::get_language(node).is_some_and(|lang| {
+                ranges
+                    .iter()
+                    .any(|range| matches_language_range(&lang, range))
+            }),
+            // The pseudo-element is checked by the selector.
+            SimpleSelector::PseudoElement(_) => true,
+            SimpleSelector::Dir(dir) => {
+                let directionality = || DomNode::get_directionality(node);
+                match dir.to_ascii_lowercase().as_str() {
+                    "ltr" => directionality() == Directionality::Ltr,
+                    "rtl" => directionality() == Directionality::Rtl,
+                    // Invalid values are not rejected, but they never match.
+                    _ => false,
+                }
             }
-            SimpleSelector::Class(class_name) => {
-                if let NodeType::Element(elm) = &dom_node.node_type {
-                    elm.attributes
-                        .iter()
-                        // e.g) p.class2 matches <p class="class1 class2 class3">
-                        .any(|(k, v)| k == "class" && v.split(' ').any(|c| c == class_name))
-                } else {
-                    false
+            SimpleSelector::Nth {
+                a,
+                b,
+                of_type,
+                from_last,
+            } => {
+                let (index, index_from_last) = get_child_index(node, *of_type);
+                let index = if *from_last { index_from_last } else { index } as i32;
+                // The index is `an+b` for some `n >= 0`.
+                match a {
+                    0 => index == *b,
+                    a => (index - b) % a == 0 && (index - b) / a >= 0,
                 }
             }
-            SimpleSelector::Id(id) => {
-                if let NodeType::Element(elm) = &dom_node.node_type {
-                    elm.attributes.iter().any(|(k, v)| k == "id" && v == id)
-                } else {
-                    false
+            SimpleSelector::PseudoClassFunction { name, selectors } => match name.as_str() {
+                "not" => !selectors.iter().any(|s| s.matches(node)),
+                "is" | "where" => selectors.iter().any(|s| s.matches(node)),
+                "has" => selectors.iter().any(|s| s.matches_relative(node)),
+                _ => false,
+            },
+            // todo: Support the namespace prefixes and the case-sensitivity modifiers.
+            // https://www.w3.org/TR/selectors-4/#attribute-selectors
+            SimpleSelector::Attribute {
+                name, op, value, ..
+            } => {
+                let Some(attr) = elm.get_attribute(&name.to_ascii_lowercase()) else {
+                    return false;
+                };
+                let value = value.as_deref().unwrap_or_default();
+                match op.as_deref() {
+                    None => true,
+                    Some("=") => attr == value,
+                    Some("~=") => attr.split_ascii_whitespace().any(|word| word == value),
+                    Some("|=") => {
+                        attr == value
+                            || attr.strip_prefix(value).is_some_and(|s| s.starts_with('-'))
+                    }
+                    Some("^=") => !value.is_empty() && attr.starts_with(value),
+                    Some("$=") => !value.is_empty() && attr.ends_with(value),
+                    Some("*=") => !value.is_empty() && attr.contains(value),
+                    _ => false,
                 }
             }
+            _ => unimplemented!(),
+        }
+    }
+}
+
+fn is_element(node: &Rc<RefCell<DomNode>>) -> bool {
+    matches!(node.borrow().node_type, NodeType::Element(_))
+}
+
+/// Returns the ancestors of the node from its parent to the root of its tree.
+fn get_ancestors(node: &Rc<RefCell<DomNode>>) -> impl Iterator<Item = Rc<RefCell<DomNode>>> {
+    std::iter::successors(DomNode::get_parent(node), DomNode::get_parent)
+}
+
+/// Returns the preceding siblings of the node from the nearest one.
+fn get_prev_siblings(node: &Rc<RefCell<DomNode>>) -> impl Iterator<Item = Rc<RefCell<DomNode>>> {
+    std::iter::successors(DomNode::get_prev_sibling(node), DomNode::get_prev_sibling)
+}
+
+/// Returns the following siblings of the node from the nearest one.
+fn get_next_siblings(node: &Rc<RefCell<DomNode>>) -> impl Iterator<Item = Rc<RefCell<DomNode>>> {
+    std::iter::successors(node.borrow().next_sibling.clone(), |node| {
+        node.borrow().next_sibling.clone()
+    })
+}
+
+/// Returns the indices of the element among the element children of its parent, or among the ones of the
+/// same type if `of_type`, counted from 1 from the first and from the last child. These are `(0, 0)` for
+/// the other nodes.
+/// https://drafts.csswg.org/selectors-4/#child-index
+fn get_child_index(node: &Rc<RefCell<DomNode>>, of_type: bool) -> (u32, u32) {
+    let tag_name = match &node.borrow().node_type {
+        NodeType::Element(elm) => elm.tag_name.clone(),
+        _ => return (0, 0),
+    };
+    if DomNode::get_parent(node).is_none() {
+        return (0, 0);
+    }
+    let is_counted = |sibling: &Rc<RefCell<DomNode>>| {
+        matches!(&sibling.borrow().node_type,
+            NodeType::Element(elm) if !of_type || elm.tag_name == tag_name)
+    };
+    (
+        get_prev_siblings(node).filter(is_counted).count() as u32 + 1,
+        get_next_siblings(node).filter(is_counted).count() as u32 + 1,
+    )
+}
+
+/// Returns whether the language tag matches the language range by the extended filtering.
+/// - https://www.w3.org/TR/selectors-4/#the-lang-pseudo
+/// - https://www.rfc-editor.org/rfc/rfc4647#section-3.3.2
+fn matches_language_range(lang: &str, range: &str) -> bool {
+    if range.is_empty() {
+        return lang.is_empty();
+    }
+    let lang = lang.to_ascii_lowercase();
+    let range = range.to_ascii_lowercase();
+    let mut lang_subtags = lang.split('-');
+    let mut range_subtags = range.split('-');
+
+    match (range_subtags.next(), lang_subtags.next()) {
+        (Some("*"), Some(l)) if !l.is_empty() => {}
+        (Some(r), Some(l)) if r == l => {}
+        _ => return false,
+    }
+
+    let mut lang_subtags = lang_subtags.peekable();
+    for r in range_subtags {
+        if r == "*" {
+            continue;
+        }
+        loop {
+            match lang_subtags.next() {
+                Some(l) if l == r => break,
+                // A singleton subtag (e.g. "x" in "en-x-private") cannot be skipped.
+                Some(l) if l.len() == 1 => return false,
+                Some(_) => continue,
+                None => return false,
+            }
+        }
+    }
+    true
+}
+
+/// https://drafts.csswg.org/cssom/#serializing-selectors
+impl fmt::Display for SimpleSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimpleSelector::Type {
+                namespace_prefix,
+                name,
+            } => {
+                write_namespace_prefix(f, namespace_prefix)?;
+                write!(f, "{}", serialize_identifier(name))
+            }
+            SimpleSelector::Universal(namespace_prefix) => {
+                write_namespace_prefix(f, namespace_prefix)?;
+                write!(f, "*")
+            }
+            SimpleSelector::Attribute {
+                namespace_prefix,
+                name,
+                op,
+                value,
+            } => {
+                write!(f, "[")?;
+                write_namespace_prefix(f, namespace_prefix)?;
+                write!(f, "{}", serialize_identifier(name))?;
+                if let (Some(op), Some(value)) = (op, value) {
+                    write!(f, "{}{}", op, serialize_string(value))?;
+                }
+                write!(f, "]")
+            }
+            SimpleSelector::Class(class_name) => write!(f, ".{}", serialize_identifier(class_name)),
+            SimpleSelector::Id(id) => write!(f, "#{}", serialize_identifier(id)),
             SimpleSelector::PseudoClass(class_name) => {
-                if let NodeType::Element(elm) = &dom_node.node_type {
-                    match class_name.as_str() {
-                        // https://developer.mozilla.org/en-US/docs/Web/CSS/:link
-                        "link" => elm.attributes.iter().any(|(k, _)| k == "href"),
-                        _ => {
-                            // todo
-                            false
+                write!(f, ":{}", serialize_identifier(class_name))
+            }
+            SimpleSelector::Lang(ranges) => write!(
+                f,
+                ":lang({})",
+                ranges
+                    .iter()
+                    .map(|range| serialize_identifier(range))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            SimpleSelector::Dir(dir) => write!(f, ":dir({})", serialize_identifier(dir)),
+            SimpleSelector::PseudoElement(name) => write!(f, "::{}", serialize_identifier(name)),
+            SimpleSelector::Nth {
+                a,
+                b,
+                of_type,
+                from_last,
+            } => {
+                let name = match (of_type, from_last) {
+                    (false, false) => "nth-child",
+                    (false, true) => "nth-last-child",
+                    (true, false) => "nth-of-type",
+                    (true, true) => "nth-last-of-type",
+                };
+                write!(f, ":{}(", name)?;
+                // https://drafts.csswg.org/css-syntax-3/#serialize-an-anplusb-value
+                match a {
+                    0 => write!(f, "{}", b)?,
+                    1 => write!(f, "n")?,
+                    -1 => write!(f, "-n")?,
+                    a => write!(f, "{}n", a)?,
+                }
+                match b {
+                    _ if *a == 0 => {}
+                    0 => {}
+                    b if *b > 0 => write!(f, "+{}", b)?,
+                    b => write!(f, "{}", b)?,
+                }
+                write!(f, ")")
+            }
+            SimpleSelector::PseudoClassFunction { name, selectors } => {
+                let selectors = selectors
+                    .iter()
+                    .map(|selector| match selector {
+                        // The `:scope` of the relative selectors is implied.
+                        Selector::Complex(_, Combinator::Whitespace, right) if name == "has" => {
+                            right.to_string()
                         }
-                    }
-                } else {
-                    false
+                        Selector::Complex(_, combinator, right) if name == "has" => {
+                            format!("{} {}", combinator, right)
+                        }
+                        selector => selector.to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                write!(f, ":{}({})", name, selectors.join(", "))
+            }
+        }
+    }
+}
+
+fn write_namespace_prefix(
+    f: &mut fmt::Formatter,
+    namespace_prefix: &Option<String>,
+) -> fmt::Result {
+    match namespace_prefix.as_deref() {
+        None => fmt::Result::Ok(()),
+        Some("*") => write!(f, "*|"),
+        Some(prefix) => write!(f, "{}|", serialize_identifier(prefix)),
+    }
+}
+
+/// https://drafts.csswg.org/cssom/#serialize-an-identifier
+fn serialize_identifier(ident: &str) -> String {
+    let chars = ident.chars().collect::<Vec<_>>();
+    let mut serialized = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            '\u{0000}' => serialized.push('\u{FFFD}'),
+            '\u{0001}'..='\u{001F}' | '\u{007F}' => {
+                serialized.push_str(&format!("\\{:x} ", c as u32))
+            }
+            c if c.is_ascii_digit() && (i == 0 || (i == 1 && chars[0] == '-')) => {
+                serialized.push_str(&format!("\\{:x} ", c as u32))
+            }
+            '-' if i == 0 && chars.len() == 1 => serialized.push_str("\\-"),
+            c if !c.is_ascii() || c.is_ascii_alphanumeric() || c == '-' || c == '_' => {
+                serialized.push(c)
+            }
+            _ => {
+                serialized.push('\\');
+                serialized.push(c);
+            }
+        }
+    }
+    serialized
+}
+
+/// Splits the component values by the commas, and strips the whitespace around each part.
+fn split_by_commas(values: &[ComponentValue]) -> impl Iterator<Item = &[ComponentValue]> {
+    let is_whitespace =
+        |v: &ComponentValue| *v == ComponentValue::PreservedToken(CssToken::Whitespace);
+    values
+        .split(|v| *v == ComponentValue::PreservedToken(CssToken::Comma))
+        .map(move |mut part| {
+            while let [first, rest @ ..] = part {
+                if !is_whitespace(first) {
+                    break;
                 }
+                part = rest;
             }
-            _ => unimplemented!(),
+            while let [rest @ .., last] = part {
+                if !is_whitespace(last) {
+                    break;
+                }
+                part = rest;
+            }
+            part
+        })
+}
+
+/// https://drafts.csswg.org/cssom/#serialize-a-string
+fn serialize_string(string: &str) -> String {
+    let mut serialized = String::from('"');
+    for c in string.chars() {
+        match c {
+            '\u{0000}' => serialized.push('\u{FFFD}'),
+            '\u{0001}'..='\u{001F}' | '\u{007F}' => {
+                serialized.push_str(&format!("\\{:x} ", c as u32))
+            }
+            '"' | '\\' => {
+                serialized.push('\\');
+                serialized.push(c);
+            }
+            _ => serialized.push(c),
         }
     }
+    serialized.push('"');
+    serialized
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -94,6 +451,17 @@ pub enum Combinator {
     Tilde,
 }
 
+impl fmt::Display for Combinator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Combinator::Whitespace => write!(f, " "),
+            Combinator::GreaterThan => write!(f, ">"),
+            Combinator::Plus => write!(f, "+"),
+            Combinator::Tilde => write!(f, "~"),
+        }
+    }
+}
+
 /// https://www.w3.org/TR/selectors-3/#selector-syntax
 #[derive(Clone, Debug, PartialEq)]
 pub enum Selector {
@@ -105,21 +473,102 @@ pub enum Selector {
     Complex(Box<Selector>, Combinator, Box<Selector>),
 }
 
+/// Serializes the selector in the normalized form, e.g. `ul>li.a` is serialized as `ul > li.a`.
+/// https://drafts.csswg.org/cssom/#serialize-a-selector
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Selector::Simple(selectors) => {
+                for simple_selector in selectors {
+                    // The universal selector without a namespace prefix is omitted
+                    // if it is not the only simple selector in the compound selector.
+                    if selectors.len() > 1 && *simple_selector == SimpleSelector::Universal(None) {
+                        continue;
+                    }
+                    write!(f, "{}", simple_selector)?;
+                }
+                fmt::Result::Ok(())
+            }
+            Selector::Complex(left, Combinator::Whitespace, right) => {
+                write!(f, "{} {}", left, right)
+            }
+            Selector::Complex(left, combinator, right) => {
+                write!(f, "{} {} {}", left, combinator, right)
+            }
+        }
+    }
+}
+
 impl Selector {
-    pub fn matches(&self, dom_node: &Rc<RefCell<DomNode>>) -> bool {
-        /// Returns the DOM node that the selector constructed in the current tree evaluates for the node backtracked from the target node.
+    /// Returns whether the selector matches the node in the tree. The selectors with pseudo-elements never
+    /// match elements.
+    pub fn matches(&self, node: &Rc<RefCell<DomNode>>) -> bool {
+        self.get_pseudo_element().is_none() && self.match_leftmost(node).is_some()
+    }
+
+    /// Returns whether the selector matches the pseudo-element of the node, e.g. `p::before` matches the
+    /// `before` pseudo-element of the `p` elements.
+    pub fn matches_pseudo_element(&self, node: &Rc<RefCell<DomNode>>, name: &str) -> bool {
+        self.get_pseudo_element() == Some(name) && self.match_leftmost(node).is_some()
+    }
+
+    /// Returns the name of the pseudo-element which the selector represents, if any.
+    pub fn get_pseudo_element(&self) -> Option<&str> {
+        match self {
+            Selector::Simple(selectors) => selectors.iter().find_map(|selector| match selector {
+                SimpleSelector::PseudoElement(name) => Some(name.as_str()),
+                _ => None,
+            }),
+            Selector::Complex(_, _, right) => right.get_pseudo_element(),
+        }
+    }
+
+    /// Returns whether the relative selector absolutized with `:scope` (e.g. `:scope > p`) matches any node
+    /// relative to the anchor node, in which case `:has()` with it matches the anchor.
+    /// https://www.w3.org/TR/selectors-4/#relational
+    fn matches_relative(&self, anchor: &Rc<RefCell<DomNode>>) -> bool {
+        let Selector::Complex(_, combinator, right) = self else {
+            return false;
+        };
+        // The nodes which the rightmost compound selector can match.
+        let descendants = matches!(combinator, Combinator::Whitespace | Combinator::GreaterThan)
+            .then(|| DomNode::get_descendants(anchor));
+        let siblings = matches!(combinator, Combinator::Plus | Combinator::Tilde).then(|| {
+            get_next_siblings(anchor)
+                .flat_map(|s| std::iter::once(Rc::clone(&s)).chain(DomNode::get_descendants(&s)))
+        });
+        descendants
+            .into_iter()
+            .flatten()
+            .chain(siblings.into_iter().flatten())
+            .filter_map(|candidate| right.match_leftmost(&candidate))
+            .any(|leftmost| match combinator {
+                Combinator::Whitespace => get_ancestors(&leftmost).any(|a| Rc::ptr_eq(&a, anchor)),
+                Combinator::GreaterThan => {
+                    DomNode::get_parent(&leftmost).is_some_and(|p| Rc::ptr_eq(&p, anchor))
+                }
+                Combinator::Plus => get_prev_siblings(&leftmost)
+                    .find(is_element)
+                    .is_some_and(|s| Rc::ptr_eq(&s, anchor)),
+                Combinator::Tilde => get_prev_siblings(&leftmost).any(|s| Rc::ptr_eq(&s, anchor)),
+            })
+    }
+
+    /// Returns the node which the leftmost compound selector matches if the selector matches the node.
+    fn match_leftmost(&self, node: &Rc<RefCell<DomNode>>) -> Option<Rc<RefCell<DomNode>>> {
+        /// Returns the node that the selector constructed in the current tree evaluates for the node backtracked from the target node.
         /// If the selector does not match the node, the whole selector tree does not match the node, so this function returns None.
         fn matches_helper(
             current_selector: &Selector,
-            target_dom_node: &Rc<RefCell<DomNode>>,
+            target: &Rc<RefCell<DomNode>>,
         ) -> Option<Rc<RefCell<DomNode>>> {
             if let Selector::Simple(selectors) = current_selector {
                 // Simple, base-case
                 let success_match = selectors
                     .iter()
-                    .all(|simple_selector| simple_selector.matches(target_dom_node));
+                    .all(|simple_selector| simple_selector.matches(target));
                 if success_match {
-                    Some(Rc::clone(target_dom_node))
+                    Some(Rc::clone(target))
                 } else {
                     None
                 }
@@ -135,156 +584,119 @@ impl Selector {
                         unreachable!();
                     };
 
-                let right_node = matches_helper(right, target_dom_node)?;
+                let right_node = matches_helper(right, target)?;
+                let matches_left = |node: &Rc<RefCell<DomNode>>| {
+                    left.iter()
+                        .all(|simple_selector| simple_selector.matches(node))
+                };
 
                 // https://developer.mozilla.org/en-US/docs/Learn/CSS/Building_blocks/Selectors/Combinators
                 match combinator {
                     // https://www.w3.org/TR/selectors-3/#descendant-combinators
-                    Combinator::Whitespace => {
-                        // NOTE: html tag has no parent element.
-                        let right_node_parent = right_node.borrow().parent.as_ref()?.upgrade()?;
-                        let mut ancestor = right_node_parent;
-
-                        // Check whether the left selector exists in the ancestor of the right selector.
-                        loop {
-                            for simple_selector in left {
-                                if simple_selector.matches(&ancestor) {
-                                    return Some(Rc::clone(&ancestor));
-                                }
-                            }
-                            if ancestor.borrow().parent.is_none() {
-                                break;
-                            }
-                            let a = ancestor.borrow().parent.as_ref()?.upgrade()?;
-                            ancestor = a;
-                        }
-                        None
-                    }
+                    // Check whether the left selector exists in the ancestor of the right selector.
+                    Combinator::Whitespace => get_ancestors(&right_node).find(matches_left),
 
                     // https://www.w3.org/TR/selectors-3/#child-combinators
+                    // Check that the left selector is a parent of the right selector.
+                    // NOTE: html tag has no parent element.
                     Combinator::GreaterThan => {
-                        // NOTE: html tag has no parent element.
-                        let right_node_parent = right_node.borrow().parent.as_ref()?.upgrade()?;
-
-                        // Check that the left selector is a parent of the right selector.
-                        for simple_selector in left {
-                            if simple_selector.matches(&right_node_parent) {
-                                return Some(Rc::clone(&right_node_parent));
-                            }
-                        }
-                        None
+                        DomNode::get_parent(&right_node).filter(matches_left)
                     }
 
                     // https://www.w3.org/TR/selectors-3/#adjacent-sibling-combinators
-                    Combinator::Plus => {
-                        let mut right_node_prev_sibling =
-                            right_node.borrow().prev_sib.as_ref()?.upgrade()?;
-
-                        loop {
-                            // Non-element nodes (e.g. text between elements) are ignored when considering adjacency of elements.
-                            if let NodeType::Element(_) = right_node_prev_sibling.borrow().node_type
-                            {
-                                for simple_selector in left {
-                                    if simple_selector.matches(&right_node_prev_sibling) {
-                                        return Some(Rc::clone(&right_node_prev_sibling));
-                                    }
-                                }
-                                return None;
-                            }
-
-                            // Set the previous sibling of the previous sibling if previous sibling is not Element.
-                            let s = right_node_prev_sibling
-                                .borrow()
-                                .prev_sib
-                                .as_ref()?
-                                .upgrade()?;
-                            right_node_prev_sibling = s;
-                        }
-                    }
+                    // Non-element nodes (e.g. text between elements) are ignored when considering adjacency of elements.
+                    Combinator::Plus => get_prev_siblings(&right_node)
+                        .find(is_element)
+                        .filter(matches_left),
 
                     // https://www.w3.org/TR/selectors-3/#general-sibling-combinators
                     Combinator::Tilde => {
-                        let mut right_node_prev_sibling =
-                            right_node.borrow().prev_sib.as_ref()?.upgrade()?;
-
-                        loop {
-                            // Non-element nodes (e.g. text between elements) are ignored when considering adjacency of elements.
-                            if let NodeType::Element(_) = right_node_prev_sibling.borrow().node_type
-                            {
-                                for simple_selector in left {
-                                    if simple_selector.matches(&right_node_prev_sibling) {
-                                        return Some(right_node_prev_sibling.clone());
-                                    }
-                                }
-                                // If not matched, continue to the next sibling.
-                            }
-
-                            // Set the previous sibling of the previous sibling if previous sibling is not Element.
-                            let s = right_node_prev_sibling
-                                .borrow()
-                                .prev_sib
-                                .as_ref()?
-                                .upgrade()?;
-                            right_node_prev_sibling = s;
-                        }
+                        get_prev_siblings(&right_node).find(|s| is_element(s) && matches_left(s))
                     }
                 }
             }
         }
 
-        matches_helper(self, dom_node).is_some()
+        matches_helper(self, node)
     }
 
     /// - https://www.w3.org/TR/selectors-3/#specificity
     /// - https://developer.mozilla.org/en-US/docs/Web/CSS/Specificity
-    pub fn calc_specificity(&self) -> u32 {
-        /// Returns `(a, b, c)`, where `a` is the number of ID selectors, `b` is the number of class selectors, attributes selectors, and pseudo-classes,
-        /// and `c` is the number of type selectors and pseudo-elements.
-        fn calc_helper(
-            current_selector: &Selector,
-            current_specificity: (u32, u32, u32),
-        ) -> (u32, u32, u32) {
-            if let Selector::Simple(selectors) = current_selector {
-                let mut spec = current_specificity;
+    pub fn calc_specificity(&self) -> Specificity {
+        match self {
+            Selector::Simple(selectors) => {
+                let mut spec = Specificity::default();
                 for simple_selector in selectors {
                     match simple_selector {
-                        SimpleSelector::Type { .. } => spec.2 += 1,
+                        SimpleSelector::Type { .. } | SimpleSelector::PseudoElement(_) => {
+                            spec.2 += 1
+                        }
                         SimpleSelector::Universal(_) => {} // Ignore the universal selector.
                         SimpleSelector::Attribute { .. } => spec.1 += 1,
                         SimpleSelector::Class(_) => spec.1 += 1,
                         SimpleSelector::Id(_) => spec.0 += 1,
-                        SimpleSelector::PseudoClass(_) => spec.1 += 1,
+                        SimpleSelector::PseudoClass(_)
+                        | SimpleSelector::Lang(_)
+                        | SimpleSelector::Dir(_)
+                        | SimpleSelector::Nth { .. } => spec.1 += 1,
+                        // `:where()` has no specificity, while the others have the largest specificity
+                        // of their arguments.
+                        SimpleSelector::PseudoClassFunction { name, .. } if name == "where" => {}
+                        SimpleSelector::PseudoClassFunction { name, selectors } => {
+                            spec = spec
+                                + selectors
+                                    .iter()
+                                    .map(|selector| match selector {
+                                        // The implied `:scope` of the relative selectors is not counted.
+                                        Selector::Complex(_, _, right) if name == "has" => {
+                                            right.calc_specificity()
+                                        }
+                                        selector => selector.calc_specificity(),
+                                    })
+                                    .max()
+                                    .unwrap_or_default()
+                        }
                     }
                 }
                 spec
-            } else {
-                let Selector::Complex(left, _, right) = current_selector else {
-                    unreachable!();
-                };
-                let right_spec = calc_helper(right, current_specificity);
-                let left_spec = calc_helper(left, current_specificity);
-                (
-                    left_spec.0 + right_spec.0,
-                    left_spec.1 + right_spec.1,
-                    left_spec.2 + right_spec.2,
-                )
             }
+            Selector::Complex(left, _, right) => left.calc_specificity() + right.calc_specificity(),
         }
+    }
+}
 
-        let w = calc_helper(self, (0, 0, 0));
-        w.0 * 100 + w.1 * 10 + w.2
+/// `Specificity(a, b, c)`, where `a` is the number of ID selectors, `b` is the number of class selectors, attributes selectors,
+/// and pseudo-classes, and `c` is the number of type selectors and pseudo-elements.
+/// Specificities are compared by comparing the three components in order (e.g. `(1, 0, 0)` is greater than `(0, 11, 0)`).
+/// https://www.w3.org/TR/selectors-3/#specificity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Specificity(pub u32, pub u32, pub u32);
+
+impl Add for Specificity {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self(self.0 + other.0, self.1 + other.1, self.2 + other.2)
+    }
+}
+
+impl fmt::Display for Specificity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {}, {})", self.0, self.1, self.2)
     }
 }
 
 #[derive(Debug)]
-pub struct SelectorParser {
-    input: Peekable<IntoIter<ComponentValue>>,
+pub struct SelectorParser<'a> {
+    input: &'a [ComponentValue],
+    pos: usize,
 }
 
-impl SelectorParser {
-    pub fn new(values: Vec<ComponentValue>) -> Self {
+impl<'a> SelectorParser<'a> {
+    pub fn new(values: &'a [ComponentValue]) -> Self {
         Self {
-            input: values.into_iter().peekable(),
+            input: values,
+            pos: 0,
         }
     }
 
@@ -293,6 +705,42 @@ impl SelectorParser {
         self.parse_selectors_group()
     }
 
+    /// Parses the selector list given as a string, e.g. to `querySelector()`. The whitespace around it is
+    /// ignored.
+    /// https://drafts.csswg.org/selectors-4/#parse-a-selector
+    pub fn parse_str(input: &str) -> Result<Vec<Selector>> {
+        let values = CssParser::new(&CssTokenizer::new(input.trim()).tokenize()?)
+            .parse_list_of_component_values();
+        ensure!(!values.is_empty(), "The selector list is empty.");
+        SelectorParser::new(&values).parse()
+    }
+
+    /// Returns the `n`-th next component value without consuming it.
+    fn peek_nth(&self, n: usize) -> Option<&'a ComponentValue> {
+        self.input.get(self.pos + n)
+    }
+
+    fn peek(&self) -> Option<&'a ComponentValue> {
+        self.peek_nth(0)
+    }
+
+    fn next(&mut self) -> Option<&'a ComponentValue> {
+        let v = self.peek();
+        if v.is_some() {
+            self.pos += 1;
+        }
+        v
+    }
+
+    /// Consumes the whitespace tokens and returns whether any whitespace was consumed.
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while let Some(ComponentValue::PreservedToken(CssToken::Whitespace)) = self.peek() {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
     // selectors_group
     //   : selector [ COMMA S* selector ]*
     //   ;
@@ -300,13 +748,9 @@ impl SelectorParser {
         let mut selectors = Vec::new();
         selectors.push(self.parse_selector()?);
         loop {
-            match self.input.next() {
+            match self.next() {
                 Some(ComponentValue::PreservedToken(CssToken::Comma)) => {
-                    while self
-                        .input
-                        .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-                        .is_some()
-                    {}
+                    self.skip_whitespace();
                     selectors.push(self.parse_selector()?);
                 }
                 Some(v) => {
@@ -329,8 +773,13 @@ impl SelectorParser {
 
         if let Some(ComponentValue::PreservedToken(
             CssToken::Delim('+' | '>' | '~') | CssToken::Whitespace,
-        )) = self.input.peek()
+        )) = self.peek()
         {
+            ensure!(
+                simple.get_pseudo_element().is_none(),
+                "The pseudo-element must be in the last compound selector: {}",
+                simple
+            );
             Ok(Selector::Complex(
                 Box::new(simple),
                 self.parse_combinator()?,
@@ -346,26 +795,13 @@ impl SelectorParser {
     //   : PLUS S* | GREATER S* | TILDE S* | S+
     //   ;
     fn parse_combinator(&mut self) -> Result<Combinator> {
-        let mut is_detected_space = false;
-        while self
-            .input
-            .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-            .is_some()
-        {
-            is_detected_space = true;
-        }
+        let is_detected_space = self.skip_whitespace();
 
-        if let Some(ComponentValue::PreservedToken(CssToken::Delim('+' | '>' | '~'))) =
-            self.input.peek()
+        if let Some(ComponentValue::PreservedToken(CssToken::Delim(c @ ('+' | '>' | '~')))) =
+            self.peek()
         {
-            let Some(ComponentValue::PreservedToken(CssToken::Delim(c))) = self.input.next() else {
-                unreachable!();
-            };
-            while self
-                .input
-                .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-                .is_some()
-            {}
+            self.next();
+            self.skip_whitespace();
             match c {
                 '+' => Ok(Combinator::Plus),
                 '>' => Ok(Combinator::GreaterThan),
@@ -377,7 +813,7 @@ impl SelectorParser {
         } else {
             bail!(
             "Expected \"+\", \">\", \"~\", or whitespace but found {:?} when parsing CSS selectors in parse_combinator",
-            self.input.peek())
+            self.peek())
         }
     }
 
@@ -387,12 +823,9 @@ impl SelectorParser {
     //   | [ HASH | class | attrib | pseudo | negation ]+
     //   ;
     fn parse_simple_selector_seq(&mut self) -> Result<Vec<SimpleSelector>> {
-        // todo: parse pseudo and negation
-
         let mut selector_seq = Vec::new();
 
-        let v = self.input.clone().take(3).collect::<Vec<_>>();
-        match (v.first(), v.get(1), v.get(2)) {
+        match (self.peek(), self.peek_nth(1), self.peek_nth(2)) {
             (
                 Some(ComponentValue::PreservedToken(CssToken::Ident(_) | CssToken::Delim('*'))),
                 Some(ComponentValue::PreservedToken(CssToken::Delim('|'))),
@@ -422,50 +855,35 @@ impl SelectorParser {
             _ => {}
         }
 
-        match self.input.peek() {
-            Some(ComponentValue::PreservedToken(CssToken::Hash(..))) => {
-                while let Some(ComponentValue::PreservedToken(CssToken::Hash(s, ..))) =
-                    self.input.peek()
-                {
-                    let s = s.clone();
-                    self.input.next();
-                    selector_seq.push(SimpleSelector::Id(s.to_string()));
+        loop {
+            match self.peek() {
+                Some(ComponentValue::PreservedToken(CssToken::Hash(s, ..))) => {
+                    self.next();
+                    selector_seq.push(SimpleSelector::Id(s.clone()));
                 }
-            }
-            Some(ComponentValue::PreservedToken(CssToken::Delim('.'))) => {
-                while let Some(ComponentValue::PreservedToken(CssToken::Delim('.'))) =
-                    self.input.peek()
-                {
+                Some(ComponentValue::PreservedToken(CssToken::Delim('.'))) => {
                     selector_seq.push(self.parse_class()?);
                 }
-            }
-            Some(ComponentValue::SimpleBlock { .. }) => {
-                while let Some(ComponentValue::SimpleBlock {
-                    associated_token: t,
-                    ..
-                }) = self.input.peek()
-                {
-                    ensure!(
-                        t == &CssToken::OpenSquareBracket,
-                        "Expected \"[\" but found {:?} when parsing CSS selectors in parse_simple_selector_seq",
-                        self.input.peek()
-                    );
+                Some(ComponentValue::SimpleBlock { .. }) => {
                     selector_seq.push(self.parse_attrib()?);
                 }
-            }
-            Some(ComponentValue::PreservedToken(CssToken::Colon)) => {
-                while let Some(ComponentValue::PreservedToken(CssToken::Colon)) = self.input.peek()
-                {
-                    selector_seq.push(self.parse_pseudo()?);
+                Some(ComponentValue::PreservedToken(CssToken::Colon)) => {
+                    let pseudo = self.parse_pseudo()?;
+                    let is_pseudo_element = matches!(pseudo, SimpleSelector::PseudoElement(_));
+                    selector_seq.push(pseudo);
+                    // todo: Allow the user action pseudo-classes after the pseudo-element, e.g. `::before:hover`.
+                    if is_pseudo_element {
+                        break;
+                    }
                 }
+                _ => break,
             }
-            _ => {}
         }
 
         ensure!(
             !selector_seq.is_empty(),
             "Expected type selector, universal selector, hash, class, attribute, pseudo, or negation but found {:?} when parsing CSS selectors in parse_simple_selector_seq",
-            self.input.peek()
+            self.peek()
         );
 
         Ok(selector_seq)
@@ -475,8 +893,7 @@ impl SelectorParser {
     //   : [ namespace_prefix ]? element_name
     //   ;
     fn parse_type_selector(&mut self) -> Result<SimpleSelector> {
-        let v = self.input.clone().take(2).collect::<Vec<_>>();
-        match (v.first(), v.get(1)) {
+        match (self.peek(), self.peek_nth(1)) {
             (Some(ComponentValue::PreservedToken(CssToken::Delim('|'))), _)
             | (Some(ComponentValue::PreservedToken(CssToken::Ident(_) | CssToken::Delim('*'))), Some(ComponentValue::PreservedToken(CssToken::Delim('|')))) => {
                 Ok(SimpleSelector::Type {
@@ -492,7 +909,7 @@ impl SelectorParser {
             }
             _ => bail!(
                 "Expected namespace prefix or element name but found {:?} when parsing CSS selectors in parse_type_selector",
-                self.input.peek())
+                self.peek())
         }
     }
 
@@ -500,14 +917,14 @@ impl SelectorParser {
     //   : [ IDENT | '*' ]? '|'
     //   ;
     fn parse_namespace_prefix(&mut self) -> Result<String> {
-        let v = self.input.next();
-        match v.as_ref() {
+        let v = self.next();
+        match v {
             Some(ComponentValue::PreservedToken(CssToken::Delim('|'))) => {
                 Ok("".to_string())
             }
             Some(ComponentValue::PreservedToken(CssToken::Ident(s))) => {
-                let v = self.input.next();
-                if v.as_ref() == Some(&ComponentValue::PreservedToken(CssToken::Delim('|')))
+                let v = self.next();
+                if v == Some(&ComponentValue::PreservedToken(CssToken::Delim('|')))
                 {
                     Ok(s.clone())
                 } else {
@@ -517,8 +934,8 @@ impl SelectorParser {
                 }
             }
             Some(ComponentValue::PreservedToken(CssToken::Delim('*'))) => {
-                let v = self.input.next();
-                if v.as_ref() == Some(&ComponentValue::PreservedToken(CssToken::Delim('|')))
+                let v = self.next();
+                if v == Some(&ComponentValue::PreservedToken(CssToken::Delim('|')))
                 {
                     Ok("*".to_string())
                 } else {
@@ -537,9 +954,9 @@ impl SelectorParser {
     //   : IDENT
     //   ;
     fn parse_element_name(&mut self) -> Result<String> {
-        let v = self.input.next();
+        let v = self.next();
         if let Some(ComponentValue::PreservedToken(CssToken::Ident(s))) = v {
-            Ok(s)
+            Ok(s.clone())
         } else {
             bail!(
                 "Expected ident but found {:?} when parsing CSS selectors in parse_element_name",
@@ -552,21 +969,20 @@ impl SelectorParser {
     //   : [ namespace_prefix ]? '*'
     //   ;
     fn parse_universal(&mut self) -> Result<SimpleSelector> {
-        let v = self.input.clone().take(2).collect::<Vec<_>>();
-        match (v.first(), v.get(1)) {
+        match (self.peek(), self.peek_nth(1)) {
             (Some(ComponentValue::PreservedToken(CssToken::Delim('|'))), _)
             | (Some(ComponentValue::PreservedToken(CssToken::Ident(_) | CssToken::Delim('*'))), Some(ComponentValue::PreservedToken(CssToken::Delim('|')))) => {
                 let prefix = self.parse_namespace_prefix()?;
-                self.input.next();
+                self.next();
                 Ok(SimpleSelector::Universal(Some(prefix)))
             }
             (Some(ComponentValue::PreservedToken(CssToken::Delim('*'))), _) => {
-                self.input.next();
+                self.next();
                 Ok(SimpleSelector::Universal(None))
             }
             _ => bail!(
                 "Expected namespace prefix or \"*\" but found {:?} when parsing CSS selectors in parse_universal",
-                v.first())
+                self.peek())
         }
     }
 
@@ -574,7 +990,7 @@ impl SelectorParser {
     //   : '.' IDENT
     //   ;
     fn parse_class(&mut self) -> Result<SimpleSelector> {
-        let v = self.input.next();
+        let v = self.next();
         if let Some(ComponentValue::PreservedToken(CssToken::Delim('.'))) = v {
             Ok(SimpleSelector::Class(self.parse_element_name()?))
         } else {
@@ -596,119 +1012,106 @@ impl SelectorParser {
     //         ]? ']'
     //   ;
     fn parse_attrib(&mut self) -> Result<SimpleSelector> {
-        let v = self.input.next();
-        let mut values_in_block = if let Some(ComponentValue::SimpleBlock {
+        let v = self.next();
+        let Some(ComponentValue::SimpleBlock {
             associated_token: t,
             values: values_in_block,
         }) = v
-        {
-            ensure!(
-                t == CssToken::OpenSquareBracket,
-                "Expected \"[\" but found {:?} when parsing CSS selectors in parse_attrib",
-                t
-            );
-
-            values_in_block.clone().into_iter().peekable()
-        } else {
+        else {
             bail!(
                 "Expected simple block but found {:?} when parsing CSS selectors in parse_attrib",
                 v
             );
         };
+        ensure!(
+            *t == CssToken::OpenSquareBracket,
+            "Expected \"[\" but found {:?} when parsing CSS selectors in parse_attrib",
+            t
+        );
 
-        while values_in_block
-            .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-            .is_some()
-        {}
+        // The values in the block are parsed by another parser that borrows them.
+        let mut block = SelectorParser::new(values_in_block);
 
-        let v = values_in_block.clone().take(2).collect::<Vec<_>>();
-        let prefix = match (v.first(), v.get(1)) {
-            (Some(ComponentValue::PreservedToken(CssToken::Delim('|'))), _)
-            | (Some(ComponentValue::PreservedToken(CssToken::Ident(_) | CssToken::Delim('*'))), Some(ComponentValue::PreservedToken(CssToken::Delim('|')))) => {
-                Some(Self::new(values_in_block.clone().collect()).parse_namespace_prefix()?)
+        block.skip_whitespace();
+
+        // Look ahead 3 values to distinguish the namespace prefix (e.g. `ns|attr`) from DASHMATCH (e.g. `attr|=value`).
+        let prefix = match (block.peek(), block.peek_nth(1), block.peek_nth(2)) {
+            (Some(ComponentValue::PreservedToken(CssToken::Delim('|'))), _, _)
+            | (Some(ComponentValue::PreservedToken(CssToken::Ident(_) | CssToken::Delim('*'))), Some(ComponentValue::PreservedToken(CssToken::Delim('|'))), Some(ComponentValue::PreservedToken(CssToken::Ident(_)))) => {
+                Some(block.parse_namespace_prefix()?)
             }
-            (Some(ComponentValue::PreservedToken(CssToken::Ident(_))), _) => {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(_))), _, _) => {
                 None
             }
             _ => bail!(
                 "Expected namespace prefix or ident but found {:?} when parsing CSS selectors in parse_attrib",
-                v.first(),)
+                block.peek(),)
         };
 
-        let v = values_in_block.next();
+        let v = block.next();
         let Some(ComponentValue::PreservedToken(CssToken::Ident(name))) = v else {
             bail!(
                 "Expected ident but found {:?} when parsing CSS selectors in parse_attrib",
                 v
             );
         };
-        while values_in_block
-            .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-            .is_some()
-        {}
+        block.skip_whitespace();
 
-        match values_in_block.peek() {
+        match block.peek() {
             Some(ComponentValue::PreservedToken(CssToken::Delim(c))) => {
                 let c = *c;
                 let op = if c == '=' {
-                    values_in_block.next();
+                    block.next();
                     "=".to_string()
                 } else if matches!(c, '^' | '$' | '*' | '~' | '|') {
-                    values_in_block.next();
-                    if let Some(ComponentValue::PreservedToken(CssToken::Delim('='))) =
-                        values_in_block.peek()
+                    block.next();
+                    if let Some(ComponentValue::PreservedToken(CssToken::Delim('='))) = block.peek()
                     {
-                        values_in_block.next();
+                        block.next();
                         format!("{}=", c)
                     } else {
                         bail!(
                             "Expected \"=\" but found {:?} when parsing CSS selectors in parse_attrib",
-                            values_in_block.peek()
+                            block.peek()
                         )
                     }
                 } else {
                     bail!(
                         "Expected \"=\", \"^=\", \"$=\", \"*=\", \"~=\", \"|=\" but found {:?} when parsing CSS selectors in parse_attrib",
-                        values_in_block.peek()
+                        block.peek()
                     );
                 };
 
-                while values_in_block
-                    .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-                    .is_some()
-                {}
+                block.skip_whitespace();
 
-                let v = values_in_block.next();
+                let v = block.next();
                 let value = if let Some(ComponentValue::PreservedToken(
                     CssToken::Ident(s) | CssToken::String(s),
                 )) = v
                 {
-                    Some(s)
+                    Some(s.clone())
                 } else {
                     bail!("Expected ident or string but found {:?} when parsing CSS selectors in parse_attrib", v);
                 };
 
-                while values_in_block
-                    .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-                    .is_some()
-                {}
+                block.skip_whitespace();
 
                 Ok(SimpleSelector::Attribute {
                     namespace_prefix: prefix,
-                    name,
+                    name: name.clone(),
                     op: Some(op),
                     value,
                 })
             }
             None => Ok(SimpleSelector::Attribute {
                 namespace_prefix: prefix,
-                name,
+                name: name.clone(),
                 op: None,
                 value: None,
             }),
             _ => bail!(
                 "Unexpected token when parsing CSS selectors in parse_attrib: {:?}",
-                values_in_block.peek()
+                block.peek()
             ),
         }
     }
@@ -717,35 +1120,278 @@ impl SelectorParser {
     //     : ':' ':'? [ IDENT | functional_pseudo ]
     //     ;
     fn parse_pseudo(&mut self) -> Result<SimpleSelector> {
-        let v = self.input.clone().take(2).collect::<Vec<_>>();
-        match (v.first(), v.get(1)) {
+        let is_pseudo_element = match (self.peek(), self.peek_nth(1)) {
             (
                 Some(ComponentValue::PreservedToken(CssToken::Colon)),
                 Some(ComponentValue::PreservedToken(CssToken::Colon)),
             ) => {
                 // pseudo-element
-                self.input.next();
-                self.input.next();
+                self.next();
+                self.next();
+                true
             }
             (Some(ComponentValue::PreservedToken(CssToken::Colon)), _) => {
                 // pseudo-class
-                self.input.next();
+                self.next();
+                false
             }
             _ => bail!(
                 "Expected \":\" but found {:?} when parsing CSS selectors in parse_pseudo",
-                v.first()
+                self.peek()
+            ),
+        };
+
+        let v = self.next();
+        match v {
+            Some(ComponentValue::PreservedToken(CssToken::Ident(s))) => {
+                let name = s.to_ascii_lowercase();
+                // The pseudo-elements of CSS 2 can also be written with a single colon.
+                // https://www.w3.org/TR/selectors-4/#pseudo-element-syntax
+                if is_pseudo_element
+                    || matches!(
+                        name.as_str(),
+                        "before" | "after" | "first-line" | "first-letter"
+                    )
+                {
+                    Ok(SimpleSelector::PseudoElement(name))
+                } else {
+                    Ok(SimpleSelector::PseudoClass(s.clone()))
+                }
+            }
+            // todo: Support the functional pseudo-elements, e.g. `::part()`.
+            Some(ComponentValue::Function { .. }) if is_pseudo_element => bail!(
+                "Functional pseudo-elements are not supported: {:?}",
+                v
+            ),
+            Some(ComponentValue::Function { name, values }) => {
+                self.parse_functional_pseudo(name, values)
+            }
+            _ => bail!(
+                "Expected ident or function but found {:?} when parsing CSS selectors in parse_pseudo",
+                v
+            ),
+        }
+    }
+
+    // functional_pseudo
+    //   : FUNCTION S* expression ')'
+    //   ;
+    fn parse_functional_pseudo(
+        &mut self,
+        name: &str,
+        values: &'a [ComponentValue],
+    ) -> Result<SimpleSelector> {
+        let mut args = SelectorParser::new(values);
+        match name.to_ascii_lowercase().as_str() {
+            // :lang( [ <ident> | <string> ]# )
+            "lang" => {
+                let mut ranges = Vec::new();
+                loop {
+                    args.skip_whitespace();
+                    let v = args.next();
+                    let Some(ComponentValue::PreservedToken(
+                        CssToken::Ident(s) | CssToken::String(s),
+                    )) = v
+                    else {
+                        bail!("Expected ident or string but found {:?} when parsing CSS selectors in parse_functional_pseudo", v);
+                    };
+                    ranges.push(s.clone());
+                    args.skip_whitespace();
+                    match args.next() {
+                        Some(ComponentValue::PreservedToken(CssToken::Comma)) => continue,
+                        None => break,
+                        v => bail!("Expected \",\" or \")\" but found {:?} when parsing CSS selectors in parse_functional_pseudo", v),
+                    }
+                }
+                Ok(SimpleSelector::Lang(ranges))
+            }
+            // :dir( <ident> )
+            "dir" => {
+                args.skip_whitespace();
+                let v = args.next();
+                let Some(ComponentValue::PreservedToken(CssToken::Ident(dir))) = v else {
+                    bail!("Expected ident but found {:?} when parsing CSS selectors in parse_functional_pseudo", v);
+                };
+                args.skip_whitespace();
+                ensure!(
+                    args.peek().is_none(),
+                    "Unexpected token when parsing CSS selectors in parse_functional_pseudo: {:?}",
+                    args.peek()
+                );
+                Ok(SimpleSelector::Dir(dir.clone()))
+            }
+            // :not( <complex-selector-list> ) and :has( <relative-selector-list> )
+            name @ ("not" | "has") => {
+                let selectors = split_by_commas(values)
+                    .map(|values| match name {
+                        "has" => SelectorParser::new(values).parse_relative_selector(),
+                        _ => SelectorParser::new(values).parse_selector_argument(),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(SimpleSelector::PseudoClassFunction {
+                    name: name.to_string(),
+                    selectors,
+                })
+            }
+            // :is( <forgiving-selector-list> ) and :where( <forgiving-selector-list> ), where the invalid
+            // selectors are ignored instead of making the whole selector invalid.
+            // https://www.w3.org/TR/selectors-4/#forgiving-selector
+            name @ ("is" | "where") => Ok(SimpleSelector::PseudoClassFunction {
+                name: name.to_string(),
+                selectors: split_by_commas(values)
+                    .filter_map(|values| SelectorParser::new(values).parse_selector_argument().ok())
+                    .collect(),
+            }),
+            // :nth-child( <an+b> ), etc.
+            // todo: Support the `of <complex-real-selector-list>` argument of `:nth-child()`.
+            name @ ("nth-child" | "nth-last-child" | "nth-of-type" | "nth-last-of-type") => {
+                let (a, b) = args.parse_an_plus_b()?;
+                args.skip_whitespace();
+                ensure!(
+                    args.peek().is_none(),
+                    "Unexpected token when parsing CSS selectors in parse_functional_pseudo: {:?}",
+                    args.peek()
+                );
+                Ok(SimpleSelector::Nth {
+                    a,
+                    b,
+                    of_type: name.ends_with("of-type"),
+                    from_last: name.starts_with("nth-last"),
+                })
+            }
+            _ => bail!(
+                "Unsupported functional pseudo-class when parsing CSS selectors in parse_functional_pseudo: {:?}",
+                name
             ),
         }
+    }
 
-        // todo: handle functional-pseudo and pseudo-element
-        let v = self.input.next();
-        if let Some(ComponentValue::PreservedToken(CssToken::Ident(s))) = v {
-            Ok(SimpleSelector::PseudoClass(s))
-        } else {
-            bail!(
-                "Expected ident but found {:?} when parsing CSS selectors in parse_pseudo",
+    /// Parses a selector in the argument of a functional pseudo-class, which must be the whole argument.
+    fn parse_selector_argument(&mut self) -> Result<Selector> {
+        let selector = self.parse_selector()?;
+        ensure!(
+            self.peek().is_none(),
+            "Unexpected token when parsing CSS selectors in parse_selector_argument: {:?}",
+            self.peek()
+        );
+        Ok(selector)
+    }
+
+    /// Parses a relative selector, which can start with a combinator, and absolutizes it with `:scope`.
+    /// https://www.w3.org/TR/selectors-4/#relative
+    fn parse_relative_selector(&mut self) -> Result<Selector> {
+        let combinator = match self.peek() {
+            Some(ComponentValue::PreservedToken(CssToken::Delim('+' | '>' | '~'))) => {
+                self.parse_combinator()?
+            }
+            _ => Combinator::Whitespace,
+        };
+        Ok(Selector::Complex(
+            Box::new(Selector::Simple(vec![SimpleSelector::PseudoClass(
+                "scope".to_string(),
+            )])),
+            combinator,
+            Box::new(self.parse_selector_argument()?),
+        ))
+    }
+
+    /// Parses the `An+B` notation into `(A, B)`, e.g. `odd`, `3`, `-n+2` and `2n - 1`.
+    /// The tokenizer drops the `+` signs of numbers, so `n 2` is accepted as `n+2` too.
+    /// https://www.w3.org/TR/css-syntax-3/#anb-microsyntax
+    fn parse_an_plus_b(&mut self) -> Result<(i32, i32)> {
+        self.skip_whitespace();
+        let v = self.next();
+        let (a, rest) = match v {
+            Some(ComponentValue::PreservedToken(CssToken::Ident(ident)))
+                if ident.eq_ignore_ascii_case("odd") =>
+            {
+                return Ok((2, 1))
+            }
+            Some(ComponentValue::PreservedToken(CssToken::Ident(ident)))
+                if ident.eq_ignore_ascii_case("even") =>
+            {
+                return Ok((2, 0))
+            }
+            Some(ComponentValue::PreservedToken(CssToken::Number(NumericType::Integer(b)))) => {
+                return Ok((0, *b))
+            }
+            Some(ComponentValue::PreservedToken(CssToken::Dimension(
+                NumericType::Integer(a),
+                unit,
+            ))) => (*a, unit.to_ascii_lowercase()),
+            Some(ComponentValue::PreservedToken(CssToken::Ident(ident))) => {
+                match ident.to_ascii_lowercase().strip_prefix('-') {
+                    Some(rest) => (-1, rest.to_string()),
+                    None => (1, ident.to_ascii_lowercase()),
+                }
+            }
+            // `+n`, where no whitespace is allowed between `+` and `n`.
+            Some(ComponentValue::PreservedToken(CssToken::Delim('+'))) => match self.next() {
+                Some(ComponentValue::PreservedToken(CssToken::Ident(ident)))
+                    if !ident.starts_with('-') =>
+                {
+                    (1, ident.to_ascii_lowercase())
+                }
+                v => bail!("Expected ident after \"+\" but found {:?} when parsing CSS selectors in parse_an_plus_b", v),
+            },
+            v => bail!(
+                "Expected <an+b> but found {:?} when parsing CSS selectors in parse_an_plus_b",
                 v
-            );
+            ),
+        };
+
+        let b = match rest.as_str() {
+            // `An`, optionally followed by `+B`, `-B`, `+ B` or `- B`.
+            "n" => {
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(ComponentValue::PreservedToken(CssToken::Number(
+                        NumericType::Integer(b),
+                    ))) => {
+                        self.next();
+                        *b
+                    }
+                    Some(ComponentValue::PreservedToken(CssToken::Delim(sign @ ('+' | '-')))) => {
+                        self.next();
+                        let sign = if *sign == '-' { -1 } else { 1 };
+                        self.skip_whitespace();
+                        sign * self.parse_signless_integer()?
+                    }
+                    _ => 0,
+                }
+            }
+            // `An- B`
+            "n-" => {
+                self.skip_whitespace();
+                -self.parse_signless_integer()?
+            }
+            // `An-B`
+            rest => match rest.strip_prefix("n-") {
+                Some(digits)
+                    if !digits.is_empty() && digits.bytes().all(|c| c.is_ascii_digit()) =>
+                {
+                    -digits.parse::<i32>()?
+                }
+                _ => bail!(
+                    "Invalid <an+b> when parsing CSS selectors in parse_an_plus_b: {:?}",
+                    v
+                ),
+            },
+        };
+        Ok((a, b))
+    }
+
+    fn parse_signless_integer(&mut self) -> Result<i32> {
+        match self.next() {
+            Some(ComponentValue::PreservedToken(CssToken::Number(NumericType::Integer(b))))
+                if *b >= 0 =>
+            {
+                Ok(*b)
+            }
+            v => bail!(
+                "Expected an integer but found {:?} when parsing CSS selectors in parse_an_plus_b",
+                v
+            ),
         }
     }
 }
@@ -753,6 +1399,10 @@ impl SelectorParser {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::renderer::css::cssom::Rule;
+    use crate::renderer::html::dom::{DocumentTree, NodeType};
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
 
     #[test]
     fn parse_selector_with_combinator() {
@@ -766,7 +1416,7 @@ mod tests {
         ];
 
         assert_eq!(
-            SelectorParser::new(input).parse().unwrap(),
+            SelectorParser::new(&input).parse().unwrap(),
             vec![Selector::Complex(
                 Box::new(Selector::Simple(vec![SimpleSelector::Type {
                     namespace_prefix: None,
@@ -799,7 +1449,7 @@ mod tests {
             ComponentValue::PreservedToken(CssToken::Ident("b".to_string())),
         ];
         assert_eq!(
-            SelectorParser::new(input).parse().unwrap(),
+            SelectorParser::new(&input).parse().unwrap(),
             vec![
                 Selector::Complex(
                     Box::new(Selector::Simple(vec![SimpleSelector::Type {
@@ -869,7 +1519,7 @@ mod tests {
             ComponentValue::PreservedToken(CssToken::Ident("example".to_string())),
         ];
         assert_eq!(
-            SelectorParser::new(input).parse().unwrap(),
+            SelectorParser::new(&input).parse().unwrap(),
             vec![
                 Selector::Complex(
                     Box::new(Selector::Simple(vec![
@@ -932,7 +1582,7 @@ mod tests {
             },
         ];
         assert_eq!(
-            SelectorParser::new(input).parse().unwrap(),
+            SelectorParser::new(&input).parse().unwrap(),
             vec![Selector::Simple(vec![
                 SimpleSelector::Type {
                     namespace_prefix: None,
@@ -967,7 +1617,7 @@ mod tests {
             ComponentValue::PreservedToken(CssToken::Ident("class3".to_string())),
         ];
         assert_eq!(
-            SelectorParser::new(input).parse().unwrap(),
+            SelectorParser::new(&input).parse().unwrap(),
             vec![Selector::Simple(vec![
                 SimpleSelector::Type {
                     namespace_prefix: None,
@@ -980,18 +1630,76 @@ mod tests {
         );
     }
 
+    #[test]
+    fn look_ahead_in_slices() {
+        // ns|a  [ns|href|="en"]
+        let input = vec![
+            ComponentValue::PreservedToken(CssToken::Ident("ns".to_string())),
+            ComponentValue::PreservedToken(CssToken::Delim('|')),
+            ComponentValue::PreservedToken(CssToken::Ident("a".to_string())),
+            ComponentValue::PreservedToken(CssToken::Whitespace),
+            ComponentValue::PreservedToken(CssToken::Whitespace),
+            ComponentValue::SimpleBlock {
+                associated_token: CssToken::OpenSquareBracket,
+                values: vec![
+                    ComponentValue::PreservedToken(CssToken::Ident("ns".to_string())),
+                    ComponentValue::PreservedToken(CssToken::Delim('|')),
+                    ComponentValue::PreservedToken(CssToken::Ident("href".to_string())),
+                    ComponentValue::PreservedToken(CssToken::Delim('|')),
+                    ComponentValue::PreservedToken(CssToken::Delim('=')),
+                    ComponentValue::PreservedToken(CssToken::String("en".to_string())),
+                ],
+            },
+        ];
+
+        // The lookahead borrows the values without consuming them.
+        let mut parser = SelectorParser::new(&input);
+        assert_eq!(parser.peek_nth(2), Some(&input[2]));
+        assert_eq!(parser.peek_nth(input.len()), None);
+        assert_eq!(parser.peek(), Some(&input[0]));
+        assert_eq!(parser.next(), Some(&input[0]));
+        assert_eq!(parser.peek(), Some(&input[1]));
+        assert!(!parser.skip_whitespace());
+        parser.next();
+        parser.next();
+        assert!(parser.skip_whitespace());
+        assert_eq!(parser.next(), Some(&input[5]));
+        assert_eq!(parser.next(), None);
+        assert_eq!(parser.peek_nth(1), None);
+
+        // The lookahead does not see the values beyond the slice.
+        assert!(SelectorParser::new(&input[..2]).parse().is_err());
+        assert_eq!(
+            SelectorParser::new(&input[..3]).parse().unwrap(),
+            vec![Selector::Simple(vec![SimpleSelector::Type {
+                namespace_prefix: Some("ns".to_string()),
+                name: "a".to_string(),
+            }])]
+        );
+        // The namespace prefix is distinguished from DASHMATCH in the block.
+        assert_eq!(
+            SelectorParser::new(&input[5..]).parse().unwrap(),
+            vec![Selector::Simple(vec![SimpleSelector::Attribute {
+                namespace_prefix: Some("ns".to_string()),
+                name: "href".to_string(),
+                op: Some("|=".to_string()),
+                value: Some("en".to_string()),
+            }])]
+        );
+    }
+
     #[test]
     fn calculate_specificity() {
         // *
         let selector = Selector::Simple(vec![SimpleSelector::Universal(None)]);
-        assert_eq!(selector.calc_specificity(), 0);
+        assert_eq!(selector.calc_specificity(), Specificity(0, 0, 0));
 
         // LI
         let selector = Selector::Simple(vec![SimpleSelector::Type {
             namespace_prefix: None,
             name: "LI".to_string(),
         }]);
-        assert_eq!(selector.calc_specificity(), 1);
+        assert_eq!(selector.calc_specificity(), Specificity(0, 0, 1));
 
         // UL LI
         let selector = Selector::Complex(
@@ -1005,7 +1713,7 @@ mod tests {
                 name: "LI".to_string(),
             }])),
         );
-        assert_eq!(selector.calc_specificity(), 2);
+        assert_eq!(selector.calc_specificity(), Specificity(0, 0, 2));
 
         // UL OL + LI
         let selector = Selector::Complex(
@@ -1026,7 +1734,7 @@ mod tests {
                 }])),
             )),
         );
-        assert_eq!(selector.calc_specificity(), 3);
+        assert_eq!(selector.calc_specificity(), Specificity(0, 0, 3));
 
         // H1 + *[REL=up]
         let selector = Selector::Complex(
@@ -1045,7 +1753,7 @@ mod tests {
                 },
             ])),
         );
-        assert_eq!(selector.calc_specificity(), 11);
+        assert_eq!(selector.calc_specificity(), Specificity(0, 1, 1));
 
         // UL OL LI.red
         let selector = Selector::Complex(
@@ -1069,7 +1777,7 @@ mod tests {
                 ])),
             )),
         );
-        assert_eq!(selector.calc_specificity(), 13);
+        assert_eq!(selector.calc_specificity(), Specificity(0, 1, 3));
 
         // LI.red.level
         let selector = Selector::Simple(vec![
@@ -1080,17 +1788,347 @@ mod tests {
             SimpleSelector::Class("red".to_string()),
             SimpleSelector::Class("level".to_string()),
         ]);
-        assert_eq!(selector.calc_specificity(), 21);
+        assert_eq!(selector.calc_specificity(), Specificity(0, 2, 1));
 
         // #x34y
         let selector = Selector::Simple(vec![SimpleSelector::Id("x34y".to_string())]);
-        assert_eq!(selector.calc_specificity(), 100);
+        assert_eq!(selector.calc_specificity(), Specificity(1, 0, 0));
+
+        // .a.b.c.d.e.f.g.h.i.j.k < #x34y
+        let selector_with_classes = Selector::Simple(
+            ('a'..='k')
+                .map(|c| SimpleSelector::Class(c.to_string()))
+                .collect(),
+        );
+        assert_eq!(
+            selector_with_classes.calc_specificity(),
+            Specificity(0, 11, 0)
+        );
+        assert!(selector_with_classes.calc_specificity() < selector.calc_specificity());
 
         // todo: handle functional pseudo-class
         // #s12:not(FOO)
         // let selector = Selector::Simple(vec![
         //     SimpleSelector::Id("s12".to_string()),
         // ]);
-        // assert_eq!(selector.calc_specificity(), 101);
+        // assert_eq!(selector.calc_specificity(), Specificity(1, 0, 1));
+    }
+
+    #[test]
+    fn serialize_selectors() {
+        let css =
+            "h1[title=hello]>.myclass+p, ul  li~*.a, *, ns|*, *|a, |b, #x1:link, [lang|='en\"'], a[ns|href] {}";
+        let style_sheet = CssParser::new(&CssTokenizer::new(css).tokenize().unwrap())
+            .parse()
+            .unwrap();
+        let Rule::QualifiedRule(rule) = &style_sheet.rules[0] else {
+            unreachable!();
+        };
+        assert_eq!(
+            rule.selectors
+                .iter()
+                .map(|selector| selector.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "h1[title=\"hello\"] > .myclass + p",
+                "ul li ~ .a",
+                "*",
+                "ns|*",
+                "*|a",
+                "|b",
+                "#x1:link",
+                "[lang|=\"en\\\"\"]",
+                "a[ns|href]",
+            ]
+        );
+    }
+
+    #[test]
+    fn match_root_lang_dir() {
+        let html = "<html lang=en-US><head></head><body><div id=a dir=rtl><p id=b>x</p></div><div id=c lang=fr dir=auto>\u{05D0}</div><p id=d>y</p></body></html>";
+        let tree =
+            DocumentTree::build(HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap().0)
+                .unwrap();
+        let get_elm = |tag_name: &str, id: Option<&str>| {
+            tree.get_dfs_iter()
+                .find(|node| match &node.borrow().node_type {
+                    NodeType::Element(elm) => {
+                        elm.tag_name == tag_name && elm.get_attribute("id") == id
+                    }
+                    _ => false,
+                })
+                .unwrap()
+        };
+        let parse_selector = |css: &str| {
+            let style_sheet = CssParser::new(&CssTokenizer::new(css).tokenize().unwrap())
+                .parse()
+                .unwrap();
+            let Rule::QualifiedRule(rule) = &style_sheet.rules[0] else {
+                unreachable!();
+            };
+            rule.selectors[0].clone()
+        };
+
+        let html_elm = get_elm("html", None);
+        let (a, b, c, d) = (
+            get_elm("div", Some("a")),
+            get_elm("p", Some("b")),
+            get_elm("div", Some("c")),
+            get_elm("p", Some("d")),
+        );
+
+        let root = parse_selector(":root {}");
+        assert!(root.matches(&html_elm));
+        assert!(!root.matches(&a));
+
+        let lang_en = parse_selector(":lang(en) {}");
+        assert!(lang_en.matches(&html_elm));
+        assert!(lang_en.matches(&b));
+        assert!(!lang_en.matches(&c));
+        assert!(parse_selector(":lang(de, \"*-US\") {}").matches(&d));
+        assert!(!parse_selector(":lang(en-GB) {}").matches(&d));
+
+        let dir_rtl = parse_selector(":dir(rtl) {}");
+        assert!(dir_rtl.matches(&a));
+        assert!(dir_rtl.matches(&b));
+        assert!(dir_rtl.matches(&c));
+        assert!(!dir_rtl.matches(&d));
+        assert!(parse_selector(":dir(ltr) {}").matches(&d));
+
+        assert!(parse_selector("[DIR] {}").matches(&a));
+        assert!(!parse_selector("[dir] {}").matches(&b));
+        assert!(parse_selector("[lang|=fr] {}").matches(&c));
+        assert!(parse_selector("[dir^=au] {}").matches(&c));
+        assert!(!parse_selector("[dir=\"\"] {}").matches(&c));
+    }
+
+    #[test]
+    fn match_duplicate_attributes() {
+        // Only the first of the duplicate attributes is kept.
+        let html =
+            "<html><head></head><body><div id=a class=x ID=b class=\"y z\"></div></body></html>";
+        let tree =
+            DocumentTree::build(HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap().0)
+                .unwrap();
+        let div = tree
+            .get_dfs_iter()
+            .find(|node| matches!(&node.borrow().node_type, NodeType::Element(elm) if elm.tag_name == "div"))
+            .unwrap();
+        let matches = |css: &str| {
+            let style_sheet = CssParser::new(&CssTokenizer::new(css).tokenize().unwrap())
+                .parse()
+                .unwrap();
+            let Rule::QualifiedRule(rule) = &style_sheet.rules[0] else {
+                unreachable!();
+            };
+            rule.selectors[0].matches(&div)
+        };
+
+        assert!(matches("#a {}"));
+        assert!(matches("div.x {}"));
+        assert!(!matches("#b {}"));
+        assert!(!matches(".y {}"));
+        assert!(!matches(".z {}"));
+    }
+
+    #[test]
+    fn match_defined() {
+        let html = "<html><head></head><body><my-elm></my-elm><p is=\"my-p\"></p></body></html>";
+        let tree =
+            DocumentTree::build(HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap().0)
+                .unwrap();
+        let style_sheet = CssParser::new(&CssTokenizer::new(":defined {}").tokenize().unwrap())
+            .parse()
+            .unwrap();
+        let Rule::QualifiedRule(rule) = &style_sheet.rules[0] else {
+            unreachable!();
+        };
+        let defined = &rule.selectors[0];
+
+        let matched = tree
+            .get_dfs_iter()
+            .filter(|node| defined.matches(node))
+            .map(|node| node.borrow().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            matched,
+            vec![
+                "Elem( tag: <html> )",
+                "Elem( tag: <head> )",
+                "Elem( tag: <body> )"
+            ]
+        );
+    }
+
+    #[test]
+    fn query_selectors() {
+        let html = "<html><head></head><body><div id=\"a\"><p class=\"x\">1</p><b>2</b></div><p class=\"x\">3</p></body></html>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let to_strings = |nodes: Vec<Rc<RefCell<DomNode>>>| {
+            nodes
+                .iter()
+                .map(|node| node.borrow().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            to_strings(DomNode::query_selector_all(&document, " div > .x, b ").unwrap()),
+            vec![
+                "Elem( tag: <p>, attr: [\"class\"=\"x\"] )",
+                "Elem( tag: <b> )"
+            ]
+        );
+        assert_eq!(
+            to_strings(DomNode::query_selector_all(&document, "p.x").unwrap()).len(),
+            2
+        );
+        let div = DomNode::query_selector(&document, "#a").unwrap().unwrap();
+        assert_eq!(
+            to_strings(DomNode::query_selector_all(&div, "div, p").unwrap()),
+            vec!["Elem( tag: <p>, attr: [\"class\"=\"x\"] )"]
+        );
+        assert!(DomNode::query_selector(&div, "body").unwrap().is_none());
+        assert!(DomNode::query_selector(&document, "").is_err());
+        assert!(DomNode::query_selector(&document, "div >").is_err());
+    }
+
+    #[test]
+    fn match_structural_pseudo_classes() {
+        let html = "<ul><li id=a></li><p id=b></p><li id=c></li><li id=d></li><li id=e></li></ul>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let ids = |selector: &str| {
+            DomNode::query_selector_all(&document, selector)
+                .unwrap()
+                .iter()
+                .filter_map(|node| match &node.borrow().node_type {
+                    NodeType::Element(elm) => elm.get_attribute("id").map(str::to_string),
+                    _ => None,
+                })
+                .collect::<String>()
+        };
+
+        assert_eq!(ids("ul > :first-child, ul > :last-child"), "ae");
+        assert_eq!(ids("ul > :only-of-type"), "b");
+        assert_eq!(ids("li:first-of-type, li:last-of-type"), "ae");
+        assert_eq!(ids(":nth-child(odd)"), "ace");
+        assert_eq!(ids(":nth-child(2n)"), "bd");
+        assert_eq!(ids(":nth-child(-n+2)"), "ab");
+        assert_eq!(ids(":nth-child(n + 4)"), "de");
+        assert_eq!(ids(":nth-child(3n-1)"), "be");
+        assert_eq!(ids(":nth-last-child(2)"), "d");
+        assert_eq!(ids("li:nth-of-type(even)"), "ce");
+        assert_eq!(ids("li:nth-last-of-type(-n+1)"), "e");
+        for selector in [
+            ":nth-child(n-)",
+            ":nth-child(2n 1 2)",
+            ":nth-child(1.5)",
+            ":nth-child(+ n)",
+        ] {
+            assert!(SelectorParser::parse_str(selector).is_err(), "{}", selector);
+        }
+
+        let selectors = SelectorParser::parse_str(
+            ":nth-child(2n+1), :nth-last-of-type(-n-2), :nth-child( 3 ), :nth-of-type(N)",
+        )
+        .unwrap();
+        assert_eq!(
+            selectors
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                ":nth-child(2n+1)",
+                ":nth-last-of-type(-n-2)",
+                ":nth-child(3)",
+                ":nth-of-type(n)"
+            ]
+        );
+        assert_eq!(selectors[0].calc_specificity(), Specificity(0, 1, 0));
+    }
+
+    #[test]
+    fn match_structural_selectors() {
+        let html = "<html><body><ul><li id=a>1</li><p id=b></p><li id=c><b></b></li><li id=d></li></ul><div id=e><p id=g></p></div><p id=f></p></body></html>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let ids = |selector: &str| {
+            DomNode::query_selector_all(&document, selector)
+                .unwrap()
+                .iter()
+                .filter_map(|node| match &node.borrow().node_type {
+                    NodeType::Element(elm) => elm.get_attribute("id").map(str::to_string),
+                    _ => None,
+                })
+                .collect::<String>()
+        };
+
+        assert_eq!(ids("li:first-child, li:last-of-type"), "ad");
+        assert_eq!(ids("li:nth-child(2n+1)"), "ac");
+        assert_eq!(ids("p:only-of-type"), "bgf");
+        assert_eq!(ids("ul > li + li"), "d");
+        assert_eq!(ids("p ~ li"), "cd");
+        assert_eq!(ids("body :has(> b)"), "c");
+        assert_eq!(ids("div:has(+ p)"), "e");
+        assert_eq!(ids("body p:not(#b)"), "gf");
+    }
+
+    #[test]
+    fn match_functional_pseudo_classes() {
+        let html = "<div id=a class=x><p id=b></p></div><div id=c><span id=d><p id=e class=x></p></span></div><div id=f></div>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let ids = |selector: &str| {
+            DomNode::query_selector_all(&document, selector)
+                .unwrap()
+                .iter()
+                .filter_map(|node| match &node.borrow().node_type {
+                    NodeType::Element(elm) => elm.get_attribute("id").map(str::to_string),
+                    _ => None,
+                })
+                .collect::<String>()
+        };
+
+        assert_eq!(ids("div:not(.x)"), "cf");
+        assert_eq!(ids("p:not(div > p, #e)"), "");
+        assert_eq!(ids(":is(div, span) > p"), "be");
+        assert_eq!(ids(":where(.x, #d) p"), "be");
+        // The invalid selectors in `:is()` are ignored.
+        assert_eq!(ids(":is(#b, 1, ) "), "b");
+        assert_eq!(ids("div:has(p)"), "ac");
+        assert_eq!(ids("div:has(> p)"), "a");
+        assert_eq!(ids("div:has(span .x)"), "c");
+        assert_eq!(ids("div:has(+ div:not(:has(p)))"), "c");
+        assert_eq!(ids("div:has(~ #f)"), "ac");
+        assert_eq!(ids("div :not(:has(p, span))"), "be");
+        assert_eq!(ids("div:not(.x) :is(p)"), "e");
+        assert!(SelectorParser::parse_str(":not(p, 1)").is_err());
+        assert!(SelectorParser::parse_str(":has(> )").is_err());
+
+        let selectors = SelectorParser::parse_str(
+            ":not( #a , p ), :is(.x), :where(#a, p), :has( > p,span ~ b)",
+        )
+        .unwrap();
+        assert_eq!(
+            selectors
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                ":not(#a, p)",
+                ":is(.x)",
+                ":where(#a, p)",
+                ":has(> p, span ~ b)"
+            ]
+        );
+        assert_eq!(
+            selectors
+                .iter()
+                .map(Selector::calc_specificity)
+                .collect::<Vec<_>>(),
+            [
+                Specificity(1, 0, 0),
+                Specificity(0, 1, 0),
+                Specificity(0, 0, 0),
+                Specificity(0, 0, 2)
+            ]
+        );
     }
 }
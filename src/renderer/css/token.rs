@@ -2,6 +2,10 @@ use std::vec;
 
 use anyhow::{ensure, Ok, Result};
 
+use crate::renderer::parse_error::{
+    ParseErrorReport, SharedParseErrorHandler, StderrParseErrorHandler,
+};
+use crate::renderer::source::{SourceLocator, SourcePosition, SourceSpan};
 use crate::utils::TokenIterator;
 
 /// https://www.w3.org/TR/css-syntax-3/#tokenization
@@ -36,6 +40,40 @@ pub enum CssToken {
     Eof,
 }
 
+impl CssToken {
+    /// Returns the name of the type of the token.
+    /// https://www.w3.org/TR/css-syntax-3/#tokenization
+    pub fn get_type_name(&self) -> &'static str {
+        match self {
+            Self::Ident(_) => "ident",
+            Self::Function(_) => "function",
+            Self::AtKeyword(_) => "at-keyword",
+            Self::Hash(..) => "hash",
+            Self::String(_) => "string",
+            Self::BadString => "bad-string",
+            Self::Url(_) => "url",
+            Self::BadUrl => "bad-url",
+            Self::Delim(_) => "delim",
+            Self::Number(_) => "number",
+            Self::Percentage(_) => "percentage",
+            Self::Dimension(..) => "dimension",
+            Self::Whitespace => "whitespace",
+            Self::Cdo => "CDO",
+            Self::Cdc => "CDC",
+            Self::Colon => "colon",
+            Self::Semicolon => "semicolon",
+            Self::Comma => "comma",
+            Self::OpenSquareBracket => "[",
+            Self::CloseSquareBracket => "]",
+            Self::OpenParenthesis => "(",
+            Self::CloseParenthesis => ")",
+            Self::OpenCurlyBrace => "{",
+            Self::CloseCurlyBrace => "}",
+            Self::Eof => "EOF",
+        }
+    }
+}
+
 /// The default hash type is unrestricted.
 #[derive(Clone, Debug, PartialEq)]
 pub enum HashType {
@@ -59,20 +97,71 @@ pub enum NumericType {
 #[derive(Debug)]
 pub struct CssTokenizer {
     input: TokenIterator<char>,
+    error_handler: SharedParseErrorHandler,
+    locator: SourceLocator,
+    span_locator: SourceLocator,
 }
 
 impl CssTokenizer {
     pub fn new(css: &str) -> Self {
         let input = TokenIterator::new(&css.chars().collect::<Vec<char>>());
-        Self { input }
+        Self {
+            input,
+            error_handler: StderrParseErrorHandler::new_shared(),
+            locator: SourceLocator::default(),
+            span_locator: SourceLocator::default(),
+        }
+    }
+
+    pub fn set_error_handler(&mut self, handler: SharedParseErrorHandler) -> &mut Self {
+        self.error_handler = handler;
+        self
+    }
+
+    /// Sets the position of the start of the CSS in the enclosing source text, e.g. the position of
+    /// the contents of a `style` element in the HTML document.
+    pub fn set_source_start(&mut self, start: SourcePosition) -> &mut Self {
+        self.locator = SourceLocator::new(start);
+        self.span_locator = SourceLocator::new(start);
+        self
+    }
+
+    /// Reports a parse error at the last consumed character.
+    /// https://www.w3.org/TR/css-syntax-3/#error-handling
+    fn report_error(&mut self, code: &str) {
+        let (position, context) = self.locator.locate(
+            self.input.get_items(),
+            self.input.get_pos().saturating_sub(1),
+        );
+        self.error_handler.borrow_mut().handle(ParseErrorReport {
+            code: code.to_string(),
+            detail: None,
+            position: Some(position),
+            context: Some(context),
+        });
     }
 
     /// https://www.w3.org/TR/css-syntax-3/#tokenization
+    #[tracing::instrument(skip_all)]
     pub fn tokenize(&mut self) -> Result<Vec<CssToken>> {
+        Ok(self
+            .tokenize_with_spans()?
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect())
+    }
+
+    /// Tokenizes the CSS, and returns the tokens with the spans of the source text from which they were created.
+    /// Comments are not included in the spans.
+    pub fn tokenize_with_spans(&mut self) -> Result<Vec<(CssToken, SourceSpan)>> {
         let mut tokens = Vec::new();
         loop {
+            self.consume_comments()?;
+            let start = self.input.get_pos();
             let token = self.consume_token()?;
-            tokens.push(token.clone());
+            let end = self.input.get_pos();
+            let span = self.span_locator.span(self.input.get_items(), start, end);
+            tokens.push((token.clone(), span));
             if token == CssToken::Eof {
                 break;
             }
@@ -193,7 +282,7 @@ impl CssTokenizer {
                         self.input.rewind(1);
                         Ok(self.consume_ident_like_sequence())
                     } else {
-                        eprintln!("parse error: invalid escape in consume_token");
+                        self.report_error("invalid-escape");
                         Ok(CssToken::Delim(c))
                     }
                 }
@@ -225,7 +314,7 @@ impl CssTokenizer {
                         return CssToken::String(string);
                     }
                     '\n' => {
-                        eprintln!("parse error: newline in consume_string_token");
+                        self.report_error("newline-in-string");
                         self.input.rewind(1);
                         return CssToken::BadString;
                     }
@@ -243,7 +332,7 @@ impl CssTokenizer {
                     }
                 },
                 None => {
-                    eprintln!("parse error: EOF in consume_string_token");
+                    self.report_error("eof-in-string");
                     return CssToken::String(string);
                 }
             }
@@ -342,7 +431,7 @@ impl CssTokenizer {
                     | '\u{000E}'..='\u{001F}'
                     | '\u{007F}',
                 ) => {
-                    eprintln!("parse error: invalid character in consume_url_token");
+                    self.report_error("invalid-character-in-url");
                     self.consume_remnants_of_bad_url();
                     return CssToken::BadUrl;
                 }
@@ -350,7 +439,7 @@ impl CssTokenizer {
                     if Self::is_valid_escape(&[self.input.get_last_consumed(), self.input.peek()]) {
                         url.push(self.consume_escaped_char());
                     } else {
-                        eprintln!("parse error: invalid escape in consume_url_token");
+                        self.report_error("invalid-escape-in-url");
                         self.consume_remnants_of_bad_url();
                         return CssToken::BadUrl;
                     }
@@ -360,7 +449,7 @@ impl CssTokenizer {
                         self.input.next();
                     }
                     if self.input.peek().is_none() {
-                        eprintln!("parse error: EOF in consume_url_token");
+                        self.report_error("eof-in-url");
                     }
                     if let Some(')') | None = self.input.peek() {
                         self.input.next();
@@ -370,7 +459,7 @@ impl CssTokenizer {
                     return CssToken::BadUrl;
                 }
                 None => {
-                    eprintln!("parse error: EOF in consume_url_token");
+                    self.report_error("eof-in-url");
                     return CssToken::Url(url);
                 }
                 _ => {
@@ -408,7 +497,7 @@ impl CssTokenizer {
                 unimplemented!()
             }
             None => {
-                eprintln!("parse error: EOF in consume_escaped_char");
+                self.report_error("eof-in-escape");
                 '\u{FFFD}'
             }
             _ => *self.input.get_last_consumed().unwrap(),
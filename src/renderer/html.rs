@@ -1,3 +1,19 @@
+pub mod char_ref;
+// The async clipboard is only used by scripts, which are not run yet.
+#[allow(dead_code)]
+pub(crate) mod clipboard;
 pub mod dom;
+pub mod editing;
+pub mod encoding;
+pub mod form;
+pub mod image_map;
+pub mod interaction;
+pub mod lifecycle;
+pub mod live_region;
+pub mod metadata;
+pub mod mutation;
+pub mod parse_thread;
 pub mod parser;
+pub mod serializer;
+pub mod spellcheck;
 pub mod token;
@@ -0,0 +1,133 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use anyhow::{bail, ensure, Result};
+
+/// The system clipboard, which is read asynchronously since the text may be provided by another process.
+pub trait ClipboardBackend: fmt::Debug {
+    /// Reads the text on the clipboard, and calls `on_read` with it once it is available. `None` is passed
+    /// if the clipboard has no text.
+    fn read_text(&self, on_read: Box<dyn FnOnce(Option<String>)>);
+    fn write_text(&self, text: &str);
+}
+
+/// Asks the user whether the page of the origin may use a powerful feature, such as reading the clipboard.
+pub trait PermissionPrompt: fmt::Debug {
+    fn request(&self, origin: &str, permission_name: &str) -> bool;
+}
+
+/// The clipboard exposed to scripts as `navigator.clipboard`. Reading requires the `clipboard-read`
+/// permission, which is asked once per origin, and writing requires transient user activation, e.g. a click.
+/// todo: Expose this to scripts and store the decisions with the site settings.
+/// https://w3c.github.io/clipboard-apis/#async-clipboard-api
+#[derive(Debug)]
+pub struct AsyncClipboard {
+    backend: Rc<dyn ClipboardBackend>,
+    prompt: Rc<dyn PermissionPrompt>,
+    /// Whether reading the clipboard is allowed for each origin, as decided by the user.
+    read_permissions: RefCell<HashMap<String, bool>>,
+}
+
+impl AsyncClipboard {
+    pub fn new(backend: Rc<dyn ClipboardBackend>, prompt: Rc<dyn PermissionPrompt>) -> Self {
+        Self {
+            backend,
+            prompt,
+            read_permissions: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Reads the text on the clipboard for the page of the origin, and calls `on_read` with it. The
+    /// permission is checked before reading, so an error is returned immediately if it is denied.
+    /// https://w3c.github.io/clipboard-apis/#dom-clipboard-readtext
+    pub fn read_text(&self, origin: &str, on_read: impl FnOnce(String) + 'static) -> Result<()> {
+        let granted = *self
+            .read_permissions
+            .borrow_mut()
+            .entry(origin.to_string())
+            .or_insert_with(|| self.prompt.request(origin, "clipboard-read"));
+        if !granted {
+            bail!("NotAllowedError: Reading the clipboard is not allowed.");
+        }
+        self.backend
+            .read_text(Box::new(move |text| on_read(text.unwrap_or_default())));
+        Ok(())
+    }
+
+    /// Writes the text to the clipboard. Pages can't overwrite the clipboard without user interaction.
+    /// https://w3c.github.io/clipboard-apis/#dom-clipboard-writetext
+    pub fn write_text(&self, text: &str, has_transient_activation: bool) -> Result<()> {
+        ensure!(
+            has_transient_activation,
+            "NotAllowedError: Writing the clipboard requires user activation."
+        );
+        self.backend.write_text(text);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct MockClipboard {
+        text: RefCell<Option<String>>,
+    }
+
+    impl ClipboardBackend for MockClipboard {
+        fn read_text(&self, on_read: Box<dyn FnOnce(Option<String>)>) {
+            on_read(self.text.borrow().clone());
+        }
+
+        fn write_text(&self, text: &str) {
+            self.text.replace(Some(text.to_string()));
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct MockPrompt {
+        requests: RefCell<Vec<String>>,
+    }
+
+    impl PermissionPrompt for MockPrompt {
+        fn request(&self, origin: &str, permission_name: &str) -> bool {
+            assert_eq!(permission_name, "clipboard-read");
+            self.requests.borrow_mut().push(origin.to_string());
+            origin == "https://example.com"
+        }
+    }
+
+    #[test]
+    fn read_and_write_clipboard() {
+        let backend = Rc::new(MockClipboard::default());
+        let prompt = Rc::new(MockPrompt::default());
+        let clipboard = AsyncClipboard::new(Rc::clone(&backend) as _, Rc::clone(&prompt) as _);
+
+        assert!(clipboard.write_text("a", false).is_err());
+        assert_eq!(*backend.text.borrow(), None);
+        clipboard.write_text("a", true).unwrap();
+
+        let read = Rc::new(RefCell::new(Vec::new()));
+        for origin in [
+            "https://example.com",
+            "http://evil.test",
+            "https://example.com",
+        ] {
+            let read = Rc::clone(&read);
+            let result = clipboard.read_text(origin, move |text| read.borrow_mut().push(text));
+            assert_eq!(result.is_ok(), origin == "https://example.com");
+        }
+        assert!(clipboard
+            .read_text("http://evil.test", |_| unreachable!())
+            .is_err());
+        assert_eq!(*read.borrow(), ["a", "a"]);
+        // The user is asked only once per origin.
+        assert_eq!(
+            *prompt.requests.borrow(),
+            ["https://example.com", "http://evil.test"]
+        );
+    }
+}
@@ -1,11 +1,15 @@
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt;
 use std::rc::{Rc, Weak};
 
-use anyhow::{ensure, Result};
+use anyhow::{bail, ensure, Result};
 
 use crate::renderer::css::cssom::StyleSheet;
-use crate::renderer::style::style_model::RenderTree;
+use crate::renderer::css::media::MediaEnvironment;
+use crate::renderer::css::selector::{Selector, SelectorParser, SimpleSelector};
+use crate::renderer::source::SourceSpan;
+use crate::renderer::style::style_model::{RenderTree, StyleCache};
 use crate::utils::PrintableTree;
 
 /// https://dom.spec.whatwg.org/#node
@@ -16,16 +20,56 @@ pub struct DomNode {
     pub parent: Option<Weak<RefCell<Self>>>,
     pub prev_sib: Option<Weak<RefCell<Self>>>,
     pub next_sibling: Option<Rc<RefCell<Self>>>,
+    /// The shadow root attached to the node if the node is a shadow host.
+    /// https://dom.spec.whatwg.org/#concept-element-shadow-root
+    pub shadow_root: Option<Rc<RefCell<Self>>>,
+    /// The contents of a `template` element.
+    /// https://html.spec.whatwg.org/multipage/scripting.html#template-contents
+    pub template_contents: Option<Rc<RefCell<Self>>>,
+    /// The `form` element with which the form-associated element is associated.
+    /// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#form-owner
+    pub form_owner: Option<Weak<RefCell<Self>>>,
+    /// The span of the source text from which the node was created. This is `None` for the nodes
+    /// which have no corresponding source text, such as implied elements.
+    pub source_span: Option<SourceSpan>,
+    /// The parts of the rendering which must be recomputed since the tree was mutated.
+    pub dirty: DirtyFlags,
+    /// The states of the element matched by the dynamic pseudo-classes.
+    pub state: ElementState,
+    /// The scroll position (x, y) of the element if its box is a scroll container, which is kept within its
+    /// scrollable overflow area when the box is laid out.
+    /// https://drafts.csswg.org/cssom-view/#dom-element-scrolltop
+    pub scroll_offset: (f32, f32),
+    /// The index of the elements by their IDs if the node is a document whose tree has been built, which is
+    /// updated as the tree is mutated.
+    pub id_index: Option<IdIndex>,
+    /// The target element if the node is a document, whose state is cleared when the target changes.
+    /// https://html.spec.whatwg.org/multipage/browsing-the-web.html#target-element
+    pub target_element: Option<Weak<RefCell<DomNode>>>,
+    /// Whether the image is lazily loaded and has approached the viewport, after which its resource is
+    /// fetched as if it was not lazily loaded.
+    /// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#lazy-load-resumption-steps
+    pub lazy_load_resumed: bool,
 }
 
 impl Default for DomNode {
     fn default() -> Self {
         Self {
-            node_type: NodeType::Document,
+            node_type: NodeType::Document(DocumentMode::NoQuirks),
             children: Vec::new(),
             parent: None,
             prev_sib: None,
             next_sibling: None,
+            shadow_root: None,
+            template_contents: None,
+            form_owner: None,
+            source_span: None,
+            dirty: DirtyFlags::default(),
+            state: ElementState::default(),
+            scroll_offset: (0.0, 0.0),
+            id_index: None,
+            target_element: None,
+            lazy_load_resumed: false,
         }
     }
 }
@@ -39,7 +83,52 @@ impl DomNode {
     }
 
     pub fn append_child(node_ref: &Rc<RefCell<Self>>, child: Self) -> Rc<RefCell<Self>> {
-        let child = Rc::new(RefCell::new(child));
+        Self::append_child_node(node_ref, Rc::new(RefCell::new(child)))
+    }
+
+    /// Appends the existing node to the children of `node_ref`, removing it from its previous parent if any.
+    /// https://dom.spec.whatwg.org/#concept-node-append
+    pub fn append_child_node(
+        node_ref: &Rc<RefCell<Self>>,
+        child: Rc<RefCell<Self>>,
+    ) -> Rc<RefCell<Self>> {
+        Self::insert_child_node(node_ref, child, None)
+    }
+
+    /// Inserts the existing node into the children of `node_ref` before `before`, or appends it if `before` is `None`.
+    /// The node is removed from its previous parent if any.
+    /// https://dom.spec.whatwg.org/#concept-node-insert
+    pub fn insert_child_node(
+        node_ref: &Rc<RefCell<Self>>,
+        child: Rc<RefCell<Self>>,
+        before: Option<&Rc<RefCell<Self>>>,
+    ) -> Rc<RefCell<Self>> {
+        Self::detach(&child);
+        if let Some(before) = before {
+            let index = node_ref
+                .borrow()
+                .children
+                .iter()
+                .position(|c| Rc::ptr_eq(c, before))
+                .expect("the reference node must be a child of the parent");
+            let prev_sib = before.borrow_mut().prev_sib.take();
+            if let Some(prev_sib) = prev_sib.as_ref().and_then(|p| p.upgrade()) {
+                prev_sib.borrow_mut().next_sibling = Some(Rc::clone(&child));
+            }
+            before.borrow_mut().prev_sib = Some(Rc::downgrade(&child));
+            {
+                let mut c = child.borrow_mut();
+                c.parent = Some(Rc::downgrade(node_ref));
+                c.prev_sib = prev_sib;
+                c.next_sibling = Some(Rc::clone(before));
+            }
+            node_ref
+                .borrow_mut()
+                .children
+                .insert(index, Rc::clone(&child));
+            return child;
+        }
+
         child.borrow_mut().parent = Some(Rc::downgrade(node_ref));
         if node_ref.borrow().children.is_empty() {
             child.borrow_mut().prev_sib = None;
@@ -53,6 +142,182 @@ impl DomNode {
         child
     }
 
+    /// Removes the node from its parent, and relinks its siblings.
+    /// https://dom.spec.whatwg.org/#concept-node-remove
+    pub fn detach(node_ref: &Rc<RefCell<Self>>) {
+        let Some(parent) = Self::get_parent(node_ref) else {
+            return;
+        };
+        let prev_sib = node_ref.borrow_mut().prev_sib.take();
+        let next_sibling = node_ref.borrow_mut().next_sibling.take();
+        if let Some(prev_sib) = prev_sib.as_ref().and_then(|p| p.upgrade()) {
+            prev_sib.borrow_mut().next_sibling = next_sibling.clone();
+        }
+        if let Some(next_sibling) = &next_sibling {
+            next_sibling.borrow_mut().prev_sib = prev_sib;
+        }
+        parent
+            .borrow_mut()
+            .children
+            .retain(|child| !Rc::ptr_eq(child, node_ref));
+        node_ref.borrow_mut().parent = None;
+    }
+
+    /// Returns the parent of the node, which is `None` for the root of a tree.
+    /// https://dom.spec.whatwg.org/#concept-tree-parent
+    pub fn get_parent(node_ref: &Rc<RefCell<Self>>) -> Option<Rc<RefCell<Self>>> {
+        node_ref.borrow().parent.as_ref().and_then(|p| p.upgrade())
+    }
+
+    /// Returns the root of the tree the node is in, which is a document, a shadow root, or a detached node.
+    /// https://dom.spec.whatwg.org/#concept-tree-root
+    pub fn get_root(node_ref: &Rc<RefCell<Self>>) -> Rc<RefCell<Self>> {
+        let mut node = Rc::clone(node_ref);
+        loop {
+            let Some(parent) = Self::get_parent(&node) else {
+                break;
+            };
+            node = parent;
+        }
+        node
+    }
+
+    /// Returns the descendants of the node in tree order.
+    /// https://dom.spec.whatwg.org/#concept-tree-descendant
+    pub fn get_descendants(
+        node_ref: &Rc<RefCell<Self>>,
+    ) -> impl Iterator<Item = Rc<RefCell<Self>>> {
+        let mut stack = node_ref
+            .borrow()
+            .children
+            .iter()
+            .rev()
+            .map(Rc::clone)
+            .collect::<Vec<_>>();
+        std::iter::from_fn(move || {
+            let current = stack.pop()?;
+            stack.extend(current.borrow().children.iter().rev().map(Rc::clone));
+            Some(current)
+        })
+    }
+
+    /// Returns the first descendant element in tree order which matches the selector list.
+    /// https://dom.spec.whatwg.org/#dom-parentnode-queryselector
+    pub fn query_selector(
+        node_ref: &Rc<RefCell<Self>>,
+        selectors: &str,
+    ) -> Result<Option<Rc<RefCell<Self>>>> {
+        let selectors = SelectorParser::parse_str(selectors)?;
+        Ok(Self::get_descendants(node_ref).find(|node| Self::matches_any(node, &selectors)))
+    }
+
+    /// Returns the descendant elements which match the selector list, in tree order.
+    /// https://dom.spec.whatwg.org/#dom-parentnode-queryselectorall
+    pub fn query_selector_all(
+        node_ref: &Rc<RefCell<Self>>,
+        selectors: &str,
+    ) -> Result<Vec<Rc<RefCell<Self>>>> {
+        let selectors = SelectorParser::parse_str(selectors)?;
+        if matches!(node_ref.borrow().node_type, NodeType::Document(_)) {
+            let tree = DocumentTree {
+                root: Rc::clone(node_ref),
+            };
+            if let Some(nodes) = tree.get_elements_by_simple_selectors(&selectors) {
+                return Ok(nodes);
+            }
+        }
+        Ok(Self::get_descendants(node_ref)
+            .filter(|node| Self::matches_any(node, &selectors))
+            .collect())
+    }
+
+    fn matches_any(node_ref: &Rc<RefCell<Self>>, selectors: &[Selector]) -> bool {
+        matches!(node_ref.borrow().node_type, NodeType::Element(_))
+            && selectors.iter().any(|selector| selector.matches(node_ref))
+    }
+
+    /// Returns the previous sibling of the node.
+    /// https://dom.spec.whatwg.org/#concept-tree-previous-sibling
+    pub fn get_prev_sibling(node_ref: &Rc<RefCell<Self>>) -> Option<Rc<RefCell<Self>>> {
+        node_ref
+            .borrow()
+            .prev_sib
+            .as_ref()
+            .and_then(|p| p.upgrade())
+    }
+
+    /// Attaches a new shadow root to the host element and returns it.
+    /// https://dom.spec.whatwg.org/#concept-attach-a-shadow-root
+    pub fn attach_shadow(
+        host_ref: &Rc<RefCell<Self>>,
+        mode: ShadowRootMode,
+    ) -> Result<Rc<RefCell<Self>>> {
+        let NodeType::Element(host) = host_ref.borrow().node_type.clone() else {
+            bail!("A shadow root can only be attached to an element.");
+        };
+        ensure!(
+            is_valid_shadow_host_name(&host.tag_name),
+            "<{}> is not a valid shadow host.",
+            host.tag_name
+        );
+        ensure!(
+            host_ref.borrow().shadow_root.is_none(),
+            "<{}> is already a shadow host.",
+            host.tag_name
+        );
+        let shadow_root = Rc::new(RefCell::new(Self::new(NodeType::ShadowRoot(mode))));
+        host_ref.borrow_mut().shadow_root = Some(Rc::clone(&shadow_root));
+        Ok(shadow_root)
+    }
+
+    /// Returns the slottables of the shadow host that are assigned to the slot in its shadow tree.
+    /// A slottable is assigned to the first slot in tree order whose name is the same as the slottable's `slot` attribute.
+    /// https://dom.spec.whatwg.org/#find-slottables
+    pub fn find_slottables(
+        host_ref: &Rc<RefCell<Self>>,
+        slot_ref: &Rc<RefCell<Self>>,
+    ) -> Vec<Rc<RefCell<Self>>> {
+        fn find_first_slot(
+            node: &Rc<RefCell<DomNode>>,
+            name: &str,
+        ) -> Option<Rc<RefCell<DomNode>>> {
+            for child in &node.borrow().children {
+                if let NodeType::Element(elm) = &child.borrow().node_type {
+                    if elm.tag_name == "slot" && elm.get_attribute("name").unwrap_or("") == name {
+                        return Some(Rc::clone(child));
+                    }
+                }
+                if let Some(slot) = find_first_slot(child, name) {
+                    return Some(slot);
+                }
+            }
+            None
+        }
+
+        let Some(shadow_root) = host_ref.borrow().shadow_root.clone() else {
+            return Vec::new();
+        };
+        let name = match &slot_ref.borrow().node_type {
+            NodeType::Element(elm) => elm.get_attribute("name").unwrap_or("").to_string(),
+            _ => return Vec::new(),
+        };
+        if !find_first_slot(&shadow_root, &name).is_some_and(|slot| Rc::ptr_eq(&slot, slot_ref)) {
+            return Vec::new();
+        }
+
+        host_ref
+            .borrow()
+            .children
+            .iter()
+            .filter(|child| match &child.borrow().node_type {
+                NodeType::Element(elm) => elm.get_attribute("slot").unwrap_or("") == name,
+                NodeType::Text(_) => name.is_empty(),
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+
     pub fn get_inside_text(&self) -> Option<String> {
         match &self.node_type {
             NodeType::Comment(text) | NodeType::DocumentType(text) | NodeType::Text(text) => {
@@ -67,25 +332,296 @@ impl DomNode {
             *t = text.to_string();
         }
     }
+
+    /// Returns the language of the node, which is inherited from the nearest ancestor with the `lang` attribute.
+    /// `None` means the language is unknown.
+    /// https://html.spec.whatwg.org/multipage/dom.html#language
+    pub fn get_language(node_ref: &Rc<RefCell<Self>>) -> Option<String> {
+        let mut node = Rc::clone(node_ref);
+        loop {
+            if let NodeType::Element(elm) = &node.borrow().node_type {
+                if let Some(lang) = elm
+                    .get_attribute("xml:lang")
+                    .or_else(|| elm.get_attribute("lang"))
+                {
+                    return Some(lang.to_string());
+                }
+            }
+            let parent = node.borrow().parent.as_ref()?.upgrade()?;
+            node = parent;
+        }
+    }
+
+    /// Returns whether the spelling and grammar of the text in the element should be checked.
+    /// The state is inherited from the nearest ancestor with the `spellcheck` attribute, and only the text
+    /// fields and the editable elements are checked if no ancestor has it.
+    /// https://html.spec.whatwg.org/multipage/interaction.html#spelling-and-grammar-checking
+    pub fn is_spellcheck_enabled(node_ref: &Rc<RefCell<Self>>) -> bool {
+        let mut node = Rc::clone(node_ref);
+        loop {
+            if let NodeType::Element(elm) = &node.borrow().node_type {
+                match elm
+                    .get_attribute("spellcheck")
+                    .map(|value| value.to_ascii_lowercase())
+                    .as_deref()
+                {
+                    Some("" | "true") => return true,
+                    Some("false") => return false,
+                    _ => {}
+                }
+            }
+            let Some(parent) = Self::get_parent(&node) else {
+                break;
+            };
+            node = parent;
+        }
+        match &node_ref.borrow().node_type {
+            NodeType::Element(elm) if elm.tag_name == "textarea" => true,
+            NodeType::Element(elm) if elm.tag_name == "input" => elm
+                .get_attribute("type")
+                .is_none_or(|t| t.eq_ignore_ascii_case("text") || t.eq_ignore_ascii_case("search")),
+            _ => Self::is_editable(node_ref),
+        }
+    }
+
+    /// Returns whether the node is editable or an editing host, which is determined by the `contenteditable`
+    /// attribute of the nearest inclusive ancestor element which has it in a valid state.
+    /// https://html.spec.whatwg.org/multipage/interaction.html#contenteditable
+    pub fn is_editable(node_ref: &Rc<RefCell<Self>>) -> bool {
+        let mut node = Rc::clone(node_ref);
+        loop {
+            if let NodeType::Element(elm) = &node.borrow().node_type {
+                if let Some(editable) = elm.get_content_editable_state() {
+                    return editable;
+                }
+            }
+            let Some(parent) = Self::get_parent(&node) else {
+                return false;
+            };
+            node = parent;
+        }
+    }
+
+    /// Returns whether the node is an editable element whose parent is not editable.
+    /// https://html.spec.whatwg.org/multipage/interaction.html#editing-host
+    pub fn is_editing_host(node_ref: &Rc<RefCell<Self>>) -> bool {
+        matches!(node_ref.borrow().node_type, NodeType::Element(_))
+            && Self::is_editable(node_ref)
+            && !node_ref
+                .borrow()
+                .parent
+                .as_ref()
+                .and_then(|p| p.upgrade())
+                .is_some_and(|parent| Self::is_editable(&parent))
+    }
+
+    /// Returns the editing host of the node, which is its nearest inclusive ancestor that is an editing host,
+    /// or `None` if the node is not editable.
+    /// https://html.spec.whatwg.org/multipage/interaction.html#editing-host
+    pub fn get_editing_host(node_ref: &Rc<RefCell<Self>>) -> Option<Rc<RefCell<Self>>> {
+        let mut node = Rc::clone(node_ref);
+        while !Self::is_editing_host(&node) {
+            if !Self::is_editable(&node) {
+                return None;
+            }
+            node = Self::get_parent(&node)?;
+        }
+        Some(node)
+    }
+
+    /// https://html.spec.whatwg.org/multipage/dom.html#the-directionality
+    pub fn get_directionality(node_ref: &Rc<RefCell<Self>>) -> Directionality {
+        let parent_directionality = || {
+            node_ref
+                .borrow()
+                .parent
+                .as_ref()
+                .and_then(|parent| parent.upgrade())
+                .map_or(Directionality::Ltr, |parent| {
+                    Self::get_directionality(&parent)
+                })
+        };
+
+        let NodeType::Element(elm) = &node_ref.borrow().node_type else {
+            return parent_directionality();
+        };
+        match elm
+            .get_attribute("dir")
+            .map(|dir| dir.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("ltr") => Directionality::Ltr,
+            Some("rtl") => Directionality::Rtl,
+            Some("auto") => Self::get_auto_directionality(node_ref).unwrap_or(Directionality::Ltr),
+            _ => parent_directionality(),
+        }
+    }
+
+    /// Returns the directionality of the first character of bidirectional character type L, AL, or R
+    /// in the descendant text nodes.
+    /// https://html.spec.whatwg.org/multipage/dom.html#contained-text-auto-directionality
+    fn get_auto_directionality(node_ref: &Rc<RefCell<Self>>) -> Option<Directionality> {
+        for child in &node_ref.borrow().children {
+            match &child.borrow().node_type {
+                NodeType::Text(text) => {
+                    if let Some(dir) = text.chars().find_map(Directionality::from_strong_char) {
+                        return Some(dir);
+                    }
+                }
+                NodeType::Element(elm) => {
+                    // Skip the elements whose content does not affect the directionality.
+                    if matches!(elm.tag_name.as_str(), "script" | "style" | "textarea")
+                        || elm.get_attribute("dir").is_some()
+                    {
+                        continue;
+                    }
+                    if let Some(dir) = Self::get_auto_directionality(child) {
+                        return Some(dir);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Returns the form owner of the form-associated element. The form owner of a `label` element is that of
+    /// its labeled control.
+    /// https://html.spec.whatwg.org/multipage/forms.html#dom-label-form
+    pub fn get_form_owner(node_ref: &Rc<RefCell<Self>>) -> Option<Rc<RefCell<Self>>> {
+        let is_label = matches!(&node_ref.borrow().node_type, NodeType::Element(elm) if elm.tag_name == "label");
+        if is_label {
+            return Self::get_form_owner(&Self::get_labeled_control(node_ref)?);
+        }
+        node_ref.borrow().form_owner.as_ref()?.upgrade()
+    }
+
+    /// Associates the form-associated element with the form specified by its `form` attribute, or with the
+    /// nearest ancestor `form` element.
+    /// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#reset-the-form-owner
+    pub fn reset_form_owner(node_ref: &Rc<RefCell<Self>>) {
+        let form_id = match &node_ref.borrow().node_type {
+            NodeType::Element(elm) if elm.is_form_associated() => elm
+                .is_listed()
+                .then(|| elm.get_attribute("form").map(str::to_string))
+                .flatten(),
+            _ => return,
+        };
+        let is_form = |node: &Rc<RefCell<Self>>| matches!(&node.borrow().node_type, NodeType::Element(elm) if elm.tag_name == "form");
+
+        let owner = if let Some(form_id) = form_id {
+            // Only the first element with the ID in tree order is looked up, even if it is not a form.
+            Self::get_element_by_id_in_tree(node_ref, &form_id).filter(is_form)
+        } else {
+            let mut ancestor = Self::get_parent(node_ref);
+            while let Some(node) = ancestor.take_if(|node| !is_form(node)) {
+                ancestor = Self::get_parent(&node);
+            }
+            ancestor
+        };
+        node_ref.borrow_mut().form_owner = owner.as_ref().map(Rc::downgrade);
+    }
+
+    /// Returns the labeled control of the `label` element, which is the element specified by its `for`
+    /// attribute, or its first labelable descendant.
+    /// https://html.spec.whatwg.org/multipage/forms.html#labeled-control
+    pub fn get_labeled_control(node_ref: &Rc<RefCell<Self>>) -> Option<Rc<RefCell<Self>>> {
+        let for_id = match &node_ref.borrow().node_type {
+            NodeType::Element(elm) if elm.tag_name == "label" => {
+                elm.get_attribute("for").map(str::to_string)
+            }
+            _ => return None,
+        };
+        let is_labelable = |node: &Rc<RefCell<Self>>| matches!(&node.borrow().node_type, NodeType::Element(elm) if elm.is_labelable());
+
+        match for_id {
+            Some(for_id) => Self::get_element_by_id_in_tree(node_ref, &for_id).filter(is_labelable),
+            None => Self::get_descendants(node_ref).find(is_labelable),
+        }
+    }
+
+    /// Returns the first element in tree order whose ID is `id` in the tree of the node. The ID index of the
+    /// document is used once it is built, and the other trees, e.g. the ones being parsed, are scanned.
+    pub fn get_element_by_id_in_tree(
+        node_ref: &Rc<RefCell<Self>>,
+        id: &str,
+    ) -> Option<Rc<RefCell<Self>>> {
+        let root = Self::get_root(node_ref);
+        if root.borrow().id_index.is_some() {
+            return DocumentTree { root }.get_element_by_id(id);
+        }
+        Self::get_descendants(&root).find(|node| get_id(node).as_deref() == Some(id))
+    }
 }
 
 impl fmt::Display for DomNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if let NodeType::Element(elm) = &self.node_type {
-            write!(f, "{}", elm)
-        } else {
-            write!(f, "{:?}", self.node_type)
+        match &self.node_type {
+            NodeType::Element(elm) => write!(f, "{}", elm),
+            // The mode is not shown in the tree.
+            NodeType::Document(_) => write!(f, "Document"),
+            node_type => write!(f, "{:?}", node_type),
         }
     }
 }
 
+/// The flags set by the mutations of the tree, which tell the style and layout stages the nodes to be
+/// recomputed. The nodes created by the parser are clean, since the first rendering computes everything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirtyFlags {
+    /// The style of the node must be recomputed.
+    pub style: bool,
+    /// The styles of all the descendants must be recomputed too, e.g. since the node was inserted.
+    pub subtree_style: bool,
+    /// Some descendant has a dirty style, so the subtree can't be skipped when restyling.
+    pub descendant_style: bool,
+    /// The box of the node must be laid out again, which is also set on the ancestors since their sizes
+    /// depend on it.
+    pub layout: bool,
+}
+
+/// The states of an element which are not reflected in its attributes, and which are changed by the user
+/// interactions instead of the markup.
+/// https://html.spec.whatwg.org/multipage/semantics-other.html#pseudo-classes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ElementState {
+    /// Whether the element is the target element of the document, which is indicated by the fragment of
+    /// its URL.
+    /// https://html.spec.whatwg.org/multipage/browsing-the-web.html#target-element
+    pub target: bool,
+    /// Whether the element or one of its descendants is designated by the pointing device.
+    /// https://www.w3.org/TR/selectors-4/#the-hover-pseudo
+    pub hover: bool,
+    /// Whether the element or one of its descendants is being activated, e.g. while the mouse button is
+    /// pressed on it.
+    /// https://www.w3.org/TR/selectors-4/#the-active-pseudo
+    pub active: bool,
+    /// Whether the element has the focus.
+    /// https://www.w3.org/TR/selectors-4/#the-focus-pseudo
+    pub focus: bool,
+}
+
+/// https://dom.spec.whatwg.org/#concept-document-mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DocumentMode {
+    #[default]
+    NoQuirks,
+    Quirks,
+    LimitedQuirks,
+}
+
 /// https://dom.spec.whatwg.org/#dom-node-nodetype
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum NodeType {
     Comment(String),
-    Document,
+    /// The document with its mode, which is determined while it is parsed.
+    Document(DocumentMode),
+    /// https://dom.spec.whatwg.org/#interface-documentfragment
+    DocumentFragment,
     DocumentType(String),
     Element(Element),
+    /// https://dom.spec.whatwg.org/#interface-shadowroot
+    ShadowRoot(ShadowRootMode),
     Text(String),
 }
 
@@ -93,14 +629,99 @@ impl fmt::Display for NodeType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             NodeType::Comment(text) => write!(f, "Comment( {} )", text),
-            NodeType::Document => write!(f, "Document"),
+            NodeType::Document(_) => write!(f, "Document"),
+            NodeType::DocumentFragment => write!(f, "DocumentFragment"),
             NodeType::DocumentType(text) => write!(f, "DocumentType( {} )", text),
             NodeType::Element(elm) => write!(f, "{}", elm),
+            NodeType::ShadowRoot(mode) => write!(f, "ShadowRoot( {} )", mode),
             NodeType::Text(text) => write!(f, "Text( {} )", text),
         }
     }
 }
 
+/// https://dom.spec.whatwg.org/#shadowroot-mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowRootMode {
+    Open,
+    Closed,
+}
+
+impl fmt::Display for ShadowRootMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShadowRootMode::Open => write!(f, "open"),
+            ShadowRootMode::Closed => write!(f, "closed"),
+        }
+    }
+}
+
+/// https://dom.spec.whatwg.org/#valid-shadow-host-name
+fn is_valid_shadow_host_name(name: &str) -> bool {
+    matches!(
+        name,
+        "article"
+            | "aside"
+            | "blockquote"
+            | "body"
+            | "div"
+            | "footer"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "header"
+            | "main"
+            | "nav"
+            | "p"
+            | "section"
+            | "span"
+    ) || is_valid_custom_element_name(name)
+}
+
+/// https://html.spec.whatwg.org/multipage/custom-elements.html#valid-custom-element-name
+pub fn is_valid_custom_element_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.contains('-')
+        && !name.chars().any(|c| c.is_ascii_uppercase())
+        && !matches!(
+            name,
+            "annotation-xml"
+                | "color-profile"
+                | "font-face"
+                | "font-face-src"
+                | "font-face-uri"
+                | "font-face-format"
+                | "font-face-name"
+                | "missing-glyph"
+        )
+}
+
+/// https://html.spec.whatwg.org/multipage/dom.html#the-directionality
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Directionality {
+    Ltr,
+    Rtl,
+}
+
+impl Directionality {
+    /// Returns the directionality of a strong bidirectional character (L, AL, or R), or `None` for other characters.
+    /// This roughly approximates the bidirectional character types by the Unicode blocks of right-to-left scripts.
+    fn from_strong_char(c: char) -> Option<Self> {
+        match c as u32 {
+            // Hebrew, Arabic, Syriac, Thaana, NKo, Samaritan, Mandaic, etc.
+            0x0590..=0x08FF
+            | 0xFB1D..=0xFDFF
+            | 0xFE70..=0xFEFF
+            | 0x10800..=0x10FFF
+            | 0x1E800..=0x1EFFF => Some(Directionality::Rtl),
+            _ if c.is_alphabetic() => Some(Directionality::Ltr),
+            _ => None,
+        }
+    }
+}
+
 /// https://dom.spec.whatwg.org/#element
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Element {
@@ -108,6 +729,104 @@ pub struct Element {
     pub attributes: Vec<(String, String)>,
 }
 
+impl Element {
+    /// Returns whether the element is defined, i.e. it is a built-in element or an upgraded custom element.
+    /// Custom element definitions can't be registered without scripting, so autonomous custom elements and
+    /// customized built-in elements (with the `is` attribute) are always undefined for now.
+    /// https://dom.spec.whatwg.org/#concept-element-defined
+    pub fn is_defined(&self) -> bool {
+        !is_valid_custom_element_name(&self.tag_name) && self.get_attribute("is").is_none()
+    }
+
+    pub fn get_attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns whether the `contenteditable` attribute makes the element editable (`true` and `plaintext-only`)
+    /// or not (`false`). `None` means the state is inherited from the parent.
+    /// https://html.spec.whatwg.org/multipage/interaction.html#attr-contenteditable
+    pub fn get_content_editable_state(&self) -> Option<bool> {
+        match self
+            .get_attribute("contenteditable")?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "" | "true" | "plaintext-only" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the element can have a form owner.
+    /// https://html.spec.whatwg.org/multipage/forms.html#form-associated-element
+    pub fn is_form_associated(&self) -> bool {
+        matches!(
+            self.tag_name.as_str(),
+            "button" | "fieldset" | "img" | "input" | "object" | "output" | "select" | "textarea"
+        )
+    }
+
+    /// Returns whether the element is listed in the `elements` of its form owner, which is every
+    /// form-associated element except `img`. Only listed elements can have the `form` attribute.
+    /// https://html.spec.whatwg.org/multipage/forms.html#category-listed
+    pub fn is_listed(&self) -> bool {
+        self.is_form_associated() && self.tag_name != "img"
+    }
+
+    /// Returns whether the element can be associated with a `label` element.
+    /// https://html.spec.whatwg.org/multipage/forms.html#category-label
+    pub fn is_labelable(&self) -> bool {
+        match self.tag_name.as_str() {
+            "button" | "meter" | "output" | "progress" | "select" | "textarea" => true,
+            "input" => !self
+                .get_attribute("type")
+                .is_some_and(|t| t.eq_ignore_ascii_case("hidden")),
+            _ => false,
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/media.html#media-element
+    pub fn is_media_element(&self) -> bool {
+        matches!(self.tag_name.as_str(), "audio" | "video")
+    }
+
+    /// Returns whether the user agent should expose its own user interface of the media element.
+    /// https://html.spec.whatwg.org/multipage/media.html#attr-media-controls
+    pub fn has_controls(&self) -> bool {
+        self.is_media_element() && self.get_attribute("controls").is_some()
+    }
+
+    /// Returns the URL of the image to show while no video data is available.
+    /// https://html.spec.whatwg.org/multipage/media.html#attr-video-poster
+    pub fn get_poster(&self) -> Option<&str> {
+        if self.tag_name != "video" {
+            return None;
+        }
+        self.get_attribute("poster").filter(|url| !url.is_empty())
+    }
+
+    /// Returns the length of the media resource in seconds. Media resources are not fetched yet,
+    /// so this is always NaN, which means that no media data is available.
+    /// https://html.spec.whatwg.org/multipage/media.html#dom-media-duration
+    #[allow(dead_code)]
+    pub fn get_duration(&self) -> Option<f64> {
+        self.is_media_element().then_some(f64::NAN)
+    }
+
+    /// Returns whether the fetch of the resource of the image or the iframe is deferred until it approaches
+    /// the viewport.
+    /// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#lazy-loading-attributes
+    pub fn is_lazy_loading(&self) -> bool {
+        matches!(self.tag_name.as_str(), "img" | "iframe")
+            && self
+                .get_attribute("loading")
+                .is_some_and(|loading| loading.eq_ignore_ascii_case("lazy"))
+    }
+}
+
 impl fmt::Display for Element {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let attr = self
@@ -128,6 +847,84 @@ impl fmt::Display for Element {
     }
 }
 
+/// The elements with each ID in tree order, which the document keeps so that looking up an element by its ID
+/// doesn't scan the whole tree.
+#[derive(Debug, Clone, Default)]
+pub struct IdIndex(HashMap<String, Vec<Weak<RefCell<DomNode>>>>);
+
+impl IdIndex {
+    fn build(root: &Rc<RefCell<DomNode>>) -> Self {
+        let mut index = Self::default();
+        for node in DomNode::get_descendants(root) {
+            if let Some(id) = get_id(&node) {
+                index.0.entry(id).or_default().push(Rc::downgrade(&node));
+            }
+        }
+        index
+    }
+
+    /// Returns the first element in tree order whose ID is `id`.
+    pub fn get(&self, id: &str) -> Option<Rc<RefCell<DomNode>>> {
+        self.0.get(id)?.iter().find_map(Weak::upgrade)
+    }
+
+    /// Adds the element with the ID after the ones preceding it in tree order.
+    pub fn insert(&mut self, id: &str, node_ref: &Rc<RefCell<DomNode>>) {
+        self.remove(id, node_ref);
+        let position = get_tree_position(node_ref);
+        let nodes = self.0.entry(id.to_string()).or_default();
+        let index = nodes
+            .iter()
+            .position(|node| {
+                node.upgrade()
+                    .is_some_and(|n| get_tree_position(&n) > position)
+            })
+            .unwrap_or(nodes.len());
+        nodes.insert(index, Rc::downgrade(node_ref));
+    }
+
+    /// Removes the element with the ID, and the elements which have been dropped.
+    pub fn remove(&mut self, id: &str, node_ref: &Rc<RefCell<DomNode>>) {
+        if let Some(nodes) = self.0.get_mut(id) {
+            nodes.retain(|node| node.upgrade().is_some_and(|n| !Rc::ptr_eq(&n, node_ref)));
+            if nodes.is_empty() {
+                self.0.remove(id);
+            }
+        }
+    }
+}
+
+/// Returns the ID of the element, which is `None` for the other nodes and the empty IDs.
+/// https://dom.spec.whatwg.org/#concept-id
+pub fn get_id(node_ref: &Rc<RefCell<DomNode>>) -> Option<String> {
+    match &node_ref.borrow().node_type {
+        NodeType::Element(elm) => elm
+            .get_attribute("id")
+            .filter(|id| !id.is_empty())
+            .map(str::to_string),
+        _ => None,
+    }
+}
+
+/// Returns the indices of the node and its ancestors in their parents from the root, which are compared
+/// in tree order.
+fn get_tree_position(node_ref: &Rc<RefCell<DomNode>>) -> Vec<usize> {
+    let mut position = Vec::new();
+    let mut node = Rc::clone(node_ref);
+    while let Some(parent) = DomNode::get_parent(&node) {
+        let index = parent
+            .borrow()
+            .children
+            .iter()
+            .position(|child| Rc::ptr_eq(child, &node))
+            .unwrap();
+        position.push(index);
+        node = parent;
+    }
+    position.reverse();
+    position
+}
+
 /// https://dom.spec.whatwg.org/#document-trees
 #[derive(Debug)]
 pub struct DocumentTree {
@@ -135,16 +932,108 @@ pub struct DocumentTree {
 }
 
 impl DocumentTree {
+    /// Builds the tree of the document, which indexes its elements by their IDs the first time.
+    #[tracing::instrument(skip_all)]
     pub fn build(root: Rc<RefCell<DomNode>>) -> Result<Self> {
         ensure!(
-            root.borrow().node_type == NodeType::Document,
+            matches!(root.borrow().node_type, NodeType::Document(_)),
             "The root node of a document tree must be a document node."
         );
+        if root.borrow().id_index.is_none() {
+            let id_index = IdIndex::build(&root);
+            root.borrow_mut().id_index = Some(id_index);
+        }
         Ok(Self { root })
     }
 
-    pub fn to_render_tree(&self, style_sheets: Vec<StyleSheet>) -> Result<RenderTree> {
-        RenderTree::build(self, style_sheets)
+    /// Returns the first element in tree order whose ID is `id`.
+    /// https://dom.spec.whatwg.org/#dom-nonelementparentnode-getelementbyid
+    pub fn get_element_by_id(&self, id: &str) -> Option<Rc<RefCell<DomNode>>> {
+        self.root.borrow().id_index.as_ref()?.get(id)
+    }
+
+    /// Returns the elements which have all the classes in the space-separated `class_names`, in tree order.
+    /// https://dom.spec.whatwg.org/#concept-getelementsbyclassname
+    pub fn get_elements_by_class_name(&self, class_names: &str) -> Vec<Rc<RefCell<DomNode>>> {
+        let class_names = class_names.split_ascii_whitespace().collect::<Vec<_>>();
+        if class_names.is_empty() {
+            return Vec::new();
+        }
+        DomNode::get_descendants(&self.root)
+            .filter(|node| match &node.borrow().node_type {
+                NodeType::Element(elm) => elm.get_attribute("class").is_some_and(|classes| {
+                    class_names
+                        .iter()
+                        .all(|name| classes.split_ascii_whitespace().any(|c| c == *name))
+                }),
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// Returns the elements whose tag name is `name`, or all elements if it is `*`, in tree order.
+    /// https://dom.spec.whatwg.org/#concept-getelementsbytagname
+    pub fn get_elements_by_tag_name(&self, name: &str) -> Vec<Rc<RefCell<DomNode>>> {
+        DomNode::get_descendants(&self.root)
+            .filter(|node| match &node.borrow().node_type {
+                // All elements are HTML elements for now, whose names are compared in lowercase.
+                NodeType::Element(elm) => name == "*" || elm.tag_name == name.to_ascii_lowercase(),
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// Returns the elements selected by the selector list if it only selects the elements by their type or
+    /// their classes, which are collected without matching the selectors against each element. Returns `None`
+    /// for the other selector lists.
+    fn get_elements_by_simple_selectors(
+        &self,
+        selectors: &[Selector],
+    ) -> Option<Vec<Rc<RefCell<DomNode>>>> {
+        let [Selector::Simple(simple_selectors)] = selectors else {
+            return None;
+        };
+        match simple_selectors.as_slice() {
+            [SimpleSelector::Type {
+                namespace_prefix: None,
+                name,
+            }] => Some(self.get_elements_by_tag_name(name)),
+            [SimpleSelector::Universal(None)] => Some(self.get_elements_by_tag_name("*")),
+            _ => {
+                // The escaped whitespace in the class names can't be separated by spaces.
+                let class_names = simple_selectors
+                    .iter()
+                    .map(|selector| match selector {
+                        SimpleSelector::Class(name) if !name.contains(char::is_whitespace) => {
+                            Some(name.as_str())
+                        }
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .filter(|names| !names.is_empty())?;
+                Some(self.get_elements_by_class_name(&class_names.join(" ")))
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub fn to_render_tree(
+        &self,
+        style_sheets: Vec<StyleSheet>,
+        media: &MediaEnvironment,
+    ) -> Result<RenderTree> {
+        RenderTree::build(self, style_sheets, media)
+    }
+
+    /// Builds the render tree reusing the styles kept in `styles` for the elements not invalidated since.
+    #[tracing::instrument(skip_all)]
+    pub fn restyle_render_tree(
+        &self,
+        style_sheets: Vec<StyleSheet>,
+        media: &MediaEnvironment,
+        styles: &mut StyleCache,
+    ) -> Result<RenderTree> {
+        RenderTree::restyle(self, style_sheets, media, styles)
     }
 
     #[cfg(test)]
@@ -180,8 +1069,18 @@ impl fmt::Display for DocumentTree {
             }
             indent_and_branches.push_str(if is_last_child { "└─" } else { "├─" });
             node_tree.push_str(&format!("{}{}\n", indent_and_branches, node.borrow()));
-            let children_num = node.borrow().children.len();
-            for (i, child) in node.borrow().children.iter().enumerate() {
+            // The shadow root and the template contents are shown as the first child of the node,
+            // like the developer tools of browsers.
+            let children = node
+                .borrow()
+                .shadow_root
+                .iter()
+                .chain(node.borrow().template_contents.iter())
+                .chain(node.borrow().children.iter())
+                .cloned()
+                .collect::<Vec<_>>();
+            let children_num = children.len();
+            for (i, child) in children.iter().enumerate() {
                 construct_node_view(
                     node_tree,
                     child,
@@ -199,3 +1098,79 @@ impl fmt::Display for DocumentTree {
 }
 
 impl PrintableTree for DocumentTree {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::mutation::remove_attribute;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    #[test]
+    fn get_elements() {
+        let html = "<html><head></head><body><p id=a class=\"x y\">1</p><div id=a class=\"y\"><p class=\"y  x z\">2</p></div></body></html>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(document).unwrap();
+        let to_strings = |nodes: Vec<Rc<RefCell<DomNode>>>| {
+            nodes
+                .iter()
+                .map(|node| node.borrow().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // The first element in tree order is returned if the ID is duplicated.
+        let p = tree.get_element_by_id("a").unwrap();
+        assert_eq!(
+            p.borrow().to_string(),
+            "Elem( tag: <p>, attr: [\"id\"=\"a\"; \"class\"=\"x y\"] )"
+        );
+        assert!(tree.get_element_by_id("b").is_none());
+        assert!(remove_attribute(&p, "id"));
+        assert_eq!(
+            tree.get_element_by_id("a").unwrap().borrow().to_string(),
+            "Elem( tag: <div>, attr: [\"id\"=\"a\"; \"class\"=\"y\"] )"
+        );
+
+        assert_eq!(
+            to_strings(tree.get_elements_by_class_name(" x  y ")),
+            vec![
+                "Elem( tag: <p>, attr: [\"class\"=\"x y\"] )",
+                "Elem( tag: <p>, attr: [\"class\"=\"y  x z\"] )"
+            ]
+        );
+        assert!(tree.get_elements_by_class_name(" ").is_empty());
+        assert_eq!(tree.get_elements_by_tag_name("P").len(), 2);
+        assert_eq!(tree.get_elements_by_tag_name("*").len(), 6);
+
+        // The elements in the tree of the document are looked up with its collections.
+        assert!(Rc::ptr_eq(
+            &DomNode::get_element_by_id_in_tree(&p, "a").unwrap(),
+            &tree.get_element_by_id("a").unwrap()
+        ));
+        let query = |selectors: &str| {
+            to_strings(DomNode::query_selector_all(&tree.root, selectors).unwrap())
+        };
+        assert_eq!(
+            query(".x.y"),
+            to_strings(tree.get_elements_by_class_name("x y"))
+        );
+        assert_eq!(query("p"), to_strings(tree.get_elements_by_tag_name("p")));
+        assert_eq!(query("*").len(), 6);
+        assert_eq!(query("div .x").len(), 1);
+    }
+
+    #[test]
+    fn lazy_loading() {
+        let element = |tag_name: &str, attributes: &[(&str, &str)]| Element {
+            tag_name: tag_name.to_string(),
+            attributes: attributes
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+        assert!(element("img", &[("loading", "LAZY")]).is_lazy_loading());
+        assert!(element("iframe", &[("loading", "lazy")]).is_lazy_loading());
+        assert!(!element("img", &[("loading", "eager")]).is_lazy_loading());
+        assert!(!element("video", &[("loading", "lazy")]).is_lazy_loading());
+    }
+}
@@ -0,0 +1,580 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::renderer::html::dom::{DomNode, NodeType};
+
+/// The position of the caret in a text node, where `offset` is the number of characters before it.
+/// https://w3c.github.io/selection-api/#dfn-caret
+#[derive(Debug, Clone)]
+pub struct Caret {
+    pub node: Rc<RefCell<DomNode>>,
+    pub offset: usize,
+}
+
+impl PartialEq for Caret {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.node, &other.node) && self.offset == other.offset
+    }
+}
+
+/// The time for which the caret is shown or hidden while it blinks.
+pub const CARET_BLINK_INTERVAL: Duration = Duration::from_millis(600);
+/// The time after the last input at which the caret stops blinking and stays visible, to save power.
+pub const CARET_BLINK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Returns whether the blinking caret is visible at the time elapsed since the last input, which is
+/// measured with the frame clock. The caret is visible right after the input.
+pub fn is_caret_visible(since_last_input: Duration) -> bool {
+    since_last_input >= CARET_BLINK_TIMEOUT
+        || (since_last_input.as_millis() / CARET_BLINK_INTERVAL.as_millis()).is_multiple_of(2)
+}
+
+/// The text being composed with an input method, e.g. the reading of CJK text before a candidate is
+/// chosen. It is shown at the caret with an underline, but is not inserted into the DOM until committed.
+/// https://w3c.github.io/uievents/#events-compositionevents
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Composition {
+    pub text: String,
+    /// The position of the cursor in the composed text, in characters.
+    pub cursor: usize,
+}
+
+/// A change of the data of a text node, which is recorded in the undo history.
+#[derive(Debug, Clone)]
+struct TextEdit {
+    node: Rc<RefCell<DomNode>>,
+    offset: usize,
+    deleted: String,
+    inserted: String,
+}
+
+impl TextEdit {
+    fn inverse(&self) -> Self {
+        Self {
+            node: Rc::clone(&self.node),
+            offset: self.offset,
+            deleted: self.inserted.clone(),
+            inserted: self.deleted.clone(),
+        }
+    }
+}
+
+/// Edits the text in an editing host, i.e. an element with the `contenteditable` attribute.
+/// Only the data of the text nodes is changed, and text nodes in the descendants which are not editable,
+/// e.g. the ones with `contenteditable=false`, are skipped.
+/// https://html.spec.whatwg.org/multipage/interaction.html#editing-2
+#[derive(Debug)]
+pub struct Editor {
+    host: Rc<RefCell<DomNode>>,
+    caret: Option<Caret>,
+    undo_stack: Vec<TextEdit>,
+    redo_stack: Vec<TextEdit>,
+    /// Whether the next insertion starts a new undo step even if it continues the last one.
+    is_undo_step_closed: bool,
+    composition: Option<Composition>,
+}
+
+impl Editor {
+    /// Creates an editor of the editing host, placing the caret at the start of its first text node.
+    /// Returns `None` if the node is not an editing host.
+    pub fn new(host: &Rc<RefCell<DomNode>>) -> Option<Self> {
+        if !DomNode::is_editing_host(host) {
+            return None;
+        }
+        let mut editor = Self {
+            host: Rc::clone(host),
+            caret: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            is_undo_step_closed: false,
+            composition: None,
+        };
+        editor.caret = editor.get_text_nodes().first().map(|node| Caret {
+            node: Rc::clone(node),
+            offset: 0,
+        });
+        Some(editor)
+    }
+
+    /// Creates an editor of the editing host of the text node, placing the caret at the offset in it.
+    /// Returns `None` if the node is not an editable text node.
+    pub fn with_caret(node: &Rc<RefCell<DomNode>>, offset: usize) -> Option<Self> {
+        let mut editor = Self::new(&DomNode::get_editing_host(node)?)?;
+        editor.set_caret(node, offset).then_some(editor)
+    }
+
+    pub fn get_host(&self) -> &Rc<RefCell<DomNode>> {
+        &self.host
+    }
+
+    pub fn get_caret(&self) -> Option<&Caret> {
+        self.caret.as_ref()
+    }
+
+    /// Places the caret in the text node, clamping the offset to its length. Returns `false` if the node
+    /// is not an editable text node in the editing host.
+    pub fn set_caret(&mut self, node: &Rc<RefCell<DomNode>>, offset: usize) -> bool {
+        if !self.get_text_nodes().iter().any(|n| Rc::ptr_eq(n, node)) {
+            return false;
+        }
+        self.caret = Some(Caret {
+            node: Rc::clone(node),
+            offset: offset.min(get_length(node)),
+        });
+        true
+    }
+
+    /// Inserts the text at the caret, and moves the caret after it. A text node is created if the editing
+    /// host has none.
+    /// https://w3c.github.io/editing/docs/execCommand/#the-inserttext-command
+    pub fn insert_text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let caret = match &self.caret {
+            Some(caret) => caret.clone(),
+            None => {
+                let node =
+                    DomNode::append_child(&self.host, DomNode::new(NodeType::Text(String::new())));
+                Caret { node, offset: 0 }
+            }
+        };
+        let edit = TextEdit {
+            node: caret.node,
+            offset: caret.offset,
+            deleted: String::new(),
+            inserted: text.to_string(),
+        };
+        self.apply(&edit);
+
+        // Consecutive insertions are undone at once, like typing a word.
+        if let Some(last) = self.undo_stack.last_mut() {
+            if !self.is_undo_step_closed
+                && Rc::ptr_eq(&last.node, &edit.node)
+                && last.deleted.is_empty()
+                && last.offset + last.inserted.chars().count() == edit.offset
+                && !text.starts_with(char::is_whitespace)
+            {
+                last.inserted.push_str(text);
+                self.redo_stack.clear();
+                return;
+            }
+        }
+        self.record(edit);
+        self.is_undo_step_closed = false;
+    }
+
+    pub fn get_composition(&self) -> Option<&Composition> {
+        self.composition.as_ref()
+    }
+
+    /// Starts or updates the composition with the preedit text of the input method. An empty text ends
+    /// the composition without inserting anything.
+    /// https://w3c.github.io/uievents/#event-type-compositionupdate
+    pub fn update_composition(&mut self, text: &str, cursor: usize) {
+        self.composition = (!text.is_empty()).then(|| Composition {
+            text: text.to_string(),
+            cursor: cursor.min(text.chars().count()),
+        });
+    }
+
+    /// Ends the composition and inserts the committed text, e.g. the chosen candidate, as an undo step
+    /// of its own.
+    /// https://w3c.github.io/uievents/#event-type-compositionend
+    pub fn commit_composition(&mut self, text: &str) {
+        self.composition = None;
+        self.is_undo_step_closed = true;
+        self.insert_text(text);
+        self.is_undo_step_closed = true;
+    }
+
+    /// Inserts the text read from the clipboard as an undo step of its own. Line breaks are normalized to
+    /// line feeds, and null characters are removed.
+    /// https://w3c.github.io/clipboard-apis/#paste-action
+    pub fn paste(&mut self, text: &str) {
+        let text = text
+            .replace("\r\n", "\n")
+            .replace('\r', "\n")
+            .replace('\0', "");
+        self.is_undo_step_closed = true;
+        self.insert_text(&text);
+        self.is_undo_step_closed = true;
+    }
+
+    pub fn cancel_composition(&mut self) {
+        self.composition = None;
+    }
+
+    /// Deletes the character before the caret, which may be at the end of the previous text node.
+    /// https://w3c.github.io/editing/docs/execCommand/#the-delete-command
+    pub fn delete_backward(&mut self) {
+        let Some(caret) = self.caret.clone() else {
+            return;
+        };
+        let (node, offset) = if caret.offset > 0 {
+            (caret.node, caret.offset - 1)
+        } else {
+            let Some(prev) = self.get_adjacent_text_node(&caret.node, false) else {
+                return;
+            };
+            let len = get_length(&prev);
+            if len == 0 {
+                return;
+            }
+            (prev, len - 1)
+        };
+        self.delete_char(node, offset);
+    }
+
+    /// Deletes the character after the caret, which may be at the start of the next text node.
+    /// https://w3c.github.io/editing/docs/execCommand/#the-forwarddelete-command
+    pub fn delete_forward(&mut self) {
+        let Some(caret) = self.caret.clone() else {
+            return;
+        };
+        if caret.offset < get_length(&caret.node) {
+            self.delete_char(caret.node, caret.offset);
+        } else if let Some(next) = self.get_adjacent_text_node(&caret.node, true) {
+            if get_length(&next) > 0 {
+                self.delete_char(next, 0);
+            }
+        }
+    }
+
+    /// Moves the caret one character backward, crossing into the previous text node at its start.
+    pub fn move_backward(&mut self) {
+        let Some(caret) = self.caret.clone() else {
+            return;
+        };
+        if caret.offset > 0 {
+            self.caret = Some(Caret {
+                offset: caret.offset - 1,
+                ..caret
+            });
+        } else if let Some(prev) = self.get_adjacent_text_node(&caret.node, false) {
+            // The end of the previous node and the start of this one are visually the same position.
+            let offset = get_length(&prev).saturating_sub(1);
+            self.caret = Some(Caret { node: prev, offset });
+        }
+    }
+
+    /// Moves the caret one character forward, crossing into the next text node at its end.
+    pub fn move_forward(&mut self) {
+        let Some(caret) = self.caret.clone() else {
+            return;
+        };
+        if caret.offset < get_length(&caret.node) {
+            self.caret = Some(Caret {
+                offset: caret.offset + 1,
+                ..caret
+            });
+        } else if let Some(next) = self.get_adjacent_text_node(&caret.node, true) {
+            let offset = get_length(&next).min(1);
+            self.caret = Some(Caret { node: next, offset });
+        }
+    }
+
+    /// Moves the caret to the previous line of the text node, keeping the column if possible.
+    /// The lines are the ones separated by the line breaks inserted by the layout.
+    pub fn move_up(&mut self) {
+        self.move_vertically(false);
+    }
+
+    /// Moves the caret to the next line of the text node, keeping the column if possible.
+    pub fn move_down(&mut self) {
+        self.move_vertically(true);
+    }
+
+    /// Reverts the last edit, and moves the caret to where it was made. Returns `false` if there is nothing
+    /// to undo.
+    /// https://w3c.github.io/editing/docs/execCommand/#the-undo-command
+    pub fn undo(&mut self) -> bool {
+        let Some(edit) = self.undo_stack.pop() else {
+            return false;
+        };
+        self.apply(&edit.inverse());
+        self.redo_stack.push(edit);
+        true
+    }
+
+    /// Reapplies the last undone edit. Returns `false` if there is nothing to redo.
+    /// https://w3c.github.io/editing/docs/execCommand/#the-redo-command
+    pub fn redo(&mut self) -> bool {
+        let Some(edit) = self.redo_stack.pop() else {
+            return false;
+        };
+        self.apply(&edit);
+        self.undo_stack.push(edit);
+        true
+    }
+
+    fn delete_char(&mut self, node: Rc<RefCell<DomNode>>, offset: usize) {
+        let deleted = get_data(&node).chars().nth(offset).unwrap().to_string();
+        let edit = TextEdit {
+            node,
+            offset,
+            deleted,
+            inserted: String::new(),
+        };
+        self.apply(&edit);
+        self.record(edit);
+    }
+
+    /// Replaces the deleted text with the inserted one, and places the caret after the inserted text.
+    fn apply(&mut self, edit: &TextEdit) {
+        let data = get_data(&edit.node);
+        let start = get_byte_index(&data, edit.offset);
+        let end = start + edit.deleted.len();
+        debug_assert_eq!(&data[start..end], edit.deleted);
+        let data = format!("{}{}{}", &data[..start], edit.inserted, &data[end..]);
+        edit.node.borrow_mut().set_inside_text(&data);
+        self.caret = Some(Caret {
+            node: Rc::clone(&edit.node),
+            offset: edit.offset + edit.inserted.chars().count(),
+        });
+    }
+
+    fn record(&mut self, edit: TextEdit) {
+        self.undo_stack.push(edit);
+        self.redo_stack.clear();
+    }
+
+    fn move_vertically(&mut self, down: bool) {
+        let Some(caret) = &mut self.caret else {
+            return;
+        };
+        let data = get_data(&caret.node);
+        let lines = data
+            .split('\n')
+            .map(|l| l.chars().count())
+            .collect::<Vec<_>>();
+        // Find the line and the column of the caret.
+        let (mut line, mut column) = (0, caret.offset);
+        while column > lines[line] {
+            column -= lines[line] + 1;
+            line += 1;
+        }
+        let target = match down {
+            true if line + 1 < lines.len() => line + 1,
+            false if line > 0 => line - 1,
+            _ => return,
+        };
+        caret.offset =
+            lines[..target].iter().map(|len| len + 1).sum::<usize>() + column.min(lines[target]);
+    }
+
+    /// Returns the text nodes in the editing host which are editable, in tree order.
+    fn get_text_nodes(&self) -> Vec<Rc<RefCell<DomNode>>> {
+        DomNode::get_descendants(&self.host)
+            .filter(|node| {
+                matches!(node.borrow().node_type, NodeType::Text(_)) && DomNode::is_editable(node)
+            })
+            .collect()
+    }
+
+    fn get_adjacent_text_node(
+        &self,
+        node: &Rc<RefCell<DomNode>>,
+        next: bool,
+    ) -> Option<Rc<RefCell<DomNode>>> {
+        let nodes = self.get_text_nodes();
+        let i = nodes.iter().position(|n| Rc::ptr_eq(n, node))?;
+        if next {
+            nodes.get(i + 1).cloned()
+        } else {
+            i.checked_sub(1).map(|i| Rc::clone(&nodes[i]))
+        }
+    }
+}
+
+fn get_data(node: &Rc<RefCell<DomNode>>) -> String {
+    node.borrow().get_inside_text().unwrap_or_default()
+}
+
+fn get_length(node: &Rc<RefCell<DomNode>>) -> usize {
+    get_data(node).chars().count()
+}
+
+fn get_byte_index(text: &str, offset: usize) -> usize {
+    text.char_indices()
+        .nth(offset)
+        .map_or(text.len(), |(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::serializer::serialize_children;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    fn get_element_by_tag_name(root: &Rc<RefCell<DomNode>>, name: &str) -> Rc<RefCell<DomNode>> {
+        DomNode::get_descendants(root)
+            .find(|node| {
+                matches!(&node.borrow().node_type, NodeType::Element(elm) if elm.tag_name == name)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn edit_text() {
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(
+            "<html><head></head><body><div contenteditable>ab<b>c\u{e9}</b><i contenteditable=false>x</i>d</div><p>y</p></body></html>",
+        ))
+        .parse()
+        .unwrap();
+        let div = get_element_by_tag_name(&document, "div");
+        let b = get_element_by_tag_name(&document, "b");
+        let i = get_element_by_tag_name(&document, "i");
+        let p = get_element_by_tag_name(&document, "p");
+        assert!(DomNode::get_editing_host(&b.borrow().children[0])
+            .is_some_and(|host| Rc::ptr_eq(&host, &div)));
+        assert!(DomNode::get_editing_host(&i.borrow().children[0]).is_none());
+        assert!(DomNode::get_editing_host(&p).is_none());
+        assert!(Editor::new(&p).is_none());
+        assert!(Editor::new(&b).is_none());
+        let mut editor = Editor::new(&div).unwrap();
+
+        editor.move_forward();
+        editor.insert_text("1");
+        editor.insert_text("2");
+        assert_eq!(
+            serialize_children(&div),
+            "a12b<b>c\u{e9}</b><i contenteditable=\"false\">x</i>d"
+        );
+
+        // The caret crosses the text nodes, skipping the ones which are not editable.
+        editor.move_forward();
+        editor.move_forward();
+        editor.move_forward();
+        editor.delete_forward();
+        editor.move_forward();
+        editor.insert_text("!");
+        assert_eq!(
+            serialize_children(&div),
+            "a12b<b>c\u{e9}</b><i contenteditable=\"false\">x</i>!"
+        );
+        editor.delete_backward();
+        editor.delete_backward();
+        assert_eq!(
+            serialize_children(&div),
+            "a12b<b>c</b><i contenteditable=\"false\">x</i>"
+        );
+
+        // The consecutive insertions are undone at once.
+        assert!(editor.undo());
+        assert!(editor.undo());
+        assert!(editor.undo());
+        assert!(editor.undo());
+        assert_eq!(
+            serialize_children(&div),
+            "a12b<b>c\u{e9}</b><i contenteditable=\"false\">x</i>d"
+        );
+        assert!(editor.undo());
+        assert!(!editor.undo());
+        assert_eq!(
+            serialize_children(&div),
+            "ab<b>c\u{e9}</b><i contenteditable=\"false\">x</i>d"
+        );
+        assert!(editor.redo());
+        assert_eq!(editor.get_caret().unwrap().offset, 3);
+        editor.insert_text("3");
+        assert!(!editor.redo());
+        assert_eq!(
+            serialize_children(&div),
+            "a123b<b>c\u{e9}</b><i contenteditable=\"false\">x</i>d"
+        );
+    }
+
+    #[test]
+    fn move_between_lines() {
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(
+            "<html><head></head><body><p contenteditable>abcd\nef\nghi</p></body></html>",
+        ))
+        .parse()
+        .unwrap();
+        let p = get_element_by_tag_name(&document, "p");
+        let text = Rc::clone(&p.borrow().children[0]);
+        let mut editor = Editor::with_caret(&text, 3).unwrap();
+        assert!(Rc::ptr_eq(editor.get_host(), &p));
+        editor.move_down();
+        assert_eq!(editor.get_caret().unwrap().offset, 7);
+        editor.move_down();
+        assert_eq!(editor.get_caret().unwrap().offset, 10);
+        editor.move_down();
+        assert_eq!(editor.get_caret().unwrap().offset, 10);
+        editor.move_up();
+        editor.move_up();
+        assert_eq!(editor.get_caret().unwrap().offset, 2);
+        assert!(!editor.set_caret(&p, 0));
+    }
+
+    #[test]
+    fn compose_text() {
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(
+            "<html><head></head><body><p contenteditable>a</p></body></html>",
+        ))
+        .parse()
+        .unwrap();
+        let p = get_element_by_tag_name(&document, "p");
+        let mut editor = Editor::new(&p).unwrap();
+        editor.move_forward();
+        editor.insert_text("b");
+
+        // The preedit text is not inserted until it is committed.
+        editor.update_composition("\u{304b}", 1);
+        editor.update_composition("\u{304b}\u{3093}", 5);
+        assert_eq!(
+            editor.get_composition(),
+            Some(&Composition {
+                text: "\u{304b}\u{3093}".to_string(),
+                cursor: 2
+            })
+        );
+        assert_eq!(serialize_children(&p), "ab");
+        editor.commit_composition("\u{6f22}");
+        assert!(editor.get_composition().is_none());
+        editor.insert_text("c");
+        assert_eq!(serialize_children(&p), "ab\u{6f22}c");
+
+        // The committed text is undone separately from the typed ones.
+        editor.undo();
+        assert_eq!(serialize_children(&p), "ab\u{6f22}");
+        editor.undo();
+        assert_eq!(serialize_children(&p), "ab");
+
+        editor.update_composition("x", 1);
+        editor.cancel_composition();
+        assert!(editor.get_composition().is_none());
+        assert_eq!(serialize_children(&p), "ab");
+    }
+
+    #[test]
+    fn paste_text() {
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(
+            "<html><head></head><body><p contenteditable>a</p></body></html>",
+        ))
+        .parse()
+        .unwrap();
+        let p = get_element_by_tag_name(&document, "p");
+        let mut editor = Editor::new(&p).unwrap();
+        editor.move_forward();
+        editor.insert_text("b");
+        editor.paste("c\r\nd\0\re");
+        assert_eq!(serialize_children(&p), "abc\nd\ne");
+        assert_eq!(editor.get_caret().unwrap().offset, 7);
+
+        // The pasted text is undone separately from the typed ones.
+        editor.undo();
+        assert_eq!(serialize_children(&p), "ab");
+    }
+
+    #[test]
+    fn blink_caret() {
+        assert!(is_caret_visible(Duration::ZERO));
+        assert!(!is_caret_visible(CARET_BLINK_INTERVAL));
+        assert!(is_caret_visible(CARET_BLINK_INTERVAL * 2));
+        assert!(is_caret_visible(CARET_BLINK_TIMEOUT + CARET_BLINK_INTERVAL));
+    }
+}
@@ -0,0 +1,323 @@
+use std::fmt;
+
+use encoding_rs::{Decoder, Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+
+/// The number of bytes examined by the prescan, which is the recommended value in the spec.
+const PRESCAN_LEN: usize = 1024;
+
+/// Determines the character encoding of an HTML document from its bytes and the `Content-Type` header,
+/// if any, with which it was transferred.
+/// https://html.spec.whatwg.org/multipage/parsing.html#encoding-sniffing-algorithm
+pub fn sniff_encoding(bytes: &[u8], content_type: Option<&str>) -> &'static Encoding {
+    // The byte order mark takes precedence over everything else.
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    if let Some(encoding) = content_type.and_then(get_encoding_from_content_type) {
+        return encoding;
+    }
+    if let Some(encoding) = prescan(&bytes[..bytes.len().min(PRESCAN_LEN)]) {
+        return encoding;
+    }
+    // The default is implementation-defined. Documents which happen to be valid UTF-8 are
+    // most likely UTF-8, and the others fall back to the legacy encoding of the Web.
+    if std::str::from_utf8(bytes).is_ok() {
+        UTF_8
+    } else {
+        WINDOWS_1252
+    }
+}
+
+/// Decodes the whole HTML document into a string.
+pub fn decode(bytes: &[u8], content_type: Option<&str>) -> String {
+    let (text, _) = sniff_encoding(bytes, content_type).decode_with_bom_removal(bytes);
+    text.into_owned()
+}
+
+/// A decoder of the input byte stream which accepts the bytes in chunks. A character split across
+/// chunks is decoded when the rest of it is fed, and errors are replaced with U+FFFD.
+/// https://encoding.spec.whatwg.org/#decode
+pub struct ByteStreamDecoder {
+    decoder: Decoder,
+}
+
+impl fmt::Debug for ByteStreamDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ByteStreamDecoder")
+            .field("encoding", &self.decoder.encoding().name())
+            .finish()
+    }
+}
+
+impl Default for ByteStreamDecoder {
+    fn default() -> Self {
+        Self::new(UTF_8)
+    }
+}
+
+impl ByteStreamDecoder {
+    pub fn new(encoding: &'static Encoding) -> Self {
+        Self {
+            decoder: encoding.new_decoder_with_bom_removal(),
+        }
+    }
+
+    /// Decodes the chunk. `last` must be true for the last chunk, so that an incomplete sequence
+    /// at the end is flushed as U+FFFD.
+    pub fn decode(&mut self, bytes: &[u8], last: bool) -> String {
+        let mut text = String::with_capacity(
+            self.decoder
+                .max_utf8_buffer_length(bytes.len())
+                .unwrap_or(bytes.len()),
+        );
+        let mut bytes = bytes;
+        loop {
+            let (result, read, _) = self.decoder.decode_to_string(bytes, &mut text, last);
+            bytes = &bytes[read..];
+            match result {
+                encoding_rs::CoderResult::InputEmpty => return text,
+                encoding_rs::CoderResult::OutputFull => text.reserve(bytes.len().max(4) * 3),
+            }
+        }
+    }
+}
+
+/// Returns the encoding specified by the `charset` parameter of the `Content-Type` header.
+/// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#content-type
+fn get_encoding_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("charset") {
+            return None;
+        }
+        Encoding::for_label(value.trim().trim_matches('"').as_bytes())
+    })
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+fn prescan(bytes: &[u8]) -> Option<&'static Encoding> {
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &bytes[pos..];
+        if rest.starts_with(b"<!--") {
+            // The two dashes of the comment start may also be the ones of the comment end.
+            pos += find(&rest[2..], b"-->").map_or(rest.len(), |i| i + 5);
+        } else if starts_with_ignore_case(rest, b"<meta")
+            && rest
+                .get(5)
+                .is_some_and(|b| b.is_ascii_whitespace() || *b == b'/')
+        {
+            pos += 6;
+            if let Some(encoding) = process_meta_attributes(bytes, &mut pos) {
+                return Some(encoding);
+            }
+        } else if rest.len() >= 2
+            && (rest[1].is_ascii_alphabetic()
+                || (rest[1] == b'/' && rest.get(2).is_some_and(|b| b.is_ascii_alphabetic())))
+            && rest[0] == b'<'
+        {
+            // Skip the tag name and the attributes.
+            pos += rest
+                .iter()
+                .position(|b| b.is_ascii_whitespace() || *b == b'>')
+                .unwrap_or(rest.len());
+            while get_attribute(bytes, &mut pos).is_some() {}
+        } else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            pos += rest.iter().position(|b| *b == b'>').unwrap_or(rest.len()) + 1;
+        } else {
+            pos += 1;
+        }
+    }
+    None
+}
+
+/// Processes the attributes of a `meta` element in the prescan, and returns the encoding it declares.
+fn process_meta_attributes(bytes: &[u8], pos: &mut usize) -> Option<&'static Encoding> {
+    let mut attribute_list = Vec::new();
+    let mut got_pragma = false;
+    let mut need_pragma = None;
+    let mut charset = None;
+    while let Some((name, value)) = get_attribute(bytes, pos) {
+        if attribute_list.contains(&name) {
+            continue;
+        }
+        match name.as_slice() {
+            b"http-equiv" if value == b"content-type" => got_pragma = true,
+            b"content" if charset.is_none() => {
+                if let Some(encoding) = extract_encoding_from_meta(&value) {
+                    charset = Some(encoding);
+                    need_pragma = Some(true);
+                }
+            }
+            b"charset" => {
+                charset = Encoding::for_label(&value);
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+        attribute_list.push(name);
+    }
+
+    match (need_pragma, charset) {
+        (Some(true), _) if !got_pragma => None,
+        (Some(_), Some(charset)) if charset == UTF_16BE || charset == UTF_16LE => Some(UTF_8),
+        (Some(_), Some(charset)) if charset == X_USER_DEFINED => Some(WINDOWS_1252),
+        (Some(_), charset) => charset,
+        (None, _) => None,
+    }
+}
+
+/// Returns the next attribute as a lowercased name and a value, the latter of which is lowercased too.
+/// https://html.spec.whatwg.org/multipage/parsing.html#concept-get-attributes-when-sniffing
+fn get_attribute(bytes: &[u8], pos: &mut usize) -> Option<(Vec<u8>, Vec<u8>)> {
+    let peek = |pos: usize| bytes.get(pos).copied();
+    while peek(*pos).is_some_and(|b| b.is_ascii_whitespace() || b == b'/') {
+        *pos += 1;
+    }
+    if peek(*pos).is_none_or(|b| b == b'>') {
+        return None;
+    }
+
+    let mut name = Vec::new();
+    loop {
+        match peek(*pos)? {
+            b'=' if !name.is_empty() => break,
+            b if b.is_ascii_whitespace() => {
+                while peek(*pos).is_some_and(|b| b.is_ascii_whitespace()) {
+                    *pos += 1;
+                }
+                if peek(*pos)? != b'=' {
+                    return Some((name, Vec::new()));
+                }
+                break;
+            }
+            b'/' | b'>' => return Some((name, Vec::new())),
+            b => name.push(b.to_ascii_lowercase()),
+        }
+        *pos += 1;
+    }
+
+    // Skip the equals sign and the whitespace after it.
+    *pos += 1;
+    while peek(*pos).is_some_and(|b| b.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+    let mut value = Vec::new();
+    match peek(*pos)? {
+        quote @ (b'"' | b'\'') => loop {
+            *pos += 1;
+            match peek(*pos)? {
+                b if b == quote => {
+                    *pos += 1;
+                    return Some((name, value));
+                }
+                b => value.push(b.to_ascii_lowercase()),
+            }
+        },
+        b'>' => return Some((name, value)),
+        _ => {}
+    }
+    while let Some(b) = peek(*pos) {
+        if b.is_ascii_whitespace() || b == b'>' {
+            break;
+        }
+        value.push(b.to_ascii_lowercase());
+        *pos += 1;
+    }
+    Some((name, value))
+}
+
+/// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#algorithm-for-extracting-a-character-encoding-from-a-meta-element
+fn extract_encoding_from_meta(content: &[u8]) -> Option<&'static Encoding> {
+    let mut pos = 0;
+    loop {
+        pos += find(&content[pos..], b"charset")? + b"charset".len();
+        let rest = content[pos..].trim_ascii_start();
+        if let Some(rest) = rest.strip_prefix(b"=") {
+            let rest = rest.trim_ascii_start();
+            let value = match rest.first()? {
+                quote @ (b'"' | b'\'') => {
+                    let rest = &rest[1..];
+                    &rest[..find(rest, &[*quote])?]
+                }
+                _ => {
+                    let end = rest
+                        .iter()
+                        .position(|b| b.is_ascii_whitespace() || *b == b';')
+                        .unwrap_or(rest.len());
+                    &rest[..end]
+                }
+            };
+            return Encoding::for_label(value);
+        }
+        // The loop is restarted after "charset" if it is not followed by an equals sign.
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes.len() >= prefix.len() && bytes[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{ISO_8859_2, SHIFT_JIS};
+
+    #[test]
+    fn sniff_encodings() {
+        // BOM > transport layer > prescan.
+        assert_eq!(
+            sniff_encoding(b"\xFE\xFF\x00<", Some("text/html; charset=shift_jis")),
+            UTF_16BE
+        );
+        assert_eq!(
+            sniff_encoding(
+                b"<meta charset=utf-8>",
+                Some("text/html; charset=\"Shift_JIS\"")
+            ),
+            SHIFT_JIS
+        );
+        assert_eq!(
+            sniff_encoding(
+                b"<!-- <meta charset=utf-8> --><title x='>'></title><META charset = 'sjis'>",
+                None
+            ),
+            SHIFT_JIS
+        );
+        // The content attribute is used only with the pragma.
+        assert_eq!(
+            sniff_encoding(
+                b"<meta content=\"text/html; charset=iso-8859-2\"><meta content='text/html;charset = \"latin2\"' http-equiv=Content-Type>",
+                None
+            ),
+            ISO_8859_2
+        );
+        assert_eq!(sniff_encoding(b"<meta charset=utf-16le>", None), UTF_8);
+        assert_eq!(
+            sniff_encoding(b"<meta charset=x-user-defined>", None),
+            WINDOWS_1252
+        );
+        // Without any declaration, the encoding is guessed from the bytes.
+        assert_eq!(sniff_encoding("<p>caf\u{e9}</p>".as_bytes(), None), UTF_8);
+        assert_eq!(sniff_encoding(b"<p>caf\xE9</p>", None), WINDOWS_1252);
+    }
+
+    #[test]
+    fn decode_in_chunks() {
+        assert_eq!(
+            decode(b"<meta charset=shift_jis><p>\x82\xA0</p>", None),
+            "<meta charset=shift_jis><p>\u{3042}</p>"
+        );
+        assert_eq!(decode(b"\xEF\xBB\xBF<p>", None), "<p>");
+
+        // A character split across chunks is decoded when the rest of it is fed.
+        let mut decoder = ByteStreamDecoder::new(SHIFT_JIS);
+        assert_eq!(decoder.decode(b"a\x82", false), "a");
+        assert_eq!(decoder.decode(b"\xA0\x82", false), "\u{3042}");
+        assert_eq!(decoder.decode(b"", true), "\u{FFFD}");
+    }
+}
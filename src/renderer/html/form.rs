@@ -0,0 +1,517 @@
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::rc::Rc;
+
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::net::url::{set_query, QueryString};
+use crate::renderer::html::dom::{DomNode, Element, NodeType};
+
+/// A file selected in a file input.
+/// https://w3c.github.io/FileAPI/#dfn-file
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FormFile {
+    pub name: String,
+    pub content_type: String,
+    pub contents: Vec<u8>,
+}
+
+/// https://xhr.spec.whatwg.org/#formdataentryvalue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormDataEntryValue {
+    Text(String),
+    File(FormFile),
+}
+
+/// An entry of the entry list of a form, which is the data submitted by the form.
+/// https://xhr.spec.whatwg.org/#concept-formdata-entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormDataEntry {
+    pub name: String,
+    pub value: FormDataEntryValue,
+}
+
+impl FormDataEntry {
+    fn text(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            value: FormDataEntryValue::Text(value.to_string()),
+        }
+    }
+}
+
+/// The request with which a form is submitted. The URL of the action is not resolved yet, and is empty if
+/// the form is submitted to the URL of the document.
+/// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#form-submission-algorithm
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormSubmission {
+    /// The entries are submitted in the query of the action URL.
+    Get { action: String, query: String },
+    Post {
+        action: String,
+        content_type: String,
+        body: Vec<u8>,
+    },
+}
+
+impl FormSubmission {
+    pub fn get_action(&self) -> &str {
+        match self {
+            Self::Get { action, .. } | Self::Post { action, .. } => action,
+        }
+    }
+
+    /// Returns the URL to be navigated to, given the resolved URL of the action.
+    /// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#submit-mutate-action
+    pub fn get_url(&self, action_url: &str) -> String {
+        match self {
+            Self::Get { query, .. } => set_query(action_url, query),
+            Self::Post { .. } => action_url.to_string(),
+        }
+    }
+}
+
+/// Submits the form with the submit button, and returns the request to be sent, or `None` if the method is
+/// not supported. The attributes of the submit button, e.g. `formaction`, override those of the form. No
+/// files can be selected yet, so the file inputs submit empty files.
+/// todo: Support the `dialog` method, the `text/plain` enctype, the `target` attribute, and the encoding of
+/// the document, which is assumed to be UTF-8.
+/// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#concept-form-submit
+pub fn submit_form(
+    form: &Rc<RefCell<DomNode>>,
+    submitter: Option<&Rc<RefCell<DomNode>>>,
+) -> Option<FormSubmission> {
+    let get_attribute = |node: &Rc<RefCell<DomNode>>, name: &str| match &node.borrow().node_type {
+        NodeType::Element(elm) => elm.get_attribute(name).map(str::to_string),
+        _ => None,
+    };
+    let get_form_attribute = |name: &str| {
+        submitter
+            .and_then(|submitter| get_attribute(submitter, &format!("form{}", name)))
+            .or_else(|| get_attribute(form, name))
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    };
+    let action = get_form_attribute("action");
+    let method = get_form_attribute("method").to_ascii_lowercase();
+    let enctype = get_form_attribute("enctype").to_ascii_lowercase();
+
+    let encoding = pick_encoding(form, UTF_8);
+    let entries = construct_entry_list(form, submitter, encoding, |_| Vec::new());
+    match method.as_str() {
+        "post" if enctype == "multipart/form-data" => {
+            let boundary = generate_boundary();
+            Some(FormSubmission::Post {
+                action,
+                content_type: format!("multipart/form-data; boundary={}", boundary),
+                body: encode_multipart(&entries, encoding, &boundary),
+            })
+        }
+        "post" => Some(FormSubmission::Post {
+            action,
+            content_type: "application/x-www-form-urlencoded".to_string(),
+            body: encode_urlencoded(&entries, encoding).into_bytes(),
+        }),
+        "dialog" => None,
+        _ => Some(FormSubmission::Get {
+            action,
+            query: encode_urlencoded(&entries, encoding),
+        }),
+    }
+}
+
+/// Returns the entries submitted by the form in tree order. `submitter` is the button with which the form
+/// is submitted, if any, and `get_selected_files` returns the files selected in a file input.
+/// todo: Exclude the elements in the first `legend` of a disabled `fieldset` from the disabled ones, and
+/// support the `dirname` attribute.
+/// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#constructing-the-form-data-set
+pub fn construct_entry_list(
+    form: &Rc<RefCell<DomNode>>,
+    submitter: Option<&Rc<RefCell<DomNode>>>,
+    encoding: &'static Encoding,
+    get_selected_files: impl Fn(&Rc<RefCell<DomNode>>) -> Vec<FormFile>,
+) -> Vec<FormDataEntry> {
+    let mut entries = Vec::new();
+    for field in DomNode::get_descendants(&DomNode::get_root(form)) {
+        let NodeType::Element(elm) = field.borrow().node_type.clone() else {
+            continue;
+        };
+        if !elm.is_listed()
+            || elm.tag_name == "object"
+            || !DomNode::get_form_owner(&field).is_some_and(|owner| Rc::ptr_eq(&owner, form))
+            || has_ancestor(&field, |a| a.tag_name == "datalist")
+            || is_disabled(&field, &elm)
+        {
+            continue;
+        }
+        let is_submitter = submitter.is_some_and(|s| Rc::ptr_eq(s, &field));
+        let input_type = get_input_type(&elm);
+        match input_type.as_deref() {
+            // Only the button with which the form is submitted is submitted.
+            Some("submit" | "image" | "reset" | "button") if !is_submitter => continue,
+            Some("checkbox" | "radio") if elm.get_attribute("checked").is_none() => continue,
+            _ => {}
+        }
+
+        // The image button submits the coordinates at which it is clicked, which is always the origin.
+        if input_type.as_deref() == Some("image") {
+            let prefix = match elm.get_attribute("name") {
+                Some(name) if !name.is_empty() => format!("{}.", name),
+                _ => String::new(),
+            };
+            entries.push(FormDataEntry::text(&format!("{}x", prefix), "0"));
+            entries.push(FormDataEntry::text(&format!("{}y", prefix), "0"));
+            continue;
+        }
+        let Some(name) = elm.get_attribute("name").filter(|name| !name.is_empty()) else {
+            continue;
+        };
+
+        match (elm.tag_name.as_str(), input_type.as_deref()) {
+            ("select", _) => {
+                for option in get_selected_options(&field, &elm) {
+                    entries.push(FormDataEntry::text(name, &option));
+                }
+            }
+            (_, Some("checkbox" | "radio")) => {
+                entries.push(FormDataEntry::text(
+                    name,
+                    elm.get_attribute("value").unwrap_or("on"),
+                ));
+            }
+            (_, Some("file")) => {
+                let files = get_selected_files(&field);
+                if files.is_empty() {
+                    // An empty file is submitted if no file is selected.
+                    entries.push(FormDataEntry {
+                        name: name.to_string(),
+                        value: FormDataEntryValue::File(FormFile {
+                            content_type: "application/octet-stream".to_string(),
+                            ..Default::default()
+                        }),
+                    });
+                }
+                for file in files {
+                    entries.push(FormDataEntry {
+                        name: name.to_string(),
+                        value: FormDataEntryValue::File(file),
+                    });
+                }
+            }
+            // The value of the hidden input named `_charset_` is replaced with the name of the encoding.
+            (_, Some("hidden")) if name.eq_ignore_ascii_case("_charset_") => {
+                entries.push(FormDataEntry::text(name, encoding.name()));
+            }
+            ("textarea", _) => {
+                entries.push(FormDataEntry::text(name, &get_child_text(&field)));
+            }
+            _ => {
+                entries.push(FormDataEntry::text(
+                    name,
+                    elm.get_attribute("value").unwrap_or(""),
+                ));
+            }
+        }
+    }
+    entries
+}
+
+/// Returns the encoding in which the form is submitted, which is the first encoding in the
+/// `accept-charset` attribute that is supported, or the encoding of the document.
+/// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#picking-an-encoding-for-the-form
+pub fn pick_encoding(
+    form: &Rc<RefCell<DomNode>>,
+    document_encoding: &'static Encoding,
+) -> &'static Encoding {
+    let encoding = match &form.borrow().node_type {
+        NodeType::Element(elm) => match elm.get_attribute("accept-charset") {
+            Some(labels) => labels
+                .split_ascii_whitespace()
+                .find_map(|label| Encoding::for_label(label.as_bytes()))
+                .unwrap_or(UTF_8),
+            None => document_encoding,
+        },
+        _ => document_encoding,
+    };
+    // Encodings such as UTF-16 can't be used for submissions, so they are replaced with UTF-8.
+    encoding.output_encoding()
+}
+
+/// Encodes the entries into the body of a submission whose enctype is `application/x-www-form-urlencoded`,
+/// which is also the query of the URL for the GET method. Files are submitted by their names.
+/// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#application/x-www-form-urlencoded-encoding-algorithm
+pub fn encode_urlencoded(entries: &[FormDataEntry], encoding: &'static Encoding) -> String {
+    let mut query = QueryString::new();
+    for entry in entries {
+        let value = match &entry.value {
+            FormDataEntryValue::Text(text) => normalize_newlines(text),
+            FormDataEntryValue::File(file) => file.name.clone(),
+        };
+        query.append(&normalize_newlines(&entry.name), &value);
+    }
+    query.serialize(encoding)
+}
+
+/// Encodes the entries into the body of a submission whose enctype is `multipart/form-data`, in which the
+/// entries are separated by the boundary. The names and the texts are encoded in the encoding, and the
+/// contents of the files are written as they are.
+/// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#multipart/form-data-encoding-algorithm
+/// https://datatracker.ietf.org/doc/html/rfc7578
+pub fn encode_multipart(
+    entries: &[FormDataEntry],
+    encoding: &'static Encoding,
+    boundary: &str,
+) -> Vec<u8> {
+    let encode = |s: &str| encoding.output_encoding().encode(s).0.into_owned();
+    // The quotes and the newlines in the names can't be written in the headers as they are.
+    let escape = |s: &str| {
+        normalize_newlines(s)
+            .replace('\n', "%0A")
+            .replace('\r', "%0D")
+            .replace('"', "%22")
+    };
+
+    let mut body = Vec::new();
+    for entry in entries {
+        body.extend(format!("--{}\r\n", boundary).bytes());
+        body.extend(b"Content-Disposition: form-data; name=\"");
+        body.extend(encode(&escape(&entry.name)));
+        body.push(b'"');
+        match &entry.value {
+            FormDataEntryValue::Text(text) => {
+                body.extend(b"\r\n\r\n");
+                body.extend(encode(&normalize_newlines(text)));
+            }
+            FormDataEntryValue::File(file) => {
+                body.extend(b"; filename=\"");
+                body.extend(encode(&escape(&file.name)));
+                body.extend(b"\"\r\nContent-Type: ");
+                let content_type = if file.content_type.is_empty() {
+                    "application/octet-stream"
+                } else {
+                    &file.content_type
+                };
+                body.extend(content_type.bytes());
+                body.extend(b"\r\n\r\n");
+                body.extend(&file.contents);
+            }
+        }
+        body.extend(b"\r\n");
+    }
+    body.extend(format!("--{}--\r\n", boundary).bytes());
+    body
+}
+
+/// Returns a random boundary for `multipart/form-data`, which is unlikely to appear in the entries.
+pub fn generate_boundary() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    format!("----PentasFormBoundary{:016x}", hasher.finish())
+}
+
+/// Returns the type of the input, or `None` if the element is not an input. A `button` element is a submit
+/// button unless its type says otherwise.
+pub fn get_input_type(elm: &Element) -> Option<String> {
+    let input_type = elm.get_attribute("type").map(str::to_ascii_lowercase);
+    match elm.tag_name.as_str() {
+        "input" => Some(input_type.unwrap_or_else(|| "text".to_string())),
+        "button" => Some(
+            input_type
+                .filter(|t| matches!(t.as_str(), "reset" | "button"))
+                .unwrap_or_else(|| "submit".to_string()),
+        ),
+        _ => None,
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#concept-fe-disabled
+fn is_disabled(field: &Rc<RefCell<DomNode>>, elm: &Element) -> bool {
+    elm.get_attribute("disabled").is_some()
+        || has_ancestor(field, |a| {
+            a.tag_name == "fieldset" && a.get_attribute("disabled").is_some()
+        })
+}
+
+fn has_ancestor(node: &Rc<RefCell<DomNode>>, pred: impl Fn(&Element) -> bool) -> bool {
+    let mut ancestor = DomNode::get_parent(node);
+    while let Some(node) = ancestor {
+        if matches!(&node.borrow().node_type, NodeType::Element(elm) if pred(elm)) {
+            return true;
+        }
+        ancestor = DomNode::get_parent(&node);
+    }
+    false
+}
+
+/// Returns the values of the selected options which are not disabled. If the `select` element can select
+/// only one option and none is selected, the first option is selected.
+/// https://html.spec.whatwg.org/multipage/form-elements.html#selectedness-setting-algorithm
+fn get_selected_options(select: &Rc<RefCell<DomNode>>, elm: &Element) -> Vec<String> {
+    let options = DomNode::get_descendants(select)
+        .filter_map(|node| match &node.borrow().node_type {
+            NodeType::Element(option) if option.tag_name == "option" => Some((
+                option.get_attribute("selected").is_some(),
+                option.get_attribute("disabled").is_some(),
+                // The value is the text of the option with the whitespace collapsed, if it is not specified.
+                option.get_attribute("value").map_or_else(
+                    || {
+                        get_child_text(&node)
+                            .split_ascii_whitespace()
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    },
+                    str::to_string,
+                ),
+            )),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let selected = if elm.get_attribute("multiple").is_some() {
+        options.iter().filter(|(selected, ..)| *selected).collect()
+    } else {
+        // The last selected option wins, and the first option which is not disabled is the default.
+        options
+            .iter()
+            .rfind(|(selected, ..)| *selected)
+            .or_else(|| options.iter().find(|(_, disabled, _)| !disabled))
+            .into_iter()
+            .collect::<Vec<_>>()
+    };
+    selected
+        .into_iter()
+        .filter(|(_, disabled, _)| !disabled)
+        .map(|(.., value)| value.clone())
+        .collect()
+}
+
+fn get_child_text(node: &Rc<RefCell<DomNode>>) -> String {
+    DomNode::get_descendants(node)
+        .filter_map(|n| match &n.borrow().node_type {
+            NodeType::Text(text) => Some(text.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Replaces the newlines which are not CRLF with CRLF.
+/// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#converting-an-entry-list-to-a-list-of-name-value-pairs
+fn normalize_newlines(s: &str) -> String {
+    s.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .replace('\n', "\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::dom::DocumentTree;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    #[test]
+    fn encode_form_data() {
+        let html = "<html><head></head><body><form id=f accept-charset=\"x-unknown windows-1252\">\
+            <input name=q value=\"a b\"><input name=none><input type=checkbox name=c>\
+            <input type=checkbox name=c value=2 checked><input type=radio name=r checked>\
+            <input name=d disabled><fieldset disabled><input name=e></fieldset>\
+            <select name=s><option>x<option selected> y  z </option></select>\
+            <select name=m multiple><option selected>1<option>2<option selected disabled>3</select>\
+            <textarea name=t>1\n2</textarea><input type=hidden name=_charset_>\
+            <input type=file name=f><button name=b value=1>B</button><button name=b value=2>C</button>\
+            </form><input name=out form=f value=\"&#x3042;\"></body></html>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(document).unwrap();
+        let form = tree.get_element_by_id("f").unwrap();
+        let submitter = tree.get_elements_by_tag_name("button").remove(1);
+
+        let encoding = pick_encoding(&form, UTF_8);
+        assert_eq!(encoding, encoding_rs::WINDOWS_1252);
+        let entries = construct_entry_list(&form, Some(&submitter), encoding, |_| {
+            vec![FormFile {
+                name: "a\"b.txt".to_string(),
+                content_type: "text/plain".to_string(),
+                contents: b"abc".to_vec(),
+            }]
+        });
+        assert_eq!(
+            encode_urlencoded(&entries, encoding),
+            "q=a+b&none=&c=2&r=on&s=y+z&m=1&t=1%0D%0A2&_charset_=windows-1252&f=a%22b.txt&b=2\
+             &out=%26%2312354%3B"
+        );
+
+        let entries = [
+            FormDataEntry::text("a\nb", "1\n2"),
+            FormDataEntry {
+                name: "f".to_string(),
+                value: FormDataEntryValue::File(FormFile {
+                    name: "x\".bin".to_string(),
+                    content_type: String::new(),
+                    contents: vec![0, 255],
+                }),
+            },
+        ];
+        let mut expected =
+            b"--B\r\nContent-Disposition: form-data; name=\"a%0D%0Ab\"\r\n\r\n1\r\n2\r\n\
+            --B\r\nContent-Disposition: form-data; name=\"f\"; filename=\"x%22.bin\"\r\n\
+            Content-Type: application/octet-stream\r\n\r\n"
+                .to_vec();
+        expected.extend([0, 255]);
+        expected.extend(b"\r\n--B--\r\n");
+        assert_eq!(encode_multipart(&entries, UTF_8, "B"), expected);
+        assert_ne!(generate_boundary(), generate_boundary());
+    }
+
+    #[test]
+    fn submit_forms() {
+        let html = "<html><head></head><body>\
+            <form id=g action=\"search?old=1#top\"><input name=q value=\"a b\"><button>Go</button></form>\
+            <form id=p method=POST><input name=q value=1><input type=file name=f>\
+            <button formenctype=multipart/form-data>Upload</button>\
+            <button formaction=/post formmethod=get>Get</button></form>\
+            <form id=d method=dialog></form></body></html>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(document).unwrap();
+        let buttons = tree.get_elements_by_tag_name("button");
+
+        let form = tree.get_element_by_id("g").unwrap();
+        let submission = submit_form(&form, Some(&buttons[0])).unwrap();
+        assert_eq!(submission.get_action(), "search?old=1#top");
+        assert_eq!(
+            submission.get_url("http://example.com/search?old=1#top"),
+            "http://example.com/search?q=a+b#top"
+        );
+
+        let form = tree.get_element_by_id("p").unwrap();
+        assert_eq!(
+            submit_form(&form, None),
+            Some(FormSubmission::Post {
+                action: String::new(),
+                content_type: "application/x-www-form-urlencoded".to_string(),
+                body: b"q=1&f=".to_vec(),
+            })
+        );
+        let Some(FormSubmission::Post {
+            content_type, body, ..
+        }) = submit_form(&form, Some(&buttons[1]))
+        else {
+            panic!("The form is not submitted with POST");
+        };
+        let boundary = content_type
+            .strip_prefix("multipart/form-data; boundary=")
+            .unwrap();
+        assert!(body.ends_with(format!("--{}--\r\n", boundary).as_bytes()));
+        assert_eq!(
+            submit_form(&form, Some(&buttons[2])),
+            Some(FormSubmission::Get {
+                action: "/post".to_string(),
+                query: "q=1&f=".to_string(),
+            })
+        );
+
+        let form = tree.get_element_by_id("d").unwrap();
+        assert_eq!(submit_form(&form, None), None);
+    }
+}
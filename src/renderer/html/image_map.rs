@@ -0,0 +1,288 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::renderer::html::dom::{DomNode, NodeType};
+
+/// The shape of an `area` element, in CSS pixels relative to the top-left corner of the image.
+/// https://html.spec.whatwg.org/multipage/image-maps.html#attr-area-shape
+#[derive(Debug, Clone, PartialEq)]
+pub enum AreaShape {
+    Circle {
+        x: f64,
+        y: f64,
+        radius: f64,
+    },
+    /// The area covers the whole image.
+    Default,
+    Polygon(Vec<(f64, f64)>),
+    Rectangle {
+        left: f64,
+        top: f64,
+        right: f64,
+        bottom: f64,
+    },
+}
+
+impl AreaShape {
+    /// Returns the shape from the `shape` and `coords` attributes, or `None` if the area must be ignored.
+    /// https://html.spec.whatwg.org/multipage/image-maps.html#image-map-processing-model
+    pub fn parse(shape: Option<&str>, coords: Option<&str>) -> Option<Self> {
+        let coords = parse_float_list(coords.unwrap_or_default());
+        match shape.map(|s| s.to_ascii_lowercase()).as_deref() {
+            Some("circle" | "circ") => {
+                let [x, y, radius, ..] = coords[..] else {
+                    return None;
+                };
+                (radius > 0.0).then_some(Self::Circle { x, y, radius })
+            }
+            Some("default") => Some(Self::Default),
+            Some("poly" | "polygon") => {
+                if coords.len() < 6 {
+                    return None;
+                }
+                // If the number of coordinates is odd, the last one is ignored.
+                Some(Self::Polygon(
+                    coords.chunks_exact(2).map(|c| (c[0], c[1])).collect(),
+                ))
+            }
+            // The missing value default and the invalid value default are the rectangle state.
+            _ => {
+                let [x1, y1, x2, y2, ..] = coords[..] else {
+                    return None;
+                };
+                Some(Self::Rectangle {
+                    left: x1.min(x2),
+                    top: y1.min(y2),
+                    right: x1.max(x2),
+                    bottom: y1.max(y2),
+                })
+            }
+        }
+    }
+
+    /// Returns whether the point is inside the shape. `width` and `height` are the size of the image.
+    pub fn contains(&self, x: f64, y: f64, width: f64, height: f64) -> bool {
+        match self {
+            Self::Circle {
+                x: cx,
+                y: cy,
+                radius,
+            } => (x - cx).powi(2) + (y - cy).powi(2) <= radius.powi(2),
+            Self::Default => (0.0..=width).contains(&x) && (0.0..=height).contains(&y),
+            // The even-odd rule is used for polygons.
+            Self::Polygon(points) => {
+                let mut inside = false;
+                for (i, &(x1, y1)) in points.iter().enumerate() {
+                    let (x2, y2) = points[(i + 1) % points.len()];
+                    if (y1 > y) != (y2 > y) && x < (x2 - x1) * (y - y1) / (y2 - y1) + x1 {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+            Self::Rectangle {
+                left,
+                top,
+                right,
+                bottom,
+            } => (*left..=*right).contains(&x) && (*top..=*bottom).contains(&y),
+        }
+    }
+
+    /// Returns the bounding box of the shape as `(x, y, width, height)`.
+    pub fn get_bounding_box(&self, width: f64, height: f64) -> (f64, f64, f64, f64) {
+        match self {
+            Self::Circle { x, y, radius } => (x - radius, y - radius, radius * 2.0, radius * 2.0),
+            Self::Default => (0.0, 0.0, width, height),
+            Self::Polygon(points) => {
+                let (min_x, min_y, max_x, max_y) = points.iter().fold(
+                    (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+                    |(min_x, min_y, max_x, max_y), &(x, y)| {
+                        (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+                    },
+                );
+                (min_x, min_y, max_x - min_x, max_y - min_y)
+            }
+            Self::Rectangle {
+                left,
+                top,
+                right,
+                bottom,
+            } => (*left, *top, right - left, bottom - top),
+        }
+    }
+}
+
+/// An `area` element of the image map associated with an image.
+/// https://html.spec.whatwg.org/multipage/image-maps.html#the-area-element
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageMapArea {
+    pub shape: AreaShape,
+    /// The URL of the hyperlink. Areas without hyperlinks are not interactive, but still hide the areas
+    /// behind them from hit testing.
+    pub href: Option<String>,
+    pub alt: String,
+}
+
+impl ImageMapArea {
+    /// Returns whether the area is focusable, which is the case if the area is a hyperlink.
+    /// https://html.spec.whatwg.org/multipage/interaction.html#focusable-area
+    pub fn is_focusable(&self) -> bool {
+        self.href.is_some()
+    }
+}
+
+/// Returns the areas of the image map associated with the `img` element by its `usemap` attribute,
+/// in tree order. If no image map is associated, an empty list is returned.
+/// https://html.spec.whatwg.org/multipage/image-maps.html#image-map-processing-model
+pub fn get_image_map_areas(img: &Rc<RefCell<DomNode>>) -> Vec<ImageMapArea> {
+    let name = match &img.borrow().node_type {
+        NodeType::Element(elm) if elm.tag_name == "img" => {
+            // The value must be a valid hash-name reference.
+            match elm
+                .get_attribute("usemap")
+                .and_then(|v| v.strip_prefix('#'))
+            {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => return Vec::new(),
+            }
+        }
+        _ => return Vec::new(),
+    };
+
+    // The first map element in tree order whose name or id matches is used.
+    let is_map = |node: &Rc<RefCell<DomNode>>| match &node.borrow().node_type {
+        NodeType::Element(elm) => {
+            elm.tag_name == "map"
+                && (elm.get_attribute("name") == Some(&name)
+                    || elm.get_attribute("id") == Some(&name))
+        }
+        _ => false,
+    };
+    let Some(map) = DomNode::get_descendants(&DomNode::get_root(img)).find(is_map) else {
+        return Vec::new();
+    };
+
+    DomNode::get_descendants(&map)
+        .filter_map(|node| {
+            let node = node.borrow();
+            let NodeType::Element(elm) = &node.node_type else {
+                return None;
+            };
+            if elm.tag_name != "area" {
+                return None;
+            }
+            Some(ImageMapArea {
+                shape: AreaShape::parse(elm.get_attribute("shape"), elm.get_attribute("coords"))?,
+                href: elm.get_attribute("href").map(|href| href.to_string()),
+                alt: elm.get_attribute("alt").unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// https://html.spec.whatwg.org/multipage/common-microsyntaxes.html#rules-for-parsing-a-list-of-floating-point-numbers
+fn parse_float_list(input: &str) -> Vec<f64> {
+    input
+        .split(|c: char| c.is_ascii_whitespace() || c == ',' || c == ';')
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            // Garbage after a number is ignored, and a value with no number is treated as zero.
+            let end = s
+                .char_indices()
+                .find(|(i, c)| !(c.is_ascii_digit() || *c == '.' || (*i == 0 && *c == '-')))
+                .map_or(s.len(), |(i, _)| i);
+            s[..end].parse::<f64>().unwrap_or(0.0)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    #[test]
+    fn parse_area_shapes() {
+        assert_eq!(
+            AreaShape::parse(None, Some("30,40, 10 ;20")),
+            Some(AreaShape::Rectangle {
+                left: 10.0,
+                top: 20.0,
+                right: 30.0,
+                bottom: 40.0
+            })
+        );
+        assert_eq!(
+            AreaShape::parse(Some("CIRC"), Some("5,5,3px")),
+            Some(AreaShape::Circle {
+                x: 5.0,
+                y: 5.0,
+                radius: 3.0
+            })
+        );
+        assert_eq!(AreaShape::parse(Some("circle"), Some("5,5,0")), None);
+        assert_eq!(
+            AreaShape::parse(Some("poly"), Some("0,0,10,0,0,10,5")),
+            Some(AreaShape::Polygon(vec![
+                (0.0, 0.0),
+                (10.0, 0.0),
+                (0.0, 10.0)
+            ]))
+        );
+        assert_eq!(AreaShape::parse(Some("rect"), Some("1,2,3")), None);
+        assert_eq!(
+            AreaShape::parse(Some("default"), None),
+            Some(AreaShape::Default)
+        );
+    }
+
+    #[test]
+    fn hit_test_areas() {
+        let triangle = AreaShape::Polygon(vec![(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)]);
+        assert!(triangle.contains(2.0, 2.0, 100.0, 100.0));
+        assert!(!triangle.contains(8.0, 8.0, 100.0, 100.0));
+
+        let circle = AreaShape::Circle {
+            x: 50.0,
+            y: 50.0,
+            radius: 10.0,
+        };
+        assert!(circle.contains(55.0, 55.0, 100.0, 100.0));
+        assert!(!circle.contains(59.0, 59.0, 100.0, 100.0));
+
+        assert!(AreaShape::Default.contains(100.0, 0.0, 100.0, 100.0));
+        assert!(!AreaShape::Default.contains(101.0, 0.0, 100.0, 100.0));
+    }
+
+    #[test]
+    fn get_areas_of_image_map() {
+        let html = r##"<html><head></head><body><img usemap="#m" width="100" height="100"><map name="m"><area href="/a" coords="0,0,50,50"><p><area shape="circle" coords="1,1"><area shape="default" alt="rest"></p></map></body></html>"##;
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let body = Rc::clone(&root.borrow().children[0].borrow().children[1]);
+        let img = Rc::clone(&body.borrow().children[0]);
+
+        // The circle without a radius is ignored.
+        assert_eq!(
+            get_image_map_areas(&img),
+            vec![
+                ImageMapArea {
+                    shape: AreaShape::Rectangle {
+                        left: 0.0,
+                        top: 0.0,
+                        right: 50.0,
+                        bottom: 50.0
+                    },
+                    href: Some("/a".to_string()),
+                    alt: String::new(),
+                },
+                ImageMapArea {
+                    shape: AreaShape::Default,
+                    href: None,
+                    alt: "rest".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::renderer::html::dom::{DomNode, Element, NodeType};
+use crate::renderer::html::form::{get_input_type, submit_form, FormSubmission};
+use crate::renderer::html::mutation::{remove_attribute, set_attribute, set_element_state};
+
+/// What the browser must do after an element is activated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Activation {
+    /// No element with activation behavior is activated.
+    None,
+    /// The states or the attributes of the elements are changed, so the document must be rendered again.
+    Restyle,
+    /// The hyperlink must be followed to the URL, which is not resolved yet.
+    FollowHyperlink(String),
+    /// The form is submitted, and the browser must navigate with the request.
+    SubmitForm(FormSubmission),
+}
+
+/// Runs the activation behavior of the node or its nearest inclusive ancestor which has one, as if it is
+/// clicked. This makes the pages interactive without scripting: the checkboxes and the radio buttons are
+/// toggled (including through their labels), the `details` elements are opened and closed with their
+/// summaries, the links to fragments change the target element, and the submit buttons submit their forms.
+/// https://html.spec.whatwg.org/multipage/interaction.html#activation-behaviour
+pub fn activate(node_ref: &Rc<RefCell<DomNode>>) -> Activation {
+    let mut node = Some(Rc::clone(node_ref));
+    while let Some(current) = node {
+        if let Some(activation) = run_activation_behavior(&current) {
+            return activation;
+        }
+        node = DomNode::get_parent(&current);
+    }
+    Activation::None
+}
+
+/// Makes the element indicated by the fragment the target element of the document, which is the element
+/// whose ID is the fragment, or the `a` element whose name is the fragment. Returns whether the target
+/// element is found.
+/// https://html.spec.whatwg.org/multipage/browsing-the-web.html#find-a-potential-indicated-element
+pub fn set_target(document: &Rc<RefCell<DomNode>>, fragment: &str) -> bool {
+    let is_named_anchor = |node: &Rc<RefCell<DomNode>>| {
+        matches!(&node.borrow().node_type,
+            NodeType::Element(elm) if elm.tag_name == "a" && elm.get_attribute("name") == Some(fragment))
+    };
+    // The names of the anchors are not indexed, so the tree is scanned only if no element has the ID.
+    let target = (!fragment.is_empty())
+        .then(|| {
+            DomNode::get_element_by_id_in_tree(document, fragment)
+                .or_else(|| DomNode::get_descendants(document).find(is_named_anchor))
+        })
+        .flatten();
+    let old_target = document
+        .borrow_mut()
+        .target_element
+        .take()
+        .and_then(|target| target.upgrade())
+        .filter(|old| !target.as_ref().is_some_and(|new| Rc::ptr_eq(old, new)));
+    for (element, is_target) in [(old_target, false), (target.clone(), true)] {
+        if let Some(element) = element {
+            let mut state = element.borrow().state;
+            state.target = is_target;
+            set_element_state(&element, state);
+        }
+    }
+    document.borrow_mut().target_element = target.as_ref().map(Rc::downgrade);
+    target.is_some()
+}
+
+/// The user actions which are reflected in the states of the elements and matched by the user action
+/// pseudo-classes.
+/// https://www.w3.org/TR/selectors-4/#useraction-pseudos
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserAction {
+    Hover,
+    Active,
+    Focus,
+}
+
+impl UserAction {
+    pub const ALL: [Self; 3] = [Self::Hover, Self::Active, Self::Focus];
+
+    /// Returns the name of the pseudo-class which matches the elements in the state, without the colon.
+    pub fn get_pseudo_class(&self) -> &'static str {
+        match self {
+            Self::Hover => "hover",
+            Self::Active => "active",
+            Self::Focus => "focus",
+        }
+    }
+
+    /// Sets the state of the element without invalidating any styles, since the elements to be restyled
+    /// are found with the invalidation map of the user action instead.
+    fn set_state(&self, node_ref: &Rc<RefCell<DomNode>>, value: bool) {
+        let state = &mut node_ref.borrow_mut().state;
+        match self {
+            Self::Hover => state.hover = value,
+            Self::Active => state.active = value,
+            Self::Focus => state.focus = value,
+        }
+    }
+}
+
+/// Moves the state of the user action from the element `old` to the element `new`, and returns the elements
+/// which enter or leave the state, whose styles are not invalidated. The hover and active states also apply to the ancestors of the element,
+/// while the focus state applies only to the element itself.
+/// https://www.w3.org/TR/selectors-4/#the-hover-pseudo
+pub fn move_user_action(
+    action: UserAction,
+    old: Option<&Rc<RefCell<DomNode>>>,
+    new: Option<&Rc<RefCell<DomNode>>>,
+) -> Vec<Rc<RefCell<DomNode>>> {
+    let get_elements = |node: Option<&Rc<RefCell<DomNode>>>| {
+        let mut elements = Vec::new();
+        let mut node = node.cloned();
+        while let Some(current) = node {
+            if matches!(current.borrow().node_type, NodeType::Element(_)) {
+                elements.push(Rc::clone(&current));
+            }
+            if action == UserAction::Focus {
+                break;
+            }
+            node = DomNode::get_parent(&current);
+        }
+        elements
+    };
+    let old_elements = get_elements(old);
+    let new_elements = get_elements(new);
+    let contains = |elements: &[Rc<RefCell<DomNode>>], node: &Rc<RefCell<DomNode>>| {
+        elements.iter().any(|n| Rc::ptr_eq(n, node))
+    };
+
+    let mut changed = Vec::new();
+    for node in &old_elements {
+        if !contains(&new_elements, node) {
+            action.set_state(node, false);
+            changed.push(Rc::clone(node));
+        }
+    }
+    for node in &new_elements {
+        if !contains(&old_elements, node) {
+            action.set_state(node, true);
+            changed.push(Rc::clone(node));
+        }
+    }
+    changed
+}
+
+/// Returns the node or its nearest inclusive ancestor which can be focused by clicking it.
+/// todo: Support the `contenteditable` elements and the sequential focus navigation with the Tab key.
+/// https://html.spec.whatwg.org/multipage/interaction.html#focusable-area
+pub fn get_focusable_ancestor(node_ref: &Rc<RefCell<DomNode>>) -> Option<Rc<RefCell<DomNode>>> {
+    let mut node = Some(Rc::clone(node_ref));
+    while let Some(current) = node {
+        if matches!(&current.borrow().node_type, NodeType::Element(elm) if is_focusable(elm)) {
+            return Some(current);
+        }
+        node = DomNode::get_parent(&current);
+    }
+    None
+}
+
+fn is_focusable(elm: &Element) -> bool {
+    if elm.get_attribute("tabindex").is_some() {
+        return true;
+    }
+    match elm.tag_name.as_str() {
+        "a" | "area" => elm.get_attribute("href").is_some(),
+        // https://html.spec.whatwg.org/multipage/semantics-other.html#concept-element-disabled
+        "button" | "select" | "textarea" => elm.get_attribute("disabled").is_none(),
+        "input" => {
+            elm.get_attribute("disabled").is_none()
+                && get_input_type(elm).as_deref() != Some("hidden")
+        }
+        _ => false,
+    }
+}
+
+fn run_activation_behavior(node_ref: &Rc<RefCell<DomNode>>) -> Option<Activation> {
+    let NodeType::Element(elm) = node_ref.borrow().node_type.clone() else {
+        return None;
+    };
+    match elm.tag_name.as_str() {
+        // https://html.spec.whatwg.org/multipage/links.html#following-hyperlinks-2
+        "a" | "area" => {
+            let href = elm.get_attribute("href")?;
+            Some(match href.strip_prefix('#') {
+                Some(fragment) => {
+                    set_target(&DomNode::get_root(node_ref), fragment);
+                    Activation::Restyle
+                }
+                None => Activation::FollowHyperlink(href.to_string()),
+            })
+        }
+        // https://html.spec.whatwg.org/multipage/input.html#submit-button-state-(type=submit)
+        "input" | "button"
+            if matches!(get_input_type(&elm).as_deref(), Some("submit" | "image")) =>
+        {
+            if elm.get_attribute("disabled").is_some() {
+                return Some(Activation::None);
+            }
+            let form = DomNode::get_form_owner(node_ref)?;
+            Some(
+                submit_form(&form, Some(node_ref)).map_or(Activation::None, Activation::SubmitForm),
+            )
+        }
+        "input" => {
+            let input_type = get_input_type(&elm);
+            if !matches!(input_type.as_deref(), Some("checkbox" | "radio")) {
+                return None;
+            }
+            if elm.get_attribute("disabled").is_some() {
+                return Some(Activation::None);
+            }
+            if input_type.as_deref() == Some("checkbox") {
+                // https://html.spec.whatwg.org/multipage/input.html#checkbox-state-(type=checkbox)
+                if !remove_attribute(node_ref, "checked") {
+                    set_attribute(node_ref, "checked", "").ok()?;
+                }
+            } else {
+                // Checking a radio button unchecks the others in its group.
+                // https://html.spec.whatwg.org/multipage/input.html#radio-button-group
+                for other in get_radio_group(node_ref, &elm) {
+                    remove_attribute(&other, "checked");
+                }
+                set_attribute(node_ref, "checked", "").ok()?;
+            }
+            Some(Activation::Restyle)
+        }
+        // https://html.spec.whatwg.org/multipage/forms.html#the-label-element
+        "label" => {
+            let control = DomNode::get_labeled_control(node_ref)?;
+            Some(run_activation_behavior(&control).unwrap_or(Activation::None))
+        }
+        // https://html.spec.whatwg.org/multipage/interactive-elements.html#the-summary-element
+        "summary" => {
+            let details = DomNode::get_parent(node_ref)?;
+            let is_summary_for_details = match &details.borrow().node_type {
+                NodeType::Element(parent) if parent.tag_name == "details" => {
+                    get_summary_for_details(&details).is_some_and(|s| Rc::ptr_eq(&s, node_ref))
+                }
+                _ => false,
+            };
+            if !is_summary_for_details {
+                return None;
+            }
+            if !remove_attribute(&details, "open") {
+                set_attribute(&details, "open", "").ok()?;
+            }
+            Some(Activation::Restyle)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the first `summary` child of the `details` element, which opens and closes it.
+/// https://html.spec.whatwg.org/multipage/interactive-elements.html#summary-for-its-parent-details
+pub fn get_summary_for_details(details: &Rc<RefCell<DomNode>>) -> Option<Rc<RefCell<DomNode>>> {
+    details
+        .borrow()
+        .children
+        .iter()
+        .find(|child| {
+            matches!(&child.borrow().node_type, NodeType::Element(elm) if elm.tag_name == "summary")
+        })
+        .cloned()
+}
+
+/// Returns the other radio buttons in the same tree with the same form owner and name as the radio button.
+fn get_radio_group(node_ref: &Rc<RefCell<DomNode>>, elm: &Element) -> Vec<Rc<RefCell<DomNode>>> {
+    let Some(name) = elm.get_attribute("name").filter(|name| !name.is_empty()) else {
+        return Vec::new();
+    };
+    let form_owner = DomNode::get_form_owner(node_ref);
+    DomNode::get_descendants(&DomNode::get_root(node_ref))
+        .filter(|other| {
+            !Rc::ptr_eq(other, node_ref)
+                && matches!(&other.borrow().node_type, NodeType::Element(o)
+                    if o.tag_name == "input"
+                        && get_input_type(o).as_deref() == Some("radio")
+                        && o.get_attribute("name") == Some(name))
+                && match (&form_owner, DomNode::get_form_owner(other)) {
+                    (Some(a), Some(b)) => Rc::ptr_eq(a, &b),
+                    (None, None) => true,
+                    _ => false,
+                }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    #[test]
+    fn activate_elements() {
+        let html = "<label>Dark <input type=checkbox id=dark></label>\
+            <input type=radio name=r id=r1 checked><label for=r2 id=l2>Two</label><input type=radio name=r id=r2>\
+            <details id=d><summary id=s>More</summary><p>Hidden</p><summary id=s2></summary></details>\
+            <a href=\"#sec\" id=to-sec>Section</a><a href=\"/next\" id=next>Next</a><section id=sec></section>\
+            <form action=/search><input name=q><button id=submit><span>Go</span></button>\
+            <input type=submit disabled id=disabled-submit></form><button id=no-form></button>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let get = |selector: &str| {
+            DomNode::query_selector(&document, selector)
+                .unwrap()
+                .unwrap()
+        };
+        let matches = |selector: &str| DomNode::query_selector(&document, selector).unwrap();
+        let is_open = || match &get("#d").borrow().node_type {
+            NodeType::Element(elm) => elm.get_attribute("open").is_some(),
+            _ => unreachable!(),
+        };
+
+        // The checkbox is toggled through its label, including the text in it.
+        let text = Rc::clone(&get("label").borrow().children[0]);
+        assert_eq!(activate(&text), Activation::Restyle);
+        assert!(matches("#dark:checked").is_some());
+        assert_eq!(activate(&get("#dark")), Activation::Restyle);
+        assert!(matches("#dark:checked").is_none());
+
+        // Checking a radio button unchecks the other one in the group.
+        assert_eq!(activate(&get("#l2")), Activation::Restyle);
+        assert!(matches("#r1:checked").is_none());
+        assert!(matches("#r2:checked").is_some());
+
+        // Only the first summary opens and closes the details.
+        assert_eq!(activate(&get("#s2")), Activation::None);
+        assert_eq!(activate(&get("#s")), Activation::Restyle);
+        assert!(is_open());
+        assert_eq!(activate(&get("#s")), Activation::Restyle);
+        assert!(!is_open());
+
+        // The links to fragments change the target element, while the others are followed.
+        assert_eq!(activate(&get("#to-sec")), Activation::Restyle);
+        assert!(matches("section:target").is_some());
+        assert!(set_target(&document, "next"));
+        assert!(matches("section:target").is_none());
+        assert!(matches("#next:target").is_some());
+        assert!(set_target(&document, "sec"));
+        assert_eq!(
+            activate(&get("#next")),
+            Activation::FollowHyperlink("/next".to_string())
+        );
+        assert!(!set_target(&document, "missing"));
+        assert!(matches(":target").is_none());
+        assert_eq!(activate(&get("p")), Activation::None);
+
+        // The submit buttons submit their form owners, if any.
+        assert_eq!(
+            activate(&get("#submit")),
+            Activation::SubmitForm(FormSubmission::Get {
+                action: "/search".to_string(),
+                query: "q=".to_string(),
+            })
+        );
+        assert_eq!(activate(&get("#disabled-submit")), Activation::None);
+        assert_eq!(activate(&get("#no-form")), Activation::None);
+    }
+
+    #[test]
+    fn move_user_actions() {
+        let html = "<div id=outer><p id=p><a href=\"/\" id=link><span id=text>Link</span></a></p>\
+            <input type=hidden id=hidden><button disabled id=disabled></button><div tabindex=0 id=tab></div></div>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let get = |id: &str| {
+            DomNode::query_selector(&document, &format!("#{}", id))
+                .unwrap()
+                .unwrap()
+        };
+        let get_ids = |nodes: Vec<Rc<RefCell<DomNode>>>| {
+            nodes
+                .iter()
+                .map(|node| match &node.borrow().node_type {
+                    NodeType::Element(elm) => {
+                        elm.get_attribute("id").unwrap_or_default().to_string()
+                    }
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let matches =
+            |selector: &str| get_ids(DomNode::query_selector_all(&document, selector).unwrap());
+
+        // The hovered element and its ancestors are in the hover state.
+        let changed = move_user_action(UserAction::Hover, None, Some(&get("text")));
+        assert_eq!(get_ids(changed), ["text", "link", "p", "outer", "", ""]);
+        assert_eq!(matches("p :hover"), ["link", "text"]);
+        // Only the elements which enter or leave the state are changed.
+        let changed = move_user_action(UserAction::Hover, Some(&get("text")), Some(&get("tab")));
+        assert_eq!(get_ids(changed), ["text", "link", "p", "tab"]);
+        assert_eq!(matches("div:hover"), ["outer", "tab"]);
+
+        // The focus moves to the nearest focusable ancestor, but not to its ancestors.
+        let link = get_focusable_ancestor(&get("text")).unwrap();
+        assert_eq!(
+            get_ids(move_user_action(UserAction::Focus, None, Some(&link))),
+            ["link"]
+        );
+        assert_eq!(matches(":focus"), ["link"]);
+        assert!(get_focusable_ancestor(&get("hidden")).is_none());
+        assert!(get_focusable_ancestor(&get("disabled")).is_none());
+        assert!(get_focusable_ancestor(&get("tab")).is_some());
+    }
+}
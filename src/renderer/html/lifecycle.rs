@@ -0,0 +1,60 @@
+use std::fmt;
+use std::time::Duration;
+
+/// The interval to which the timers of hidden pages are aligned.
+pub const HIDDEN_TIMER_ALIGNMENT: Duration = Duration::from_secs(1);
+
+/// Whether the page is visible to the user. The page is hidden while its window is minimized or closed, in
+/// which case its work is throttled: it is not painted, so its images are not decoded, its timers are
+/// clamped, and its navigations scheduled by the timers wait until it is visible again.
+/// todo: Expose this as `document.visibilityState`, and pause the animations and `requestAnimationFrame()`
+/// callbacks of the hidden pages, once scripting is supported.
+/// https://html.spec.whatwg.org/multipage/interaction.html#page-visibility
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VisibilityState {
+    #[default]
+    Visible,
+    Hidden,
+}
+
+impl fmt::Display for VisibilityState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Visible => write!(f, "visible"),
+            Self::Hidden => write!(f, "hidden"),
+        }
+    }
+}
+
+/// Returns the delay after which a timer of the page fires. The delays of the timers of hidden pages are
+/// rounded up to the alignment, so that they wake the browser up at most once per alignment.
+/// https://developer.chrome.com/blog/timer-throttling-in-chrome-88
+pub fn get_timer_delay(delay: Duration, visibility: VisibilityState) -> Duration {
+    match visibility {
+        VisibilityState::Visible => delay,
+        VisibilityState::Hidden => {
+            let alignment = HIDDEN_TIMER_ALIGNMENT.as_millis();
+            let delay = delay.as_millis().div_ceil(alignment).max(1) * alignment;
+            Duration::from_millis(delay as u64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamp_hidden_timers() {
+        let delays = [0, 10, 1000, 1500, 3000].map(Duration::from_millis);
+        assert_eq!(
+            delays.map(|d| get_timer_delay(d, VisibilityState::Visible)),
+            delays
+        );
+        assert_eq!(
+            delays.map(|d| get_timer_delay(d, VisibilityState::Hidden)),
+            [1000, 1000, 1000, 2000, 3000].map(Duration::from_millis)
+        );
+        assert_eq!(VisibilityState::Hidden.to_string(), "hidden");
+    }
+}
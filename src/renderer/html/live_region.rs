@@ -0,0 +1,296 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+use crate::renderer::html::dom::{DomNode, Element, NodeType};
+
+/// How urgently the changes of a live region are announced.
+/// https://w3c.github.io/aria/#aria-live
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Politeness {
+    /// Announced when the user is idle.
+    Polite,
+    /// Announced immediately, interrupting the current speech.
+    Assertive,
+}
+
+impl Politeness {
+    /// Returns the politeness of the live region, or `None` if the element is not a live region. The
+    /// `aria-live` attribute takes precedence over the implicit value of the role.
+    /// https://w3c.github.io/aria/#live_region_roles
+    fn from_element(elm: &Element) -> Option<Self> {
+        match elm.get_attribute("aria-live").map(str::to_ascii_lowercase) {
+            Some(live) if live == "polite" => return Some(Self::Polite),
+            Some(live) if live == "assertive" => return Some(Self::Assertive),
+            Some(live) if live == "off" => return None,
+            _ => {}
+        }
+        let role = elm
+            .get_attribute("role")
+            .and_then(|role| role.split_ascii_whitespace().next())
+            .map(str::to_ascii_lowercase);
+        match role.as_deref() {
+            Some("alert") => Some(Self::Assertive),
+            Some("log" | "status") => Some(Self::Polite),
+            // The `output` element has the implicit role of `status`.
+            None if elm.tag_name == "output" => Some(Self::Polite),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Politeness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Polite => write!(f, "polite"),
+            Self::Assertive => write!(f, "assertive"),
+        }
+    }
+}
+
+/// A change of a live region to be spoken by screen readers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Announcement {
+    pub politeness: Politeness,
+    pub text: String,
+}
+
+impl fmt::Display for Announcement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {}", self.politeness, self.text)
+    }
+}
+
+/// The accessibility API of the platform, through which the announcements reach screen readers.
+pub trait AccessibilityBackend: fmt::Debug {
+    fn announce(&self, announcement: &Announcement);
+}
+
+/// A backend which keeps the announcements, which is used when no screen reader is attached, e.g. in
+/// CLI mode.
+#[derive(Debug, Default)]
+pub struct AnnouncementLog {
+    announcements: RefCell<Vec<Announcement>>,
+}
+
+impl AnnouncementLog {
+    pub fn take_announcements(&self) -> Vec<Announcement> {
+        self.announcements.take()
+    }
+}
+
+impl AccessibilityBackend for AnnouncementLog {
+    fn announce(&self, announcement: &Announcement) {
+        self.announcements.borrow_mut().push(announcement.clone());
+    }
+}
+
+#[derive(Debug)]
+struct LiveRegion {
+    node: Weak<RefCell<DomNode>>,
+    /// The rendered text of the region at the last update, one item per text node.
+    text: Vec<String>,
+}
+
+/// Tracks the live regions of a document, and announces the changes of their rendered text between
+/// updates. The content of a region found for the first time is not announced, since it is not a change.
+/// https://w3c.github.io/aria/#dfn-live-region
+#[derive(Debug, Default)]
+pub struct LiveRegionTracker {
+    regions: Vec<LiveRegion>,
+}
+
+impl LiveRegionTracker {
+    /// Compares the rendered text of the live regions with that at the last update, and sends the
+    /// announcements of the changed regions to the backend in tree order.
+    pub fn update(&mut self, document: &Rc<RefCell<DomNode>>, backend: &dyn AccessibilityBackend) {
+        let mut regions = Vec::new();
+        for node in DomNode::get_descendants(document) {
+            let (politeness, is_busy, is_atomic, relevant) = match &node.borrow().node_type {
+                NodeType::Element(elm) => {
+                    let Some(politeness) = Politeness::from_element(elm) else {
+                        continue;
+                    };
+                    let relevant = elm
+                        .get_attribute("aria-relevant")
+                        .unwrap_or("additions text")
+                        .to_ascii_lowercase();
+                    (
+                        politeness,
+                        elm.get_attribute("aria-busy") == Some("true"),
+                        elm.get_attribute("aria-atomic") == Some("true"),
+                        relevant,
+                    )
+                }
+                _ => continue,
+            };
+
+            let text = get_rendered_text(&node);
+            let Some(old) = self
+                .regions
+                .iter_mut()
+                .find(|r| r.node.upgrade().is_some_and(|n| Rc::ptr_eq(&n, &node)))
+            else {
+                regions.push(LiveRegion {
+                    node: Rc::downgrade(&node),
+                    text,
+                });
+                continue;
+            };
+            // The changes of a busy region are announced at once when it is no longer busy.
+            let old_text = std::mem::take(&mut old.text);
+            if is_busy || old_text == text {
+                regions.push(LiveRegion {
+                    node: Rc::downgrade(&node),
+                    text: old_text,
+                });
+                continue;
+            }
+
+            let announced = if is_atomic {
+                text.clone()
+            } else {
+                let has = |token: &str| {
+                    relevant
+                        .split_ascii_whitespace()
+                        .any(|t| t == token || t == "all")
+                };
+                let mut changed = Vec::new();
+                if has("additions") || has("text") {
+                    changed.extend(subtract(&text, &old_text));
+                }
+                if has("removals") {
+                    changed.extend(subtract(&old_text, &text));
+                }
+                changed
+            };
+            if !announced.is_empty() {
+                backend.announce(&Announcement {
+                    politeness,
+                    text: announced.join(" "),
+                });
+            }
+            regions.push(LiveRegion {
+                node: Rc::downgrade(&node),
+                text,
+            });
+        }
+        // The regions removed from the document are forgotten.
+        self.regions = regions;
+    }
+}
+
+/// Returns the items of `a` which are not in `b`, counting the duplicates.
+fn subtract(a: &[String], b: &[String]) -> Vec<String> {
+    let mut b = b.iter().collect::<Vec<_>>();
+    a.iter()
+        .filter(|item| match b.iter().position(|other| other == item) {
+            Some(i) => {
+                b.remove(i);
+                false
+            }
+            None => true,
+        })
+        .cloned()
+        .collect()
+}
+
+/// Returns the text of the text nodes in the region with the whitespace collapsed. The hidden elements
+/// and the nested live regions, which are announced by themselves, are excluded.
+fn get_rendered_text(region: &Rc<RefCell<DomNode>>) -> Vec<String> {
+    let mut text = Vec::new();
+    for child in &region.borrow().children {
+        match &child.borrow().node_type {
+            NodeType::Text(t) => {
+                let t = t.split_ascii_whitespace().collect::<Vec<_>>().join(" ");
+                if !t.is_empty() {
+                    text.push(t);
+                }
+            }
+            NodeType::Element(elm)
+                if elm.get_attribute("hidden").is_some()
+                    || elm.get_attribute("aria-hidden") == Some("true")
+                    || matches!(elm.tag_name.as_str(), "script" | "style" | "template")
+                    || Politeness::from_element(elm).is_some() => {}
+            NodeType::Element(_) => text.extend(get_rendered_text(child)),
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::mutation::{append_child, remove_child, set_attribute};
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    #[test]
+    fn announce_live_regions() {
+        let html = "<html><head></head><body><div id=log role=log><p>a</p></div>\
+            <div id=alert role=alert aria-live=off>x</div><output>1</output>\
+            <div aria-live=assertive aria-atomic=true>Total: <b>1</b><span hidden>h</span></div>\
+            </body></html>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let log = AnnouncementLog::default();
+        let mut tracker = LiveRegionTracker::default();
+        let get_text_node = |index: usize| {
+            DomNode::get_descendants(&document)
+                .filter(|n| matches!(n.borrow().node_type, NodeType::Text(_)))
+                .nth(index)
+                .unwrap()
+        };
+        let text = |s: &str| Rc::new(RefCell::new(DomNode::new(NodeType::Text(s.to_string()))));
+
+        // The initial content is not announced.
+        tracker.update(&document, &log);
+        assert!(log.take_announcements().is_empty());
+
+        let region = DomNode::get_descendants(&document).nth(3).unwrap();
+        append_child(&region, &text("b  c")).unwrap();
+        get_text_node(2).borrow_mut().set_inside_text("x2");
+        get_text_node(3).borrow_mut().set_inside_text("2");
+        get_text_node(5).borrow_mut().set_inside_text("2");
+        get_text_node(6).borrow_mut().set_inside_text("hidden");
+        tracker.update(&document, &log);
+        assert_eq!(
+            log.take_announcements()
+                .iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>(),
+            ["[polite] b c", "[polite] 2", "[assertive] Total: 2"]
+        );
+
+        // Removals are announced only if they are relevant, and busy regions wait until they are done.
+        let p = get_text_node(0)
+            .borrow()
+            .parent
+            .as_ref()
+            .unwrap()
+            .upgrade()
+            .unwrap();
+        remove_child(&region, &p).unwrap();
+        set_attribute(&region, "aria-relevant", "removals").unwrap();
+        let output = get_text_node(2)
+            .borrow()
+            .parent
+            .as_ref()
+            .unwrap()
+            .upgrade()
+            .unwrap();
+        set_attribute(&output, "aria-busy", "true").unwrap();
+        get_text_node(2).borrow_mut().set_inside_text("3");
+        tracker.update(&document, &log);
+        assert_eq!(
+            log.take_announcements(),
+            [Announcement {
+                politeness: Politeness::Polite,
+                text: "a".to_string()
+            }]
+        );
+        set_attribute(&output, "aria-busy", "false").unwrap();
+        tracker.update(&document, &log);
+        assert_eq!(log.take_announcements()[0].text, "3");
+    }
+}
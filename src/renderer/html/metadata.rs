@@ -0,0 +1,654 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::renderer::html::dom::{DomNode, Element, NodeType};
+
+/// The metadata of a page declared by the `link` and `meta` elements in its `head` element.
+/// The URLs are the values of the `href` attributes, which are not resolved against the document URL.
+/// https://html.spec.whatwg.org/multipage/links.html#linkTypes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageMetadata {
+    /// The title of the document given by its first `title` element.
+    /// https://html.spec.whatwg.org/multipage/dom.html#document.title
+    pub title: Option<String>,
+    /// The icons of the page in tree order, which includes the ones with the legacy `shortcut icon` keywords.
+    /// https://html.spec.whatwg.org/multipage/links.html#rel-icon
+    pub icons: Vec<IconLink>,
+    /// The icons used on home screens of touch devices, which are not in the HTML spec but widely used.
+    pub apple_touch_icons: Vec<IconLink>,
+    /// https://w3c.github.io/manifest/#linking
+    pub manifest: Option<String>,
+    /// https://www.rfc-editor.org/rfc/rfc6596
+    pub canonical: Option<String>,
+    /// https://html.spec.whatwg.org/multipage/links.html#rel-alternate
+    pub alternates: Vec<AlternateLink>,
+    /// The content of the first `meta` element whose name is `description`.
+    pub description: Option<String>,
+    /// The Open Graph properties, e.g. `og:title`, and their values in tree order. A property may appear
+    /// multiple times, e.g. for multiple images.
+    /// https://ogp.me/
+    pub open_graph: Vec<(String, String)>,
+    /// The Twitter card properties, e.g. `twitter:card`, and their values in tree order.
+    pub twitter_card: Vec<(String, String)>,
+    /// The URL of the first `base` element with an `href` attribute, against which the relative URLs in
+    /// the document are resolved.
+    /// https://html.spec.whatwg.org/multipage/semantics.html#frozen-base-url
+    pub base: Option<String>,
+    /// The external style sheets in tree order, excluding the alternative ones. They are loaded by the parser.
+    /// https://html.spec.whatwg.org/multipage/links.html#link-type-stylesheet
+    pub stylesheets: Vec<StylesheetLink>,
+    /// The hints to fetch resources or to connect to origins ahead of their use, in tree order. The resources
+    /// are fetched by the parser with the external style sheets.
+    /// https://html.spec.whatwg.org/multipage/links.html#link-type-preconnect
+    pub resource_hints: Vec<ResourceHint>,
+    /// The navigation scheduled by the first valid `meta` element with `http-equiv="refresh"`.
+    /// https://html.spec.whatwg.org/multipage/semantics.html#attr-meta-http-equiv-refresh
+    pub refresh: Option<Refresh>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IconLink {
+    pub href: String,
+    /// https://html.spec.whatwg.org/multipage/semantics.html#attr-link-sizes
+    pub sizes: Vec<IconSize>,
+    pub mime_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IconSize {
+    /// The icon is scalable, e.g. an SVG image.
+    Any,
+    Size {
+        width: u32,
+        height: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlternateLink {
+    pub href: String,
+    pub hreflang: Option<String>,
+    pub mime_type: Option<String>,
+    pub media: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StylesheetLink {
+    pub href: String,
+    pub media: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceHint {
+    pub kind: ResourceHintKind,
+    pub href: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourceHintKind {
+    /// Resolves the host name of the origin.
+    /// https://html.spec.whatwg.org/multipage/links.html#link-type-dns-prefetch
+    DnsPrefetch,
+    /// Opens a connection to the origin.
+    Preconnect,
+    /// Fetches a resource which is likely to be needed by a future navigation.
+    /// https://html.spec.whatwg.org/multipage/links.html#link-type-prefetch
+    Prefetch,
+    /// Fetches a resource which the current page needs, whose destination is the value of the `as`
+    /// attribute, e.g. `style`. Preloads without a valid destination are ignored.
+    /// https://html.spec.whatwg.org/multipage/links.html#link-type-preload
+    Preload(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Refresh {
+    /// The number of seconds to wait before the navigation.
+    pub delay: u32,
+    /// The URL to navigate to. If `None`, the page is reloaded.
+    pub url: Option<String>,
+}
+
+impl fmt::Display for IconSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::Size { width, height } => write!(f, "{}x{}", width, height),
+        }
+    }
+}
+
+impl fmt::Display for IconLink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.href)?;
+        if !self.sizes.is_empty() {
+            let sizes = self.sizes.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            write!(f, " sizes=\"{}\"", sizes.join(" "))?;
+        }
+        if let Some(mime_type) = &self.mime_type {
+            write!(f, " type=\"{}\"", mime_type)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AlternateLink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.href)?;
+        for (name, value) in [
+            ("hreflang", &self.hreflang),
+            ("type", &self.mime_type),
+            ("media", &self.media),
+            ("title", &self.title),
+        ] {
+            if let Some(value) = value {
+                write!(f, " {}=\"{}\"", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for StylesheetLink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.href)?;
+        for (name, value) in [("media", &self.media), ("title", &self.title)] {
+            if let Some(value) = value {
+                write!(f, " {}=\"{}\"", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ResourceHint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ResourceHintKind::DnsPrefetch => write!(f, "dns-prefetch: {}", self.href),
+            ResourceHintKind::Preconnect => write!(f, "preconnect: {}", self.href),
+            ResourceHintKind::Prefetch => write!(f, "prefetch: {}", self.href),
+            ResourceHintKind::Preload(destination) => {
+                write!(f, "preload: {} as=\"{}\"", self.href, destination)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Refresh {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.delay)?;
+        if let Some(url) = &self.url {
+            write!(f, " {}", url)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints one link or property per line, prefixed with its link type or name.
+impl fmt::Display for PageMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(title) = &self.title {
+            writeln!(f, "title: {}", title)?;
+        }
+        for icon in &self.icons {
+            writeln!(f, "icon: {}", icon)?;
+        }
+        for icon in &self.apple_touch_icons {
+            writeln!(f, "apple-touch-icon: {}", icon)?;
+        }
+        if let Some(manifest) = &self.manifest {
+            writeln!(f, "manifest: {}", manifest)?;
+        }
+        if let Some(canonical) = &self.canonical {
+            writeln!(f, "canonical: {}", canonical)?;
+        }
+        for alternate in &self.alternates {
+            writeln!(f, "alternate: {}", alternate)?;
+        }
+        if let Some(description) = &self.description {
+            writeln!(f, "description: {}", description)?;
+        }
+        for (property, content) in self.open_graph.iter().chain(&self.twitter_card) {
+            writeln!(f, "{}: {}", property, content)?;
+        }
+        if let Some(base) = &self.base {
+            writeln!(f, "base: {}", base)?;
+        }
+        for stylesheet in &self.stylesheets {
+            writeln!(f, "stylesheet: {}", stylesheet)?;
+        }
+        for hint in &self.resource_hints {
+            writeln!(f, "{}", hint)?;
+        }
+        if let Some(refresh) = &self.refresh {
+            writeln!(f, "refresh: {}", refresh)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the title of the document, which is the text of the first `title` element with the ASCII
+/// whitespace stripped and collapsed, or `None` if there is no `title` element.
+/// https://html.spec.whatwg.org/multipage/dom.html#document.title
+pub fn get_document_title(document: &Rc<RefCell<DomNode>>) -> Option<String> {
+    let title_element = get_title_element(document)?;
+    // The child text content excludes the texts of the descendants which are not children.
+    let text = title_element
+        .borrow()
+        .children
+        .iter()
+        .filter_map(|child| match &child.borrow().node_type {
+            NodeType::Text(text) => Some(text.clone()),
+            _ => None,
+        })
+        .collect::<String>();
+    Some(text.split_ascii_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Returns the first `title` element of the document in tree order.
+/// https://html.spec.whatwg.org/multipage/dom.html#the-title-element-2
+pub fn get_title_element(document: &Rc<RefCell<DomNode>>) -> Option<Rc<RefCell<DomNode>>> {
+    DomNode::get_descendants(document).find(|node| {
+        matches!(&node.borrow().node_type, NodeType::Element(elm) if elm.tag_name == "title")
+    })
+}
+
+impl PageMetadata {
+    /// Collects the metadata from the `link` and `meta` elements in the `head` element of the document, and
+    /// its title.
+    pub fn from_document(document: &Rc<RefCell<DomNode>>) -> Self {
+        let mut metadata = Self {
+            title: get_document_title(document),
+            ..Default::default()
+        };
+        let is_head = |node: &Rc<RefCell<DomNode>>| matches!(&node.borrow().node_type, NodeType::Element(elm) if elm.tag_name == "head");
+        let Some(head) = DomNode::get_descendants(document).find(is_head) else {
+            return metadata;
+        };
+
+        for node in DomNode::get_descendants(&head) {
+            let node = node.borrow();
+            match &node.node_type {
+                NodeType::Element(elm) if elm.tag_name == "link" => metadata.add_link(elm),
+                NodeType::Element(elm) if elm.tag_name == "meta" => metadata.add_meta(elm),
+                NodeType::Element(elm) if elm.tag_name == "base" && metadata.base.is_none() => {
+                    metadata.base = elm.get_attribute("href").map(|href| href.to_string());
+                }
+                _ => {}
+            }
+        }
+        metadata
+    }
+
+    /// Returns the summary of the page shown when it is bookmarked or shared. The Open Graph properties are
+    /// preferred, and the Twitter card properties and the standard metadata are used as fallbacks.
+    pub fn get_share_preview(&self) -> SharePreview {
+        let find = |properties: &[(String, String)], name: &str| {
+            properties
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, content)| content.clone())
+        };
+        SharePreview {
+            title: find(&self.open_graph, "og:title")
+                .or_else(|| find(&self.twitter_card, "twitter:title")),
+            description: find(&self.open_graph, "og:description")
+                .or_else(|| find(&self.twitter_card, "twitter:description"))
+                .or_else(|| self.description.clone()),
+            image: find(&self.open_graph, "og:image")
+                .or_else(|| find(&self.twitter_card, "twitter:image")),
+            url: find(&self.open_graph, "og:url").or_else(|| self.canonical.clone()),
+        }
+    }
+
+    fn add_link(&mut self, elm: &Element) {
+        // A link element without a non-empty href attribute doesn't define a link.
+        let Some(href) = elm.get_attribute("href").filter(|h| !h.is_empty()) else {
+            return;
+        };
+        let get_attribute = |name| elm.get_attribute(name).map(|v| v.to_string());
+
+        // The keywords are ASCII case-insensitive, and an element may have several of them.
+        let rel = elm
+            .get_attribute("rel")
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let keywords = rel.split_ascii_whitespace().collect::<Vec<_>>();
+        let icon = || IconLink {
+            href: href.to_string(),
+            sizes: parse_sizes(elm.get_attribute("sizes").unwrap_or_default()),
+            mime_type: get_attribute("type"),
+        };
+        if keywords.contains(&"icon") {
+            self.icons.push(icon());
+        }
+        if keywords
+            .iter()
+            .any(|k| *k == "apple-touch-icon" || *k == "apple-touch-icon-precomposed")
+        {
+            self.apple_touch_icons.push(icon());
+        }
+        // Only the first manifest and canonical links are used.
+        if keywords.contains(&"manifest") && self.manifest.is_none() {
+            self.manifest = Some(href.to_string());
+        }
+        if keywords.contains(&"canonical") && self.canonical.is_none() {
+            self.canonical = Some(href.to_string());
+        }
+        // "alternate stylesheet" is an alternative style sheet rather than an alternate version of the page.
+        if keywords.contains(&"alternate") && !keywords.contains(&"stylesheet") {
+            self.alternates.push(AlternateLink {
+                href: href.to_string(),
+                hreflang: get_attribute("hreflang"),
+                mime_type: get_attribute("type"),
+                media: get_attribute("media"),
+                title: get_attribute("title"),
+            });
+        }
+        for keyword in &keywords {
+            let kind = match *keyword {
+                "dns-prefetch" => ResourceHintKind::DnsPrefetch,
+                "preconnect" => ResourceHintKind::Preconnect,
+                "prefetch" => ResourceHintKind::Prefetch,
+                "preload" => {
+                    // https://fetch.spec.whatwg.org/#concept-request-destination
+                    let destination = elm.get_attribute("as").unwrap_or_default();
+                    let destination = destination.to_ascii_lowercase();
+                    if !matches!(
+                        destination.as_str(),
+                        "audio"
+                            | "document"
+                            | "embed"
+                            | "fetch"
+                            | "font"
+                            | "image"
+                            | "object"
+                            | "script"
+                            | "style"
+                            | "track"
+                            | "video"
+                            | "worker"
+                    ) {
+                        continue;
+                    }
+                    ResourceHintKind::Preload(destination)
+                }
+                _ => continue,
+            };
+            self.resource_hints.push(ResourceHint {
+                kind,
+                href: href.to_string(),
+            });
+        }
+        if keywords.contains(&"stylesheet") && !keywords.contains(&"alternate") {
+            self.stylesheets.push(StylesheetLink {
+                href: href.to_string(),
+                media: get_attribute("media"),
+                title: get_attribute("title"),
+            });
+        }
+    }
+
+    fn add_meta(&mut self, elm: &Element) {
+        let Some(content) = elm.get_attribute("content") else {
+            return;
+        };
+        if elm
+            .get_attribute("http-equiv")
+            .is_some_and(|v| v.eq_ignore_ascii_case("refresh"))
+            && self.refresh.is_none()
+        {
+            self.refresh = parse_refresh(content);
+        }
+        // https://html.spec.whatwg.org/multipage/semantics.html#meta-description
+        if let Some(name) = elm.get_attribute("name") {
+            let name = name.to_ascii_lowercase();
+            if name == "description" && self.description.is_none() {
+                self.description = Some(content.to_string());
+            } else if name.starts_with("twitter:") {
+                self.twitter_card.push((name, content.to_string()));
+            }
+        }
+        // Open Graph uses the `property` attribute from RDFa, although some pages use `name` instead.
+        // https://ogp.me/#metadata
+        let property = elm
+            .get_attribute("property")
+            .or(elm.get_attribute("name"))
+            .map(|p| p.to_ascii_lowercase());
+        if let Some(property) = property.filter(|p| p.starts_with("og:")) {
+            self.open_graph.push((property, content.to_string()));
+        }
+    }
+}
+
+/// https://ogp.me/#metadata
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SharePreview {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// The URL of the image representing the page.
+    pub image: Option<String>,
+    pub url: Option<String>,
+}
+
+/// Parses the content of a `meta` element with `http-equiv="refresh"`, e.g. `5; url=/next`.
+/// The fractional part of the delay is ignored.
+/// https://html.spec.whatwg.org/multipage/semantics.html#shared-declarative-refresh-steps
+fn parse_refresh(content: &str) -> Option<Refresh> {
+    let input = content.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let digits_len = input.bytes().take_while(u8::is_ascii_digit).count();
+    let (digits, rest) = input.split_at(digits_len);
+    if digits.is_empty() && !rest.starts_with('.') {
+        return None;
+    }
+    let delay = if digits.is_empty() {
+        0
+    } else {
+        digits.parse().unwrap_or(u32::MAX)
+    };
+    let rest = rest.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    if rest.is_empty() {
+        return Some(Refresh { delay, url: None });
+    }
+    if !rest.starts_with([';', ',']) && !rest.starts_with(|c: char| c.is_ascii_whitespace()) {
+        return None;
+    }
+    let rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    let rest = rest.strip_prefix([';', ',']).unwrap_or(rest);
+    let mut url = rest.trim_start_matches(|c: char| c.is_ascii_whitespace());
+    // The URL may be preceded by `url=`, and may be quoted.
+    if url.len() >= 3 && url[..3].eq_ignore_ascii_case("url") {
+        let after = url[3..].trim_start_matches(|c: char| c.is_ascii_whitespace());
+        if let Some(after) = after.strip_prefix('=') {
+            url = after.trim_start_matches(|c: char| c.is_ascii_whitespace());
+        }
+    }
+    let url = match url.chars().next() {
+        Some(quote @ ('"' | '\'')) => url[1..].split(quote).next().unwrap_or_default(),
+        _ => url,
+    };
+    let url = url.trim_end_matches(|c: char| c.is_ascii_whitespace());
+    Some(Refresh {
+        delay,
+        url: (!url.is_empty()).then(|| url.to_string()),
+    })
+}
+
+/// Parses the value of the `sizes` attribute. Invalid sizes are ignored.
+/// https://html.spec.whatwg.org/multipage/semantics.html#attr-link-sizes
+fn parse_sizes(value: &str) -> Vec<IconSize> {
+    let parse_dimension = |s: &str| {
+        // Each dimension must be a valid non-negative integer that doesn't start with a zero.
+        (!s.is_empty() && !s.starts_with('0') && s.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| s.parse().ok())
+            .flatten()
+    };
+    value
+        .split_ascii_whitespace()
+        .filter_map(|size| {
+            if size.eq_ignore_ascii_case("any") {
+                return Some(IconSize::Any);
+            }
+            let (width, height) = size.split_once(['x', 'X'])?;
+            Some(IconSize::Size {
+                width: parse_dimension(width)?,
+                height: parse_dimension(height)?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    #[test]
+    fn collect_page_metadata() {
+        let html = r#"<html><head>
+<link rel="Shortcut Icon" href="/favicon.ico">
+<link rel=icon href="/icon.svg" sizes="any 32X32 016x16 4x" type="image/svg+xml">
+<link rel="apple-touch-icon" href="/apple.png" sizes="180x180">
+<link rel=icon>
+<link rel=manifest href="/site.webmanifest"><link rel=manifest href="/other.webmanifest">
+<link rel=canonical href="https://example.com/">
+<link rel=alternate href="/feed.xml" type="application/rss+xml" title="Feed">
+<link rel=alternate hreflang=ja href="/ja/">
+<link rel="alternate stylesheet" href="/dark.css">
+<link rel=Stylesheet href="/print.css" media=print><link rel=stylesheet>
+<base href="https://example.com/a/"><base href="/ignored/">
+<link rel="preconnect dns-prefetch" href="https://cdn.example.com"><link rel=prefetch href="/next.html">
+<link rel=preload href="/font.woff2" as=FONT><link rel=preload href="/x" as=unknown>
+</head><body><link rel=canonical href="/ignored"></body></html>"#;
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let metadata = PageMetadata::from_document(&document);
+
+        assert_eq!(
+            metadata.icons,
+            vec![
+                IconLink {
+                    href: "/favicon.ico".to_string(),
+                    sizes: Vec::new(),
+                    mime_type: None,
+                },
+                IconLink {
+                    href: "/icon.svg".to_string(),
+                    sizes: vec![
+                        IconSize::Any,
+                        IconSize::Size {
+                            width: 32,
+                            height: 32
+                        }
+                    ],
+                    mime_type: Some("image/svg+xml".to_string()),
+                },
+            ]
+        );
+        assert_eq!(
+            metadata.to_string(),
+            [
+                "icon: /favicon.ico",
+                "icon: /icon.svg sizes=\"any 32x32\" type=\"image/svg+xml\"",
+                "apple-touch-icon: /apple.png sizes=\"180x180\"",
+                "manifest: /site.webmanifest",
+                "canonical: https://example.com/",
+                "alternate: /feed.xml type=\"application/rss+xml\" title=\"Feed\"",
+                "alternate: /ja/ hreflang=\"ja\"",
+                "base: https://example.com/a/",
+                "stylesheet: /print.css media=\"print\"",
+                "preconnect: https://cdn.example.com",
+                "dns-prefetch: https://cdn.example.com",
+                "prefetch: /next.html",
+                "preload: /font.woff2 as=\"font\"",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+    #[test]
+    fn collect_meta_properties() {
+        let html = r#"<html><head>
+<title>
+  A page
+  title </title><title>Ignored</title>
+<meta name=Description content="A page."><meta name=description content="Ignored.">
+<meta property="og:title" content="Title"><meta property="og:image" content="/a.png"><meta property="og:image" content="/b.png">
+<meta name="twitter:card" content="summary"><meta name="twitter:title" content="Twitter title">
+<meta name="og:url" content="https://example.com/page">
+<meta property="og:description">
+</head><body></body></html>"#;
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let metadata = PageMetadata::from_document(&document);
+
+        assert_eq!(
+            metadata.to_string(),
+            [
+                "title: A page title",
+                "description: A page.",
+                "og:title: Title",
+                "og:image: /a.png",
+                "og:image: /b.png",
+                "og:url: https://example.com/page",
+                "twitter:card: summary",
+                "twitter:title: Twitter title",
+                "",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            metadata.get_share_preview(),
+            SharePreview {
+                title: Some("Title".to_string()),
+                description: Some("A page.".to_string()),
+                image: Some("/a.png".to_string()),
+                url: Some("https://example.com/page".to_string()),
+            }
+        );
+
+        // The canonical URL is used if there is no Open Graph URL.
+        let metadata = PageMetadata {
+            canonical: Some("/canonical".to_string()),
+            twitter_card: vec![("twitter:image".to_string(), "/t.png".to_string())],
+            ..Default::default()
+        };
+        assert_eq!(
+            metadata.get_share_preview(),
+            SharePreview {
+                image: Some("/t.png".to_string()),
+                url: Some("/canonical".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+    #[test]
+    fn parse_meta_refresh() {
+        let refresh = |delay, url: Option<&str>| {
+            Some(Refresh {
+                delay,
+                url: url.map(|u| u.to_string()),
+            })
+        };
+        assert_eq!(parse_refresh("5"), refresh(5, None));
+        assert_eq!(
+            parse_refresh(" 0; URL = '/next page' x"),
+            refresh(0, Some("/next page"))
+        );
+        assert_eq!(
+            parse_refresh("1.5,http://example.com/ "),
+            refresh(1, Some("http://example.com/"))
+        );
+        assert_eq!(parse_refresh(".5 /a"), refresh(0, Some("/a")));
+        assert_eq!(parse_refresh("3; urlx"), refresh(3, Some("urlx")));
+        assert_eq!(parse_refresh("soon"), None);
+        assert_eq!(parse_refresh("5x"), None);
+
+        let html = r#"<html><head><meta http-equiv=Refresh content="x"><meta http-equiv="refresh" content="2;url=/b"><meta http-equiv="refresh" content="0"></head><body></body></html>"#;
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let metadata = PageMetadata::from_document(&document);
+        assert_eq!(metadata.refresh, refresh(2, Some("/b")));
+        assert_eq!(metadata.to_string(), "refresh: 2 /b\n");
+    }
+}
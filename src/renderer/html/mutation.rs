@@ -0,0 +1,536 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use anyhow::{bail, ensure, Result};
+
+use crate::renderer::html::dom::{get_id, DomNode, Element, ElementState, IdIndex, NodeType};
+use crate::renderer::html::metadata::get_title_element;
+
+/// Appends the node to the children of the parent, and returns it. If the node is a document fragment,
+/// its children are appended instead.
+/// https://dom.spec.whatwg.org/#dom-node-appendchild
+pub fn append_child(
+    parent_ref: &Rc<RefCell<DomNode>>,
+    node_ref: &Rc<RefCell<DomNode>>,
+) -> Result<Rc<RefCell<DomNode>>> {
+    insert_before(parent_ref, node_ref, None)
+}
+
+/// Inserts the node into the children of the parent before `child`, or appends it if `child` is `None`,
+/// and returns it. The node is removed from its previous parent if any.
+/// https://dom.spec.whatwg.org/#dom-node-insertbefore
+pub fn insert_before(
+    parent_ref: &Rc<RefCell<DomNode>>,
+    node_ref: &Rc<RefCell<DomNode>>,
+    child: Option<&Rc<RefCell<DomNode>>>,
+) -> Result<Rc<RefCell<DomNode>>> {
+    ensure_pre_insertion_validity(parent_ref, node_ref, child)?;
+
+    // Inserting a node before itself is inserting it before its next sibling.
+    let next_sibling = node_ref.borrow().next_sibling.clone();
+    let child = match child {
+        Some(child) if Rc::ptr_eq(child, node_ref) => next_sibling.as_ref(),
+        child => child,
+    };
+
+    let nodes = if matches!(node_ref.borrow().node_type, NodeType::DocumentFragment) {
+        node_ref.borrow().children.clone()
+    } else {
+        vec![Rc::clone(node_ref)]
+    };
+    if let Some(old_parent) = DomNode::get_parent(node_ref) {
+        invalidate_siblings(node_ref);
+        invalidate_relative_anchors(node_ref);
+        mark_layout_dirty(&old_parent);
+        unindex_ids(node_ref);
+    }
+    for node in nodes {
+        DomNode::insert_child_node(parent_ref, Rc::clone(&node), child);
+        index_ids(&node);
+        // The inserted node and its siblings can match different selectors, e.g. `:first-child`,
+        // `:last-child` and `+`.
+        mark_style_dirty(&node, true);
+        invalidate_siblings(&node);
+        invalidate_relative_anchors(&node);
+    }
+    mark_layout_dirty(parent_ref);
+    Ok(Rc::clone(node_ref))
+}
+
+/// Removes the child from the parent, and returns it.
+/// https://dom.spec.whatwg.org/#dom-node-removechild
+pub fn remove_child(
+    parent_ref: &Rc<RefCell<DomNode>>,
+    child_ref: &Rc<RefCell<DomNode>>,
+) -> Result<Rc<RefCell<DomNode>>> {
+    ensure!(
+        DomNode::get_parent(child_ref).is_some_and(|parent| Rc::ptr_eq(&parent, parent_ref)),
+        "NotFoundError: The node to be removed is not a child of this node."
+    );
+    invalidate_siblings(child_ref);
+    invalidate_relative_anchors(child_ref);
+    unindex_ids(child_ref);
+    DomNode::detach(child_ref);
+    mark_layout_dirty(parent_ref);
+    Ok(Rc::clone(child_ref))
+}
+
+/// Sets the title of the document by replacing the children of its first `title` element with the text,
+/// where the `title` element is appended to the `head` element if there is none. Nothing is done if there
+/// is neither of them.
+/// https://html.spec.whatwg.org/multipage/dom.html#document.title
+pub fn set_document_title(document_ref: &Rc<RefCell<DomNode>>, title: &str) -> Result<()> {
+    let title_element = match get_title_element(document_ref) {
+        Some(title_element) => title_element,
+        None => {
+            let Some(head) = DomNode::get_descendants(document_ref).find(|node| {
+                matches!(&node.borrow().node_type, NodeType::Element(elm) if elm.tag_name == "head")
+            }) else {
+                return Ok(());
+            };
+            let title_element = Rc::new(RefCell::new(DomNode::new(NodeType::Element(Element {
+                tag_name: "title".to_string(),
+                attributes: Vec::new(),
+            }))));
+            append_child(&head, &title_element)?
+        }
+    };
+
+    // https://dom.spec.whatwg.org/#string-replace-all
+    let children = title_element.borrow().children.clone();
+    for child in &children {
+        remove_child(&title_element, child)?;
+    }
+    if !title.is_empty() {
+        let text = Rc::new(RefCell::new(DomNode::new(NodeType::Text(
+            title.to_string(),
+        ))));
+        append_child(&title_element, &text)?;
+    }
+    Ok(())
+}
+
+/// Sets the value of the attribute of the element, adding the attribute if it doesn't exist.
+/// The name is lowercased since all elements are in the HTML namespace.
+/// https://dom.spec.whatwg.org/#dom-element-setattribute
+pub fn set_attribute(node_ref: &Rc<RefCell<DomNode>>, name: &str, value: &str) -> Result<()> {
+    ensure!(
+        !name.is_empty()
+            && !name
+                .chars()
+                .any(|c| c.is_ascii_whitespace() || matches!(c, '\0' | '/' | '>' | '=')),
+        "InvalidCharacterError: \"{}\" is not a valid attribute name.",
+        name
+    );
+    let name = name.to_ascii_lowercase();
+    let old_id = get_id(node_ref);
+    {
+        let mut node = node_ref.borrow_mut();
+        let NodeType::Element(elm) = &mut node.node_type else {
+            bail!("Only elements can have attributes.");
+        };
+        match elm.attributes.iter_mut().find(|(k, _)| *k == name) {
+            Some((_, v)) if v == value => return Ok(()),
+            Some((_, v)) => *v = value.to_string(),
+            None => elm.attributes.push((name.clone(), value.to_string())),
+        }
+    }
+    if name == "id" {
+        update_id(node_ref, old_id);
+    }
+    invalidate_attribute_change(node_ref);
+    Ok(())
+}
+
+/// Removes the attribute of the element, and returns whether it existed.
+/// https://dom.spec.whatwg.org/#dom-element-removeattribute
+pub fn remove_attribute(node_ref: &Rc<RefCell<DomNode>>, name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    let old_id = get_id(node_ref);
+    {
+        let mut node = node_ref.borrow_mut();
+        let NodeType::Element(elm) = &mut node.node_type else {
+            return false;
+        };
+        let len = elm.attributes.len();
+        elm.attributes.retain(|(k, _)| *k != name);
+        if elm.attributes.len() == len {
+            return false;
+        }
+    }
+    if name == "id" {
+        update_id(node_ref, old_id);
+    }
+    invalidate_attribute_change(node_ref);
+    true
+}
+
+/// Replaces `count` characters of the data of the text node from `offset` with the data. The offsets are in
+/// characters instead of the UTF-16 code units.
+/// https://dom.spec.whatwg.org/#concept-cd-replace
+pub fn replace_data(
+    node_ref: &Rc<RefCell<DomNode>>,
+    offset: usize,
+    count: usize,
+    data: &str,
+) -> Result<()> {
+    let Some(text) = node_ref.borrow().get_inside_text() else {
+        bail!("Only text nodes have data.");
+    };
+    let length = text.chars().count();
+    ensure!(
+        offset <= length,
+        "IndexSizeError: The offset {} is beyond the length {}.",
+        offset,
+        length
+    );
+    let text = text
+        .chars()
+        .take(offset)
+        .chain(data.chars())
+        .chain(text.chars().skip(offset + count))
+        .collect::<String>();
+    node_ref.borrow_mut().set_inside_text(&text);
+    mark_layout_dirty(node_ref);
+    Ok(())
+}
+
+/// Changes the states of the element, and restyles the elements which the change may affect.
+pub fn set_element_state(node_ref: &Rc<RefCell<DomNode>>, state: ElementState) {
+    if node_ref.borrow().state == state {
+        return;
+    }
+    node_ref.borrow_mut().state = state;
+    invalidate_attribute_change(node_ref);
+}
+
+/// Invalidates the style of the node alone, e.g. when its state changes and the nodes whose styles depend on
+/// it are invalidated with an invalidation map, which is narrower than the invalidation of the attribute
+/// changes.
+pub fn invalidate_style(node_ref: &Rc<RefCell<DomNode>>) {
+    mark_style_dirty(node_ref, false);
+    mark_layout_dirty(node_ref);
+}
+
+/// Clears the style flags of the nodes in the tree, which is called when the styles are recomputed.
+pub fn clear_style_dirty_flags(root_ref: &Rc<RefCell<DomNode>>) {
+    for node in std::iter::once(Rc::clone(root_ref)).chain(DomNode::get_descendants(root_ref)) {
+        let dirty = &mut node.borrow_mut().dirty;
+        dirty.style = false;
+        dirty.subtree_style = false;
+        dirty.descendant_style = false;
+    }
+}
+
+/// Clears the layout flags of the nodes in the tree, which is called when the boxes are laid out again.
+pub fn clear_layout_dirty_flags(root_ref: &Rc<RefCell<DomNode>>) {
+    for node in std::iter::once(Rc::clone(root_ref)).chain(DomNode::get_descendants(root_ref)) {
+        node.borrow_mut().dirty.layout = false;
+    }
+}
+
+/// https://dom.spec.whatwg.org/#concept-node-ensure-pre-insertion-validity
+fn ensure_pre_insertion_validity(
+    parent_ref: &Rc<RefCell<DomNode>>,
+    node_ref: &Rc<RefCell<DomNode>>,
+    child: Option<&Rc<RefCell<DomNode>>>,
+) -> Result<()> {
+    ensure!(
+        matches!(
+            parent_ref.borrow().node_type,
+            NodeType::Document(_)
+                | NodeType::DocumentFragment
+                | NodeType::ShadowRoot(_)
+                | NodeType::Element(_)
+        ),
+        "HierarchyRequestError: The parent can't have children."
+    );
+    // A node can't be inserted into itself or its descendants.
+    // todo: Check the shadow hosts too, which are not kept by the shadow roots for now.
+    let mut ancestor = Some(Rc::clone(parent_ref));
+    while let Some(node) = ancestor {
+        ensure!(
+            !Rc::ptr_eq(&node, node_ref),
+            "HierarchyRequestError: The node is an ancestor of the parent."
+        );
+        ancestor = DomNode::get_parent(&node);
+    }
+    if let Some(child) = child {
+        ensure!(
+            DomNode::get_parent(child).is_some_and(|parent| Rc::ptr_eq(&parent, parent_ref)),
+            "NotFoundError: The reference node is not a child of the parent."
+        );
+    }
+
+    let parent = parent_ref.borrow();
+    let node = node_ref.borrow();
+    match (&parent.node_type, &node.node_type) {
+        (_, NodeType::Document(_) | NodeType::ShadowRoot(_)) => {
+            bail!("HierarchyRequestError: A document or a shadow root can't be inserted.")
+        }
+        (NodeType::Document(_), NodeType::Text(_)) => {
+            bail!("HierarchyRequestError: A document can't have text.")
+        }
+        (NodeType::Document(_), NodeType::Element(_)) => ensure!(
+            !parent.children.iter().any(|c| {
+                !Rc::ptr_eq(c, node_ref) && matches!(c.borrow().node_type, NodeType::Element(_))
+            }),
+            "HierarchyRequestError: A document can have only one element."
+        ),
+        (NodeType::Document(_), NodeType::DocumentType(_)) => ensure!(
+            !parent.children.iter().any(|c| {
+                !Rc::ptr_eq(c, node_ref)
+                    && matches!(c.borrow().node_type, NodeType::DocumentType(_))
+            }),
+            "HierarchyRequestError: A document can have only one doctype."
+        ),
+        (_, NodeType::DocumentType(_)) => {
+            bail!("HierarchyRequestError: Only a document can have a doctype.")
+        }
+        // The children of a fragment inserted into a document are not validated for simplicity.
+        _ => {}
+    }
+    Ok(())
+}
+
+/// An attribute change can affect the styles of the element, its descendants (e.g. `.a p`), its following
+/// siblings (e.g. `.a + p`), and the anchors of `:has()` (e.g. `:has(.a)`).
+fn invalidate_attribute_change(node_ref: &Rc<RefCell<DomNode>>) {
+    mark_style_dirty(node_ref, true);
+    invalidate_following_siblings(node_ref);
+    invalidate_relative_anchors(node_ref);
+    mark_layout_dirty(node_ref);
+}
+
+fn invalidate_following_siblings(node_ref: &Rc<RefCell<DomNode>>) {
+    let mut sibling = node_ref.borrow().next_sibling.clone();
+    while let Some(node) = sibling {
+        mark_style_dirty(&node, true);
+        sibling = node.borrow().next_sibling.clone();
+    }
+}
+
+/// Invalidates the styles of all the siblings of the node, since the indices of the siblings from both ends
+/// change when the node is inserted or removed.
+fn invalidate_siblings(node_ref: &Rc<RefCell<DomNode>>) {
+    let Some(parent) = DomNode::get_parent(node_ref) else {
+        return;
+    };
+    let siblings = parent.borrow().children.clone();
+    for sibling in siblings.iter().filter(|s| !Rc::ptr_eq(s, node_ref)) {
+        mark_style_dirty(sibling, true);
+    }
+}
+
+/// Invalidates the styles of the ancestors of the node, which `:has()` can match depending on the node,
+/// e.g. `:has(> p)` and `:has(p)`.
+/// todo: Invalidate the preceding siblings of the node and its ancestors for `:has(+ p)` and `:has(~ p)`.
+fn invalidate_relative_anchors(node_ref: &Rc<RefCell<DomNode>>) {
+    let mut ancestor = DomNode::get_parent(node_ref);
+    while let Some(node) = ancestor {
+        mark_style_dirty(&node, false);
+        ancestor = DomNode::get_parent(&node);
+    }
+}
+
+fn mark_style_dirty(node_ref: &Rc<RefCell<DomNode>>, subtree: bool) {
+    {
+        let dirty = &mut node_ref.borrow_mut().dirty;
+        dirty.style = true;
+        dirty.subtree_style |= subtree;
+    }
+    let mut ancestor = DomNode::get_parent(node_ref);
+    while let Some(node) = ancestor {
+        if node.borrow().dirty.descendant_style {
+            break;
+        }
+        node.borrow_mut().dirty.descendant_style = true;
+        ancestor = DomNode::get_parent(&node);
+    }
+}
+
+/// Calls `f` with the ID index of the document which the node is in, if any. The index is taken out of the
+/// document while `f` is called, so that `f` can borrow the nodes in the document.
+fn update_id_index(node_ref: &Rc<RefCell<DomNode>>, f: impl FnOnce(&mut IdIndex)) {
+    let root = DomNode::get_root(node_ref);
+    let Some(mut id_index) = root.borrow_mut().id_index.take() else {
+        return;
+    };
+    f(&mut id_index);
+    root.borrow_mut().id_index = Some(id_index);
+}
+
+/// Adds the elements with IDs in the inclusive descendants of the node to the ID index of its document.
+fn index_ids(node_ref: &Rc<RefCell<DomNode>>) {
+    update_id_index(node_ref, |id_index| {
+        for node in std::iter::once(Rc::clone(node_ref)).chain(DomNode::get_descendants(node_ref)) {
+            if let Some(id) = get_id(&node) {
+                id_index.insert(&id, &node);
+            }
+        }
+    });
+}
+
+/// Removes the elements with IDs in the inclusive descendants of the node from the ID index of its document.
+fn unindex_ids(node_ref: &Rc<RefCell<DomNode>>) {
+    update_id_index(node_ref, |id_index| {
+        for node in std::iter::once(Rc::clone(node_ref)).chain(DomNode::get_descendants(node_ref)) {
+            if let Some(id) = get_id(&node) {
+                id_index.remove(&id, &node);
+            }
+        }
+    });
+}
+
+/// Moves the element in the ID index of its document from its old ID to the current one.
+fn update_id(node_ref: &Rc<RefCell<DomNode>>, old_id: Option<String>) {
+    let new_id = get_id(node_ref);
+    if old_id == new_id {
+        return;
+    }
+    update_id_index(node_ref, |id_index| {
+        if let Some(old_id) = old_id {
+            id_index.remove(&old_id, node_ref);
+        }
+        if let Some(new_id) = new_id {
+            id_index.insert(&new_id, node_ref);
+        }
+    });
+}
+
+fn mark_layout_dirty(node_ref: &Rc<RefCell<DomNode>>) {
+    let mut node = Some(Rc::clone(node_ref));
+    while let Some(n) = node {
+        n.borrow_mut().dirty.layout = true;
+        node = DomNode::get_parent(&n);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::dom::DocumentTree;
+    use crate::renderer::html::metadata::get_document_title;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::serializer::serialize_children;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    #[test]
+    fn mutate_tree() {
+        let html = "<html><head></head><body><p id=a>1</p><p id=b>2</p><p id=c>3</p></body></html>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(document).unwrap();
+        let body = tree.get_elements_by_tag_name("body").remove(0);
+        let [a, b, c] = ["a", "b", "c"].map(|id| tree.get_element_by_id(id).unwrap());
+        let html = tree.get_elements_by_tag_name("html").remove(0);
+        clear_style_dirty_flags(&tree.root);
+
+        // Move the last paragraph before the first one.
+        insert_before(&body, &c, Some(&a)).unwrap();
+        assert_eq!(
+            serialize_children(&body),
+            "<p id=\"c\">3</p><p id=\"a\">1</p><p id=\"b\">2</p>"
+        );
+        assert!(Rc::ptr_eq(
+            a.borrow()
+                .prev_sib
+                .as_ref()
+                .unwrap()
+                .upgrade()
+                .as_ref()
+                .unwrap(),
+            &c
+        ));
+        assert!(c.borrow().prev_sib.is_none());
+        assert!(b.borrow().next_sibling.is_none());
+        assert!(c.borrow().dirty.subtree_style && a.borrow().dirty.style);
+        assert!(html.borrow().dirty.descendant_style && html.borrow().dirty.layout);
+
+        remove_child(&body, &a).unwrap();
+        assert!(a.borrow().parent.is_none());
+        assert!(Rc::ptr_eq(c.borrow().next_sibling.as_ref().unwrap(), &b));
+        append_child(&c, &a).unwrap();
+        assert_eq!(
+            serialize_children(&body),
+            "<p id=\"c\">3<p id=\"a\">1</p></p><p id=\"b\">2</p>"
+        );
+
+        clear_style_dirty_flags(&tree.root);
+        set_attribute(&b, "CLASS", "x").unwrap();
+        assert!(b.borrow().dirty.style && !c.borrow().dirty.style);
+        assert!(remove_attribute(&b, "id"));
+        assert!(!remove_attribute(&b, "id"));
+        assert_eq!(
+            serialize_children(&body).rsplit_once("<p").unwrap().1,
+            " class=\"x\">2</p>"
+        );
+
+        // Invalid mutations are rejected without changing the tree.
+        assert!(append_child(&a, &body).is_err());
+        assert!(append_child(&c, &c).is_err());
+        assert!(insert_before(&body, &b, Some(&a)).is_err());
+        assert!(remove_child(&body, &a).is_err());
+        assert!(append_child(&tree.root, &b).is_err());
+        assert!(set_attribute(&b, "a b", "").is_err());
+        assert!(Rc::ptr_eq(&DomNode::get_parent(&b).unwrap(), &body));
+    }
+
+    #[test]
+    fn index_ids() {
+        let html = "<html><head></head><body><p id=a>1</p><div><p id=b>2</p></div></body></html>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(document).unwrap();
+        let body = tree.get_elements_by_tag_name("body").remove(0);
+        let div = tree.get_elements_by_tag_name("div").remove(0);
+        let [a, b] = ["a", "b"].map(|id| tree.get_element_by_id(id).unwrap());
+
+        // The elements are looked up by their current IDs.
+        set_attribute(&a, "ID", "x").unwrap();
+        assert!(tree.get_element_by_id("a").is_none());
+        assert!(Rc::ptr_eq(&tree.get_element_by_id("x").unwrap(), &a));
+        set_attribute(&b, "id", "x").unwrap();
+        assert!(Rc::ptr_eq(&tree.get_element_by_id("x").unwrap(), &a));
+
+        // The first element with the ID in tree order is found after the elements are moved.
+        append_child(&body, &a).unwrap();
+        assert!(Rc::ptr_eq(&tree.get_element_by_id("x").unwrap(), &b));
+        remove_child(&body, &div).unwrap();
+        assert!(Rc::ptr_eq(&tree.get_element_by_id("x").unwrap(), &a));
+        remove_attribute(&a, "id");
+        assert!(tree.get_element_by_id("x").is_none());
+
+        // The elements in the detached subtrees are indexed when they are inserted again.
+        set_attribute(&b, "id", "y").unwrap();
+        assert!(tree.get_element_by_id("y").is_none());
+        insert_before(&body, &div, Some(&a)).unwrap();
+        assert!(Rc::ptr_eq(&tree.get_element_by_id("y").unwrap(), &b));
+        assert!(DocumentTree::build(Rc::clone(&tree.root))
+            .unwrap()
+            .get_element_by_id("y")
+            .is_some());
+    }
+
+    #[test]
+    fn set_title() {
+        let html = "<html><head></head><body></body></html>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(document).unwrap();
+        let head = tree.get_elements_by_tag_name("head").remove(0);
+        assert_eq!(get_document_title(&tree.root), None);
+
+        // The `title` element is created in the `head` element, and its children are replaced.
+        set_document_title(&tree.root, " A  title ").unwrap();
+        assert_eq!(serialize_children(&head), "<title> A  title </title>");
+        assert_eq!(get_document_title(&tree.root).as_deref(), Some("A title"));
+        set_document_title(&tree.root, "").unwrap();
+        assert_eq!(serialize_children(&head), "<title></title>");
+        assert_eq!(get_document_title(&tree.root).as_deref(), Some(""));
+        assert!(head.borrow().dirty.layout);
+
+        // Nothing is done without the `title` and `head` elements.
+        let html = tree.get_elements_by_tag_name("html").remove(0);
+        remove_child(&tree.root, &html).unwrap();
+        set_document_title(&tree.root, "x").unwrap();
+        assert_eq!(get_document_title(&tree.root), None);
+    }
+}
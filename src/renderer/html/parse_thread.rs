@@ -0,0 +1,345 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{Context, Result};
+use encoding_rs::Encoding;
+
+use crate::net::fetch::{Cancellable, DefaultFetcher};
+use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::css::media::MediaEnvironment;
+use crate::renderer::html::dom::{DomNode, NodeType};
+use crate::renderer::html::parser::HtmlParser;
+use crate::renderer::html::token::HtmlTokenizer;
+use crate::renderer::source::SourceSpan;
+
+/// The number of bytes sent to the parser thread at once.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A copy of a DOM tree which can be sent to another thread. The nodes of the DOM are reference-counted
+/// and can't be shared across threads, so the tree built on the parser thread is copied into this and
+/// rebuilt on the receiving thread.
+#[derive(Debug, Clone)]
+pub struct DetachedNode {
+    pub node_type: NodeType,
+    pub source_span: Option<SourceSpan>,
+    pub children: Vec<Self>,
+    pub shadow_root: Option<Box<Self>>,
+    pub template_contents: Option<Box<Self>>,
+    /// The index of the form owner in the order in which the nodes are visited by `visit()`.
+    form_owner: Option<usize>,
+}
+
+impl DetachedNode {
+    pub fn from_dom(node: &Rc<RefCell<DomNode>>) -> Self {
+        let mut indices = HashMap::new();
+        Self::visit(node, &mut |node| {
+            let index = indices.len();
+            indices.insert(Rc::as_ptr(node), index);
+        });
+        Self::copy(node, &indices)
+    }
+
+    fn copy(
+        node: &Rc<RefCell<DomNode>>,
+        indices: &HashMap<*const RefCell<DomNode>, usize>,
+    ) -> Self {
+        let n = node.borrow();
+        Self {
+            node_type: n.node_type.clone(),
+            source_span: n.source_span,
+            children: n.children.iter().map(|c| Self::copy(c, indices)).collect(),
+            shadow_root: n
+                .shadow_root
+                .as_ref()
+                .map(|r| Box::new(Self::copy(r, indices))),
+            template_contents: n
+                .template_contents
+                .as_ref()
+                .map(|c| Box::new(Self::copy(c, indices))),
+            form_owner: n
+                .form_owner
+                .as_ref()
+                .and_then(|owner| owner.upgrade())
+                .and_then(|owner| indices.get(&Rc::as_ptr(&owner)).copied()),
+        }
+    }
+
+    /// Visits the node and its descendants, including those in the shadow root and the template contents.
+    fn visit(node: &Rc<RefCell<DomNode>>, f: &mut impl FnMut(&Rc<RefCell<DomNode>>)) {
+        f(node);
+        let n = node.borrow();
+        for child in n.shadow_root.iter().chain(&n.template_contents) {
+            Self::visit(child, f);
+        }
+        for child in &n.children {
+            Self::visit(child, f);
+        }
+    }
+
+    /// Rebuilds the DOM tree from the copy.
+    pub fn into_dom(self) -> Rc<RefCell<DomNode>> {
+        let mut nodes = Vec::new();
+        let mut form_owners = Vec::new();
+        let root = self.build(&mut nodes, &mut form_owners);
+        for (node, owner) in nodes.iter().zip(form_owners) {
+            node.borrow_mut().form_owner = owner.map(|i| Rc::downgrade(&nodes[i]));
+        }
+        root
+    }
+
+    fn build(
+        self,
+        nodes: &mut Vec<Rc<RefCell<DomNode>>>,
+        form_owners: &mut Vec<Option<usize>>,
+    ) -> Rc<RefCell<DomNode>> {
+        let node = Rc::new(RefCell::new(DomNode {
+            source_span: self.source_span,
+            ..DomNode::new(self.node_type)
+        }));
+        nodes.push(Rc::clone(&node));
+        form_owners.push(self.form_owner);
+        // The nodes are created in the same order as `visit()`.
+        let shadow_root = self.shadow_root.map(|r| r.build(nodes, form_owners));
+        let template_contents = self.template_contents.map(|c| c.build(nodes, form_owners));
+        {
+            let mut n = node.borrow_mut();
+            n.shadow_root = shadow_root;
+            n.template_contents = template_contents;
+        }
+        for child in self.children {
+            let child = child.build(nodes, form_owners);
+            DomNode::append_child_node(&node, child);
+        }
+        node
+    }
+}
+
+/// A message sent to the parser thread.
+#[derive(Debug)]
+enum ParserInput {
+    Bytes(Vec<u8>),
+    /// The end of the input, after which the parser completes the document.
+    End,
+    /// Stops the parser without completing the document.
+    Cancel,
+}
+
+/// A parser thread which may still be running, kept so that it is canceled and joined on shutdown.
+#[derive(Debug)]
+struct ParserThreadHandle {
+    cancellable: Cancellable,
+    input: Sender<ParserInput>,
+    thread: JoinHandle<()>,
+}
+
+thread_local! {
+    /// The parser threads spawned on this thread. The finished ones are removed when a new one is spawned.
+    static PARSER_THREADS: RefCell<Vec<ParserThreadHandle>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Cancels the parser threads spawned on this thread and the fetches running on them, and waits until
+/// they exit. Their documents are discarded.
+pub fn cancel_parser_threads() {
+    for handle in PARSER_THREADS.take() {
+        handle.cancellable.cancel();
+        // The thread may be waiting for the input.
+        let _ = handle.input.send(ParserInput::Cancel);
+        let _ = handle.thread.join();
+    }
+}
+
+/// The whole document parsed on the parser thread and its style sheets, or the error with which the
+/// parsing failed.
+pub type ParseResult = Result<(DetachedNode, Vec<StyleSheet>)>;
+
+/// Parses an HTML document on a worker thread, so that the thread which renders the page keeps responding
+/// while a large document is parsed. The input is fed in chunks, and the tree is sent back once the whole
+/// document is parsed.
+/// Dropping this cancels the parsing and the fetches of its style sheets, and the result is discarded.
+/// todo: Paint the partial documents while the rest is parsed, for which the nodes inserted since the
+/// last update should be sent instead of copying the whole tree.
+#[derive(Debug)]
+pub struct ParserThread {
+    input: Option<Sender<ParserInput>>,
+    result: Receiver<ParseResult>,
+    cancellable: Cancellable,
+}
+
+impl ParserThread {
+    /// Starts the parser thread. The external style sheets are fetched relative to `document_url` if it is
+    /// not empty, and the input bytes are decoded in the encoding. The style sheets are selected for the media.
+    pub fn spawn(
+        document_url: &str,
+        encoding: &'static Encoding,
+        media: MediaEnvironment,
+    ) -> Result<Self> {
+        let (input_tx, input_rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        let document_url = document_url.to_string();
+        let cancellable = Cancellable::default();
+        let fetcher = DefaultFetcher::new(&document_url, cancellable.clone());
+        let thread = thread::Builder::new()
+            .name("html-parser".to_string())
+            .spawn(move || {
+                let mut tokenizer = HtmlTokenizer::new_streaming();
+                tokenizer.set_encoding(encoding);
+                let mut parser = HtmlParser::new(tokenizer);
+                parser.set_media_environment(media);
+                if !document_url.is_empty() {
+                    parser.set_resource_fetcher(&document_url, Rc::new(fetcher));
+                }
+                // The sender of `cancel_parser_threads()` keeps the channel open, so the input ends only
+                // with `End` or `Cancel`.
+                loop {
+                    match input_rx.recv() {
+                        Ok(ParserInput::Bytes(bytes)) => {
+                            if let Err(e) = parser.feed(&bytes) {
+                                let _ = result_tx.send(Err(e));
+                                return;
+                            }
+                        }
+                        Ok(ParserInput::End) => break,
+                        Ok(ParserInput::Cancel) | Err(_) => return,
+                    }
+                }
+                let result = parser.finish().map(|(document, style_sheets)| {
+                    (DetachedNode::from_dom(&document), style_sheets)
+                });
+                // The receiver may have gone, in which case nobody needs the document.
+                let _ = result_tx.send(result);
+            })
+            .context("Failed to spawn the parser thread")?;
+        PARSER_THREADS.with_borrow_mut(|threads| {
+            threads.retain(|handle| !handle.thread.is_finished());
+            threads.push(ParserThreadHandle {
+                cancellable: cancellable.clone(),
+                input: input_tx.clone(),
+                thread,
+            });
+        });
+        Ok(Self {
+            input: Some(input_tx),
+            result: result_rx,
+            cancellable,
+        })
+    }
+
+    /// Starts parsing the whole document, which is fed in chunks.
+    pub fn spawn_with_input(
+        document_url: &str,
+        html: &str,
+        media: MediaEnvironment,
+    ) -> Result<Self> {
+        let mut thread = Self::spawn(document_url, encoding_rs::UTF_8, media)?;
+        for chunk in html.as_bytes().chunks(CHUNK_SIZE) {
+            thread.feed(chunk);
+        }
+        thread.finish();
+        Ok(thread)
+    }
+
+    /// Sends the bytes to the parser. This does nothing after `finish()`.
+    pub fn feed(&self, bytes: &[u8]) {
+        if let Some(input) = &self.input {
+            let _ = input.send(ParserInput::Bytes(bytes.to_vec()));
+        }
+    }
+
+    /// Closes the input stream, after which the parser completes the document.
+    pub fn finish(&mut self) {
+        if let Some(input) = self.input.take() {
+            let _ = input.send(ParserInput::End);
+        }
+    }
+
+    /// Returns the result if the document is complete, without blocking.
+    pub fn try_recv(&self) -> Option<ParseResult> {
+        self.result.try_recv().ok()
+    }
+
+    /// Waits until the document is complete.
+    #[cfg(test)]
+    pub fn join(self) -> Result<(Rc<RefCell<DomNode>>, Vec<StyleSheet>)> {
+        self.result
+            .recv()
+            .context("The parser thread has exited without completing the document.")?
+            .map(|(document, style_sheets)| (document.into_dom(), style_sheets))
+    }
+}
+
+impl Drop for ParserThread {
+    fn drop(&mut self) {
+        self.cancellable.cancel();
+        if let Some(input) = self.input.take() {
+            let _ = input.send(ParserInput::Cancel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::fetch::{FetchError, ResourceFetcher};
+    use crate::renderer::html::dom::{DocumentMode, DocumentTree};
+
+    #[test]
+    fn parse_on_worker_thread() {
+        let html = format!(
+            "<html><head></head><body><template><p>t</p></template><form id=f></form>{}\
+             <input id=i form=f></body></html>",
+            "<p>Lorem ipsum</p>".repeat(5000)
+        );
+        let mut thread =
+            ParserThread::spawn("", encoding_rs::UTF_8, MediaEnvironment::default()).unwrap();
+        for chunk in html.as_bytes().chunks(CHUNK_SIZE) {
+            thread.feed(chunk);
+        }
+        thread.finish();
+        let (document, _) = thread.join().unwrap();
+
+        let (expected, _) = HtmlParser::new(HtmlTokenizer::new(&html)).parse().unwrap();
+        let tree = DocumentTree::build(document).unwrap();
+        assert_eq!(
+            tree.to_string(),
+            DocumentTree::build(expected).unwrap().to_string()
+        );
+        let template = tree.get_elements_by_tag_name("template").remove(0);
+        assert!(template.borrow().template_contents.is_some());
+        // The form associations are kept.
+        let form = tree.get_element_by_id("f").unwrap();
+        let input = tree.get_element_by_id("i").unwrap();
+        assert!(DomNode::get_form_owner(&input).is_some_and(|owner| Rc::ptr_eq(&owner, &form)));
+        // The mode of the document without the DOCTYPE is kept.
+        assert_eq!(
+            tree.root.borrow().node_type,
+            NodeType::Document(DocumentMode::Quirks)
+        );
+    }
+
+    #[test]
+    fn cancel_on_shutdown() {
+        let waiting = ParserThread::spawn(
+            "file:///nonexistent/index.html",
+            encoding_rs::UTF_8,
+            MediaEnvironment::default(),
+        )
+        .unwrap();
+        waiting.feed(b"<html><head><link rel=stylesheet href=a.css></head><body><p>");
+        // The thread waiting for the rest of the input exits, and its document is discarded.
+        cancel_parser_threads();
+        assert!(waiting.try_recv().is_none());
+        assert!(PARSER_THREADS.with_borrow(Vec::is_empty));
+
+        let cancellable = Cancellable::default();
+        let fetcher = DefaultFetcher::new("file:///nonexistent/index.html", cancellable.clone());
+        cancellable.cancel();
+        let error = fetcher.fetch("file:///nonexistent/a.css").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<FetchError>(),
+            Some(FetchError::Cancelled(_))
+        ));
+    }
+}
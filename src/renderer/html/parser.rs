@@ -1,14 +1,24 @@
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::rc::Rc;
 
 use anyhow::{bail, ensure, Ok, Result};
 use thiserror::Error;
 
+use crate::net::fetch::ResourceFetcher;
+use crate::net::scheduler::{ResourcePriority, ResourceScheduler};
+use crate::net::url::resolve_url;
 use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::css::loader::{load_imports, load_style_sheet};
+use crate::renderer::css::media::{MediaEnvironment, MediaQueryEvaluator};
 use crate::renderer::css::parser::CssParser;
 use crate::renderer::css::token::CssTokenizer;
-use crate::renderer::html::dom::{DocumentTree, DomNode, Element, NodeType};
+use crate::renderer::html::dom::{
+    DocumentMode, DocumentTree, DomNode, Element, NodeType, ShadowRootMode,
+};
+use crate::renderer::html::metadata::{PageMetadata, ResourceHint};
 use crate::renderer::html::token::{HtmlToken, HtmlTokenizer, TokenizationState};
+use crate::renderer::source::SourceSpan;
 
 #[derive(Error, Debug)]
 #[error("{message} (in the HTML tree construction stage)\nCurrent HTML token: {current_token:?}\nCurrent DOM tree:\n{current_tree}")]
@@ -24,12 +34,59 @@ enum InsertionMode {
     BeforeHtml,
     BeforeHead,
     InHead,
+    InHeadNoscript,
     AfterHead,
     InBody,
+    InTable,
+    InTableText,
+    InCaption,
+    InColumnGroup,
+    InTableBody,
+    InRow,
+    InCell,
     InFrameset,
+    AfterFrameset,
+    InTemplate,
     Text,
     AfterBody,
     AfterAfterBody,
+    AfterAfterFrameset,
+}
+
+/// The kinds of scopes in which the stack of open elements is searched for an element. The search stops at
+/// the boundary elements of the scope.
+/// todo: Add the MathML and SVG boundary elements once the elements in the other namespaces are created.
+/// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-the-specific-scope
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ElementScope {
+    Default,
+    ListItem,
+    Button,
+    Table,
+}
+
+impl ElementScope {
+    fn is_boundary(self, tag_name: &str) -> bool {
+        match self {
+            Self::Default => matches!(
+                tag_name,
+                "applet"
+                    | "caption"
+                    | "html"
+                    | "table"
+                    | "td"
+                    | "th"
+                    | "marquee"
+                    | "object"
+                    | "template"
+            ),
+            Self::ListItem => {
+                Self::Default.is_boundary(tag_name) || matches!(tag_name, "ol" | "ul")
+            }
+            Self::Button => Self::Default.is_boundary(tag_name) || tag_name == "button",
+            Self::Table => matches!(tag_name, "html" | "table" | "template"),
+        }
+    }
 }
 
 /// https://html.spec.whatwg.org/multipage/parsing.html#overview-of-the-parsing-model
@@ -42,6 +99,120 @@ pub struct HtmlParser {
     // When the insertion mode is switched to "text" or "in table text", the original insertion mode is also set.
     // This is the insertion mode to which the tree construction stage will return.
     orig_insertion_mode: Option<InsertionMode>,
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#stack-of-template-insertion-modes
+    template_insertion_modes: Vec<InsertionMode>,
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#head-element-pointer
+    head_element: Option<Rc<RefCell<DomNode>>>,
+
+    /// The last opened `form` element which has not been closed by its end tag. Form-associated elements are
+    /// associated with it even if it is no longer on the stack, e.g. when it is misplaced in a table.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#form-element-pointer
+    form_element: Option<Rc<RefCell<DomNode>>>,
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#list-of-active-formatting-elements
+    active_formatting_elements: Vec<ActiveFormattingElement>,
+
+    /// Whether scripting was enabled for the document when the parser was created.
+    /// Scripts are not executed yet, so this is disabled by default.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#scripting-flag
+    scripting: bool,
+
+    /// Whether a `frameset` start tag in the body can still replace the body, which is no longer the case
+    /// once any content which would be rendered has been inserted.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#frameset-ok-flag
+    frameset_ok: bool,
+
+    /// Whether the next token should be ignored if it is a line feed character token.
+    /// This is set at the start of a `textarea` element.
+    ignore_next_lf: bool,
+
+    /// Whether nodes inserted into table elements are moved before the table. This is enabled while
+    /// content misplaced in a table is processed using the rules for the "in body" insertion mode.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#foster-parent
+    foster_parenting: bool,
+
+    /// The character tokens collected in the "in table text" insertion mode, which are inserted at once
+    /// when another token is found.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#concept-pending-table-char-tokens
+    pending_table_chars: String,
+
+    /// The span of the token being processed, and its tag name if it is a start tag.
+    /// Only the nodes created for the token are associated with the span.
+    current_span: SourceSpan,
+    current_start_tag: Option<String>,
+
+    /// The output of the whole parsing (tree construction), which is a Document object.
+    document: Rc<RefCell<DomNode>>,
+
+    /// The document has an associated list of zero or more CSS style sheets.
+    /// This is an ordered list that contains:
+    /// 1. Any CSS style sheets created from HTTP Link headers, in header order
+    /// 2. Any CSS style sheets associated with the DocumentOrShadowRoot, in tree order
+    ///
+    /// https://drafts.csswg.org/cssom/#documentorshadowroot-document-or-shadow-root-css-style-sheets
+    style_sheets: Vec<StyleSheet>,
+    /// The URLs of the external style sheets which are fetched once the document is parsed, with the numbers
+    /// of the style sheets before them in tree order.
+    pending_style_sheets: Vec<(usize, String)>,
+
+    /// Whether the end-of-file token has been processed.
+    end_of_parsing: bool,
+
+    /// The fetcher of the external style sheets, and the URL of the document against which their URLs are
+    /// resolved. External style sheets are not loaded if the fetcher is not set.
+    fetcher: Option<Rc<dyn ResourceFetcher>>,
+    document_url: String,
+    /// The URL of the first `base` element with an `href` attribute, which is resolved against the
+    /// document URL.
+    /// https://html.spec.whatwg.org/multipage/semantics.html#frozen-base-url
+    base_url: Option<String>,
+
+    /// The element in whose context the input is parsed, which is set only when parsing a fragment.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#concept-frag-parse-context
+    context_element: Option<Rc<RefCell<DomNode>>>,
+
+    /// The environment against which the `media` attributes of the style sheets are evaluated.
+    media: MediaEnvironment,
+}
+
+/// A position in the tree at which a new node is inserted.
+/// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-place-for-inserting-a-node
+#[derive(Debug)]
+struct InsertionLocation {
+    parent: Rc<RefCell<DomNode>>,
+    /// The child of `parent` before which the node is inserted. If `None`, the node is appended.
+    before: Option<Rc<RefCell<DomNode>>>,
+}
+
+impl InsertionLocation {
+    fn insert(&self, node: Rc<RefCell<DomNode>>) -> Rc<RefCell<DomNode>> {
+        DomNode::insert_child_node(&self.parent, node, self.before.as_ref())
+    }
+
+    /// Returns the node immediately before the location.
+    fn get_prev_sibling(&self) -> Option<Rc<RefCell<DomNode>>> {
+        match &self.before {
+            Some(before) => before.borrow().prev_sib.as_ref().and_then(|p| p.upgrade()),
+            None => self.parent.borrow().children.last().cloned(),
+        }
+    }
+}
+
+/// An entry in the list of active formatting elements.
+/// https://html.spec.whatwg.org/multipage/parsing.html#list-of-active-formatting-elements
+#[derive(Debug, Clone)]
+enum ActiveFormattingElement {
+    /// Inserted when entering elements such as `template`, to prevent formatting from "leaking" into them.
+    Marker,
+    Element(Rc<RefCell<DomNode>>),
+}
+
+impl ActiveFormattingElement {
+    fn is(&self, node: &Rc<RefCell<DomNode>>) -> bool {
+        matches!(self, Self::Element(n) if Rc::ptr_eq(n, node))
+    }
 }
 
 impl HtmlParser {
@@ -51,66 +222,273 @@ impl HtmlParser {
             tokenizer,
             stack: Vec::new(),
             orig_insertion_mode: None,
+            template_insertion_modes: Vec::new(),
+            head_element: None,
+            form_element: None,
+            active_formatting_elements: Vec::new(),
+            frameset_ok: true,
+            ignore_next_lf: false,
+            scripting: false,
+            foster_parenting: false,
+            pending_table_chars: String::new(),
+            current_span: SourceSpan::default(),
+            current_start_tag: None,
+            document: Rc::new(RefCell::new(DomNode::new(NodeType::Document(
+                DocumentMode::NoQuirks,
+            )))),
+            style_sheets: Vec::new(),
+            pending_style_sheets: Vec::new(),
+            end_of_parsing: false,
+            fetcher: None,
+            document_url: String::new(),
+            base_url: None,
+            context_element: None,
+            media: MediaEnvironment::default(),
+        }
+    }
+
+    /// Sets the scripting flag of the parser. This must be called before `parse()`.
+    #[allow(dead_code)]
+    pub fn set_scripting(&mut self, enabled: bool) -> &mut Self {
+        self.scripting = enabled;
+        self
+    }
+
+    /// Sets the fetcher with which the external style sheets are loaded, and the URL of the document against
+    /// which their URLs are resolved. This must be called before `parse()`.
+    pub fn set_resource_fetcher(
+        &mut self,
+        document_url: &str,
+        fetcher: Rc<dyn ResourceFetcher>,
+    ) -> &mut Self {
+        self.document_url = document_url.to_string();
+        self.fetcher = Some(fetcher);
+        self
+    }
+
+    /// Sets the environment in which the document is rendered, which decides the style sheets applied to it.
+    /// This must be called before `parse()`.
+    pub fn set_media_environment(&mut self, media: MediaEnvironment) -> &mut Self {
+        self.media = media;
+        self
+    }
+
+    /// Returns the mode of the parsed document, which is kept by its document node. This is determined by the
+    /// DOCTYPE token in the "initial" insertion mode.
+    pub fn get_document_mode(&self) -> DocumentMode {
+        match self.document.borrow().node_type {
+            NodeType::Document(mode) => mode,
+            _ => unreachable!(),
         }
     }
 
+    /// https://dom.spec.whatwg.org/#concept-document-mode
+    fn set_document_mode(&self, mode: DocumentMode) {
+        self.document.borrow_mut().node_type = NodeType::Document(mode);
+    }
+
+    /// Returns the numbers of the tokens processed so far by type.
+    pub fn get_token_counts(&self) -> Vec<(&'static str, usize)> {
+        self.tokenizer.get_token_counts()
+    }
+
     /// Returns a Document object node and its associated list of CSS style sheets.
+    /// The tokenizer must have the whole document, or the input stream is closed by this.
+    #[tracing::instrument(skip_all)]
     pub fn parse(&mut self) -> Result<(Rc<RefCell<DomNode>>, Vec<StyleSheet>)> {
-        // The output of the whole parsing (tree construction) is a Document object.
-        let document_node = Rc::new(RefCell::new(DomNode::new(NodeType::Document)));
+        self.finish()
+    }
+
+    /// Feeds a chunk of the document to the tokenizer, and builds the tree as far as the available input allows.
+    /// The tree can be inspected between chunks, e.g. to start rendering before the response completes.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<()> {
+        self.tokenizer.feed(bytes);
+        self.process_tokens()
+    }
+
+    /// Returns the Document object node, whose tree is incomplete until the input stream is closed.
+    pub fn get_document(&self) -> Rc<RefCell<DomNode>> {
+        Rc::clone(&self.document)
+    }
+
+    /// Closes the input stream and builds the rest of the tree. Returns the same as `parse()`.
+    pub fn finish(&mut self) -> Result<(Rc<RefCell<DomNode>>, Vec<StyleSheet>)> {
+        self.tokenizer.finish();
+        self.process_tokens()?;
+        self.load_subresources();
+        Ok((
+            Rc::clone(&self.document),
+            std::mem::take(&mut self.style_sheets),
+        ))
+    }
+
+    /// Fetches the external style sheets linked by the document and the resources requested by its resource
+    /// hints in the order of their priorities, and inserts the style sheets into the list of the style sheets
+    /// in tree order. The other resources are only fetched into the HTTP cache, from which they are loaded
+    /// when they are used. A style sheet which fails to load is skipped, as browsers do.
+    /// https://html.spec.whatwg.org/multipage/links.html#link-type-preload
+    fn load_subresources(&mut self) {
+        let Some(fetcher) = self.fetcher.clone() else {
+            return;
+        };
+        let pending = std::mem::take(&mut self.pending_style_sheets);
+        let mut scheduler = ResourceScheduler::default();
+        for (_, url) in &pending {
+            scheduler.enqueue(url, ResourcePriority::RenderBlockingStyle);
+        }
+        let base_url = self.base_url.as_deref().unwrap_or(&self.document_url);
+        let hints = PageMetadata::from_document(&self.document)
+            .resource_hints
+            .into_iter()
+            .map(|hint| ResourceHint {
+                href: resolve_url(base_url, &hint.href),
+                ..hint
+            })
+            .collect::<Vec<_>>();
+        scheduler.enqueue_hints(&hints);
+        // The failures of the speculative fetches are not reported, since the resources may not be used.
+        let responses = scheduler
+            .fetch_all(|url| fetcher.fetch(url))
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let mut loaded = Vec::new();
+        for (index, url) in pending {
+            match &responses[&url] {
+                Result::Ok(bytes) => match load_style_sheet(&url, bytes, fetcher.as_ref()) {
+                    Result::Ok(style_sheet) => loaded.push((index, style_sheet)),
+                    Err(e) => eprintln!("{:#}", e),
+                },
+                Err(e) => eprintln!("{:#}", e),
+            }
+        }
+        // The later ones are inserted first so that the indices of the earlier ones are kept.
+        for (index, style_sheet) in loaded.into_iter().rev() {
+            self.style_sheets.insert(index, style_sheet);
+        }
+    }
+
+    /// Parses the input as the contents of the context element, and returns the parsed nodes, which are detached
+    /// from the temporary document they were parsed in.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-html-fragments
+    pub fn parse_fragment(
+        &mut self,
+        context_element: &Rc<RefCell<DomNode>>,
+    ) -> Result<Vec<Rc<RefCell<DomNode>>>> {
+        let Some(context_name) = Self::get_tag_name(context_element) else {
+            bail!("The context of a fragment must be an element.");
+        };
+
+        // The tokenizer starts in the state in which the contents of the context element are tokenized.
+        self.tokenizer.change_state(match context_name.as_str() {
+            "title" | "textarea" => TokenizationState::RcData,
+            "style" | "xmp" | "iframe" | "noembed" | "noframes" => TokenizationState::RawText,
+            "script" => TokenizationState::ScriptData,
+            "noscript" if self.scripting => TokenizationState::RawText,
+            "plaintext" => TokenizationState::PlainText,
+            _ => TokenizationState::Data,
+        });
+
+        let root = DomNode::append_child(
+            &self.document,
+            DomNode::new(NodeType::Element(Element {
+                tag_name: "html".to_string(),
+                attributes: Vec::new(),
+            })),
+        );
+        self.stack.push(Rc::clone(&root));
+        if context_name == "template" {
+            self.template_insertion_modes
+                .push(InsertionMode::InTemplate);
+        }
+        self.context_element = Some(Rc::clone(context_element));
+        self.reset_insertion_mode();
+        // The nearest inclusive ancestor form of the context element is open.
+        let mut node = Some(Rc::clone(context_element));
+        while let Some(n) = node {
+            if Self::get_tag_name(&n).as_deref() == Some("form") {
+                self.form_element = Some(n);
+                break;
+            }
+            node = DomNode::get_parent(&n);
+        }
+
+        self.finish()?;
+        let children = root.borrow().children.clone();
+        children.iter().for_each(DomNode::detach);
+        Ok(children)
+    }
 
-        // The document has an associated list of zero or more CSS style sheets.
-        // This is an ordered list that contains:
-        // 1. Any CSS style sheets created from HTTP Link headers, in header order
-        // 2. Any CSS style sheets associated with the DocumentOrShadowRoot, in tree order
-        // https://drafts.csswg.org/cssom/#documentorshadowroot-document-or-shadow-root-css-style-sheets
-        let mut style_sheets = Vec::new();
+    /// Processes the tokens until the tokenizer is suspended for more input or the parsing ends.
+    fn process_tokens(&mut self) -> Result<()> {
+        let document_node = Rc::clone(&self.document);
+        while !self.end_of_parsing {
+            let Some((token, span)) = self.tokenizer.try_consume_token_with_span() else {
+                break;
+            };
+            self.current_span = span;
+            self.current_start_tag = match &token {
+                HtmlToken::StartTag { tag_name, .. } => Some(tag_name.clone()),
+                _ => None,
+            };
+            if std::mem::take(&mut self.ignore_next_lf) && token == HtmlToken::Character('\n') {
+                continue;
+            }
+            if self.process_token_in_foreign_content(&token) {
+                continue;
+            }
 
-        let mut end_of_parsing = false;
-        while !end_of_parsing {
-            let token = self.tokenizer.consume_token();
+            // When a token is processed "using the rules for" another insertion mode, the mode is set here
+            // without changing the current insertion mode.
+            let mut rules_mode = None;
 
             loop {
                 // https://html.spec.whatwg.org/multipage/parsing.html#tree-construction
-                match &self.insertion_mode {
+                match rules_mode.take().unwrap_or(self.insertion_mode) {
                     // https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
                     InsertionMode::Initial => {
                         match &token {
                             HtmlToken::Character(c) if Self::is_blank(*c) => {}
-                            HtmlToken::Comment(_) => {
-                                unimplemented!("token: {:?}", token);
+                            HtmlToken::Comment(comment) => {
+                                self.insert_comment_at(comment.clone(), &document_node);
                             }
                             HtmlToken::Doctype {
                                 name,
                                 public_identifier,
                                 system_identifier,
-                                ..
+                                force_quirks,
                             } => {
-                                if (name.is_some() && name.clone().unwrap() != "html")
+                                if name.as_deref() != Some("html")
                                     || public_identifier.is_some()
-                                    || (system_identifier.is_some()
-                                        && system_identifier.clone().unwrap()
-                                            != "about:legacy-compat")
+                                    || system_identifier
+                                        .as_deref()
+                                        .is_some_and(|id| id != "about:legacy-compat")
                                 {
-                                    bail!(ParseError {
-                                        message: "Invalid doctype".to_string(),
-                                        current_token: token,
-                                        current_tree: DocumentTree::build(Rc::clone(
-                                            &document_node
-                                        ))?
-                                        .to_string(),
-                                    });
+                                    self.report_error(
+                                        "invalid-doctype",
+                                        Some(format!("{:?}", token)),
+                                    );
                                 }
                                 DomNode::append_child(
                                     &document_node,
-                                    DomNode::new(NodeType::DocumentType(match name {
-                                        Some(name) => name.clone(),
-                                        None => String::new(),
-                                    })),
+                                    self.create_node_for_token(NodeType::DocumentType(
+                                        match name {
+                                            Some(name) => name.clone(),
+                                            None => String::new(),
+                                        },
+                                    )),
                                 );
+                                self.set_document_mode(Self::determine_document_mode(
+                                    name.as_deref(),
+                                    public_identifier.as_deref(),
+                                    system_identifier.as_deref(),
+                                    *force_quirks,
+                                ));
                                 self.insertion_mode = InsertionMode::BeforeHtml;
                             }
                             _ => {
+                                // If the document is not an iframe srcdoc document, this is a parse error.
+                                self.report_error("missing-doctype", None);
+                                self.set_document_mode(DocumentMode::Quirks);
                                 self.insertion_mode = InsertionMode::BeforeHtml;
                                 continue; // reprocess the token
                             }
@@ -120,7 +498,10 @@ impl HtmlParser {
                     // https://html.spec.whatwg.org/multipage/parsing.html#the-before-html-insertion-mode
                     InsertionMode::BeforeHtml => match &token {
                         HtmlToken::Doctype { .. } => {
-                            eprintln!("parse error, ignored the token: {:?}", token);
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::Comment(comment) => {
+                            self.insert_comment_at(comment.clone(), &document_node);
                         }
                         HtmlToken::Character(c) if Self::is_blank(*c) => {}
                         HtmlToken::StartTag {
@@ -130,7 +511,7 @@ impl HtmlParser {
                         } if tag_name == "html" => {
                             let n = DomNode::append_child(
                                 &document_node,
-                                DomNode::new(NodeType::Element(Element {
+                                self.create_node_for_token(NodeType::Element(Element {
                                     tag_name: tag_name.clone(),
                                     attributes: attributes.clone(),
                                 })),
@@ -150,7 +531,7 @@ impl HtmlParser {
                                 self.stack.push(Rc::clone(&n));
                                 self.insertion_mode = InsertionMode::BeforeHead;
                             } else {
-                                eprintln!("parse error, ignored the token: {:?}", token);
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
                             }
                         }
                         _ => {
@@ -170,8 +551,11 @@ impl HtmlParser {
                     // https://html.spec.whatwg.org/multipage/parsing.html#the-before-head-insertion-mode
                     InsertionMode::BeforeHead => match &token {
                         HtmlToken::Character(c) if Self::is_blank(*c) => {}
+                        HtmlToken::Comment(comment) => {
+                            self.insert_comment(comment.clone());
+                        }
                         HtmlToken::Doctype { .. } => {
-                            eprintln!("parse error, ignored the token: {:?}", token);
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
                         }
                         HtmlToken::StartTag {
                             tag_name,
@@ -180,11 +564,16 @@ impl HtmlParser {
                         } => match tag_name.as_str() {
                             "head" => {
                                 self.insert_element(tag_name, attributes);
+                                self.head_element = self.stack.last().cloned();
                                 self.insertion_mode = InsertionMode::InHead;
                             }
-                            "html" => unimplemented!("token: {:?}", token),
+                            "html" => {
+                                rules_mode = Some(InsertionMode::InBody);
+                                continue;
+                            }
                             _ => {
                                 self.insert_element("head", &Vec::new());
+                                self.head_element = self.stack.last().cloned();
                                 self.insertion_mode = InsertionMode::InHead;
                                 continue;
                             }
@@ -195,114 +584,174 @@ impl HtmlParser {
                                 self.insertion_mode = InsertionMode::InHead;
                                 continue;
                             } else {
-                                eprintln!("parse error, ignored the token: {:?}", token);
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
                             }
                         }
                         _ => {
                             self.insert_element("head", &Vec::new());
+                            self.head_element = self.stack.last().cloned();
                             self.insertion_mode = InsertionMode::InHead;
                             continue;
                         }
                     },
 
                     // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inhead
-                    InsertionMode::InHead => {
-                        match &token {
-                            HtmlToken::Character(c) if Self::is_blank(*c) => {
-                                self.insert_char_to_token(*c);
+                    InsertionMode::InHead => match &token {
+                        HtmlToken::Character(c) if Self::is_blank(*c) => {
+                            self.insert_char_to_token(*c);
+                        }
+                        HtmlToken::Comment(comment) => {
+                            self.insert_comment(comment.clone());
+                        }
+                        HtmlToken::Doctype { .. } => {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::StartTag {
+                            tag_name,
+                            attributes,
+                            ..
+                        } => match tag_name.as_str() {
+                            "base" | "basefont" | "bgsound" | "link" | "meta" => {
+                                self.insert_metadata_element(tag_name, attributes)?;
                             }
-                            HtmlToken::Comment(comment) => {
-                                self.insert_comment(comment.clone());
-                            }
-                            HtmlToken::Doctype { .. } => {
-                                eprintln!("parse error, ignored the token: {:?}", token);
-                            }
-                            HtmlToken::StartTag {
-                                tag_name,
-                                attributes,
-                                ..
-                            } => match tag_name.as_str() {
-                                "meta" => {
-                                    DomNode::append_child(
-                                        self.stack.last().unwrap(),
-                                        DomNode::new(NodeType::Element(Element {
-                                            tag_name: tag_name.clone(),
-                                            attributes: attributes.clone(),
-                                        })),
-                                    );
-                                    // Insert the element (including pushing it onto the stack of open elements) and pop it immediately,
-                                    // so the content of the stack is not changed here.
-                                }
-                                "title" => {
-                                    // Quite simplified
-                                    self.insert_element(tag_name, attributes);
-                                    self.orig_insertion_mode = Some(InsertionMode::InHead);
-                                    self.insertion_mode = InsertionMode::Text;
-                                }
-                                "style" => {
-                                    // https://html.spec.whatwg.org/multipage/parsing.html#generic-raw-text-element-parsing-algorithm
-                                    self.insert_element(tag_name, attributes);
-                                    self.tokenizer.change_state(TokenizationState::RawText);
-                                    self.orig_insertion_mode = Some(InsertionMode::InHead);
-                                    self.insertion_mode = InsertionMode::Text;
-                                }
-                                "head" => {
-                                    eprintln!("parse error, ignored the token: {:?}", token);
-                                }
-                                "html" | "template" | "noscript" | "script" | "noframes"
-                                | "base" | "basefont" | "bgsound" | "link" => {
-                                    unimplemented!("token: {:?}", token);
-                                }
-                                _ => {
-                                    self.stack.pop();
-                                    self.insertion_mode = InsertionMode::AfterHead;
-                                    continue;
-                                }
-                            },
-                            HtmlToken::EndTag { tag_name, .. } => match tag_name.as_str() {
-                                "head" => {
-                                    let elm = self.stack.pop().unwrap();
-                                    if let NodeType::Element(elm) = &elm.borrow().node_type {
-                                        ensure!(
-                                            elm.tag_name == "head",
-                                            ParseError {
-                                                message: "Expected head element".to_string(),
-                                                current_token: token,
-                                                current_tree: DocumentTree::build(Rc::clone(
-                                                    &document_node
-                                                ))?
-                                                .to_string(),
-                                            }
-                                        );
-                                    } else {
-                                        bail!(ParseError {
+                            "title" => {
+                                self.insert_rcdata_element(tag_name, attributes);
+                            }
+                            "style" => {
+                                self.insert_raw_text_element(tag_name, attributes);
+                            }
+                            "head" => {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            }
+                            "script" => {
+                                self.insert_script_element(tag_name, attributes);
+                            }
+                            "template" => {
+                                self.insert_template_element(tag_name, attributes);
+                            }
+                            "noscript" if self.scripting => {
+                                self.insert_raw_text_element(tag_name, attributes);
+                            }
+                            "noscript" => {
+                                self.insert_element(tag_name, attributes);
+                                self.insertion_mode = InsertionMode::InHeadNoscript;
+                            }
+                            "noframes" => {
+                                self.insert_raw_text_element(tag_name, attributes);
+                            }
+                            "html" => {
+                                rules_mode = Some(InsertionMode::InBody);
+                                continue;
+                            }
+                            _ => {
+                                self.stack.pop();
+                                self.insertion_mode = InsertionMode::AfterHead;
+                                continue;
+                            }
+                        },
+                        HtmlToken::EndTag { tag_name, .. } => match tag_name.as_str() {
+                            "head" => {
+                                let elm = self.stack.pop().unwrap();
+                                if let NodeType::Element(elm) = &elm.borrow().node_type {
+                                    ensure!(
+                                        elm.tag_name == "head",
+                                        ParseError {
                                             message: "Expected head element".to_string(),
                                             current_token: token,
                                             current_tree: DocumentTree::build(Rc::clone(
                                                 &document_node
                                             ))?
                                             .to_string(),
-                                        });
-                                    }
-                                    self.insertion_mode = InsertionMode::AfterHead;
+                                        }
+                                    );
+                                } else {
+                                    bail!(ParseError {
+                                        message: "Expected head element".to_string(),
+                                        current_token: token,
+                                        current_tree: DocumentTree::build(Rc::clone(
+                                            &document_node
+                                        ))?
+                                        .to_string(),
+                                    });
                                 }
-                                _ => unimplemented!("token: {:?}", token),
-                            },
-                            _ => {
+                                self.insertion_mode = InsertionMode::AfterHead;
+                            }
+                            "template" => {
+                                self.close_template_element();
+                            }
+                            "body" | "html" | "br" => {
                                 self.stack.pop();
                                 self.insertion_mode = InsertionMode::AfterHead;
                                 continue;
                             }
+                            _ => {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            }
+                        },
+                        _ => {
+                            self.stack.pop();
+                            self.insertion_mode = InsertionMode::AfterHead;
+                            continue;
                         }
-                    }
+                    },
+
+                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inheadnoscript
+                    InsertionMode::InHeadNoscript => match &token {
+                        HtmlToken::Doctype { .. } => {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::StartTag { tag_name, .. } if tag_name == "html" => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "noscript" => {
+                            self.stack.pop();
+                            self.insertion_mode = InsertionMode::InHead;
+                        }
+                        HtmlToken::Character(c) if Self::is_blank(*c) => {
+                            rules_mode = Some(InsertionMode::InHead);
+                            continue;
+                        }
+                        HtmlToken::Comment(_) => {
+                            rules_mode = Some(InsertionMode::InHead);
+                            continue;
+                        }
+                        HtmlToken::StartTag { tag_name, .. }
+                            if matches!(
+                                tag_name.as_str(),
+                                "basefont" | "bgsound" | "link" | "meta" | "noframes" | "style"
+                            ) =>
+                        {
+                            rules_mode = Some(InsertionMode::InHead);
+                            continue;
+                        }
+                        HtmlToken::StartTag { tag_name, .. }
+                            if tag_name == "head" || tag_name == "noscript" =>
+                        {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::EndTag { tag_name, .. } if tag_name != "br" => {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        _ => {
+                            self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                            self.stack.pop();
+                            self.insertion_mode = InsertionMode::InHead;
+                            continue;
+                        }
+                    },
 
                     // https://html.spec.whatwg.org/multipage/parsing.html#the-after-head-insertion-mode
                     InsertionMode::AfterHead => match &token {
+                        // The head element has been popped off, so the character is inserted into the html element.
                         HtmlToken::Character(c) if Self::is_blank(*c) => {
                             self.insert_char_to_token(*c);
                         }
+                        HtmlToken::Comment(comment) => {
+                            self.insert_comment(comment.clone());
+                        }
                         HtmlToken::Doctype { .. } => {
-                            eprintln!("parse error, ignored the token: {:?}", token);
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
                         }
                         HtmlToken::StartTag {
                             tag_name,
@@ -318,11 +767,32 @@ impl HtmlParser {
                                 self.insertion_mode = InsertionMode::InFrameset;
                             }
                             "head" => {
-                                eprintln!("parse error, ignored the token: {:?}", token);
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            }
+                            "html" => {
+                                rules_mode = Some(InsertionMode::InBody);
+                                continue;
                             }
-                            "html" | "base" | "basefont" | "bgsound" | "link" | "meta"
-                            | "noframes" | "script" | "style" | "template" | "title" => {
-                                unimplemented!("token: {:?}", token);
+                            "base" | "basefont" | "bgsound" | "link" | "meta" | "noframes"
+                            | "script" | "style" | "template" | "title" => {
+                                // The head element is pushed back onto the stack while the token is
+                                // processed using the rules for the "in head" insertion mode, so that the
+                                // element is inserted into it.
+                                self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                                let head = Rc::clone(self.head_element.as_ref().unwrap());
+                                self.stack.push(Rc::clone(&head));
+                                match tag_name.as_str() {
+                                    "noframes" | "style" => {
+                                        self.insert_raw_text_element(tag_name, attributes)
+                                    }
+                                    "script" => self.insert_script_element(tag_name, attributes),
+                                    "template" => {
+                                        self.insert_template_element(tag_name, attributes)
+                                    }
+                                    "title" => self.insert_rcdata_element(tag_name, attributes),
+                                    _ => self.insert_metadata_element(tag_name, attributes)?,
+                                }
+                                self.stack.retain(|n| !Rc::ptr_eq(n, &head));
                             }
                             _ => {
                                 self.insert_element("body", &Vec::new());
@@ -330,7 +800,20 @@ impl HtmlParser {
                                 continue;
                             }
                         },
-                        HtmlToken::EndTag { .. } => unimplemented!("token: {:?}", token),
+                        HtmlToken::EndTag { tag_name, .. } => match tag_name.as_str() {
+                            "template" => {
+                                rules_mode = Some(InsertionMode::InHead);
+                                continue;
+                            }
+                            "body" | "html" | "br" => {
+                                self.insert_element("body", &Vec::new());
+                                self.insertion_mode = InsertionMode::InBody;
+                                continue;
+                            }
+                            _ => {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            }
+                        },
                         _ => {
                             self.insert_element("body", &Vec::new());
                             self.insertion_mode = InsertionMode::InBody;
@@ -341,420 +824,2554 @@ impl HtmlParser {
                     // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
                     InsertionMode::InBody => match &token {
                         HtmlToken::Character(c) => match c {
-                            '\u{0000}' => eprintln!("parse error, ignored the token: {:?}", token),
+                            '\u{0000}' => {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)))
+                            }
                             _ => {
+                                self.reconstruct_active_formatting_elements();
                                 self.insert_char_to_token(*c);
+                                if !Self::is_blank(*c) {
+                                    self.frameset_ok = false;
+                                }
                             }
                         },
                         HtmlToken::Comment(comment) => {
                             self.insert_comment(comment.clone());
                         }
                         HtmlToken::Doctype { .. } => {
-                            eprintln!("parse error, ignored the token: {:?}", token)
+                            self.report_error("ignored-token", Some(format!("{:?}", token)))
                         }
                         HtmlToken::StartTag {
                             tag_name,
                             attributes,
                             ..
                         } => match tag_name.as_str() {
-                            "a" | "div" | "p" | "ul" => {
-                                self.insert_element(tag_name, attributes);
-                            }
-                            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
-                                let mut need_to_pop = false;
-                                if let Some(n) = &self.stack.last() {
-                                    if let NodeType::Element(elm) = &n.borrow().node_type {
-                                        if let "h1" | "h2" | "h3" | "h4" | "h5" | "h6" =
-                                            elm.tag_name.as_str()
-                                        {
-                                            eprintln!("parse error");
-                                            need_to_pop = true;
+                            "html" => {
+                                self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                                // The attributes are added to the root element unless it already has them.
+                                if !self.is_template_on_stack() {
+                                    if let NodeType::Element(html) =
+                                        &mut self.stack[0].borrow_mut().node_type
+                                    {
+                                        for (name, value) in attributes {
+                                            if html.get_attribute(name).is_none() {
+                                                html.attributes.push((name.clone(), value.clone()));
+                                            }
                                         }
                                     }
                                 }
-                                if need_to_pop {
-                                    self.stack.pop();
+                            }
+                            "frameset" => {
+                                self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                                let body = self
+                                    .stack
+                                    .get(1)
+                                    .filter(|n| Self::get_tag_name(n).as_deref() == Some("body"))
+                                    .cloned();
+                                // The body is replaced by the frameset only if nothing has been rendered in it.
+                                if let Some(body) = body.filter(|_| self.frameset_ok) {
+                                    DomNode::detach(&body);
+                                    self.stack.truncate(1);
+                                    self.insert_element(tag_name, attributes);
+                                    self.insertion_mode = InsertionMode::InFrameset;
                                 }
-                                self.insert_element(tag_name, attributes);
                             }
-                            "li" => {
-                                let mut node_type = self.get_current_elm_name().unwrap();
-
-                                loop {
-                                    if node_type.as_str() == "li" {
-                                        self.generate_implied_end_tags(Some("li"));
-                                        if self.get_current_elm_name().unwrap().as_str() != "li" {
-                                            eprintln!("parse error");
-                                        }
-                                        loop {
-                                            if let Some(n) = &self.stack.pop() {
-                                                if let NodeType::Element(elm) =
-                                                    &n.borrow().node_type
-                                                {
-                                                    if elm.tag_name == "li" {
-                                                        break;
-                                                    }
-                                                }
-                                            } else {
-                                                bail!(ParseError {
-                                                    message: "li element not found".to_string(),
-                                                    current_token: token,
-                                                    current_tree: DocumentTree::build(Rc::clone(
-                                                        &document_node
-                                                    ))?
-                                                    .to_string(),
-                                                });
+                            "body" => {
+                                self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                                let body = self
+                                    .stack
+                                    .get(1)
+                                    .filter(|n| Self::get_tag_name(n).as_deref() == Some("body"))
+                                    .cloned();
+                                if let Some(body) = body.filter(|_| !self.is_template_on_stack()) {
+                                    self.frameset_ok = false;
+                                    if let NodeType::Element(body) =
+                                        &mut body.borrow_mut().node_type
+                                    {
+                                        for (name, value) in attributes {
+                                            if body.get_attribute(name).is_none() {
+                                                body.attributes.push((name.clone(), value.clone()));
                                             }
                                         }
-                                        break;
-                                    }
-
-                                    // This is quite simplified.
-                                    if let "address" | "div" | "p" = node_type.as_str() {
-                                        // Set the node to the previous entry in the stack of open elements.
-                                        node_type = if let Some(node) =
-                                            &self.stack.get(self.stack.len() - 2)
-                                        {
-                                            let NodeType::Element(elm) = &node.borrow().node_type
-                                            else {
-                                                bail!(ParseError {
-                                                    message: "The node is not an element"
-                                                        .to_string(),
-                                                    current_token: token,
-                                                    current_tree: DocumentTree::build(Rc::clone(
-                                                        &document_node
-                                                    ))?
-                                                    .to_string(),
-                                                });
-                                            };
-                                            elm.tag_name.clone()
-                                        } else {
-                                            bail!(ParseError {
-                                                message: "Previous node not found".to_string(),
-                                                current_token: token,
-                                                current_tree: DocumentTree::build(Rc::clone(
-                                                    &document_node
-                                                ))?
-                                                .to_string(),
-                                            });
-                                        };
-                                        continue;
-                                    } else {
-                                        // If node is in the special category, but is not an address, div, or p element
-                                        break;
                                     }
                                 }
+                            }
+                            "frame" | "head" | "caption" | "col" | "colgroup" | "tbody" | "td"
+                            | "tfoot" | "th" | "thead" | "tr" => {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            }
+                            "noframes" => {
+                                // Process the token using the rules for the "in head" insertion mode.
+                                self.insert_raw_text_element(tag_name, attributes);
+                            }
+                            "address" | "article" | "aside" | "blockquote" | "center"
+                            | "details" | "dialog" | "dir" | "div" | "dl" | "fieldset"
+                            | "figcaption" | "figure" | "footer" | "header" | "hgroup" | "main"
+                            | "menu" | "nav" | "ol" | "p" | "search" | "section" | "summary"
+                            | "ul" => {
+                                self.close_p_element_in_button_scope();
                                 self.insert_element(tag_name, attributes);
                             }
-                            "br" => {
+                            "pre" | "listing" => {
+                                self.close_p_element_in_button_scope();
                                 self.insert_element(tag_name, attributes);
-                                self.stack.pop();
+                                // Newlines at the start of pre elements are ignored as an authoring convenience.
+                                self.ignore_next_lf = true;
+                                self.frameset_ok = false;
                             }
-                            _ => {
-                                unimplemented!("token: {:?}", token);
+                            "form" => {
+                                // Forms can't be nested, but they can be in templates.
+                                let is_in_template = self.is_template_on_stack();
+                                if self.form_element.is_some() && !is_in_template {
+                                    self.report_error(
+                                        "ignored-token",
+                                        Some(format!("{:?}", token)),
+                                    );
+                                } else {
+                                    self.close_p_element_in_button_scope();
+                                    self.insert_element(tag_name, attributes);
+                                    if !is_in_template {
+                                        self.form_element = self.stack.last().cloned();
+                                    }
+                                }
                             }
-                        },
-                        HtmlToken::EndTag { tag_name, .. } => match tag_name.as_str() {
-                            "a" => {
-                                if self.get_current_elm_name().unwrap().as_str() != "a" {
-                                    eprintln!("parse error");
-                                }
-                                loop {
-                                    if let Some(n) = &self.stack.pop() {
-                                        if let NodeType::Element(elm) = &n.borrow().node_type {
-                                            if elm.tag_name == "a" {
-                                                break;
-                                            }
-                                        }
-                                    } else {
-                                        bail!(ParseError {
-                                            message: "a element not found".to_string(),
-                                            current_token: token,
-                                            current_tree: DocumentTree::build(Rc::clone(
-                                                &document_node
-                                            ))?
-                                            .to_string(),
-                                        });
-                                    }
+                            "plaintext" => {
+                                self.close_p_element_in_button_scope();
+                                self.insert_element(tag_name, attributes);
+                                // The rest of the input is the text of the element.
+                                self.tokenizer.change_state(TokenizationState::PlainText);
+                            }
+                            "button" => {
+                                if self.has_element_in_scope("button") {
+                                    self.report_error(
+                                        "unexpected-token",
+                                        Some(format!("{:?}", token)),
+                                    );
+                                    self.generate_implied_end_tags(None);
+                                    self.pop_until(&["button"]);
                                 }
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element(tag_name, attributes);
+                                self.frameset_ok = false;
                             }
-                            "body" => {
-                                self.insertion_mode = InsertionMode::AfterBody;
+                            "a" => {
+                                if let Some(ActiveFormattingElement::Element(a)) = self
+                                    .find_active_formatting_element("a")
+                                    .map(|i| self.active_formatting_elements[i].clone())
+                                {
+                                    self.report_error(
+                                        "unexpected-token",
+                                        Some(format!("{:?}", token)),
+                                    );
+                                    self.run_adoption_agency("a");
+                                    self.active_formatting_elements.retain(|e| !e.is(&a));
+                                    self.stack.retain(|n| !Rc::ptr_eq(n, &a));
+                                }
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element(tag_name, attributes);
+                                self.push_active_formatting_element();
                             }
-                            "div" | "ul" => {
-                                self.generate_implied_end_tags(None);
-                                if self.get_current_elm_name().unwrap().as_str() != tag_name {
-                                    eprintln!("parse error");
+                            "b" | "big" | "code" | "em" | "font" | "i" | "s" | "small"
+                            | "strike" | "strong" | "tt" | "u" => {
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element(tag_name, attributes);
+                                self.push_active_formatting_element();
+                            }
+                            "nobr" => {
+                                self.reconstruct_active_formatting_elements();
+                                if self.has_element_in_scope("nobr") {
+                                    self.report_error(
+                                        "unexpected-token",
+                                        Some(format!("{:?}", token)),
+                                    );
+                                    self.run_adoption_agency("nobr");
+                                    self.reconstruct_active_formatting_elements();
                                 }
-                                while let Some(n) = self.stack.pop() {
+                                self.insert_element(tag_name, attributes);
+                                self.push_active_formatting_element();
+                            }
+                            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                                self.close_p_element_in_button_scope();
+                                let mut need_to_pop = false;
+                                if let Some(n) = &self.stack.last() {
                                     if let NodeType::Element(elm) = &n.borrow().node_type {
-                                        if elm.tag_name == *tag_name {
-                                            break;
+                                        if let "h1" | "h2" | "h3" | "h4" | "h5" | "h6" =
+                                            elm.tag_name.as_str()
+                                        {
+                                            need_to_pop = true;
                                         }
                                     }
                                 }
+                                if need_to_pop {
+                                    self.report_error(
+                                        "unexpected-token",
+                                        Some(format!("{:?}", token)),
+                                    );
+                                    self.stack.pop();
+                                }
+                                self.insert_element(tag_name, attributes);
                             }
-                            "p" => {
-                                self.generate_implied_end_tags(Some("p"));
-                                if self.get_current_elm_name().unwrap().as_str() != "p" {
-                                    eprintln!("parse error");
-                                }
-                                loop {
-                                    if let Some(n) = &self.stack.pop() {
-                                        if let NodeType::Element(elm) = &n.borrow().node_type {
-                                            if elm.tag_name == "p" {
-                                                break;
-                                            }
+                            "li" | "dd" | "dt" => {
+                                self.frameset_ok = false;
+                                // An open list item of the same kind is closed, unless it is inside another
+                                // special element such as a nested list.
+                                let closed: &[&str] = if tag_name == "li" {
+                                    &["li"]
+                                } else {
+                                    &["dd", "dt"]
+                                };
+                                for node in self.stack.clone().iter().rev() {
+                                    let Some(name) = Self::get_tag_name(node) else {
+                                        break;
+                                    };
+                                    if closed.contains(&name.as_str()) {
+                                        self.generate_implied_end_tags(Some(&name));
+                                        if self.get_current_elm_name().as_ref() != Some(&name) {
+                                            self.report_error(
+                                                "unclosed-elements",
+                                                Some(name.clone()),
+                                            );
                                         }
-                                    } else {
-                                        bail!(ParseError {
-                                            message: "p element not found".to_string(),
-                                            current_token: token,
-                                            current_tree: DocumentTree::build(Rc::clone(
-                                                &document_node
-                                            ))?
-                                            .to_string(),
-                                        });
+                                        self.pop_until(&[&name]);
+                                        break;
+                                    }
+                                    if Self::is_special_tag(&name)
+                                        && !matches!(name.as_str(), "address" | "div" | "p")
+                                    {
+                                        break;
                                     }
                                 }
+                                self.close_p_element_in_button_scope();
+                                self.insert_element(tag_name, attributes);
+                            }
+                            "applet" | "marquee" | "object" => {
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element(tag_name, attributes);
+                                self.active_formatting_elements
+                                    .push(ActiveFormattingElement::Marker);
+                                self.frameset_ok = false;
                             }
-                            "li" => {
-                                self.generate_implied_end_tags(Some("li"));
-                                if self.get_current_elm_name().unwrap().as_str() != "li" {
-                                    eprintln!("parse error");
+                            "table" => {
+                                if self.get_document_mode() != DocumentMode::Quirks {
+                                    self.close_p_element_in_button_scope();
                                 }
-                                loop {
-                                    if let Some(n) = &self.stack.pop() {
-                                        if let NodeType::Element(elm) = &n.borrow().node_type {
-                                            if elm.tag_name == "li" {
-                                                break;
-                                            }
-                                        }
-                                    } else {
-                                        bail!(ParseError {
-                                            message: "li element not found".to_string(),
-                                            current_token: token,
-                                            current_tree: DocumentTree::build(Rc::clone(
-                                                &document_node
-                                            ))?
-                                            .to_string(),
-                                        });
+                                self.insert_element(tag_name, attributes);
+                                self.frameset_ok = false;
+                                self.insertion_mode = InsertionMode::InTable;
+                            }
+                            "area" | "br" | "embed" | "img" | "keygen" | "wbr" => {
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element(tag_name, attributes);
+                                self.stack.pop();
+                                self.frameset_ok = false;
+                            }
+                            "image" => {
+                                self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element("img", attributes);
+                                self.stack.pop();
+                                self.frameset_ok = false;
+                            }
+                            "input" if self.is_select_context() => {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            }
+                            "input" => {
+                                if self.has_element_in_scope("select") {
+                                    // Inputs can't be in select elements.
+                                    self.report_error(
+                                        "unexpected-token",
+                                        Some(format!("{:?}", token)),
+                                    );
+                                    self.pop_until(&["select"]);
+                                }
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element(tag_name, attributes);
+                                self.stack.pop();
+                                // Hidden inputs are not rendered.
+                                if !attributes.iter().any(|(name, value)| {
+                                    name == "type" && value.eq_ignore_ascii_case("hidden")
+                                }) {
+                                    self.frameset_ok = false;
+                                }
+                            }
+                            "hr" => {
+                                self.close_p_element_in_button_scope();
+                                if self.has_element_in_scope("select") {
+                                    self.generate_implied_end_tags(None);
+                                    if self.has_element_in_scope("option")
+                                        || self.has_element_in_scope("optgroup")
+                                    {
+                                        self.report_error(
+                                            "unexpected-token",
+                                            Some(format!("{:?}", token)),
+                                        );
+                                    }
+                                }
+                                self.insert_element(tag_name, attributes);
+                                self.stack.pop();
+                                self.frameset_ok = false;
+                            }
+                            "xmp" => {
+                                self.close_p_element_in_button_scope();
+                                self.reconstruct_active_formatting_elements();
+                                self.frameset_ok = false;
+                                self.insert_raw_text_element(tag_name, attributes);
+                            }
+                            "iframe" => {
+                                self.frameset_ok = false;
+                                self.insert_raw_text_element(tag_name, attributes);
+                            }
+                            "noembed" => {
+                                self.insert_raw_text_element(tag_name, attributes);
+                            }
+                            "select" if self.is_select_context() => {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            }
+                            "select" => {
+                                if self.has_element_in_scope("select") {
+                                    // A nested select start tag closes the open select element.
+                                    self.report_error(
+                                        "ignored-token",
+                                        Some(format!("{:?}", token)),
+                                    );
+                                    self.pop_until(&["select"]);
+                                } else {
+                                    self.reconstruct_active_formatting_elements();
+                                    self.insert_element(tag_name, attributes);
+                                    self.frameset_ok = false;
+                                }
+                            }
+                            "optgroup" | "option" => {
+                                if self.has_element_in_scope("select") {
+                                    // An option closes the open option, and an optgroup closes both.
+                                    self.generate_implied_end_tags(
+                                        (tag_name == "option").then_some("optgroup"),
+                                    );
+                                    if self.has_element_in_scope("option")
+                                        || (tag_name == "optgroup"
+                                            && self.has_element_in_scope("optgroup"))
+                                    {
+                                        self.report_error(
+                                            "unexpected-token",
+                                            Some(format!("{:?}", token)),
+                                        );
+                                    }
+                                } else if self.get_current_elm_name().as_deref() == Some("option") {
+                                    self.stack.pop();
+                                }
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element(tag_name, attributes);
+                            }
+                            "rb" | "rtc" => {
+                                if self.has_element_in_scope("ruby") {
+                                    self.generate_implied_end_tags(None);
+                                    if self.get_current_elm_name().as_deref() != Some("ruby") {
+                                        self.report_error(
+                                            "unexpected-token",
+                                            Some(format!("{:?}", token)),
+                                        );
+                                    }
+                                }
+                                self.insert_element(tag_name, attributes);
+                            }
+                            "rp" | "rt" => {
+                                if self.has_element_in_scope("ruby") {
+                                    self.generate_implied_end_tags(Some("rtc"));
+                                    if !matches!(
+                                        self.get_current_elm_name().as_deref(),
+                                        Some("rtc" | "ruby")
+                                    ) {
+                                        self.report_error(
+                                            "unexpected-token",
+                                            Some(format!("{:?}", token)),
+                                        );
+                                    }
+                                }
+                                self.insert_element(tag_name, attributes);
+                            }
+                            "math" | "svg" => {
+                                // todo: Insert the element in the MathML or SVG namespace.
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element(tag_name, attributes);
+                                if matches!(
+                                    token,
+                                    HtmlToken::StartTag {
+                                        self_closing: true,
+                                        ..
+                                    }
+                                ) {
+                                    self.stack.pop();
+                                }
+                            }
+                            "noscript" if self.scripting => {
+                                self.insert_raw_text_element(tag_name, attributes);
+                            }
+                            "textarea" => {
+                                self.insert_rcdata_element(tag_name, attributes);
+                                // Newlines at the start of textarea elements are ignored as an authoring convenience.
+                                self.ignore_next_lf = true;
+                                self.frameset_ok = false;
+                            }
+                            "param" | "source" | "track" => {
+                                self.insert_element(tag_name, attributes);
+                                self.stack.pop();
+                            }
+                            "audio" | "video" => {
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element(tag_name, attributes);
+                            }
+                            "base" | "basefont" | "bgsound" | "link" | "meta" => {
+                                // Process the token using the rules for the "in head" insertion mode.
+                                self.insert_metadata_element(tag_name, attributes)?;
+                            }
+                            "script" => {
+                                // Process the token using the rules for the "in head" insertion mode.
+                                self.insert_script_element(tag_name, attributes);
+                            }
+                            "style" => {
+                                // Process the token using the rules for the "in head" insertion mode.
+                                self.insert_raw_text_element(tag_name, attributes);
+                            }
+                            "template" => {
+                                // Process the token using the rules for the "in head" insertion mode.
+                                self.insert_template_element(tag_name, attributes);
+                            }
+                            // Any other start tag, including image maps, slots, custom elements, and `noscript`
+                            // elements with scripting disabled.
+                            _ => {
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element(tag_name, attributes);
+                            }
+                        },
+                        HtmlToken::EndTag { tag_name, .. } => match tag_name.as_str() {
+                            "a" | "b" | "big" | "code" | "em" | "font" | "i" | "nobr" | "s"
+                            | "small" | "strike" | "strong" | "tt" | "u" => {
+                                if !self.run_adoption_agency(tag_name) {
+                                    self.close_element_for_end_tag(tag_name);
+                                }
+                            }
+                            "body" => {
+                                if !self.has_element_in_scope("body") {
+                                    self.report_error(
+                                        "ignored-token",
+                                        Some(format!("{:?}", token)),
+                                    );
+                                } else {
+                                    self.insertion_mode = InsertionMode::AfterBody;
+                                }
+                            }
+                            "html" => {
+                                if !self.has_element_in_scope("body") {
+                                    self.report_error(
+                                        "ignored-token",
+                                        Some(format!("{:?}", token)),
+                                    );
+                                } else {
+                                    self.insertion_mode = InsertionMode::AfterBody;
+                                    continue;
+                                }
+                            }
+                            "template" => {
+                                // Process the token using the rules for the "in head" insertion mode.
+                                self.close_template_element();
+                            }
+                            "address" | "article" | "aside" | "blockquote" | "button"
+                            | "center" | "details" | "dialog" | "dir" | "div" | "dl"
+                            | "fieldset" | "figcaption" | "figure" | "footer" | "header"
+                            | "hgroup" | "listing" | "main" | "menu" | "nav" | "ol" | "pre"
+                            | "search" | "section" | "select" | "summary" | "ul" => {
+                                self.close_block_element(tag_name);
+                            }
+                            "form" if self.is_template_on_stack() => {
+                                self.close_block_element(tag_name);
+                            }
+                            "form" => {
+                                // The form is closed even if it is not the current node, and the elements
+                                // opened in it are left open.
+                                let form = self.form_element.take().filter(|form| {
+                                    self.has_node_in_scope(
+                                        |n| Rc::ptr_eq(n, form),
+                                        ElementScope::Default,
+                                    )
+                                });
+                                if let Some(form) = form {
+                                    self.generate_implied_end_tags(None);
+                                    if !self.stack.last().is_some_and(|n| Rc::ptr_eq(n, &form)) {
+                                        self.report_error(
+                                            "unclosed-elements",
+                                            Some(tag_name.to_string()),
+                                        );
+                                    }
+                                    self.stack.retain(|n| !Rc::ptr_eq(n, &form));
+                                } else {
+                                    self.report_error(
+                                        "unexpected-end-tag",
+                                        Some(tag_name.to_string()),
+                                    );
+                                }
+                            }
+                            "p" => {
+                                if !self.has_element_in_button_scope("p") {
+                                    // An empty paragraph is inserted for a stray end tag.
+                                    self.report_error(
+                                        "unexpected-end-tag",
+                                        Some(tag_name.to_string()),
+                                    );
+                                    self.insert_element("p", &[]);
+                                }
+                                self.close_p_element();
+                            }
+                            "li" | "dd" | "dt" => {
+                                let in_scope = if tag_name == "li" {
+                                    self.has_element_in_list_item_scope(tag_name)
+                                } else {
+                                    self.has_element_in_scope(tag_name)
+                                };
+                                if !in_scope {
+                                    self.report_error(
+                                        "unexpected-end-tag",
+                                        Some(tag_name.to_string()),
+                                    );
+                                } else {
+                                    self.generate_implied_end_tags(Some(tag_name));
+                                    if self.get_current_elm_name().as_deref() != Some(tag_name) {
+                                        self.report_error(
+                                            "unclosed-elements",
+                                            Some(tag_name.to_string()),
+                                        );
                                     }
+                                    self.pop_until(&[tag_name]);
                                 }
                             }
                             "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
-                                self.generate_implied_end_tags(None);
-                                if self.get_current_elm_name().unwrap().as_str() != tag_name {
-                                    eprintln!("parse error");
-                                }
-                                loop {
-                                    if let Some(n) = &self.stack.pop() {
-                                        if let NodeType::Element(elm) = &n.borrow().node_type {
-                                            if let "h1" | "h2" | "h3" | "h4" | "h5" | "h6" =
-                                                elm.tag_name.as_str()
-                                            {
-                                                break;
-                                            }
-                                        }
-                                    } else {
-                                        bail!(ParseError {
-                                            message: "h1-h6 element not found".to_string(),
-                                            current_token: token,
-                                            current_tree: DocumentTree::build(Rc::clone(
-                                                &document_node
-                                            ))?
-                                            .to_string(),
-                                        });
+                                const HEADINGS: [&str; 6] = ["h1", "h2", "h3", "h4", "h5", "h6"];
+                                if !self.has_node_in_scope(
+                                    |n| {
+                                        Self::get_tag_name(n)
+                                            .is_some_and(|t| HEADINGS.contains(&t.as_str()))
+                                    },
+                                    ElementScope::Default,
+                                ) {
+                                    self.report_error(
+                                        "unexpected-end-tag",
+                                        Some(tag_name.to_string()),
+                                    );
+                                } else {
+                                    self.generate_implied_end_tags(None);
+                                    if self.get_current_elm_name().as_deref() != Some(tag_name) {
+                                        self.report_error(
+                                            "unclosed-elements",
+                                            Some(tag_name.to_string()),
+                                        );
                                     }
+                                    self.pop_until(&HEADINGS);
+                                }
+                            }
+                            "applet" | "marquee" | "object" => {
+                                if self.has_element_in_scope(tag_name) {
+                                    self.close_block_element(tag_name);
+                                    self.clear_active_formatting_elements_to_last_marker();
+                                } else {
+                                    self.report_error(
+                                        "unexpected-end-tag",
+                                        Some(tag_name.to_string()),
+                                    );
                                 }
                             }
-                            _ => unimplemented!("token: {:?}", token),
+                            "br" => {
+                                // The end tag is treated as a `br` start tag.
+                                self.report_error("unexpected-end-tag", Some(tag_name.to_string()));
+                                self.reconstruct_active_formatting_elements();
+                                self.insert_element(tag_name, &[]);
+                                self.stack.pop();
+                                self.frameset_ok = false;
+                            }
+                            _ => self.close_element_for_end_tag(tag_name),
                         },
                         HtmlToken::Eof => {
-                            end_of_parsing = true;
+                            if !self.template_insertion_modes.is_empty() {
+                                rules_mode = Some(InsertionMode::InTemplate);
+                                continue;
+                            }
+                            self.end_of_parsing = true;
                         }
                     },
 
-                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-incdata
-                    InsertionMode::Text => match &token {
-                        HtmlToken::Character(c) => match c {
-                            '\u{0000}' => unreachable!(),
-                            _ => {
-                                self.insert_char_to_token(*c);
-                            }
-                        },
-                        HtmlToken::EndTag { tag_name, .. } if tag_name != "script" => {
-                            let node = self.stack.pop().unwrap();
-
-                            // The user agent must run the update a style block algorithm whenever any of the following conditions occur:
-                            // - The element is popped off the stack of open elements of an HTML parser or XML parser.
-                            // - The element is not on the stack of open elements of an HTML parser or XML parser, and it becomes connected or disconnected.
-                            // - The element's children changed steps run.
-                            // https://html.spec.whatwg.org/multipage/semantics.html#the-style-element
-                            if tag_name == "style" {
-                                self.update_style_block(node, &mut style_sheets)?;
+                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intable
+                    InsertionMode::InTable => match &token {
+                        HtmlToken::Character(_)
+                            if matches!(
+                                self.get_current_elm_name().as_deref(),
+                                Some("table" | "tbody" | "template" | "tfoot" | "thead" | "tr")
+                            ) =>
+                        {
+                            self.pending_table_chars.clear();
+                            self.orig_insertion_mode = Some(self.insertion_mode);
+                            self.insertion_mode = InsertionMode::InTableText;
+                            continue;
+                        }
+                        HtmlToken::Comment(comment) => {
+                            self.insert_comment(comment.clone());
+                        }
+                        HtmlToken::Doctype { .. } => {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::StartTag {
+                            tag_name,
+                            attributes,
+                            ..
+                        } if matches!(
+                            tag_name.as_str(),
+                            "caption"
+                                | "colgroup"
+                                | "col"
+                                | "tbody"
+                                | "tfoot"
+                                | "thead"
+                                | "td"
+                                | "th"
+                                | "tr"
+                        ) =>
+                        {
+                            self.clear_stack_back_to(&["table"]);
+                            match tag_name.as_str() {
+                                "caption" => {
+                                    self.active_formatting_elements
+                                        .push(ActiveFormattingElement::Marker);
+                                    self.insert_element(tag_name, attributes);
+                                    self.insertion_mode = InsertionMode::InCaption;
+                                }
+                                "colgroup" => {
+                                    self.insert_element(tag_name, attributes);
+                                    self.insertion_mode = InsertionMode::InColumnGroup;
+                                }
+                                "col" => {
+                                    self.insert_element("colgroup", &[]);
+                                    self.insertion_mode = InsertionMode::InColumnGroup;
+                                    continue;
+                                }
+                                "tbody" | "tfoot" | "thead" => {
+                                    self.insert_element(tag_name, attributes);
+                                    self.insertion_mode = InsertionMode::InTableBody;
+                                }
+                                _ => {
+                                    self.insert_element("tbody", &[]);
+                                    self.insertion_mode = InsertionMode::InTableBody;
+                                    continue;
+                                }
                             }
-                            self.insertion_mode = self.orig_insertion_mode.unwrap();
                         }
-                        _ => {
-                            unimplemented!("token: {:?}", token);
+                        HtmlToken::StartTag { tag_name, .. } if tag_name == "table" => {
+                            self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                            // The open table is closed, and the new table is inserted after it.
+                            if self.has_element_in_table_scope("table") {
+                                self.pop_until(&["table"]);
+                                self.reset_insertion_mode();
+                                continue;
+                            }
                         }
-                    },
-
-                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-afterbody
-                    InsertionMode::AfterBody => match &token {
-                        HtmlToken::Character(c) => {
-                            if Self::is_blank(*c) {
-                                self.insert_char_to_token(*c);
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "table" => {
+                            if self.has_element_in_table_scope("table") {
+                                self.pop_until(&["table"]);
+                                self.reset_insertion_mode();
+                            } else {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
                             }
                         }
-                        HtmlToken::Doctype { .. } => {
-                            eprintln!("parse error, ignored the token: {:?}", token)
+                        HtmlToken::EndTag { tag_name, .. }
+                            if matches!(
+                                tag_name.as_str(),
+                                "body"
+                                    | "caption"
+                                    | "col"
+                                    | "colgroup"
+                                    | "html"
+                                    | "tbody"
+                                    | "td"
+                                    | "tfoot"
+                                    | "th"
+                                    | "thead"
+                                    | "tr"
+                            ) =>
+                        {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
                         }
-                        HtmlToken::EndTag { tag_name, .. } if tag_name == "html" => {
-                            self.insertion_mode = InsertionMode::AfterAfterBody;
+                        HtmlToken::StartTag { tag_name, .. }
+                            if matches!(tag_name.as_str(), "style" | "script" | "template") =>
+                        {
+                            rules_mode = Some(InsertionMode::InHead);
+                            continue;
+                        }
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "template" => {
+                            rules_mode = Some(InsertionMode::InHead);
+                            continue;
+                        }
+                        HtmlToken::StartTag {
+                            tag_name,
+                            attributes,
+                            ..
+                        } if tag_name == "input"
+                            && attributes.iter().any(|(name, value)| {
+                                name == "type" && value.eq_ignore_ascii_case("hidden")
+                            }) =>
+                        {
+                            // Hidden inputs are not rendered, so they are not moved out of the table.
+                            self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                            self.insert_element(tag_name, attributes);
+                            self.stack.pop();
+                        }
+                        HtmlToken::StartTag {
+                            tag_name,
+                            attributes,
+                            ..
+                        } if tag_name == "form" => {
+                            // The form is inserted as an empty element, and the form-associated elements
+                            // in the table are associated with it through the form element pointer.
+                            self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                            if !self.is_template_on_stack() && self.form_element.is_none() {
+                                self.insert_element(tag_name, attributes);
+                                self.form_element = self.stack.pop();
+                            }
                         }
                         HtmlToken::Eof => {
-                            end_of_parsing = true;
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
                         }
                         _ => {
-                            unimplemented!("token: {:?}", token);
+                            // The misplaced content is moved before the table.
+                            self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                            self.foster_parenting = true;
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
                         }
                     },
 
-                    // https://html.spec.whatwg.org/multipage/parsing.html#the-after-after-body-insertion-mode
-                    InsertionMode::AfterAfterBody => match &token {
-                        HtmlToken::Doctype { .. } => {
-                            eprintln!("parse error, ignored the token: {:?}", token);
-                        }
-                        HtmlToken::Character(c) if Self::is_blank(*c) => {
-                            self.insert_char_to_token(*c);
+                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intabletext
+                    InsertionMode::InTableText => match &token {
+                        HtmlToken::Character('\u{0000}') => {
+                            self.report_error("unexpected-null-character", None);
                         }
-                        HtmlToken::StartTag { tag_name, .. } if tag_name == "html" => {
-                            unimplemented!("token: {:?}", token)
-                        }
-                        HtmlToken::Eof => {
-                            end_of_parsing = true;
+                        HtmlToken::Character(c) => {
+                            self.pending_table_chars.push(*c);
                         }
                         _ => {
-                            eprintln!("parse error");
-                            self.insertion_mode = InsertionMode::InBody;
+                            let chars = std::mem::take(&mut self.pending_table_chars);
+                            if chars.chars().all(Self::is_blank) {
+                                for c in chars.chars() {
+                                    self.insert_char_to_token(c);
+                                }
+                            } else {
+                                // The characters are processed using the rules for the "in body" insertion
+                                // mode with foster parenting, as the "anything else" entry of the "in table"
+                                // insertion mode.
+                                self.report_error("unexpected-character", Some(chars.clone()));
+                                self.foster_parenting = true;
+                                self.reconstruct_active_formatting_elements();
+                                for c in chars.chars() {
+                                    self.insert_char_to_token(c);
+                                }
+                                self.foster_parenting = false;
+                                self.frameset_ok = false;
+                            }
+                            self.insertion_mode = self.orig_insertion_mode.unwrap();
                             continue;
                         }
                     },
 
-                    _ => {
-                        unimplemented!("token: {:?}", token);
-                    }
-                }
+                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-incaption
+                    InsertionMode::InCaption => match &token {
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "caption" => {
+                            if !self.close_caption() {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            }
+                        }
+                        HtmlToken::StartTag { tag_name, .. }
+                            if matches!(
+                                tag_name.as_str(),
+                                "caption"
+                                    | "col"
+                                    | "colgroup"
+                                    | "tbody"
+                                    | "td"
+                                    | "tfoot"
+                                    | "th"
+                                    | "thead"
+                                    | "tr"
+                            ) =>
+                        {
+                            if self.close_caption() {
+                                continue;
+                            }
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "table" => {
+                            if self.close_caption() {
+                                continue;
+                            }
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::EndTag { tag_name, .. }
+                            if matches!(
+                                tag_name.as_str(),
+                                "body"
+                                    | "col"
+                                    | "colgroup"
+                                    | "html"
+                                    | "tbody"
+                                    | "td"
+                                    | "tfoot"
+                                    | "th"
+                                    | "thead"
+                                    | "tr"
+                            ) =>
+                        {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        _ => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                    },
+
+                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-incolgroup
+                    InsertionMode::InColumnGroup => match &token {
+                        HtmlToken::Character(c) if Self::is_blank(*c) => {
+                            self.insert_char_to_token(*c);
+                        }
+                        HtmlToken::Comment(comment) => {
+                            self.insert_comment(comment.clone());
+                        }
+                        HtmlToken::Doctype { .. } => {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::StartTag { tag_name, .. } if tag_name == "html" => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                        HtmlToken::StartTag {
+                            tag_name,
+                            attributes,
+                            ..
+                        } if tag_name == "col" => {
+                            self.insert_element(tag_name, attributes);
+                            self.stack.pop();
+                        }
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "colgroup" => {
+                            if self.get_current_elm_name().as_deref() == Some("colgroup") {
+                                self.stack.pop();
+                                self.insertion_mode = InsertionMode::InTable;
+                            } else {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            }
+                        }
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "col" => {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::StartTag { tag_name, .. }
+                        | HtmlToken::EndTag { tag_name, .. }
+                            if tag_name == "template" =>
+                        {
+                            rules_mode = Some(InsertionMode::InHead);
+                            continue;
+                        }
+                        HtmlToken::Eof => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                        _ => {
+                            if self.get_current_elm_name().as_deref() == Some("colgroup") {
+                                self.stack.pop();
+                                self.insertion_mode = InsertionMode::InTable;
+                                continue;
+                            }
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                    },
+
+                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intbody
+                    InsertionMode::InTableBody => match &token {
+                        HtmlToken::StartTag {
+                            tag_name,
+                            attributes,
+                            ..
+                        } if tag_name == "tr" => {
+                            self.clear_stack_back_to(&["tbody", "tfoot", "thead"]);
+                            self.insert_element(tag_name, attributes);
+                            self.insertion_mode = InsertionMode::InRow;
+                        }
+                        HtmlToken::StartTag { tag_name, .. }
+                            if tag_name == "td" || tag_name == "th" =>
+                        {
+                            self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                            self.clear_stack_back_to(&["tbody", "tfoot", "thead"]);
+                            self.insert_element("tr", &[]);
+                            self.insertion_mode = InsertionMode::InRow;
+                            continue;
+                        }
+                        HtmlToken::EndTag { tag_name, .. }
+                            if matches!(tag_name.as_str(), "tbody" | "tfoot" | "thead") =>
+                        {
+                            if self.has_element_in_table_scope(tag_name) {
+                                self.clear_stack_back_to(&["tbody", "tfoot", "thead"]);
+                                self.stack.pop();
+                                self.insertion_mode = InsertionMode::InTable;
+                            } else {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            }
+                        }
+                        HtmlToken::StartTag { tag_name, .. }
+                            if matches!(
+                                tag_name.as_str(),
+                                "caption" | "col" | "colgroup" | "tbody" | "tfoot" | "thead"
+                            ) =>
+                        {
+                            if self.close_table_body() {
+                                continue;
+                            }
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "table" => {
+                            if self.close_table_body() {
+                                continue;
+                            }
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::EndTag { tag_name, .. }
+                            if matches!(
+                                tag_name.as_str(),
+                                "body"
+                                    | "caption"
+                                    | "col"
+                                    | "colgroup"
+                                    | "html"
+                                    | "td"
+                                    | "th"
+                                    | "tr"
+                            ) =>
+                        {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        _ => {
+                            rules_mode = Some(InsertionMode::InTable);
+                            continue;
+                        }
+                    },
+
+                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intr
+                    InsertionMode::InRow => match &token {
+                        HtmlToken::StartTag {
+                            tag_name,
+                            attributes,
+                            ..
+                        } if tag_name == "td" || tag_name == "th" => {
+                            self.clear_stack_back_to(&["tr"]);
+                            self.insert_element(tag_name, attributes);
+                            self.insertion_mode = InsertionMode::InCell;
+                            self.active_formatting_elements
+                                .push(ActiveFormattingElement::Marker);
+                        }
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "tr" => {
+                            if !self.close_row() {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            }
+                        }
+                        HtmlToken::StartTag { tag_name, .. }
+                            if matches!(
+                                tag_name.as_str(),
+                                "caption" | "col" | "colgroup" | "tbody" | "tfoot" | "thead" | "tr"
+                            ) =>
+                        {
+                            if self.close_row() {
+                                continue;
+                            }
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "table" => {
+                            if self.close_row() {
+                                continue;
+                            }
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::EndTag { tag_name, .. }
+                            if matches!(tag_name.as_str(), "tbody" | "tfoot" | "thead") =>
+                        {
+                            if self.has_element_in_table_scope(tag_name) && self.close_row() {
+                                continue;
+                            }
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::EndTag { tag_name, .. }
+                            if matches!(
+                                tag_name.as_str(),
+                                "body" | "caption" | "col" | "colgroup" | "html" | "td" | "th"
+                            ) =>
+                        {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        _ => {
+                            rules_mode = Some(InsertionMode::InTable);
+                            continue;
+                        }
+                    },
+
+                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intd
+                    InsertionMode::InCell => match &token {
+                        HtmlToken::EndTag { tag_name, .. }
+                            if tag_name == "td" || tag_name == "th" =>
+                        {
+                            if self.has_element_in_table_scope(tag_name) {
+                                self.generate_implied_end_tags(None);
+                                if self.get_current_elm_name().as_deref() != Some(tag_name) {
+                                    self.report_error("unclosed-elements", Some(tag_name.clone()));
+                                }
+                                self.pop_until(&[tag_name]);
+                                self.clear_active_formatting_elements_to_last_marker();
+                                self.insertion_mode = InsertionMode::InRow;
+                            } else {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            }
+                        }
+                        HtmlToken::StartTag { tag_name, .. }
+                            if matches!(
+                                tag_name.as_str(),
+                                "caption"
+                                    | "col"
+                                    | "colgroup"
+                                    | "tbody"
+                                    | "td"
+                                    | "tfoot"
+                                    | "th"
+                                    | "thead"
+                                    | "tr"
+                            ) =>
+                        {
+                            if self.has_element_in_table_scope("td")
+                                || self.has_element_in_table_scope("th")
+                            {
+                                self.close_cell();
+                                continue;
+                            }
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::EndTag { tag_name, .. }
+                            if matches!(
+                                tag_name.as_str(),
+                                "body" | "caption" | "col" | "colgroup" | "html"
+                            ) =>
+                        {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::EndTag { tag_name, .. }
+                            if matches!(
+                                tag_name.as_str(),
+                                "table" | "tbody" | "tfoot" | "thead" | "tr"
+                            ) =>
+                        {
+                            if self.has_element_in_table_scope(tag_name) {
+                                self.close_cell();
+                                continue;
+                            }
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        _ => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                    },
+
+                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intemplate
+                    InsertionMode::InTemplate => match &token {
+                        HtmlToken::Character(_)
+                        | HtmlToken::Comment(_)
+                        | HtmlToken::Doctype { .. } => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                        HtmlToken::StartTag { tag_name, .. } => match tag_name.as_str() {
+                            "base" | "basefont" | "bgsound" | "link" | "meta" | "noframes"
+                            | "script" | "style" | "template" | "title" => {
+                                rules_mode = Some(InsertionMode::InHead);
+                                continue;
+                            }
+                            "caption" | "colgroup" | "tbody" | "tfoot" | "thead" | "col" | "tr"
+                            | "td" | "th" => {
+                                let mode = match tag_name.as_str() {
+                                    "col" => InsertionMode::InColumnGroup,
+                                    "tr" => InsertionMode::InTableBody,
+                                    "td" | "th" => InsertionMode::InRow,
+                                    _ => InsertionMode::InTable,
+                                };
+                                self.template_insertion_modes.pop();
+                                self.template_insertion_modes.push(mode);
+                                self.insertion_mode = mode;
+                                continue;
+                            }
+                            _ => {
+                                self.template_insertion_modes.pop();
+                                self.template_insertion_modes.push(InsertionMode::InBody);
+                                self.insertion_mode = InsertionMode::InBody;
+                                continue;
+                            }
+                        },
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "template" => {
+                            rules_mode = Some(InsertionMode::InHead);
+                            continue;
+                        }
+                        HtmlToken::EndTag { .. } => {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                        HtmlToken::Eof => {
+                            if !self.is_template_on_stack() {
+                                self.end_of_parsing = true;
+                            } else {
+                                self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                                self.pop_until_template();
+                                self.reset_insertion_mode();
+                                continue;
+                            }
+                        }
+                    },
+
+                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-incdata
+                    InsertionMode::Text => match &token {
+                        HtmlToken::Character(c) => match c {
+                            '\u{0000}' => unreachable!(),
+                            _ => {
+                                self.insert_char_to_token(*c);
+                            }
+                        },
+                        HtmlToken::EndTag { tag_name, .. } if tag_name != "script" => {
+                            let node = self.stack.pop().unwrap();
+
+                            // The user agent must run the update a style block algorithm whenever any of the following conditions occur:
+                            // - The element is popped off the stack of open elements of an HTML parser or XML parser.
+                            // - The element is not on the stack of open elements of an HTML parser or XML parser, and it becomes connected or disconnected.
+                            // - The element's children changed steps run.
+                            // https://html.spec.whatwg.org/multipage/semantics.html#the-style-element
+                            if tag_name == "style" {
+                                self.update_style_block(node)?;
+                            }
+                            self.insertion_mode = self.orig_insertion_mode.unwrap();
+                        }
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "script" => {
+                            // Scripts are not executed, so the script element is just popped off the stack.
+                            self.stack.pop();
+                            self.insertion_mode = self.orig_insertion_mode.unwrap();
+                        }
+                        HtmlToken::Eof => {
+                            self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                            self.stack.pop();
+                            self.insertion_mode = self.orig_insertion_mode.unwrap();
+                            continue;
+                        }
+                        _ => {
+                            unimplemented!("token: {:?}", token);
+                        }
+                    },
+
+                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-afterbody
+                    InsertionMode::AfterBody => match &token {
+                        // The body element is still open, so whitespace after `</body>` ends up in the body element.
+                        HtmlToken::Character(c) if Self::is_blank(*c) => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                        HtmlToken::Comment(comment) => {
+                            // The comment is inserted as the last child of the html element.
+                            let html = Rc::clone(self.stack.first().unwrap());
+                            self.insert_comment_at(comment.clone(), &html);
+                        }
+                        HtmlToken::Doctype { .. } => {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)))
+                        }
+                        HtmlToken::StartTag { tag_name, .. } if tag_name == "html" => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "html" => {
+                            if self.context_element.is_some() {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            } else {
+                                self.insertion_mode = InsertionMode::AfterAfterBody;
+                            }
+                        }
+                        HtmlToken::Eof => {
+                            self.end_of_parsing = true;
+                        }
+                        _ => {
+                            self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                            self.insertion_mode = InsertionMode::InBody;
+                            continue;
+                        }
+                    },
+
+                    // https://html.spec.whatwg.org/multipage/parsing.html#the-after-after-body-insertion-mode
+                    InsertionMode::AfterAfterBody => match &token {
+                        HtmlToken::Comment(comment) => {
+                            // The comment is inserted as the last child of the Document object.
+                            self.insert_comment_at(comment.clone(), &document_node);
+                        }
+                        HtmlToken::Doctype { .. } => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                        HtmlToken::Character(c) if Self::is_blank(*c) => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                        HtmlToken::StartTag { tag_name, .. } if tag_name == "html" => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                        HtmlToken::Eof => {
+                            self.end_of_parsing = true;
+                        }
+                        _ => {
+                            self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                            self.insertion_mode = InsertionMode::InBody;
+                            continue;
+                        }
+                    },
+
+                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inframeset
+                    InsertionMode::InFrameset => match &token {
+                        HtmlToken::Character(c) if Self::is_blank(*c) => {
+                            self.insert_char_to_token(*c);
+                        }
+                        HtmlToken::Comment(comment) => {
+                            self.insert_comment(comment.clone());
+                        }
+                        HtmlToken::StartTag {
+                            tag_name,
+                            attributes,
+                            ..
+                        } => match tag_name.as_str() {
+                            "html" => {
+                                rules_mode = Some(InsertionMode::InBody);
+                                continue;
+                            }
+                            "frameset" => {
+                                self.insert_element(tag_name, attributes);
+                            }
+                            "frame" => {
+                                self.insert_element(tag_name, attributes);
+                                self.stack.pop();
+                            }
+                            "noframes" => {
+                                rules_mode = Some(InsertionMode::InHead);
+                                continue;
+                            }
+                            _ => {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            }
+                        },
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "frameset" => {
+                            // The root html element can't be popped off in the fragment case.
+                            if self.stack.len() == 1 {
+                                self.report_error("ignored-token", Some(format!("{:?}", token)));
+                            } else {
+                                self.stack.pop();
+                                if self.context_element.is_none()
+                                    && self.get_current_elm_name().as_deref() != Some("frameset")
+                                {
+                                    self.insertion_mode = InsertionMode::AfterFrameset;
+                                }
+                            }
+                        }
+                        HtmlToken::Eof => {
+                            if self.stack.len() != 1 {
+                                self.report_error("unexpected-token", Some(format!("{:?}", token)));
+                            }
+                            self.end_of_parsing = true;
+                        }
+                        _ => {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                    },
+
+                    // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-afterframeset
+                    InsertionMode::AfterFrameset => match &token {
+                        HtmlToken::Character(c) if Self::is_blank(*c) => {
+                            self.insert_char_to_token(*c);
+                        }
+                        HtmlToken::Comment(comment) => {
+                            self.insert_comment(comment.clone());
+                        }
+                        HtmlToken::StartTag { tag_name, .. } if tag_name == "html" => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                        HtmlToken::StartTag { tag_name, .. } if tag_name == "noframes" => {
+                            rules_mode = Some(InsertionMode::InHead);
+                            continue;
+                        }
+                        HtmlToken::EndTag { tag_name, .. } if tag_name == "html" => {
+                            self.insertion_mode = InsertionMode::AfterAfterFrameset;
+                        }
+                        HtmlToken::Eof => {
+                            self.end_of_parsing = true;
+                        }
+                        _ => {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                    },
+
+                    // https://html.spec.whatwg.org/multipage/parsing.html#the-after-after-frameset-insertion-mode
+                    InsertionMode::AfterAfterFrameset => match &token {
+                        HtmlToken::Comment(comment) => {
+                            self.insert_comment_at(comment.clone(), &document_node);
+                        }
+                        HtmlToken::Doctype { .. } => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                        HtmlToken::Character(c) if Self::is_blank(*c) => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                        HtmlToken::StartTag { tag_name, .. } if tag_name == "html" => {
+                            rules_mode = Some(InsertionMode::InBody);
+                            continue;
+                        }
+                        HtmlToken::StartTag { tag_name, .. } if tag_name == "noframes" => {
+                            rules_mode = Some(InsertionMode::InHead);
+                            continue;
+                        }
+                        HtmlToken::Eof => {
+                            self.end_of_parsing = true;
+                        }
+                        _ => {
+                            self.report_error("ignored-token", Some(format!("{:?}", token)));
+                        }
+                    },
+                }
+
+                break;
+            }
+            self.foster_parenting = false;
+        }
+
+        Ok(())
+    }
+
+    /// Determines the document mode from the DOCTYPE token.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode
+    fn determine_document_mode(
+        name: Option<&str>,
+        public_identifier: Option<&str>,
+        system_identifier: Option<&str>,
+        force_quirks: bool,
+    ) -> DocumentMode {
+        const QUIRKS_PUBLIC_IDS: [&str; 3] = [
+            "-//W3O//DTD W3 HTML Strict 3.0//EN//",
+            "-/W3C/DTD HTML 4.0 Transitional/EN",
+            "HTML",
+        ];
+        const QUIRKS_PUBLIC_ID_PREFIXES: [&str; 55] = [
+            "+//Silmaril//dtd html Pro v0r11 19970101//",
+            "-//AS//DTD HTML 3.0 asWedit + extensions//",
+            "-//AdvaSoft Ltd//DTD HTML 3.0 asWedit + extensions//",
+            "-//IETF//DTD HTML 2.0 Level 1//",
+            "-//IETF//DTD HTML 2.0 Level 2//",
+            "-//IETF//DTD HTML 2.0 Strict Level 1//",
+            "-//IETF//DTD HTML 2.0 Strict Level 2//",
+            "-//IETF//DTD HTML 2.0 Strict//",
+            "-//IETF//DTD HTML 2.0//",
+            "-//IETF//DTD HTML 2.1E//",
+            "-//IETF//DTD HTML 3.0//",
+            "-//IETF//DTD HTML 3.2 Final//",
+            "-//IETF//DTD HTML 3.2//",
+            "-//IETF//DTD HTML 3//",
+            "-//IETF//DTD HTML Level 0//",
+            "-//IETF//DTD HTML Level 1//",
+            "-//IETF//DTD HTML Level 2//",
+            "-//IETF//DTD HTML Level 3//",
+            "-//IETF//DTD HTML Strict Level 0//",
+            "-//IETF//DTD HTML Strict Level 1//",
+            "-//IETF//DTD HTML Strict Level 2//",
+            "-//IETF//DTD HTML Strict Level 3//",
+            "-//IETF//DTD HTML Strict//",
+            "-//IETF//DTD HTML//",
+            "-//Metrius//DTD Metrius Presentational//",
+            "-//Microsoft//DTD Internet Explorer 2.0 HTML Strict//",
+            "-//Microsoft//DTD Internet Explorer 2.0 HTML//",
+            "-//Microsoft//DTD Internet Explorer 2.0 Tables//",
+            "-//Microsoft//DTD Internet Explorer 3.0 HTML Strict//",
+            "-//Microsoft//DTD Internet Explorer 3.0 HTML//",
+            "-//Microsoft//DTD Internet Explorer 3.0 Tables//",
+            "-//Netscape Comm. Corp.//DTD HTML//",
+            "-//Netscape Comm. Corp.//DTD Strict HTML//",
+            "-//O'Reilly and Associates//DTD HTML 2.0//",
+            "-//O'Reilly and Associates//DTD HTML Extended 1.0//",
+            "-//O'Reilly and Associates//DTD HTML Extended Relaxed 1.0//",
+            "-//SQ//DTD HTML 2.0 HoTMetaL + extensions//",
+            "-//SoftQuad Software//DTD HoTMetaL PRO 6.0::19990601::extensions to HTML 4.0//",
+            "-//SoftQuad//DTD HoTMetaL PRO 4.0::19971010::extensions to HTML 4.0//",
+            "-//Spyglass//DTD HTML 2.0 Extended//",
+            "-//Sun Microsystems Corp.//DTD HotJava HTML//",
+            "-//Sun Microsystems Corp.//DTD HotJava Strict HTML//",
+            "-//W3C//DTD HTML 3 1995-03-24//",
+            "-//W3C//DTD HTML 3.2 Draft//",
+            "-//W3C//DTD HTML 3.2 Final//",
+            "-//W3C//DTD HTML 3.2//",
+            "-//W3C//DTD HTML 3.2S Draft//",
+            "-//W3C//DTD HTML 4.0 Frameset//",
+            "-//W3C//DTD HTML 4.0 Transitional//",
+            "-//W3C//DTD HTML Experimental 19960712//",
+            "-//W3C//DTD HTML Experimental 970421//",
+            "-//W3C//DTD W3 HTML//",
+            "-//W3O//DTD W3 HTML 3.0//",
+            "-//WebTechs//DTD Mozilla HTML 2.0//",
+            "-//WebTechs//DTD Mozilla HTML//",
+        ];
+        const HTML401_PUBLIC_ID_PREFIXES: [&str; 2] = [
+            "-//W3C//DTD HTML 4.01 Frameset//",
+            "-//W3C//DTD HTML 4.01 Transitional//",
+        ];
+        const LIMITED_QUIRKS_PUBLIC_ID_PREFIXES: [&str; 2] = [
+            "-//W3C//DTD XHTML 1.0 Frameset//",
+            "-//W3C//DTD XHTML 1.0 Transitional//",
+        ];
+
+        // The identifiers are compared in an ASCII case-insensitive manner.
+        let public_id = public_identifier.map(|id| id.to_ascii_lowercase());
+        let system_id = system_identifier.map(|id| id.to_ascii_lowercase());
+        let public_id_is = |ids: &[&str]| {
+            public_id
+                .as_deref()
+                .is_some_and(|p| ids.iter().any(|id| p == id.to_ascii_lowercase()))
+        };
+        let public_id_starts_with = |prefixes: &[&str]| {
+            public_id.as_deref().is_some_and(|p| {
+                prefixes
+                    .iter()
+                    .any(|prefix| p.starts_with(&prefix.to_ascii_lowercase()))
+            })
+        };
+
+        if force_quirks
+            || name != Some("html")
+            || public_id_is(&QUIRKS_PUBLIC_IDS)
+            || system_id.as_deref()
+                == Some("http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd")
+            || public_id_starts_with(&QUIRKS_PUBLIC_ID_PREFIXES)
+            || (system_id.is_none() && public_id_starts_with(&HTML401_PUBLIC_ID_PREFIXES))
+        {
+            DocumentMode::Quirks
+        } else if public_id_starts_with(&LIMITED_QUIRKS_PUBLIC_ID_PREFIXES)
+            || (system_id.is_some() && public_id_starts_with(&HTML401_PUBLIC_ID_PREFIXES))
+        {
+            DocumentMode::LimitedQuirks
+        } else {
+            DocumentMode::NoQuirks
+        }
+    }
+
+    /// Reports a tree construction error at the end of the current token. The specification doesn't define
+    /// codes for these errors, so the codes here are descriptive ones.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+    fn report_error(&mut self, code: &str, detail: Option<String>) {
+        self.tokenizer.report_error_with_detail(code, detail);
+    }
+
+    fn is_blank(c: char) -> bool {
+        matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' ')
+    }
+
+    /// Returns the index of the `svg` or `math` element in the stack of open elements if the current node
+    /// is in its subtree and the subtree is not an HTML or MathML text integration point, whose content
+    /// is parsed as HTML.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#html-integration-point
+    fn get_foreign_root_index(&self) -> Option<usize> {
+        let i = self.stack.iter().rposition(|node| {
+            matches!(Self::get_tag_name(node).as_deref(), Some("svg" | "math"))
+        })?;
+        let is_integrated = self.stack[i + 1..].iter().any(|node| {
+            matches!(
+                Self::get_tag_name(node).as_deref(),
+                Some("foreignobject" | "desc" | "title" | "mi" | "mo" | "mn" | "ms" | "mtext")
+            )
+        });
+        (!is_integrated).then_some(i)
+    }
+
+    /// Processes the start and end tags in the SVG or MathML subtree, and returns whether the token is
+    /// processed. This follows the rules for foreign content only partially, so that the self-closing
+    /// elements don't contain the following elements, and the elements of HTML break out of the subtree.
+    /// todo: Create the elements in the SVG and MathML namespaces, and adjust the cases of the names.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inforeign
+    fn process_token_in_foreign_content(&mut self, token: &HtmlToken) -> bool {
+        let Some(root_index) = self.get_foreign_root_index() else {
+            return false;
+        };
+        match token {
+            HtmlToken::StartTag {
+                tag_name,
+                attributes,
+                self_closing,
+            } => {
+                let is_html = matches!(
+                    tag_name.as_str(),
+                    "b" | "big"
+                        | "blockquote"
+                        | "body"
+                        | "br"
+                        | "center"
+                        | "code"
+                        | "dd"
+                        | "div"
+                        | "dl"
+                        | "dt"
+                        | "em"
+                        | "embed"
+                        | "h1"
+                        | "h2"
+                        | "h3"
+                        | "h4"
+                        | "h5"
+                        | "h6"
+                        | "head"
+                        | "hr"
+                        | "i"
+                        | "img"
+                        | "li"
+                        | "listing"
+                        | "menu"
+                        | "meta"
+                        | "nobr"
+                        | "ol"
+                        | "p"
+                        | "pre"
+                        | "ruby"
+                        | "s"
+                        | "small"
+                        | "span"
+                        | "strong"
+                        | "strike"
+                        | "sub"
+                        | "sup"
+                        | "table"
+                        | "tt"
+                        | "u"
+                        | "ul"
+                        | "var"
+                ) || (tag_name == "font"
+                    && attributes
+                        .iter()
+                        .any(|(name, _)| matches!(name.as_str(), "color" | "face" | "size")));
+                if is_html {
+                    self.report_error(
+                        "unexpected-html-element-in-foreign-content",
+                        Some(format!("{:?}", token)),
+                    );
+                    self.stack.truncate(root_index);
+                    return false;
+                }
+                self.insert_element(tag_name, attributes);
+                if *self_closing {
+                    self.stack.pop();
+                }
+                true
+            }
+            HtmlToken::EndTag { tag_name, .. } => {
+                let Some(i) = self.stack[root_index..].iter().rposition(|node| {
+                    Self::get_tag_name(node).is_some_and(|name| name.eq_ignore_ascii_case(tag_name))
+                }) else {
+                    return false;
+                };
+                self.stack.truncate(root_index + i);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the tag name of the current element, if the current node is an element.
+    fn get_current_elm_name(&self) -> Option<String> {
+        self.stack.last().and_then(Self::get_tag_name)
+    }
+
+    /// Returns the tag name of the node, if the node is an element.
+    fn get_tag_name(node: &Rc<RefCell<DomNode>>) -> Option<String> {
+        let NodeType::Element(elm) = &node.borrow().node_type else {
+            return None;
+        };
+        Some(elm.tag_name.clone())
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#special
+    fn is_special_tag(tag_name: &str) -> bool {
+        matches!(
+            tag_name,
+            "address"
+                | "applet"
+                | "area"
+                | "article"
+                | "aside"
+                | "base"
+                | "basefont"
+                | "bgsound"
+                | "blockquote"
+                | "body"
+                | "br"
+                | "button"
+                | "caption"
+                | "center"
+                | "col"
+                | "colgroup"
+                | "dd"
+                | "details"
+                | "dir"
+                | "div"
+                | "dl"
+                | "dt"
+                | "embed"
+                | "fieldset"
+                | "figcaption"
+                | "figure"
+                | "footer"
+                | "form"
+                | "frame"
+                | "frameset"
+                | "h1"
+                | "h2"
+                | "h3"
+                | "h4"
+                | "h5"
+                | "h6"
+                | "head"
+                | "header"
+                | "hgroup"
+                | "hr"
+                | "html"
+                | "iframe"
+                | "img"
+                | "input"
+                | "keygen"
+                | "li"
+                | "link"
+                | "listing"
+                | "main"
+                | "marquee"
+                | "menu"
+                | "meta"
+                | "nav"
+                | "noembed"
+                | "noframes"
+                | "noscript"
+                | "object"
+                | "ol"
+                | "p"
+                | "param"
+                | "plaintext"
+                | "pre"
+                | "script"
+                | "search"
+                | "section"
+                | "select"
+                | "source"
+                | "style"
+                | "summary"
+                | "table"
+                | "tbody"
+                | "td"
+                | "template"
+                | "textarea"
+                | "tfoot"
+                | "th"
+                | "thead"
+                | "title"
+                | "tr"
+                | "track"
+                | "ul"
+                | "wbr"
+                | "xmp"
+        )
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-scope
+    fn has_element_in_scope(&self, tag_name: &str) -> bool {
+        self.has_node_in_scope(
+            |node| Self::get_tag_name(node).as_deref() == Some(tag_name),
+            ElementScope::Default,
+        )
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-list-item-scope
+    fn has_element_in_list_item_scope(&self, tag_name: &str) -> bool {
+        self.has_node_in_scope(
+            |node| Self::get_tag_name(node).as_deref() == Some(tag_name),
+            ElementScope::ListItem,
+        )
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-button-scope
+    fn has_element_in_button_scope(&self, tag_name: &str) -> bool {
+        self.has_node_in_scope(
+            |node| Self::get_tag_name(node).as_deref() == Some(tag_name),
+            ElementScope::Button,
+        )
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-table-scope
+    fn has_element_in_table_scope(&self, tag_name: &str) -> bool {
+        self.has_node_in_scope(
+            |node| Self::get_tag_name(node).as_deref() == Some(tag_name),
+            ElementScope::Table,
+        )
+    }
+
+    /// Returns whether a node satisfying `is_target` is in the stack of open elements before any of the boundaries
+    /// of the scope.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#has-an-element-in-the-specific-scope
+    fn has_node_in_scope(
+        &self,
+        is_target: impl Fn(&Rc<RefCell<DomNode>>) -> bool,
+        scope: ElementScope,
+    ) -> bool {
+        for node in self.stack.iter().rev() {
+            if is_target(node) {
+                return true;
+            }
+            if Self::get_tag_name(node).is_some_and(|t| scope.is_boundary(&t)) {
+                return false;
+            }
+        }
+        false
+    }
+
+    /// Pops elements from the stack of open elements until an element with one of the tag names has been popped.
+    fn pop_until(&mut self, tag_names: &[&str]) {
+        while let Some(n) = self.stack.pop() {
+            if Self::get_tag_name(&n).is_some_and(|t| tag_names.contains(&t.as_str())) {
+                break;
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#close-a-p-element
+    fn close_p_element(&mut self) {
+        self.generate_implied_end_tags(Some("p"));
+        if self.get_current_elm_name().as_deref() != Some("p") {
+            self.report_error("unclosed-elements", Some("p".to_string()));
+        }
+        self.pop_until(&["p"]);
+    }
+
+    /// Closes the open paragraph before a block-level element is inserted, since paragraphs can't contain them.
+    /// Paragraphs outside the current button are left open.
+    fn close_p_element_in_button_scope(&mut self) {
+        if self.has_element_in_button_scope("p") {
+            self.close_p_element();
+        }
+    }
+
+    /// Processes the end tag of an element which is closed together with the elements in it, such as `div`.
+    /// The end tag is ignored if the element is not in scope.
+    fn close_block_element(&mut self, tag_name: &str) {
+        if !self.has_element_in_scope(tag_name) {
+            self.report_error("unexpected-end-tag", Some(tag_name.to_string()));
+            return;
+        }
+        self.generate_implied_end_tags(None);
+        if self.get_current_elm_name().as_deref() != Some(tag_name) {
+            self.report_error("unclosed-elements", Some(tag_name.to_string()));
+        }
+        self.pop_until(&[tag_name]);
+    }
+
+    /// Pops elements until the current node is an element with one of the tag names, a `template` element or
+    /// the `html` element.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-stack-back-to-a-table-context
+    fn clear_stack_back_to(&mut self, tag_names: &[&str]) {
+        while let Some(name) = self.get_current_elm_name() {
+            if tag_names.contains(&name.as_str()) || name == "template" || name == "html" {
+                break;
+            }
+            self.stack.pop();
+        }
+    }
+
+    /// Closes the open caption and switches to the "in table" insertion mode. Returns `false` if no
+    /// caption is in table scope.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-incaption
+    fn close_caption(&mut self) -> bool {
+        if !self.has_element_in_table_scope("caption") {
+            return false;
+        }
+        self.generate_implied_end_tags(None);
+        if self.get_current_elm_name().as_deref() != Some("caption") {
+            self.report_error("unclosed-elements", Some("caption".to_string()));
+        }
+        self.pop_until(&["caption"]);
+        self.clear_active_formatting_elements_to_last_marker();
+        self.insertion_mode = InsertionMode::InTable;
+        true
+    }
+
+    /// Closes the open table section and switches to the "in table" insertion mode. Returns `false` if no
+    /// table section is in table scope.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intbody
+    fn close_table_body(&mut self) -> bool {
+        if !["tbody", "thead", "tfoot"]
+            .iter()
+            .any(|t| self.has_element_in_table_scope(t))
+        {
+            return false;
+        }
+        self.clear_stack_back_to(&["tbody", "tfoot", "thead"]);
+        self.stack.pop();
+        self.insertion_mode = InsertionMode::InTable;
+        true
+    }
+
+    /// Closes the open row and switches to the "in table body" insertion mode. Returns `false` if no row is
+    /// in table scope.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-intr
+    fn close_row(&mut self) -> bool {
+        if !self.has_element_in_table_scope("tr") {
+            return false;
+        }
+        self.clear_stack_back_to(&["tr"]);
+        self.stack.pop();
+        self.insertion_mode = InsertionMode::InTableBody;
+        true
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#close-the-cell
+    fn close_cell(&mut self) {
+        self.generate_implied_end_tags(None);
+        if !matches!(self.get_current_elm_name().as_deref(), Some("td" | "th")) {
+            self.report_error("unclosed-elements", Some("td".to_string()));
+        }
+        self.pop_until(&["td", "th"]);
+        self.clear_active_formatting_elements_to_last_marker();
+        self.insertion_mode = InsertionMode::InRow;
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generate-implied-end-tags
+    fn generate_implied_end_tags(&mut self, excluded_tag: Option<&str>) {
+        let mut tag_lists = vec![
+            "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc",
+        ];
+        if let Some(excluded_tag) = excluded_tag {
+            tag_lists.retain(|&x| x != excluded_tag);
+        }
+        loop {
+            let current_node_type = self.get_current_elm_name().unwrap();
+            if tag_lists.contains(&current_node_type.as_str()) {
+                self.stack.pop();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-an-html-element
+    fn insert_element(&mut self, tag_name: &str, attributes: &[(String, String)]) {
+        let node_type = NodeType::Element(Element {
+            tag_name: tag_name.to_owned(),
+            attributes: attributes.to_owned(),
+        });
+        // Implied elements have no corresponding source text.
+        let node = if self.current_start_tag.as_deref() == Some(tag_name) {
+            self.create_node_for_token(node_type)
+        } else {
+            DomNode::new(node_type)
+        };
+        let location = self.get_appropriate_insertion_place(None);
+        let new_node = location.insert(Rc::new(RefCell::new(node)));
+        self.associate_with_form(&new_node, &location.parent);
+        self.stack.push(Rc::clone(&new_node));
+    }
+
+    /// Associates the inserted form-associated element with the form in the form element pointer. The form
+    /// owner is reset as with the elements inserted by scripts if the pointer is not used, e.g. when the
+    /// element has the `form` attribute.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#create-an-element-for-the-token
+    fn associate_with_form(
+        &self,
+        node: &Rc<RefCell<DomNode>>,
+        intersecting_parent: &Rc<RefCell<DomNode>>,
+    ) {
+        let has_form_attr = match &node.borrow().node_type {
+            NodeType::Element(elm) if elm.is_form_associated() => {
+                elm.is_listed() && elm.get_attribute("form").is_some()
+            }
+            _ => return,
+        };
+        match &self.form_element {
+            Some(form)
+                if !has_form_attr
+                    && !self.is_template_on_stack()
+                    && Rc::ptr_eq(
+                        &DomNode::get_root(form),
+                        &DomNode::get_root(intersecting_parent),
+                    ) =>
+            {
+                node.borrow_mut().form_owner = Some(Rc::downgrade(form));
+            }
+            _ => DomNode::reset_form_owner(node),
+        }
+    }
+
+    /// Pushes the current node onto the list of active formatting elements.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#push-onto-the-list-of-active-formatting-elements
+    fn push_active_formatting_element(&mut self) {
+        let element = Rc::clone(self.stack.last().unwrap());
+        let is_same_element = |node: &Rc<RefCell<DomNode>>| {
+            let (NodeType::Element(a), NodeType::Element(b)) =
+                (&node.borrow().node_type, &element.borrow().node_type)
+            else {
+                return false;
+            };
+            a.tag_name == b.tag_name
+                && a.attributes.len() == b.attributes.len()
+                && a.attributes.iter().all(|attr| b.attributes.contains(attr))
+        };
+
+        // If there are already three elements with the same tag name and attributes after the last marker,
+        // remove the earliest one (a.k.a. the Noah's Ark clause).
+        let last_marker_end = self
+            .active_formatting_elements
+            .iter()
+            .rposition(|e| matches!(e, ActiveFormattingElement::Marker))
+            .map_or(0, |i| i + 1);
+        let same_elements = self
+            .active_formatting_elements
+            .iter()
+            .enumerate()
+            .skip(last_marker_end)
+            .filter(|(_, e)| matches!(e, ActiveFormattingElement::Element(n) if is_same_element(n)))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if same_elements.len() >= 3 {
+            self.active_formatting_elements.remove(same_elements[0]);
+        }
+
+        self.active_formatting_elements
+            .push(ActiveFormattingElement::Element(element));
+    }
+
+    /// Reopens the formatting elements that were implicitly closed, e.g. `b` in `<b><p>a</b>b`.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#reconstruct-the-active-formatting-elements
+    fn reconstruct_active_formatting_elements(&mut self) {
+        // Reopen the entries after the last marker or the last entry that is still open.
+        let start = self
+            .active_formatting_elements
+            .iter()
+            .rposition(|entry| match entry {
+                ActiveFormattingElement::Marker => true,
+                ActiveFormattingElement::Element(node) => {
+                    self.stack.iter().any(|n| Rc::ptr_eq(n, node))
+                }
+            })
+            .map_or(0, |i| i + 1);
+        for i in start..self.active_formatting_elements.len() {
+            let ActiveFormattingElement::Element(node) = &self.active_formatting_elements[i] else {
+                unreachable!();
+            };
+            let NodeType::Element(elm) = node.borrow().node_type.clone() else {
+                unreachable!();
+            };
+            self.insert_element(&elm.tag_name, &elm.attributes);
+            self.active_formatting_elements[i] =
+                ActiveFormattingElement::Element(Rc::clone(self.stack.last().unwrap()));
+        }
+    }
+
+    /// Returns the index of the last element with the tag name between the end of the list of active formatting elements
+    /// and the last marker in the list, if any.
+    fn find_active_formatting_element(&self, tag_name: &str) -> Option<usize> {
+        for (i, entry) in self.active_formatting_elements.iter().enumerate().rev() {
+            match entry {
+                ActiveFormattingElement::Marker => return None,
+                ActiveFormattingElement::Element(node)
+                    if Self::get_tag_name(node).as_deref() == Some(tag_name) =>
+                {
+                    return Some(i);
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#clear-the-list-of-active-formatting-elements-up-to-the-last-marker
+    fn clear_active_formatting_elements_to_last_marker(&mut self) {
+        while let Some(entry) = self.active_formatting_elements.pop() {
+            if let ActiveFormattingElement::Marker = entry {
+                break;
+            }
+        }
+    }
+
+    /// Fixes up the misnested formatting elements, e.g. `<b>1<p>2</b>3</p>` is parsed as `<b>1</b><p><b>2</b>3</p>`.
+    /// Returns `false` if the end tag should be processed as "any other end tag" instead.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#adoption-agency-algorithm
+    fn run_adoption_agency(&mut self, subject: &str) -> bool {
+        // If the current node is not a formatting element, there is nothing to adopt.
+        if let Some(current_node) = self.stack.last() {
+            if Self::get_tag_name(current_node).as_deref() == Some(subject)
+                && !self
+                    .active_formatting_elements
+                    .iter()
+                    .any(|e| e.is(current_node))
+            {
+                self.stack.pop();
+                return true;
+            }
+        }
+
+        // Outer loop
+        for _ in 0..8 {
+            let Some(formatting_elm_list_idx) = self.find_active_formatting_element(subject) else {
+                return false;
+            };
+            let ActiveFormattingElement::Element(formatting_elm) =
+                self.active_formatting_elements[formatting_elm_list_idx].clone()
+            else {
+                unreachable!();
+            };
+
+            let Some(formatting_elm_stack_idx) = self
+                .stack
+                .iter()
+                .position(|n| Rc::ptr_eq(n, &formatting_elm))
+            else {
+                self.report_error("misnested-formatting-element", Some(subject.to_string()));
+                self.active_formatting_elements
+                    .remove(formatting_elm_list_idx);
+                return true;
+            };
+            if !self.has_node_in_scope(|n| Rc::ptr_eq(n, &formatting_elm), ElementScope::Default) {
+                self.report_error("misnested-formatting-element", Some(subject.to_string()));
+                return true;
+            }
+            if formatting_elm_stack_idx != self.stack.len() - 1 {
+                self.report_error("misnested-formatting-element", Some(subject.to_string()));
+            }
+
+            // The furthest block is the topmost special element below the formatting element in the stack.
+            let Some(furthest_block_idx) =
+                (formatting_elm_stack_idx + 1..self.stack.len()).find(|&i| {
+                    Self::get_tag_name(&self.stack[i]).is_some_and(|t| Self::is_special_tag(&t))
+                })
+            else {
+                self.stack.truncate(formatting_elm_stack_idx);
+                self.active_formatting_elements
+                    .remove(formatting_elm_list_idx);
+                return true;
+            };
+            let furthest_block = Rc::clone(&self.stack[furthest_block_idx]);
+            let common_ancestor = Rc::clone(&self.stack[formatting_elm_stack_idx - 1]);
+            let mut bookmark = formatting_elm_list_idx;
+            let mut node_idx = furthest_block_idx;
+            let mut last_node = Rc::clone(&furthest_block);
+
+            // Inner loop
+            let mut inner_loop_counter = 0;
+            loop {
+                inner_loop_counter += 1;
+                node_idx -= 1;
+                let node = Rc::clone(&self.stack[node_idx]);
+                if Rc::ptr_eq(&node, &formatting_elm) {
+                    break;
+                }
+
+                let mut node_list_idx = self
+                    .active_formatting_elements
+                    .iter()
+                    .position(|e| e.is(&node));
+                if inner_loop_counter > 3 {
+                    if let Some(i) = node_list_idx.take() {
+                        self.active_formatting_elements.remove(i);
+                        if i < bookmark {
+                            bookmark -= 1;
+                        }
+                    }
+                }
+                let Some(node_list_idx) = node_list_idx else {
+                    self.stack.remove(node_idx);
+                    continue;
+                };
+
+                // Replace the node with a new element created for the same token.
+                let new_node = Rc::new(RefCell::new(DomNode::new(node.borrow().node_type.clone())));
+                self.active_formatting_elements[node_list_idx] =
+                    ActiveFormattingElement::Element(Rc::clone(&new_node));
+                self.stack[node_idx] = Rc::clone(&new_node);
+                if Rc::ptr_eq(&last_node, &furthest_block) {
+                    bookmark = node_list_idx + 1;
+                }
+                DomNode::append_child_node(&new_node, last_node);
+                last_node = new_node;
+            }
+
+            self.get_appropriate_insertion_place(Some(&common_ancestor))
+                .insert(last_node);
+
+            // Move the children of the furthest block into a new element created for the formatting element's token.
+            let new_elm = Rc::new(RefCell::new(DomNode::new(
+                formatting_elm.borrow().node_type.clone(),
+            )));
+            let children = furthest_block.borrow().children.clone();
+            for child in children {
+                DomNode::append_child_node(&new_elm, child);
+            }
+            DomNode::append_child_node(&furthest_block, Rc::clone(&new_elm));
+
+            let formatting_elm_list_idx = self
+                .active_formatting_elements
+                .iter()
+                .position(|e| e.is(&formatting_elm))
+                .unwrap();
+            self.active_formatting_elements
+                .remove(formatting_elm_list_idx);
+            if formatting_elm_list_idx < bookmark {
+                bookmark -= 1;
+            }
+            self.active_formatting_elements.insert(
+                bookmark,
+                ActiveFormattingElement::Element(Rc::clone(&new_elm)),
+            );
+
+            self.stack.retain(|n| !Rc::ptr_eq(n, &formatting_elm));
+            let furthest_block_idx = self
+                .stack
+                .iter()
+                .position(|n| Rc::ptr_eq(n, &furthest_block))
+                .unwrap();
+            self.stack.insert(furthest_block_idx + 1, new_elm);
+        }
+
+        true
+    }
+
+    /// Closes the element for the end tag, following the "any other end tag" steps in the "in body" insertion mode.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+    fn close_element_for_end_tag(&mut self, tag_name: &str) {
+        for node in self.stack.clone().iter().rev() {
+            let node_tag_name = Self::get_tag_name(node);
+            if node_tag_name.as_deref() == Some(tag_name) {
+                self.generate_implied_end_tags(Some(tag_name));
+                if !Rc::ptr_eq(self.stack.last().unwrap(), node) {
+                    self.report_error("unclosed-elements", Some(tag_name.to_string()));
+                }
+                while let Some(n) = self.stack.pop() {
+                    if Rc::ptr_eq(&n, node) {
+                        break;
+                    }
+                }
+                return;
+            }
+            if node_tag_name.is_some_and(|t| Self::is_special_tag(&t)) {
+                self.report_error("unexpected-end-tag", Some(tag_name.to_string()));
+                return;
+            }
+        }
+    }
+
+    /// Returns the location at which a new node is inserted. The target is the current node unless `override_target`
+    /// is given. If foster parenting is enabled, nodes targeted at table elements are moved before the table, and nodes
+    /// targeted at `template` elements are inserted into their template contents.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-place-for-inserting-a-node
+    fn get_appropriate_insertion_place(
+        &self,
+        override_target: Option<&Rc<RefCell<DomNode>>>,
+    ) -> InsertionLocation {
+        let target = override_target.unwrap_or_else(|| self.stack.last().unwrap());
+        let is_table_target = matches!(
+            Self::get_tag_name(target).as_deref(),
+            Some("table" | "tbody" | "tfoot" | "thead" | "tr")
+        );
+
+        let location = if self.foster_parenting && is_table_target {
+            let find_last = |tag_name: &str| {
+                self.stack
+                    .iter()
+                    .rposition(|n| Self::get_tag_name(n).as_deref() == Some(tag_name))
+            };
+            match (find_last("template"), find_last("table")) {
+                (Some(template), table) if table.is_none_or(|table| template > table) => {
+                    InsertionLocation {
+                        parent: Rc::clone(&self.stack[template]),
+                        before: None,
+                    }
+                }
+                // Fragment case
+                (_, None) => InsertionLocation {
+                    parent: Rc::clone(&self.stack[0]),
+                    before: None,
+                },
+                (_, Some(table)) => {
+                    let table_node = &self.stack[table];
+                    let parent = table_node
+                        .borrow()
+                        .parent
+                        .as_ref()
+                        .and_then(|p| p.upgrade());
+                    match parent {
+                        Some(parent) => InsertionLocation {
+                            parent,
+                            before: Some(Rc::clone(table_node)),
+                        },
+                        None => InsertionLocation {
+                            parent: Rc::clone(&self.stack[table - 1]),
+                            before: None,
+                        },
+                    }
+                }
+            }
+        } else {
+            InsertionLocation {
+                parent: Rc::clone(target),
+                before: None,
+            }
+        };
+
+        // If the location is inside a template element, the node is inserted into its template contents instead.
+        let template_contents = location.parent.borrow().template_contents.clone();
+        match template_contents {
+            Some(contents) => InsertionLocation {
+                parent: contents,
+                before: None,
+            },
+            None => location,
+        }
+    }
+
+    /// Inserts a `template` element. If it has the `shadowrootmode` attribute, a shadow root is attached to
+    /// the current node instead, and the contents of the template are parsed into the shadow root.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inhead
+    fn insert_template_element(&mut self, tag_name: &str, attributes: &[(String, String)]) {
+        self.active_formatting_elements
+            .push(ActiveFormattingElement::Marker);
+        self.insertion_mode = InsertionMode::InTemplate;
+        self.template_insertion_modes
+            .push(InsertionMode::InTemplate);
+
+        let mode = attributes
+            .iter()
+            .find(|(name, _)| name == "shadowrootmode")
+            .and_then(|(_, value)| match value.to_ascii_lowercase().as_str() {
+                "open" => Some(ShadowRootMode::Open),
+                "closed" => Some(ShadowRootMode::Closed),
+                _ => None,
+            });
+        // The document element can't be a declarative shadow host.
+        if let (Some(mode), true) = (mode, self.stack.len() > 1) {
+            let host = Rc::clone(self.stack.last().unwrap());
+            match DomNode::attach_shadow(&host, mode) {
+                Result::Ok(shadow_root) => {
+                    // The template element is only pushed onto the stack of open elements, and is not inserted into the document.
+                    let mut template = DomNode::new(NodeType::Element(Element {
+                        tag_name: tag_name.to_owned(),
+                        attributes: attributes.to_owned(),
+                    }));
+                    template.template_contents = Some(shadow_root);
+                    self.stack.push(Rc::new(RefCell::new(template)));
+                    return;
+                }
+                Err(e) => self.report_error("shadow-root-not-attached", Some(e.to_string())),
+            }
+        }
+
+        self.insert_element(tag_name, attributes);
+        // The contents of the template are parsed into a separate document fragment,
+        // so they are not children of the template element and are never rendered.
+        self.stack.last().unwrap().borrow_mut().template_contents = Some(Rc::new(RefCell::new(
+            DomNode::new(NodeType::DocumentFragment),
+        )));
+    }
+
+    /// Processes a `template` end tag.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inhead
+    fn close_template_element(&mut self) {
+        if !self.is_template_on_stack() {
+            self.report_error("unexpected-end-tag", Some("template".to_string()));
+            return;
+        }
+        self.generate_implied_end_tags(None);
+        if self.get_current_elm_name().as_deref() != Some("template") {
+            self.report_error("unclosed-elements", Some("template".to_string()));
+        }
+        self.pop_until_template();
+        self.reset_insertion_mode();
+    }
+
+    /// Returns whether a fragment is parsed in the context of a `select` element, in which `select` and
+    /// `input` elements are ignored.
+    fn is_select_context(&self) -> bool {
+        self.context_element
+            .as_ref()
+            .is_some_and(|n| Self::get_tag_name(n).as_deref() == Some("select"))
+    }
+
+    fn is_template_on_stack(&self) -> bool {
+        self.stack
+            .iter()
+            .any(|n| Self::get_tag_name(n).as_deref() == Some("template"))
+    }
 
+    /// Pops elements from the stack of open elements until a `template` element has been popped,
+    /// and discards the state associated with it.
+    fn pop_until_template(&mut self) {
+        while let Some(n) = self.stack.pop() {
+            if Self::get_tag_name(&n).as_deref() == Some("template") {
                 break;
             }
         }
+        self.clear_active_formatting_elements_to_last_marker();
+        self.template_insertion_modes.pop();
+    }
 
-        Ok((document_node, style_sheets))
+    /// https://html.spec.whatwg.org/multipage/parsing.html#reset-the-insertion-mode-appropriately
+    fn reset_insertion_mode(&mut self) {
+        for (i, node) in self.stack.iter().enumerate().rev() {
+            let last = i == 0;
+            // In the fragment case, the context element is used instead of the root html element.
+            let node = match &self.context_element {
+                Some(context_element) if last => context_element,
+                _ => node,
+            };
+            match Self::get_tag_name(node).as_deref() {
+                Some("td" | "th") if !last => {
+                    self.insertion_mode = InsertionMode::InCell;
+                    return;
+                }
+                Some("tr") => {
+                    self.insertion_mode = InsertionMode::InRow;
+                    return;
+                }
+                Some("tbody" | "thead" | "tfoot") => {
+                    self.insertion_mode = InsertionMode::InTableBody;
+                    return;
+                }
+                Some("caption") => {
+                    self.insertion_mode = InsertionMode::InCaption;
+                    return;
+                }
+                Some("colgroup") => {
+                    self.insertion_mode = InsertionMode::InColumnGroup;
+                    return;
+                }
+                Some("table") => {
+                    self.insertion_mode = InsertionMode::InTable;
+                    return;
+                }
+                Some("template") => {
+                    self.insertion_mode = *self.template_insertion_modes.last().unwrap();
+                    return;
+                }
+                Some("head") if !last => {
+                    self.insertion_mode = InsertionMode::InHead;
+                    return;
+                }
+                Some("body") => {
+                    self.insertion_mode = InsertionMode::InBody;
+                    return;
+                }
+                Some("frameset") => {
+                    self.insertion_mode = InsertionMode::InFrameset;
+                    return;
+                }
+                Some("html") => {
+                    self.insertion_mode = if self.head_element.is_none() {
+                        InsertionMode::BeforeHead
+                    } else {
+                        InsertionMode::AfterHead
+                    };
+                    return;
+                }
+                _ if last => {
+                    self.insertion_mode = InsertionMode::InBody;
+                    return;
+                }
+                _ => {}
+            }
+        }
     }
 
-    fn is_blank(c: char) -> bool {
-        matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' ')
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generic-rcdata-element-parsing-algorithm
+    fn insert_rcdata_element(&mut self, tag_name: &str, attributes: &[(String, String)]) {
+        self.insert_element(tag_name, attributes);
+        self.tokenizer.change_state(TokenizationState::RcData);
+        self.orig_insertion_mode = Some(self.insertion_mode);
+        self.insertion_mode = InsertionMode::Text;
     }
 
-    /// Returns the tag name of the current element, if the current node is an element.
-    fn get_current_elm_name(&self) -> Option<String> {
-        if let Some(node) = &self.stack.last() {
-            let NodeType::Element(elm) = &node.borrow().node_type else {
-                return None;
-            };
-            Some(elm.tag_name.clone())
-        } else {
-            None
-        }
+    /// https://html.spec.whatwg.org/multipage/parsing.html#generic-raw-text-element-parsing-algorithm
+    fn insert_raw_text_element(&mut self, tag_name: &str, attributes: &[(String, String)]) {
+        self.insert_element(tag_name, attributes);
+        self.tokenizer.change_state(TokenizationState::RawText);
+        self.orig_insertion_mode = Some(self.insertion_mode);
+        self.insertion_mode = InsertionMode::Text;
     }
 
-    /// https://html.spec.whatwg.org/multipage/parsing.html#generate-implied-end-tags
-    fn generate_implied_end_tags(&mut self, excluded_tag: Option<&str>) {
-        let mut tag_lists = vec![
-            "dd", "dt", "li", "optgroup", "option", "p", "rb", "rp", "rt", "rtc",
-        ];
-        if let Some(excluded_tag) = excluded_tag {
-            let pos = tag_lists
-                .iter()
-                .position(|&x| x == excluded_tag)
-                .unwrap_or_else(|| {
-                    panic!(
-                        "The tag {:?} is not in the list of tags that can be excluded",
-                        excluded_tag
-                    )
-                });
-            tag_lists.remove(pos);
+    /// Inserts a script element and switches the tokenizer to the script data state.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inhead
+    fn insert_script_element(&mut self, tag_name: &str, attributes: &[(String, String)]) {
+        self.insert_element(tag_name, attributes);
+        self.tokenizer.change_state(TokenizationState::ScriptData);
+        self.orig_insertion_mode = Some(self.insertion_mode);
+        self.insertion_mode = InsertionMode::Text;
+    }
+
+    /// Inserts a void element processed in the "in head" insertion mode, such as `link` and `meta`. The style
+    /// sheet of a `link` element is loaded here, so that the style sheets are in tree order.
+    fn insert_metadata_element(
+        &mut self,
+        tag_name: &str,
+        attributes: &[(String, String)],
+    ) -> Result<()> {
+        self.insert_element(tag_name, attributes);
+        let node = self.stack.pop().unwrap();
+        // The elements in template contents and shadow trees don't affect the document.
+        if let NodeType::ShadowRoot(_) | NodeType::DocumentFragment =
+            DomNode::get_root(&node).borrow().node_type
+        {
+            return Ok(());
         }
-        loop {
-            let current_node_type = self.get_current_elm_name().unwrap();
-            if tag_lists.contains(&current_node_type.as_str()) {
-                self.stack.pop();
-            } else {
-                break;
+        let node = node.borrow();
+        let NodeType::Element(elm) = &node.node_type else {
+            return Ok(());
+        };
+        let Some(href) = elm.get_attribute("href").filter(|h| !h.is_empty()) else {
+            return Ok(());
+        };
+        match tag_name {
+            // https://html.spec.whatwg.org/multipage/semantics.html#set-the-frozen-base-url
+            "base" if self.base_url.is_none() => {
+                self.base_url = Some(resolve_url(&self.document_url, href));
             }
+            // https://html.spec.whatwg.org/multipage/links.html#link-type-stylesheet
+            "link" => {
+                let rel = elm
+                    .get_attribute("rel")
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+                let keywords = rel.split_ascii_whitespace().collect::<Vec<_>>();
+                // Alternative style sheets are not applied unless the user selects them.
+                if !keywords.contains(&"stylesheet")
+                    || keywords.contains(&"alternate")
+                    || !MediaQueryEvaluator::new(&self.media)
+                        .matches(elm.get_attribute("media").unwrap_or_default())
+                {
+                    return Ok(());
+                }
+                if self.fetcher.is_none() {
+                    return Ok(());
+                }
+                let url = resolve_url(self.base_url.as_deref().unwrap_or(&self.document_url), href);
+                self.pending_style_sheets
+                    .push((self.style_sheets.len(), url));
+            }
+            _ => {}
         }
+        Ok(())
     }
 
-    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-an-html-element
-    fn insert_element(&mut self, tag_name: &str, attributes: &[(String, String)]) {
-        let new_node = DomNode::append_child(
-            self.stack.last().unwrap(),
-            DomNode::new(NodeType::Element(Element {
-                tag_name: tag_name.to_owned(),
-                attributes: attributes.to_owned(),
-            })),
-        );
-        self.stack.push(Rc::clone(&new_node));
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-a-comment
+    fn insert_comment(&mut self, comment: String) {
+        let node = self.create_node_for_token(NodeType::Comment(comment));
+        self.get_appropriate_insertion_place(None)
+            .insert(Rc::new(RefCell::new(node)));
     }
 
+    /// Inserts a comment as the last child of `parent`, instead of the appropriate place for inserting a node.
     /// https://html.spec.whatwg.org/multipage/parsing.html#insert-a-comment
-    fn insert_comment(&mut self, comment: String) {
+    fn insert_comment_at(&mut self, comment: String, parent: &Rc<RefCell<DomNode>>) {
         DomNode::append_child(
-            self.stack.last().unwrap(),
-            DomNode::new(NodeType::Comment(comment)),
+            parent,
+            self.create_node_for_token(NodeType::Comment(comment)),
         );
     }
 
+    /// Creates a node associated with the span of the current token.
+    fn create_node_for_token(&self, node_type: NodeType) -> DomNode {
+        DomNode {
+            source_span: Some(self.current_span),
+            ..DomNode::new(node_type)
+        }
+    }
+
     /// https://html.spec.whatwg.org/multipage/parsing.html#insert-a-character
     fn insert_char_to_token(&mut self, c: char) {
-        let mut need_to_push_node = false;
-        if let Some(n) = &mut self.stack.last().unwrap().borrow_mut().children.last() {
-            if let NodeType::Text(text) = &mut n.borrow_mut().node_type {
+        let location = self.get_appropriate_insertion_place(None);
+        // The Document object can't have Text nodes as its children.
+        if matches!(location.parent.borrow().node_type, NodeType::Document(_)) {
+            return;
+        }
+        if let Some(prev_sib) = location.get_prev_sibling() {
+            let mut prev_sib = prev_sib.borrow_mut();
+            if let NodeType::Text(text) = &mut prev_sib.node_type {
                 text.push(c);
-            } else {
-                need_to_push_node = true;
+                if let Some(span) = &mut prev_sib.source_span {
+                    span.end = self.current_span.end;
+                }
+                return;
             }
-        } else {
-            need_to_push_node = true;
-        }
-
-        if need_to_push_node {
-            DomNode::append_child(
-                self.stack.last().unwrap(),
-                DomNode::new(NodeType::Text(c.to_string())),
-            );
         }
+        let node = self.create_node_for_token(NodeType::Text(c.to_string()));
+        location.insert(Rc::new(RefCell::new(node)));
     }
 
     /// https://html.spec.whatwg.org/multipage/semantics.html#update-a-style-block
-    fn update_style_block(
-        &mut self,
-        node: Rc<RefCell<DomNode>>,
-        style_sheets: &mut Vec<StyleSheet>,
-    ) -> Result<()> {
+    fn update_style_block(&mut self, node: Rc<RefCell<DomNode>>) -> Result<()> {
+        // The style sheets in shadow trees are scoped to the trees, and are collected when the render tree is built.
+        // The style sheets in template contents are inert.
+        if let NodeType::ShadowRoot(_) | NodeType::DocumentFragment =
+            DomNode::get_root(&node).borrow().node_type
+        {
+            return Ok(());
+        }
+        if let NodeType::Element(elm) = &node.borrow().node_type {
+            if !MediaQueryEvaluator::new(&self.media)
+                .matches(elm.get_attribute("media").unwrap_or_default())
+            {
+                return Ok(());
+            }
+        }
+
         // When the UA should parse the CSS for the new stylesheet is not clearly defined:
         // https://github.com/whatwg/html/issues/2997
-        if let NodeType::Text(css) = &node.borrow().children.last().unwrap().borrow().node_type {
-            let style_sheet = CssParser::new(&CssTokenizer::new(css).tokenize()?).parse()?;
-            style_sheets.push(style_sheet);
+        let text_node = Rc::clone(node.borrow().children.last().unwrap());
+        let text_node = text_node.borrow();
+        if let NodeType::Text(css) = &text_node.node_type {
+            // The parse errors in the style sheet are reported to the same handler as the document,
+            // and the positions in the style sheet are resolved in the document.
+            let error_handler = self.tokenizer.get_error_handler();
+            let (tokens, spans): (Vec<_>, Vec<_>) = CssTokenizer::new(css)
+                .set_error_handler(Rc::clone(&error_handler))
+                .set_source_start(text_node.source_span.unwrap_or_default().start)
+                .tokenize_with_spans()?
+                .into_iter()
+                .unzip();
+            let mut style_sheet = CssParser::new(&tokens)
+                .set_error_handler(error_handler)
+                .set_source_spans(spans)
+                .parse()?;
+            if let Some(fetcher) = &self.fetcher {
+                let base_url = self.base_url.as_deref().unwrap_or(&self.document_url);
+                load_imports(&mut style_sheet, base_url, fetcher.as_ref());
+            }
+            self.style_sheets.push(style_sheet);
         }
         Ok(())
     }
 }
 
+/// Parses the input as the contents of the context element, e.g. for setting `innerHTML`.
+/// https://html.spec.whatwg.org/multipage/parsing.html#parsing-html-fragments
+#[allow(dead_code)]
+pub fn parse_fragment(
+    context_element: &Rc<RefCell<DomNode>>,
+    input: &str,
+) -> Result<Vec<Rc<RefCell<DomNode>>>> {
+    HtmlParser::new(HtmlTokenizer::new(input)).parse_fragment(context_element)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -780,7 +3397,7 @@ mod tests {
             .map(|node| node.borrow().node_type.clone())
             .collect::<Vec<_>>();
         let expected = vec![
-            NodeType::Document,
+            NodeType::Document(DocumentMode::NoQuirks),
             NodeType::DocumentType("html".to_string()),
             NodeType::Element(Element {
                 tag_name: "html".to_string(),
@@ -835,7 +3452,7 @@ mod tests {
             .map(|node| node.borrow().node_type.clone())
             .collect::<Vec<_>>();
         let expected = vec![
-            NodeType::Document,
+            NodeType::Document(DocumentMode::NoQuirks),
             NodeType::DocumentType("html".to_string()),
             NodeType::Element(Element {
                 tag_name: "html".to_string(),
@@ -906,7 +3523,7 @@ mod tests {
             .map(|node| node.borrow().node_type.clone())
             .collect::<Vec<_>>();
         let expected = vec![
-            NodeType::Document,
+            NodeType::Document(DocumentMode::Quirks),
             NodeType::Element(Element {
                 tag_name: "html".to_string(),
                 attributes: vec![],
@@ -934,4 +3551,964 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn parse_script() {
+        let html = "<html><head><script>if (a</b) { x = '</div>'; }</script></head><body><script><!--<script></script>--></script></body></html>";
+        let tree =
+            DocumentTree::build(HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap().0)
+                .unwrap();
+        let actual = tree
+            .get_dfs_iter()
+            .map(|node| node.borrow().node_type.clone())
+            .collect::<Vec<_>>();
+        let expected = vec![
+            NodeType::Document(DocumentMode::Quirks),
+            NodeType::Element(Element {
+                tag_name: "html".to_string(),
+                attributes: vec![],
+            }),
+            NodeType::Element(Element {
+                tag_name: "head".to_string(),
+                attributes: vec![],
+            }),
+            NodeType::Element(Element {
+                tag_name: "script".to_string(),
+                attributes: vec![],
+            }),
+            NodeType::Text("if (a</b) { x = '</div>'; }".to_string()),
+            NodeType::Element(Element {
+                tag_name: "body".to_string(),
+                attributes: vec![],
+            }),
+            NodeType::Element(Element {
+                tag_name: "script".to_string(),
+                attributes: vec![],
+            }),
+            NodeType::Text("<!--<script></script>-->".to_string()),
+        ];
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn determine_document_mode() {
+        let get_mode = |html: &str| {
+            let mut parser = HtmlParser::new(HtmlTokenizer::new(html));
+            parser.parse().unwrap();
+            parser.get_document_mode()
+        };
+
+        assert_eq!(
+            get_mode("<!DOCTYPE html><html><head></head><body></body></html>"),
+            DocumentMode::NoQuirks
+        );
+        assert_eq!(
+            get_mode("<!DOCTYPE html SYSTEM \"about:legacy-compat\"><html><head></head><body></body></html>"),
+            DocumentMode::NoQuirks
+        );
+        assert_eq!(
+            get_mode("<html><head></head><body></body></html>"),
+            DocumentMode::Quirks
+        );
+        assert_eq!(
+            get_mode("<!DOCTYPE foo><html><head></head><body></body></html>"),
+            DocumentMode::Quirks
+        );
+        assert_eq!(
+            get_mode("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 3.2 Final//EN\"><html><head></head><body></body></html>"),
+            DocumentMode::Quirks
+        );
+        assert_eq!(
+            get_mode(
+                "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\"><html><head></head><body></body></html>"
+            ),
+            DocumentMode::Quirks
+        );
+        assert_eq!(
+            get_mode("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\" \"http://www.w3.org/TR/html4/loose.dtd\"><html><head></head><body></body></html>"),
+            DocumentMode::LimitedQuirks
+        );
+        assert_eq!(
+            get_mode("<!DOCTYPE html PUBLIC \"-//w3c//dtd xhtml 1.0 transitional//en\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\"><html><head></head><body></body></html>"),
+            DocumentMode::LimitedQuirks
+        );
+        assert_eq!(
+            get_mode("<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\" \"http://www.w3.org/TR/html4/strict.dtd\"><html><head></head><body></body></html>"),
+            DocumentMode::NoQuirks
+        );
+    }
+
+    #[test]
+    fn parse_misnested_formatting_elements() {
+        let parse = |body: &str| {
+            let html = format!("<html><head></head><body>{}</body></html>", body);
+            let body = HtmlParser::new(HtmlTokenizer::new(&html))
+                .parse()
+                .unwrap()
+                .0;
+            let body = Rc::clone(&body.borrow().children[0].borrow().children[1]);
+            DocumentTree { root: body }.to_string()
+        };
+
+        assert_eq!(
+            parse("<b><i>text</b></i>"),
+            [
+                "└─Elem( tag: <body> )",
+                "  └─Elem( tag: <b> )",
+                "    └─Elem( tag: <i> )",
+                "      └─Text(\"text\")",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            parse("<p>1<b>2<i>3</b>4</i>5</p>"),
+            [
+                "└─Elem( tag: <body> )",
+                "  └─Elem( tag: <p> )",
+                "    ├─Text(\"1\")",
+                "    ├─Elem( tag: <b> )",
+                "    │ ├─Text(\"2\")",
+                "    │ └─Elem( tag: <i> )",
+                "    │   └─Text(\"3\")",
+                "    ├─Elem( tag: <i> )",
+                "    │ └─Text(\"4\")",
+                "    └─Text(\"5\")",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            parse("<b>1<p>2</b>3</p>"),
+            [
+                "└─Elem( tag: <body> )",
+                "  ├─Elem( tag: <b> )",
+                "  │ └─Text(\"1\")",
+                "  └─Elem( tag: <p> )",
+                "    ├─Elem( tag: <b> )",
+                "    │ └─Text(\"2\")",
+                "    └─Text(\"3\")",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            parse("<a>1<div>2<a>3</a></div></a>"),
+            [
+                "└─Elem( tag: <body> )",
+                "  ├─Elem( tag: <a> )",
+                "  │ └─Text(\"1\")",
+                "  └─Elem( tag: <div> )",
+                "    ├─Elem( tag: <a> )",
+                "    │ └─Text(\"2\")",
+                "    └─Elem( tag: <a> )",
+                "      └─Text(\"3\")",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn parse_declarative_shadow_root() {
+        let html = "<html><head></head><body><div><template shadowrootmode=\"open\"><style>p { color: red; }</style><p><slot></slot></p></template><b>light</b></div><template><i>inert</i></template></body></html>";
+        let (root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(root).unwrap();
+
+        // The shadow root is shown as the first child of the host, and the template element of the shadow root is not in the tree.
+        assert_eq!(
+            tree.to_string(),
+            [
+                "└─Document",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    └─Elem( tag: <body> )",
+                "      ├─Elem( tag: <div> )",
+                "      │ ├─ShadowRoot(Open)",
+                "      │ │ ├─Elem( tag: <style> )",
+                "      │ │ │ └─Text(\"p { color: red; }\")",
+                "      │ │ └─Elem( tag: <p> )",
+                "      │ │   └─Elem( tag: <slot> )",
+                "      │ └─Elem( tag: <b> )",
+                "      │   └─Text(\"light\")",
+                "      └─Elem( tag: <template> )",
+                "        └─DocumentFragment",
+                "          └─Elem( tag: <i> )",
+                "            └─Text(\"inert\")",
+            ]
+            .join("\n")
+        );
+        // The style sheet in the shadow tree is not a style sheet of the document.
+        assert!(style_sheets.is_empty());
+    }
+
+    #[test]
+    fn parse_template_contents() {
+        let html = "<html><head><template><style>p { color: red; }</style><p>head</p></template><title>t</title></head><body><template><b>x</b><template>y</template></template><p>z</p><template><i>eof";
+        let (root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(root).unwrap();
+
+        // The contents are kept in the document fragment of each template, and the parser goes back
+        // to the insertion mode that was used before the template.
+        assert_eq!(
+            tree.to_string(),
+            [
+                "└─Document",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    │ ├─Elem( tag: <template> )",
+                "    │ │ └─DocumentFragment",
+                "    │ │   ├─Elem( tag: <style> )",
+                "    │ │   │ └─Text(\"p { color: red; }\")",
+                "    │ │   └─Elem( tag: <p> )",
+                "    │ │     └─Text(\"head\")",
+                "    │ └─Elem( tag: <title> )",
+                "    │   └─Text(\"t\")",
+                "    └─Elem( tag: <body> )",
+                "      ├─Elem( tag: <template> )",
+                "      │ └─DocumentFragment",
+                "      │   ├─Elem( tag: <b> )",
+                "      │   │ └─Text(\"x\")",
+                "      │   └─Elem( tag: <template> )",
+                "      │     └─DocumentFragment",
+                "      │       └─Text(\"y\")",
+                "      ├─Elem( tag: <p> )",
+                "      │ └─Text(\"z\")",
+                "      └─Elem( tag: <template> )",
+                "        └─DocumentFragment",
+                "          └─Elem( tag: <i> )",
+                "            └─Text(\"eof\")",
+            ]
+            .join("\n")
+        );
+        // The style sheet in the template contents is inert.
+        assert!(style_sheets.is_empty());
+    }
+
+    #[test]
+    fn parse_media_elements() {
+        let html = "<html><head></head><body><video controls poster=\"poster.png\"><source src=\"a.webm\"><track kind=\"captions\">fallback</video><audio></audio></body></html>";
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(root).unwrap();
+        assert_eq!(
+            tree.to_string(),
+            [
+                "└─Document",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    └─Elem( tag: <body> )",
+                "      ├─Elem( tag: <video>, attr: [\"controls\"=\"\"; \"poster\"=\"poster.png\"] )",
+                "      │ ├─Elem( tag: <source>, attr: [\"src\"=\"a.webm\"] )",
+                "      │ ├─Elem( tag: <track>, attr: [\"kind\"=\"captions\"] )",
+                "      │ └─Text(\"fallback\")",
+                "      └─Elem( tag: <audio> )",
+            ]
+            .join("\n")
+        );
+
+        let media = tree
+            .get_dfs_iter()
+            .filter_map(|node| match &node.borrow().node_type {
+                NodeType::Element(elm) if elm.is_media_element() => Some(elm.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(media.len(), 2);
+        assert!(media[0].has_controls());
+        assert_eq!(media[0].get_poster(), Some("poster.png"));
+        assert!(media[0].get_duration().unwrap().is_nan());
+        assert!(!media[1].has_controls());
+        assert_eq!(media[1].get_poster(), None);
+    }
+
+    #[test]
+    fn parse_rcdata_elements() {
+        let html = "<html><head><title>a </b> &amp; c</title></head><body><textarea>\n<p>x</textarea y></body></html>";
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(root).unwrap();
+        assert_eq!(
+            tree.to_string(),
+            [
+                "└─Document",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    │ └─Elem( tag: <title> )",
+                "    │   └─Text(\"a </b> & c\")",
+                "    └─Elem( tag: <body> )",
+                "      └─Elem( tag: <textarea> )",
+                "        └─Text(\"<p>x\")",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn parse_whitespace_around_head_and_body() {
+        let html = "<html><head></head> <!--a--><body>x</body> <!--b-->\n</html> <!--c-->\n";
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(root).unwrap();
+        assert_eq!(
+            tree.to_string(),
+            [
+                "└─Document",
+                "  ├─Elem( tag: <html> )",
+                "  │ ├─Elem( tag: <head> )",
+                "  │ ├─Text(\" \")",
+                "  │ ├─Comment(\"a\")",
+                "  │ ├─Elem( tag: <body> )",
+                "  │ │ └─Text(\"x \\n \\n\")",
+                "  │ └─Comment(\"b\")",
+                "  └─Comment(\"c\")",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn foster_parent_nodes() {
+        // The table is appended manually, so that the nodes are inserted without the table insertion modes.
+        let html = "<html><head></head><body><p></p></body></html>";
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let html_elm = Rc::clone(&root.borrow().children[0]);
+        let body = Rc::clone(&html_elm.borrow().children[1]);
+        let p = Rc::clone(&body.borrow().children[0]);
+        let table = DomNode::append_child(
+            &p,
+            DomNode::new(NodeType::Element(Element {
+                tag_name: "table".to_string(),
+                attributes: Vec::new(),
+            })),
+        );
+
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(""));
+        parser.stack = vec![html_elm, body, p, table];
+        parser.foster_parenting = true;
+        parser.insert_char_to_token('a');
+        parser.insert_char_to_token('b');
+        parser.insert_element("b", &[]);
+        parser.insert_comment("c".to_string());
+
+        // The nodes targeted at the table are inserted before it.
+        assert_eq!(
+            DocumentTree::build(root).unwrap().to_string(),
+            [
+                "└─Document",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    └─Elem( tag: <body> )",
+                "      └─Elem( tag: <p> )",
+                "        ├─Text(\"ab\")",
+                "        ├─Elem( tag: <b> )",
+                "        │ └─Comment(\"c\")",
+                "        └─Elem( tag: <table> )",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn parse_noscript() {
+        let html = "<html><head><noscript><style>p { color: red; }</style></noscript></head><body><noscript><b>no</b> script</noscript></body></html>";
+
+        // When scripting is disabled, the contents are parsed as normal markup.
+        let (root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        assert_eq!(
+            DocumentTree::build(root).unwrap().to_string(),
+            [
+                "└─Document",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    │ └─Elem( tag: <noscript> )",
+                "    │   └─Elem( tag: <style> )",
+                "    │     └─Text(\"p { color: red; }\")",
+                "    └─Elem( tag: <body> )",
+                "      └─Elem( tag: <noscript> )",
+                "        ├─Elem( tag: <b> )",
+                "        │ └─Text(\"no\")",
+                "        └─Text(\" script\")",
+            ]
+            .join("\n")
+        );
+        assert_eq!(style_sheets.len(), 1);
+
+        // When scripting is enabled, the contents are parsed as raw text.
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(html));
+        let (root, style_sheets) = parser.set_scripting(true).parse().unwrap();
+        assert_eq!(
+            DocumentTree::build(root).unwrap().to_string(),
+            [
+                "└─Document",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    │ └─Elem( tag: <noscript> )",
+                "    │   └─Text(\"<style>p { color: red; }</style>\")",
+                "    └─Elem( tag: <body> )",
+                "      └─Elem( tag: <noscript> )",
+                "        └─Text(\"<b>no</b> script\")",
+            ]
+            .join("\n")
+        );
+        assert!(style_sheets.is_empty());
+    }
+
+    #[test]
+    fn parse_common_elements() {
+        let html = "<!DOCTYPE html><!--c--><html><head></head><body class=a><body id=b>\
+            <p>one<div>two</div><p>three</h2><dl><dt>t<dd>d<dt>u</dl>\
+            <pre>\nx</pre></p><button>a<button>b</button><hr><input type=hidden><image src=i.png>\
+            <select><option>1<option>2</select><ruby>r<rp>(<rt>t</ruby></br>\
+            <h1>h<h2>i</h1><svg/><span>s</span></body></html>";
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        assert_eq!(
+            DocumentTree::build(root).unwrap().to_string(),
+            [
+                "└─Document",
+                "  ├─DocumentType(\"html\")",
+                "  ├─Comment(\"c\")",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    └─Elem( tag: <body>, attr: [\"class\"=\"a\"; \"id\"=\"b\"] )",
+                "      ├─Elem( tag: <p> )",
+                "      │ └─Text(\"one\")",
+                "      ├─Elem( tag: <div> )",
+                "      │ └─Text(\"two\")",
+                "      ├─Elem( tag: <p> )",
+                "      │ └─Text(\"three\")",
+                "      ├─Elem( tag: <dl> )",
+                "      │ ├─Elem( tag: <dt> )",
+                "      │ │ └─Text(\"t\")",
+                "      │ ├─Elem( tag: <dd> )",
+                "      │ │ └─Text(\"d\")",
+                "      │ └─Elem( tag: <dt> )",
+                "      │   └─Text(\"u\")",
+                "      ├─Elem( tag: <pre> )",
+                "      │ └─Text(\"x\")",
+                // A stray `</p>` inserts an empty paragraph.
+                "      ├─Elem( tag: <p> )",
+                "      ├─Elem( tag: <button> )",
+                "      │ └─Text(\"a\")",
+                "      ├─Elem( tag: <button> )",
+                "      │ └─Text(\"b\")",
+                "      ├─Elem( tag: <hr> )",
+                "      ├─Elem( tag: <input>, attr: [\"type\"=\"hidden\"] )",
+                "      ├─Elem( tag: <img>, attr: [\"src\"=\"i.png\"] )",
+                "      ├─Elem( tag: <select> )",
+                "      │ ├─Elem( tag: <option> )",
+                "      │ │ └─Text(\"1\")",
+                "      │ └─Elem( tag: <option> )",
+                "      │   └─Text(\"2\")",
+                "      ├─Elem( tag: <ruby> )",
+                "      │ ├─Text(\"r\")",
+                "      │ ├─Elem( tag: <rp> )",
+                "      │ │ └─Text(\"(\")",
+                "      │ └─Elem( tag: <rt> )",
+                "      │   └─Text(\"t\")",
+                "      ├─Elem( tag: <br> )",
+                "      ├─Elem( tag: <h1> )",
+                "      │ └─Text(\"h\")",
+                "      ├─Elem( tag: <h2> )",
+                "      │ └─Text(\"i\")",
+                "      ├─Elem( tag: <svg> )",
+                "      └─Elem( tag: <span> )",
+                "        └─Text(\"s\")",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn parse_foreign_content() {
+        // The self-closing elements in the SVG and MathML subtrees are closed, and an HTML element breaks out
+        // of them.
+        let html =
+            "<html><head></head><body><svg viewBox=\"0 0 8 8\"><path d=\"M0\"/><g><circle r=1 /></G>\
+            <title>t<b>u</b></title><rect/><p>p</p><span>s</span><math><mspace/><mi>x</mi></math></body></html>";
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        assert_eq!(
+            DocumentTree::build(root).unwrap().to_string(),
+            [
+                "└─Document",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    └─Elem( tag: <body> )",
+                "      ├─Elem( tag: <svg>, attr: [\"viewbox\"=\"0 0 8 8\"] )",
+                "      │ ├─Elem( tag: <path>, attr: [\"d\"=\"M0\"] )",
+                "      │ ├─Elem( tag: <g> )",
+                "      │ │ └─Elem( tag: <circle>, attr: [\"r\"=\"1\"] )",
+                "      │ ├─Elem( tag: <title> )",
+                "      │ │ ├─Text(\"t\")",
+                "      │ │ └─Elem( tag: <b> )",
+                "      │ │   └─Text(\"u\")",
+                "      │ └─Elem( tag: <rect> )",
+                "      ├─Elem( tag: <p> )",
+                "      │ └─Text(\"p\")",
+                "      ├─Elem( tag: <span> )",
+                "      │ └─Text(\"s\")",
+                "      └─Elem( tag: <math> )",
+                "        ├─Elem( tag: <mspace> )",
+                "        └─Elem( tag: <mi> )",
+                "          └─Text(\"x\")",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn check_element_scopes() {
+        // A paragraph outside a button is not closed by a block in the button, and a list item outside a
+        // nested list is not closed by an end tag in the list.
+        let html = "<html><head></head><body><p><button><div>a</div></button>\
+            <ul><li>b<ol><li>c</li><p>d</li>e</ol></ul></body></html>";
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        assert_eq!(
+            DocumentTree::build(root).unwrap().to_string(),
+            [
+                "└─Document",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    └─Elem( tag: <body> )",
+                "      ├─Elem( tag: <p> )",
+                "      │ └─Elem( tag: <button> )",
+                "      │   └─Elem( tag: <div> )",
+                "      │     └─Text(\"a\")",
+                "      └─Elem( tag: <ul> )",
+                "        └─Elem( tag: <li> )",
+                "          ├─Text(\"b\")",
+                "          └─Elem( tag: <ol> )",
+                "            ├─Elem( tag: <li> )",
+                "            │ └─Text(\"c\")",
+                "            └─Elem( tag: <p> )",
+                "              └─Text(\"de\")",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn parse_tables() {
+        let html = "<html><head></head><body><table>a<caption>c</caption><col>\
+            <tr><td>1<td>2<tr><th>3</table><table><thead><tr><td>x<b>y</table></b>z\
+            <table><tr><td><table><td>n</table></td></tr><input type=hidden><form></table></body></html>";
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        assert_eq!(
+            DocumentTree::build(root).unwrap().to_string(),
+            [
+                "└─Document",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    └─Elem( tag: <body> )",
+                // Text misplaced in a table is moved before it.
+                "      ├─Text(\"a\")",
+                "      ├─Elem( tag: <table> )",
+                "      │ ├─Elem( tag: <caption> )",
+                "      │ │ └─Text(\"c\")",
+                "      │ ├─Elem( tag: <colgroup> )",
+                "      │ │ └─Elem( tag: <col> )",
+                "      │ └─Elem( tag: <tbody> )",
+                "      │   ├─Elem( tag: <tr> )",
+                "      │   │ ├─Elem( tag: <td> )",
+                "      │   │ │ └─Text(\"1\")",
+                "      │   │ └─Elem( tag: <td> )",
+                "      │   │   └─Text(\"2\")",
+                "      │   └─Elem( tag: <tr> )",
+                "      │     └─Elem( tag: <th> )",
+                "      │       └─Text(\"3\")",
+                "      ├─Elem( tag: <table> )",
+                "      │ └─Elem( tag: <thead> )",
+                "      │   └─Elem( tag: <tr> )",
+                "      │     └─Elem( tag: <td> )",
+                "      │       ├─Text(\"x\")",
+                "      │       └─Elem( tag: <b> )",
+                "      │         └─Text(\"y\")",
+                // Formatting elements opened in a cell are closed with the cell.
+                "      ├─Text(\"z\")",
+                "      └─Elem( tag: <table> )",
+                "        └─Elem( tag: <tbody> )",
+                "          ├─Elem( tag: <tr> )",
+                "          │ └─Elem( tag: <td> )",
+                "          │   └─Elem( tag: <table> )",
+                "          │     └─Elem( tag: <tbody> )",
+                "          │       └─Elem( tag: <tr> )",
+                "          │         └─Elem( tag: <td> )",
+                "          │           └─Text(\"n\")",
+                "          ├─Elem( tag: <input>, attr: [\"type\"=\"hidden\"] )",
+                "          └─Elem( tag: <form> )",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn parse_forms() {
+        let html = "<html><head></head><body><table><form id=f><tr><td><input id=a></table>\
+            <form><select><option>1<option>2<input id=b></select>\
+            <label>L<textarea id=t></textarea></label></body></html>";
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(root).unwrap();
+        assert_eq!(
+            tree.to_string(),
+            [
+                "└─Document",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    └─Elem( tag: <body> )",
+                "      ├─Elem( tag: <table> )",
+                "      │ ├─Elem( tag: <form>, attr: [\"id\"=\"f\"] )",
+                "      │ └─Elem( tag: <tbody> )",
+                "      │   └─Elem( tag: <tr> )",
+                "      │     └─Elem( tag: <td> )",
+                "      │       └─Elem( tag: <input>, attr: [\"id\"=\"a\"] )",
+                // The nested form is ignored, and the input closes the select.
+                "      ├─Elem( tag: <select> )",
+                "      │ ├─Elem( tag: <option> )",
+                "      │ │ └─Text(\"1\")",
+                "      │ └─Elem( tag: <option> )",
+                "      │   └─Text(\"2\")",
+                "      ├─Elem( tag: <input>, attr: [\"id\"=\"b\"] )",
+                "      └─Elem( tag: <label> )",
+                "        ├─Text(\"L\")",
+                "        └─Elem( tag: <textarea>, attr: [\"id\"=\"t\"] )",
+            ]
+            .join("\n")
+        );
+        // The elements are associated with the form in the table, which is not their ancestor.
+        let form = tree.get_element_by_id("f").unwrap();
+        for id in ["a", "b", "t"] {
+            let owner = DomNode::get_form_owner(&tree.get_element_by_id(id).unwrap());
+            assert!(owner.is_some_and(|owner| Rc::ptr_eq(&owner, &form)));
+        }
+        let label = tree.get_elements_by_tag_name("label").remove(0);
+        let owner = DomNode::get_form_owner(&label);
+        assert!(owner.is_some_and(|owner| Rc::ptr_eq(&owner, &form)));
+
+        // Closing the form stops the association, except for the elements with the `form` attribute.
+        let html = "<html><head></head><body><form id=f><div><input id=a></form>\
+            <input id=b></div><input id=c><input id=d form=f><label for=d>D</label></body></html>";
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(root).unwrap();
+        let form = tree.get_element_by_id("f").unwrap();
+        let get_owner = |id| DomNode::get_form_owner(&tree.get_element_by_id(id).unwrap());
+        // The div is left open, so `b` is still a descendant of the form.
+        assert!(get_owner("a").is_some_and(|owner| Rc::ptr_eq(&owner, &form)));
+        assert!(get_owner("b").is_some_and(|owner| Rc::ptr_eq(&owner, &form)));
+        assert!(get_owner("c").is_none());
+        assert!(get_owner("d").is_some_and(|owner| Rc::ptr_eq(&owner, &form)));
+        let label = tree.get_elements_by_tag_name("label").remove(0);
+        let control = DomNode::get_labeled_control(&label).unwrap();
+        assert!(Rc::ptr_eq(&control, &tree.get_element_by_id("d").unwrap()));
+    }
+
+    #[test]
+    fn parse_frameset() {
+        let html = "<html><head><noscript><html lang=en></noscript></head><title>t</title>\
+            <frameset cols=\"50%,50%\"><frame src=a.html><frameset><frame src=b.html></frameset>\
+            <noframes><p>No frames</p></noframes>x</frameset></html><!--a-->";
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        assert_eq!(
+            DocumentTree::build(root).unwrap().to_string(),
+            [
+                "└─Document",
+                "  ├─Elem( tag: <html>, attr: [\"lang\"=\"en\"] )",
+                "  │ ├─Elem( tag: <head> )",
+                "  │ │ ├─Elem( tag: <noscript> )",
+                "  │ │ └─Elem( tag: <title> )",
+                "  │ │   └─Text(\"t\")",
+                "  │ └─Elem( tag: <frameset>, attr: [\"cols\"=\"50%,50%\"] )",
+                "  │   ├─Elem( tag: <frame>, attr: [\"src\"=\"a.html\"] )",
+                "  │   ├─Elem( tag: <frameset> )",
+                "  │   │ └─Elem( tag: <frame>, attr: [\"src\"=\"b.html\"] )",
+                "  │   └─Elem( tag: <noframes> )",
+                "  │     └─Text(\"<p>No frames</p>\")",
+                "  └─Comment(\"a\")",
+            ]
+            .join("\n")
+        );
+
+        // A frameset replaces the body only if nothing has been rendered in it.
+        let html = "<html><head></head><body> <frameset><frame></frameset></body></html>";
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        assert_eq!(
+            DocumentTree::build(root).unwrap().to_string(),
+            [
+                "└─Document",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    └─Elem( tag: <frameset> )",
+                "      └─Elem( tag: <frame> )",
+            ]
+            .join("\n")
+        );
+        let html = "<html><head></head><body>x<frameset><frame></frameset></body></html>";
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        assert_eq!(
+            DocumentTree::build(root).unwrap().to_string(),
+            [
+                "└─Document",
+                "  └─Elem( tag: <html> )",
+                "    ├─Elem( tag: <head> )",
+                "    └─Elem( tag: <body> )",
+                "      └─Text(\"x\")",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[derive(Debug, Default)]
+    struct MockFetcher {
+        fetched: RefCell<Vec<String>>,
+    }
+
+    impl ResourceFetcher for MockFetcher {
+        fn fetch(&self, url: &str) -> Result<Vec<u8>> {
+            self.fetched.borrow_mut().push(url.to_string());
+            match url {
+                "http://example.com/css/a.css" => {
+                    Ok(b"p { color: red; } a { color: blue; }".to_vec())
+                }
+                "http://example.com/b.css" => Ok(b"\xEF\xBB\xBFp { color: green; }".to_vec()),
+                _ => bail!("Not found: {}", url),
+            }
+        }
+    }
+
+    #[test]
+    fn load_external_style_sheets() {
+        let html = "<html><head><style>p { color: black; }</style><base href=\"/css/\">\
+            <link rel=\"Stylesheet\" href=\"a.css\"><link rel=stylesheet href=missing.css>\
+            <link rel=\"alternate stylesheet\" href=alt.css><link rel=stylesheet href=print.css media=print>\
+            <template><link rel=stylesheet href=inert.css></template></head>\
+            <body><link rel=stylesheet href=\"/b.css\" media=\"screen, print\"></body></html>";
+        let fetcher = Rc::new(MockFetcher::default());
+        let (_, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html))
+            .set_resource_fetcher("http://example.com/index.html", Rc::clone(&fetcher) as _)
+            .parse()
+            .unwrap();
+        assert_eq!(
+            *fetcher.fetched.borrow(),
+            [
+                "http://example.com/css/a.css",
+                "http://example.com/css/missing.css",
+                "http://example.com/b.css",
+            ]
+        );
+        // The style sheets are in tree order, and the ones which failed to load are skipped.
+        assert_eq!(
+            style_sheets
+                .iter()
+                .map(|style_sheet| style_sheet.rules.len())
+                .collect::<Vec<_>>(),
+            [1, 2, 1]
+        );
+
+        // External style sheets are not loaded without a fetcher.
+        let (_, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        assert_eq!(style_sheets.len(), 1);
+    }
+
+    #[test]
+    fn fetch_resource_hints() {
+        let html = "<html><head><link rel=prefetch href=next.html><link rel=preload as=font href=f.woff2>\
+            <link rel=preload as=style href=css/a.css><link rel=preconnect href=\"https://cdn.example.com\">\
+            <link rel=stylesheet href=css/a.css></head><body></body></html>";
+        let fetcher = Rc::new(MockFetcher::default());
+        let (_, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html))
+            .set_resource_fetcher("http://example.com/index.html", Rc::clone(&fetcher) as _)
+            .parse()
+            .unwrap();
+        // The preloaded style sheet is fetched once, before the others.
+        assert_eq!(
+            *fetcher.fetched.borrow(),
+            [
+                "http://example.com/css/a.css",
+                "http://example.com/f.woff2",
+                "http://example.com/next.html",
+            ]
+        );
+        assert_eq!(style_sheets.len(), 1);
+    }
+
+    #[test]
+    fn track_source_spans() {
+        use crate::renderer::css::cssom::Rule;
+
+        let html =
+            "<html><head><style>p { color: red; }</style></head>\n<body>ab<!--c--></body></html>";
+        let (root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let span = |node: &Rc<RefCell<DomNode>>| {
+            node.borrow()
+                .source_span
+                .map(|s| ((s.start.line, s.start.column), (s.end.line, s.end.column)))
+        };
+
+        let html_elm = Rc::clone(&root.borrow().children[0]);
+        let head = Rc::clone(&html_elm.borrow().children[0]);
+        let style = Rc::clone(&head.borrow().children[0]);
+        let body = Rc::clone(&html_elm.borrow().children[2]);
+        assert_eq!(span(&html_elm), Some(((1, 1), (1, 7))));
+        assert_eq!(span(&style), Some(((1, 13), (1, 20))));
+        assert_eq!(span(&style.borrow().children[0]), Some(((1, 20), (1, 37))));
+        assert_eq!(
+            span(&html_elm.borrow().children[1]),
+            Some(((1, 52), (2, 1)))
+        );
+        assert_eq!(span(&body), Some(((2, 1), (2, 7))));
+        assert_eq!(span(&body.borrow().children[0]), Some(((2, 7), (2, 9))));
+        assert_eq!(span(&body.borrow().children[1]), Some(((2, 9), (2, 17))));
+
+        // The spans of the rules in the style sheet are resolved in the document.
+        let Rule::QualifiedRule(rule) = &style_sheets[0].rules[0] else {
+            panic!("expected a qualified rule");
+        };
+        let rule_span = rule.source_span.unwrap();
+        assert_eq!((rule_span.start.column, rule_span.end.column), (20, 37));
+
+        // Implied elements have no spans.
+        let (root, _) = HtmlParser::new(HtmlTokenizer::new("text")).parse().unwrap();
+        assert_eq!(span(&root.borrow().children[0]), None);
+    }
+
+    #[test]
+    fn report_parse_errors() {
+        use crate::renderer::parse_error::ParseErrorCollector;
+        use crate::renderer::source::SourcePosition;
+
+        let html = "<html><head><style>p { color: red</style></head>\n<body></b>&amp</body></html>";
+        let collector = Rc::new(RefCell::new(ParseErrorCollector::default()));
+        let mut tokenizer = HtmlTokenizer::new(html);
+        tokenizer.set_error_handler(Rc::clone(&collector) as _);
+        HtmlParser::new(tokenizer).parse().unwrap();
+
+        let errors = collector.borrow_mut().take_errors();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|e| (e.code.as_str(), e.position.map(|p| (p.line, p.column))))
+                .collect::<Vec<_>>(),
+            vec![
+                // Tree construction errors are reported at the end of the token.
+                ("missing-doctype", Some((1, 6))),
+                // The positions in the style sheet are resolved in the document.
+                ("eof-in-simple-block", Some((1, 34))),
+                ("unexpected-end-tag", Some((2, 10))),
+                ("missing-semicolon-after-character-reference", Some((2, 14))),
+            ]
+        );
+        assert_eq!(
+            errors[2].position,
+            Some(SourcePosition {
+                offset: 58,
+                line: 2,
+                column: 10
+            })
+        );
+        assert_eq!(errors[2].detail.as_deref(), Some("b"));
+        assert_eq!(
+            errors[2].context.as_deref(),
+            Some("<body></b>&amp</body></html>")
+        );
+    }
+    #[test]
+    fn parse_in_chunks() {
+        // The document is long enough for the consumed input to be discarded while parsing.
+        let html = format!(
+            "<!DOCTYPE html><html><head><style>p {{ color: red; }}</style></head><body>{}<p id=last>café &amp;&notin; 😀</p></body></html>",
+            "<p>Lorem ipsum &lt;dolor&gt; sit amet</p>\n".repeat(200)
+        );
+        let (expected, expected_style_sheets) =
+            HtmlParser::new(HtmlTokenizer::new(&html)).parse().unwrap();
+
+        // Multi-byte characters and character references are split across chunks.
+        let mut parser = HtmlParser::new(HtmlTokenizer::new_streaming());
+        for chunk in html.as_bytes().chunks(3) {
+            parser.feed(chunk).unwrap();
+        }
+        // The tree is built before the input stream is closed.
+        assert!(!parser.document.borrow().children.is_empty());
+        let (document, style_sheets) = parser.finish().unwrap();
+
+        assert_eq!(
+            DocumentTree::build(Rc::clone(&document))
+                .unwrap()
+                .to_string(),
+            DocumentTree::build(Rc::clone(&expected))
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            format!("{style_sheets:?}"),
+            format!("{expected_style_sheets:?}")
+        );
+        let last_p = |doc: &Rc<RefCell<DomNode>>| {
+            let body = Rc::clone(&doc.borrow().children[1].borrow().children[1]);
+            let p = Rc::clone(body.borrow().children.last().unwrap());
+            let span = p.borrow().source_span;
+            span
+        };
+        assert_eq!(last_p(&document), last_p(&expected));
+        assert!(last_p(&document).unwrap().start.line > 200);
+
+        // An invalid sequence and an incomplete sequence at the end are replaced with U+FFFD.
+        let mut tokenizer = HtmlTokenizer::new_streaming();
+        tokenizer.feed(b"a\xFFb\xE3\x81");
+        tokenizer.finish();
+        let mut chars = String::new();
+        while let (HtmlToken::Character(c), _) = tokenizer.consume_token_with_span() {
+            chars.push(c);
+        }
+        assert_eq!(chars, "a\u{FFFD}b\u{FFFD}");
+    }
+
+    #[test]
+    fn parse_fragments() {
+        // Returns the parsed nodes and their descendants in tree order.
+        let parse = |context: &str, input: &str| {
+            let context = Rc::new(RefCell::new(DomNode::new(NodeType::Element(Element {
+                tag_name: context.to_string(),
+                attributes: Vec::new(),
+            }))));
+            parse_fragment(&context, input)
+                .unwrap()
+                .iter()
+                .flat_map(|node| {
+                    assert!(node.borrow().parent.is_none());
+                    std::iter::once(Rc::clone(node)).chain(DomNode::get_descendants(node))
+                })
+                .map(|node| node.borrow().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            parse("div", "<p>a<b>b</p>c</body></html>d"),
+            vec![
+                "Elem( tag: <p> )",
+                "Text(\"a\")",
+                "Elem( tag: <b> )",
+                "Text(\"b\")",
+                "Elem( tag: <b> )",
+                "Text(\"cd\")",
+            ]
+        );
+        // The contents of the context element are tokenized in the state for the element.
+        assert_eq!(
+            parse("title", "<b>&amp;</b></title>"),
+            vec!["Text(\"<b>&</b></title>\")"]
+        );
+        assert_eq!(parse("style", "a&amp;<b>"), vec!["Text(\"a&amp;<b>\")"]);
+        assert_eq!(
+            parse("plaintext", "</plaintext>"),
+            vec!["Text(\"</plaintext>\")"]
+        );
+        // The insertion mode is reset according to the context element.
+        assert_eq!(
+            parse("html", "<title>x</title>y"),
+            vec![
+                "Elem( tag: <head> )",
+                "Elem( tag: <title> )",
+                "Text(\"x\")",
+                "Elem( tag: <body> )",
+                "Text(\"y\")",
+            ]
+        );
+        assert_eq!(
+            parse("template", "<p>x"),
+            vec!["Elem( tag: <p> )", "Text(\"x\")"]
+        );
+    }
 }
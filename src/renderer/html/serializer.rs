@@ -0,0 +1,162 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::renderer::html::dom::{DocumentTree, DomNode, NodeType};
+
+/// Serializes the document back into HTML text, which produces the same tree when it is parsed again
+/// in most cases. Shadow roots are not serialized, as `innerHTML` does.
+/// https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments
+pub fn serialize_document(tree: &DocumentTree) -> String {
+    serialize_children(&tree.root)
+}
+
+/// Serializes the children of the node, which is the value of `innerHTML` of the node.
+/// The contents of a `template` element are serialized in place of its children.
+/// https://html.spec.whatwg.org/multipage/parsing.html#html-fragment-serialisation-algorithm
+pub fn serialize_children(node_ref: &Rc<RefCell<DomNode>>) -> String {
+    let mut html = String::new();
+    let template_contents = node_ref.borrow().template_contents.clone();
+    let parent = template_contents.as_ref().unwrap_or(node_ref).borrow();
+    for child in &parent.children {
+        serialize_node(&mut html, child, &parent.node_type);
+    }
+    html
+}
+
+/// Serializes the node itself and its descendants, which is the value of `outerHTML` of the node.
+/// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-outerhtml
+pub fn serialize_outer(node_ref: &Rc<RefCell<DomNode>>) -> String {
+    let parent_type = node_ref
+        .borrow()
+        .parent
+        .as_ref()
+        .and_then(|p| p.upgrade())
+        .map_or(NodeType::DocumentFragment, |parent| {
+            parent.borrow().node_type.clone()
+        });
+    let mut html = String::new();
+    serialize_node(&mut html, node_ref, &parent_type);
+    html
+}
+
+fn serialize_node(html: &mut String, node_ref: &Rc<RefCell<DomNode>>, parent: &NodeType) {
+    match &node_ref.borrow().node_type {
+        NodeType::Element(elm) => {
+            html.push('<');
+            html.push_str(&elm.tag_name);
+            for (name, value) in &elm.attributes {
+                html.push(' ');
+                html.push_str(name);
+                html.push_str("=\"");
+                html.push_str(&escape_string(value, true));
+                html.push('"');
+            }
+            html.push('>');
+            // Void elements can't have any contents nor an end tag.
+            if is_void_element(&elm.tag_name) {
+                return;
+            }
+            html.push_str(&serialize_children(node_ref));
+            html.push_str("</");
+            html.push_str(&elm.tag_name);
+            html.push('>');
+        }
+        NodeType::Text(text) => match parent {
+            // The contents of raw text elements are not escaped, as character references are not
+            // decoded in them.
+            NodeType::Element(parent)
+                if matches!(
+                    parent.tag_name.as_str(),
+                    "style" | "script" | "xmp" | "iframe" | "noembed" | "noframes" | "plaintext"
+                ) =>
+            {
+                html.push_str(text)
+            }
+            _ => html.push_str(&escape_string(text, false)),
+        },
+        NodeType::Comment(data) => {
+            html.push_str("<!--");
+            html.push_str(data);
+            html.push_str("-->");
+        }
+        NodeType::DocumentType(name) => {
+            html.push_str("<!DOCTYPE ");
+            html.push_str(name);
+            html.push('>');
+        }
+        NodeType::Document(_) | NodeType::DocumentFragment | NodeType::ShadowRoot(_) => {
+            html.push_str(&serialize_children(node_ref))
+        }
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#escapingString
+pub fn escape_string(text: &str, attribute_mode: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '\u{A0}' => escaped.push_str("&nbsp;"),
+            '"' if attribute_mode => escaped.push_str("&quot;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// https://html.spec.whatwg.org/multipage/syntax.html#void-elements
+/// `basefont`, `bgsound`, `frame`, `keygen` and `param` are obsolete, but are serialized in the same way.
+fn is_void_element(tag_name: &str) -> bool {
+    matches!(
+        tag_name,
+        "area"
+            | "base"
+            | "basefont"
+            | "bgsound"
+            | "br"
+            | "col"
+            | "embed"
+            | "frame"
+            | "hr"
+            | "img"
+            | "input"
+            | "keygen"
+            | "link"
+            | "meta"
+            | "param"
+            | "source"
+            | "track"
+            | "wbr"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    fn parse(html: &str) -> DocumentTree {
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        DocumentTree::build(document).unwrap()
+    }
+
+    #[test]
+    fn serialize_and_reparse() {
+        let html = "<!DOCTYPE html><html><head><title>a &amp; b</title><style>p > a { color: red; }</style></head>\
+            <body><!-- note --><p class=\"x &quot;y&quot;\" id=a>1 &lt; 2&nbsp;<br><img src=a.png alt='>'></p>\
+            <template><li>item</li></template><textarea>&lt;/textarea&gt;</textarea></body></html>";
+        let tree = parse(html);
+        let serialized = serialize_document(&tree);
+        assert_eq!(
+            serialized,
+            "<!DOCTYPE html><html><head><title>a &amp; b</title><style>p > a { color: red; }</style></head>\
+            <body><!-- note --><p class=\"x &quot;y&quot;\" id=\"a\">1 &lt; 2&nbsp;<br><img src=\"a.png\" alt=\"&gt;\"></p>\
+            <template><li>item</li></template><textarea>&lt;/textarea&gt;</textarea></body></html>"
+        );
+        // The serialized HTML is parsed into the same tree.
+        assert_eq!(parse(&serialized).to_string(), tree.to_string());
+    }
+}
@@ -0,0 +1,550 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
+
+use anyhow::{bail, Context, Result};
+
+use crate::renderer::html::dom::{DomNode, NodeType};
+use crate::renderer::html::mutation::replace_data;
+
+thread_local! {
+    /// The dictionary with which the spelling is checked, which is set in window mode. The pages are rendered
+    /// on the main thread, where it's set.
+    static DICTIONARY: RefCell<Option<Rc<Dictionary>>> = const { RefCell::new(None) };
+}
+
+/// The directories in which the dictionaries of hunspell are installed.
+const DICTIONARY_DIRS: [&str; 3] = [
+    "/usr/share/hunspell",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+];
+
+/// The list of words installed on most Unix systems, which is used if no dictionary of hunspell is found.
+const WORD_LIST: &str = "/usr/share/dict/words";
+
+/// The maximum number of the suggestions for a misspelled word.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// How the flags of the affixes are written in the dictionary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlagType {
+    /// Each character is a flag.
+    Char,
+    /// Each pair of characters is a flag.
+    Long,
+    /// The flags are decimal numbers separated by commas.
+    Num,
+}
+
+impl FlagType {
+    fn split(&self, flags: &str) -> Vec<String> {
+        match self {
+            Self::Char => flags.chars().map(String::from).collect(),
+            Self::Long => flags
+                .chars()
+                .collect::<Vec<_>>()
+                .chunks(2)
+                .map(|pair| pair.iter().collect())
+                .collect(),
+            Self::Num => flags
+                .split(',')
+                .map(|flag| flag.trim().to_string())
+                .filter(|flag| !flag.is_empty())
+                .collect(),
+        }
+    }
+}
+
+/// A prefix or a suffix rule of the affix file, which derives a word from a stem with the flag of the
+/// rule by removing `strip` from the stem and adding `add`, if the stem matches the condition.
+#[derive(Debug, Clone)]
+struct Affix {
+    flag: String,
+    /// Whether the rule can be combined with the rules of the other kind.
+    cross_product: bool,
+    strip: String,
+    add: String,
+    /// The character classes which the start of the stem for a prefix, or the end of the stem for a suffix
+    /// must match.
+    condition: Vec<CharClass>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CharClass {
+    Any,
+    Of(Vec<char>),
+    NotOf(Vec<char>),
+}
+
+impl CharClass {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Of(chars) => chars.contains(&c),
+            Self::NotOf(chars) => !chars.contains(&c),
+        }
+    }
+
+    /// Parses the condition of an affix rule, e.g. `[^aeiou]y`.
+    fn parse_condition(condition: &str) -> Vec<Self> {
+        let mut classes = Vec::new();
+        let mut chars = condition.chars();
+        while let Some(c) = chars.next() {
+            classes.push(match c {
+                '.' => Self::Any,
+                '[' => {
+                    let class = chars.by_ref().take_while(|&c| c != ']').collect::<Vec<_>>();
+                    match class.split_first() {
+                        Some(('^', rest)) => Self::NotOf(rest.to_vec()),
+                        _ => Self::Of(class),
+                    }
+                }
+                c => Self::Of(vec![c]),
+            });
+        }
+        classes
+    }
+}
+
+/// A dictionary in the format of hunspell, with which the words are checked and the suggestions for the
+/// misspelled ones are made. The prefix and the suffix rules of the affix file are supported, but the
+/// compounding and the other options are not.
+/// https://man.archlinux.org/man/hunspell.5
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    /// The stems with the flags of the affix rules which apply to them.
+    words: HashMap<String, Vec<String>>,
+    prefixes: Vec<Affix>,
+    suffixes: Vec<Affix>,
+    /// The characters tried in the suggestions, the more frequent ones first.
+    try_chars: Vec<char>,
+}
+
+impl Dictionary {
+    /// Parses the dictionary file and the affix file, if any. The first line of the dictionary file is the
+    /// number of the words, which may be omitted for the plain word lists.
+    pub fn parse(dic: &str, aff: Option<&str>) -> Self {
+        let mut dictionary = Self::default();
+        let mut flag_type = FlagType::Char;
+        let mut cross_products = HashMap::new();
+        for line in aff.unwrap_or_default().lines() {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            match fields.as_slice() {
+                ["FLAG", "long", ..] => flag_type = FlagType::Long,
+                ["FLAG", "num", ..] => flag_type = FlagType::Num,
+                ["TRY", chars, ..] => dictionary.try_chars = chars.chars().collect(),
+                // The rules of a flag follow the header `PFX flag cross_product count`.
+                [kind @ ("PFX" | "SFX"), flag, cross_product @ ("Y" | "N"), count]
+                    if count.parse::<usize>().is_ok() =>
+                {
+                    cross_products.insert((*kind, *flag), *cross_product == "Y");
+                }
+                // `PFX flag strip add condition`, where `0` is the empty string.
+                [kind @ ("PFX" | "SFX"), flag, strip, add, rest @ ..] => {
+                    let empty_or = |s: &str| {
+                        if s == "0" {
+                            String::new()
+                        } else {
+                            s.to_string()
+                        }
+                    };
+                    let affix = Affix {
+                        flag: flag.to_string(),
+                        cross_product: cross_products
+                            .get(&(*kind, *flag))
+                            .copied()
+                            .unwrap_or(false),
+                        strip: empty_or(strip),
+                        // The flags of the continuation classes after the slash are not supported.
+                        add: empty_or(add.split('/').next().unwrap_or_default()),
+                        condition: CharClass::parse_condition(rest.first().unwrap_or(&".")),
+                    };
+                    if *kind == "PFX" {
+                        dictionary.prefixes.push(affix);
+                    } else {
+                        dictionary.suffixes.push(affix);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut lines = dic.lines().peekable();
+        if lines
+            .peek()
+            .is_some_and(|line| line.trim().parse::<usize>().is_ok())
+        {
+            lines.next();
+        }
+        for line in lines {
+            // The morphological fields follow the word after white space.
+            let Some(entry) = line.split_whitespace().next() else {
+                continue;
+            };
+            let (word, flags) = entry.split_once('/').unwrap_or((entry, ""));
+            dictionary
+                .words
+                .entry(word.to_string())
+                .or_default()
+                .extend(flag_type.split(flags));
+        }
+        if dictionary.try_chars.is_empty() {
+            dictionary.try_chars = ('a'..='z').collect();
+        }
+        dictionary
+    }
+
+    /// Loads the dictionary of hunspell for the language, e.g. `en_US`, or the list of words of the system
+    /// if there is none.
+    pub fn load(language: &str) -> Result<Self> {
+        for dir in DICTIONARY_DIRS {
+            let dic = Path::new(dir).join(format!("{}.dic", language));
+            if !dic.exists() {
+                continue;
+            }
+            let dic = fs::read_to_string(&dic)
+                .with_context(|| format!("Failed to read {}", dic.display()))?;
+            let aff = fs::read_to_string(Path::new(dir).join(format!("{}.aff", language))).ok();
+            return Ok(Self::parse(&dic, aff.as_deref()));
+        }
+        if Path::new(WORD_LIST).exists() {
+            let words = fs::read_to_string(WORD_LIST)
+                .with_context(|| format!("Failed to read {}", WORD_LIST))?;
+            return Ok(Self::parse(&words, None));
+        }
+        bail!("No dictionary is found for {}", language)
+    }
+
+    /// Loads the dictionary for the language of the locale of the process, which is English if it's not
+    /// set.
+    pub fn load_system() -> Result<Self> {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty() && value != "C" && value != "POSIX")
+            .unwrap_or_else(|| "en_US".to_string());
+        // e.g. `en_US.UTF-8@euro` is `en_US`.
+        let language = locale
+            .split(['.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        Self::load(&language)
+    }
+
+    /// Returns whether the word is spelled correctly. A word in lowercase in the dictionary is also correct
+    /// when it's capitalized or in uppercase, but not the other way around.
+    pub fn check(&self, word: &str) -> bool {
+        let word = word.replace('\u{2019}', "'");
+        let lower = word.to_lowercase();
+        let mut forms = vec![word.clone()];
+        if is_capitalized(&word) || word.chars().all(|c| !c.is_lowercase()) {
+            forms.push(lower.clone());
+            forms.push(capitalize(&lower));
+        }
+        forms.iter().any(|form| self.check_form(form))
+    }
+
+    /// Returns the byte ranges of the misspelled words in the text. The words are the runs of the letters
+    /// and the apostrophes between them, and the ones with digits are skipped.
+    pub fn find_misspelled_words(&self, text: &str) -> Vec<Range<usize>> {
+        get_words(text)
+            .into_iter()
+            .filter(|range| !self.check(&text[range.clone()]))
+            .collect()
+    }
+
+    /// Returns the correctly spelled words which are one edit away from the misspelled word, i.e. with a
+    /// character removed, swapped with the next one, replaced or inserted, and the pairs of the words into
+    /// which it's split. The case of the word is kept in the suggestions.
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let chars = word.chars().collect::<Vec<_>>();
+        let try_chars = self
+            .try_chars
+            .iter()
+            .copied()
+            .chain(word.chars().flat_map(char::to_lowercase))
+            .collect::<Vec<_>>();
+        let mut candidates = Vec::new();
+        for i in 0..=chars.len() {
+            let (head, tail) = chars.split_at(i);
+            if let Some((_, rest)) = tail.split_first() {
+                candidates.push(head.iter().chain(rest).collect::<String>());
+            }
+            if let [a, b, rest @ ..] = tail {
+                candidates.push(head.iter().chain([b, a]).chain(rest).collect());
+            }
+            for &c in &try_chars {
+                if let Some((_, rest)) = tail.split_first() {
+                    candidates.push(head.iter().chain([&c]).chain(rest).collect());
+                }
+                candidates.push(head.iter().chain([&c]).chain(tail).collect());
+            }
+        }
+        for i in 1..chars.len() {
+            let (first, second) = chars.split_at(i);
+            candidates.push(format!(
+                "{} {}",
+                first.iter().collect::<String>(),
+                second.iter().collect::<String>()
+            ));
+        }
+
+        let mut seen = HashSet::new();
+        candidates
+            .into_iter()
+            .map(|candidate| {
+                if is_capitalized(word) {
+                    capitalize(&candidate)
+                } else {
+                    candidate
+                }
+            })
+            .filter(|candidate| candidate != word && seen.insert(candidate.clone()))
+            .filter(|candidate| candidate.split(' ').all(|w| self.check(w)))
+            .take(MAX_SUGGESTIONS)
+            .collect()
+    }
+
+    fn check_form(&self, word: &str) -> bool {
+        self.has_stem(word, &[])
+            || self
+                .get_stems(word, &self.suffixes, false)
+                .any(|(stem, affix)| self.has_stem(&stem, &[&affix.flag]))
+            || self
+                .get_stems(word, &self.prefixes, true)
+                .any(|(stem, prefix)| {
+                    self.has_stem(&stem, &[&prefix.flag])
+                        || (prefix.cross_product
+                            && self
+                                .get_stems(&stem, &self.suffixes, false)
+                                .filter(|(_, suffix)| suffix.cross_product)
+                                .any(|(stem, suffix)| {
+                                    self.has_stem(&stem, &[&prefix.flag, &suffix.flag])
+                                }))
+                })
+    }
+
+    fn has_stem(&self, stem: &str, flags: &[&str]) -> bool {
+        self.words.get(stem).is_some_and(|word_flags| {
+            flags
+                .iter()
+                .all(|flag| word_flags.iter().any(|f| f == flag))
+        })
+    }
+
+    /// Returns the stems from which the word is derived with the rules, with the rules.
+    fn get_stems<'a>(
+        &'a self,
+        word: &'a str,
+        affixes: &'a [Affix],
+        is_prefix: bool,
+    ) -> impl Iterator<Item = (String, &'a Affix)> + 'a {
+        affixes.iter().filter_map(move |affix| {
+            // The word must have some characters other than the affix.
+            let stem = if is_prefix {
+                let rest = word.strip_prefix(affix.add.as_str())?;
+                (!rest.is_empty()).then(|| format!("{}{}", affix.strip, rest))?
+            } else {
+                let rest = word.strip_suffix(affix.add.as_str())?;
+                (!rest.is_empty()).then(|| format!("{}{}", rest, affix.strip))?
+            };
+            if affix.add.is_empty() && affix.strip.is_empty() {
+                return None;
+            }
+            let chars = stem.chars().collect::<Vec<_>>();
+            let condition = &affix.condition;
+            if condition.len() > chars.len() {
+                return None;
+            }
+            let matched = if is_prefix {
+                &chars[..condition.len()]
+            } else {
+                &chars[chars.len() - condition.len()..]
+            };
+            condition
+                .iter()
+                .zip(matched)
+                .all(|(class, &c)| class.matches(c))
+                .then_some((stem, affix))
+        })
+    }
+}
+
+/// Sets the dictionary with which the spelling is checked in the pages rendered after it's set on the thread.
+pub fn set_dictionary(dictionary: Dictionary) {
+    DICTIONARY.with(|d| *d.borrow_mut() = Some(Rc::new(dictionary)));
+}
+
+/// Returns the dictionary of the thread, which is `None` until it's set, in which case the spelling is not
+/// checked.
+pub fn get_dictionary() -> Option<Rc<Dictionary>> {
+    DICTIONARY.with(|d| d.borrow().clone())
+}
+
+/// Returns whether the spelling of the text node is checked, which is the case if its parent is a text
+/// field or is editable, and the checking is enabled for it.
+/// https://html.spec.whatwg.org/multipage/interaction.html#spelling-and-grammar-checking
+pub fn is_spellchecked(text_ref: &Rc<RefCell<DomNode>>) -> bool {
+    let Some(parent) = DomNode::get_parent(text_ref) else {
+        return false;
+    };
+    let is_text_field = matches!(&parent.borrow().node_type,
+        NodeType::Element(elm) if matches!(elm.tag_name.as_str(), "textarea" | "input"));
+    (is_text_field || DomNode::is_editable(&parent)) && DomNode::is_spellcheck_enabled(&parent)
+}
+
+/// Replaces the misspelled word of the text node at the index in the ones found by the dictionary with the
+/// replacement, e.g. a suggestion chosen by the user. Returns `false` if there is no such word.
+pub fn replace_misspelled_word(
+    dictionary: &Dictionary,
+    text_ref: &Rc<RefCell<DomNode>>,
+    index: usize,
+    replacement: &str,
+) -> Result<bool> {
+    let data = text_ref.borrow().get_inside_text().unwrap_or_default();
+    let Some(range) = dictionary
+        .find_misspelled_words(&data)
+        .into_iter()
+        .nth(index)
+    else {
+        return Ok(false);
+    };
+    let offset = data[..range.start].chars().count();
+    let count = data[range].chars().count();
+    replace_data(text_ref, offset, count, replacement)?;
+    Ok(true)
+}
+
+/// Returns the byte ranges of the words in the text to be checked.
+fn get_words(text: &str) -> Vec<Range<usize>> {
+    let is_apostrophe = |c: char| matches!(c, '\'' | '\u{2019}');
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, c)| c);
+        let is_word_char = c.is_alphanumeric()
+            || (is_apostrophe(c) && start.is_some() && next.is_some_and(char::is_alphanumeric));
+        match (is_word_char, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                words.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push(s..text.len());
+    }
+    words.retain(|range| !text[range.clone()].chars().any(|c| c.is_numeric()));
+    words
+}
+
+fn is_capitalized(word: &str) -> bool {
+    let mut chars = word.chars();
+    chars.next().is_some_and(char::is_uppercase) && chars.all(|c| !c.is_uppercase())
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::dom::DocumentTree;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    const AFF: &str = "TRY esianrtolcdugmphbyfvkwzESIANRTOLCDUGMPHBYFVKWZ'
+PFX U Y 1
+PFX U   0     un         .
+SFX D Y 4
+SFX D   0     d          e
+SFX D   y     ied        [^aeiou]y
+SFX D   0     ed         [^ey]
+SFX D   0     ed         [aeiou]y
+SFX S N 1
+SFX S   0     s          .";
+    const DIC: &str = "8\nwalk/DUS\ntry/D\nlike/DU\nday/S\nPari\nthe\ndon't\nI";
+
+    #[test]
+    fn check_words() {
+        let dictionary = Dictionary::parse(DIC, Some(AFF));
+        for word in [
+            "walk",
+            "walked",
+            "unwalked",
+            "tried",
+            "liked",
+            "unliked",
+            "walks",
+            "days",
+            "Walked",
+            "WALKED",
+            "don\u{2019}t",
+            "Pari",
+            "I",
+        ] {
+            assert!(dictionary.check(word), "{}", word);
+        }
+        // The suffix `S` is not a cross product, and the conditions and the cases must match.
+        for word in ["unwalks", "tryed", "likeed", "dayed", "pari", "wlk", "i"] {
+            assert!(!dictionary.check(word), "{}", word);
+        }
+
+        let text = "I walkd the dog on 2nd day, didn't I? Th\u{e9} end";
+        let words = dictionary
+            .find_misspelled_words(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect::<Vec<_>>();
+        assert_eq!(words, ["walkd", "dog", "on", "didn't", "Th\u{e9}", "end"]);
+
+        // The plain lists of the words have no counts and no flags.
+        let dictionary = Dictionary::parse("apple\nbanana\n", None);
+        assert!(dictionary.check("Banana") && !dictionary.check("apples"));
+    }
+
+    #[test]
+    fn suggest_words() {
+        let dictionary = Dictionary::parse(DIC, Some(AFF));
+        assert_eq!(dictionary.suggest("walkd"), ["walk", "walked", "walks"]);
+        assert_eq!(dictionary.suggest("Teh"), ["The"]);
+        assert_eq!(dictionary.suggest("thewalk"), ["the walk"]);
+        assert!(dictionary.suggest("xyzzy").is_empty());
+    }
+
+    #[test]
+    fn replace_misspelled_words_in_text_fields() {
+        let html = "<html><head></head><body><textarea>I walkd, walkd</textarea><div contenteditable>walkd</div>\
+            <div contenteditable spellcheck=false>walkd</div><p>walkd</p></body></html>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let tree = DocumentTree::build(document).unwrap();
+        let texts = DomNode::get_descendants(&tree.root)
+            .filter(|node| matches!(node.borrow().node_type, NodeType::Text(_)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts.iter().map(is_spellchecked).collect::<Vec<_>>(),
+            [true, true, false, false]
+        );
+
+        let dictionary = Dictionary::parse(DIC, Some(AFF));
+        assert!(replace_misspelled_word(&dictionary, &texts[0], 1, "walked").unwrap());
+        assert!(!replace_misspelled_word(&dictionary, &texts[0], 1, "walked").unwrap());
+        assert_eq!(
+            texts[0].borrow().get_inside_text().unwrap(),
+            "I walkd, walked"
+        );
+    }
+}
@@ -1,17 +1,50 @@
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
+use std::ops::Range;
+use std::rc::Rc;
 
+use encoding_rs::Encoding;
+
+use crate::renderer::html::char_ref::{match_longest_named_char_ref, MAX_NAMED_CHAR_REF_LEN};
+use crate::renderer::html::encoding::ByteStreamDecoder;
+use crate::renderer::parse_error::{
+    ParseErrorReport, SharedParseErrorHandler, StderrParseErrorHandler,
+};
+use crate::renderer::source::{SourceLocator, SourceSpan};
 use crate::utils::TokenIterator;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TokenizationState {
     Data,
+    RcData,
     RawText,
+    ScriptData,
+    PlainText,
     TagOpen,
     EndTagOpen,
     TagName,
+    RcDataLessThanSign,
+    RcDataEndTagOpen,
+    RcDataEndTagName,
     RawTextLessThanSign,
     RawTextEndTagOpen,
     RawTextEndTagName,
+    ScriptDataLessThanSign,
+    ScriptDataEndTagOpen,
+    ScriptDataEndTagName,
+    ScriptDataEscapeStart,
+    ScriptDataEscapeStartDash,
+    ScriptDataEscaped,
+    ScriptDataEscapedDash,
+    ScriptDataEscapedDashDash,
+    ScriptDataEscapedLessThanSign,
+    ScriptDataEscapedEndTagOpen,
+    ScriptDataEscapedEndTagName,
+    ScriptDataDoubleEscapeStart,
+    ScriptDataDoubleEscaped,
+    ScriptDataDoubleEscapedDash,
+    ScriptDataDoubleEscapedDashDash,
+    ScriptDataDoubleEscapedLessThanSign,
+    ScriptDataDoubleEscapeEnd,
     BeforeAttributeName,
     AttributeName,
     AfterAttributeName,
@@ -26,12 +59,38 @@ pub enum TokenizationState {
     CommentStart,
     CommentStartDash,
     Comment,
+    CommentLessThanSign,
+    CommentLessThanSignBang,
+    CommentLessThanSignBangDash,
+    CommentLessThanSignBangDashDash,
     CommentEndDash,
     CommentEnd,
+    CommentEndBang,
     Doctype,
     BeforeDoctypeName,
     DoctypeName,
     AfterDoctypeName,
+    AfterDoctypePublicKeyword,
+    BeforeDoctypePublicIdentifier,
+    DoctypePublicIdentifierDoubleQuoted,
+    DoctypePublicIdentifierSingleQuoted,
+    AfterDoctypePublicIdentifier,
+    BetweenDoctypePublicAndSystemIdentifiers,
+    AfterDoctypeSystemKeyword,
+    BeforeDoctypeSystemIdentifier,
+    DoctypeSystemIdentifierDoubleQuoted,
+    DoctypeSystemIdentifierSingleQuoted,
+    AfterDoctypeSystemIdentifier,
+    BogusDoctype,
+    CharacterReference,
+    NamedCharacterReference,
+    AmbiguousAmpersand,
+    NumericCharacterReference,
+    HexadecimalCharacterReferenceStart,
+    DecimalCharacterReferenceStart,
+    HexadecimalCharacterReference,
+    DecimalCharacterReference,
+    NumericCharacterReferenceEnd,
 }
 
 /// The output of the tokenization step is a series of zero or more of the following tokens:
@@ -66,40 +125,228 @@ pub enum HtmlToken {
     Eof,
 }
 
+impl HtmlToken {
+    /// The names of the types of the tokens, in the order of `get_type_index()`.
+    pub const TYPE_NAMES: [&'static str; 6] = [
+        "doctype",
+        "start-tag",
+        "end-tag",
+        "comment",
+        "character",
+        "eof",
+    ];
+
+    /// Returns the index of the type of the token in `TYPE_NAMES`.
+    pub fn get_type_index(&self) -> usize {
+        match self {
+            Self::Doctype { .. } => 0,
+            Self::StartTag { .. } => 1,
+            Self::EndTag { .. } => 2,
+            Self::Comment(_) => 3,
+            Self::Character(_) => 4,
+            Self::Eof => 5,
+        }
+    }
+}
+
+/// The maximum number of characters that a step of the tokenizer consumes or peeks at.
+/// The longest lookahead is in the named character reference state.
+const MAX_LOOKAHEAD: usize = MAX_NAMED_CHAR_REF_LEN + 1;
+
+/// The number of consumed characters kept in the input stream, which is enough to show the context of
+/// parse errors and to look back at the last consumed character.
+const KEPT_CONSUMED_INPUT_LEN: usize = 64;
+
+/// The number of consumed characters in the input stream that triggers discarding them.
+const DISCARD_THRESHOLD: usize = 4096;
+
 #[derive(Debug)]
 pub struct HtmlTokenizer {
     state: TokenizationState,
     current_token: Option<HtmlToken>,
     input: TokenIterator<char>,
-    output: VecDeque<HtmlToken>,
+    /// The emitted tokens and the ranges of the input characters from which they were created.
+    output: VecDeque<(HtmlToken, Range<usize>)>,
+    /// The index of the input character after the last emitted token.
+    token_end: usize,
 
     /// https://html.spec.whatwg.org/multipage/parsing.html#temporary-buffer
     temp_buf: Vec<char>,
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#return-state
+    return_state: TokenizationState,
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#character-reference-code
+    char_ref_code: u32,
+
+    /// The tag name of the last start tag to have been emitted from this tokenizer, if any.
+    /// This is used to determine whether an end tag token is an appropriate end tag token.
+    last_start_tag_name: Option<String>,
+
+    /// The handler of the parse errors reported by this tokenizer and the tree construction stage.
+    error_handler: SharedParseErrorHandler,
+    locator: SourceLocator,
+    span_locator: SourceLocator,
+
+    /// Whether the end of the input stream has been reached, i.e. no more input will be fed.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-input-stream
+    input_closed: bool,
+    /// The decoder of the fed bytes, which keeps an incomplete sequence at the end of them.
+    decoder: ByteStreamDecoder,
+    /// The numbers of the consumed tokens by type, indexed by `HtmlToken::get_type_index()`.
+    token_counts: [usize; HtmlToken::TYPE_NAMES.len()],
 }
 
 impl HtmlTokenizer {
+    /// Creates a tokenizer for the whole document.
     pub fn new(html: &str) -> Self {
+        let mut tokenizer = Self::new_streaming();
+        tokenizer.input.extend(html.chars());
+        tokenizer.input_closed = true;
+        tokenizer
+    }
+
+    /// Creates a tokenizer whose input is fed incrementally by `feed()`, e.g. as network data arrives.
+    /// `finish()` must be called after the last input is fed.
+    pub fn new_streaming() -> Self {
         Self {
             state: TokenizationState::Data,
             current_token: None,
-            input: TokenIterator::new(&html.chars().collect::<Vec<_>>()),
+            input: TokenIterator::new(&[]),
             output: VecDeque::new(),
+            token_end: 0,
             temp_buf: Vec::new(),
+            return_state: TokenizationState::Data,
+            char_ref_code: 0,
+            last_start_tag_name: None,
+            error_handler: StderrParseErrorHandler::new_shared(),
+            locator: SourceLocator::default(),
+            span_locator: SourceLocator::default(),
+            input_closed: false,
+            decoder: ByteStreamDecoder::default(),
+            token_counts: Default::default(),
+        }
+    }
+
+    /// Returns the numbers of the tokens consumed so far by type.
+    pub fn get_token_counts(&self) -> Vec<(&'static str, usize)> {
+        HtmlToken::TYPE_NAMES
+            .into_iter()
+            .zip(self.token_counts)
+            .collect()
+    }
+
+    /// Sets the character encoding with which the fed bytes are decoded. This must be called before `feed()`.
+    /// The default is UTF-8.
+    #[allow(dead_code)]
+    pub fn set_encoding(&mut self, encoding: &'static Encoding) -> &mut Self {
+        self.decoder = ByteStreamDecoder::new(encoding);
+        self
+    }
+
+    /// Appends the bytes to the input stream. A character split across chunks is decoded when the rest of it
+    /// is fed, and invalid sequences are replaced with U+FFFD.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        assert!(!self.input_closed, "input must not be fed after finish()");
+        let text = self.decoder.decode(bytes, false);
+        self.input.extend(text.chars());
+        self.discard_consumed_input();
+    }
+
+    /// Marks the end of the input stream, so the rest of the input is tokenized and an end-of-file token is emitted.
+    pub fn finish(&mut self) {
+        if !self.input_closed {
+            let text = self.decoder.decode(&[], true);
+            self.input.extend(text.chars());
+        }
+        self.input_closed = true;
+    }
+
+    /// Removes the consumed characters from the input stream to keep the memory usage bounded.
+    fn discard_consumed_input(&mut self) {
+        let count = self
+            .input
+            .get_pos()
+            .saturating_sub(KEPT_CONSUMED_INPUT_LEN)
+            .min(self.token_end);
+        if count < DISCARD_THRESHOLD {
+            return;
+        }
+        self.locator.discard(self.input.get_items(), count);
+        self.span_locator.discard(self.input.get_items(), count);
+        self.input.discard_consumed(count);
+        self.token_end -= count;
+        for (_, range) in self.output.iter_mut() {
+            range.start = range.start.saturating_sub(count);
+            range.end = range.end.saturating_sub(count);
         }
     }
 
+    pub fn set_error_handler(&mut self, handler: SharedParseErrorHandler) -> &mut Self {
+        self.error_handler = handler;
+        self
+    }
+
+    pub fn get_error_handler(&self) -> SharedParseErrorHandler {
+        Rc::clone(&self.error_handler)
+    }
+
+    /// Reports a parse error at the last consumed character. This is also called by the HTML parser
+    /// to report tree construction errors with the token that caused them.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+    pub fn report_error_with_detail(&mut self, code: &str, detail: Option<String>) {
+        let (position, context) = self.locator.locate(
+            self.input.get_items(),
+            self.input.get_pos().saturating_sub(1),
+        );
+        self.error_handler.borrow_mut().handle(ParseErrorReport {
+            code: code.to_string(),
+            detail,
+            position: Some(position),
+            context: Some(context),
+        });
+    }
+
+    fn report_error(&mut self, code: &str) {
+        self.report_error_with_detail(code, None);
+    }
+
     /// https://html.spec.whatwg.org/multipage/parsing.html#reconsume
     fn allow_reconsume(&mut self, move_to: TokenizationState) {
         self.state = move_to;
         self.input.rewind(1);
+
+        // The character to be reconsumed is not a part of the token emitted just before.
+        let pos = self.input.get_pos();
+        if let Some((_, range)) = self.output.back_mut() {
+            range.end = range.end.min(pos);
+            range.start = range.start.min(range.end);
+        }
+        self.token_end = self.token_end.min(pos);
     }
 
     fn create_token(&mut self, token: HtmlToken) {
         self.current_token = Some(token);
     }
 
-    fn emit_token(&mut self, token: HtmlToken) {
-        self.output.push_back(token);
+    fn emit_token(&mut self, mut token: HtmlToken) {
+        if let HtmlToken::StartTag { tag_name, .. } = &token {
+            self.last_start_tag_name = Some(tag_name.clone());
+        }
+        // The duplicate attributes have been reported when leaving the attribute name state,
+        // and they are removed from the token so that only the first one is kept.
+        if let HtmlToken::StartTag { attributes, .. } | HtmlToken::EndTag { attributes, .. } =
+            &mut token
+        {
+            let mut names = HashSet::new();
+            attributes.retain(|(name, _)| names.insert(name.clone()));
+        }
+        // The tokens cover the input without gaps, so each token starts at the end of the previous one.
+        // If multiple tokens are emitted at once, the first one covers all the consumed characters.
+        let end = self.input.get_pos().min(self.input.get_items().len());
+        let start = self.token_end.min(end);
+        self.token_end = end;
+        self.output.push_back((token, start..end));
     }
 
     fn emit_tokens(&mut self, tokens: Vec<HtmlToken>) {
@@ -108,27 +355,209 @@ impl HtmlTokenizer {
         }
     }
 
+    /// Reports an error if the name of the current attribute is already in the attributes of the current tag.
+    /// This is called when leaving the attribute name state.
+    /// https://html.spec.whatwg.org/multipage/parsing.html#attribute-name-state
+    fn check_duplicate_attribute(&mut self) {
+        if let Some(HtmlToken::StartTag { attributes, .. } | HtmlToken::EndTag { attributes, .. }) =
+            &self.current_token
+        {
+            if let Some(((name, _), others)) = attributes.split_last() {
+                if others.iter().any(|(n, _)| n == name) {
+                    let name = name.clone();
+                    self.report_error_with_detail("duplicate-attribute", Some(name));
+                }
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-end-tag-token
+    fn is_appropriate_end_tag(&self) -> bool {
+        match (&self.current_token, &self.last_start_tag_name) {
+            (Some(HtmlToken::EndTag { tag_name, .. }), Some(last_start_tag_name)) => {
+                tag_name == last_start_tag_name
+            }
+            _ => false,
+        }
+    }
+
+    /// Processes a character in the RCDATA, RAWTEXT, script data, or script data escaped end tag name state.
+    /// If the character does not end the tag name as an appropriate end tag, the characters consumed so far
+    /// are emitted and the character is reconsumed in `fallback_state`.
+    /// - https://html.spec.whatwg.org/multipage/parsing.html#rcdata-end-tag-name-state
+    /// - https://html.spec.whatwg.org/multipage/parsing.html#rawtext-end-tag-name-state
+    /// - https://html.spec.whatwg.org/multipage/parsing.html#script-data-end-tag-name-state
+    /// - https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-end-tag-name-state
+    fn consume_end_tag_name(&mut self, fallback_state: TokenizationState) {
+        match self.input.next() {
+            Some('\t' | '\n' | '\x0C' | ' ') if self.is_appropriate_end_tag() => {
+                self.state = TokenizationState::BeforeAttributeName;
+            }
+            Some('/') if self.is_appropriate_end_tag() => {
+                self.state = TokenizationState::SelfClosingStartTag;
+            }
+            Some('>') if self.is_appropriate_end_tag() => {
+                self.state = TokenizationState::Data;
+                self.emit_token(self.current_token.clone().unwrap());
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                if let Some(HtmlToken::EndTag { tag_name, .. }) = &mut self.current_token {
+                    tag_name.push(c.to_ascii_lowercase());
+                }
+                self.temp_buf.push(c);
+            }
+            _ => {
+                self.emit_tokens(vec![HtmlToken::Character('<'), HtmlToken::Character('/')]);
+                for c in std::mem::take(&mut self.temp_buf) {
+                    self.emit_token(HtmlToken::Character(c));
+                }
+                self.allow_reconsume(fallback_state);
+            }
+        }
+    }
+
+    fn set_force_quirks(&mut self) {
+        if let Some(HtmlToken::Doctype { force_quirks, .. }) = &mut self.current_token {
+            *force_quirks = true;
+        }
+    }
+
+    /// Sets the current DOCTYPE token's public identifier (if `is_public` is true) or system identifier to the empty string.
+    fn init_doctype_identifier(&mut self, is_public: bool) {
+        if let Some(HtmlToken::Doctype {
+            public_identifier,
+            system_identifier,
+            ..
+        }) = &mut self.current_token
+        {
+            if is_public {
+                *public_identifier = Some(String::new());
+            } else {
+                *system_identifier = Some(String::new());
+            }
+        }
+    }
+
+    /// Appends a character to the current DOCTYPE token's public identifier (if `is_public` is true) or system identifier.
+    fn append_to_doctype_identifier(&mut self, is_public: bool, c: char) {
+        if let Some(HtmlToken::Doctype {
+            public_identifier,
+            system_identifier,
+            ..
+        }) = &mut self.current_token
+        {
+            let identifier = if is_public {
+                public_identifier
+            } else {
+                system_identifier
+            };
+            if let Some(identifier) = identifier {
+                identifier.push(c);
+            }
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#charref-in-attribute
+    fn is_char_ref_in_attribute(&self) -> bool {
+        matches!(
+            self.return_state,
+            TokenizationState::AttributeValueDoubleQuoted
+                | TokenizationState::AttributeValueSingleQuoted
+                | TokenizationState::AttributeValueUnquoted
+        )
+    }
+
+    fn append_to_current_attribute_value(&mut self, c: char) {
+        if let Some(HtmlToken::StartTag { attributes, .. } | HtmlToken::EndTag { attributes, .. }) =
+            &mut self.current_token
+        {
+            attributes.last_mut().unwrap().1.push(c);
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/parsing.html#flush-code-points-consumed-as-a-character-reference
+    fn flush_code_points_consumed_as_char_ref(&mut self) {
+        for c in std::mem::take(&mut self.temp_buf) {
+            if self.is_char_ref_in_attribute() {
+                self.append_to_current_attribute_value(c);
+            } else {
+                self.emit_token(HtmlToken::Character(c));
+            }
+        }
+    }
+
     /// This is called by the HTML parser.
     pub fn change_state(&mut self, new_state: TokenizationState) {
         self.state = new_state;
     }
 
     /// When a token is emitted, it must immediately be handled by the tree construction stage.
+    #[allow(dead_code)]
     pub fn consume_token(&mut self) -> HtmlToken {
+        self.consume_token_with_span().0
+    }
+
+    /// Returns the next token and the span of the source text from which it was created.
+    /// The input stream must be closed.
+    pub fn consume_token_with_span(&mut self) -> (HtmlToken, SourceSpan) {
+        assert!(
+            self.input_closed,
+            "finish() must be called before consuming all tokens"
+        );
+        self.try_consume_token_with_span().unwrap()
+    }
+
+    /// Returns the next token and its span, or `None` if more input is needed to determine the next token.
+    /// The tokenizer is suspended until more input is fed or the input stream is closed.
+    pub fn try_consume_token_with_span(&mut self) -> Option<(HtmlToken, SourceSpan)> {
         // Buffer the output since multiple tokens can be emitted at once.
         while self.output.is_empty() {
+            // A step is taken only if all the characters it may look at are available.
+            let remaining = self
+                .input
+                .get_items()
+                .len()
+                .saturating_sub(self.input.get_pos());
+            if !self.input_closed && remaining <= MAX_LOOKAHEAD {
+                return None;
+            }
+
             match self.state {
                 // https://html.spec.whatwg.org/multipage/parsing.html#data-state
                 TokenizationState::Data => match self.input.next() {
                     Some(c) => match c {
                         '&' => {
-                            unimplemented!();
+                            self.return_state = TokenizationState::Data;
+                            self.state = TokenizationState::CharacterReference;
                         }
                         '<' => {
                             self.state = TokenizationState::TagOpen;
                         }
                         '\u{0000}' => {
-                            eprintln!("unexpected-null-character parse error");
+                            self.report_error("unexpected-null-character");
+                            self.emit_token(HtmlToken::Character('\u{FFFD}'));
+                        }
+                        _ => {
+                            self.emit_token(HtmlToken::Character(c));
+                        }
+                    },
+                    None => {
+                        self.emit_token(HtmlToken::Eof);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#rcdata-state
+                TokenizationState::RcData => match self.input.next() {
+                    Some(c) => match c {
+                        '&' => {
+                            self.return_state = TokenizationState::RcData;
+                            self.state = TokenizationState::CharacterReference;
+                        }
+                        '<' => {
+                            self.state = TokenizationState::RcDataLessThanSign;
+                        }
+                        '\u{0000}' => {
+                            self.report_error("unexpected-null-character");
                             self.emit_token(HtmlToken::Character('\u{FFFD}'));
                         }
                         _ => {
@@ -147,7 +576,7 @@ impl HtmlTokenizer {
                             self.state = TokenizationState::RawTextLessThanSign;
                         }
                         '\u{0000}' => {
-                            eprintln!("unexpected-null-character parse error");
+                            self.report_error("unexpected-null-character");
                             self.emit_token(HtmlToken::Character('\u{FFFD}'));
                         }
                         _ => {
@@ -159,6 +588,20 @@ impl HtmlTokenizer {
                     }
                 },
 
+                // https://html.spec.whatwg.org/multipage/parsing.html#plaintext-state
+                TokenizationState::PlainText => match self.input.next() {
+                    Some('\u{0000}') => {
+                        self.report_error("unexpected-null-character");
+                        self.emit_token(HtmlToken::Character('\u{FFFD}'));
+                    }
+                    Some(c) => {
+                        self.emit_token(HtmlToken::Character(c));
+                    }
+                    None => {
+                        self.emit_token(HtmlToken::Eof);
+                    }
+                },
+
                 // https://html.spec.whatwg.org/multipage/parsing.html#tag-open-state
                 TokenizationState::TagOpen => match self.input.next() {
                     Some(c) => match c {
@@ -177,13 +620,13 @@ impl HtmlTokenizer {
                             self.allow_reconsume(TokenizationState::TagName);
                         }
                         _ => {
-                            eprintln!("invalid-first-character-of-tag-name parse error");
+                            self.report_error("invalid-first-character-of-tag-name");
                             self.allow_reconsume(TokenizationState::Data);
                             self.emit_token(HtmlToken::Character('<'));
                         }
                     },
                     None => {
-                        eprintln!("eof-before-tag-name parse error");
+                        self.report_error("eof-before-tag-name");
                         self.emit_tokens(vec![HtmlToken::Character('<'), HtmlToken::Eof]);
                     }
                 },
@@ -200,17 +643,17 @@ impl HtmlTokenizer {
                             self.allow_reconsume(TokenizationState::TagName);
                         }
                         '>' => {
-                            eprintln!("missing-end-tag-name parse error");
+                            self.report_error("missing-end-tag-name");
                             self.state = TokenizationState::Data;
                         }
                         _ => {
-                            eprintln!("invalid-first-character-of-tag-name parse error");
+                            self.report_error("invalid-first-character-of-tag-name");
                             self.create_token(HtmlToken::Comment(String::new()));
                             self.allow_reconsume(TokenizationState::BogusComment);
                         }
                     },
                     None => {
-                        eprintln!("eof-before-tag-name parse error");
+                        self.report_error("eof-before-tag-name");
                         self.emit_tokens(vec![
                             HtmlToken::Character('<'),
                             HtmlToken::Character('/'),
@@ -242,12 +685,12 @@ impl HtmlTokenizer {
                             }
                         }
                         '\u{0000}' => {
+                            self.report_error("unexpected-null-character");
                             if let Some(
                                 HtmlToken::StartTag { tag_name, .. }
                                 | HtmlToken::EndTag { tag_name, .. },
                             ) = &mut self.current_token
                             {
-                                eprintln!("unexpected-null-character parse error");
                                 tag_name.push('\u{FFFD}');
                             }
                         }
@@ -262,11 +705,47 @@ impl HtmlTokenizer {
                         }
                     },
                     None => {
-                        eprintln!("eof-in-tag parse error");
+                        self.report_error("eof-in-tag");
                         self.emit_token(HtmlToken::Eof);
                     }
                 },
 
+                // https://html.spec.whatwg.org/multipage/parsing.html#rcdata-less-than-sign-state
+                TokenizationState::RcDataLessThanSign => match self.input.next() {
+                    Some('/') => {
+                        self.temp_buf.clear();
+                        self.state = TokenizationState::RcDataEndTagOpen;
+                    }
+                    _ => {
+                        self.emit_token(HtmlToken::Character('<'));
+                        self.allow_reconsume(TokenizationState::RcData);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#rcdata-end-tag-open-state
+                TokenizationState::RcDataEndTagOpen => match self.input.next() {
+                    Some(c) if c.is_ascii_alphabetic() => {
+                        self.create_token(HtmlToken::EndTag {
+                            tag_name: String::new(),
+                            attributes: vec![],
+                            self_closing: false,
+                        });
+                        self.allow_reconsume(TokenizationState::RcDataEndTagName);
+                    }
+                    _ => {
+                        self.emit_tokens(vec![
+                            HtmlToken::Character('<'),
+                            HtmlToken::Character('/'),
+                        ]);
+                        self.allow_reconsume(TokenizationState::RcData);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#rcdata-end-tag-name-state
+                TokenizationState::RcDataEndTagName => {
+                    self.consume_end_tag_name(TokenizationState::RcData);
+                }
+
                 // https://html.spec.whatwg.org/multipage/parsing.html#rawtext-less-than-sign-state
                 TokenizationState::RawTextLessThanSign => match self.input.next() {
                     Some('/') => {
@@ -274,6 +753,7 @@ impl HtmlTokenizer {
                         self.state = TokenizationState::RawTextEndTagOpen;
                     }
                     _ => {
+                        self.emit_token(HtmlToken::Character('<'));
                         self.allow_reconsume(TokenizationState::RawText);
                     }
                 },
@@ -298,35 +778,343 @@ impl HtmlTokenizer {
                 },
 
                 // https://html.spec.whatwg.org/multipage/parsing.html#rawtext-end-tag-name-state
-                TokenizationState::RawTextEndTagName => match self.input.next() {
-                    Some('\t' | '\n' | '\x0C' | ' ') => {
-                        // todo: check if the current end tag token's tag name is an appropriate end tag name
-                        self.state = TokenizationState::BeforeAttributeName;
+                TokenizationState::RawTextEndTagName => {
+                    self.consume_end_tag_name(TokenizationState::RawText);
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-state
+                TokenizationState::ScriptData => match self.input.next() {
+                    Some(c) => match c {
+                        '<' => {
+                            self.state = TokenizationState::ScriptDataLessThanSign;
+                        }
+                        '\u{0000}' => {
+                            self.report_error("unexpected-null-character");
+                            self.emit_token(HtmlToken::Character('\u{FFFD}'));
+                        }
+                        _ => {
+                            self.emit_token(HtmlToken::Character(c));
+                        }
+                    },
+                    None => {
+                        self.emit_token(HtmlToken::Eof);
                     }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-less-than-sign-state
+                TokenizationState::ScriptDataLessThanSign => match self.input.next() {
                     Some('/') => {
-                        // todo: check if the current end tag token's tag name is an appropriate end tag name
-                        self.state = TokenizationState::SelfClosingStartTag;
+                        self.temp_buf.clear();
+                        self.state = TokenizationState::ScriptDataEndTagOpen;
                     }
-                    Some('>') => {
-                        // todo: check if the current end tag token's tag name is an appropriate end tag name
-                        self.state = TokenizationState::Data;
-                        self.emit_token(self.current_token.clone().unwrap());
+                    Some('!') => {
+                        self.state = TokenizationState::ScriptDataEscapeStart;
+                        self.emit_tokens(vec![
+                            HtmlToken::Character('<'),
+                            HtmlToken::Character('!'),
+                        ]);
+                    }
+                    _ => {
+                        self.emit_token(HtmlToken::Character('<'));
+                        self.allow_reconsume(TokenizationState::ScriptData);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-end-tag-open-state
+                TokenizationState::ScriptDataEndTagOpen => match self.input.next() {
+                    Some(c) if c.is_ascii_alphabetic() => {
+                        self.create_token(HtmlToken::EndTag {
+                            tag_name: String::new(),
+                            attributes: vec![],
+                            self_closing: false,
+                        });
+                        self.allow_reconsume(TokenizationState::ScriptDataEndTagName);
                     }
-                    Some(c) if c.is_ascii() => {
-                        if let Some(HtmlToken::EndTag { tag_name, .. }) = &mut self.current_token {
-                            tag_name.push(c.to_ascii_lowercase());
+                    _ => {
+                        self.emit_tokens(vec![
+                            HtmlToken::Character('<'),
+                            HtmlToken::Character('/'),
+                        ]);
+                        self.allow_reconsume(TokenizationState::ScriptData);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-end-tag-name-state
+                TokenizationState::ScriptDataEndTagName => {
+                    self.consume_end_tag_name(TokenizationState::ScriptData);
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escape-start-state
+                TokenizationState::ScriptDataEscapeStart => match self.input.next() {
+                    Some('-') => {
+                        self.state = TokenizationState::ScriptDataEscapeStartDash;
+                        self.emit_token(HtmlToken::Character('-'));
+                    }
+                    _ => {
+                        self.allow_reconsume(TokenizationState::ScriptData);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escape-start-dash-state
+                TokenizationState::ScriptDataEscapeStartDash => match self.input.next() {
+                    Some('-') => {
+                        self.state = TokenizationState::ScriptDataEscapedDashDash;
+                        self.emit_token(HtmlToken::Character('-'));
+                    }
+                    _ => {
+                        self.allow_reconsume(TokenizationState::ScriptData);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-state
+                TokenizationState::ScriptDataEscaped => match self.input.next() {
+                    Some(c) => match c {
+                        '-' => {
+                            self.state = TokenizationState::ScriptDataEscapedDash;
+                            self.emit_token(HtmlToken::Character('-'));
+                        }
+                        '<' => {
+                            self.state = TokenizationState::ScriptDataEscapedLessThanSign;
+                        }
+                        '\u{0000}' => {
+                            self.report_error("unexpected-null-character");
+                            self.emit_token(HtmlToken::Character('\u{FFFD}'));
+                        }
+                        _ => {
+                            self.emit_token(HtmlToken::Character(c));
                         }
-                        self.temp_buf.push(c);
+                    },
+                    None => {
+                        self.report_error("eof-in-script-html-comment-like-text");
+                        self.emit_token(HtmlToken::Eof);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-dash-state
+                TokenizationState::ScriptDataEscapedDash => match self.input.next() {
+                    Some(c) => match c {
+                        '-' => {
+                            self.state = TokenizationState::ScriptDataEscapedDashDash;
+                            self.emit_token(HtmlToken::Character('-'));
+                        }
+                        '<' => {
+                            self.state = TokenizationState::ScriptDataEscapedLessThanSign;
+                        }
+                        '\u{0000}' => {
+                            self.report_error("unexpected-null-character");
+                            self.state = TokenizationState::ScriptDataEscaped;
+                            self.emit_token(HtmlToken::Character('\u{FFFD}'));
+                        }
+                        _ => {
+                            self.state = TokenizationState::ScriptDataEscaped;
+                            self.emit_token(HtmlToken::Character(c));
+                        }
+                    },
+                    None => {
+                        self.report_error("eof-in-script-html-comment-like-text");
+                        self.emit_token(HtmlToken::Eof);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-dash-dash-state
+                TokenizationState::ScriptDataEscapedDashDash => match self.input.next() {
+                    Some(c) => match c {
+                        '-' => {
+                            self.emit_token(HtmlToken::Character('-'));
+                        }
+                        '<' => {
+                            self.state = TokenizationState::ScriptDataEscapedLessThanSign;
+                        }
+                        '>' => {
+                            self.state = TokenizationState::ScriptData;
+                            self.emit_token(HtmlToken::Character('>'));
+                        }
+                        '\u{0000}' => {
+                            self.report_error("unexpected-null-character");
+                            self.state = TokenizationState::ScriptDataEscaped;
+                            self.emit_token(HtmlToken::Character('\u{FFFD}'));
+                        }
+                        _ => {
+                            self.state = TokenizationState::ScriptDataEscaped;
+                            self.emit_token(HtmlToken::Character(c));
+                        }
+                    },
+                    None => {
+                        self.report_error("eof-in-script-html-comment-like-text");
+                        self.emit_token(HtmlToken::Eof);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-less-than-sign-state
+                TokenizationState::ScriptDataEscapedLessThanSign => match self.input.next() {
+                    Some('/') => {
+                        self.temp_buf.clear();
+                        self.state = TokenizationState::ScriptDataEscapedEndTagOpen;
+                    }
+                    Some(c) if c.is_ascii_alphabetic() => {
+                        self.temp_buf.clear();
+                        self.emit_token(HtmlToken::Character('<'));
+                        self.allow_reconsume(TokenizationState::ScriptDataDoubleEscapeStart);
+                    }
+                    _ => {
+                        self.emit_token(HtmlToken::Character('<'));
+                        self.allow_reconsume(TokenizationState::ScriptDataEscaped);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-end-tag-open-state
+                TokenizationState::ScriptDataEscapedEndTagOpen => match self.input.next() {
+                    Some(c) if c.is_ascii_alphabetic() => {
+                        self.create_token(HtmlToken::EndTag {
+                            tag_name: String::new(),
+                            attributes: vec![],
+                            self_closing: false,
+                        });
+                        self.allow_reconsume(TokenizationState::ScriptDataEscapedEndTagName);
                     }
                     _ => {
                         self.emit_tokens(vec![
                             HtmlToken::Character('<'),
                             HtmlToken::Character('/'),
                         ]);
-                        for c in &self.temp_buf {
-                            self.output.push_back(HtmlToken::Character(*c));
+                        self.allow_reconsume(TokenizationState::ScriptDataEscaped);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-escaped-end-tag-name-state
+                TokenizationState::ScriptDataEscapedEndTagName => {
+                    self.consume_end_tag_name(TokenizationState::ScriptDataEscaped);
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-double-escape-start-state
+                TokenizationState::ScriptDataDoubleEscapeStart => match self.input.next() {
+                    Some(c @ ('\t' | '\n' | '\x0C' | ' ' | '/' | '>')) => {
+                        if self.temp_buf.iter().collect::<String>() == "script" {
+                            self.state = TokenizationState::ScriptDataDoubleEscaped;
+                        } else {
+                            self.state = TokenizationState::ScriptDataEscaped;
+                        }
+                        self.emit_token(HtmlToken::Character(c));
+                    }
+                    Some(c) if c.is_ascii_alphabetic() => {
+                        self.temp_buf.push(c.to_ascii_lowercase());
+                        self.emit_token(HtmlToken::Character(c));
+                    }
+                    _ => {
+                        self.allow_reconsume(TokenizationState::ScriptDataEscaped);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-double-escaped-state
+                TokenizationState::ScriptDataDoubleEscaped => match self.input.next() {
+                    Some(c) => match c {
+                        '-' => {
+                            self.state = TokenizationState::ScriptDataDoubleEscapedDash;
+                            self.emit_token(HtmlToken::Character('-'));
                         }
-                        self.allow_reconsume(TokenizationState::RawText);
+                        '<' => {
+                            self.state = TokenizationState::ScriptDataDoubleEscapedLessThanSign;
+                            self.emit_token(HtmlToken::Character('<'));
+                        }
+                        '\u{0000}' => {
+                            self.report_error("unexpected-null-character");
+                            self.emit_token(HtmlToken::Character('\u{FFFD}'));
+                        }
+                        _ => {
+                            self.emit_token(HtmlToken::Character(c));
+                        }
+                    },
+                    None => {
+                        self.report_error("eof-in-script-html-comment-like-text");
+                        self.emit_token(HtmlToken::Eof);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-double-escaped-dash-state
+                TokenizationState::ScriptDataDoubleEscapedDash => match self.input.next() {
+                    Some(c) => match c {
+                        '-' => {
+                            self.state = TokenizationState::ScriptDataDoubleEscapedDashDash;
+                            self.emit_token(HtmlToken::Character('-'));
+                        }
+                        '<' => {
+                            self.state = TokenizationState::ScriptDataDoubleEscapedLessThanSign;
+                            self.emit_token(HtmlToken::Character('<'));
+                        }
+                        '\u{0000}' => {
+                            self.report_error("unexpected-null-character");
+                            self.state = TokenizationState::ScriptDataDoubleEscaped;
+                            self.emit_token(HtmlToken::Character('\u{FFFD}'));
+                        }
+                        _ => {
+                            self.state = TokenizationState::ScriptDataDoubleEscaped;
+                            self.emit_token(HtmlToken::Character(c));
+                        }
+                    },
+                    None => {
+                        self.report_error("eof-in-script-html-comment-like-text");
+                        self.emit_token(HtmlToken::Eof);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-double-escaped-dash-dash-state
+                TokenizationState::ScriptDataDoubleEscapedDashDash => match self.input.next() {
+                    Some(c) => match c {
+                        '-' => {
+                            self.emit_token(HtmlToken::Character('-'));
+                        }
+                        '<' => {
+                            self.state = TokenizationState::ScriptDataDoubleEscapedLessThanSign;
+                            self.emit_token(HtmlToken::Character('<'));
+                        }
+                        '>' => {
+                            self.state = TokenizationState::ScriptData;
+                            self.emit_token(HtmlToken::Character('>'));
+                        }
+                        '\u{0000}' => {
+                            self.report_error("unexpected-null-character");
+                            self.state = TokenizationState::ScriptDataDoubleEscaped;
+                            self.emit_token(HtmlToken::Character('\u{FFFD}'));
+                        }
+                        _ => {
+                            self.state = TokenizationState::ScriptDataDoubleEscaped;
+                            self.emit_token(HtmlToken::Character(c));
+                        }
+                    },
+                    None => {
+                        self.report_error("eof-in-script-html-comment-like-text");
+                        self.emit_token(HtmlToken::Eof);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-double-escaped-less-than-sign-state
+                TokenizationState::ScriptDataDoubleEscapedLessThanSign => match self.input.next() {
+                    Some('/') => {
+                        self.temp_buf.clear();
+                        self.state = TokenizationState::ScriptDataDoubleEscapeEnd;
+                        self.emit_token(HtmlToken::Character('/'));
+                    }
+                    _ => {
+                        self.allow_reconsume(TokenizationState::ScriptDataDoubleEscaped);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#script-data-double-escape-end-state
+                TokenizationState::ScriptDataDoubleEscapeEnd => match self.input.next() {
+                    Some(c @ ('\t' | '\n' | '\x0C' | ' ' | '/' | '>')) => {
+                        if self.temp_buf.iter().collect::<String>() == "script" {
+                            self.state = TokenizationState::ScriptDataEscaped;
+                        } else {
+                            self.state = TokenizationState::ScriptDataDoubleEscaped;
+                        }
+                        self.emit_token(HtmlToken::Character(c));
+                    }
+                    Some(c) if c.is_ascii_alphabetic() => {
+                        self.temp_buf.push(c.to_ascii_lowercase());
+                        self.emit_token(HtmlToken::Character(c));
+                    }
+                    _ => {
+                        self.allow_reconsume(TokenizationState::ScriptDataDoubleEscaped);
                     }
                 },
 
@@ -338,7 +1126,7 @@ impl HtmlTokenizer {
                             self.allow_reconsume(TokenizationState::AfterAttributeName);
                         }
                         '=' => {
-                            eprintln!("unexpected-equals-sign-before-attribute-name parse error");
+                            self.report_error("unexpected-equals-sign-before-attribute-name");
 
                             if let Some(
                                 HtmlToken::StartTag { attributes, .. }
@@ -366,12 +1154,13 @@ impl HtmlTokenizer {
 
                 // https://html.spec.whatwg.org/multipage/parsing.html#attribute-name-state
                 TokenizationState::AttributeName => match self.input.next() {
-                    // todo: UA needs to check if current attribute's name is already in the attributes list before leaving this state
                     Some(c) => match c {
                         '\t' | '\n' | '\x0C' | ' ' | '/' | '>' => {
+                            self.check_duplicate_attribute();
                             self.allow_reconsume(TokenizationState::AfterAttributeName);
                         }
                         '=' => {
+                            self.check_duplicate_attribute();
                             self.state = TokenizationState::BeforeAttributeValue;
                         }
                         c if c.is_ascii_uppercase() => match &mut self.current_token {
@@ -390,18 +1179,18 @@ impl HtmlTokenizer {
                             }
                         },
                         '\u{0000}' => {
+                            self.report_error("unexpected-null-character");
                             if let Some(
                                 HtmlToken::StartTag { attributes, .. }
                                 | HtmlToken::EndTag { attributes, .. },
                             ) = &mut self.current_token
                             {
-                                eprintln!("unexpected-null-character parse error");
                                 attributes.last_mut().unwrap().0.push('\u{FFFD}');
                             }
                         }
                         _ => {
                             if matches!(c, '"' | '\'' | '<') {
-                                eprintln!("invalid-character-in-attribute-name parse error");
+                                self.report_error("invalid-character-in-attribute-name");
                             }
                             if let Some(
                                 HtmlToken::StartTag { attributes, .. }
@@ -413,6 +1202,7 @@ impl HtmlTokenizer {
                         }
                     },
                     None => {
+                        self.check_duplicate_attribute();
                         self.allow_reconsume(TokenizationState::AfterAttributeName);
                     }
                 },
@@ -443,7 +1233,7 @@ impl HtmlTokenizer {
                         }
                     },
                     None => {
-                        eprintln!("eof-in-tag parse error");
+                        self.report_error("eof-in-tag");
                         self.emit_token(HtmlToken::Eof);
                     }
                 },
@@ -458,7 +1248,7 @@ impl HtmlTokenizer {
                         self.state = TokenizationState::AttributeValueSingleQuoted;
                     }
                     Some('>') => {
-                        eprintln!("missing-attribute-value parse error");
+                        self.report_error("missing-attribute-value");
                         self.state = TokenizationState::Data;
                         self.emit_token(self.current_token.clone().unwrap());
                     }
@@ -474,10 +1264,11 @@ impl HtmlTokenizer {
                             self.state = TokenizationState::AfterAttributeValueQuoted;
                         }
                         '&' => {
-                            unimplemented!();
+                            self.return_state = TokenizationState::AttributeValueDoubleQuoted;
+                            self.state = TokenizationState::CharacterReference;
                         }
                         '\u{0000}' => {
-                            eprintln!("unexpected-null-character parse error");
+                            self.report_error("unexpected-null-character");
                             if let Some(
                                 HtmlToken::StartTag { attributes, .. }
                                 | HtmlToken::EndTag { attributes, .. },
@@ -497,7 +1288,7 @@ impl HtmlTokenizer {
                         }
                     },
                     None => {
-                        eprintln!("eof-in-tag parse error");
+                        self.report_error("eof-in-tag");
                         self.emit_token(HtmlToken::Eof);
                     }
                 },
@@ -509,10 +1300,11 @@ impl HtmlTokenizer {
                             self.state = TokenizationState::AfterAttributeValueQuoted;
                         }
                         '&' => {
-                            unimplemented!();
+                            self.return_state = TokenizationState::AttributeValueSingleQuoted;
+                            self.state = TokenizationState::CharacterReference;
                         }
                         '\u{0000}' => {
-                            eprintln!("unexpected-null-character parse error");
+                            self.report_error("unexpected-null-character");
                             if let Some(
                                 HtmlToken::StartTag { attributes, .. }
                                 | HtmlToken::EndTag { attributes, .. },
@@ -532,54 +1324,55 @@ impl HtmlTokenizer {
                         }
                     },
                     None => {
-                        eprintln!("eof-in-tag parse error");
+                        self.report_error("eof-in-tag");
                         self.emit_token(HtmlToken::Eof);
                     }
                 },
 
                 // https://html.spec.whatwg.org/multipage/parsing.html#attribute-value-(unquoted)-state
-                TokenizationState::AttributeValueUnquoted => {
-                    match self.input.next() {
-                        Some(c) => match c {
-                            '\t' | '\n' | '\x0C' | ' ' => {
-                                self.state = TokenizationState::BeforeAttributeName;
-                            }
-                            '&' => {
-                                unimplemented!();
-                            }
-                            '>' => {
-                                self.state = TokenizationState::Data;
-                                self.emit_token(self.current_token.clone().unwrap());
+                TokenizationState::AttributeValueUnquoted => match self.input.next() {
+                    Some(c) => match c {
+                        '\t' | '\n' | '\x0C' | ' ' => {
+                            self.state = TokenizationState::BeforeAttributeName;
+                        }
+                        '&' => {
+                            self.return_state = TokenizationState::AttributeValueUnquoted;
+                            self.state = TokenizationState::CharacterReference;
+                        }
+                        '>' => {
+                            self.state = TokenizationState::Data;
+                            self.emit_token(self.current_token.clone().unwrap());
+                        }
+                        '\u{0000}' => {
+                            self.report_error("unexpected-null-character");
+                            if let Some(
+                                HtmlToken::StartTag { attributes, .. }
+                                | HtmlToken::EndTag { attributes, .. },
+                            ) = &mut self.current_token
+                            {
+                                attributes.last_mut().unwrap().1.push('\u{FFFD}');
                             }
-                            '\u{0000}' => {
-                                eprintln!("unexpected-null-character parse error");
-                                if let Some(
-                                    HtmlToken::StartTag { attributes, .. }
-                                    | HtmlToken::EndTag { attributes, .. },
-                                ) = &mut self.current_token
-                                {
-                                    attributes.last_mut().unwrap().1.push('\u{FFFD}');
-                                }
+                        }
+                        _ => {
+                            if matches!(c, '"' | '\'' | '<' | '=' | '`') {
+                                self.report_error(
+                                    "unexpected-character-in-unquoted-attribute-value",
+                                );
                             }
-                            _ => {
-                                if matches!(c, '"' | '\'' | '<' | '=' | '`') {
-                                    eprintln!("unexpected-character-in-unquoted-attribute-value parse error");
-                                }
-                                if let Some(
-                                    HtmlToken::StartTag { attributes, .. }
-                                    | HtmlToken::EndTag { attributes, .. },
-                                ) = &mut self.current_token
-                                {
-                                    attributes.last_mut().unwrap().1.push(c);
-                                }
+                            if let Some(
+                                HtmlToken::StartTag { attributes, .. }
+                                | HtmlToken::EndTag { attributes, .. },
+                            ) = &mut self.current_token
+                            {
+                                attributes.last_mut().unwrap().1.push(c);
                             }
-                        },
-                        None => {
-                            eprintln!("eof-in-tag parse error");
-                            self.emit_token(HtmlToken::Eof);
                         }
+                    },
+                    None => {
+                        self.report_error("eof-in-tag");
+                        self.emit_token(HtmlToken::Eof);
                     }
-                }
+                },
 
                 // https://html.spec.whatwg.org/multipage/parsing.html#after-attribute-value-(quoted)-state
                 TokenizationState::AfterAttributeValueQuoted => match self.input.next() {
@@ -595,12 +1388,12 @@ impl HtmlTokenizer {
                             self.emit_token(self.current_token.clone().unwrap());
                         }
                         _ => {
-                            eprintln!("missing-whitespace-between-attributes parse error");
+                            self.report_error("missing-whitespace-between-attributes");
                             self.allow_reconsume(TokenizationState::BeforeAttributeName);
                         }
                     },
                     None => {
-                        eprintln!("eof-in-tag parse error");
+                        self.report_error("eof-in-tag");
                         self.emit_token(HtmlToken::Eof);
                     }
                 },
@@ -620,12 +1413,12 @@ impl HtmlTokenizer {
                             self.emit_token(self.current_token.clone().unwrap());
                         }
                         _ => {
-                            eprintln!("unexpected-solidus-in-tag parse error");
+                            self.report_error("unexpected-solidus-in-tag");
                             self.allow_reconsume(TokenizationState::BeforeAttributeName);
                         }
                     },
                     None => {
-                        eprintln!("eof-in-tag parse error");
+                        self.report_error("eof-in-tag");
                         self.emit_token(HtmlToken::Eof);
                     }
                 },
@@ -638,7 +1431,7 @@ impl HtmlTokenizer {
                             self.emit_token(self.current_token.clone().unwrap());
                         }
                         '\u{0000}' => {
-                            eprintln!("unexpected-null-character parse error");
+                            self.report_error("unexpected-null-character");
                             if let Some(HtmlToken::Comment(comment)) = &mut self.current_token {
                                 comment.push('\u{FFFD}');
                             }
@@ -690,7 +1483,7 @@ impl HtmlTokenizer {
                     {
                         unimplemented!();
                     } else {
-                        eprintln!("incorrectly-opened-comment parse error");
+                        self.report_error("incorrectly-opened-comment");
                         self.create_token(HtmlToken::Comment(String::new()));
                         self.state = TokenizationState::BogusComment;
                     }
@@ -702,7 +1495,7 @@ impl HtmlTokenizer {
                         self.state = TokenizationState::CommentStartDash;
                     }
                     Some('>') => {
-                        eprintln!("abrupt-closing-of-empty-comment parse error");
+                        self.report_error("abrupt-closing-of-empty-comment");
                         self.state = TokenizationState::Data;
                         self.emit_token(self.current_token.clone().unwrap());
                     }
@@ -718,7 +1511,7 @@ impl HtmlTokenizer {
                             self.state = TokenizationState::CommentEnd;
                         }
                         '>' => {
-                            eprintln!("abrupt-closing-of-empty-comment parse error");
+                            self.report_error("abrupt-closing-of-empty-comment");
                             self.state = TokenizationState::Data;
                             self.emit_token(self.current_token.clone().unwrap());
                         }
@@ -730,7 +1523,7 @@ impl HtmlTokenizer {
                         }
                     },
                     None => {
-                        eprintln!("eof-in-comment parse error");
+                        self.report_error("eof-in-comment");
                         self.emit_tokens(vec![self.current_token.clone().unwrap(), HtmlToken::Eof]);
                     }
                 },
@@ -742,13 +1535,13 @@ impl HtmlTokenizer {
                             if let Some(HtmlToken::Comment(comment)) = &mut self.current_token {
                                 comment.push(c);
                             }
-                            todo!()
+                            self.state = TokenizationState::CommentLessThanSign;
                         }
                         '-' => {
                             self.state = TokenizationState::CommentEndDash;
                         }
                         '\u{0000}' => {
-                            eprintln!("unexpected-null-character parse error");
+                            self.report_error("unexpected-null-character");
                             if let Some(HtmlToken::Comment(comment)) = &mut self.current_token {
                                 comment.push('\u{FFFD}');
                             }
@@ -760,11 +1553,58 @@ impl HtmlTokenizer {
                         }
                     },
                     None => {
-                        eprintln!("eof-in-comment parse error");
+                        self.report_error("eof-in-comment");
                         self.emit_tokens(vec![self.current_token.clone().unwrap(), HtmlToken::Eof]);
                     }
                 },
 
+                // https://html.spec.whatwg.org/multipage/parsing.html#comment-less-than-sign-state
+                TokenizationState::CommentLessThanSign => match self.input.next() {
+                    Some(c @ ('!' | '<')) => {
+                        if let Some(HtmlToken::Comment(comment)) = &mut self.current_token {
+                            comment.push(c);
+                        }
+                        if c == '!' {
+                            self.state = TokenizationState::CommentLessThanSignBang;
+                        }
+                    }
+                    _ => {
+                        self.allow_reconsume(TokenizationState::Comment);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#comment-less-than-sign-bang-state
+                TokenizationState::CommentLessThanSignBang => match self.input.next() {
+                    Some('-') => {
+                        self.state = TokenizationState::CommentLessThanSignBangDash;
+                    }
+                    _ => {
+                        self.allow_reconsume(TokenizationState::Comment);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#comment-less-than-sign-bang-dash-state
+                TokenizationState::CommentLessThanSignBangDash => match self.input.next() {
+                    Some('-') => {
+                        self.state = TokenizationState::CommentLessThanSignBangDashDash;
+                    }
+                    _ => {
+                        self.allow_reconsume(TokenizationState::CommentEndDash);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#comment-less-than-sign-bang-dash-dash-state
+                TokenizationState::CommentLessThanSignBangDashDash => match self.input.next() {
+                    Some('>') | None => {
+                        self.allow_reconsume(TokenizationState::CommentEnd);
+                    }
+                    _ => {
+                        // Comments can't be nested, e.g. `<!-- <!-- -->`.
+                        self.report_error("nested-comment");
+                        self.allow_reconsume(TokenizationState::CommentEnd);
+                    }
+                },
+
                 // https://html.spec.whatwg.org/multipage/parsing.html#comment-end-dash-state
                 TokenizationState::CommentEndDash => match self.input.next() {
                     Some(c) => match c {
@@ -779,7 +1619,7 @@ impl HtmlTokenizer {
                         }
                     },
                     None => {
-                        eprintln!("eof-in-comment parse error");
+                        self.report_error("eof-in-comment");
                         self.emit_tokens(vec![self.current_token.clone().unwrap(), HtmlToken::Eof]);
                     }
                 },
@@ -792,7 +1632,7 @@ impl HtmlTokenizer {
                             self.emit_token(self.current_token.clone().unwrap());
                         }
                         '!' => {
-                            unimplemented!();
+                            self.state = TokenizationState::CommentEndBang;
                         }
                         '-' => {
                             if let Some(HtmlToken::Comment(comment)) = &mut self.current_token {
@@ -801,15 +1641,41 @@ impl HtmlTokenizer {
                         }
                         _ => {
                             if let Some(HtmlToken::Comment(comment)) = &mut self.current_token {
-                                comment.push('-');
-                                comment.push('-');
-                                comment.push(c);
+                                comment.push_str("--");
+                            }
+                            self.allow_reconsume(TokenizationState::Comment);
+                        }
+                    },
+                    None => {
+                        self.report_error("eof-in-comment");
+                        self.emit_tokens(vec![self.current_token.clone().unwrap(), HtmlToken::Eof]);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#comment-end-bang-state
+                TokenizationState::CommentEndBang => match self.input.next() {
+                    Some(c) => match c {
+                        '-' => {
+                            if let Some(HtmlToken::Comment(comment)) = &mut self.current_token {
+                                comment.push_str("--!");
+                            }
+                            self.state = TokenizationState::CommentEndDash;
+                        }
+                        '>' => {
+                            // `--!>` closes the comment as `-->` does.
+                            self.report_error("incorrectly-closed-comment");
+                            self.state = TokenizationState::Data;
+                            self.emit_token(self.current_token.clone().unwrap());
+                        }
+                        _ => {
+                            if let Some(HtmlToken::Comment(comment)) = &mut self.current_token {
+                                comment.push_str("--!");
                             }
                             self.allow_reconsume(TokenizationState::Comment);
                         }
                     },
                     None => {
-                        eprintln!("eof-in-comment parse error");
+                        self.report_error("eof-in-comment");
                         self.emit_tokens(vec![self.current_token.clone().unwrap(), HtmlToken::Eof]);
                     }
                 },
@@ -824,12 +1690,12 @@ impl HtmlTokenizer {
                             self.allow_reconsume(TokenizationState::BeforeDoctypeName);
                         }
                         _ => {
-                            eprintln!("missing-whitespace-before-doctype-name parse error");
+                            self.report_error("missing-whitespace-before-doctype-name");
                             self.allow_reconsume(TokenizationState::BeforeDoctypeName);
                         }
                     },
                     None => {
-                        eprintln!("eof-in-doctype parse error");
+                        self.report_error("eof-in-doctype");
                         self.create_token(HtmlToken::Doctype {
                             name: None,
                             public_identifier: None,
@@ -854,7 +1720,7 @@ impl HtmlTokenizer {
                             self.state = TokenizationState::DoctypeName;
                         }
                         '\u{0000}' => {
-                            eprintln!("unexpected-null-character parse error");
+                            self.report_error("unexpected-null-character");
                             self.create_token(HtmlToken::Doctype {
                                 name: '\u{FFFD}'.to_string().into(),
                                 public_identifier: None,
@@ -864,7 +1730,7 @@ impl HtmlTokenizer {
                             self.state = TokenizationState::DoctypeName;
                         }
                         '>' => {
-                            eprintln!("missing-doctype-name parse error");
+                            self.report_error("missing-doctype-name");
                             self.create_token(HtmlToken::Doctype {
                                 name: None,
                                 public_identifier: None,
@@ -885,7 +1751,7 @@ impl HtmlTokenizer {
                         }
                     },
                     None => {
-                        eprintln!("eof-in-doctype parse error");
+                        self.report_error("eof-in-doctype");
                         self.create_token(HtmlToken::Doctype {
                             name: None,
                             public_identifier: None,
@@ -917,7 +1783,7 @@ impl HtmlTokenizer {
                             }
                         },
                         '\u{0000}' => {
-                            eprintln!("unexpected-null-character parse error");
+                            self.report_error("unexpected-null-character");
                             if let Some(HtmlToken::Doctype { name: Some(n), .. }) =
                                 &mut self.current_token
                             {
@@ -933,7 +1799,7 @@ impl HtmlTokenizer {
                         }
                     },
                     None => {
-                        eprintln!("eof-in-doctype parse error");
+                        self.report_error("eof-in-doctype");
                         if let Some(HtmlToken::Doctype { force_quirks, .. }) =
                             &mut self.current_token
                         {
@@ -952,30 +1818,543 @@ impl HtmlTokenizer {
                             self.emit_token(self.current_token.clone().unwrap());
                         }
                         _ => {
-                            unimplemented!();
+                            self.input.rewind(1);
+                            let keyword = self
+                                .input
+                                .peek_chunk(6)
+                                .iter()
+                                .flatten()
+                                .copied()
+                                .collect::<String>();
+                            if keyword.eq_ignore_ascii_case("PUBLIC") {
+                                self.input.forward(6);
+                                self.state = TokenizationState::AfterDoctypePublicKeyword;
+                            } else if keyword.eq_ignore_ascii_case("SYSTEM") {
+                                self.input.forward(6);
+                                self.state = TokenizationState::AfterDoctypeSystemKeyword;
+                            } else {
+                                self.report_error("invalid-character-sequence-after-doctype-name");
+                                self.set_force_quirks();
+                                self.state = TokenizationState::BogusDoctype;
+                            }
                         }
                     },
                     None => {
-                        eprintln!("eof-in-doctype parse error");
-                        if let Some(HtmlToken::Doctype { force_quirks, .. }) =
-                            &mut self.current_token
-                        {
-                            *force_quirks = true;
+                        self.report_error("eof-in-doctype");
+                        self.set_force_quirks();
+                        self.emit_tokens(vec![self.current_token.clone().unwrap(), HtmlToken::Eof]);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-public-keyword-state
+                // https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-system-keyword-state
+                TokenizationState::AfterDoctypePublicKeyword
+                | TokenizationState::AfterDoctypeSystemKeyword => {
+                    let is_public = self.state == TokenizationState::AfterDoctypePublicKeyword;
+                    match self.input.next() {
+                        Some(c) => match c {
+                            '\t' | '\n' | '\x0C' | ' ' => {
+                                self.state = if is_public {
+                                    TokenizationState::BeforeDoctypePublicIdentifier
+                                } else {
+                                    TokenizationState::BeforeDoctypeSystemIdentifier
+                                };
+                            }
+                            '"' | '\'' => {
+                                if is_public {
+                                    self.report_error(
+                                        "missing-whitespace-after-doctype-public-keyword",
+                                    );
+                                } else {
+                                    self.report_error(
+                                        "missing-whitespace-after-doctype-system-keyword",
+                                    );
+                                }
+                                self.init_doctype_identifier(is_public);
+                                self.state = match (is_public, c) {
+                                    (true, '"') => {
+                                        TokenizationState::DoctypePublicIdentifierDoubleQuoted
+                                    }
+                                    (true, _) => {
+                                        TokenizationState::DoctypePublicIdentifierSingleQuoted
+                                    }
+                                    (false, '"') => {
+                                        TokenizationState::DoctypeSystemIdentifierDoubleQuoted
+                                    }
+                                    (false, _) => {
+                                        TokenizationState::DoctypeSystemIdentifierSingleQuoted
+                                    }
+                                };
+                            }
+                            '>' => {
+                                if is_public {
+                                    self.report_error("missing-doctype-public-identifier");
+                                } else {
+                                    self.report_error("missing-doctype-system-identifier");
+                                }
+                                self.set_force_quirks();
+                                self.state = TokenizationState::Data;
+                                self.emit_token(self.current_token.clone().unwrap());
+                            }
+                            _ => {
+                                if is_public {
+                                    self.report_error(
+                                        "missing-quote-before-doctype-public-identifier",
+                                    );
+                                } else {
+                                    self.report_error(
+                                        "missing-quote-before-doctype-system-identifier",
+                                    );
+                                }
+                                self.set_force_quirks();
+                                self.allow_reconsume(TokenizationState::BogusDoctype);
+                            }
+                        },
+                        None => {
+                            self.report_error("eof-in-doctype");
+                            self.set_force_quirks();
+                            self.emit_tokens(vec![
+                                self.current_token.clone().unwrap(),
+                                HtmlToken::Eof,
+                            ]);
+                        }
+                    }
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#before-doctype-public-identifier-state
+                // https://html.spec.whatwg.org/multipage/parsing.html#before-doctype-system-identifier-state
+                TokenizationState::BeforeDoctypePublicIdentifier
+                | TokenizationState::BeforeDoctypeSystemIdentifier => {
+                    let is_public = self.state == TokenizationState::BeforeDoctypePublicIdentifier;
+                    match self.input.next() {
+                        Some(c) => match c {
+                            '\t' | '\n' | '\x0C' | ' ' => {}
+                            '"' | '\'' => {
+                                self.init_doctype_identifier(is_public);
+                                self.state = match (is_public, c) {
+                                    (true, '"') => {
+                                        TokenizationState::DoctypePublicIdentifierDoubleQuoted
+                                    }
+                                    (true, _) => {
+                                        TokenizationState::DoctypePublicIdentifierSingleQuoted
+                                    }
+                                    (false, '"') => {
+                                        TokenizationState::DoctypeSystemIdentifierDoubleQuoted
+                                    }
+                                    (false, _) => {
+                                        TokenizationState::DoctypeSystemIdentifierSingleQuoted
+                                    }
+                                };
+                            }
+                            '>' => {
+                                if is_public {
+                                    self.report_error("missing-doctype-public-identifier");
+                                } else {
+                                    self.report_error("missing-doctype-system-identifier");
+                                }
+                                self.set_force_quirks();
+                                self.state = TokenizationState::Data;
+                                self.emit_token(self.current_token.clone().unwrap());
+                            }
+                            _ => {
+                                if is_public {
+                                    self.report_error(
+                                        "missing-quote-before-doctype-public-identifier",
+                                    );
+                                } else {
+                                    self.report_error(
+                                        "missing-quote-before-doctype-system-identifier",
+                                    );
+                                }
+                                self.set_force_quirks();
+                                self.allow_reconsume(TokenizationState::BogusDoctype);
+                            }
+                        },
+                        None => {
+                            self.report_error("eof-in-doctype");
+                            self.set_force_quirks();
+                            self.emit_tokens(vec![
+                                self.current_token.clone().unwrap(),
+                                HtmlToken::Eof,
+                            ]);
+                        }
+                    }
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#doctype-public-identifier-(double-quoted)-state
+                // https://html.spec.whatwg.org/multipage/parsing.html#doctype-public-identifier-(single-quoted)-state
+                // https://html.spec.whatwg.org/multipage/parsing.html#doctype-system-identifier-(double-quoted)-state
+                // https://html.spec.whatwg.org/multipage/parsing.html#doctype-system-identifier-(single-quoted)-state
+                TokenizationState::DoctypePublicIdentifierDoubleQuoted
+                | TokenizationState::DoctypePublicIdentifierSingleQuoted
+                | TokenizationState::DoctypeSystemIdentifierDoubleQuoted
+                | TokenizationState::DoctypeSystemIdentifierSingleQuoted => {
+                    let (is_public, quote) = match self.state {
+                        TokenizationState::DoctypePublicIdentifierDoubleQuoted => (true, '"'),
+                        TokenizationState::DoctypePublicIdentifierSingleQuoted => (true, '\''),
+                        TokenizationState::DoctypeSystemIdentifierDoubleQuoted => (false, '"'),
+                        _ => (false, '\''),
+                    };
+                    match self.input.next() {
+                        Some(c) if c == quote => {
+                            self.state = if is_public {
+                                TokenizationState::AfterDoctypePublicIdentifier
+                            } else {
+                                TokenizationState::AfterDoctypeSystemIdentifier
+                            };
+                        }
+                        Some('\u{0000}') => {
+                            self.report_error("unexpected-null-character");
+                            self.append_to_doctype_identifier(is_public, '\u{FFFD}');
+                        }
+                        Some('>') => {
+                            if is_public {
+                                self.report_error("abrupt-doctype-public-identifier");
+                            } else {
+                                self.report_error("abrupt-doctype-system-identifier");
+                            }
+                            self.set_force_quirks();
+                            self.state = TokenizationState::Data;
+                            self.emit_token(self.current_token.clone().unwrap());
+                        }
+                        Some(c) => {
+                            self.append_to_doctype_identifier(is_public, c);
+                        }
+                        None => {
+                            self.report_error("eof-in-doctype");
+                            self.set_force_quirks();
+                            self.emit_tokens(vec![
+                                self.current_token.clone().unwrap(),
+                                HtmlToken::Eof,
+                            ]);
+                        }
+                    }
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-public-identifier-state
+                // https://html.spec.whatwg.org/multipage/parsing.html#between-doctype-public-and-system-identifiers-state
+                TokenizationState::AfterDoctypePublicIdentifier
+                | TokenizationState::BetweenDoctypePublicAndSystemIdentifiers => {
+                    let is_after_public_identifier =
+                        self.state == TokenizationState::AfterDoctypePublicIdentifier;
+                    match self.input.next() {
+                        Some(c) => match c {
+                            '\t' | '\n' | '\x0C' | ' ' => {
+                                self.state =
+                                    TokenizationState::BetweenDoctypePublicAndSystemIdentifiers;
+                            }
+                            '>' => {
+                                self.state = TokenizationState::Data;
+                                self.emit_token(self.current_token.clone().unwrap());
+                            }
+                            '"' | '\'' => {
+                                if is_after_public_identifier {
+                                    self.report_error("missing-whitespace-between-doctype-public-and-system-identifiers");
+                                }
+                                self.init_doctype_identifier(false);
+                                self.state = if c == '"' {
+                                    TokenizationState::DoctypeSystemIdentifierDoubleQuoted
+                                } else {
+                                    TokenizationState::DoctypeSystemIdentifierSingleQuoted
+                                };
+                            }
+                            _ => {
+                                self.report_error("missing-quote-before-doctype-system-identifier");
+                                self.set_force_quirks();
+                                self.allow_reconsume(TokenizationState::BogusDoctype);
+                            }
+                        },
+                        None => {
+                            self.report_error("eof-in-doctype");
+                            self.set_force_quirks();
+                            self.emit_tokens(vec![
+                                self.current_token.clone().unwrap(),
+                                HtmlToken::Eof,
+                            ]);
+                        }
+                    }
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#after-doctype-system-identifier-state
+                TokenizationState::AfterDoctypeSystemIdentifier => match self.input.next() {
+                    Some(c) => match c {
+                        '\t' | '\n' | '\x0C' | ' ' => {}
+                        '>' => {
+                            self.state = TokenizationState::Data;
+                            self.emit_token(self.current_token.clone().unwrap());
                         }
+                        _ => {
+                            // This does not set the force-quirks flag.
+                            self.report_error(
+                                "unexpected-character-after-doctype-system-identifier",
+                            );
+                            self.allow_reconsume(TokenizationState::BogusDoctype);
+                        }
+                    },
+                    None => {
+                        self.report_error("eof-in-doctype");
+                        self.set_force_quirks();
+                        self.emit_tokens(vec![self.current_token.clone().unwrap(), HtmlToken::Eof]);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#bogus-doctype-state
+                TokenizationState::BogusDoctype => match self.input.next() {
+                    Some('>') => {
+                        self.state = TokenizationState::Data;
+                        self.emit_token(self.current_token.clone().unwrap());
+                    }
+                    Some('\u{0000}') => {
+                        self.report_error("unexpected-null-character");
+                    }
+                    Some(_) => {}
+                    None => {
                         self.emit_tokens(vec![self.current_token.clone().unwrap(), HtmlToken::Eof]);
                     }
                 },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#character-reference-state
+                TokenizationState::CharacterReference => {
+                    self.temp_buf = vec!['&'];
+                    match self.input.next() {
+                        Some(c) if c.is_ascii_alphanumeric() => {
+                            self.allow_reconsume(TokenizationState::NamedCharacterReference);
+                        }
+                        Some('#') => {
+                            self.temp_buf.push('#');
+                            self.state = TokenizationState::NumericCharacterReference;
+                        }
+                        _ => {
+                            self.flush_code_points_consumed_as_char_ref();
+                            self.allow_reconsume(self.return_state);
+                        }
+                    }
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#named-character-reference-state
+                TokenizationState::NamedCharacterReference => {
+                    let lookahead = self
+                        .input
+                        .peek_chunk(MAX_NAMED_CHAR_REF_LEN)
+                        .into_iter()
+                        .map_while(|c| c.copied())
+                        .collect::<Vec<_>>();
+                    match match_longest_named_char_ref(&lookahead) {
+                        Some((name, chars)) => {
+                            self.input.forward(name.chars().count());
+                            self.temp_buf.extend(name.chars());
+                            // For historical reasons, the consumed characters are flushed as they are
+                            // if the reference is in an attribute and is followed by '=' or an alphanumeric.
+                            let is_historical = self.is_char_ref_in_attribute()
+                                && !name.ends_with(';')
+                                && matches!(self.input.peek(), Some(c) if *c == '=' || c.is_ascii_alphanumeric());
+                            if !is_historical {
+                                if !name.ends_with(';') {
+                                    self.report_error(
+                                        "missing-semicolon-after-character-reference",
+                                    );
+                                }
+                                self.temp_buf = chars.chars().collect();
+                            }
+                            self.flush_code_points_consumed_as_char_ref();
+                            self.state = self.return_state;
+                        }
+                        None => {
+                            self.flush_code_points_consumed_as_char_ref();
+                            self.state = TokenizationState::AmbiguousAmpersand;
+                        }
+                    }
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#ambiguous-ampersand-state
+                TokenizationState::AmbiguousAmpersand => match self.input.next() {
+                    Some(c) if c.is_ascii_alphanumeric() => {
+                        if self.is_char_ref_in_attribute() {
+                            self.append_to_current_attribute_value(c);
+                        } else {
+                            self.emit_token(HtmlToken::Character(c));
+                        }
+                    }
+                    Some(';') => {
+                        self.report_error("unknown-named-character-reference");
+                        self.allow_reconsume(self.return_state);
+                    }
+                    _ => {
+                        self.allow_reconsume(self.return_state);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-state
+                TokenizationState::NumericCharacterReference => {
+                    self.char_ref_code = 0;
+                    match self.input.next() {
+                        Some(c @ ('x' | 'X')) => {
+                            self.temp_buf.push(c);
+                            self.state = TokenizationState::HexadecimalCharacterReferenceStart;
+                        }
+                        _ => {
+                            self.allow_reconsume(TokenizationState::DecimalCharacterReferenceStart);
+                        }
+                    }
+                }
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#hexadecimal-character-reference-start-state
+                TokenizationState::HexadecimalCharacterReferenceStart => match self.input.next() {
+                    Some(c) if c.is_ascii_hexdigit() => {
+                        self.allow_reconsume(TokenizationState::HexadecimalCharacterReference);
+                    }
+                    _ => {
+                        self.report_error("absence-of-digits-in-numeric-character-reference");
+                        self.flush_code_points_consumed_as_char_ref();
+                        self.allow_reconsume(self.return_state);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#decimal-character-reference-start-state
+                TokenizationState::DecimalCharacterReferenceStart => match self.input.next() {
+                    Some(c) if c.is_ascii_digit() => {
+                        self.allow_reconsume(TokenizationState::DecimalCharacterReference);
+                    }
+                    _ => {
+                        self.report_error("absence-of-digits-in-numeric-character-reference");
+                        self.flush_code_points_consumed_as_char_ref();
+                        self.allow_reconsume(self.return_state);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#hexadecimal-character-reference-state
+                TokenizationState::HexadecimalCharacterReference => match self.input.next() {
+                    Some(c) if c.is_ascii_hexdigit() => {
+                        // Saturate so that huge values are still reported as out of the Unicode range.
+                        self.char_ref_code = self
+                            .char_ref_code
+                            .saturating_mul(16)
+                            .saturating_add(c.to_digit(16).unwrap());
+                    }
+                    Some(';') => {
+                        self.state = TokenizationState::NumericCharacterReferenceEnd;
+                    }
+                    _ => {
+                        self.report_error("missing-semicolon-after-character-reference");
+                        self.allow_reconsume(TokenizationState::NumericCharacterReferenceEnd);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#decimal-character-reference-state
+                TokenizationState::DecimalCharacterReference => match self.input.next() {
+                    Some(c) if c.is_ascii_digit() => {
+                        self.char_ref_code = self
+                            .char_ref_code
+                            .saturating_mul(10)
+                            .saturating_add(c.to_digit(10).unwrap());
+                    }
+                    Some(';') => {
+                        self.state = TokenizationState::NumericCharacterReferenceEnd;
+                    }
+                    _ => {
+                        self.report_error("missing-semicolon-after-character-reference");
+                        self.allow_reconsume(TokenizationState::NumericCharacterReferenceEnd);
+                    }
+                },
+
+                // https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-end-state
+                TokenizationState::NumericCharacterReferenceEnd => {
+                    let code = match self.char_ref_code {
+                        0x00 => {
+                            self.report_error("null-character-reference");
+                            0xFFFD
+                        }
+                        c if c > 0x10FFFF => {
+                            self.report_error("character-reference-outside-unicode-range");
+                            0xFFFD
+                        }
+                        0xD800..=0xDFFF => {
+                            self.report_error("surrogate-character-reference");
+                            0xFFFD
+                        }
+                        c if is_noncharacter(c) => {
+                            self.report_error("noncharacter-character-reference");
+                            c
+                        }
+                        c if c == 0x0D || (is_control(c) && !is_ascii_whitespace(c)) => {
+                            self.report_error("control-character-reference");
+                            replace_c1_control(c).unwrap_or(c)
+                        }
+                        c => c,
+                    };
+                    self.temp_buf = vec![char::from_u32(code).unwrap()];
+                    self.flush_code_points_consumed_as_char_ref();
+                    self.state = self.return_state;
+                }
             }
         }
 
         assert!(!self.output.is_empty());
-        self.output.pop_front().unwrap()
+        let (token, range) = self.output.pop_front().unwrap();
+        self.token_counts[token.get_type_index()] += 1;
+        let span = self
+            .span_locator
+            .span(self.input.get_items(), range.start, range.end);
+        Some((token, span))
+    }
+}
+
+/// https://infra.spec.whatwg.org/#noncharacter
+fn is_noncharacter(c: u32) -> bool {
+    matches!(c, 0xFDD0..=0xFDEF) || (c & 0xFFFE == 0xFFFE && c <= 0x10FFFF)
+}
+
+/// https://infra.spec.whatwg.org/#control
+fn is_control(c: u32) -> bool {
+    matches!(c, 0x00..=0x1F | 0x7F..=0x9F)
+}
+
+/// https://infra.spec.whatwg.org/#ascii-whitespace
+fn is_ascii_whitespace(c: u32) -> bool {
+    matches!(c, 0x09 | 0x0A | 0x0C | 0x0D | 0x20)
+}
+
+/// Returns the code point that a C1 control character reference is replaced with.
+/// https://html.spec.whatwg.org/multipage/parsing.html#numeric-character-reference-end-state
+fn replace_c1_control(c: u32) -> Option<u32> {
+    match c {
+        0x80 => Some(0x20AC),
+        0x82 => Some(0x201A),
+        0x83 => Some(0x0192),
+        0x84 => Some(0x201E),
+        0x85 => Some(0x2026),
+        0x86 => Some(0x2020),
+        0x87 => Some(0x2021),
+        0x88 => Some(0x02C6),
+        0x89 => Some(0x2030),
+        0x8A => Some(0x0160),
+        0x8B => Some(0x2039),
+        0x8C => Some(0x0152),
+        0x8E => Some(0x017D),
+        0x91 => Some(0x2018),
+        0x92 => Some(0x2019),
+        0x93 => Some(0x201C),
+        0x94 => Some(0x201D),
+        0x95 => Some(0x2022),
+        0x96 => Some(0x2013),
+        0x97 => Some(0x2014),
+        0x98 => Some(0x02DC),
+        0x99 => Some(0x2122),
+        0x9A => Some(0x0161),
+        0x9B => Some(0x203A),
+        0x9C => Some(0x0153),
+        0x9E => Some(0x017E),
+        0x9F => Some(0x0178),
+        _ => None,
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::cell::RefCell;
+
     use super::*;
+    use crate::renderer::parse_error::ParseErrorCollector;
 
     #[test]
     fn tokenize_only_html_tag() {
@@ -1160,4 +2539,196 @@ mod tests {
         );
         assert_eq!(tokenizer.consume_token(), HtmlToken::Eof);
     }
+
+    #[test]
+    fn tokenize_doctype_identifiers() {
+        let mut tokenizer = HtmlTokenizer::new(
+            "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\" 'http://www.w3.org/TR/html4/strict.dtd'><!doctype html SYSTEM \"about:legacy-compat\"><!DOCTYPE html PUBLIC x>",
+        );
+        assert_eq!(
+            tokenizer.consume_token(),
+            HtmlToken::Doctype {
+                name: "html".to_string().into(),
+                public_identifier: "-//W3C//DTD HTML 4.01//EN".to_string().into(),
+                system_identifier: "http://www.w3.org/TR/html4/strict.dtd".to_string().into(),
+                force_quirks: false
+            }
+        );
+        assert_eq!(
+            tokenizer.consume_token(),
+            HtmlToken::Doctype {
+                name: "html".to_string().into(),
+                public_identifier: None,
+                system_identifier: "about:legacy-compat".to_string().into(),
+                force_quirks: false
+            }
+        );
+        assert_eq!(
+            tokenizer.consume_token(),
+            HtmlToken::Doctype {
+                name: "html".to_string().into(),
+                public_identifier: None,
+                system_identifier: None,
+                force_quirks: true
+            }
+        );
+        assert_eq!(tokenizer.consume_token(), HtmlToken::Eof);
+    }
+
+    #[test]
+    fn tokenize_char_refs_in_text() {
+        let mut tokenizer =
+            HtmlTokenizer::new("&amp;&lt&#169;&#x41;&#X1F600;&#128;&#0;&notin;&notit;&foo; &");
+        let mut chars = String::new();
+        loop {
+            match tokenizer.consume_token() {
+                HtmlToken::Character(c) => chars.push(c),
+                HtmlToken::Eof => break,
+                t => panic!("unexpected token: {:?}", t),
+            }
+        }
+        assert_eq!(
+            chars,
+            "&<\u{A9}A\u{1F600}\u{20AC}\u{FFFD}\u{2209}\u{AC}it;&foo; &"
+        );
+    }
+
+    #[test]
+    fn tokenize_char_refs_in_attribute() {
+        let mut tokenizer =
+            HtmlTokenizer::new("<a href=\"?a=1&copy=2&amp;b=3\" title='&quot;&#34' alt=&lt;x&gt>");
+        assert_eq!(
+            tokenizer.consume_token(),
+            HtmlToken::StartTag {
+                tag_name: "a".to_string(),
+                attributes: vec![
+                    ("href".to_string(), "?a=1&copy=2&b=3".to_string()),
+                    ("title".to_string(), "\"\"".to_string()),
+                    ("alt".to_string(), "<x>".to_string()),
+                ],
+                self_closing: false
+            }
+        );
+        assert_eq!(tokenizer.consume_token(), HtmlToken::Eof);
+    }
+
+    #[test]
+    fn tokenize_appropriate_end_tags() {
+        let tokenize = |state: TokenizationState, html: &str| {
+            let mut tokenizer = HtmlTokenizer::new(html);
+            tokenizer.last_start_tag_name = Some("title".to_string());
+            tokenizer.change_state(state);
+            let mut tokens = Vec::new();
+            loop {
+                match tokenizer.consume_token() {
+                    HtmlToken::Eof => break,
+                    t => tokens.push(t),
+                }
+            }
+            tokens
+        };
+        let chars = |s: &str| s.chars().map(HtmlToken::Character).collect::<Vec<_>>();
+        let end_tag = HtmlToken::EndTag {
+            tag_name: "title".to_string(),
+            attributes: vec![],
+            self_closing: false,
+        };
+
+        // Character references are decoded only in RCDATA.
+        assert_eq!(
+            tokenize(TokenizationState::RcData, "a</b>&lt;</title>"),
+            [chars("a</b><"), vec![end_tag.clone()]].concat()
+        );
+        assert_eq!(
+            tokenize(TokenizationState::RawText, "a<b</x>&lt;</TITLE>"),
+            [chars("a<b</x>&lt;"), vec![end_tag]].concat()
+        );
+        assert_eq!(
+            tokenize(TokenizationState::RcData, "</titlex>"),
+            chars("</titlex>")
+        );
+    }
+    #[test]
+    fn drop_duplicate_attributes() {
+        let collector = Rc::new(RefCell::new(ParseErrorCollector::default()));
+        let mut tokenizer = HtmlTokenizer::new("<div id=a ID=\"b\" class=x id/></div dir=ltr Dir>");
+        tokenizer.set_error_handler(Rc::clone(&collector) as _);
+        assert_eq!(
+            tokenizer.consume_token(),
+            HtmlToken::StartTag {
+                tag_name: "div".to_string(),
+                attributes: vec![
+                    ("id".to_string(), "a".to_string()),
+                    ("class".to_string(), "x".to_string()),
+                ],
+                self_closing: true
+            }
+        );
+        assert_eq!(
+            tokenizer.consume_token(),
+            HtmlToken::EndTag {
+                tag_name: "div".to_string(),
+                attributes: vec![("dir".to_string(), "ltr".to_string())],
+                self_closing: false
+            }
+        );
+        assert_eq!(
+            collector
+                .borrow_mut()
+                .take_errors()
+                .iter()
+                .map(|e| (e.code.as_str(), e.detail.clone().unwrap_or_default()))
+                .filter(|(code, _)| *code == "duplicate-attribute")
+                .collect::<Vec<_>>(),
+            vec![
+                ("duplicate-attribute", "id".to_string()),
+                ("duplicate-attribute", "id".to_string()),
+                ("duplicate-attribute", "dir".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_comments() {
+        let collector = Rc::new(RefCell::new(ParseErrorCollector::default()));
+        let mut tokenizer = HtmlTokenizer::new("<!--a<b<!c--><!--<!-- d--><!--e--!><!--h<!--->");
+        tokenizer.set_error_handler(Rc::clone(&collector) as _);
+        let mut tokens = Vec::new();
+        loop {
+            let token = tokenizer.consume_token();
+            if token == HtmlToken::Eof {
+                break;
+            }
+            tokens.push(token);
+        }
+        assert_eq!(
+            tokens,
+            [
+                HtmlToken::Comment("a<b<!c".to_string()),
+                HtmlToken::Comment("<!-- d".to_string()),
+                HtmlToken::Comment("e".to_string()),
+                HtmlToken::Comment("h<!-".to_string()),
+            ]
+        );
+        assert_eq!(
+            collector
+                .borrow_mut()
+                .take_errors()
+                .iter()
+                .map(|e| e.code.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "nested-comment",
+                "incorrectly-closed-comment",
+                "nested-comment"
+            ]
+        );
+
+        // The characters after `--!` are part of the comment unless it is closed.
+        let mut tokenizer = HtmlTokenizer::new("<!--a--!b--!-->");
+        assert_eq!(
+            tokenizer.consume_token(),
+            HtmlToken::Comment("a--!b--!".to_string())
+        );
+    }
 }
@@ -1,4 +1,18 @@
+pub mod bidi;
 pub mod block;
 pub mod box_model;
+pub mod font;
+pub mod geometry;
 pub mod inline;
+pub mod intersection;
+pub mod line_box;
+pub mod line_break;
+pub mod lines;
+pub mod math;
+pub mod overflow;
+pub mod positioned;
+pub mod replaced;
+pub mod resize;
+pub mod stacking;
+pub mod table;
 pub mod text;
@@ -0,0 +1,504 @@
+use crate::renderer::style::property::{DirectionProp, UnicodeBidiProp};
+
+/// The bidirectional character types.
+/// https://www.unicode.org/reports/tr9/#Bidirectional_Character_Types
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BidiClass {
+    /// Left-to-right
+    L,
+    /// Right-to-left
+    R,
+    /// Right-to-left Arabic
+    Al,
+    /// European number
+    En,
+    /// European number separator
+    Es,
+    /// European number terminator
+    Et,
+    /// Arabic number
+    An,
+    /// Common number separator
+    Cs,
+    /// Nonspacing mark
+    Nsm,
+    /// Boundary neutral
+    Bn,
+    /// Paragraph separator
+    B,
+    /// Segment separator
+    S,
+    /// Whitespace
+    Ws,
+    /// Other neutrals
+    On,
+    /// Left-to-right embedding
+    Lre,
+    /// Left-to-right override
+    Lro,
+    /// Right-to-left embedding
+    Rle,
+    /// Right-to-left override
+    Rlo,
+    /// Pop directional format
+    Pdf,
+    /// Left-to-right isolate
+    Lri,
+    /// Right-to-left isolate
+    Rli,
+    /// First strong isolate
+    Fsi,
+    /// Pop directional isolate
+    Pdi,
+}
+
+/// The maximum explicit embedding level.
+const MAX_DEPTH: u8 = 125;
+
+/// Returns the bidirectional character type of the character.
+/// todo: Use the full data of the Unicode Character Database. The characters which aren't listed here are
+/// approximated by the Unicode blocks of the right-to-left scripts and by whether they are alphabetic.
+/// https://www.unicode.org/reports/tr9/#Bidirectional_Character_Types
+pub fn get_bidi_class(c: char) -> BidiClass {
+    use BidiClass::*;
+    match c {
+        '\u{202A}' => Lre,
+        '\u{202B}' => Rle,
+        '\u{202C}' => Pdf,
+        '\u{202D}' => Lro,
+        '\u{202E}' => Rlo,
+        '\u{2066}' => Lri,
+        '\u{2067}' => Rli,
+        '\u{2068}' => Fsi,
+        '\u{2069}' => Pdi,
+        '\u{200E}' => L,
+        '\u{200F}' => R,
+        '\u{061C}' => Al,
+        '\n' | '\r' | '\u{1C}'..='\u{1E}' | '\u{85}' | '\u{2029}' => B,
+        '\t' | '\u{0B}' | '\u{1F}' => S,
+        ' '
+        | '\u{0C}'
+        | '\u{1680}'
+        | '\u{2000}'..='\u{200A}'
+        | '\u{2028}'
+        | '\u{205F}'
+        | '\u{3000}' => Ws,
+        '\u{00}'..='\u{08}'
+        | '\u{0E}'..='\u{1B}'
+        | '\u{7F}'..='\u{84}'
+        | '\u{86}'..='\u{9F}'
+        | '\u{AD}'
+        | '\u{200B}'..='\u{200D}'
+        | '\u{2060}'..='\u{2064}'
+        | '\u{FEFF}' => Bn,
+        '0'..='9'
+        | '\u{B2}'
+        | '\u{B3}'
+        | '\u{B9}'
+        | '\u{06F0}'..='\u{06F9}'
+        | '\u{2070}'..='\u{2079}'
+        | '\u{2080}'..='\u{2089}'
+        | '\u{FF10}'..='\u{FF19}' => En,
+        '+' | '-' | '\u{207A}' | '\u{207B}' | '\u{2212}' | '\u{FF0B}' | '\u{FF0D}' => Es,
+        '#'..='%'
+        | '\u{A2}'..='\u{A5}'
+        | '\u{B0}'
+        | '\u{B1}'
+        | '\u{2030}'..='\u{2034}'
+        | '\u{20A0}'..='\u{20CF}'
+        | '\u{FF03}'..='\u{FF05}' => Et,
+        '\u{0600}'..='\u{0605}' | '\u{0660}'..='\u{0669}' | '\u{066B}' | '\u{066C}' => An,
+        ',' | '.' | '/' | ':' | '\u{A0}' | '\u{060C}' | '\u{202F}' | '\u{FF0C}' | '\u{FF0E}'
+        | '\u{FF0F}' | '\u{FF1A}' => Cs,
+        '\u{0300}'..='\u{036F}'
+        | '\u{0483}'..='\u{0489}'
+        | '\u{0591}'..='\u{05BD}'
+        | '\u{05BF}'
+        | '\u{05C1}'
+        | '\u{05C2}'
+        | '\u{05C4}'
+        | '\u{05C5}'
+        | '\u{05C7}'
+        | '\u{0610}'..='\u{061A}'
+        | '\u{064B}'..='\u{065F}'
+        | '\u{0670}'
+        | '\u{06D6}'..='\u{06DC}'
+        | '\u{06DF}'..='\u{06E4}'
+        | '\u{06E7}'
+        | '\u{06E8}'
+        | '\u{06EA}'..='\u{06ED}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}' => Nsm,
+        '\u{0590}'..='\u{05FF}'
+        | '\u{07C0}'..='\u{085F}'
+        | '\u{FB1D}'..='\u{FB4F}'
+        | '\u{10800}'..='\u{10CFF}'
+        | '\u{1E800}'..='\u{1EDFF}' => R,
+        '\u{0600}'..='\u{07BF}'
+        | '\u{0860}'..='\u{08FF}'
+        | '\u{FB50}'..='\u{FDFF}'
+        | '\u{FE70}'..='\u{FEFE}'
+        | '\u{10D00}'..='\u{10FFF}'
+        | '\u{1EE00}'..='\u{1EEFF}' => Al,
+        _ if c.is_alphabetic() || c.is_numeric() => L,
+        _ => On,
+    }
+}
+
+/// Returns the classes of the control characters which the start and the end of an inline box are treated
+/// as by `unicode-bidi`.
+/// https://drafts.csswg.org/css-writing-modes/#unicode-bidi
+pub fn get_box_controls(
+    direction: &DirectionProp,
+    unicode_bidi: &UnicodeBidiProp,
+) -> (Vec<BidiClass>, Vec<BidiClass>) {
+    use BidiClass::*;
+    let (embed, bidi_override, isolate) = if direction.is_rtl() {
+        (Rle, Rlo, Rli)
+    } else {
+        (Lre, Lro, Lri)
+    };
+    match unicode_bidi.as_str() {
+        "embed" => (vec![embed], vec![Pdf]),
+        "isolate" => (vec![isolate], vec![Pdi]),
+        "bidi-override" => (vec![bidi_override], vec![Pdf]),
+        "isolate-override" => (vec![isolate, bidi_override], vec![Pdf, Pdi]),
+        "plaintext" => (vec![Fsi], vec![Pdi]),
+        _ => (Vec::new(), Vec::new()),
+    }
+}
+
+/// Returns the embedding level given by the first strong character out of the isolates, which is looked
+/// up until the end of the paragraph or of the isolate containing the characters.
+/// https://www.unicode.org/reports/tr9/#P2
+pub fn find_first_strong_level(classes: &[BidiClass]) -> Option<u8> {
+    use BidiClass::*;
+    let mut isolates = 0;
+    for class in classes {
+        match class {
+            Lri | Rli | Fsi => isolates += 1,
+            Pdi if isolates > 0 => isolates -= 1,
+            Pdi | B => return None,
+            L if isolates == 0 => return Some(0),
+            R | Al if isolates == 0 => return Some(1),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[derive(Clone, Copy)]
+struct DirectionalStatus {
+    level: u8,
+    override_class: Option<BidiClass>,
+    is_isolate: bool,
+}
+
+/// Resolves the embedding levels of the characters of the classes in the paragraphs separated by the
+/// paragraph separators.
+/// todo: Resolve the weak and the neutral types in the isolating run sequences rather than in the level
+/// runs, and support the bracket pairs.
+/// https://www.unicode.org/reports/tr9/#Resolving_Embedding_Levels
+pub fn resolve_levels(classes: &[BidiClass], paragraph_level: u8) -> Vec<u8> {
+    use BidiClass::*;
+    let mut types = classes.to_vec();
+    let mut levels = vec![paragraph_level; classes.len()];
+
+    // X1-X8: The explicit embeddings, overrides and isolates.
+    let initial = DirectionalStatus {
+        level: paragraph_level,
+        override_class: None,
+        is_isolate: false,
+    };
+    let mut stack = vec![initial];
+    let (mut overflow_isolates, mut overflow_embeddings, mut valid_isolates) = (0, 0, 0);
+    for (i, &class) in classes.iter().enumerate() {
+        let last = *stack.last().unwrap();
+        match class {
+            Rle | Lre | Rlo | Lro | Rli | Lri | Fsi => {
+                let is_isolate = matches!(class, Rli | Lri | Fsi);
+                let is_rtl = match class {
+                    Rle | Rlo | Rli => true,
+                    Fsi => find_first_strong_level(&classes[i + 1..]) == Some(1),
+                    _ => false,
+                };
+                levels[i] = last.level;
+                if is_isolate {
+                    types[i] = last.override_class.unwrap_or(class);
+                } else {
+                    types[i] = Bn;
+                }
+                let level = if is_rtl {
+                    (last.level + 1) | 1
+                } else {
+                    (last.level + 2) & !1
+                };
+                if level <= MAX_DEPTH && overflow_isolates == 0 && overflow_embeddings == 0 {
+                    valid_isolates += is_isolate as usize;
+                    stack.push(DirectionalStatus {
+                        level,
+                        override_class: match class {
+                            Rlo => Some(R),
+                            Lro => Some(L),
+                            _ => None,
+                        },
+                        is_isolate,
+                    });
+                } else if is_isolate {
+                    overflow_isolates += 1;
+                } else if overflow_isolates == 0 {
+                    overflow_embeddings += 1;
+                }
+            }
+            Pdi => {
+                if overflow_isolates > 0 {
+                    overflow_isolates -= 1;
+                } else if valid_isolates > 0 {
+                    overflow_embeddings = 0;
+                    while stack.pop().is_some_and(|status| !status.is_isolate) {}
+                    valid_isolates -= 1;
+                }
+                let last = stack.last().unwrap();
+                levels[i] = last.level;
+                types[i] = last.override_class.unwrap_or(Pdi);
+            }
+            Pdf => {
+                if overflow_isolates > 0 {
+                } else if overflow_embeddings > 0 {
+                    overflow_embeddings -= 1;
+                } else if !last.is_isolate && stack.len() >= 2 {
+                    stack.pop();
+                }
+                levels[i] = last.level;
+                types[i] = Bn;
+            }
+            B => {
+                stack.truncate(1);
+                (overflow_isolates, overflow_embeddings, valid_isolates) = (0, 0, 0);
+            }
+            Bn => levels[i] = last.level,
+            _ => {
+                levels[i] = last.level;
+                types[i] = last.override_class.unwrap_or(class);
+            }
+        }
+    }
+
+    // X9, X10: The level runs without the removed characters.
+    let indices = (0..classes.len())
+        .filter(|&i| types[i] != Bn)
+        .collect::<Vec<_>>();
+    let mut start = 0;
+    while start < indices.len() {
+        let level = levels[indices[start]];
+        let mut end = start;
+        while end < indices.len() && levels[indices[end]] == level {
+            end += 1;
+        }
+        let prev_level = start
+            .checked_sub(1)
+            .map_or(paragraph_level, |i| levels[indices[i]]);
+        let next_level = match types[indices[end - 1]] {
+            Lri | Rli | Fsi => paragraph_level,
+            _ => indices.get(end).map_or(paragraph_level, |&i| levels[i]),
+        };
+        let direction = |level: u8| if level.is_multiple_of(2) { L } else { R };
+        resolve_run(
+            &mut types,
+            &mut levels,
+            &indices[start..end],
+            direction(level.max(prev_level)),
+            direction(level.max(next_level)),
+        );
+        start = end;
+    }
+
+    // L1: The separators and the whitespace before them and at the end are reset to the paragraph level.
+    let mut reset = true;
+    for i in (0..classes.len()).rev() {
+        match classes[i] {
+            B | S => {
+                levels[i] = paragraph_level;
+                reset = true;
+            }
+            Ws | Bn | Lre | Lro | Rle | Rlo | Pdf | Lri | Rli | Fsi | Pdi if reset => {
+                levels[i] = paragraph_level;
+            }
+            _ => reset = false,
+        }
+    }
+    levels
+}
+
+/// Resolves the weak and the neutral types, and the implicit levels of a level run.
+/// https://www.unicode.org/reports/tr9/#Resolving_Weak_Types
+fn resolve_run(
+    types: &mut [BidiClass],
+    levels: &mut [u8],
+    run: &[usize],
+    sos: BidiClass,
+    eos: BidiClass,
+) {
+    use BidiClass::*;
+    // W1: The nonspacing marks take the types of the characters before them.
+    let mut prev = sos;
+    for &i in run {
+        if types[i] == Nsm {
+            types[i] = match prev {
+                Lri | Rli | Fsi | Pdi => On,
+                prev => prev,
+            };
+        }
+        prev = types[i];
+    }
+    // W2, W3: The European numbers after the Arabic letters are Arabic numbers, and the Arabic letters
+    // are right-to-left.
+    let mut last_strong = sos;
+    for &i in run {
+        match types[i] {
+            L | R | Al => last_strong = types[i],
+            En if last_strong == Al => types[i] = An,
+            _ => {}
+        }
+    }
+    for &i in run {
+        if types[i] == Al {
+            types[i] = R;
+        }
+    }
+    // W4: A single separator between the numbers of the same type joins them.
+    for k in 1..run.len().saturating_sub(1) {
+        let (prev, next) = (types[run[k - 1]], types[run[k + 1]]);
+        match types[run[k]] {
+            Es if prev == En && next == En => types[run[k]] = En,
+            Cs if prev == next && matches!(prev, En | An) => types[run[k]] = prev,
+            _ => {}
+        }
+    }
+    // W5, W6: The terminators adjacent to the European numbers are European numbers, and the other
+    // separators and terminators are neutral.
+    let mut k = 0;
+    while k < run.len() {
+        if types[run[k]] != Et {
+            k += 1;
+            continue;
+        }
+        let start = k;
+        while k < run.len() && types[run[k]] == Et {
+            k += 1;
+        }
+        let is_number =
+            (start > 0 && types[run[start - 1]] == En) || (k < run.len() && types[run[k]] == En);
+        if is_number {
+            for &i in &run[start..k] {
+                types[i] = En;
+            }
+        }
+    }
+    for &i in run {
+        if matches!(types[i], Es | Et | Cs) {
+            types[i] = On;
+        }
+    }
+    // W7: The European numbers after the left-to-right characters are left-to-right.
+    let mut last_strong = sos;
+    for &i in run {
+        match types[i] {
+            L | R => last_strong = types[i],
+            En if last_strong == L => types[i] = L,
+            _ => {}
+        }
+    }
+
+    // N1, N2: The neutrals between the characters of the same direction take it, or else the embedding
+    // direction.
+    let embedding = if levels[run[0]].is_multiple_of(2) {
+        L
+    } else {
+        R
+    };
+    let strong = |class: BidiClass| match class {
+        L => Some(L),
+        R | En | An => Some(R),
+        _ => None,
+    };
+    let mut k = 0;
+    while k < run.len() {
+        if strong(types[run[k]]).is_some() {
+            k += 1;
+            continue;
+        }
+        let start = k;
+        while k < run.len() && strong(types[run[k]]).is_none() {
+            k += 1;
+        }
+        let before = start
+            .checked_sub(1)
+            .map_or(sos, |j| strong(types[run[j]]).unwrap());
+        let after = run.get(k).map_or(eos, |&i| strong(types[i]).unwrap());
+        let direction = if before == after { before } else { embedding };
+        for &i in &run[start..k] {
+            types[i] = direction;
+        }
+    }
+
+    // I1, I2: The implicit levels.
+    for &i in run {
+        levels[i] += match (levels[i] % 2, types[i]) {
+            (0, R) => 1,
+            (0, An | En) => 2,
+            (1, L | En | An) => 1,
+            _ => 0,
+        };
+    }
+}
+
+/// Returns the visual order of the items of a line from the embedding levels of them, reversing the
+/// sequences at each level from the highest to the lowest odd level.
+/// todo: Reset the levels of the trailing whitespace of each line to the paragraph level.
+/// https://www.unicode.org/reports/tr9/#L2
+pub fn get_visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order = (0..levels.len()).collect::<Vec<_>>();
+    let (Some(highest), Some(lowest_odd)) = (
+        levels.iter().max(),
+        levels.iter().filter(|level| *level % 2 == 1).min(),
+    ) else {
+        return order;
+    };
+    for level in (*lowest_odd..=*highest).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order[start..i].reverse();
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reorder_bidi_text() {
+        let classes = |text: &str| text.chars().map(get_bidi_class).collect::<Vec<_>>();
+        let levels = resolve_levels(&classes("ab אב 12"), 0);
+        assert_eq!(levels, [0, 0, 0, 1, 1, 1, 2, 2]);
+        assert_eq!(get_visual_order(&levels), [0, 1, 2, 6, 7, 5, 4, 3]);
+        assert_eq!(resolve_levels(&classes("אב ab!"), 1), [1, 1, 1, 2, 2, 1]);
+
+        let mut overridden = vec![BidiClass::Rlo];
+        overridden.extend(classes("ab"));
+        overridden.push(BidiClass::Pdf);
+        assert_eq!(resolve_levels(&overridden, 0), [0, 1, 1, 0]);
+        assert_eq!(find_first_strong_level(&classes("12 אב")), Some(1));
+    }
+}
@@ -1,11 +1,15 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use gtk4::pango;
+
+use crate::renderer::html::dom::{DocumentMode, DomNode, NodeType};
 use crate::renderer::layout::box_model::{BoxNode, LayoutBox, LayoutInfo};
-use crate::renderer::layout::inline::InlineBox;
-use crate::renderer::layout::text::Text;
+use crate::renderer::layout::line_box::layout_line_boxes;
+use crate::renderer::layout::positioned::{get_border_box, is_out_of_flow, set_static_position};
+use crate::renderer::layout::table::layout_table_row;
 use crate::renderer::style::property::display::{DisplayInside, DisplayOutside};
-use crate::renderer::style::property::{AbsoluteLengthUnit, CssValue, LengthUnit};
+use crate::renderer::style::property::CssValue;
 use crate::renderer::style::style_model::{ComputedStyle, RenderNode};
 
 #[derive(Debug)]
@@ -13,257 +17,427 @@ pub struct BlockBox {
     pub style_node: Rc<RefCell<RenderNode>>,
     pub layout_info: LayoutInfo,
     pub children: Vec<Rc<RefCell<BoxNode>>>,
+    /// The context with which the fonts of the line boxes are measured.
+    pub draw_ctx: pango::Context,
 }
 
 impl LayoutBox for BlockBox {
+    #[tracing::instrument(skip_all)]
     fn layout(
         &mut self,
         containing_block_info: &LayoutInfo,
-        _: Option<LayoutInfo>,
+        parent_info: Option<LayoutInfo>,
         prev_sibling_info: Option<LayoutInfo>,
     ) {
         self.calc_used_values(containing_block_info);
-        // The margin of the box is not included in the width because it is outside the box.
-        self.layout_info.size.width = self.layout_info.used_values.border.left
-            + self.layout_info.used_values.padding.left
-            + self.layout_info.used_values.width.unwrap()
-            + self.layout_info.used_values.padding.right
-            + self.layout_info.used_values.border.right;
-        self.calc_pos(containing_block_info, prev_sibling_info);
+        self.update_width();
+        if self.is_atomic_inline() {
+            self.calc_inline_pos(containing_block_info, parent_info, prev_sibling_info);
+        } else {
+            self.calc_pos(containing_block_info);
+        }
+        // The height filled in quirks mode is set before the children are laid out, since the body element
+        // fills the html element.
+        let min_height = self.get_quirks_min_height(containing_block_info);
+        self.layout_info.size.height = min_height.unwrap_or(0.0);
         self.layout_children(containing_block_info);
+        if let Some(min_height) = min_height {
+            self.layout_info.size.height = self.layout_info.size.height.max(min_height);
+        }
+        let float = self.style_node.borrow().style.float.clone();
+        if (self.is_atomic_inline() || float.is_floating())
+            && self.style_node.borrow().style.width.size == CssValue::Ident("auto".to_string())
+        {
+            self.shrink_to_fit(containing_block_info.get_content_width());
+        }
+        if float.is_floating() {
+            self.place_float(containing_block_info, float.is_right());
+        }
     }
 
     fn layout_children(&mut self, _: &LayoutInfo) {
-        let is_every_child_block = self.children.iter().all(|child| {
-            matches!(
-                *child.borrow(),
-                BoxNode::BlockBox(_) | BoxNode::AnonymousBox(_)
-            )
-        });
-        let is_every_child_inline = self
-            .children
-            .iter()
-            .all(|child| matches!(*child.borrow(), BoxNode::InlineBox(_) | BoxNode::Text(_)));
+        let is_every_child_block = self.children.iter().all(is_block_level);
+        let is_every_child_inline = self.children.iter().all(is_inline_level);
 
-        if is_every_child_block {
-            let mut prev_sib_info = None;
-
-            // If `height` is `auto`, the height of the box depends on whether the element
-            // has any block-level children and whether it has padding or borders.
+        if self.style_node.borrow().style.display.is_table_row() {
+            let row_height = layout_table_row(&self.children, &self.layout_info);
+            self.layout_info.size.height = self.layout_info.used_values.border.top
+                + self.layout_info.used_values.padding.top
+                + row_height
+                + self.layout_info.used_values.padding.bottom
+                + self.layout_info.used_values.border.bottom;
+        } else if is_every_child_block {
+            // The margins of the first and last children collapse with the ones of the box unless they're
+            // separated by the border or the padding, or the box establishes a block formatting context.
+            // If the height of the box is not the one of its contents, the bottom margin of the last child
+            // doesn't adjoin the one of the box.
             // https://www.w3.org/TR/CSS22/visudet.html#normal-block
-            for child in self.children.iter_mut() {
-                child.borrow_mut().layout(
-                    &self.layout_info,
-                    Some(self.layout_info.clone()),
-                    prev_sib_info.clone(),
-                );
-
-                let child_ref = child.borrow();
-                let child_layout_info = match *child_ref {
-                    BoxNode::BlockBox(BlockBox {
-                        ref layout_info, ..
-                    })
-                    | BoxNode::AnonymousBox(AnonymousBox {
-                        ref layout_info, ..
-                    }) => layout_info,
-                    _ => unreachable!(),
-                };
-
-                // This is where the margin collapse happens, which is tricky. This
-                // implementation is quite simple and does not cover complex cases.
-                // https://www.w3.org/TR/CSS22/box.html#collapsing-margins
-                // https://developer.mozilla.org/en-US/docs/Web/CSS/CSS_box_model/Mastering_margin_collapsing
-                if let Some(info) = &prev_sib_info {
-                    if child_layout_info.used_values.margin.top < info.used_values.margin.bottom {
-                        self.layout_info.size.height +=
-                            child_layout_info.get_expanded_size().height
-                                - child_layout_info.used_values.margin.top;
-                    } else {
-                        self.layout_info.size.height +=
-                            child_layout_info.get_expanded_size().height
-                                - info.used_values.margin.bottom;
-                    }
-                } else {
-                    self.layout_info.size.height += child_layout_info.get_expanded_size().height;
-                }
-
-                prev_sib_info = Some(child_layout_info.clone());
-            }
+            let is_root = self.establishes_block_formatting_context();
+            let used_values = &self.layout_info.used_values;
+            let flow = stack_block_children(
+                &self.children,
+                &self.layout_info,
+                !is_root && used_values.border.top == 0.0 && used_values.padding.top == 0.0,
+                !is_root
+                    && used_values.border.bottom == 0.0
+                    && used_values.padding.bottom == 0.0
+                    && self.has_content_height(),
+            );
 
+            let margin = &mut self.layout_info.used_values.margin;
+            margin.top = flow.top_margin.with(margin.top).get_size();
+            margin.bottom = flow.bottom_margin.with(margin.bottom).get_size();
             // The margin of the box is not included in the height because it is outside the box.
-            self.layout_info.size.height += self.layout_info.used_values.padding.top
-                + self.layout_info.used_values.border.top
+            self.layout_info.size.height = self.layout_info.used_values.border.top
+                + self.layout_info.used_values.padding.top
+                + flow.height
                 + self.layout_info.used_values.padding.bottom
                 + self.layout_info.used_values.border.bottom;
         } else if is_every_child_inline {
-            let mut inline_max_height = 0.0;
-            let mut prev_sib_info = None;
-
-            for child in self.children.iter_mut() {
-                child.borrow_mut().layout(
-                    &self.layout_info,
-                    Some(self.layout_info.clone()),
-                    prev_sib_info,
-                );
-
-                let child_ref = child.borrow();
-                let child_layout_info = match *child_ref {
-                    BoxNode::InlineBox(InlineBox {
-                        ref layout_info, ..
-                    })
-                    | BoxNode::Text(Text {
-                        ref layout_info, ..
-                    }) => layout_info,
-                    _ => unreachable!(),
-                };
-
-                let ch_exp_height = child_layout_info.get_expanded_size().height;
-                if inline_max_height < ch_exp_height {
-                    inline_max_height = ch_exp_height;
-                }
-                prev_sib_info = Some(child_layout_info.clone());
-            }
-
             // If parent is a block-level box and children are inline-level boxes, the parent's width
             // is defined by the parent itself (so the width is not determined here by the children).
+            let lines_height = layout_line_boxes(
+                &self.children,
+                &self.layout_info,
+                &self.style_node.borrow().style,
+                &self.draw_ctx,
+            );
 
             // The margin of the box is not included in the height because it is outside the box.
             self.layout_info.size.height = self.layout_info.used_values.border.top
                 + self.layout_info.used_values.padding.top
-                + inline_max_height
+                + lines_height
                 + self.layout_info.used_values.padding.bottom
                 + self.layout_info.used_values.border.bottom;
         } else if !self.children.is_empty() {
             unreachable!()
         }
 
-        // If `height` is not `auto`, the height of the box is the value of `height`.
-        if let CssValue::Length(height, _) = self.style_node.borrow().style.height.size {
-            self.layout_info.size.height = self.layout_info.used_values.border.top
-                + self.layout_info.used_values.padding.top
-                + height
-                + self.layout_info.used_values.padding.bottom
-                + self.layout_info.used_values.border.bottom;
-        }
+        let used_values = &self.layout_info.used_values;
+        let edges = used_values.border.top
+            + used_values.padding.top
+            + used_values.padding.bottom
+            + used_values.border.bottom;
+        self.layout_info.size.height =
+            edges + self.get_used_height((self.layout_info.size.height - edges).max(0.0));
     }
 }
 
 impl BlockBox {
+    /// Returns whether the box is an atomic inline-level box such as `inline-block`, which is placed in the
+    /// inline formatting context of its parent.
+    pub fn is_atomic_inline(&self) -> bool {
+        self.style_node.borrow().style.display.is_atomic_inline()
+    }
+
+    /// Sets the width of the border box from the used width.
+    fn update_width(&mut self) {
+        // The margin of the box is not included in the width because it is outside the box.
+        self.layout_info.size.width = self.layout_info.used_values.border.left
+            + self.layout_info.used_values.padding.left
+            + self.layout_info.used_values.width.unwrap()
+            + self.layout_info.used_values.padding.right
+            + self.layout_info.used_values.border.right;
+    }
+
+    /// Shrinks the used width of the box to the extent of its laid out contents, which is the
+    /// shrink-to-fit width if the contents fit in the available width.
+    /// todo: Calculate the preferred widths of the block-level contents.
+    /// https://www.w3.org/TR/CSS22/visudet.html#shrink-to-fit-float
+    /// The width doesn't get narrower than `min-width`, whose percentage is relative to `cb_width`.
+    pub fn shrink_to_fit(&mut self, cb_width: f32) {
+        let content_x = self.layout_info.pos.x + self.layout_info.used_values.padding.left;
+        let content_right = self
+            .children
+            .iter()
+            .map(|child| get_content_right(&child.borrow()))
+            .fold(content_x, f32::max);
+        let available_width = self.layout_info.used_values.width.unwrap();
+        let (min_width, _) = self.get_width_limits(cb_width);
+        self.layout_info.used_values.width = Some(
+            (content_right - content_x)
+                .min(available_width)
+                .max(min_width),
+        );
+        self.update_width();
+    }
+
+    /// Returns whether the used height of the box is the height of its contents, which `min-height` doesn't
+    /// make taller. The percentages and the `calc()` with percentages in `height` behave as `auto` as in
+    /// `get_used_height()`.
+    /// https://www.w3.org/TR/CSS22/box.html#collapsing-margins
+    fn has_content_height(&self) -> bool {
+        let style = &self.style_node.borrow().style;
+        get_definite_height(style).is_none() && style.min_height.to_px(None) == 0.0
+    }
+
+    /// Returns the height of the border box which the `html` element or the `body` element fills in a document
+    /// in quirks mode, i.e. the height of the viewport or the content height of the `html` element without
+    /// the margins of the box, or `None` if the box is not one of them or its height is not `auto`.
+    /// https://quirks.spec.whatwg.org/#the-html-element-fills-the-viewport-quirk
+    /// https://quirks.spec.whatwg.org/#the-body-element-fills-the-html-element-quirk
+    fn get_quirks_min_height(&self, containing_block_info: &LayoutInfo) -> Option<f32> {
+        let style_node = self.style_node.borrow();
+        let style = &style_node.style;
+        if get_definite_height(style).is_some()
+            || self.is_atomic_inline()
+            || style.float.is_floating()
+            || style.position.is_absolutely_positioned()
+        {
+            return None;
+        }
+        let is_quirks_document = |node: &Rc<RefCell<DomNode>>| {
+            matches!(
+                node.borrow().node_type,
+                NodeType::Document(DocumentMode::Quirks)
+            )
+        };
+        let parent = DomNode::get_parent(&style_node.dom_node)?;
+        let filled_height = if is_quirks_document(&parent) {
+            // The containing block of the root element is the initial containing block.
+            containing_block_info.size.height
+        } else if matches!(&style_node.dom_node.borrow().node_type, NodeType::Element(elm) if elm.tag_name == "body")
+            && DomNode::get_parent(&parent).is_some_and(|root| is_quirks_document(&root))
+        {
+            let used_values = &containing_block_info.used_values;
+            containing_block_info.size.height
+                - used_values.border.top
+                - used_values.padding.top
+                - used_values.padding.bottom
+                - used_values.border.bottom
+        } else {
+            return None;
+        };
+        let margin = &self.layout_info.used_values.margin;
+        Some((filled_height - margin.top - margin.bottom).max(0.0))
+    }
+
+    /// Returns the sum of the paddings and borders in the inline axis or the block axis, which the sizes of
+    /// the box include if it's `box-sizing: border-box`.
+    /// https://drafts.csswg.org/css-sizing/#box-sizing
+    fn get_box_sizing_edges(&self, is_horizontal: bool) -> f32 {
+        if !self.style_node.borrow().style.box_sizing.is_border_box() {
+            return 0.0;
+        }
+        let used_values = &self.layout_info.used_values;
+        if is_horizontal {
+            used_values.padding.left
+                + used_values.padding.right
+                + used_values.border.left
+                + used_values.border.right
+        } else {
+            used_values.padding.top
+                + used_values.padding.bottom
+                + used_values.border.top
+                + used_values.border.bottom
+        }
+    }
+
+    /// Returns the content width of `width`, or `None` if it's `auto`.
+    fn get_specified_width(&self, cb_width: f32) -> Option<f32> {
+        let width = &self.style_node.borrow().style.width.size;
+        if *width == CssValue::Ident("auto".to_string()) {
+            return None;
+        }
+        Some((width.resolve_px(cb_width).unwrap() - self.get_box_sizing_edges(true)).max(0.0))
+    }
+
+    /// Returns the content widths of `min-width` and `max-width`, which limit the used width.
+    /// https://www.w3.org/TR/CSS22/visudet.html#min-max-widths
+    fn get_width_limits(&self, cb_width: f32) -> (f32, Option<f32>) {
+        let style_node = self.style_node.borrow();
+        let edges = self.get_box_sizing_edges(true);
+        let min_width = (style_node.style.min_width.to_px(Some(cb_width)) - edges).max(0.0);
+        let max_width = style_node
+            .style
+            .max_width
+            .to_px(Some(cb_width))
+            .map(|max_width| (max_width - edges).max(0.0));
+        (min_width, max_width)
+    }
+
+    /// Returns the used content height from the height of the laid out contents, which is replaced by
+    /// `height` if it's not `auto`, and then limited by `min-height` and `max-height`. `max-height` doesn't
+    /// make the height less than `min-height`.
+    /// todo: Resolve the percentages against the height of the containing block if it's definite.
+    /// https://www.w3.org/TR/CSS22/visudet.html#min-max-heights
+    fn get_used_height(&self, content_height: f32) -> f32 {
+        let style = &self.style_node.borrow().style;
+        let edges = self.get_box_sizing_edges(false);
+        let height = match get_definite_height(style) {
+            Some(height) => (height - edges).max(0.0),
+            None => content_height,
+        };
+        let max_height = style
+            .max_height
+            .to_px(None)
+            .map_or(f32::INFINITY, |max_height| (max_height - edges).max(0.0));
+        let min_height = (style.min_height.to_px(None) - edges).max(0.0);
+        height.min(max_height).max(min_height)
+    }
+
+    /// Resolves the used values of the width and the horizontal margins, whose sum with the paddings and
+    /// borders is the width of the containing block. `width` is the content width, or `None` if it's
+    /// `auto`.
+    /// https://www.w3.org/TR/CSS22/visudet.html#blockwidth
+    fn resolve_block_width(
+        &self,
+        width: Option<f32>,
+        margin_left: &CssValue,
+        margin_right: &CssValue,
+        cb_width: f32,
+    ) -> (f32, f32, f32) {
+        let resolve = |v: &CssValue| match v {
+            CssValue::Ident(v) if v == "auto" => None,
+            CssValue::Length(..) | CssValue::Percentage(_) | CssValue::Calc(_) => {
+                Some(v.resolve_px(cb_width).unwrap())
+            }
+            _ => unreachable!(),
+        };
+        let (mut margin_left, mut margin_right) = (resolve(margin_left), resolve(margin_right));
+        let used_values = &self.layout_info.used_values;
+        let leeway = cb_width
+            - width.unwrap_or(0.0)
+            - margin_left.unwrap_or(0.0)
+            - margin_right.unwrap_or(0.0)
+            - used_values.padding.left
+            - used_values.padding.right
+            - used_values.border.left
+            - used_values.border.right;
+
+        // If the box is wider than the containing block, the `auto` margins are zero.
+        if width.is_some() && leeway < 0.0 {
+            margin_left = margin_left.or(Some(0.0));
+            margin_right = margin_right.or(Some(0.0));
+        }
+
+        match (width, margin_left, margin_right) {
+            // Assume that the `direction` property of the containing block is `ltr`.
+            (Some(width), Some(margin_left), Some(margin_right)) => {
+                (width, margin_left, margin_right + leeway)
+            }
+            (Some(width), None, None) => (width, leeway / 2.0, leeway / 2.0),
+            (Some(width), None, Some(margin_right)) => (width, leeway, margin_right),
+            (Some(width), Some(margin_left), None) => (width, margin_left, leeway),
+            (None, margin_left, margin_right) => {
+                let margin_left = margin_left.unwrap_or(0.0);
+                let margin_right = margin_right.unwrap_or(0.0);
+                if leeway >= 0.0 {
+                    (leeway, margin_left, margin_right)
+                } else {
+                    (0.0, margin_left, margin_right + leeway)
+                }
+            }
+        }
+    }
+
     fn calc_used_values(&mut self, containing_block_info: &LayoutInfo) {
-        let (width, margin, display) = (
-            self.style_node.borrow().style.width.clone(),
+        let (margin, display) = (
             self.style_node.borrow().style.margin.clone(),
             self.style_node.borrow().style.display.clone(),
         );
-        let mut margin_left = margin.left;
-        let mut margin_right = margin.right;
+        let cb_width = containing_block_info.get_content_width();
+
+        match display.outside {
+            // Inline-block, non-replaced elements in normal flow, whose `auto` width is the available width
+            // until the box is shrunk to fit its contents.
+            // https://www.w3.org/TR/CSS22/visudet.html#inlineblock-width
+            DisplayOutside::Inline => {
+                let resolve = |v: &CssValue| match v {
+                    CssValue::Ident(v) if v == "auto" => 0.0,
+                    CssValue::Length(..) | CssValue::Percentage(_) | CssValue::Calc(_) => {
+                        v.resolve_px(cb_width).unwrap()
+                    }
+                    _ => unreachable!(),
+                };
+                self.layout_info.used_values.margin.left = resolve(&margin.left);
+                self.layout_info.used_values.margin.right = resolve(&margin.right);
+                self.layout_info.used_values.margin.top = resolve(&margin.top);
+                self.layout_info.used_values.margin.bottom = resolve(&margin.bottom);
+                let width = self.get_specified_width(cb_width).unwrap_or(
+                    cb_width
+                        - self.layout_info.used_values.margin.left
+                        - self.layout_info.used_values.margin.right
+                        - self.layout_info.used_values.padding.left
+                        - self.layout_info.used_values.padding.right
+                        - self.layout_info.used_values.border.left
+                        - self.layout_info.used_values.border.right,
+                );
+                let (min_width, max_width) = self.get_width_limits(cb_width);
+                self.layout_info.used_values.width = Some(
+                    width
+                        .min(max_width.unwrap_or(f32::INFINITY))
+                        .max(min_width)
+                        .max(0.0),
+                );
+            }
 
-        match (display.outside, display.inside) {
-            // Block-level, non-replaced elements in normal flow
+            // Block-level, non-replaced elements in normal flow, including the tables and their internal
+            // boxes, which are as wide as the containing block for now. The width is resolved again with
+            // `max-width` and then `min-width` if it's out of them.
+            // todo: Shrink the tables to fit their contents.
             // https://www.w3.org/TR/CSS22/visudet.html#blockwidth
-            (DisplayOutside::Block, DisplayInside::Flow) => {
-                let sum = [&width.size, &margin_left, &margin_right]
-                    .iter()
-                    .map(|v| match v {
-                        CssValue::Ident(v) if v == "auto" => 0.0,
-                        CssValue::Length(..) => v.to_px().unwrap(),
-                        CssValue::Percentage(_) => unimplemented!(),
-                        _ => unreachable!(),
-                    })
-                    .sum::<f32>()
-                    + self.layout_info.used_values.padding.left
-                    + self.layout_info.used_values.padding.right
-                    + self.layout_info.used_values.border.left
-                    + self.layout_info.used_values.border.right;
-
-                let leeway = containing_block_info.size.width
-                    - containing_block_info.used_values.padding.left
-                    - containing_block_info.used_values.padding.right
-                    - containing_block_info.used_values.border.left
-                    - containing_block_info.used_values.border.right
-                    - sum;
-
-                if (width.size != CssValue::Ident("auto".to_string())) && (leeway < 0.0) {
-                    if margin_left == CssValue::Ident("auto".to_string()) {
-                        margin_left = CssValue::Length(
-                            0.0,
-                            LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
-                        );
-                    }
-                    if margin_right == CssValue::Ident("auto".to_string()) {
-                        margin_right = CssValue::Length(
-                            0.0,
-                            LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
-                        );
-                    }
+            // https://www.w3.org/TR/CSS22/visudet.html#min-max-widths
+            DisplayOutside::Block => {
+                let resolve =
+                    |width| self.resolve_block_width(width, &margin.left, &margin.right, cb_width);
+                let mut used = resolve(self.get_specified_width(cb_width));
+                let (min_width, max_width) = self.get_width_limits(cb_width);
+                if let Some(max_width) = max_width.filter(|max_width| used.0 > *max_width) {
+                    used = resolve(Some(max_width));
                 }
-
-                let is_width_auto = width.size == CssValue::Ident("auto".to_string());
-                let is_margin_left_auto = margin_left == CssValue::Ident("auto".to_string());
-                let is_margin_right_auto = margin_right == CssValue::Ident("auto".to_string());
-
-                let (width_px, margin_left_px, margin_right_px) =
-                    match (is_width_auto, is_margin_left_auto, is_margin_right_auto) {
-                        (false, false, false) => {
-                            // Assume that the `direction` property of the containing block is `ltr`.
-                            (
-                                width.size.to_px().unwrap(),
-                                margin_left.to_px().unwrap(),
-                                margin_right.to_px().unwrap() + leeway,
-                            )
-                        }
-                        (false, true, true) => {
-                            (width.size.to_px().unwrap(), leeway / 2.0, leeway / 2.0)
-                        }
-                        (true, _, _) => {
-                            let margin_left_px = match margin_left {
-                                CssValue::Ident(v) if v == "auto" => 0.0,
-                                CssValue::Length(..) => margin_left.to_px().unwrap(),
-                                CssValue::Percentage(_) => unimplemented!(),
-                                _ => unreachable!(),
-                            };
-                            let margin_right_px = match margin_right {
-                                CssValue::Ident(v) if v == "auto" => 0.0,
-                                CssValue::Length(..) => margin_right.to_px().unwrap(),
-                                CssValue::Percentage(_) => unimplemented!(),
-                                _ => unreachable!(),
-                            };
-
-                            if leeway >= 0.0 {
-                                (leeway, margin_left_px, margin_right_px)
-                            } else {
-                                (0.0, margin_left_px, margin_right_px + leeway)
-                            }
-                        }
-                        _ => unimplemented!(),
-                    };
+                if used.0 < min_width {
+                    used = resolve(Some(min_width));
+                }
+                let (width_px, margin_left_px, margin_right_px) = used;
 
                 self.layout_info.used_values.width = Some(width_px);
                 self.layout_info.used_values.margin.left = margin_left_px;
                 self.layout_info.used_values.margin.right = margin_right_px;
                 self.layout_info.used_values.margin.top = match margin.top {
                     CssValue::Ident(v) if v == "auto" => 0.0,
-                    CssValue::Length(..) => margin.top.to_px().unwrap(),
-                    CssValue::Percentage(_) => unimplemented!(),
+                    CssValue::Length(..) | CssValue::Percentage(_) | CssValue::Calc(_) => {
+                        margin.top.resolve_px(cb_width).unwrap()
+                    }
                     _ => unreachable!(),
                 };
                 self.layout_info.used_values.margin.bottom = match margin.bottom {
                     CssValue::Ident(v) if v == "auto" => 0.0,
-                    CssValue::Length(..) => margin.bottom.to_px().unwrap(),
-                    CssValue::Percentage(_) => unimplemented!(),
+                    CssValue::Length(..) | CssValue::Percentage(_) | CssValue::Calc(_) => {
+                        margin.bottom.resolve_px(cb_width).unwrap()
+                    }
                     _ => unreachable!(),
                 };
             }
-
-            _ => unimplemented!("Currently, only block-level boxes in normal flow are supported."),
         }
     }
 
-    fn calc_pos(
+    /// Places the atomic inline-level box after the previous sibling in the line, or at the start of the
+    /// parent.
+    /// https://www.w3.org/TR/CSS22/visuren.html#inline-formatting
+    fn calc_inline_pos(
         &mut self,
         containing_block_info: &LayoutInfo,
+        parent_info: Option<LayoutInfo>,
         prev_sibling_info: Option<LayoutInfo>,
     ) {
+        let start_x = match (prev_sibling_info, parent_info) {
+            (Some(info), _) => info.get_expanded_pos().x + info.get_expanded_size().width,
+            (None, Some(info)) => info.pos.x + info.used_values.padding.left,
+            (None, None) => {
+                containing_block_info.pos.x + containing_block_info.used_values.padding.left
+            }
+        };
+        self.layout_info.pos.x = start_x
+            + self.layout_info.used_values.margin.left
+            + self.layout_info.used_values.border.left;
+        self.layout_info.pos.y = containing_block_info.pos.y
+            + containing_block_info.used_values.padding.top
+            + self.layout_info.used_values.margin.top
+            + self.layout_info.used_values.border.top;
+    }
+
+    fn calc_pos(&mut self, containing_block_info: &LayoutInfo) {
         // The value of x and y takes into account the border and padding of the box
         // but not the margin, because that's the space outside the box.
 
@@ -272,25 +446,63 @@ impl BlockBox {
             + self.layout_info.used_values.margin.left
             + self.layout_info.used_values.border.left;
 
-        // This is where the margin collapse happens, which is tricky. This
-        // implementation is quite simple and does not cover complex cases.
-        // https://www.w3.org/TR/CSS22/box.html#collapsing-margins
-        // https://developer.mozilla.org/en-US/docs/Web/CSS/CSS_box_model/Mastering_margin_collapsing
-        self.layout_info.pos.y = self.layout_info.used_values.border.top
-            + if let Some(info) = prev_sibling_info {
-                if self.layout_info.used_values.margin.top < info.used_values.margin.bottom {
-                    info.get_expanded_pos().y + info.get_expanded_size().height
-                } else {
-                    self.layout_info.used_values.margin.top
-                        + info.get_expanded_pos().y
-                        + info.get_expanded_size().height
-                        - info.used_values.margin.bottom
-                }
-            } else {
-                containing_block_info.pos.y
-                    + containing_block_info.used_values.padding.top
-                    + self.layout_info.used_values.margin.top
-            };
+        // This is the position at the top of the containing block, from which the parent moves the box
+        // below its previous siblings after collapsing the adjoining margins.
+        self.layout_info.pos.y = containing_block_info.pos.y
+            + containing_block_info.used_values.padding.top
+            + self.layout_info.used_values.margin.top
+            + self.layout_info.used_values.border.top;
+    }
+
+    /// Places the float at the left or the right edge of the content box of the containing block, where
+    /// the `auto` margins are zero.
+    /// todo: Take the floats out of the flow, and shorten the line boxes beside them.
+    /// https://www.w3.org/TR/CSS22/visuren.html#float-position
+    fn place_float(&mut self, containing_block_info: &LayoutInfo, is_right: bool) {
+        let margin = self.style_node.borrow().style.margin.clone();
+        let cb_width = containing_block_info.get_content_width();
+        let resolve = |margin: &CssValue| match margin {
+            CssValue::Ident(_) => 0.0,
+            _ => margin.resolve_px(cb_width).unwrap_or(0.0),
+        };
+        let used_margin = &mut self.layout_info.used_values.margin;
+        used_margin.left = resolve(&margin.left);
+        used_margin.right = resolve(&margin.right);
+        let content_x =
+            containing_block_info.pos.x + containing_block_info.used_values.padding.left;
+        let x = if is_right {
+            content_x + cb_width - used_margin.right - self.layout_info.size.width
+        } else {
+            content_x + used_margin.left
+        };
+        let dx = x + self.layout_info.used_values.border.left - self.layout_info.pos.x;
+        if dx != 0.0 {
+            self.layout_info.pos.x += dx;
+            for child in &self.children {
+                child.borrow_mut().translate(dx, 0.0);
+            }
+        }
+    }
+
+    /// Returns whether the box establishes a new block formatting context for its contents, whose margins
+    /// don't collapse with the ones of the box.
+    /// https://www.w3.org/TR/CSS22/visuren.html#block-formatting
+    fn establishes_block_formatting_context(&self) -> bool {
+        let style_node = self.style_node.borrow();
+        let display = &style_node.style.display;
+        let is_root_element = style_node
+            .dom_node
+            .borrow()
+            .parent
+            .as_ref()
+            .and_then(|parent| parent.upgrade())
+            .is_some_and(|parent| matches!(parent.borrow().node_type, NodeType::Document(_)));
+        is_root_element
+            || display.inside != DisplayInside::Flow
+            || display.is_table_cell()
+            || style_node.style.position.is_absolutely_positioned()
+            || style_node.style.float.is_floating()
+            || style_node.style.overflow.is_scroll_container()
     }
 }
 
@@ -299,9 +511,12 @@ pub struct AnonymousBox {
     pub style: Box<ComputedStyle>,
     pub layout_info: LayoutInfo,
     pub children: Vec<Rc<RefCell<BoxNode>>>,
+    /// The context with which the fonts of the line boxes are measured.
+    pub draw_ctx: pango::Context,
 }
 
 impl LayoutBox for AnonymousBox {
+    #[tracing::instrument(skip_all)]
     fn layout(
         &mut self,
         containing_block_info: &LayoutInfo,
@@ -309,7 +524,7 @@ impl LayoutBox for AnonymousBox {
         prev_sibling_info: Option<LayoutInfo>,
     ) {
         self.calc_used_values(containing_block_info);
-        self.layout_info.size.width = containing_block_info.size.width;
+        self.layout_info.size.width = self.layout_info.used_values.width.unwrap();
         self.calc_pos(containing_block_info, prev_sibling_info);
         self.layout_children(containing_block_info);
     }
@@ -318,53 +533,42 @@ impl LayoutBox for AnonymousBox {
         if self.children.is_empty() {
             unreachable!()
         }
-        let is_every_child_inline = self
-            .children
-            .iter()
-            .all(|child| matches!(*child.borrow(), BoxNode::InlineBox(_) | BoxNode::Text(_)));
+        if self.style.display.is_table_row() {
+            self.layout_info.size.height = layout_table_row(&self.children, &self.layout_info);
+            return;
+        }
+        // The anonymous tables, rows and cells may contain block-level boxes, which are stacked.
+        if self.children.iter().all(is_block_level) {
+            self.layout_info.size.height =
+                stack_block_children(&self.children, &self.layout_info, false, false).height;
+            return;
+        }
+        let is_every_child_inline = self.children.iter().all(|child| {
+            matches!(
+                &*child.borrow(),
+                BoxNode::InlineBox(_) | BoxNode::Text(_) | BoxNode::ReplacedBox(_)
+            ) || matches!(&*child.borrow(), BoxNode::BlockBox(b) if b.is_atomic_inline())
+                || is_out_of_flow(&child.borrow())
+        });
         if !is_every_child_inline {
             unreachable!("AnonymousBox currently only supports inline-level boxes and text nodes as children.");
         }
 
-        let mut inline_max_height = 0.0;
-        let mut prev_sib_info = None;
-
-        // Assume that all children are inline-level boxes or text nodes.
-        for child in self.children.iter_mut() {
-            // The containing block of an inline-level box is the nearest block-level ancestor box.
-            child.borrow_mut().layout(
-                &self.layout_info,
-                Some(self.layout_info.clone()),
-                prev_sib_info,
-            );
-
-            let child_ref = child.borrow();
-            let child_layout_info = match *child_ref {
-                BoxNode::InlineBox(InlineBox {
-                    ref layout_info, ..
-                })
-                | BoxNode::Text(Text {
-                    ref layout_info, ..
-                }) => layout_info,
-                _ => unreachable!(),
-            };
-
-            let ch_exp_height = child_layout_info.get_expanded_size().height;
-            if inline_max_height < ch_exp_height {
-                inline_max_height = ch_exp_height;
-            }
-            prev_sib_info = Some(child_layout_info.clone());
-        }
-
         // If parent is a block-level box and children are inline-level boxes, the parent's width
         // is defined by the parent itself (so the width is not determined here by the children).
-        self.layout_info.size.height = inline_max_height;
+        self.layout_info.size.height = layout_line_boxes(
+            &self.children,
+            &self.layout_info,
+            &self.style,
+            &self.draw_ctx,
+        );
     }
 }
 
 impl AnonymousBox {
+    /// The anonymous box fills the content box of its containing block.
     pub fn calc_used_values(&mut self, containing_block_info: &LayoutInfo) {
-        self.layout_info.used_values.width = Some(containing_block_info.size.width);
+        self.layout_info.used_values.width = Some(containing_block_info.get_content_width());
         self.layout_info.used_values.margin.top = 0.0;
         self.layout_info.used_values.margin.right = 0.0;
         self.layout_info.used_values.margin.bottom = 0.0;
@@ -377,7 +581,8 @@ impl AnonymousBox {
         containing_block_info: &LayoutInfo,
         prev_sibling_info: Option<LayoutInfo>,
     ) {
-        self.layout_info.pos.x = containing_block_info.pos.x;
+        self.layout_info.pos.x =
+            containing_block_info.pos.x + containing_block_info.used_values.padding.left;
         self.layout_info.pos.y = if let Some(prev_sib_info) = prev_sibling_info {
             prev_sib_info.get_expanded_pos().y + prev_sib_info.get_expanded_size().height
         } else {
@@ -385,3 +590,388 @@ impl AnonymousBox {
         };
     }
 }
+
+/// The adjoining vertical margins, which collapse into a single margin whose size is the largest positive
+/// margin plus the most negative one.
+/// https://www.w3.org/TR/CSS22/box.html#collapsing-margins
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct CollapsedMargin {
+    positive: f32,
+    negative: f32,
+}
+
+impl CollapsedMargin {
+    fn with(mut self, margin: f32) -> Self {
+        if margin > 0.0 {
+            self.positive = self.positive.max(margin);
+        } else {
+            self.negative = self.negative.min(margin);
+        }
+        self
+    }
+
+    fn get_size(&self) -> f32 {
+        self.positive + self.negative
+    }
+}
+
+/// The block-level boxes stacked in the content box of their parent.
+struct BlockFlow {
+    /// The distance from the top of the content box to the bottom of the last box, including the margins
+    /// which are not passed to the parent.
+    height: f32,
+    /// The margins which adjoin the top margin of the parent and collapse with it.
+    top_margin: CollapsedMargin,
+    /// The margins which adjoin the bottom margin of the parent and collapse with it.
+    bottom_margin: CollapsedMargin,
+}
+
+/// Lays out the block-level boxes and stacks them from the top of the content box of the parent, where the
+/// adjoining margins between them collapse. The margins at the top and the bottom are passed to the parent
+/// if they collapse with its margins. The empty boxes don't separate the margins around them, which collapse
+/// through them. The margins of the floats don't collapse.
+/// https://www.w3.org/TR/CSS22/box.html#collapsing-margins
+fn stack_block_children(
+    children: &[Rc<RefCell<BoxNode>>],
+    parent_info: &LayoutInfo,
+    collapses_top: bool,
+    collapses_bottom: bool,
+) -> BlockFlow {
+    let content_top = parent_info.pos.y + parent_info.used_values.padding.top;
+    // The bottom of the border box of the last box which is not empty, and the margins below it.
+    let mut bottom = content_top;
+    let mut margin = CollapsedMargin::default();
+    let mut top_margin = CollapsedMargin::default();
+    let mut is_at_top = collapses_top;
+    let mut prev_sib_info = None;
+
+    for child in children {
+        if is_out_of_flow(&child.borrow()) {
+            set_static_position(child, parent_info, prev_sib_info.as_ref(), false);
+            continue;
+        }
+        child
+            .borrow_mut()
+            .layout(parent_info, Some(parent_info.clone()), prev_sib_info);
+
+        let (margin_top, margin_bottom, is_empty, is_float, border_box) = {
+            let child = child.borrow();
+            let used_values = &child.get_layout_info().used_values;
+            (
+                used_values.margin.top,
+                used_values.margin.bottom,
+                collapses_through(&child),
+                child
+                    .get_element_style_node()
+                    .is_some_and(|style_node| style_node.borrow().style.float.is_floating()),
+                get_border_box(&child),
+            )
+        };
+        if is_float {
+            let top = if is_at_top {
+                bottom
+            } else {
+                bottom + margin.get_size()
+            } + margin_top;
+            child.borrow_mut().translate(0.0, top - border_box.y as f32);
+            bottom = top + border_box.height as f32 + margin_bottom;
+            margin = CollapsedMargin::default();
+            is_at_top = false;
+            prev_sib_info = Some(child.borrow().get_layout_info().clone());
+            continue;
+        }
+        let pending = if is_at_top {
+            &mut top_margin
+        } else {
+            &mut margin
+        };
+        *pending = pending.with(margin_top);
+        let top = if is_at_top {
+            bottom
+        } else {
+            bottom + pending.get_size()
+        };
+        let dy = top - border_box.y as f32;
+        if dy != 0.0 {
+            child.borrow_mut().translate(0.0, dy);
+        }
+        if is_empty {
+            *pending = pending.with(margin_bottom);
+        } else {
+            bottom = top + border_box.height as f32;
+            margin = CollapsedMargin::default().with(margin_bottom);
+            is_at_top = false;
+        }
+        prev_sib_info = Some(child.borrow().get_layout_info().clone());
+    }
+
+    if collapses_bottom && !is_at_top {
+        BlockFlow {
+            height: bottom - content_top,
+            top_margin,
+            bottom_margin: margin,
+        }
+    } else {
+        BlockFlow {
+            height: (bottom - content_top + margin.get_size()).max(0.0),
+            top_margin,
+            bottom_margin: CollapsedMargin::default(),
+        }
+    }
+}
+
+/// Returns the used value of `height` in pixels if it's a length, or `None` if the height is the one of the
+/// contents.
+fn get_definite_height(style: &ComputedStyle) -> Option<f32> {
+    match style.height.size {
+        CssValue::Length(height, _) => Some(height),
+        _ => None,
+    }
+}
+
+/// Returns whether the top and bottom margins of the laid out box adjoin, i.e. it has no height, borders,
+/// paddings, line boxes or `min-height`. The box whose height is zero by `height` adjoins only if the
+/// children in flow collapse through too.
+fn collapses_through(node: &BoxNode) -> bool {
+    match node {
+        BoxNode::BlockBox(b) => {
+            let style = &b.style_node.borrow().style;
+            b.layout_info.size.height == 0.0
+                && !b.establishes_block_formatting_context()
+                && style.min_height.to_px(None) == 0.0
+                && (get_definite_height(style).is_none()
+                    || b.children.iter().all(|child| {
+                        let child = child.borrow();
+                        is_out_of_flow(&child) || collapses_through(&child)
+                    }))
+        }
+        BoxNode::AnonymousBox(b) => b.layout_info.size.height == 0.0,
+        BoxNode::Text(t) => t.lines.is_empty(),
+        _ => false,
+    }
+}
+
+/// Returns whether the box is laid out in the block formatting context of its parent. The out-of-flow boxes
+/// are in both kinds of formatting contexts.
+fn is_block_level(child: &Rc<RefCell<BoxNode>>) -> bool {
+    if is_out_of_flow(&child.borrow()) {
+        return true;
+    }
+    match &*child.borrow() {
+        BoxNode::BlockBox(b) => !b.is_atomic_inline(),
+        BoxNode::AnonymousBox(_) => true,
+        BoxNode::ReplacedBox(r) => r.get_display_type() == DisplayOutside::Block,
+        _ => false,
+    }
+}
+
+/// Returns whether the box is laid out in the inline formatting context of its parent.
+fn is_inline_level(child: &Rc<RefCell<BoxNode>>) -> bool {
+    if is_out_of_flow(&child.borrow()) {
+        return true;
+    }
+    match &*child.borrow() {
+        BoxNode::InlineBox(_) | BoxNode::Text(_) => true,
+        BoxNode::BlockBox(b) => b.is_atomic_inline(),
+        BoxNode::ReplacedBox(r) => r.get_display_type() == DisplayOutside::Inline,
+        _ => false,
+    }
+}
+
+/// Returns the right edge of the margin box of the laid out box, or of the contents of the anonymous box,
+/// which is as wide as its containing block.
+fn get_content_right(node: &BoxNode) -> f32 {
+    match node {
+        BoxNode::AnonymousBox(AnonymousBox { children, .. }) => children
+            .iter()
+            .map(|child| get_content_right(&child.borrow()))
+            .fold(f32::MIN, f32::max),
+        // The size of a block box is the one of its border box.
+        BoxNode::BlockBox(BlockBox { layout_info, .. }) => {
+            layout_info.pos.x - layout_info.used_values.border.left
+                + layout_info.size.width
+                + layout_info.used_values.margin.right
+        }
+        _ => {
+            let layout_info = node.get_layout_info();
+            layout_info.get_expanded_pos().x + layout_info.get_expanded_size().width
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::renderer::html::dom::get_id;
+    use crate::renderer::layout::box_model::BoxTree;
+    use crate::renderer::layout::geometry::Rect;
+
+    /// Returns the border boxes of the elements with IDs in the laid out HTML document, which has no texts.
+    fn layout(html: &str) -> HashMap<String, Rect> {
+        fn helper(node: &Rc<RefCell<BoxNode>>, boxes: &mut HashMap<String, Rect>) {
+            let node = node.borrow();
+            if let Some(style_node) = node.get_element_style_node() {
+                if let Some(id) = get_id(&style_node.borrow().dom_node) {
+                    boxes.insert(id, get_border_box(&node));
+                }
+            }
+            for child in node.get_children() {
+                helper(child, boxes);
+            }
+        }
+
+        let mut boxes = HashMap::new();
+        helper(&BoxTree::from_html(html, 800, 600).root, &mut boxes);
+        boxes
+    }
+
+    #[test]
+    fn collapse_margins() {
+        let collapse = |margins: &[f32]| {
+            margins
+                .iter()
+                .fold(CollapsedMargin::default(), |m, &margin| m.with(margin))
+                .get_size()
+        };
+        assert_eq!(collapse(&[]), 0.0);
+        assert_eq!(collapse(&[16.0, 8.0]), 16.0);
+        assert_eq!(collapse(&[-4.0, 10.0, -6.0, 3.0]), 4.0);
+        assert_eq!(collapse(&[-4.0, -6.0]), -6.0);
+    }
+
+    #[test]
+    fn collapse_margins_in_layout() {
+        // The margins between the siblings.
+        let boxes = layout(
+            "<html><body style='margin: 0'>\
+             <div id=a style='height: 10px; margin: 0 0 20px'></div>\
+             <div id=b style='height: 10px; margin: 30px 0 0'></div>\
+             </body></html>",
+        );
+        assert_eq!(boxes["b"].y, boxes["a"].y + 40.0);
+
+        // The top margins of the parent and its first child, which are passed to the body and collapse
+        // with its margin.
+        let boxes = layout(
+            "<html><body style='margin: 10px'>\
+             <div id=p style='margin: 20px 0 0'><div id=c style='height: 10px; margin: 30px 0 0'></div></div>\
+             </body></html>",
+        );
+        assert_eq!((boxes["p"].y, boxes["c"].y), (30.0, 30.0));
+        // The padding separates them.
+        let boxes = layout(
+            "<html><body style='margin: 0'>\
+             <div id=p style='padding: 1px 0 0; margin: 20px 0 0'>\
+             <div id=c style='height: 10px; margin: 30px 0 0'></div></div>\
+             </body></html>",
+        );
+        assert_eq!((boxes["p"].y, boxes["c"].y), (20.0, 51.0));
+    }
+
+    #[test]
+    fn collapse_bottom_margin_with_parent() {
+        let layout_parent = |style: &str| {
+            let boxes = layout(&format!(
+                "<html><body style='margin: 0'>\
+                 <div id=p style='{}'><div style='height: 10px; margin: 0 0 20px'></div></div>\
+                 <div id=next style='height: 10px'></div>\
+                 </body></html>",
+                style
+            ));
+            (boxes["p"].height, boxes["next"].y - boxes["p"].y)
+        };
+        // The margin of the last child is outside the parent whose height is the one of its contents.
+        assert_eq!(layout_parent(""), (10.0, 30.0));
+        // The percentages behave as `auto` without the height of the containing block.
+        assert_eq!(layout_parent("height: 50%"), (10.0, 30.0));
+        assert_eq!(layout_parent("height: calc(50% + 5px)"), (10.0, 30.0));
+        // The margin is inside the parent whose height is specified.
+        assert_eq!(layout_parent("height: 40px"), (40.0, 40.0));
+        assert_eq!(layout_parent("height: calc(20px + 20px)"), (40.0, 40.0));
+        assert_eq!(layout_parent("min-height: 50px"), (50.0, 50.0));
+        assert_eq!(layout_parent("min-height: 20px"), (30.0, 30.0));
+    }
+
+    #[test]
+    fn collapse_margins_through_empty_blocks() {
+        let layout_empty = |empty: &str| {
+            let boxes = layout(&format!(
+                "<html><body style='margin: 0'>\
+                 <div id=a style='height: 10px; margin: 0 0 10px'></div>\
+                 {}\
+                 <div id=b style='height: 10px; margin: 5px 0 0'></div>\
+                 </body></html>",
+                empty
+            ));
+            boxes["b"].y - boxes["a"].y
+        };
+        assert_eq!(
+            layout_empty("<div style='margin: 20px 0 30px'><div></div></div>"),
+            40.0
+        );
+        assert_eq!(
+            layout_empty("<div style='height: 0; margin: 20px 0 30px'></div>"),
+            40.0
+        );
+        // The empty box with `min-height` or contents overflowing it separates the margins.
+        assert_eq!(
+            layout_empty("<div style='min-height: 1px; margin: 20px 0 30px'></div>"),
+            61.0
+        );
+        assert_eq!(
+            layout_empty(
+                "<div style='height: 0; margin: 20px 0 30px'><div style='height: 5px'></div></div>"
+            ),
+            60.0
+        );
+    }
+
+    #[test]
+    fn keep_margins_of_floats() {
+        let boxes = layout(
+            "<html><body style='margin: 0'>\
+             <div id=a style='height: 10px; margin: 0 0 20px'></div>\
+             <div id=f style='float: right; width: 10px; height: 10px; margin: 5px 0'></div>\
+             <div id=b style='height: 10px; margin: 30px 0 0'></div>\
+             </body></html>",
+        );
+        assert_eq!(boxes["f"].y, boxes["a"].y + 35.0);
+        assert_eq!(boxes["f"].x, 790.0);
+        assert_eq!(boxes["b"].y, boxes["f"].y + 45.0);
+    }
+
+    #[test]
+    fn fill_viewport_in_quirks_mode() {
+        let get_heights = |html: &str| {
+            let boxes = layout(html);
+            (boxes["h"].height, boxes["b"].height)
+        };
+        // The body element fills the html element without its margins, and the html element fills the
+        // viewport, whose height is 600px.
+        assert_eq!(
+            get_heights("<html id=h><body id=b style='margin: 10px'></body></html>"),
+            (600.0, 580.0)
+        );
+        assert_eq!(
+            get_heights(
+                "<html id=h style='padding: 5px'><body id=b style='margin: 10px'></body></html>"
+            ),
+            (600.0, 570.0)
+        );
+        assert_eq!(
+            get_heights("<html id=h><body id=b style='margin: 0'><div style='height: 1000px'></div></body></html>"),
+            (1000.0, 1000.0)
+        );
+        assert_eq!(
+            get_heights("<html id=h><body id=b style='margin: 0; height: 50px'></body></html>"),
+            (600.0, 50.0)
+        );
+        // The heights are the ones of the contents in no-quirks mode.
+        assert_eq!(
+            get_heights("<!DOCTYPE html><html id=h><body id=b style='margin: 0'></body></html>"),
+            (0.0, 0.0)
+        );
+    }
+}
@@ -1,29 +1,64 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::fmt;
 use std::rc::Rc;
 
 use anyhow::{ensure, Context, Ok, Result};
 use gtk4::pango;
 
-use crate::renderer::html::dom::{Element, NodeType};
+use crate::renderer::html::dom::{DomNode, Element, NodeType};
+use crate::renderer::html::image_map::get_image_map_areas;
+use crate::renderer::html::mutation::clear_layout_dirty_flags;
 use crate::renderer::layout::block::{AnonymousBox, BlockBox};
+use crate::renderer::layout::geometry::Rect;
 use crate::renderer::layout::inline::InlineBox;
-use crate::renderer::layout::text::Text;
-use crate::renderer::style::property::display::DisplayOutside;
+use crate::renderer::layout::math::MathLayouter;
+use crate::renderer::layout::overflow::{
+    clips_overflow, get_contents_clip, scroll_boxes, ScrollBox,
+};
+use crate::renderer::layout::positioned::layout_positioned_boxes;
+use crate::renderer::layout::replaced::ReplacedBox;
+use crate::renderer::layout::stacking::paint_box_tree;
+use crate::renderer::layout::table::fix_up_table_children;
+use crate::renderer::layout::text::{EditableText, MisspelledWord, Text};
+use crate::renderer::style::property::color::Color;
+use crate::renderer::style::property::display::{DisplayOutside, DisplayProp};
+use crate::renderer::style::property::TextDecorationProp;
 use crate::renderer::style::style_model::{RenderNode, RenderTree};
+use crate::renderer::svg::render_svg;
 use crate::renderer::{RenderObject, RenderObjects};
 use crate::utils::PrintableTree;
 
+thread_local! {
+    /// The number of times the boxes are laid out on this thread, which is reported in the statistics.
+    static LAYOUT_COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Returns the number of times the boxes are laid out on this thread since the last call.
+pub fn take_layout_count() -> usize {
+    LAYOUT_COUNT.take()
+}
+
+/// The border boxes of the elements on the canvas paired with the elements, in painting order.
+pub type HitRegions = Vec<(Rect, Rc<RefCell<DomNode>>)>;
+
+type ElementBoxesVisitor<'a> = dyn FnMut(&Rc<RefCell<DomNode>>, ElementBoxes, Option<Rect>) + 'a;
+
 /// https://www.w3.org/TR/css-display-3/#box-tree
 #[derive(Debug)]
 pub struct BoxTree {
     pub root: Rc<RefCell<BoxNode>>,
+    /// The scroll containers found by the last layout, in tree order.
+    scroll_boxes: Vec<ScrollBox>,
 }
 
 impl BoxTree {
+    #[tracing::instrument(skip_all)]
     pub fn build(render_tree: &RenderTree, draw_ctx: &pango::Context) -> Result<Self> {
         ensure!(
-            render_tree.root.borrow().dom_node.borrow().node_type == NodeType::Document,
+            matches!(
+                render_tree.root.borrow().dom_node.borrow().node_type,
+                NodeType::Document(_)
+            ),
             "The root node of the render tree must be a document node."
         );
 
@@ -43,14 +78,18 @@ impl BoxTree {
             "The element at the root box of the box tree must be an HTML element node."
         );
 
+        // All the boxes are built and laid out from scratch for now.
+        clear_layout_dirty_flags(&render_tree.root.borrow().dom_node);
         Ok(Self {
             root: Rc::new(RefCell::new(
-                BoxNode::build(root.unwrap(), None, draw_ctx)
+                BoxNode::build(root.unwrap(), None, &[], draw_ctx)
                     .context("Failed to build box tree")?,
             )),
+            scroll_boxes: Vec::new(),
         })
     }
 
+    #[tracing::instrument(skip_all)]
     pub fn layout(&mut self, viewport_width: i32, viewport_height: i32) -> Result<&mut Self> {
         self.root.borrow_mut().layout(
             // The containing block of the root element is initial containing block,
@@ -68,22 +107,56 @@ impl BoxTree {
             None,
             None,
         );
+        layout_positioned_boxes(
+            &self.root,
+            Rect::new(0.0, 0.0, viewport_width as f64, viewport_height as f64),
+        );
+        self.scroll_boxes = scroll_boxes(&self.root);
         Ok(self)
     }
 
-    /// Removes unnecessary whitespace from all text nodes in the tree.
-    /// https://developer.mozilla.org/en-US/docs/Web/API/Document_Object_Model/Whitespace
+    /// Builds and lays out the box tree of the HTML document styled with the user agent style sheet. The
+    /// fonts are not loaded, so the documents for this should have no texts.
+    #[cfg(test)]
+    pub fn from_html(html: &str, viewport_width: i32, viewport_height: i32) -> Self {
+        use crate::renderer::css::get_ua_style_sheet;
+        use crate::renderer::css::media::MediaEnvironment;
+        use crate::renderer::html::dom::DocumentTree;
+        use crate::renderer::html::parser::HtmlParser;
+        use crate::renderer::html::token::HtmlTokenizer;
+
+        let (document, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let style_sheets = std::iter::once(get_ua_style_sheet().unwrap())
+            .chain(style_sheets)
+            .collect::<Vec<_>>();
+        let render_tree = DocumentTree::build(document)
+            .unwrap()
+            .to_render_tree(style_sheets, &MediaEnvironment::default())
+            .unwrap();
+        let mut box_tree = Self::build(&render_tree, &pango::Context::new()).unwrap();
+        box_tree
+            .clean_up()
+            .unwrap()
+            .layout(viewport_width, viewport_height)
+            .unwrap();
+        box_tree
+    }
+
+    /// Removes the anonymous boxes which have nothing to lay out, i.e. the ones around the white space between
+    /// the block-level boxes, which collapses away. The white space in the other texts is processed when
+    /// they're laid out in the line boxes.
+    /// https://drafts.csswg.org/css-text/#white-space-processing
+    #[tracing::instrument(skip_all)]
     pub fn clean_up(&mut self) -> Result<&mut Self> {
-        Ok(self.trim_text()?.remove_empty_anonymous_boxes())
+        Ok(self.remove_empty_anonymous_boxes())
     }
 
-    fn trim_text(&mut self) -> Result<&mut Self> {
-        fn helper(node: &mut Rc<RefCell<BoxNode>>) -> Result<()> {
-            if let BoxNode::Text(_) = *node.borrow() {
-                return Ok(());
+    fn remove_empty_anonymous_boxes(&mut self) -> &mut Self {
+        fn helper(node: &mut Rc<RefCell<BoxNode>>) {
+            if let BoxNode::Text(_) | BoxNode::ReplacedBox(_) = *node.borrow() {
+                return;
             }
 
-            let mut remove_list: Vec<usize> = vec![];
             let mut n = node.borrow_mut();
             let children_enum = match &mut *n {
                 BoxNode::BlockBox(BlockBox { children, .. })
@@ -91,26 +164,23 @@ impl BoxTree {
                 | BoxNode::AnonymousBox(AnonymousBox { children, .. }) => {
                     children.iter_mut().enumerate()
                 }
-                BoxNode::Text(_) => unreachable!(),
+                BoxNode::Text(_) | BoxNode::ReplacedBox(_) => unreachable!(),
             };
-            let children_num = children_enum.len();
+            let mut remove_list: Vec<usize> = vec![];
 
             for (i, child) in children_enum {
-                if let BoxNode::Text(t) = &mut *child.borrow_mut() {
-                    t.trim_text(i == 0, i == children_num - 1)?;
-                    if t.style_node
-                        .borrow()
-                        .dom_node
-                        .borrow()
-                        .get_inside_text()
-                        .unwrap()
-                        .is_empty()
-                    {
+                if let BoxNode::AnonymousBox(AnonymousBox { children, .. }) = &*child.borrow() {
+                    let is_empty = children.iter().all(|child| {
+                        matches!(&*child.borrow(), BoxNode::Text(t) if t.is_collapsible_white_space())
+                    });
+                    if is_empty {
                         remove_list.push(i);
                     }
                 }
+
+                // If the child is not removed as an empty anonymous box, recursively check its children.
                 if !remove_list.contains(&i) {
-                    helper(child)?;
+                    helper(child);
                 }
             }
             for i in remove_list.iter().rev() {
@@ -120,75 +190,196 @@ impl BoxTree {
                     | BoxNode::AnonymousBox(AnonymousBox { children, .. }) => {
                         children.remove(*i);
                     }
-                    BoxNode::Text(_) => {}
+                    BoxNode::Text(_) | BoxNode::ReplacedBox(_) => {}
                 }
             }
-
-            Ok(())
         }
 
-        helper(&mut self.root)?;
-        Ok(self)
+        helper(&mut self.root);
+        self
     }
 
-    fn remove_empty_anonymous_boxes(&mut self) -> &mut Self {
-        fn helper(node: &mut Rc<RefCell<BoxNode>>) {
-            if let BoxNode::Text(_) = *node.borrow() {
+    /// Returns the union of the border boxes generated by the node, or `None` if it generates no box.
+    /// This is the bounding client rect used to compute the intersections of the node.
+    pub fn get_border_box(&self, node: &Rc<RefCell<DomNode>>) -> Option<Rect> {
+        self.get_element_boxes(node).map(|boxes| boxes.border_box)
+    }
+
+    /// Returns the unions of the content, padding and border boxes generated by the node, or `None` if it
+    /// generates no box.
+    pub fn get_element_boxes(&self, node: &Rc<RefCell<DomNode>>) -> Option<ElementBoxes> {
+        let mut boxes: Option<ElementBoxes> = None;
+        self.visit_element_boxes(&mut |dom_node, new, _| {
+            if !Rc::ptr_eq(dom_node, node) {
                 return;
             }
+            boxes = Some(match boxes {
+                Some(boxes) => ElementBoxes {
+                    content_box: boxes.content_box.union(&new.content_box),
+                    padding_box: boxes.padding_box.union(&new.padding_box),
+                    border_box: boxes.border_box.union(&new.border_box),
+                },
+                None => new,
+            });
+        });
+        boxes
+    }
 
-            let mut n = node.borrow_mut();
-            let children_enum = match &mut *n {
-                BoxNode::BlockBox(BlockBox { children, .. })
-                | BoxNode::InlineBox(InlineBox { children, .. })
-                | BoxNode::AnonymousBox(AnonymousBox { children, .. }) => {
-                    children.iter_mut().enumerate()
-                }
-                BoxNode::Text(_) => unreachable!(),
+    /// Returns the border boxes of the boxes generated by the elements in tree order, paired with the
+    /// elements. The boxes later in the list are painted above the earlier ones, so the last box which
+    /// contains a point is the one the pointer is over. The boxes are clipped to the padding boxes of their
+    /// ancestors which clip their overflow, and the ones clipped entirely are left out.
+    /// https://www.w3.org/TR/CSS22/zindex.html
+    pub fn get_hit_regions(&self) -> HitRegions {
+        let mut regions = Vec::new();
+        self.visit_element_boxes(&mut |dom_node, boxes, clip| {
+            let border_box = match clip {
+                Some(clip) => boxes.border_box.intersect(&clip),
+                None => Some(boxes.border_box),
             };
-            let mut remove_list: Vec<usize> = vec![];
+            if let Some(border_box) = border_box {
+                regions.push((border_box, Rc::clone(dom_node)));
+            }
+        });
+        regions
+    }
 
-            for (i, child) in children_enum {
-                if let BoxNode::AnonymousBox(AnonymousBox { children, .. }) = &*child.borrow() {
-                    if children.is_empty() {
-                        remove_list.push(i);
-                    }
-                }
+    /// Returns the misspelled words in the texts of the page in tree order.
+    pub fn get_misspelled_words(&self) -> Vec<MisspelledWord> {
+        let mut words = Vec::new();
+        self.visit_texts(&mut |t| words.extend(t.get_misspelled_words()));
+        words
+    }
 
-                // If the child is not removed as an empty anonymous box, recursively check its children.
-                if !remove_list.contains(&i) {
-                    helper(child);
-                }
+    /// Returns the fragments of the editable texts of the page in tree order.
+    pub fn get_editable_texts(&self) -> Vec<EditableText> {
+        let mut texts = Vec::new();
+        self.visit_texts(&mut |t| texts.extend(t.get_editable_texts()));
+        texts
+    }
+
+    /// Calls `f` with each text in the page in tree order.
+    fn visit_texts(&self, f: &mut impl FnMut(&Text)) {
+        fn helper(node: &Rc<RefCell<BoxNode>>, f: &mut impl FnMut(&Text)) {
+            if let BoxNode::Text(t) = &*node.borrow() {
+                f(t);
             }
-            for i in remove_list.iter().rev() {
-                match &mut *n {
-                    BoxNode::BlockBox(BlockBox { children, .. })
-                    | BoxNode::InlineBox(InlineBox { children, .. })
-                    | BoxNode::AnonymousBox(AnonymousBox { children, .. }) => {
-                        children.remove(*i);
-                    }
-                    BoxNode::Text(_) => {}
-                }
+            for child in node.borrow().get_children() {
+                helper(child, f);
             }
         }
+        helper(&self.root, f);
+    }
 
-        helper(&mut self.root);
-        self
+    /// Returns the scroll containers laid out in the page in tree order, so that the inner ones follow the
+    /// ones containing them.
+    pub fn get_scroll_boxes(&self) -> Vec<ScrollBox> {
+        self.scroll_boxes.clone()
     }
 
+    /// Calls `f` with the boxes of each box generated by an element, i.e. not the anonymous boxes and the
+    /// text, in tree order, and the area to which the box is clipped by its ancestors if any.
+    fn visit_element_boxes(&self, f: &mut ElementBoxesVisitor<'_>) {
+        fn helper(box_node: &BoxNode, clip: Option<Rect>, f: &mut ElementBoxesVisitor<'_>) {
+            let (style_node, layout_info, children) = match box_node {
+                BoxNode::BlockBox(BlockBox {
+                    style_node,
+                    layout_info,
+                    children,
+                    ..
+                })
+                | BoxNode::InlineBox(InlineBox {
+                    style_node,
+                    layout_info,
+                    children,
+                }) => (Some(style_node), layout_info, &children[..]),
+                BoxNode::ReplacedBox(ReplacedBox {
+                    style_node,
+                    layout_info,
+                    ..
+                }) => (Some(style_node), layout_info, &[][..]),
+                BoxNode::AnonymousBox(AnonymousBox {
+                    layout_info,
+                    children,
+                    ..
+                }) => (None, layout_info, &children[..]),
+                BoxNode::Text(_) => return,
+            };
+            if let Some(style_node) = style_node {
+                let LayoutInfo {
+                    size,
+                    pos,
+                    used_values:
+                        UsedValues {
+                            padding, border, ..
+                        },
+                } = layout_info;
+                let content_box = Rect::new(
+                    pos.x as f64,
+                    pos.y as f64,
+                    size.width as f64,
+                    size.height as f64,
+                );
+                let padding_box = content_box.expand((
+                    padding.top as f64,
+                    padding.right as f64,
+                    padding.bottom as f64,
+                    padding.left as f64,
+                ));
+                let border_box = padding_box.expand((
+                    border.top as f64,
+                    border.right as f64,
+                    border.bottom as f64,
+                    border.left as f64,
+                ));
+                f(
+                    &style_node.borrow().dom_node,
+                    ElementBoxes {
+                        content_box,
+                        padding_box,
+                        border_box,
+                    },
+                    clip,
+                );
+            }
+            let clip = get_contents_clip(box_node, clip);
+            for child in children {
+                helper(&child.borrow(), clip, f);
+            }
+        }
+
+        helper(&self.root.borrow(), None, f);
+    }
+
+    #[tracing::instrument(skip_all)]
     pub fn to_render_objects(&self, viewport_width: i32, viewport_height: i32) -> RenderObjects {
-        let mut objects = Vec::new();
-        let (w, h) = self.root.borrow().to_render_objects(
-            &mut objects,
+        // The contents clipped by the boxes don't extend the canvas.
+        fn get_largest_size(node: &BoxNode) -> (f32, f32) {
+            let size = node.get_layout_info().size;
+            if clips_overflow(node) {
+                return (size.width, size.height);
+            }
+            node.get_children()
+                .iter()
+                .map(|child| get_largest_size(&child.borrow()))
+                .fold((size.width, size.height), |(w, h), (cw, ch)| {
+                    (w.max(cw), h.max(ch))
+                })
+        }
+
+        let (objects, scroll_layers) = paint_box_tree(
+            &self.root,
+            &self.scroll_boxes,
             viewport_width,
             viewport_height,
-            0.0,
-            0.0,
         );
+        let (max_width, max_height) = get_largest_size(&self.root.borrow());
         RenderObjects {
             list: objects,
-            max_width: w,
-            max_height: h,
+            scroll_layers,
+            max_width,
+            max_height,
+            ..Default::default()
         }
     }
 }
@@ -217,7 +408,7 @@ impl fmt::Display for BoxTree {
             node_tree.push_str(&format!("{}{}\n", indent_and_branches, node.borrow()));
 
             // No children.
-            if let BoxNode::Text(_) = *node.borrow() {
+            if let BoxNode::Text(_) | BoxNode::ReplacedBox(_) = *node.borrow() {
                 return;
             }
 
@@ -228,7 +419,7 @@ impl fmt::Display for BoxTree {
                 | BoxNode::AnonymousBox(AnonymousBox { children, .. }) => {
                     children.iter_mut().enumerate()
                 }
-                BoxNode::Text(_) => unreachable!(),
+                BoxNode::Text(_) | BoxNode::ReplacedBox(_) => unreachable!(),
             };
             let children_num = children_enum.len();
             for (i, child) in children_enum {
@@ -250,6 +441,14 @@ impl fmt::Display for BoxTree {
 
 impl PrintableTree for BoxTree {}
 
+/// The boxes of an element on the canvas, which are the unions of the boxes of its fragments.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ElementBoxes {
+    pub content_box: Rect,
+    pub padding_box: Rect,
+    pub border_box: Rect,
+}
+
 /// Calculated width, height, and position of the BoxNode and its `used values` of the `width`, `margin`, `padding`, and `border` properties.
 #[derive(Debug, Default, Clone)]
 pub struct LayoutInfo {
@@ -279,6 +478,17 @@ impl LayoutInfo {
         }
     }
 
+    /// Returns the width of the box excluding the padding and border, against which the percentages of
+    /// the widths and margins of the child boxes are resolved.
+    /// https://www.w3.org/TR/CSS22/box.html#margin-properties
+    pub fn get_content_width(&self) -> f32 {
+        self.size.width
+            - self.used_values.padding.left
+            - self.used_values.padding.right
+            - self.used_values.border.left
+            - self.used_values.border.right
+    }
+
     /// Returns the position of the box after applying the margin, padding, and border properties.
     pub fn get_expanded_pos(&self) -> BoxPosition {
         BoxPosition {
@@ -349,16 +559,25 @@ pub enum BoxNode {
 
     /// https://www.w3.org/TR/css-display-3/#anonymous
     AnonymousBox(AnonymousBox),
+
+    /// https://www.w3.org/TR/css-display-3/#replaced-element
+    ReplacedBox(ReplacedBox),
 }
 
 impl BoxNode {
+    /// Builds the box of the node and the boxes inside it, where `decorations` are the text decorations
+    /// propagated to the node from its ancestors.
+    #[tracing::instrument(skip_all)]
     pub fn build(
         style_node: Rc<RefCell<RenderNode>>,
         parent_style_node: Option<Rc<RefCell<RenderNode>>>,
+        decorations: &[TextDecorationProp],
         draw_ctx: &pango::Context,
     ) -> Option<Self> {
         match style_node.borrow().dom_node.borrow().node_type {
-            NodeType::Document | NodeType::Comment(_) | NodeType::DocumentType(_) => return None,
+            NodeType::Document(_) | NodeType::Comment(_) | NodeType::DocumentType(_) => {
+                return None
+            }
             NodeType::Text(_) => {
                 if parent_style_node.is_none() {
                     unreachable!()
@@ -367,18 +586,103 @@ impl BoxNode {
                     style_node: Rc::clone(&style_node),
                     layout_info: LayoutInfo::default(),
                     draw_ctx: draw_ctx.clone(),
+                    lines: Vec::new(),
+                    decorations: decorations.to_vec(),
                 }));
             }
             _ => {}
         }
 
+        // Set the used values for the padding and border properties.
+        // The margin property is set later because it needs to be resolved if an `auto` value is set.
+        let padding = style_node.borrow().style.padding.to_px().unwrap();
+        let border = style_node
+            .borrow()
+            .style
+            .border
+            .border_width
+            .to_px()
+            .unwrap();
+
+        // The children of replaced elements are fallback content, which is not rendered.
+        if ReplacedBox::is_replaced_element(&style_node.borrow()) {
+            let math = match &style_node.borrow().dom_node.borrow().node_type {
+                NodeType::Element(elm) if elm.tag_name == "math" => {
+                    let style = &style_node.borrow().style;
+                    Some(
+                        MathLayouter::new(
+                            draw_ctx,
+                            style.font_family.to_name_list().unwrap(),
+                            style.font_weight.to_name().unwrap(),
+                            style.font_size.to_px().unwrap() as f64,
+                        )
+                        .layout(
+                            &style_node.borrow().dom_node,
+                            elm.get_attribute("display") == Some("block"),
+                        ),
+                    )
+                }
+                _ => None,
+            };
+            return Some(Self::ReplacedBox(ReplacedBox {
+                style_node: Rc::clone(&style_node),
+                layout_info: LayoutInfo {
+                    used_values: UsedValues {
+                        padding,
+                        border,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                math,
+            }));
+        }
+
+        // The text decorations are propagated to the in-flow contents, but not to the out-of-flow boxes nor
+        // into the atomic inline-level boxes.
+        // https://drafts.csswg.org/css-text-decor/#line-decoration
+        let decorations = {
+            let style = &style_node.borrow().style;
+            let mut propagated =
+                if style.display.is_atomic_inline() || style.position.is_absolutely_positioned() {
+                    Vec::new()
+                } else {
+                    decorations.to_vec()
+                };
+            if style.text_decoration.has_lines() {
+                propagated.push(style.text_decoration.clone());
+            }
+            propagated
+        };
+
         // Create box nodes for the children of the current node.
         let mut children: Vec<Rc<RefCell<BoxNode>>> = Vec::new();
         let mut i = 0;
         while i < style_node.borrow().children.len() {
+            // The absolutely positioned boxes are block-level, but they're out of flow, so they may be in
+            // inline boxes and don't break the runs of inline-level contents.
+            let is_out_of_flow = style_node.borrow().children[i]
+                .borrow()
+                .style
+                .position
+                .is_absolutely_positioned();
             match style_node.borrow().children[i].borrow().get_display_type() {
+                DisplayOutside::Block
+                    if is_out_of_flow
+                        && !style_node.borrow().style.display.is_block_container() =>
+                {
+                    let child = Self::build(
+                        Rc::clone(&style_node.borrow().children[i]),
+                        Some(Rc::clone(&style_node)),
+                        &decorations,
+                        draw_ctx,
+                    );
+                    if let Some(child) = child {
+                        children.push(Rc::new(RefCell::new(child)));
+                    }
+                }
                 DisplayOutside::Block => {
-                    if style_node.borrow().get_display_type() == DisplayOutside::Inline {
+                    if !style_node.borrow().style.display.is_block_container() {
                         // todo: It is tricky to handle block-level boxes within an inline box.
                         // https://www.w3.org/TR/CSS22/visuren.html#anonymous-block-level
                         // https://github.com/w3c/csswg-drafts/issues/1477
@@ -391,6 +695,7 @@ impl BoxNode {
                     let child = Self::build(
                         Rc::clone(&style_node.borrow().children[i]),
                         Some(Rc::clone(&style_node)),
+                        &decorations,
                         draw_ctx,
                     );
                     if let Some(child) = child {
@@ -399,24 +704,31 @@ impl BoxNode {
                 }
                 DisplayOutside::Inline => {
                     // If the number of children is greater than 1, wrap all inline-level contents in an anonymous box.
-                    if (style_node.borrow().get_display_type() == DisplayOutside::Block)
+                    if style_node.borrow().style.display.is_block_container()
                         && (style_node.borrow().children.len() > 1)
                     {
                         let mut anon_box = AnonymousBox {
                             style: Box::new(style_node.borrow().style.clone()),
                             layout_info: LayoutInfo::default(),
                             children: vec![],
+                            draw_ctx: draw_ctx.clone(),
                         };
 
                         // If there are successive inline-level contents, they are wrapped in the same anonymous box.
                         // https://www.w3.org/TR/css-inline-3/#root-inline-box
                         while i < style_node.borrow().children.len()
-                            && style_node.borrow().children[i].borrow().get_display_type()
+                            && (style_node.borrow().children[i].borrow().get_display_type()
                                 == DisplayOutside::Inline
+                                || style_node.borrow().children[i]
+                                    .borrow()
+                                    .style
+                                    .position
+                                    .is_absolutely_positioned())
                         {
                             let child = Self::build(
                                 Rc::clone(&style_node.borrow().children[i]),
                                 Some(Rc::clone(&style_node)),
+                                &decorations,
                                 draw_ctx,
                             );
                             if let Some(child) = child {
@@ -430,6 +742,7 @@ impl BoxNode {
                         let child = Self::build(
                             Rc::clone(&style_node.borrow().children[i]),
                             Some(Rc::clone(&style_node)),
+                            &decorations,
                             draw_ctx,
                         );
                         if let Some(child) = child {
@@ -442,46 +755,107 @@ impl BoxNode {
             i += 1;
         }
 
-        // Set the used values for the padding and border properties.
-        // The margin property is set later because it needs to be resolved if an `auto` value is set.
-        let padding = style_node.borrow().style.padding.to_px().unwrap();
-        let border = style_node
-            .borrow()
-            .style
-            .border
-            .border_width
-            .to_px()
-            .unwrap();
+        let display = style_node.borrow().style.display.clone();
+        let children =
+            fix_up_table_children(&display, &style_node.borrow().style, children, draw_ctx);
 
-        match style_node.borrow().get_display_type() {
-            DisplayOutside::Block => Some(Self::BlockBox(BlockBox {
+        // The atomic inline-level boxes such as `inline-block` are block containers, which are laid out as
+        // block boxes inside and placed as inline-level boxes.
+        let layout_info = LayoutInfo {
+            used_values: UsedValues {
+                padding,
+                border,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        if display.is_block_container() {
+            Some(Self::BlockBox(BlockBox {
                 style_node: Rc::clone(&style_node),
-                layout_info: LayoutInfo {
-                    used_values: UsedValues {
-                        padding,
-                        border,
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                },
+                layout_info,
                 children,
-            })),
-            DisplayOutside::Inline => Some(Self::InlineBox(InlineBox {
+                draw_ctx: draw_ctx.clone(),
+            }))
+        } else {
+            Some(Self::InlineBox(InlineBox {
                 style_node: Rc::clone(&style_node),
-                layout_info: LayoutInfo {
-                    used_values: UsedValues {
-                        padding,
-                        border,
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                },
+                layout_info,
                 children,
-            })),
+            }))
+        }
+    }
+
+    /// Returns the display of the box, which is the initial one for the texts.
+    pub fn get_display(&self) -> DisplayProp {
+        match self {
+            Self::BlockBox(BlockBox { style_node, .. })
+            | Self::InlineBox(InlineBox { style_node, .. })
+            | Self::ReplacedBox(ReplacedBox { style_node, .. }) => {
+                style_node.borrow().style.display.clone()
+            }
+            Self::AnonymousBox(AnonymousBox { style, .. }) => style.display.clone(),
+            Self::Text(_) => DisplayProp::default(),
+        }
+    }
+
+    pub fn get_layout_info(&self) -> &LayoutInfo {
+        match self {
+            Self::BlockBox(BlockBox { layout_info, .. })
+            | Self::InlineBox(InlineBox { layout_info, .. })
+            | Self::Text(Text { layout_info, .. })
+            | Self::AnonymousBox(AnonymousBox { layout_info, .. })
+            | Self::ReplacedBox(ReplacedBox { layout_info, .. }) => layout_info,
+        }
+    }
+
+    pub fn get_layout_info_mut(&mut self) -> &mut LayoutInfo {
+        match self {
+            Self::BlockBox(BlockBox { layout_info, .. })
+            | Self::InlineBox(InlineBox { layout_info, .. })
+            | Self::Text(Text { layout_info, .. })
+            | Self::AnonymousBox(AnonymousBox { layout_info, .. })
+            | Self::ReplacedBox(ReplacedBox { layout_info, .. }) => layout_info,
+        }
+    }
+
+    /// Returns the style node of the element which generates the box. The anonymous boxes have none, and the
+    /// texts only take the style of their parents.
+    pub fn get_element_style_node(&self) -> Option<Rc<RefCell<RenderNode>>> {
+        match self {
+            Self::BlockBox(BlockBox { style_node, .. })
+            | Self::InlineBox(InlineBox { style_node, .. })
+            | Self::ReplacedBox(ReplacedBox { style_node, .. }) => Some(Rc::clone(style_node)),
+            Self::Text(_) | Self::AnonymousBox(_) => None,
+        }
+    }
+
+    pub fn get_children(&self) -> &[Rc<RefCell<BoxNode>>] {
+        match self {
+            Self::BlockBox(BlockBox { children, .. })
+            | Self::InlineBox(InlineBox { children, .. })
+            | Self::AnonymousBox(AnonymousBox { children, .. }) => children,
+            Self::Text(_) | Self::ReplacedBox(_) => &[],
+        }
+    }
+
+    /// Moves the laid out box and the boxes inside it.
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        let pos = &mut self.get_layout_info_mut().pos;
+        pos.x += dx;
+        pos.y += dy;
+        if let Self::Text(t) = self {
+            for line in &mut t.lines {
+                line.x += dx;
+                line.y += dy;
+            }
+        }
+        for child in self.get_children() {
+            child.borrow_mut().translate(dx, dy);
         }
     }
 
     /// Sets the width, height, position, and used values for some properties of the box and its children.
+    #[tracing::instrument(skip_all)]
     pub fn layout(
         &mut self,
         // https://www.w3.org/TR/CSS22/visudet.html#containing-block-details
@@ -489,6 +863,7 @@ impl BoxNode {
         parent_info: Option<LayoutInfo>,
         prev_sibling_info: Option<LayoutInfo>,
     ) -> &mut Self {
+        LAYOUT_COUNT.set(LAYOUT_COUNT.get() + 1);
         match self {
             Self::BlockBox(b) => {
                 b.layout(containing_block_info, parent_info, prev_sibling_info);
@@ -502,95 +877,54 @@ impl BoxNode {
             Self::Text(t) => {
                 t.layout(containing_block_info, parent_info, prev_sibling_info);
             }
+            Self::ReplacedBox(b) => {
+                b.layout(containing_block_info, parent_info, prev_sibling_info);
+            }
         }
         self
     }
 
+    /// Appends the render objects of the box itself to `objects`, i.e. its background, its text or its
+    /// replaced contents, but not the ones of the boxes inside it, which are painted in the order of their
+    /// stacking contexts.
     pub fn to_render_objects(
         &self,
         objects: &mut Vec<RenderObject>,
         viewport_width: i32,
         viewport_height: i32,
-        largest_width: f32,
-        largest_height: f32,
-    ) -> (f32, f32) {
+    ) {
         match self {
             BoxNode::Text(t) => {
-                let color = t.style_node.borrow().style.color.to_rgba().unwrap();
-                let decoration_color = t
-                    .style_node
-                    .borrow()
-                    .style
-                    .text_decoration
-                    .color
-                    .to_rgba()
-                    .unwrap();
-                let decoration_line = t
-                    .style_node
-                    .borrow()
-                    .style
-                    .text_decoration
-                    .line
-                    .iter()
-                    .map(|v| v.to_name().unwrap())
-                    .collect::<Vec<String>>();
-                let decoration_style = t
-                    .style_node
-                    .borrow()
-                    .style
-                    .text_decoration
-                    .style
-                    .to_name()
-                    .unwrap();
-                objects.push(RenderObject::Text {
-                    text: t
-                        .style_node
-                        .borrow()
-                        .dom_node
-                        .borrow()
-                        .get_inside_text()
-                        .unwrap(),
-                    x: t.layout_info.pos.x as f64,
-                    y: t.layout_info.pos.y as f64,
-                    font_family: t
-                        .style_node
-                        .borrow()
-                        .style
-                        .font_family
-                        .to_name_list()
-                        .unwrap(),
-                    font_size: t.style_node.borrow().style.font_size.to_px().unwrap() as f64,
-                    font_weight: t.style_node.borrow().style.font_weight.to_name().unwrap(),
-                    color: (
-                        color.0 as f64 / 255.0,
-                        color.1 as f64 / 255.0,
-                        color.2 as f64 / 255.0,
-                    ),
-                    decoration_color: (
-                        decoration_color.0 as f64 / 255.0,
-                        decoration_color.1 as f64 / 255.0,
-                        decoration_color.2 as f64 / 255.0,
-                    ),
-                    decoration_line,
-                    decoration_style,
-                });
-                let mut largest_width = largest_width;
-                let mut largest_height = largest_height;
-                if t.layout_info.size.width > largest_width {
-                    largest_width = t.layout_info.size.width;
-                }
-                if t.layout_info.size.height > largest_height {
-                    largest_height = t.layout_info.size.height;
+                let color = t.style_node.borrow().style.color.to_color().unwrap();
+                let font = t.get_selected_font();
+                // Each fragment of the text in the line boxes is painted at its own position, between the
+                // lines of its decorations, and split into the segments in the families of the fallback.
+                for line in &t.lines {
+                    let (before, after) = t.get_decoration_objects(line);
+                    objects.extend(before);
+                    for segment in t.get_segments(line) {
+                        objects.push(RenderObject::Text {
+                            text: segment.text,
+                            x: segment.x as f64,
+                            y: segment.y as f64,
+                            font_family: segment.families,
+                            font_size: font.size as f64,
+                            font_weight: font.get_weight_name(),
+                            font_style: font.get_style_name(),
+                            color: color.to_unit_rgba(),
+                        });
+                    }
+                    objects.extend(after);
                 }
-                (largest_width, largest_height)
+                objects.extend(t.get_spelling_error_objects());
             }
             BoxNode::BlockBox(block) => {
-                let (r, g, b, a) = block
+                let color = block
                     .style_node
                     .borrow()
                     .style
                     .background_color
-                    .to_rgba()
+                    .to_color()
                     .unwrap();
                 let border_radius = block
                     .style_node
@@ -604,13 +938,13 @@ impl BoxNode {
                 let is_body = if let NodeType::Element(Element { tag_name: n, .. }) =
                     &block.style_node.borrow().dom_node.borrow().node_type
                 {
-                    n == "body"
+                    n == "body" && block.style_node.borrow().pseudo_element.is_none()
                 } else {
                     false
                 };
 
                 // Draw the rectangle only if the background color is not transparent.
-                if a != 0.0 {
+                if color.a != 0.0 {
                     objects.push(RenderObject::Rect {
                         x: block.layout_info.pos.x as f64,
                         y: block.layout_info.pos.y as f64,
@@ -624,92 +958,70 @@ impl BoxNode {
                         } else {
                             block.layout_info.size.height as f64
                         },
-                        color: (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0),
+                        color: color.to_unit_rgba(),
                         border_radius,
                     });
                 }
-                let mut largest_width = if block.layout_info.size.width > largest_width {
-                    block.layout_info.size.width
-                } else {
-                    largest_width
-                };
-                let mut largest_height = if block.layout_info.size.height > largest_height {
-                    block.layout_info.size.height
-                } else {
-                    largest_height
-                };
-                for child in block.children.iter() {
-                    let (w, h) = child.borrow().to_render_objects(
-                        objects,
-                        viewport_width,
-                        viewport_height,
-                        largest_width,
-                        largest_height,
-                    );
-                    if w > largest_width {
-                        largest_width = w;
-                    }
-                    if h > largest_height {
-                        largest_height = h;
-                    }
-                }
-                (largest_width, largest_height)
             }
-            BoxNode::InlineBox(inline) => {
-                let mut largest_width = if inline.layout_info.size.width > largest_width {
-                    inline.layout_info.size.width
-                } else {
-                    largest_width
-                };
-                let mut largest_height = if inline.layout_info.size.height > largest_height {
-                    inline.layout_info.size.height
-                } else {
-                    largest_height
-                };
-                for child in inline.children.iter() {
-                    let (w, h) = child.borrow().to_render_objects(
-                        objects,
-                        viewport_width,
-                        viewport_height,
-                        largest_width,
-                        largest_height,
-                    );
-                    if w > largest_width {
-                        largest_width = w;
-                    }
-                    if h > largest_height {
-                        largest_height = h;
-                    }
-                }
-                (largest_width, largest_height)
-            }
-            BoxNode::AnonymousBox(anonymous) => {
-                let mut largest_width = if anonymous.layout_info.size.width > largest_width {
-                    anonymous.layout_info.size.width
-                } else {
-                    largest_width
+            BoxNode::InlineBox(_) | BoxNode::AnonymousBox(_) => {}
+            BoxNode::ReplacedBox(replaced) => {
+                let dom_node = Rc::clone(&replaced.style_node.borrow().dom_node);
+                let NodeType::Element(elm) = &dom_node.borrow().node_type else {
+                    unreachable!()
                 };
-                let mut largest_height = if anonymous.layout_info.size.height > largest_height {
-                    anonymous.layout_info.size.height
+                if elm.tag_name == "img" {
+                    objects.push(RenderObject::Image {
+                        x: replaced.layout_info.pos.x as f64,
+                        y: replaced.layout_info.pos.y as f64,
+                        width: replaced.layout_info.size.width as f64,
+                        height: replaced.layout_info.size.height as f64,
+                        src: elm
+                            .get_attribute("src")
+                            .filter(|url| !url.is_empty())
+                            .map(|url| url.to_string()),
+                        alt: elm.get_attribute("alt").unwrap_or_default().to_string(),
+                        areas: get_image_map_areas(&dom_node),
+                        lazy: elm.is_lazy_loading() && !dom_node.borrow().lazy_load_resumed,
+                    });
+                } else if let Some(math) = &replaced.math {
+                    let style = &replaced.style_node.borrow().style;
+                    let color = style.color.to_color().unwrap_or(Color::BLACK);
+                    objects.extend(math.to_render_objects(
+                        replaced.layout_info.pos.x as f64,
+                        replaced.layout_info.pos.y as f64,
+                        &style.font_family.to_name_list().unwrap(),
+                        &style.font_weight.to_name().unwrap(),
+                        color.to_unit_rgba(),
+                    ));
+                } else if elm.tag_name == "svg" {
+                    let color = replaced
+                        .style_node
+                        .borrow()
+                        .style
+                        .color
+                        .to_color()
+                        .unwrap_or(Color::BLACK);
+                    objects.extend(render_svg(
+                        &dom_node,
+                        Rect::new(
+                            replaced.layout_info.pos.x as f64,
+                            replaced.layout_info.pos.y as f64,
+                            replaced.layout_info.size.width as f64,
+                            replaced.layout_info.size.height as f64,
+                        ),
+                        color.to_unit_rgba(),
+                    ));
                 } else {
-                    largest_height
-                };
-                for child in anonymous.children.iter() {
-                    let (w, h) = child.borrow().to_render_objects(
-                        objects,
-                        viewport_width,
-                        viewport_height,
-                        largest_width,
-                        largest_height,
-                    );
-                    if w > largest_width {
-                        largest_width = w;
-                    }
-                    if h > largest_height {
-                        largest_height = h;
-                    }
+                    objects.push(RenderObject::Media {
+                        x: replaced.layout_info.pos.x as f64,
+                        y: replaced.layout_info.pos.y as f64,
+                        width: replaced.layout_info.size.width as f64,
+                        height: replaced.layout_info.size.height as f64,
+                        is_video: elm.tag_name == "video",
+                        poster: elm.get_poster().map(|url| url.to_string()),
+                        controls: elm.has_controls(),
+                    });
                 }
-                (largest_width, largest_height)
             }
         }
     }
@@ -737,13 +1049,13 @@ impl fmt::Display for BoxNode {
             Self::AnonymousBox(AnonymousBox { style, .. }) => {
                 fmt_str.push_str(&format!("Box: Anonymous, Computed( {} )", style));
             }
+            Self::ReplacedBox(ReplacedBox {
+                style_node: node, ..
+            }) => {
+                fmt_str.push_str(&format!("Box: Replaced, {}", node.borrow()));
+            }
         }
-        let layout_info = match self {
-            Self::BlockBox(BlockBox { layout_info, .. })
-            | Self::InlineBox(InlineBox { layout_info, .. })
-            | Self::Text(Text { layout_info, .. })
-            | Self::AnonymousBox(AnonymousBox { layout_info, .. }) => layout_info,
-        };
+        let layout_info = self.get_layout_info();
         fmt_str.push_str(&format!(
             ", (x, y, w, h): ({}, {}, {}, {})",
             layout_info.pos.x, layout_info.pos.y, layout_info.size.width, layout_info.size.height
@@ -0,0 +1,525 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use gtk4::pango;
+use gtk4::pango::prelude::{FontExt as _, FontFaceExt as _, FontFamilyExt as _};
+
+use crate::renderer::layout::bidi::{get_bidi_class, BidiClass};
+
+use crate::renderer::style::property::font_stretch::get_stretch_name;
+use crate::renderer::style::property::font_style::OBLIQUE_ANGLE;
+use crate::renderer::style::property::font_weight::get_weight_name;
+use crate::renderer::style::property::FontStyle;
+use crate::renderer::style::style_model::ComputedStyle;
+
+thread_local! {
+    /// The faces of the families available on the system by the lowercase family names, which are listed
+    /// once since it's slow to enumerate the fonts.
+    static FAMILIES: RefCell<Option<HashMap<String, Vec<FontFaceDescriptor>>>> =
+        const { RefCell::new(None) };
+    /// The coverages of the characters of the available families by the lowercase family names, which are
+    /// loaded when the families are first used for the fallback.
+    static COVERAGES: RefCell<HashMap<String, pango::Coverage>> = RefCell::new(HashMap::new());
+}
+
+/// The properties of a face by which it's matched, where `stretch` is a percentage of the normal width.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FontFaceDescriptor {
+    pub weight: f32,
+    pub style: FontStyle,
+    pub stretch: f32,
+}
+
+/// The extents of a text laid out in a font, where `ascent` is the distance from the top of the text to its
+/// baseline.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextExtents {
+    pub width: f32,
+    pub ascent: f32,
+    pub height: f32,
+}
+
+/// The positions of the tops of the lines of the text decorations above the baseline, which are negative
+/// below it, and their thicknesses, given by a font.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DecorationMetrics {
+    pub underline_position: f32,
+    pub underline_thickness: f32,
+    pub strikethrough_position: f32,
+    pub strikethrough_thickness: f32,
+}
+
+/// A part of a text whose clusters are rendered with the same family of the fallback list.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FontSegment {
+    pub range: Range<usize>,
+    /// The index of the family in the list, which is `None` if no family has the glyphs of the clusters and
+    /// they're left to the fallback fonts of the system.
+    pub family: Option<usize>,
+}
+
+/// The font used for a text, i.e. the families in the order of the fallback and the properties of the face
+/// of the first available one that matches the computed style best.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelectedFont {
+    pub families: Vec<String>,
+    pub face: FontFaceDescriptor,
+    pub size: f32,
+}
+
+impl SelectedFont {
+    /// Selects the face of the first family available on the system for the font properties of the style.
+    /// If no family is available, the faces are left to fontconfig with the properties as they are.
+    /// https://drafts.csswg.org/css-fonts/#font-matching-algorithm
+    pub fn select(draw_ctx: &pango::Context, style: &ComputedStyle) -> Self {
+        let families = style.font_family.to_name_list().unwrap();
+        let desired = FontFaceDescriptor {
+            weight: style.font_weight.to_number().unwrap(),
+            style: style.font_style.style,
+            stretch: style.font_stretch.stretch,
+        };
+        let face = FAMILIES.with_borrow_mut(|available| {
+            let available = available.get_or_insert_with(|| list_families(draw_ctx));
+            families
+                .iter()
+                .find_map(|family| available.get(&get_family_key(family)))
+                .and_then(|faces| match_font_face(&desired, faces))
+        });
+        Self {
+            families,
+            face: face.unwrap_or(desired),
+            size: style.font_size.to_px().unwrap(),
+        }
+    }
+
+    /// Returns the weight option of the font description of pango.
+    pub fn get_weight_name(&self) -> String {
+        get_weight_name(self.face.weight).unwrap().to_string()
+    }
+
+    /// Returns the style and the stretch options of the font description of pango, e.g. `Italic Condensed`,
+    /// which are empty for the normal ones.
+    pub fn get_style_name(&self) -> String {
+        [
+            self.face.style.to_name(),
+            get_stretch_name(self.face.stretch),
+        ]
+        .into_iter()
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+
+    /// Returns the extents of the text laid out in the font, which are the sum of the widths of its segments
+    /// in their families and the largest ascent and descent of them. The ones of an empty text are of the
+    /// font itself, which are the ascent and the descent of its first available face.
+    pub fn measure(&self, draw_ctx: &pango::Context, text: &str) -> TextExtents {
+        let segments = self.split_by_fallback(draw_ctx, text);
+        if segments.len() <= 1 {
+            let family = segments.first().and_then(|segment| segment.family);
+            return self.measure_in(draw_ctx, text, family);
+        }
+        let (width, ascent, descent) = segments.iter().fold(
+            (0.0, 0.0_f32, 0.0_f32),
+            |(width, ascent, descent), segment| {
+                let extents =
+                    self.measure_in(draw_ctx, &text[segment.range.clone()], segment.family);
+                (
+                    width + extents.width,
+                    ascent.max(extents.ascent),
+                    descent.max(extents.height - extents.ascent),
+                )
+            },
+        );
+        TextExtents {
+            width,
+            ascent,
+            height: ascent + descent,
+        }
+    }
+
+    /// Returns the extents of the text laid out in the family of the index, or in the whole list if `None`.
+    fn measure_in(
+        &self,
+        draw_ctx: &pango::Context,
+        text: &str,
+        family: Option<usize>,
+    ) -> TextExtents {
+        let layout = pango::Layout::new(draw_ctx);
+        layout.set_font_description(Some(&self.to_description_in(family)));
+        layout.set_text(text);
+        let (width, height) = layout.size();
+        TextExtents {
+            width: width as f32 / pango::SCALE as f32,
+            ascent: layout.baseline() as f32 / pango::SCALE as f32,
+            height: height as f32 / pango::SCALE as f32,
+        }
+    }
+
+    /// Splits the text into the segments rendered with the same families, where each cluster is rendered
+    /// with the first family in the list which has the glyph of its base character.
+    /// https://drafts.csswg.org/css-fonts/#cluster-matching
+    pub fn split_by_fallback(&self, draw_ctx: &pango::Context, text: &str) -> Vec<FontSegment> {
+        split_by_coverage(text, self.families.len(), |family, c| {
+            self.has_glyph(draw_ctx, family, c)
+        })
+    }
+
+    /// Returns the families of the font description of the segment in the family of the index, which are
+    /// the whole list if `None` so that the system falls back to its own fonts.
+    pub fn get_families_in(&self, family: Option<usize>) -> Vec<String> {
+        match family {
+            Some(family) => vec![self.families[family].clone()],
+            None => self.families.clone(),
+        }
+    }
+
+    /// Returns whether the family of the index is available on the system and has the glyph of the
+    /// character. A family which isn't available has no glyphs, since fontconfig substitutes another one
+    /// for it.
+    fn has_glyph(&self, draw_ctx: &pango::Context, family: usize, c: char) -> bool {
+        let key = get_family_key(&self.families[family]);
+        let is_available = FAMILIES.with_borrow_mut(|available| {
+            available
+                .get_or_insert_with(|| list_families(draw_ctx))
+                .contains_key(&key)
+        });
+        if !is_available {
+            return false;
+        }
+        COVERAGES.with_borrow_mut(|coverages| {
+            if !coverages.contains_key(&key) {
+                let desc = pango::FontDescription::from_string(&format!(
+                    "{} {}px",
+                    self.families[family], self.size
+                ));
+                let Some(font) = draw_ctx.load_font(&desc) else {
+                    return false;
+                };
+                coverages.insert(key.clone(), font.coverage(&pango::Language::default()));
+            }
+            coverages[&key].get(c as i32) != pango::CoverageLevel::None
+        })
+    }
+
+    /// Returns the metrics of the text decorations of the font, which are estimated from the font size if
+    /// the font doesn't give them.
+    pub fn get_decoration_metrics(&self, draw_ctx: &pango::Context) -> DecorationMetrics {
+        let metrics = draw_ctx.metrics(Some(&self.to_description()), None);
+        let to_px = |value: i32| value as f32 / pango::SCALE as f32;
+        let or_else = |value: f32, default: f32| if value != 0.0 { value } else { default };
+        let underline_thickness = or_else(to_px(metrics.underline_thickness()), self.size / 18.0);
+        let strikethrough_thickness = or_else(
+            to_px(metrics.strikethrough_thickness()),
+            underline_thickness,
+        );
+        DecorationMetrics {
+            underline_position: or_else(to_px(metrics.underline_position()), -self.size / 10.0),
+            underline_thickness,
+            strikethrough_position: or_else(
+                to_px(metrics.strikethrough_position()),
+                self.size * 0.25 + strikethrough_thickness / 2.0,
+            ),
+            strikethrough_thickness,
+        }
+    }
+
+    pub fn to_description(&self) -> pango::FontDescription {
+        self.to_description_in(None)
+    }
+
+    /// Returns the font description of the segment in the family of the index.
+    pub fn to_description_in(&self, family: Option<usize>) -> pango::FontDescription {
+        pango::FontDescription::from_string(&format!(
+            "{} {} {} {}px",
+            self.get_families_in(family).join(", "),
+            self.get_weight_name(),
+            self.get_style_name(),
+            self.size
+        ))
+    }
+}
+
+/// Splits the text into the clusters, i.e. the characters with the combining marks, the variation selectors
+/// and the characters joined by ZWJ following them, and merges the adjacent clusters rendered with the same
+/// family into the segments. A cluster is rendered with the first family which has the glyph of its base
+/// character, and a white space is kept in the family of the preceding cluster if the family has it.
+/// todo: Split the text at the extended grapheme clusters, and match the whole sequences of the clusters.
+fn split_by_coverage(
+    text: &str,
+    family_count: usize,
+    has_glyph: impl Fn(usize, char) -> bool,
+) -> Vec<FontSegment> {
+    let mut segments: Vec<FontSegment> = Vec::new();
+    let mut previous = None;
+    for (i, c) in text.char_indices() {
+        let end = i + c.len_utf8();
+        let extends = previous == Some('\u{200D}') || is_cluster_extender(c);
+        previous = Some(c);
+        if let Some(last) = segments.last_mut() {
+            if extends
+                || (c.is_whitespace() && last.family.is_none_or(|family| has_glyph(family, c)))
+            {
+                last.range.end = end;
+                continue;
+            }
+        }
+        let family = (0..family_count).find(|&family| has_glyph(family, c));
+        match segments.last_mut() {
+            Some(last) if last.family == family => last.range.end = end,
+            _ => segments.push(FontSegment {
+                range: i..end,
+                family,
+            }),
+        }
+    }
+    segments
+}
+
+/// Returns whether the character is a part of the cluster of the preceding one.
+fn is_cluster_extender(c: char) -> bool {
+    get_bidi_class(c) == BidiClass::Nsm
+        || matches!(
+            c,
+            '\u{200D}'
+                | '\u{FE00}'..='\u{FE0F}'
+                | '\u{1F3FB}'..='\u{1F3FF}'
+                | '\u{E0020}'..='\u{E007F}'
+                | '\u{E0100}'..='\u{E01EF}'
+        )
+}
+
+/// Returns the face which matches the desired properties best, narrowing down the faces by `font-stretch`,
+/// `font-style` and `font-weight` in this order. An italic style falls back to oblique faces and then to
+/// normal ones, an oblique style to italic faces and a normal style to oblique faces first.
+/// todo: Match the angles of `oblique` and the ranges of the variable fonts.
+/// https://drafts.csswg.org/css-fonts/#font-style-matching
+pub fn match_font_face(
+    desired: &FontFaceDescriptor,
+    faces: &[FontFaceDescriptor],
+) -> Option<FontFaceDescriptor> {
+    let stretch = find_nearest(
+        faces.iter().map(|f| f.stretch),
+        desired.stretch,
+        desired.stretch <= 100.0,
+        (desired.stretch, desired.stretch),
+    )?;
+    let faces = faces
+        .iter()
+        .filter(|f| f.stretch == stretch)
+        .collect::<Vec<_>>();
+
+    let get_style_order = |style: FontStyle| match (desired.style, style) {
+        (FontStyle::Italic, FontStyle::Italic)
+        | (FontStyle::Oblique(_), FontStyle::Oblique(_))
+        | (FontStyle::Normal, FontStyle::Normal) => 0,
+        (FontStyle::Normal, FontStyle::Italic) | (_, FontStyle::Normal) => 2,
+        _ => 1,
+    };
+    let style_order = faces.iter().map(|f| get_style_order(f.style)).min()?;
+    let faces = faces
+        .into_iter()
+        .filter(|f| get_style_order(f.style) == style_order)
+        .collect::<Vec<_>>();
+
+    // The weights between 400 and 500 look for the heavier weights up to 500 first.
+    let weight = find_nearest(
+        faces.iter().map(|f| f.weight),
+        desired.weight,
+        desired.weight <= 500.0,
+        if (400.0..=500.0).contains(&desired.weight) {
+            (desired.weight, 500.0)
+        } else {
+            (desired.weight, desired.weight)
+        },
+    )?;
+    faces.into_iter().find(|f| f.weight == weight).copied()
+}
+
+/// Returns the value nearest to `desired` in the preferred direction, i.e. the values in `preferred_range`
+/// in ascending order, then the values below `desired` in descending order and the values above it in
+/// ascending order, where the values below come first if `prefers_lower`.
+fn find_nearest(
+    values: impl Iterator<Item = f32>,
+    desired: f32,
+    prefers_lower: bool,
+    preferred_range: (f32, f32),
+) -> Option<f32> {
+    let order = |v: f32| {
+        if (preferred_range.0..=preferred_range.1).contains(&v) {
+            (0, v - desired)
+        } else if (v < desired) == prefers_lower {
+            (1, (v - desired).abs())
+        } else {
+            (2, (v - desired).abs())
+        }
+    };
+    values.min_by(|a, b| {
+        let (a, b) = (order(*a), order(*b));
+        a.0.cmp(&b.0)
+            .then(a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+    })
+}
+
+/// Returns the key of the family in the list of the available families. The generic families are the
+/// aliases of fontconfig.
+fn get_family_key(family: &str) -> String {
+    match family.to_ascii_lowercase().as_str() {
+        "sans-serif" => "sans".to_string(),
+        family => family.to_string(),
+    }
+}
+
+fn list_families(draw_ctx: &pango::Context) -> HashMap<String, Vec<FontFaceDescriptor>> {
+    draw_ctx
+        .list_families()
+        .iter()
+        .map(|family| {
+            let faces = family
+                .list_faces()
+                .iter()
+                .map(|face| {
+                    let desc = face.describe();
+                    FontFaceDescriptor {
+                        weight: get_weight(desc.weight()),
+                        style: match desc.style() {
+                            pango::Style::Italic => FontStyle::Italic,
+                            pango::Style::Oblique => FontStyle::Oblique(OBLIQUE_ANGLE),
+                            _ => FontStyle::Normal,
+                        },
+                        stretch: get_stretch(desc.stretch()),
+                    }
+                })
+                .collect();
+            (family.name().to_ascii_lowercase(), faces)
+        })
+        .collect()
+}
+
+fn get_weight(weight: pango::Weight) -> f32 {
+    match weight {
+        pango::Weight::Thin => 100.0,
+        pango::Weight::Ultralight => 200.0,
+        pango::Weight::Light => 300.0,
+        pango::Weight::Semilight => 350.0,
+        pango::Weight::Book => 380.0,
+        pango::Weight::Normal => 400.0,
+        pango::Weight::Medium => 500.0,
+        pango::Weight::Semibold => 600.0,
+        pango::Weight::Bold => 700.0,
+        pango::Weight::Ultrabold => 800.0,
+        pango::Weight::Heavy => 900.0,
+        pango::Weight::Ultraheavy => 1000.0,
+        pango::Weight::__Unknown(weight) => weight as f32,
+        _ => 400.0,
+    }
+}
+
+fn get_stretch(stretch: pango::Stretch) -> f32 {
+    match stretch {
+        pango::Stretch::UltraCondensed => 50.0,
+        pango::Stretch::ExtraCondensed => 62.5,
+        pango::Stretch::Condensed => 75.0,
+        pango::Stretch::SemiCondensed => 87.5,
+        pango::Stretch::SemiExpanded => 112.5,
+        pango::Stretch::Expanded => 125.0,
+        pango::Stretch::ExtraExpanded => 150.0,
+        pango::Stretch::UltraExpanded => 200.0,
+        _ => 100.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face(weight: f32, style: FontStyle, stretch: f32) -> FontFaceDescriptor {
+        FontFaceDescriptor {
+            weight,
+            style,
+            stretch,
+        }
+    }
+
+    #[test]
+    fn match_faces() {
+        let faces = [300.0, 400.0, 600.0, 900.0].map(|w| face(w, FontStyle::Normal, 100.0));
+        let find = |weight: f32| {
+            match_font_face(&face(weight, FontStyle::Normal, 100.0), &faces)
+                .unwrap()
+                .weight
+        };
+        assert_eq!(find(400.0), 400.0);
+        // 450 falls back to lighter weights before heavier ones, since there is no face up to 500.
+        assert_eq!(find(450.0), 400.0);
+        assert_eq!(find(500.0), 400.0);
+        assert_eq!(find(350.0), 300.0);
+        assert_eq!(find(200.0), 300.0);
+        assert_eq!(find(700.0), 900.0);
+        assert_eq!(find(950.0), 900.0);
+
+        // The stretch is narrowed down first, then the style and the weight.
+        let faces = [
+            face(400.0, FontStyle::Normal, 100.0),
+            face(700.0, FontStyle::Normal, 100.0),
+            face(400.0, FontStyle::Italic, 100.0),
+            face(700.0, FontStyle::Oblique(14.0), 100.0),
+            face(700.0, FontStyle::Italic, 75.0),
+        ];
+        let find = |style: FontStyle, stretch: f32| {
+            match_font_face(&face(700.0, style, stretch), &faces).unwrap()
+        };
+        assert_eq!(
+            find(FontStyle::Italic, 100.0),
+            face(400.0, FontStyle::Italic, 100.0)
+        );
+        assert_eq!(
+            find(FontStyle::Oblique(14.0), 100.0),
+            face(700.0, FontStyle::Oblique(14.0), 100.0)
+        );
+        assert_eq!(
+            find(FontStyle::Normal, 100.0),
+            face(700.0, FontStyle::Normal, 100.0)
+        );
+        assert_eq!(
+            find(FontStyle::Normal, 87.5),
+            face(700.0, FontStyle::Italic, 75.0)
+        );
+        assert_eq!(
+            find(FontStyle::Normal, 112.5),
+            face(700.0, FontStyle::Normal, 100.0)
+        );
+        assert_eq!(match_font_face(&faces[0], &[]), None);
+    }
+
+    #[test]
+    fn split_clusters_by_coverage() {
+        // The first family has Latin and the second one has CJK, and neither has the emoji.
+        fn split(text: &str) -> Vec<(&str, Option<usize>)> {
+            let has_glyph = |family: usize, c: char| match family {
+                0 => c.is_ascii() || c == '\u{0301}',
+                _ => c.is_whitespace() || ('\u{3000}'..='\u{9FFF}').contains(&c),
+            };
+            split_by_coverage(text, 2, has_glyph)
+                .into_iter()
+                .map(|segment| (&text[segment.range], segment.family))
+                .collect()
+        }
+        assert_eq!(
+            split("ab 漢字 c"),
+            [("ab ", Some(0)), ("漢字 ", Some(1)), ("c", Some(0))]
+        );
+        // The combining marks, the variation selectors and the joined characters stay in their clusters.
+        assert_eq!(
+            split("e\u{0301}\u{2764}\u{FE0F}\u{1F468}\u{200D}\u{1F469}x"),
+            [
+                ("e\u{0301}", Some(0)),
+                ("\u{2764}\u{FE0F}\u{1F468}\u{200D}\u{1F469}", None),
+                ("x", Some(0)),
+            ]
+        );
+        assert!(split("").is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+/// A rectangle on the canvas, in CSS pixels.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the rectangle grown by the margins (top, right, bottom, left).
+    pub fn expand(&self, (top, right, bottom, left): (f64, f64, f64, f64)) -> Self {
+        Self::new(
+            self.x - left,
+            self.y - top,
+            self.width + left + right,
+            self.height + top + bottom,
+        )
+    }
+
+    /// Returns the intersection of the rectangles, or `None` if they don't intersect. Rectangles which
+    /// are only edge-adjacent intersect with an empty rectangle.
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        (left <= right && top <= bottom).then(|| Self::new(left, top, right - left, bottom - top))
+    }
+
+    /// Returns the smallest rectangle which contains both rectangles.
+    pub fn union(&self, other: &Self) -> Self {
+        let left = self.x.min(other.x);
+        let top = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Self::new(left, top, right - left, bottom - top)
+    }
+
+    /// Returns whether the point is in the rectangle, including its top and left edges.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+
+    pub fn area(&self) -> f64 {
+        self.width * self.height
+    }
+}
@@ -1,7 +1,11 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use crate::renderer::html::dom::NodeType;
+use crate::renderer::layout::block::BlockBox;
 use crate::renderer::layout::box_model::{BoxNode, BoxPosition, BoxSize, LayoutBox, LayoutInfo};
+use crate::renderer::layout::positioned::{is_out_of_flow, set_static_position};
+use crate::renderer::layout::replaced::ReplacedBox;
 use crate::renderer::layout::text::Text;
 use crate::renderer::style::property::display::{DisplayInside, DisplayOutside};
 use crate::renderer::style::property::CssValue;
@@ -15,6 +19,7 @@ pub struct InlineBox {
 }
 
 impl LayoutBox for InlineBox {
+    #[tracing::instrument(skip_all)]
     fn layout(
         &mut self,
         containing_block_info: &LayoutInfo,
@@ -30,7 +35,7 @@ impl LayoutBox for InlineBox {
             } else {
                 (None, None)
             };
-        self.calc_used_values();
+        self.calc_used_values(containing_block_info);
         self.calc_pos(containing_block_info, prev_sibling_pos, prev_sibling_size);
         self.layout_children(containing_block_info);
     }
@@ -40,22 +45,34 @@ impl LayoutBox for InlineBox {
             return;
         }
 
-        let is_every_child_inline = self
-            .children
-            .iter()
-            .all(|child| matches!(*child.borrow(), BoxNode::InlineBox(_) | BoxNode::Text(_)));
+        let is_every_child_inline = self.children.iter().all(|child| {
+            matches!(
+                &*child.borrow(),
+                BoxNode::InlineBox(_) | BoxNode::Text(_) | BoxNode::ReplacedBox(_)
+            ) || matches!(&*child.borrow(), BoxNode::BlockBox(b) if b.is_atomic_inline())
+                || is_out_of_flow(&child.borrow())
+        });
         if !is_every_child_inline {
             unimplemented!("Only inline-level boxes and text nodes are currently supported as children of a inline-level box.");
         }
+        if self.is_element("ruby") {
+            self.layout_ruby(containing_block_info);
+            return;
+        }
 
         let mut inline_width = 0.0;
         let mut inline_max_height = 0.0;
         let mut prev_sib_info = None;
 
         for child in self.children.iter_mut() {
+            if is_out_of_flow(&child.borrow()) {
+                set_static_position(child, &self.layout_info, prev_sib_info.as_ref(), true);
+                continue;
+            }
             // The containing block of an inline-level box is the nearest block-level ancestor box.
+            // The boxes in block containers are placed in the line boxes, so they're only laid out here
+            // in a ruby.
             // https://developer.mozilla.org/en-US/docs/Web/CSS/Containing_block
-            // todo: Implement the line box system for simplification.
             child.borrow_mut().layout(
                 containing_block_info,
                 Some(self.layout_info.clone()),
@@ -69,6 +86,12 @@ impl LayoutBox for InlineBox {
                 })
                 | BoxNode::Text(Text {
                     ref layout_info, ..
+                })
+                | BoxNode::BlockBox(BlockBox {
+                    ref layout_info, ..
+                })
+                | BoxNode::ReplacedBox(ReplacedBox {
+                    ref layout_info, ..
                 }) => layout_info,
                 _ => unreachable!(),
             };
@@ -91,26 +114,148 @@ impl LayoutBox for InlineBox {
 }
 
 impl InlineBox {
-    fn calc_used_values(&mut self) {
+    pub fn is_element(&self, tag_name: &str) -> bool {
+        let style_node = self.style_node.borrow();
+        style_node.pseudo_element.is_none()
+            && matches!(
+                &style_node.dom_node.borrow().node_type,
+                NodeType::Element(elm) if elm.tag_name == tag_name
+            )
+    }
+
+    /// Lays out the children of a `ruby` element, where each `rt` element annotates the preceding children
+    /// as its base. The annotations are centered on their bases, and placed over or under them depending on
+    /// the `ruby-position` property.
+    /// todo: Support the `ruby` display types, the `rb` and `rtc` elements, and line breaks in ruby.
+    /// https://drafts.csswg.org/css-ruby/#ruby-layout
+    fn layout_ruby(&mut self, containing_block_info: &LayoutInfo) {
+        let mut pairs = vec![(Vec::new(), None)];
+        for child in &self.children {
+            let is_annotation = matches!(
+                &*child.borrow(),
+                BoxNode::InlineBox(b) if b.is_element("rt")
+            );
+            if is_annotation {
+                pairs.last_mut().unwrap().1 = Some(Rc::clone(child));
+                pairs.push((Vec::new(), None));
+            } else {
+                pairs.last_mut().unwrap().0.push(Rc::clone(child));
+            }
+        }
+
+        // The bases and the annotations are laid out once to measure them, and then placed.
+        let mut base_height: f32 = 0.0;
+        let mut over_height: f32 = 0.0;
+        let mut under_height: f32 = 0.0;
+        let mut measured = Vec::new();
+        for (bases, annotation) in &pairs {
+            let base_size = self.layout_run(bases, containing_block_info, BoxPosition::default());
+            base_height = base_height.max(base_size.height);
+            let annotation = annotation.as_ref().map(|annotation| {
+                let size = self.layout_run(
+                    std::slice::from_ref(annotation),
+                    containing_block_info,
+                    BoxPosition::default(),
+                );
+                let BoxNode::InlineBox(b) = &*annotation.borrow() else {
+                    unreachable!()
+                };
+                let is_under = b.style_node.borrow().style.ruby_position.is_under();
+                if is_under {
+                    under_height = under_height.max(size.height);
+                } else {
+                    over_height = over_height.max(size.height);
+                }
+                (size, is_under)
+            });
+            measured.push((base_size, annotation));
+        }
+
+        let BoxPosition { x: start_x, y } = self.layout_info.pos;
+        let mut x = start_x;
+        for ((bases, annotation), (base_size, annotation_size)) in pairs.iter().zip(measured) {
+            let width = annotation_size
+                .map_or(base_size.width, |(size, _)| size.width.max(base_size.width));
+            let pos = BoxPosition {
+                x: x + (width - base_size.width) / 2.0,
+                y: y + over_height,
+            };
+            self.layout_run(bases, containing_block_info, pos);
+            if let (Some(annotation), Some((size, is_under))) = (annotation, annotation_size) {
+                let pos = BoxPosition {
+                    x: x + (width - size.width) / 2.0,
+                    y: if is_under {
+                        y + over_height + base_height
+                    } else {
+                        y
+                    },
+                };
+                self.layout_run(std::slice::from_ref(annotation), containing_block_info, pos);
+            }
+            x += width;
+        }
+        self.layout_info.size.width = x - start_x;
+        self.layout_info.size.height = over_height + base_height + under_height;
+    }
+
+    /// Lays out the boxes next to each other from the position, and returns the size of them.
+    fn layout_run(
+        &self,
+        boxes: &[Rc<RefCell<BoxNode>>],
+        containing_block_info: &LayoutInfo,
+        pos: BoxPosition,
+    ) -> BoxSize {
+        // The boxes are placed relative to the containing block and the parent without their paddings.
+        let mut block_info = containing_block_info.clone();
+        block_info.pos = pos;
+        block_info.used_values.padding.top = 0.0;
+        let mut parent_info = self.layout_info.clone();
+        parent_info.pos = pos;
+        parent_info.used_values.padding.left = 0.0;
+
+        let mut size = BoxSize::default();
+        let mut prev_sib_info = None;
+        for child in boxes {
+            child
+                .borrow_mut()
+                .layout(&block_info, Some(parent_info.clone()), prev_sib_info);
+            let child_ref = child.borrow();
+            let (BoxNode::InlineBox(InlineBox { layout_info, .. })
+            | BoxNode::Text(Text { layout_info, .. })
+            | BoxNode::BlockBox(BlockBox { layout_info, .. })
+            | BoxNode::ReplacedBox(ReplacedBox { layout_info, .. })) = &*child_ref
+            else {
+                unreachable!()
+            };
+            size.width += layout_info.get_expanded_size().width;
+            size.height = size.height.max(layout_info.get_expanded_size().height);
+            prev_sib_info = Some(layout_info.clone());
+        }
+        size
+    }
+
+    /// Resolves the margins of the box, whose `auto` values are zero.
+    /// https://www.w3.org/TR/CSS22/visudet.html#inline-width
+    pub fn calc_used_values(&mut self, containing_block_info: &LayoutInfo) {
         let margin = self.style_node.borrow().style.margin.clone();
+        let cb_width = containing_block_info.get_content_width();
         let display = self.style_node.borrow().style.display.clone();
 
         if (display.outside, display.inside) != (DisplayOutside::Inline, DisplayInside::Flow) {
             unimplemented!("Only inline-level boxes in normal flow are currently supported.");
         }
 
-        self.layout_info.used_values.margin.top = match margin.top {
+        let resolve = |v: &CssValue| match v {
             CssValue::Ident(v) if v == "auto" => 0.0,
-            CssValue::Length(..) => margin.top.to_px().unwrap(),
-            CssValue::Percentage(_) => unimplemented!(),
-            _ => unreachable!(),
-        };
-        self.layout_info.used_values.margin.bottom = match margin.bottom {
-            CssValue::Ident(v) if v == "auto" => 0.0,
-            CssValue::Length(..) => margin.bottom.to_px().unwrap(),
-            CssValue::Percentage(_) => unimplemented!(),
+            CssValue::Length(..) | CssValue::Percentage(_) | CssValue::Calc(_) => {
+                v.resolve_px(cb_width).unwrap()
+            }
             _ => unreachable!(),
         };
+        self.layout_info.used_values.margin.top = resolve(&margin.top);
+        self.layout_info.used_values.margin.right = resolve(&margin.right);
+        self.layout_info.used_values.margin.bottom = resolve(&margin.bottom);
+        self.layout_info.used_values.margin.left = resolve(&margin.left);
         self.layout_info.used_values.width = None;
     }
 
@@ -0,0 +1,268 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use anyhow::{bail, ensure, Result};
+
+use crate::renderer::html::dom::DomNode;
+use crate::renderer::layout::geometry::Rect;
+
+/// A component of the root margin of an intersection observer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MarginLength {
+    Px(f64),
+    /// A percentage of the width or height of the root.
+    Percentage(f64),
+}
+
+impl MarginLength {
+    fn to_px(self, root_length: f64) -> f64 {
+        match self {
+            Self::Px(px) => px,
+            Self::Percentage(percentage) => root_length * percentage / 100.0,
+        }
+    }
+}
+
+/// The change of the intersection of a target with the root, which is delivered to the observer.
+/// todo: Add the rectangles and the intersection ratio, which are only read by scripts.
+/// https://w3c.github.io/IntersectionObserver/#intersection-observer-entry
+#[derive(Debug, Clone)]
+pub struct IntersectionObserverEntry {
+    pub target: Rc<RefCell<DomNode>>,
+    pub is_intersecting: bool,
+}
+
+#[derive(Debug)]
+struct IntersectionObservation {
+    target: Rc<RefCell<DomNode>>,
+    /// The index of the threshold crossed at the last update, which is `None` before the first update.
+    previous_threshold_index: Option<usize>,
+    previous_is_intersecting: bool,
+}
+
+/// Observes the intersections of the target elements with the viewport, which is the implicit root.
+/// The entries are queued when a target crosses one of the thresholds.
+/// todo: Support explicit roots and the scroll margin, and deliver the entries to scripts.
+/// https://w3c.github.io/IntersectionObserver/#intersection-observer-interface
+#[derive(Debug)]
+pub struct IntersectionObserver {
+    /// (top, right, bottom, left)
+    root_margin: [MarginLength; 4],
+    thresholds: Vec<f64>,
+    observations: Vec<IntersectionObservation>,
+}
+
+impl IntersectionObserver {
+    /// Creates an observer with the root margin, which is like the CSS `margin` property but only accepts
+    /// pixels and percentages, and the thresholds, each of which must be in the range [0, 1].
+    /// https://w3c.github.io/IntersectionObserver/#initialize-a-new-intersectionobserver
+    pub fn new(root_margin: &str, thresholds: &[f64]) -> Result<Self> {
+        let root_margin = Self::parse_root_margin(root_margin)?;
+        ensure!(
+            thresholds.iter().all(|t| (0.0..=1.0).contains(t)),
+            "RangeError: The thresholds must be in the range [0, 1]."
+        );
+        let mut thresholds = thresholds.to_vec();
+        thresholds.sort_by(f64::total_cmp);
+        if thresholds.is_empty() {
+            thresholds.push(0.0);
+        }
+        Ok(Self {
+            root_margin,
+            thresholds,
+            observations: Vec::new(),
+        })
+    }
+
+    /// https://w3c.github.io/IntersectionObserver/#parse-a-margin
+    fn parse_root_margin(margin: &str) -> Result<[MarginLength; 4]> {
+        let mut lengths = Vec::new();
+        for token in margin.split_ascii_whitespace() {
+            let length = if let Some(value) = token.strip_suffix('%') {
+                value.parse().ok().map(MarginLength::Percentage)
+            } else if let Some(value) = token.strip_suffix("px") {
+                value.parse().ok().map(MarginLength::Px)
+            } else if token == "0" {
+                Some(MarginLength::Px(0.0))
+            } else {
+                None
+            };
+            let Some(length) = length else {
+                bail!("SyntaxError: The root margin must be in pixels or percentages.");
+            };
+            lengths.push(length);
+        }
+        // The omitted values are copied from the opposite sides, as with the `margin` property.
+        Ok(match lengths[..] {
+            [] => [MarginLength::Px(0.0); 4],
+            [all] => [all; 4],
+            [vertical, horizontal] => [vertical, horizontal, vertical, horizontal],
+            [top, horizontal, bottom] => [top, horizontal, bottom, horizontal],
+            [top, right, bottom, left] => [top, right, bottom, left],
+            _ => bail!("SyntaxError: The root margin must have at most four values."),
+        })
+    }
+
+    /// Starts observing the target. Its first update always queues an entry.
+    /// https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-observe
+    pub fn observe(&mut self, target: &Rc<RefCell<DomNode>>) {
+        if self
+            .observations
+            .iter()
+            .any(|o| Rc::ptr_eq(&o.target, target))
+        {
+            return;
+        }
+        self.observations.push(IntersectionObservation {
+            target: Rc::clone(target),
+            previous_threshold_index: None,
+            previous_is_intersecting: false,
+        });
+    }
+
+    /// https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-unobserve
+    pub fn unobserve(&mut self, target: &Rc<RefCell<DomNode>>) {
+        self.observations.retain(|o| !Rc::ptr_eq(&o.target, target));
+    }
+
+    /// https://w3c.github.io/IntersectionObserver/#dom-intersectionobserver-disconnect
+    pub fn disconnect(&mut self) {
+        self.observations.clear();
+    }
+
+    #[cfg(test)]
+    pub fn get_thresholds(&self) -> &[f64] {
+        &self.thresholds
+    }
+
+    /// Computes the intersections of the targets with `viewport`, and returns the entries of the targets
+    /// which have crossed a threshold since the last update. This is run at each rendering opportunity,
+    /// and `get_target_rect` returns the border box of a target, or `None` if it is not rendered.
+    /// https://w3c.github.io/IntersectionObserver/#update-intersection-observations-algo
+    pub fn update(
+        &mut self,
+        viewport: Rect,
+        get_target_rect: impl Fn(&Rc<RefCell<DomNode>>) -> Option<Rect>,
+    ) -> Vec<IntersectionObserverEntry> {
+        let [top, right, bottom, left] = self.root_margin;
+        let root_bounds = viewport.expand((
+            top.to_px(viewport.height),
+            right.to_px(viewport.width),
+            bottom.to_px(viewport.height),
+            left.to_px(viewport.width),
+        ));
+
+        let mut entries = Vec::new();
+        for observation in &mut self.observations {
+            let target_rect = get_target_rect(&observation.target);
+            let intersection_rect = target_rect.and_then(|rect| rect.intersect(&root_bounds));
+            let is_intersecting = intersection_rect.is_some();
+            let target_area = target_rect.unwrap_or_default().area();
+            let intersection_ratio = if target_area > 0.0 {
+                intersection_rect.unwrap_or_default().area() / target_area
+            } else if is_intersecting {
+                1.0
+            } else {
+                0.0
+            };
+            // Targets which are not intersecting are below every threshold.
+            let threshold_index = if is_intersecting {
+                self.thresholds
+                    .iter()
+                    .position(|t| *t > intersection_ratio)
+                    .unwrap_or(self.thresholds.len())
+            } else {
+                0
+            };
+
+            if observation.previous_threshold_index != Some(threshold_index)
+                || observation.previous_is_intersecting != is_intersecting
+            {
+                entries.push(IntersectionObserverEntry {
+                    target: Rc::clone(&observation.target),
+                    is_intersecting,
+                });
+            }
+            observation.previous_threshold_index = Some(threshold_index);
+            observation.previous_is_intersecting = is_intersecting;
+        }
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::dom::{Element, NodeType};
+
+    fn element(tag_name: &str) -> Rc<RefCell<DomNode>> {
+        Rc::new(RefCell::new(DomNode::new(NodeType::Element(Element {
+            tag_name: tag_name.to_string(),
+            attributes: Vec::new(),
+        }))))
+    }
+
+    #[test]
+    fn observe_intersections() {
+        let (div, img) = (element("div"), element("img"));
+        let mut observer = IntersectionObserver::new("0px 0px 10%", &[1.0, 0.5]).unwrap();
+        assert_eq!(observer.get_thresholds(), [0.5, 1.0]);
+        observer.observe(&div);
+        observer.observe(&img);
+        observer.observe(&div);
+
+        // The div is partially visible and the image is below the viewport, whose bottom margin is 60px.
+        let update = |observer: &mut IntersectionObserver, scroll_y: f64| {
+            let div_rect = Rect::new(0.0, 500.0, 100.0, 200.0);
+            let img_rect = Rect::new(0.0, 700.0, 100.0, 100.0);
+            observer.update(Rect::new(0.0, scroll_y, 800.0, 600.0), |target| {
+                Some(if Rc::ptr_eq(target, &div) {
+                    div_rect
+                } else {
+                    img_rect
+                })
+            })
+        };
+
+        let entries = update(&mut observer, 0.0);
+        assert_eq!(entries.len(), 2);
+        assert!(Rc::ptr_eq(&entries[0].target, &div));
+        assert!(entries[0].is_intersecting);
+        assert!(!entries[1].is_intersecting);
+
+        // Nothing is queued until a threshold is crossed. At 40px, the div is fully visible and the image is
+        // edge-adjacent to the bottom margin, which counts as intersecting.
+        assert!(update(&mut observer, 10.0).is_empty());
+        assert!(update(&mut observer, 39.0).is_empty());
+        let entries = update(&mut observer, 40.0);
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|entry| entry.is_intersecting));
+        // The image is fully visible, and crosses the threshold 1.0.
+        let entries = update(&mut observer, 200.0);
+        assert_eq!(entries.len(), 1);
+        assert!(Rc::ptr_eq(&entries[0].target, &img));
+
+        // The div is half visible, and crosses back below 1.0 but not below 0.5.
+        let entries = update(&mut observer, 600.0);
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].is_intersecting);
+        assert!(update(&mut observer, 599.0).is_empty());
+
+        observer.unobserve(&div);
+        let entries = update(&mut observer, 2000.0);
+        assert_eq!(entries.len(), 1);
+        assert!(!entries[0].is_intersecting);
+    }
+
+    #[test]
+    fn parse_root_margins() {
+        assert!(IntersectionObserver::new("10px 5%", &[]).is_ok());
+        assert!(IntersectionObserver::new("10em", &[]).is_err());
+        assert!(IntersectionObserver::new("1px 2px 3px 4px 5px", &[]).is_err());
+        assert!(IntersectionObserver::new("", &[1.5]).is_err());
+        assert_eq!(
+            IntersectionObserver::new("", &[]).unwrap().get_thresholds(),
+            [0.0]
+        );
+    }
+}
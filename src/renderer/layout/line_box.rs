@@ -0,0 +1,1438 @@
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+
+use gtk4::pango;
+
+use crate::renderer::layout::bidi::{
+    find_first_strong_level, get_bidi_class, get_box_controls, get_visual_order, resolve_levels,
+    BidiClass,
+};
+use crate::renderer::layout::box_model::{BoxNode, BoxPosition, BoxSize, LayoutInfo};
+use crate::renderer::layout::font::{SelectedFont, TextExtents};
+use crate::renderer::layout::geometry::Rect;
+use crate::renderer::layout::line_break::get_break_opportunities;
+use crate::renderer::layout::positioned::{get_border_box, is_out_of_flow};
+use crate::renderer::layout::text::{collapse_white_space, TextLine};
+use crate::renderer::style::property::{CssValue, TextAlignProp};
+use crate::renderer::style::style_model::ComputedStyle;
+
+/// Lays out the inline-level contents of a block container in the line boxes stacked from the top of its
+/// content box, and returns the sum of the heights of the line boxes. The contents are broken into the lines
+/// at the break opportunities of the texts and around the atomic inline-level boxes, ordered visually by the
+/// bidirectional algorithm, and aligned vertically in each line by `vertical-align` with the heights given
+/// by `line-height`. The lines are aligned horizontally by `text-align`, and the first line is indented by
+/// `text-indent`. The contents overflowing the lines are replaced with an ellipsis by `text-overflow` if the
+/// container clips them.
+/// https://www.w3.org/TR/CSS22/visuren.html#inline-formatting
+/// https://www.w3.org/TR/CSS22/visudet.html#line-height
+pub fn layout_line_boxes(
+    children: &[Rc<RefCell<BoxNode>>],
+    container: &LayoutInfo,
+    style: &ComputedStyle,
+    draw_ctx: &pango::Context,
+) -> f32 {
+    let (metrics, line_height) = get_style_metrics(draw_ctx, style);
+    let mut context = InlineFormattingContext {
+        draw_ctx,
+        container,
+        boxes: vec![InlineBoxEntry {
+            node: None,
+            parent: 0,
+            metrics,
+            line_height,
+            vertical_align: CssValue::Ident("baseline".to_string()),
+            start_edge: 0.0,
+            end_edge: 0.0,
+            wraps: style.white_space.wraps(),
+            bidi_controls: (Vec::new(), Vec::new()),
+            bounds: None,
+        }],
+        texts: Vec::new(),
+        atomics: Vec::new(),
+        items: Vec::new(),
+        follows_space: true,
+        last_char: None,
+        paragraph_level: style.direction.is_rtl() as u8,
+        text_align: style.text_align.clone(),
+        text_indent: style
+            .text_indent
+            .value
+            .resolve_px(container.get_content_width())
+            .unwrap_or(0.0),
+        ellipsis_width: (style.text_overflow.is_ellipsis() && style.overflow.clips_inline()).then(
+            || {
+                SelectedFont::select(draw_ctx, style)
+                    .measure(draw_ctx, ELLIPSIS)
+                    .width
+            },
+        ),
+    };
+    context.collect(children, 0);
+    context.resolve_bidi_levels(style.unicode_bidi.as_str() == "plaintext");
+    context.layout()
+}
+
+/// The metrics of an inline-level box or a text in a line box, relative to its baseline.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Metrics {
+    /// The distances from the baseline to the top and the bottom of the content area, i.e. of the glyphs.
+    ascent: f32,
+    descent: f32,
+    font_size: f32,
+    /// The distances from the baseline to the top and the bottom of the box in the line box, which include
+    /// the half-leading.
+    above: f32,
+    below: f32,
+}
+
+impl Metrics {
+    /// Returns the metrics of the content area whose height is adjusted to the line height by adding the
+    /// half of the difference above and below it. The line height of `normal` is the one of the font.
+    fn new(extents: TextExtents, font_size: f32, line_height: Option<f32>) -> Self {
+        let ascent = extents.ascent;
+        let descent = extents.height - extents.ascent;
+        let half_leading =
+            line_height.map_or(0.0, |line_height| (line_height - (ascent + descent)) / 2.0);
+        Self {
+            ascent,
+            descent,
+            font_size,
+            above: ascent + half_leading,
+            below: descent + half_leading,
+        }
+    }
+}
+
+/// The vertical position of a box in a line box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Alignment {
+    /// The distance from the baseline of the parent down to the baseline of the box.
+    Shift(f32),
+    /// The box is aligned to the top of the line box with the boxes aligned to it.
+    Top,
+    /// The box is aligned to the bottom of the line box with the boxes aligned to it.
+    Bottom,
+}
+
+/// A box or a text in a line box, whose parent is an earlier member. The first member is the root inline
+/// box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct LineMember {
+    parent: usize,
+    metrics: Metrics,
+    alignment: Alignment,
+}
+
+/// The height of a line box and the baselines of its members from the top of the line box.
+#[derive(Clone, Debug, PartialEq)]
+struct LineAlignment {
+    height: f32,
+    baselines: Vec<f32>,
+}
+
+/// Aligns the members of the line box vertically. The members aligned to `top` and `bottom` are placed with
+/// the members aligned to them after the others, which are aligned to the root inline box. The line box is
+/// as tall as the tallest of them.
+/// https://www.w3.org/TR/CSS22/visudet.html#line-height
+fn align_line(members: &[LineMember]) -> LineAlignment {
+    // The members aligned to `top` or `bottom` are the roots of their own groups of members.
+    let mut groups = vec![0; members.len()];
+    let mut shifts = vec![0.0; members.len()];
+    let mut extents: Vec<Option<(f32, f32)>> = vec![None; members.len()];
+    for (i, member) in members.iter().enumerate() {
+        if i > 0 {
+            match member.alignment {
+                Alignment::Shift(shift) => {
+                    groups[i] = groups[member.parent];
+                    shifts[i] = shifts[member.parent] + shift;
+                }
+                Alignment::Top | Alignment::Bottom => groups[i] = i,
+            }
+        }
+        let (top, bottom) = (
+            shifts[i] - member.metrics.above,
+            shifts[i] + member.metrics.below,
+        );
+        extents[groups[i]] = Some(match extents[groups[i]] {
+            Some((t, b)) => (t.min(top), b.max(bottom)),
+            None => (top, bottom),
+        });
+    }
+
+    let (root_top, root_bottom) = extents[0].unwrap_or_default();
+    let height = extents
+        .iter()
+        .flatten()
+        .fold(root_bottom - root_top, |height, (top, bottom)| {
+            height.max(bottom - top)
+        });
+    let baselines = (0..members.len())
+        .map(|i| {
+            let group = groups[i];
+            let (top, bottom) = extents[group].unwrap();
+            let baseline = match members[group].alignment {
+                Alignment::Bottom if group > 0 => height - bottom,
+                _ => -top,
+            };
+            baseline + shifts[i]
+        })
+        .collect();
+    LineAlignment { height, baselines }
+}
+
+/// Returns the alignment of a box from `vertical-align`, where the percentages refer to the line height of
+/// the box. The middle of the box is aligned to the half of the x-height above the baseline of the parent,
+/// where the x-height is assumed to be the half of the font size.
+/// https://www.w3.org/TR/CSS22/visudet.html#propdef-vertical-align
+fn resolve_alignment(
+    value: &CssValue,
+    metrics: &Metrics,
+    parent: &Metrics,
+    line_height: f32,
+) -> Alignment {
+    match value {
+        CssValue::Ident(v) => match v.as_str() {
+            "sub" => Alignment::Shift(parent.font_size / 5.0),
+            "super" => Alignment::Shift(-parent.font_size / 3.0),
+            "text-top" => Alignment::Shift(metrics.above - parent.ascent),
+            "text-bottom" => Alignment::Shift(parent.descent - metrics.below),
+            "middle" => {
+                Alignment::Shift(-parent.font_size / 4.0 - (metrics.below - metrics.above) / 2.0)
+            }
+            "top" => Alignment::Top,
+            "bottom" => Alignment::Bottom,
+            _ => Alignment::Shift(0.0),
+        },
+        _ => Alignment::Shift(-value.resolve_px(line_height).unwrap_or(0.0)),
+    }
+}
+
+/// Returns the metrics of the first available font of the style and its used line height.
+fn get_style_metrics(draw_ctx: &pango::Context, style: &ComputedStyle) -> (Metrics, f32) {
+    let font = SelectedFont::select(draw_ctx, style);
+    let line_height = style.line_height.to_px(font.size);
+    let metrics = Metrics::new(font.measure(draw_ctx, ""), font.size, line_height);
+    (metrics, metrics.above + metrics.below)
+}
+
+/// The string which replaces the contents overflowing the lines by `text-overflow: ellipsis`.
+const ELLIPSIS: &str = "\u{2026}";
+
+/// An inline box in the inline formatting context. The first one is the root inline box, which wraps the
+/// contents and takes the style of the block container.
+/// https://drafts.csswg.org/css-inline-3/#root-inline-box
+struct InlineBoxEntry {
+    node: Option<Rc<RefCell<BoxNode>>>,
+    parent: usize,
+    metrics: Metrics,
+    line_height: f32,
+    vertical_align: CssValue,
+    /// The widths of the margin, border and padding at the start and the end of the box, which are only
+    /// placed in the first and the last line of it.
+    start_edge: f32,
+    end_edge: f32,
+    /// Whether the lines wrap around the atomic inline-level boxes in the box.
+    wraps: bool,
+    /// The classes of the bidirectional control characters which the start and the end of the box are
+    /// treated as.
+    bidi_controls: (Vec<BidiClass>, Vec<BidiClass>),
+    /// The union of the content areas of the fragments of the box in the line boxes.
+    bounds: Option<Rect>,
+}
+
+struct TextEntry {
+    node: Rc<RefCell<BoxNode>>,
+    parent: usize,
+    text: String,
+    font: SelectedFont,
+    line_height: Option<f32>,
+    fragments: Vec<TextLine>,
+}
+
+/// An atomic inline-level box, which is laid out before it's placed in a line as a whole.
+struct AtomicEntry {
+    node: Rc<RefCell<BoxNode>>,
+    parent: usize,
+    /// The metrics of the margin box, whose ascent is the distance from its top to the baseline.
+    metrics: Metrics,
+    width: f32,
+    line_height: f32,
+    vertical_align: CssValue,
+    /// The embedding level of the box.
+    level: u8,
+}
+
+enum Item {
+    Start(usize),
+    End(usize),
+    /// A word of a text and the spaces after it up to `space_end`, whose width is `space`. The line can wrap
+    /// after the spaces if `wraps` and `break_after`, and the spaces are removed at the end of a line if
+    /// `collapses`, or else they hang there. The characters of the word and the spaces have the same
+    /// embedding level.
+    Word {
+        text: usize,
+        range: Range<usize>,
+        space_end: usize,
+        width: f32,
+        space: f32,
+        wraps: bool,
+        collapses: bool,
+        break_after: bool,
+        level: u8,
+    },
+    Atomic(usize),
+    /// A `br` element or a preserved newline.
+    ForcedBreak(Option<Rc<RefCell<BoxNode>>>),
+    OutOfFlow(Rc<RefCell<BoxNode>>),
+}
+
+/// The items between two break opportunities, which are placed in a line as a whole.
+struct Chunk {
+    range: Range<usize>,
+    width: f32,
+    hang: f32,
+    is_forced_break: bool,
+}
+
+/// A text of a line box being placed, which is a fragment of a text.
+struct PendingFragment {
+    text: usize,
+    range: Range<usize>,
+    x: f32,
+    level: u8,
+}
+
+struct InlineFormattingContext<'a> {
+    draw_ctx: &'a pango::Context,
+    container: &'a LayoutInfo,
+    boxes: Vec<InlineBoxEntry>,
+    texts: Vec<TextEntry>,
+    atomics: Vec<AtomicEntry>,
+    items: Vec<Item>,
+    /// Whether the last text collected ends with a collapsible space.
+    follows_space: bool,
+    /// The last character of the texts collected after the last atomic box or line break if any.
+    last_char: Option<char>,
+    /// The paragraph embedding level of the contents.
+    paragraph_level: u8,
+    text_align: TextAlignProp,
+    /// The indentation of the first line.
+    text_indent: f32,
+    /// The width of the ellipsis in the font of the container, if the contents overflowing the lines are
+    /// replaced with it.
+    ellipsis_width: Option<f32>,
+}
+
+impl InlineFormattingContext<'_> {
+    /// Flattens the inline-level contents into the items placed in the lines.
+    fn collect(&mut self, children: &[Rc<RefCell<BoxNode>>], parent: usize) {
+        for child in children {
+            if is_out_of_flow(&child.borrow()) {
+                self.items.push(Item::OutOfFlow(Rc::clone(child)));
+                continue;
+            }
+            if let BoxNode::Text(t) = &*child.borrow() {
+                let font = t.get_selected_font();
+                let style = &t.style_node.borrow().style;
+                self.texts.push(TextEntry {
+                    node: Rc::clone(child),
+                    parent,
+                    text: style.text_transform.apply(
+                        &collapse_white_space(
+                            &t.get_text(),
+                            &style.white_space,
+                            &mut self.follows_space,
+                        ),
+                        self.last_char,
+                    ),
+                    line_height: style.line_height.to_px(font.size),
+                    font,
+                    fragments: Vec::new(),
+                });
+                self.collect_words(self.texts.len() - 1, style);
+                continue;
+            }
+            let (is_inline_box, is_line_break) = match &*child.borrow() {
+                // The ruby is laid out as a whole like an atomic inline-level box for now.
+                BoxNode::InlineBox(b) => (!b.is_element("ruby"), b.is_element("br")),
+                _ => (false, false),
+            };
+            if is_line_break {
+                self.items.push(Item::ForcedBreak(Some(Rc::clone(child))));
+                self.follows_space = true;
+                self.last_char = None;
+            } else if is_inline_box {
+                self.collect_inline_box(child, parent);
+            } else {
+                self.collect_atomic(child, parent);
+                self.follows_space = false;
+                self.last_char = None;
+            }
+        }
+    }
+
+    /// Splits the text at the break opportunities into the words followed by the spaces, and into the lines
+    /// at the preserved newlines. The words wider than the line are split further at the characters if
+    /// `overflow-wrap` allows it.
+    /// todo: Split the words at the grapheme clusters rather than the characters.
+    fn collect_words(&mut self, text: usize, style: &ComputedStyle) {
+        let entry = &self.texts[text];
+        // The break opportunity between the last text and this one is found with the last character of it.
+        let prefix = self.last_char.map_or(String::new(), String::from);
+        let opportunities =
+            get_break_opportunities(&format!("{}{}", prefix, entry.text), &style.word_break);
+        if !prefix.is_empty() {
+            let last_item = self
+                .items
+                .iter_mut()
+                .rev()
+                .find(|item| !matches!(item, Item::Start(_) | Item::End(_)));
+            if let Some(Item::Word { break_after, .. }) = last_item {
+                *break_after = opportunities.contains(&prefix.len());
+            }
+        }
+        let opportunities = opportunities
+            .into_iter()
+            .filter_map(|i| i.checked_sub(prefix.len()))
+            .collect::<Vec<_>>();
+
+        let measure = |range: Range<usize>| self.measure(text, range);
+        let available_width = self.container.get_content_width();
+        let wraps = style.white_space.wraps();
+        let collapses = style.white_space.collapses_spaces();
+        let breaks_words = wraps && style.overflow_wrap.breaks_words();
+        let mut items = Vec::new();
+        let mut start = 0;
+        for (i, segment) in entry.text.split('\n').enumerate() {
+            if i > 0 {
+                items.push(Item::ForcedBreak(None));
+                start += 1;
+            }
+            let end = start + segment.len();
+            let boundaries = opportunities
+                .iter()
+                .copied()
+                .filter(|&i| start < i && i < end)
+                .chain([end]);
+            let mut piece_start = start;
+            for piece_end in boundaries {
+                if piece_end == piece_start {
+                    continue;
+                }
+                let word_end = piece_start
+                    + entry.text[piece_start..piece_end]
+                        .trim_end_matches(' ')
+                        .len();
+                let mut word_start = piece_start;
+                if breaks_words && measure(word_start..word_end) > available_width {
+                    let mut part_end = word_start;
+                    for (i, c) in entry.text[word_start..word_end].char_indices() {
+                        let next = piece_start + i + c.len_utf8();
+                        if part_end > word_start && measure(word_start..next) > available_width {
+                            items.push(Item::Word {
+                                text,
+                                range: word_start..part_end,
+                                space_end: part_end,
+                                width: measure(word_start..part_end),
+                                space: 0.0,
+                                wraps,
+                                collapses,
+                                break_after: true,
+                                level: 0,
+                            });
+                            word_start = part_end;
+                        }
+                        part_end = next;
+                    }
+                }
+                items.push(Item::Word {
+                    text,
+                    range: word_start..word_end,
+                    space_end: piece_end,
+                    width: measure(word_start..word_end),
+                    space: measure(word_end..piece_end),
+                    wraps,
+                    collapses,
+                    // The break opportunity at the end of the text depends on the next text if any.
+                    break_after: piece_end < end || word_end < piece_end,
+                    level: 0,
+                });
+                piece_start = piece_end;
+            }
+            start = end;
+        }
+        self.last_char = entry.text.chars().last().or(self.last_char);
+        self.items.extend(items);
+    }
+
+    fn collect_inline_box(&mut self, child: &Rc<RefCell<BoxNode>>, parent: usize) {
+        let mut node = child.borrow_mut();
+        let BoxNode::InlineBox(b) = &mut *node else {
+            unreachable!()
+        };
+        b.calc_used_values(self.container);
+        let (metrics, line_height) = get_style_metrics(self.draw_ctx, &b.style_node.borrow().style);
+        let used_values = &b.layout_info.used_values;
+        self.boxes.push(InlineBoxEntry {
+            node: Some(Rc::clone(child)),
+            parent,
+            metrics,
+            line_height,
+            vertical_align: b.style_node.borrow().style.vertical_align.value.clone(),
+            start_edge: used_values.margin.left
+                + used_values.border.left
+                + used_values.padding.left,
+            end_edge: used_values.padding.right
+                + used_values.border.right
+                + used_values.margin.right,
+            wraps: b.style_node.borrow().style.white_space.wraps(),
+            bidi_controls: get_box_controls(
+                &b.style_node.borrow().style.direction,
+                &b.style_node.borrow().style.unicode_bidi,
+            ),
+            bounds: None,
+        });
+        let index = self.boxes.len() - 1;
+        let children = b.children.clone();
+        drop(node);
+
+        self.items.push(Item::Start(index));
+        self.collect(&children, index);
+        self.items.push(Item::End(index));
+    }
+
+    fn collect_atomic(&mut self, child: &Rc<RefCell<BoxNode>>, parent: usize) {
+        child
+            .borrow_mut()
+            .layout(self.container, Some(self.container.clone()), None);
+        let node = child.borrow();
+        let margin_box = get_margin_box(&node);
+        let height = margin_box.height as f32;
+        let baseline =
+            get_atomic_baseline(&node).map_or(height, |baseline| baseline - margin_box.y as f32);
+        let style_node = node.get_element_style_node().unwrap();
+        let style = &style_node.borrow().style;
+        let (font_metrics, line_height) = get_style_metrics(self.draw_ctx, style);
+        self.atomics.push(AtomicEntry {
+            node: Rc::clone(child),
+            parent,
+            metrics: Metrics {
+                ascent: baseline,
+                descent: height - baseline,
+                font_size: font_metrics.font_size,
+                above: baseline,
+                below: height - baseline,
+            },
+            width: margin_box.width as f32,
+            line_height,
+            vertical_align: style.vertical_align.value.clone(),
+            level: 0,
+        });
+        self.items.push(Item::Atomic(self.atomics.len() - 1));
+    }
+
+    /// Resolves the embedding levels of the words and the atomic boxes by the bidirectional algorithm, and
+    /// splits the words where the levels change. The inline boxes are treated as the control characters
+    /// given by `unicode-bidi`, the atomic boxes as the object replacement characters and the forced line
+    /// breaks as the paragraph separators. The paragraph level is given by the first strong character if
+    /// `is_plaintext`.
+    /// https://drafts.csswg.org/css-writing-modes/#bidi-algo
+    fn resolve_bidi_levels(&mut self, is_plaintext: bool) {
+        let mut classes = Vec::new();
+        // The items which the classes come from, and the byte offsets of the characters in the texts.
+        let mut sources = Vec::new();
+        for (i, item) in self.items.iter().enumerate() {
+            match item {
+                Item::Start(b) | Item::End(b) => {
+                    let (start, end) = &self.boxes[*b].bidi_controls;
+                    let controls = if matches!(item, Item::Start(_)) {
+                        start
+                    } else {
+                        end
+                    };
+                    classes.extend(controls);
+                    sources.extend(controls.iter().map(|_| (i, 0)));
+                }
+                Item::Word {
+                    text,
+                    range,
+                    space_end,
+                    ..
+                } => {
+                    let chars = self.texts[*text].text[range.start..*space_end].char_indices();
+                    for (offset, c) in chars {
+                        classes.push(get_bidi_class(c));
+                        sources.push((i, range.start + offset));
+                    }
+                }
+                Item::Atomic(_) => {
+                    classes.push(BidiClass::On);
+                    sources.push((i, 0));
+                }
+                Item::ForcedBreak(_) => {
+                    classes.push(BidiClass::B);
+                    sources.push((i, 0));
+                }
+                Item::OutOfFlow(_) => {}
+            }
+        }
+        if is_plaintext {
+            self.paragraph_level = find_first_strong_level(&classes).unwrap_or(0);
+        }
+        let levels = resolve_levels(&classes, self.paragraph_level);
+        if levels.iter().all(|&level| level == 0) {
+            return;
+        }
+
+        let mut items = Vec::with_capacity(self.items.len());
+        let mut k = 0;
+        for (i, mut item) in std::mem::take(&mut self.items).into_iter().enumerate() {
+            let start = k;
+            while k < sources.len() && sources[k].0 == i {
+                k += 1;
+            }
+            let (item_levels, offsets) = (&levels[start..k], &sources[start..k]);
+            // The runs of the characters of the same level in the item.
+            let runs = (0..item_levels.len())
+                .filter(|&j| j == 0 || item_levels[j] != item_levels[j - 1])
+                .collect::<Vec<_>>();
+            match &mut item {
+                Item::Word { level, .. } if runs.len() == 1 => *level = item_levels[0],
+                Item::Word {
+                    text,
+                    range,
+                    space_end,
+                    wraps,
+                    collapses,
+                    break_after,
+                    ..
+                } => {
+                    for (r, &run) in runs.iter().enumerate() {
+                        let run_start = offsets[run].1;
+                        let run_end = runs.get(r + 1).map_or(*space_end, |&next| offsets[next].1);
+                        let word_end = run_end.min(range.end).max(run_start);
+                        items.push(Item::Word {
+                            text: *text,
+                            range: run_start..word_end,
+                            space_end: run_end,
+                            width: self.measure(*text, run_start..word_end),
+                            space: self.measure(*text, word_end..run_end),
+                            wraps: *wraps,
+                            collapses: *collapses,
+                            break_after: *break_after && run_end == *space_end,
+                            level: item_levels[run],
+                        });
+                    }
+                    continue;
+                }
+                Item::Atomic(a) => self.atomics[*a].level = item_levels[0],
+                _ => {}
+            }
+            items.push(item);
+        }
+        self.items = items;
+    }
+
+    /// Returns the width of the range of the text.
+    fn measure(&self, text: usize, range: Range<usize>) -> f32 {
+        if range.is_empty() {
+            return 0.0;
+        }
+        let entry = &self.texts[text];
+        entry.font.measure(self.draw_ctx, &entry.text[range]).width
+    }
+
+    /// Splits the items at the break opportunities, which are the ones after the words with the ends of the
+    /// boxes right after them, the both sides of the atomic inline-level boxes and the forced line breaks.
+    /// The lines don't wrap after the words and around the atomic boxes where `white-space` prevents it.
+    fn get_chunks(&self) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+        let (mut start, mut width) = (0, 0.0);
+        let mut i = 0;
+        while i < self.items.len() {
+            match &self.items[i] {
+                Item::Start(b) => width += self.boxes[*b].start_edge,
+                Item::End(b) => width += self.boxes[*b].end_edge,
+                Item::Word {
+                    width: word_width,
+                    space,
+                    wraps,
+                    break_after,
+                    ..
+                } => {
+                    width += word_width;
+                    if !wraps || !break_after {
+                        width += space;
+                    } else {
+                        let mut hang = *space;
+                        i += 1;
+                        while let Some(Item::End(b)) = self.items.get(i) {
+                            // The space doesn't hang if it's followed by the end edge of a box.
+                            if self.boxes[*b].end_edge != 0.0 {
+                                width += hang + self.boxes[*b].end_edge;
+                                hang = 0.0;
+                            }
+                            i += 1;
+                        }
+                        chunks.push(Chunk {
+                            range: start..i,
+                            width,
+                            hang,
+                            is_forced_break: false,
+                        });
+                        (start, width) = (i, 0.0);
+                        continue;
+                    }
+                }
+                Item::Atomic(a) if !self.boxes[self.atomics[*a].parent].wraps => {
+                    width += self.atomics[*a].width;
+                }
+                Item::Atomic(a) => {
+                    // The boxes which start right before the atomic box move to the next line with it.
+                    let mut split = i;
+                    while split > start && matches!(self.items[split - 1], Item::Start(_)) {
+                        split -= 1;
+                    }
+                    if split > start {
+                        let edges = self.items[split..i]
+                            .iter()
+                            .map(|item| match item {
+                                Item::Start(b) => self.boxes[*b].start_edge,
+                                _ => 0.0,
+                            })
+                            .sum::<f32>();
+                        chunks.push(Chunk {
+                            range: start..split,
+                            width: width - edges,
+                            hang: 0.0,
+                            is_forced_break: false,
+                        });
+                        (start, width) = (split, edges);
+                    }
+                    width += self.atomics[*a].width;
+                    i += 1;
+                    while let Some(Item::End(b)) = self.items.get(i) {
+                        width += self.boxes[*b].end_edge;
+                        i += 1;
+                    }
+                    chunks.push(Chunk {
+                        range: start..i,
+                        width,
+                        hang: 0.0,
+                        is_forced_break: false,
+                    });
+                    (start, width) = (i, 0.0);
+                    continue;
+                }
+                Item::ForcedBreak(_) => {
+                    chunks.push(Chunk {
+                        range: start..i + 1,
+                        width,
+                        hang: 0.0,
+                        is_forced_break: true,
+                    });
+                    (start, width) = (i + 1, 0.0);
+                }
+                Item::OutOfFlow(_) => {}
+            }
+            i += 1;
+        }
+        if start < self.items.len() {
+            chunks.push(Chunk {
+                range: start..self.items.len(),
+                width,
+                hang: 0.0,
+                is_forced_break: false,
+            });
+        }
+        chunks
+    }
+
+    /// Breaks the items into the lines greedily, where a chunk wider than the available width is placed in
+    /// a line by itself. The available width of the first line is reduced by the indentation.
+    fn break_lines(&self) -> Vec<Range<usize>> {
+        let content_width = self.container.get_content_width();
+        let mut lines = Vec::new();
+        let (mut start, mut width, mut has_contents) = (0, 0.0, false);
+        for chunk in self.get_chunks() {
+            let available_width = if lines.is_empty() {
+                content_width - self.text_indent
+            } else {
+                content_width
+            };
+            if has_contents && width + chunk.width > available_width {
+                lines.push(start..chunk.range.start);
+                (start, width, has_contents) = (chunk.range.start, 0.0, false);
+            }
+            width += chunk.width + chunk.hang;
+            has_contents |= chunk.width > 0.0;
+            if chunk.is_forced_break {
+                lines.push(start..chunk.range.end);
+                (start, width, has_contents) = (chunk.range.end, 0.0, false);
+            }
+        }
+        if start < self.items.len() {
+            lines.push(start..self.items.len());
+        }
+        lines
+    }
+
+    fn layout(&mut self) -> f32 {
+        let content_x = self.container.pos.x + self.container.used_values.padding.left;
+        let content_y = self.container.pos.y + self.container.used_values.padding.top;
+        let mut y = content_y;
+        // The boxes which continue from the previous line.
+        let mut open_boxes = Vec::new();
+        let lines = self.break_lines();
+        for (i, line) in lines.iter().enumerate() {
+            // The last line and the lines ending with forced breaks aren't justified.
+            let justifies = self.text_align.is_justified()
+                && i + 1 < lines.len()
+                && !matches!(self.items[line.end - 1], Item::ForcedBreak(_));
+            let indent = if i == 0 { self.text_indent } else { 0.0 };
+            y += self.layout_line(
+                line.clone(),
+                &mut open_boxes,
+                content_x,
+                y,
+                indent,
+                justifies,
+            );
+        }
+
+        for entry in &self.texts {
+            let mut node = entry.node.borrow_mut();
+            let BoxNode::Text(t) = &mut *node else {
+                unreachable!()
+            };
+            t.layout_info = LayoutInfo {
+                pos: BoxPosition {
+                    x: content_x,
+                    y: content_y,
+                },
+                ..Default::default()
+            };
+            t.set_lines(entry.fragments.clone());
+        }
+        for entry in &self.boxes {
+            if let (Some(node), Some(bounds)) = (&entry.node, entry.bounds) {
+                let mut node = node.borrow_mut();
+                let layout_info = node.get_layout_info_mut();
+                layout_info.pos = BoxPosition {
+                    x: bounds.x as f32,
+                    y: bounds.y as f32,
+                };
+                layout_info.size = BoxSize {
+                    width: bounds.width as f32,
+                    height: bounds.height as f32,
+                };
+            }
+        }
+        y - content_y
+    }
+
+    /// Places the items of the line in the line box at `(x, y)` indented by `indent` from its start, and
+    /// returns the height of the line box. The spaces between the words are stretched if `justifies`.
+    fn layout_line(
+        &mut self,
+        line: Range<usize>,
+        open_boxes: &mut Vec<usize>,
+        x: f32,
+        y: f32,
+        indent: f32,
+        justifies: bool,
+    ) -> f32 {
+        let is_rtl = self.paragraph_level % 2 == 1;
+        let mut x = if is_rtl { x } else { x + indent };
+        // The members of the line box, and the ones of the boxes, which are placed after the boxes
+        // containing them.
+        let mut members = vec![LineMember {
+            parent: 0,
+            metrics: self.boxes[0].metrics,
+            alignment: Alignment::Shift(0.0),
+        }];
+        let mut box_members = vec![None; self.boxes.len()];
+        box_members[0] = Some(0);
+        let line_start = x;
+        let mut spans = vec![None; self.boxes.len()];
+        for &b in open_boxes.iter() {
+            box_members[b] = Some(self.add_box_member(&mut members, &box_members, b));
+            spans[b] = Some((x, x));
+        }
+        for i in line.clone() {
+            if let Item::Start(b) = self.items[i] {
+                box_members[b] = Some(self.add_box_member(&mut members, &box_members, b));
+            }
+        }
+
+        // The collapsible spaces before the first contents and after the last contents of the line are
+        // removed or hang, and aren't placed.
+        let is_content = |item: &Item| match item {
+            Item::Word {
+                range, collapses, ..
+            } => !range.is_empty() || !collapses,
+            Item::Atomic(_) => true,
+            _ => false,
+        };
+        let first_content = line.clone().find(|&i| is_content(&self.items[i]));
+        let last_content = line.clone().rev().find(|&i| is_content(&self.items[i]));
+        let mut fragment: Option<PendingFragment> = None;
+        // The ends of the spaces between the words, where the spaces are stretched if justified.
+        let mut word_gaps = Vec::new();
+        let mut fragments = Vec::new();
+        let mut atomics = Vec::new();
+        // The out-of-flow boxes and the line breaks, which are placed at the top of the line.
+        let mut empty_boxes = Vec::new();
+        let mut is_empty = true;
+        for i in self.get_visual_order(line.clone()) {
+            if !matches!(self.items[i], Item::Word { .. }) {
+                if let Some(pending) = fragment.take() {
+                    x = self.place_fragment(pending, &mut fragments, &mut spans);
+                }
+            }
+            match &self.items[i] {
+                // The edges of a box are placed at its left first, which is its end if it's reversed.
+                Item::Start(b) | Item::End(b) => {
+                    let b = *b;
+                    if let Some((start, end)) = spans[b] {
+                        spans[b] = Some((start, end.max(x)));
+                        x += self.boxes[b].end_edge;
+                        is_empty &= self.boxes[b].end_edge == 0.0;
+                    } else {
+                        x += self.boxes[b].start_edge;
+                        spans[b] = Some((x, x));
+                        is_empty &= self.boxes[b].start_edge == 0.0;
+                    }
+                }
+                Item::Word {
+                    text,
+                    range,
+                    space_end,
+                    space,
+                    collapses,
+                    level,
+                    ..
+                } => {
+                    if !is_content(&self.items[i]) && first_content.is_none_or(|first| i < first) {
+                        continue;
+                    }
+                    is_empty &= !is_content(&self.items[i]);
+                    let end = if *space > 0.0
+                        && (!collapses || last_content.is_some_and(|last| i < last))
+                    {
+                        *space_end
+                    } else {
+                        range.end
+                    };
+                    if range.start == end {
+                        continue;
+                    }
+                    match &mut fragment {
+                        Some(pending)
+                            if pending.text == *text
+                                && pending.range.end == range.start
+                                && pending.level == *level =>
+                        {
+                            pending.range.end = end;
+                        }
+                        _ => {
+                            if let Some(pending) = fragment.take() {
+                                x = self.place_fragment(pending, &mut fragments, &mut spans);
+                            }
+                            fragment = Some(PendingFragment {
+                                text: *text,
+                                range: range.start..end,
+                                x,
+                                level: *level,
+                            });
+                        }
+                    }
+                    if justifies && end == *space_end && *space > 0.0 {
+                        x = self.place_fragment(
+                            fragment.take().unwrap(),
+                            &mut fragments,
+                            &mut spans,
+                        );
+                        word_gaps.push(x);
+                    }
+                }
+                Item::Atomic(a) => {
+                    is_empty = false;
+                    atomics.push((*a, x));
+                    let end = x + self.atomics[*a].width;
+                    self.extend_spans(&mut spans, self.atomics[*a].parent, x, end);
+                    x = end;
+                }
+                Item::ForcedBreak(node) => {
+                    is_empty = false;
+                    if let Some(node) = node {
+                        empty_boxes.push((Rc::clone(node), x));
+                    }
+                }
+                Item::OutOfFlow(node) => empty_boxes.push((Rc::clone(node), x)),
+            }
+        }
+        if let Some(pending) = fragment.take() {
+            x = self.place_fragment(pending, &mut fragments, &mut spans);
+        }
+        // The boxes continued to the next line are the ones left open in the logical order.
+        for i in line.clone() {
+            match self.items[i] {
+                Item::Start(b) => open_boxes.push(b),
+                Item::End(b) => open_boxes.retain(|&open| open != b),
+                _ => {}
+            }
+        }
+        for &b in open_boxes.iter() {
+            spans[b] = spans[b].map(|(start, end)| (start, end.max(x)));
+        }
+
+        // The free space of the line is distributed to the spaces between the words if justified, or else
+        // placed before the contents by the alignment. The spaces at the end of the line aren't stretched, and
+        // the overflowing contents are aligned to the start.
+        // https://drafts.csswg.org/css-text/#text-align-property
+        let free_space = self.container.get_content_width() - indent - (x - line_start);
+        word_gaps.retain(|&gap| gap < x);
+        let (shift, gap_width) = if justifies && free_space > 0.0 && !word_gaps.is_empty() {
+            (0.0, free_space / word_gaps.len() as f32)
+        } else if free_space < 0.0 {
+            (if is_rtl { free_space } else { 0.0 }, 0.0)
+        } else {
+            (
+                free_space * self.text_align.get_free_space_ratio(is_rtl),
+                0.0,
+            )
+        };
+        let shift_x = |x: f32| {
+            x + shift + gap_width * word_gaps.iter().filter(|&&gap| gap <= x).count() as f32
+        };
+        for span in spans.iter_mut().flatten() {
+            *span = (shift_x(span.0), shift_x(span.1));
+        }
+        for (_, line) in fragments.iter_mut() {
+            line.x = shift_x(line.x);
+        }
+        for (_, atomic_x) in atomics.iter_mut() {
+            *atomic_x = shift_x(*atomic_x);
+        }
+        for (_, empty_x) in empty_boxes.iter_mut() {
+            *empty_x = shift_x(*empty_x);
+        }
+        if let Some(ellipsis_width) = self.ellipsis_width {
+            self.truncate_line(
+                &mut fragments,
+                &mut atomics,
+                line_start - indent,
+                ellipsis_width,
+            );
+        }
+
+        let fragment_members = fragments
+            .iter()
+            .map(|(text, line): &(usize, TextLine)| {
+                let entry = &self.texts[*text];
+                members.push(LineMember {
+                    parent: box_members[entry.parent].unwrap(),
+                    metrics: Metrics::new(
+                        TextExtents {
+                            width: line.width,
+                            ascent: line.ascent,
+                            height: line.height,
+                        },
+                        entry.font.size,
+                        entry.line_height,
+                    ),
+                    alignment: Alignment::Shift(0.0),
+                });
+                members.len() - 1
+            })
+            .collect::<Vec<_>>();
+        let atomic_members = atomics
+            .iter()
+            .map(|(a, _)| {
+                let entry = &self.atomics[*a];
+                members.push(LineMember {
+                    parent: box_members[entry.parent].unwrap(),
+                    metrics: entry.metrics,
+                    alignment: resolve_alignment(
+                        &entry.vertical_align,
+                        &entry.metrics,
+                        &self.boxes[entry.parent].metrics,
+                        entry.line_height,
+                    ),
+                });
+                members.len() - 1
+            })
+            .collect::<Vec<_>>();
+
+        // The line boxes without any contents are treated as zero-height line boxes.
+        // https://www.w3.org/TR/CSS22/visuren.html#inline-formatting
+        let alignment = if is_empty {
+            LineAlignment {
+                height: 0.0,
+                baselines: vec![0.0; members.len()],
+            }
+        } else {
+            align_line(&members)
+        };
+
+        for (b, span) in spans.iter().enumerate() {
+            let (Some((start, end)), Some(member)) = (span, box_members[b]) else {
+                continue;
+            };
+            let entry = &mut self.boxes[b];
+            let baseline = y + alignment.baselines[member];
+            let area = Rect::new(
+                *start as f64,
+                (baseline - entry.metrics.ascent) as f64,
+                (end - start) as f64,
+                (entry.metrics.ascent + entry.metrics.descent) as f64,
+            );
+            entry.bounds = Some(entry.bounds.map_or(area, |bounds| bounds.union(&area)));
+        }
+        for ((text, mut line), member) in fragments.into_iter().zip(fragment_members) {
+            line.y = y + alignment.baselines[member] - line.ascent;
+            self.texts[text].fragments.push(line);
+        }
+        for ((a, atomic_x), member) in atomics.into_iter().zip(atomic_members) {
+            let entry = &self.atomics[a];
+            let margin_box = get_margin_box(&entry.node.borrow());
+            let top = y + alignment.baselines[member] - entry.metrics.above;
+            entry
+                .node
+                .borrow_mut()
+                .translate(atomic_x - margin_box.x as f32, top - margin_box.y as f32);
+        }
+        // The out-of-flow boxes are placed where they would be in flow, i.e. their static positions.
+        for (node, x) in empty_boxes {
+            node.borrow_mut().get_layout_info_mut().pos = BoxPosition { x, y };
+        }
+        alignment.height
+    }
+
+    fn add_box_member(
+        &self,
+        members: &mut Vec<LineMember>,
+        box_members: &[Option<usize>],
+        b: usize,
+    ) -> usize {
+        let entry = &self.boxes[b];
+        let parent = &self.boxes[entry.parent];
+        members.push(LineMember {
+            parent: box_members[entry.parent].unwrap(),
+            metrics: entry.metrics,
+            alignment: resolve_alignment(
+                &entry.vertical_align,
+                &entry.metrics,
+                &parent.metrics,
+                entry.line_height,
+            ),
+        });
+        members.len() - 1
+    }
+
+    /// Returns the visual order of the items of the line by the embedding levels of them, where the items
+    /// other than the words and the atomic boxes take the levels of the contents next to them, and the ones
+    /// at the end of the line take the paragraph level.
+    fn get_visual_order(&self, line: Range<usize>) -> Vec<usize> {
+        let levels = line
+            .clone()
+            .map(|i| match &self.items[i] {
+                Item::Word { level, .. } => Some(*level),
+                Item::Atomic(a) => Some(self.atomics[*a].level),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let last_leaf = levels.iter().rposition(Option::is_some);
+        let levels = (0..levels.len())
+            .map(|k| {
+                if let Some(level) = levels[k] {
+                    return level;
+                }
+                if last_leaf.is_none_or(|last| k > last) {
+                    return self.paragraph_level;
+                }
+                let prev = levels[..k].iter().rev().find_map(|level| *level);
+                let next = levels[k + 1..].iter().find_map(|level| *level);
+                match self.items[line.start + k] {
+                    Item::Start(_) => next.or(prev),
+                    _ => prev.or(next),
+                }
+                .unwrap_or(self.paragraph_level)
+            })
+            .collect::<Vec<_>>();
+        get_visual_order(&levels)
+            .into_iter()
+            .map(|k| line.start + k)
+            .collect()
+    }
+
+    /// Extends the spans of the box and the ones containing it in the line to the range.
+    fn extend_spans(&self, spans: &mut [Option<(f32, f32)>], b: usize, start: f32, end: f32) {
+        let mut b = b;
+        while b != 0 {
+            spans[b] = Some(spans[b].map_or((start, end), |(s, e)| (s.min(start), e.max(end))));
+            b = self.boxes[b].parent;
+        }
+    }
+
+    /// Hides the contents of the line overflowing the end edge of the content box from `content_start`, and
+    /// appends an ellipsis to the last text visible, whose characters are hidden to fit the ellipsis.
+    /// todo: Place the ellipsis after the atomic inline-level boxes, and truncate the fragments whose
+    /// direction is opposite to the line.
+    /// https://drafts.csswg.org/css-overflow/#text-overflow
+    fn truncate_line(
+        &self,
+        fragments: &mut Vec<(usize, TextLine)>,
+        atomics: &mut [(usize, f32)],
+        content_start: f32,
+        ellipsis_width: f32,
+    ) {
+        let is_rtl = self.paragraph_level % 2 == 1;
+        let content_end = content_start + self.container.get_content_width();
+        // The distances of the edges of the contents from the start edge of the line, which are measured to
+        // the left in the right-to-left lines.
+        let get_edges = |x: f32, width: f32| {
+            if is_rtl {
+                (content_end - x - width, content_end - x)
+            } else {
+                (x - content_start, x + width - content_start)
+            }
+        };
+        let available_width = content_end - content_start;
+        let overflows = fragments
+            .iter()
+            .map(|(_, line)| get_edges(line.x, line.width).1)
+            .chain(
+                atomics
+                    .iter()
+                    .map(|(a, x)| get_edges(*x, self.atomics[*a].width).1),
+            )
+            .any(|end| end > available_width);
+        if !overflows {
+            return;
+        }
+
+        // The contents are visited from the end edge of the line.
+        let mut items = fragments
+            .iter()
+            .enumerate()
+            .map(|(i, (_, line))| (Some(i), get_edges(line.x, line.width)))
+            .chain(
+                atomics
+                    .iter()
+                    .map(|(a, x)| (None, get_edges(*x, self.atomics[*a].width))),
+            )
+            .collect::<Vec<_>>();
+        items.sort_by(|(_, a), (_, b)| b.1.total_cmp(&a.1));
+        // The end of the contents kept visible, after which the atomic inline-level boxes are hidden.
+        let mut kept_end = f32::NEG_INFINITY;
+        let mut hidden_fragments = Vec::new();
+        for (fragment, (start, end)) in items {
+            let Some(i) = fragment else {
+                if end > available_width - ellipsis_width {
+                    continue;
+                }
+                // The ellipsis isn't placed after an atomic inline-level box for now.
+                kept_end = end;
+                break;
+            };
+            let (text, line) = &mut fragments[i];
+            let entry = &self.texts[*text];
+            let fits = |text: &str| {
+                start + entry.font.measure(self.draw_ctx, text).width <= available_width
+            };
+            let is_same_direction = line.level.is_none_or(|level| level % 2 == is_rtl as u8);
+            let mut kept = if is_same_direction {
+                line.text.as_str()
+            } else {
+                ""
+            };
+            while !kept.is_empty() && !fits(&format!("{}{}", kept.trim_end(), ELLIPSIS)) {
+                let mut chars = kept.chars();
+                chars.next_back();
+                kept = chars.as_str();
+            }
+            let truncated = format!("{}{}", kept.trim_end(), ELLIPSIS);
+            if !fits(&truncated) {
+                hidden_fragments.push(i);
+                continue;
+            }
+            let width = entry.font.measure(self.draw_ctx, &truncated).width;
+            if is_rtl {
+                line.x += line.width - width;
+            }
+            line.text = truncated;
+            line.width = width;
+            kept_end = end;
+            break;
+        }
+        // The hidden atomic inline-level boxes are moved out of the padding box, which clips them.
+        let padding = &self.container.used_values.padding;
+        for (a, x) in atomics.iter_mut() {
+            let width = self.atomics[*a].width;
+            if get_edges(*x, width).1 > kept_end {
+                *x = if is_rtl {
+                    content_start - padding.left - width
+                } else {
+                    content_end + padding.right
+                };
+            }
+        }
+        let mut i = 0;
+        fragments.retain(|_| {
+            i += 1;
+            !hidden_fragments.contains(&(i - 1))
+        });
+    }
+
+    /// Measures the fragment of the text placed from its position, extends the spans of the boxes containing
+    /// it, and returns the end of it.
+    fn place_fragment(
+        &self,
+        pending: PendingFragment,
+        fragments: &mut Vec<(usize, TextLine)>,
+        spans: &mut [Option<(f32, f32)>],
+    ) -> f32 {
+        let entry = &self.texts[pending.text];
+        let text = &entry.text[pending.range.clone()];
+        let extents = entry.font.measure(self.draw_ctx, text);
+        let is_first =
+            entry.fragments.is_empty() && fragments.iter().all(|(text, _)| *text != pending.text);
+        fragments.push((
+            pending.text,
+            TextLine {
+                text: text.to_string(),
+                x: pending.x,
+                y: 0.0,
+                width: extents.width,
+                height: extents.height,
+                ascent: extents.ascent,
+                break_offset: (!is_first).then_some(pending.range.start),
+                level: Some(pending.level),
+            },
+        ));
+        let end = pending.x + extents.width;
+        self.extend_spans(spans, entry.parent, pending.x, end);
+        end
+    }
+}
+
+/// Returns the margin box of the laid out box.
+fn get_margin_box(node: &BoxNode) -> Rect {
+    let margin = &node.get_layout_info().used_values.margin;
+    get_border_box(node).expand((
+        margin.top as f64,
+        margin.right as f64,
+        margin.bottom as f64,
+        margin.left as f64,
+    ))
+}
+
+/// Returns the baseline of the laid out atomic inline-level box, which is the one of the last line box of an
+/// `inline-block`, the first one of a ruby, or the one of a formula. It's `None` if the box has no line boxes
+/// or clips its overflow, in which case the bottom margin edge is placed on the baseline.
+/// https://www.w3.org/TR/CSS22/visudet.html#propdef-vertical-align
+fn get_atomic_baseline(node: &BoxNode) -> Option<f32> {
+    match node {
+        BoxNode::BlockBox(b) if !b.style_node.borrow().style.overflow.clips() => {
+            find_text_baseline(node, true)
+        }
+        BoxNode::InlineBox(_) => find_text_baseline(node, false),
+        BoxNode::ReplacedBox(r) => r
+            .math
+            .as_ref()
+            .map(|math| r.layout_info.pos.y + math.ascent as f32),
+        _ => None,
+    }
+}
+
+/// Returns the baseline of the first or the last text in the box, except in the out-of-flow boxes.
+fn find_text_baseline(node: &BoxNode, is_last: bool) -> Option<f32> {
+    let find = |child: &Rc<RefCell<BoxNode>>| {
+        let child = child.borrow();
+        if is_out_of_flow(&child) {
+            None
+        } else {
+            find_text_baseline(&child, is_last)
+        }
+    };
+    match node {
+        BoxNode::Text(t) => if is_last {
+            t.lines.last()
+        } else {
+            t.lines.first()
+        }
+        .map(TextLine::get_baseline),
+        _ if is_last => node.get_children().iter().rev().find_map(find),
+        _ => node.get_children().iter().find_map(find),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn align_boxes_in_line() {
+        let metrics = |above: f32, below: f32| Metrics {
+            ascent: above,
+            descent: below,
+            font_size: 15.0,
+            above,
+            below,
+        };
+        let member = |parent, metrics, alignment| LineMember {
+            parent,
+            metrics,
+            alignment,
+        };
+        let root = member(0, metrics(12.0, 4.0), Alignment::Shift(0.0));
+
+        // A text in a superscript, which is raised above the strut of the root inline box.
+        let sup = resolve_alignment(
+            &CssValue::Ident("super".to_string()),
+            &metrics(12.0, 4.0),
+            &root.metrics,
+            16.0,
+        );
+        assert_eq!(sup, Alignment::Shift(-5.0));
+        let line = align_line(&[
+            root,
+            member(0, metrics(12.0, 4.0), sup),
+            member(1, metrics(12.0, 4.0), Alignment::Shift(0.0)),
+        ]);
+        assert_eq!(line.height, 21.0);
+        assert_eq!(line.baselines, vec![17.0, 12.0, 12.0]);
+
+        // An image on the baseline and a taller one aligned to the bottom of the line box.
+        let line = align_line(&[
+            root,
+            member(0, metrics(30.0, 0.0), Alignment::Shift(0.0)),
+            member(0, metrics(50.0, 0.0), Alignment::Bottom),
+        ]);
+        assert_eq!(line.height, 50.0);
+        assert_eq!(line.baselines, vec![30.0, 30.0, 50.0]);
+
+        // A box in the middle, whose percentage refers to its line height.
+        assert_eq!(
+            resolve_alignment(
+                &CssValue::Ident("middle".to_string()),
+                &metrics(20.0, 0.0),
+                &root.metrics,
+                20.0
+            ),
+            Alignment::Shift(6.25)
+        );
+        assert_eq!(
+            resolve_alignment(
+                &CssValue::Percentage(50.0),
+                &root.metrics,
+                &root.metrics,
+                20.0
+            ),
+            Alignment::Shift(-10.0)
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use crate::renderer::style::property::WordBreakProp;
+
+/// The line breaking classes of the characters.
+/// https://www.unicode.org/reports/tr14/#Table1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BreakClass {
+    /// Mandatory break
+    Bk,
+    /// Carriage return
+    Cr,
+    /// Line feed
+    Lf,
+    /// Space
+    Sp,
+    /// Zero width space
+    Zw,
+    /// Combining mark
+    Cm,
+    /// Zero width joiner
+    Zwj,
+    /// Word joiner
+    Wj,
+    /// Non-breaking ("glue")
+    Gl,
+    /// Opening punctuation
+    Op,
+    /// Closing punctuation
+    Cl,
+    /// Closing parenthesis
+    Cp,
+    /// Quotation
+    Qu,
+    /// Nonstarter, including the small kana
+    Ns,
+    /// Exclamation or interrogation
+    Ex,
+    /// Symbol allowing break after
+    Sy,
+    /// Infix numeric separator
+    Is,
+    /// Prefix numeric
+    Pr,
+    /// Postfix numeric
+    Po,
+    /// Numeric
+    Nu,
+    /// Alphabetic
+    Al,
+    /// Hyphen
+    Hy,
+    /// Break after
+    Ba,
+    /// Break before
+    Bb,
+    /// Break opportunity before and after
+    B2,
+    /// Inseparable
+    In,
+    /// Ideographic
+    Id,
+    /// Regional indicator
+    Ri,
+    /// Emoji modifier
+    Em,
+}
+
+/// Returns the line breaking class of the character.
+/// todo: Use the full data of the Unicode Character Database. The characters which aren't listed here are
+/// treated as alphabetic, including the ones of the scripts which need a dictionary to find the words.
+/// https://www.unicode.org/reports/tr14/#Properties
+fn get_break_class(c: char) -> BreakClass {
+    use BreakClass::*;
+    match c {
+        '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}' => Bk,
+        '\r' => Cr,
+        '\n' => Lf,
+        ' ' => Sp,
+        '\u{200B}' => Zw,
+        '\u{200D}' => Zwj,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{3099}'
+        | '\u{309A}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{E0100}'..='\u{E01EF}' => Cm,
+        '\u{2060}' | '\u{FEFF}' => Wj,
+        '\u{A0}' | '\u{2007}' | '\u{2011}' | '\u{202F}' => Gl,
+        '(' | '[' | '{' | '\u{A1}' | '\u{BF}' | '\u{3008}' | '\u{300A}' | '\u{300C}'
+        | '\u{300E}' | '\u{3010}' | '\u{3014}' | '\u{3016}' | '\u{3018}' | '\u{301A}'
+        | '\u{FF08}' | '\u{FF3B}' | '\u{FF5B}' | '\u{FF62}' => Op,
+        ')' | ']' => Cp,
+        '}' | '\u{3001}' | '\u{3002}' | '\u{3009}' | '\u{300B}' | '\u{300D}' | '\u{300F}'
+        | '\u{3011}' | '\u{3015}' | '\u{3017}' | '\u{3019}' | '\u{301B}' | '\u{FF09}'
+        | '\u{FF0C}' | '\u{FF0E}' | '\u{FF3D}' | '\u{FF5D}' | '\u{FF61}' | '\u{FF63}'
+        | '\u{FF64}' => Cl,
+        '"' | '\'' | '\u{AB}' | '\u{BB}' | '\u{2018}'..='\u{201F}' | '\u{2039}' | '\u{203A}' => Qu,
+        '\u{3005}'
+        | '\u{301C}'
+        | '\u{303B}'
+        | '\u{3041}'
+        | '\u{3043}'
+        | '\u{3045}'
+        | '\u{3047}'
+        | '\u{3049}'
+        | '\u{3063}'
+        | '\u{3083}'
+        | '\u{3085}'
+        | '\u{3087}'
+        | '\u{308E}'
+        | '\u{3095}'
+        | '\u{3096}'
+        | '\u{309B}'..='\u{309E}'
+        | '\u{30A0}'
+        | '\u{30A1}'
+        | '\u{30A3}'
+        | '\u{30A5}'
+        | '\u{30A7}'
+        | '\u{30A9}'
+        | '\u{30C3}'
+        | '\u{30E3}'
+        | '\u{30E5}'
+        | '\u{30E7}'
+        | '\u{30EE}'
+        | '\u{30F5}'
+        | '\u{30F6}'
+        | '\u{30FB}'
+        | '\u{30FC}'..='\u{30FE}'
+        | '\u{31F0}'..='\u{31FF}'
+        | '\u{203C}'
+        | '\u{2047}'..='\u{2049}'
+        | '\u{FF1A}'
+        | '\u{FF1B}'
+        | '\u{FF67}'..='\u{FF70}' => Ns,
+        '!' | '?' | '\u{FF01}' | '\u{FF1F}' => Ex,
+        '/' => Sy,
+        ',' | '.' | ':' | ';' | '\u{037E}' | '\u{0589}' => Is,
+        '$' | '+' | '\\' | '\u{A3}' | '\u{A5}' | '\u{20A0}'..='\u{20CF}' => Pr,
+        '%' | '\u{A2}' | '\u{B0}' | '\u{2030}'..='\u{2037}' | '\u{2103}' | '\u{FF05}' => Po,
+        '0'..='9' | '\u{0660}'..='\u{0669}' | '\u{06F0}'..='\u{06F9}' | '\u{0966}'..='\u{096F}' => {
+            Nu
+        }
+        '-' => Hy,
+        '\t'
+        | '|'
+        | '\u{AD}'
+        | '\u{058A}'
+        | '\u{05BE}'
+        | '\u{1680}'
+        | '\u{2000}'..='\u{2006}'
+        | '\u{2008}'..='\u{200A}'
+        | '\u{2010}'
+        | '\u{2012}'
+        | '\u{2013}'
+        | '\u{2027}'
+        | '\u{205F}'
+        | '\u{3000}' => Ba,
+        '\u{B4}' | '\u{2C8}' | '\u{2CC}' | '\u{2DF}' => Bb,
+        '\u{2014}' => B2,
+        '\u{2024}'..='\u{2026}' | '\u{22EF}' | '\u{FE19}' => In,
+        '\u{1F1E6}'..='\u{1F1FF}' => Ri,
+        '\u{1F3FB}'..='\u{1F3FF}' => Em,
+        '\u{2E80}'..='\u{2FFF}'
+        | '\u{3003}'..='\u{303F}'
+        | '\u{3040}'..='\u{30FF}'
+        | '\u{3100}'..='\u{31EF}'
+        | '\u{3200}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{A000}'..='\u{A4CF}'
+        | '\u{AC00}'..='\u{D7AF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FFEF}'
+        | '\u{1F000}'..='\u{1FAFF}'
+        | '\u{20000}'..='\u{3FFFD}' => Id,
+        _ => Al,
+    }
+}
+
+/// Returns the byte offsets of the characters in the text before which the lines may break, following
+/// the line breaking algorithm of Unicode and `word-break`. The mandatory breaks are returned as the
+/// opportunities as well.
+/// todo: Support the rules for the Hangul syllables, the emoji bases and the Hebrew letters, and the full
+/// rules for the numbers.
+/// https://www.unicode.org/reports/tr14/#Algorithm
+/// https://drafts.csswg.org/css-text/#word-break-property
+pub fn get_break_opportunities(text: &str, word_break: &WordBreakProp) -> Vec<usize> {
+    use BreakClass::*;
+    // With `break-all`, the letters and the numbers break like the ideographs.
+    let resolve = |class| match class {
+        Al | Nu if word_break.breaks_all() => Id,
+        class => class,
+    };
+    let mut opportunities = Vec::new();
+    let mut chars = text.char_indices();
+    let Some((_, first)) = chars.next() else {
+        return opportunities;
+    };
+    // LB10: The combining marks which don't follow a base character are treated as alphabetic.
+    let mut prev = match resolve(get_break_class(first)) {
+        Cm | Zwj => Al,
+        class => class,
+    };
+    // The class before the spaces, for the rules which apply across the spaces.
+    let mut before_spaces = prev;
+    let mut regional_indicators = (prev == Ri) as usize;
+    let mut follows_zwj = false;
+    for (i, c) in chars {
+        let class = resolve(get_break_class(c));
+        // LB9: The combining marks belong to the base characters before them.
+        if matches!(class, Cm | Zwj) && !matches!(prev, Bk | Cr | Lf | Sp | Zw) {
+            follows_zwj = class == Zwj;
+            continue;
+        }
+        let class = if matches!(class, Cm | Zwj) { Al } else { class };
+        // LB8a: Don't break after the zero width joiners.
+        let is_letter = |class| matches!(class, Al | Nu | Id);
+        let keeps_letters = word_break.keeps_all() && is_letter(prev) && is_letter(class);
+        if !follows_zwj
+            && !keeps_letters
+            && can_break(prev, before_spaces, class, regional_indicators)
+        {
+            opportunities.push(i);
+        }
+        follows_zwj = false;
+        if class != Sp {
+            before_spaces = class;
+        }
+        regional_indicators = if class == Ri {
+            regional_indicators + 1
+        } else {
+            0
+        };
+        prev = class;
+    }
+    opportunities
+}
+
+/// Returns whether the line may break between the characters of the classes `prev` and `next`, where
+/// `before_spaces` is the class of the last character which isn't a space before `next`, and
+/// `regional_indicators` is the number of the regional indicators right before `next`.
+fn can_break(
+    prev: BreakClass,
+    before_spaces: BreakClass,
+    next: BreakClass,
+    regional_indicators: usize,
+) -> bool {
+    use BreakClass::*;
+    match (prev, next) {
+        // LB4, LB5: Always break after the hard line breaks, but not between CR and LF.
+        (Cr, Lf) => false,
+        (Bk | Cr | Lf, _) => true,
+        // LB6, LB7: Don't break before the hard line breaks and the spaces.
+        (_, Bk | Cr | Lf | Sp | Zw) => false,
+        // LB8: Break after the zero width spaces, even after the spaces.
+        _ if before_spaces == Zw => true,
+        // LB11, LB12, LB12a: Don't break around the word joiners and the non-breaking characters.
+        (Wj | Gl, _) | (_, Wj) => false,
+        (prev, Gl) if !matches!(prev, Sp | Ba | Hy) => false,
+        // LB13: Don't break before the closing punctuations, the exclamations and the separators.
+        (_, Cl | Cp | Ex | Is | Sy) => false,
+        // LB14-LB17: Don't break after the opening punctuations, and between some pairs, even after the
+        // spaces.
+        _ if before_spaces == Op => false,
+        (_, Op) if before_spaces == Qu => false,
+        (_, Ns) if matches!(before_spaces, Cl | Cp) => false,
+        (_, B2) if before_spaces == B2 => false,
+        // LB18: Break after the spaces.
+        (Sp, _) => true,
+        // LB19, LB21, LB22: Don't break around the quotation marks, before the hyphens, the nonstarters
+        // and the inseparables, and after the characters breaking before.
+        (Qu, _) | (_, Qu) | (_, Ba | Hy | Ns | In) | (Bb, _) => false,
+        // LB23-LB25: Don't break in the numbers with the letters, the prefixes and the postfixes.
+        (Al, Nu) | (Nu, Al) | (Pr, Id) | (Id, Po) | (Pr | Po, Al) | (Al, Pr | Po) => false,
+        (Cl | Cp | Nu, Po | Pr) | (Po | Pr, Op | Nu) | (Hy | Is | Nu | Sy, Nu) => false,
+        // LB28-LB30: Don't break in the words, and between the words and the parentheses.
+        (Al | Is, Al) | (Al | Nu, Op) | (Cp, Al | Nu) => false,
+        // LB30a: Break between the pairs of the regional indicators.
+        (Ri, Ri) => regional_indicators.is_multiple_of(2),
+        // LB31: Break everywhere else.
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::renderer::style::property::CssValue;
+
+    #[test]
+    fn find_break_opportunities() {
+        let word_break = |value: &str| WordBreakProp {
+            value: CssValue::Ident(value.to_string()),
+        };
+        let normal = word_break("normal");
+        assert_eq!(get_break_opportunities("Hello, world!", &normal), [7]);
+        assert_eq!(get_break_opportunities("(a) b-c 10%", &normal), [4, 6, 8]);
+        // The ideographs break between each other, but not before the closing punctuations and the small kana.
+        assert_eq!(
+            get_break_opportunities("日本語でっす。", &normal),
+            [3, 6, 9, 15]
+        );
+        assert_eq!(
+            get_break_opportunities("ab cd", &word_break("break-all")),
+            [1, 3, 4]
+        );
+        assert_eq!(
+            get_break_opportunities("日本 語", &word_break("keep-all")),
+            [7]
+        );
+    }
+}
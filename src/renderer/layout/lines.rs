@@ -0,0 +1,147 @@
+use std::fmt::Write as _;
+
+use crate::renderer::get_element_label;
+use crate::renderer::layout::box_model::{BoxNode, BoxTree, LayoutInfo};
+use crate::renderer::layout::positioned::is_out_of_flow;
+use crate::renderer::style::property::DisplayOutside;
+use crate::renderer::style::style_model::RenderNode;
+
+/// Returns the inline formatting contexts of the laid out box tree for `--dump lines`, i.e. the blocks with
+/// inline-level contents, their inline-level boxes, the fragments of the texts in the line boxes with their
+/// positions and the break opportunities used before them, and the runs of the glyphs in the fragments with
+/// their advance widths.
+/// todo: Dump the line boxes themselves, instead of the fragments of each text.
+/// https://drafts.csswg.org/css-inline-3/#line-boxes
+pub fn dump_lines(tree: &BoxTree) -> String {
+    let mut dump = String::new();
+    write_block(&tree.root.borrow(), "", &mut dump);
+    dump
+}
+
+fn write_block(node: &BoxNode, parent_label: &str, dump: &mut String) {
+    let (label, layout_info, children) = match node {
+        BoxNode::BlockBox(b) => (
+            get_label(&b.style_node.borrow()),
+            &b.layout_info,
+            &b.children,
+        ),
+        BoxNode::AnonymousBox(b) => (
+            format!("{} (anonymous)", parent_label),
+            &b.layout_info,
+            &b.children,
+        ),
+        _ => return,
+    };
+    let is_inline_formatting_context = !children.is_empty()
+        && children.iter().all(|child| match &*child.borrow() {
+            node if is_out_of_flow(node) => true,
+            BoxNode::InlineBox(_) | BoxNode::Text(_) => true,
+            BoxNode::BlockBox(b) => b.is_atomic_inline(),
+            BoxNode::ReplacedBox(r) => r.get_display_type() == DisplayOutside::Inline,
+            _ => false,
+        });
+    if !is_inline_formatting_context {
+        for child in children {
+            write_block(&child.borrow(), &label, dump);
+        }
+        return;
+    }
+    let _ = writeln!(dump, "block {} {}", label, format_rect(layout_info));
+    for child in children {
+        write_fragment(&child.borrow(), 1, dump);
+    }
+}
+
+fn write_fragment(node: &BoxNode, depth: usize, dump: &mut String) {
+    let indent = "  ".repeat(depth);
+    match node {
+        BoxNode::InlineBox(b) => {
+            let _ = writeln!(
+                dump,
+                "{}inline {} {}",
+                indent,
+                get_label(&b.style_node.borrow()),
+                format_rect(&b.layout_info)
+            );
+            for child in &b.children {
+                write_fragment(&child.borrow(), depth + 1, dump);
+            }
+        }
+        BoxNode::ReplacedBox(b) => {
+            let _ = writeln!(
+                dump,
+                "{}replaced {} {}",
+                indent,
+                get_label(&b.style_node.borrow()),
+                format_rect(&b.layout_info)
+            );
+        }
+        BoxNode::Text(t) => {
+            let _ = writeln!(dump, "{}text {}", indent, format_rect(&t.layout_info));
+            for (i, line) in t.lines.iter().enumerate() {
+                let break_offset = line
+                    .break_offset
+                    .map(|offset| format!(" broken at byte {}", offset))
+                    .unwrap_or_default();
+                let _ = writeln!(
+                    dump,
+                    "{}  line {}{}: {:?} at ({:.2}, {:.2}) {:.2}x{:.2}",
+                    indent,
+                    i + 1,
+                    break_offset,
+                    line.text,
+                    line.x,
+                    line.y,
+                    line.width,
+                    line.height
+                );
+                for run in t.get_runs(&line.text) {
+                    let _ = writeln!(
+                        dump,
+                        "{}    run {:?} [{}] advance {:.2}",
+                        indent, run.text, run.font, run.advance
+                    );
+                }
+            }
+        }
+        // The atomic inline-level boxes are laid out as blocks inside, whose contents are nested in them.
+        BoxNode::BlockBox(b) => {
+            let _ = writeln!(
+                dump,
+                "{}{} {} {}",
+                indent,
+                if b.is_atomic_inline() {
+                    "atomic"
+                } else {
+                    "block"
+                },
+                get_label(&b.style_node.borrow()),
+                format_rect(&b.layout_info)
+            );
+            for child in &b.children {
+                write_fragment(&child.borrow(), depth + 1, dump);
+            }
+        }
+        BoxNode::AnonymousBox(b) => {
+            for child in &b.children {
+                write_fragment(&child.borrow(), depth, dump);
+            }
+        }
+    }
+}
+
+/// Returns the label of the element of the box like a compound selector, followed by the pseudo-element.
+fn get_label(style_node: &RenderNode) -> String {
+    let label = get_element_label(&style_node.dom_node);
+    match &style_node.pseudo_element {
+        Some(name) => format!("{}::{}", label, name),
+        None => label,
+    }
+}
+
+fn format_rect(layout_info: &LayoutInfo) -> String {
+    format!(
+        "({:.2}, {:.2}, {:.2}, {:.2})",
+        layout_info.pos.x, layout_info.pos.y, layout_info.size.width, layout_info.size.height
+    )
+}
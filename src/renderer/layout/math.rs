@@ -0,0 +1,717 @@
+//! The layout of MathML formulas, which are laid out by the algorithms of MathML rather than the CSS
+//! formatting model.
+//! https://w3c.github.io/mathml-core/#mathml-layout
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk4::pango;
+
+use crate::renderer::html::dom::{DomNode, Element, NodeType};
+use crate::renderer::style::property::{parse_length_px, LengthContext};
+use crate::renderer::svg::path::PathSegment;
+use crate::renderer::RenderObject;
+
+/// The scale factors of the font size at the script levels 1 and 2 or more, which are the default values of
+/// `scriptPercentScaleDown` and `scriptScriptPercentScaleDown` of the OpenType MATH table.
+/// https://w3c.github.io/mathml-core/#the-math-script-level-property
+const SCRIPT_SCALES: [f64; 2] = [0.71, 0.5];
+
+/// The laid out box of a formula or its subexpression. The positions of the items are relative to the
+/// start of its baseline, and the y-axis points downward.
+/// https://w3c.github.io/mathml-core/#box-model
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MathLayout {
+    pub width: f64,
+    /// The distance from the baseline to the top of the box.
+    pub ascent: f64,
+    /// The distance from the baseline to the bottom of the box.
+    pub descent: f64,
+    pub items: Vec<MathItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MathItem {
+    /// A text, where (x, y) is the top-left corner of its layout.
+    Glyphs {
+        text: String,
+        x: f64,
+        y: f64,
+        font_size: f64,
+    },
+    /// A bar of a fraction.
+    Rule {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+    /// A radical sign with the overbar, which is drawn as a polyline.
+    Radical {
+        points: Vec<(f64, f64)>,
+        thickness: f64,
+    },
+}
+
+impl MathLayout {
+    /// Returns the items moved by (dx, dy).
+    fn translate(self, dx: f64, dy: f64) -> Vec<MathItem> {
+        self.items
+            .into_iter()
+            .map(|item| match item {
+                MathItem::Glyphs {
+                    text,
+                    x,
+                    y,
+                    font_size,
+                } => MathItem::Glyphs {
+                    text,
+                    x: x + dx,
+                    y: y + dy,
+                    font_size,
+                },
+                MathItem::Rule {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => MathItem::Rule {
+                    x: x + dx,
+                    y: y + dy,
+                    width,
+                    height,
+                },
+                MathItem::Radical { points, thickness } => MathItem::Radical {
+                    points: points.into_iter().map(|(x, y)| (x + dx, y + dy)).collect(),
+                    thickness,
+                },
+            })
+            .collect()
+    }
+
+    /// Returns the render objects of the formula whose top-left corner is placed at (x, y).
+    pub fn to_render_objects(
+        &self,
+        x: f64,
+        y: f64,
+        font_family: &[String],
+        font_weight: &str,
+        color: (f64, f64, f64, f64),
+    ) -> Vec<RenderObject> {
+        let baseline = y + self.ascent;
+        self.items
+            .iter()
+            .map(|item| match item {
+                MathItem::Glyphs {
+                    text,
+                    x: item_x,
+                    y: item_y,
+                    font_size,
+                } => RenderObject::Text {
+                    text: text.clone(),
+                    x: x + item_x,
+                    y: baseline + item_y,
+                    font_family: font_family.to_vec(),
+                    font_size: *font_size,
+                    font_weight: font_weight.to_string(),
+                    font_style: String::new(),
+                    color,
+                },
+                MathItem::Rule {
+                    x: item_x,
+                    y: item_y,
+                    width,
+                    height,
+                } => RenderObject::Rect {
+                    x: x + item_x,
+                    y: baseline + item_y,
+                    width: *width,
+                    height: *height,
+                    color,
+                    border_radius: (0.0, 0.0, 0.0, 0.0),
+                },
+                MathItem::Radical { points, thickness } => RenderObject::Path {
+                    segments: points
+                        .iter()
+                        .enumerate()
+                        .map(|(i, (px, py))| {
+                            if i == 0 {
+                                PathSegment::MoveTo(x + px, baseline + py)
+                            } else {
+                                PathSegment::LineTo(x + px, baseline + py)
+                            }
+                        })
+                        .collect(),
+                    fill: None,
+                    even_odd: false,
+                    stroke: Some(color),
+                    stroke_width: *thickness,
+                    // The strokes may slightly overflow the box.
+                    clip: (
+                        x - thickness,
+                        y - thickness,
+                        self.width + thickness * 2.0,
+                        self.ascent + self.descent + thickness * 2.0,
+                    ),
+                },
+            })
+            .collect()
+    }
+}
+
+/// The layout constants of a font size, which correspond to the ones of the OpenType MATH table.
+/// todo: Read the MATH table of the font. Currently, the rule thickness and the math axis are taken from the
+/// font metrics, and the others are the fallback values proportional to the font size.
+/// https://w3c.github.io/mathml-core/#layout-constants-mathconstants
+#[derive(Debug, Clone, Copy)]
+struct MathConstants {
+    font_size: f64,
+    /// The height of the math axis above the baseline, on which the fraction bars are centered.
+    axis_height: f64,
+    /// The thickness of the fraction bars and the overbars of radicals.
+    rule_thickness: f64,
+}
+
+impl MathConstants {
+    fn em(&self, value: f64) -> f64 {
+        self.font_size * value
+    }
+}
+
+/// The style of a subexpression, which corresponds to the `math-depth` and `math-style` properties.
+/// https://w3c.github.io/mathml-core/#css-extensions-for-math-layout
+#[derive(Debug, Clone, Copy)]
+struct MathStyle {
+    script_level: usize,
+    /// Whether the formula is laid out in the display style, where fractions are not shrunk.
+    display: bool,
+}
+
+impl MathStyle {
+    /// The style of the scripts, which are always shrunk.
+    fn script(self) -> Self {
+        Self {
+            script_level: self.script_level + 1,
+            display: false,
+        }
+    }
+
+    /// The style of the numerators and denominators.
+    /// https://w3c.github.io/mathml-core/#user-agent-stylesheet
+    fn fraction(self) -> Self {
+        Self {
+            script_level: self.script_level + usize::from(!self.display),
+            display: false,
+        }
+    }
+}
+
+/// Lays out the MathML formulas measuring the texts with the font of the `math` element.
+pub struct MathLayouter<'a> {
+    draw_ctx: &'a pango::Context,
+    font_family: Vec<String>,
+    font_weight: String,
+    font_size: f64,
+}
+
+impl<'a> MathLayouter<'a> {
+    pub fn new(
+        draw_ctx: &'a pango::Context,
+        font_family: Vec<String>,
+        font_weight: String,
+        font_size: f64,
+    ) -> Self {
+        Self {
+            draw_ctx,
+            font_family,
+            font_weight,
+            font_size,
+        }
+    }
+
+    /// Lays out the `math` element, whose children are laid out like an `mrow` element.
+    /// https://w3c.github.io/mathml-core/#the-top-level-math-element
+    pub fn layout(&self, math: &Rc<RefCell<DomNode>>, display: bool) -> MathLayout {
+        self.layout_row(
+            &get_children(math),
+            MathStyle {
+                script_level: 0,
+                display,
+            },
+        )
+    }
+
+    fn get_font_size(&self, style: MathStyle) -> f64 {
+        match style.script_level {
+            0 => self.font_size,
+            level => self.font_size * SCRIPT_SCALES[level.min(SCRIPT_SCALES.len()) - 1],
+        }
+    }
+
+    fn get_font_desc(&self, font_size: f64) -> pango::FontDescription {
+        pango::FontDescription::from_string(&format!(
+            "{} {} {}px",
+            self.font_family.join(", "),
+            self.font_weight,
+            font_size
+        ))
+    }
+
+    fn get_constants(&self, style: MathStyle) -> MathConstants {
+        let font_size = self.get_font_size(style);
+        let metrics = self
+            .draw_ctx
+            .metrics(Some(&self.get_font_desc(font_size)), None);
+        let thickness = metrics.underline_thickness() as f64 / pango::SCALE as f64;
+        // The strikethrough is placed around the middle of the lowercase letters, where the math axis is.
+        let strikethrough = metrics.strikethrough_position() as f64 / pango::SCALE as f64;
+        MathConstants {
+            font_size,
+            axis_height: if strikethrough > 0.0 {
+                strikethrough
+            } else {
+                font_size * 0.25
+            },
+            rule_thickness: if thickness > 0.0 {
+                thickness
+            } else {
+                font_size / 18.0
+            },
+        }
+    }
+
+    fn layout_node(&self, node: &Rc<RefCell<DomNode>>, style: MathStyle) -> Option<MathLayout> {
+        let elm = match &node.borrow().node_type {
+            NodeType::Element(elm) => elm.clone(),
+            // The texts outside the token elements are laid out like the `mtext` elements.
+            NodeType::Text(text) if !text.trim().is_empty() => {
+                return Some(self.layout_token(text, style, 0.0, 0.0));
+            }
+            _ => return None,
+        };
+        let children = get_children(node);
+        let layout = match (elm.tag_name.as_str(), &children[..]) {
+            // https://w3c.github.io/mathml-core/#the-mi-element
+            ("mi", _) => {
+                let text = get_text_content(node);
+                if text.chars().count() == 1 && elm.get_attribute("mathvariant") != Some("normal") {
+                    self.layout_token(&to_math_italic(&text), style, 0.0, 0.0)
+                } else {
+                    self.layout_token(&text, style, 0.0, 0.0)
+                }
+            }
+            // The operators are spaced by the rows which contain them.
+            ("mn" | "mo" | "mtext" | "ms", _) => {
+                self.layout_token(&get_text_content(node), style, 0.0, 0.0)
+            }
+            // https://w3c.github.io/mathml-core/#space-mspace
+            ("mspace", _) => MathLayout {
+                width: elm
+                    .get_attribute("width")
+                    .and_then(|v| parse_length(v, self.get_font_size(style)))
+                    .unwrap_or(0.0),
+                ..Default::default()
+            },
+            ("mfrac", [numerator, denominator]) => {
+                self.layout_fraction(&elm, numerator, denominator, style)
+            }
+            ("msub", [base, subscript]) => self.layout_scripts(base, Some(subscript), None, style),
+            ("msup", [base, superscript]) => {
+                self.layout_scripts(base, None, Some(superscript), style)
+            }
+            ("msubsup", [base, subscript, superscript]) => {
+                self.layout_scripts(base, Some(subscript), Some(superscript), style)
+            }
+            ("msqrt", _) => self.layout_sqrt(&children, style),
+            // Only the presentation MathML in the first child is rendered.
+            // https://w3c.github.io/mathml-core/#semantics-and-presentation
+            ("semantics", [first, ..]) => self.layout_node(first, style)?,
+            ("annotation" | "annotation-xml", _) => return None,
+            // https://w3c.github.io/mathml-core/#making-sub-expressions-invisible-mphantom
+            ("mphantom", _) => MathLayout {
+                items: Vec::new(),
+                ..self.layout_row(&children, style)
+            },
+            // The other elements, including the ones with the invalid number of children, are laid out
+            // like the `mrow` elements.
+            // todo: Render the invalid elements like the `merror` elements.
+            // https://w3c.github.io/mathml-core/#horizontally-group-sub-expressions-mrow
+            _ => self.layout_row(&children, style),
+        };
+        Some(layout)
+    }
+
+    /// Lays out the text of a token element on the baseline, with the spaces on the both sides.
+    /// https://w3c.github.io/mathml-core/#token-elements
+    fn layout_token(&self, text: &str, style: MathStyle, lspace: f64, rspace: f64) -> MathLayout {
+        let font_size = self.get_font_size(style);
+        let layout = pango::Layout::new(self.draw_ctx);
+        layout.set_font_description(Some(&self.get_font_desc(font_size)));
+        layout.set_text(text);
+        let (width, height) = layout.size();
+        let ascent = layout.baseline() as f64 / pango::SCALE as f64;
+        MathLayout {
+            width: lspace + width as f64 / pango::SCALE as f64 + rspace,
+            ascent,
+            descent: height as f64 / pango::SCALE as f64 - ascent,
+            items: vec![MathItem::Glyphs {
+                text: text.to_string(),
+                x: lspace,
+                y: -ascent,
+                font_size,
+            }],
+        }
+    }
+
+    /// Lays out the children, which are the elements and the non-whitespace texts, horizontally on the same
+    /// baseline.
+    /// https://w3c.github.io/mathml-core/#layout-of-mrow
+    fn layout_row(&self, children: &[Rc<RefCell<DomNode>>], style: MathStyle) -> MathLayout {
+        let mut row = MathLayout::default();
+        for (i, child) in children.iter().enumerate() {
+            let is_infix = i > 0 && i + 1 < children.len();
+            let layout = match get_operator(child) {
+                Some(operator) => {
+                    let (lspace, rspace) = get_operator_spacing(&operator, is_infix, style);
+                    let em = self.get_font_size(style);
+                    self.layout_token(&operator, style, lspace * em, rspace * em)
+                }
+                None => match self.layout_node(child, style) {
+                    Some(layout) => layout,
+                    None => continue,
+                },
+            };
+            row.ascent = row.ascent.max(layout.ascent);
+            row.descent = row.descent.max(layout.descent);
+            let x = row.width;
+            row.width += layout.width;
+            row.items.extend(layout.translate(x, 0.0));
+        }
+        row
+    }
+
+    /// Lays out the numerator and the denominator above and below the fraction bar on the math axis.
+    /// https://w3c.github.io/mathml-core/#fractions-mfrac
+    fn layout_fraction(
+        &self,
+        elm: &Element,
+        numerator: &Rc<RefCell<DomNode>>,
+        denominator: &Rc<RefCell<DomNode>>,
+        style: MathStyle,
+    ) -> MathLayout {
+        let c = self.get_constants(style);
+        let numerator = self
+            .layout_node(numerator, style.fraction())
+            .unwrap_or_default();
+        let denominator = self
+            .layout_node(denominator, style.fraction())
+            .unwrap_or_default();
+        let thickness = match elm.get_attribute("linethickness") {
+            Some(value) => parse_length(value, c.font_size).unwrap_or(c.rule_thickness),
+            None => c.rule_thickness,
+        };
+        let gap = if style.display {
+            c.rule_thickness * 3.0
+        } else {
+            c.rule_thickness
+        };
+        let numerator_shift = c.axis_height + thickness / 2.0 + gap + numerator.descent;
+        let denominator_shift = denominator.ascent + gap + thickness / 2.0 - c.axis_height;
+        // The bar is slightly wider than the numerator and the denominator.
+        let padding = c.rule_thickness;
+        let width = numerator.width.max(denominator.width) + padding * 2.0;
+
+        let mut items = Vec::new();
+        let (numerator_width, denominator_width) = (numerator.width, denominator.width);
+        let (ascent, descent) = (
+            numerator_shift + numerator.ascent,
+            denominator_shift + denominator.descent,
+        );
+        items.extend(numerator.translate((width - numerator_width) / 2.0, -numerator_shift));
+        items.extend(denominator.translate((width - denominator_width) / 2.0, denominator_shift));
+        if thickness > 0.0 {
+            items.push(MathItem::Rule {
+                x: 0.0,
+                y: -(c.axis_height + thickness / 2.0),
+                width,
+                height: thickness,
+            });
+        }
+        MathLayout {
+            width,
+            ascent,
+            descent,
+            items,
+        }
+    }
+
+    /// Lays out the base with the subscript shifted down and the superscript shifted up after it.
+    /// https://w3c.github.io/mathml-core/#subscripts-and-superscripts-msub-msup-msubsup
+    fn layout_scripts(
+        &self,
+        base: &Rc<RefCell<DomNode>>,
+        subscript: Option<&Rc<RefCell<DomNode>>>,
+        superscript: Option<&Rc<RefCell<DomNode>>>,
+        style: MathStyle,
+    ) -> MathLayout {
+        let c = self.get_constants(style);
+        let base = self.layout_node(base, style).unwrap_or_default();
+        let subscript =
+            subscript.map(|node| self.layout_node(node, style.script()).unwrap_or_default());
+        let superscript =
+            superscript.map(|node| self.layout_node(node, style.script()).unwrap_or_default());
+
+        let mut subscript_shift = subscript.as_ref().map_or(0.0, |sub| {
+            c.em(0.15)
+                .max(base.descent + c.em(0.05))
+                .max(sub.ascent - c.em(0.35))
+        });
+        let superscript_shift = superscript.as_ref().map_or(0.0, |sup| {
+            c.em(0.4)
+                .max(base.ascent - c.em(0.25))
+                .max(sup.descent + c.em(0.1))
+        });
+        // The subscript is shifted down further if it is too close to the superscript.
+        if let (Some(sub), Some(sup)) = (&subscript, &superscript) {
+            let gap = (subscript_shift - sub.ascent) + (superscript_shift - sup.descent);
+            let min_gap = c.rule_thickness * 4.0;
+            if gap < min_gap {
+                subscript_shift += min_gap - gap;
+            }
+        }
+
+        let mut layout = MathLayout {
+            width: base.width,
+            ascent: base.ascent,
+            descent: base.descent,
+            items: Vec::new(),
+        };
+        let mut script_width: f64 = 0.0;
+        if let Some(sub) = subscript {
+            layout.descent = layout.descent.max(subscript_shift + sub.descent);
+            script_width = script_width.max(sub.width);
+            layout
+                .items
+                .extend(sub.translate(base.width, subscript_shift));
+        }
+        if let Some(sup) = superscript {
+            layout.ascent = layout.ascent.max(superscript_shift + sup.ascent);
+            script_width = script_width.max(sup.width);
+            layout
+                .items
+                .extend(sup.translate(base.width, -superscript_shift));
+        }
+        // The space after the scripts is the default value of `spaceAfterScript`.
+        layout.width += script_width + c.em(0.05);
+        layout.items.extend(base.translate(0.0, 0.0));
+        layout
+    }
+
+    /// Lays out the children under the radical sign with the overbar.
+    /// https://w3c.github.io/mathml-core/#radicals-msqrt-mroot
+    fn layout_sqrt(&self, children: &[Rc<RefCell<DomNode>>], style: MathStyle) -> MathLayout {
+        let c = self.get_constants(style);
+        let base = self.layout_row(children, style);
+        let thickness = c.rule_thickness;
+        let gap = if style.display {
+            thickness + c.axis_height / 2.0
+        } else {
+            thickness * 1.25
+        };
+        let sign_width = c.em(0.6);
+        let width = sign_width + base.width + thickness;
+        // The overbar is centered on the top of the radical sign.
+        let top = -(base.ascent + gap + thickness / 2.0);
+        let bottom = base.descent + thickness;
+        let middle = bottom - (bottom - top) * 0.45;
+        let points = vec![
+            (0.0, middle + c.em(0.05)),
+            (sign_width * 0.25, middle - c.em(0.03)),
+            (sign_width * 0.5, bottom),
+            (sign_width, top),
+            (width, top),
+        ];
+        let ascent = -top + thickness / 2.0 + thickness;
+        let descent = bottom + thickness / 2.0;
+        let mut items = vec![MathItem::Radical { points, thickness }];
+        items.extend(base.translate(sign_width, 0.0));
+        MathLayout {
+            width,
+            ascent,
+            descent,
+            items,
+        }
+    }
+}
+
+fn get_children(node: &Rc<RefCell<DomNode>>) -> Vec<Rc<RefCell<DomNode>>> {
+    node.borrow()
+        .children
+        .iter()
+        .filter(|child| match &child.borrow().node_type {
+            NodeType::Element(_) => true,
+            NodeType::Text(text) => !text.trim().is_empty(),
+            _ => false,
+        })
+        .cloned()
+        .collect()
+}
+
+/// Returns the text of the descendants, whose whitespace is collapsed and trimmed.
+/// https://w3c.github.io/mathml-core/#token-elements
+fn get_text_content(node: &Rc<RefCell<DomNode>>) -> String {
+    fn collect(node: &Rc<RefCell<DomNode>>, text: &mut String) {
+        match &node.borrow().node_type {
+            NodeType::Text(t) => text.push_str(t),
+            _ => node
+                .borrow()
+                .children
+                .iter()
+                .for_each(|child| collect(child, text)),
+        }
+    }
+    let mut text = String::new();
+    collect(node, &mut text);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Returns the text of the node if it is an `mo` element. The hyphen-minus is replaced with the minus sign.
+fn get_operator(node: &Rc<RefCell<DomNode>>) -> Option<String> {
+    match &node.borrow().node_type {
+        NodeType::Element(elm) if elm.tag_name == "mo" => {}
+        _ => return None,
+    }
+    Some(get_text_content(node).replace('-', "\u{2212}"))
+}
+
+/// Returns the spaces (lspace, rspace) in em around the operator. The spaces around the binary operators and
+/// relations are removed if they are not in the infix form or in the scripts, like TeX.
+/// todo: Use the whole operator dictionary and the `form`, `lspace` and `rspace` attributes.
+/// https://w3c.github.io/mathml-core/#operator-dictionary
+fn get_operator_spacing(operator: &str, is_infix: bool, style: MathStyle) -> (f64, f64) {
+    let is_spaced = is_infix && style.script_level == 0;
+    match operator {
+        "=" | "<" | ">" | "\u{2264}" | "\u{2265}" | "\u{2260}" | "\u{2248}" | "\u{2261}"
+        | "\u{2192}" | "\u{2190}" | "\u{21D2}" | "\u{2208}" | "\u{2209}" | "\u{2282}"
+        | "\u{2286}" | "\u{223C}" | "\u{221D}" | ":="
+            if is_spaced =>
+        {
+            (5.0 / 18.0, 5.0 / 18.0)
+        }
+        "+" | "\u{2212}" | "\u{00B1}" | "\u{2213}" | "\u{00D7}" | "\u{00F7}" | "\u{00B7}"
+        | "\u{22C5}" | "\u{2217}" | "\u{2218}" | "\u{222A}" | "\u{2229}" | "\u{2295}"
+        | "\u{2297}"
+            if is_spaced =>
+        {
+            (4.0 / 18.0, 4.0 / 18.0)
+        }
+        "," | ";" => (0.0, 3.0 / 18.0),
+        _ => (0.0, 0.0),
+    }
+}
+
+/// Maps the Latin and Greek letters to the mathematical italic ones, so that the single-letter identifiers
+/// are rendered in italic.
+/// https://w3c.github.io/mathml-core/#italic-mappings
+fn to_math_italic(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            let mapped = match c {
+                // The italic small h is in the Letterlike Symbols block.
+                'h' => Some(0x210E),
+                'a'..='z' => Some(0x1D44E + c as u32 - 'a' as u32),
+                'A'..='Z' => Some(0x1D434 + c as u32 - 'A' as u32),
+                '\u{03B1}'..='\u{03C9}' => Some(0x1D6FC + c as u32 - 0x03B1),
+                '\u{0391}'..='\u{03A9}' => Some(0x1D6E2 + c as u32 - 0x0391),
+                _ => None,
+            };
+            mapped.and_then(char::from_u32).unwrap_or(c)
+        })
+        .collect()
+}
+
+/// Parses the length of an attribute, where the font-relative units are relative to the font size.
+fn parse_length(value: &str, font_size: f64) -> Option<f64> {
+    let context = LengthContext {
+        font_size: font_size as f32,
+        ..Default::default()
+    };
+    parse_length_px(value, &context).map(f64::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_identifiers_and_operators() {
+        assert_eq!(to_math_italic("x"), "\u{1D465}");
+        assert_eq!(to_math_italic("h"), "\u{210E}");
+        assert_eq!(to_math_italic("\u{03B1}"), "\u{1D6FC}");
+        assert_eq!(to_math_italic("1"), "1");
+
+        let style = MathStyle {
+            script_level: 0,
+            display: false,
+        };
+        assert_eq!(
+            get_operator_spacing("=", true, style),
+            (5.0 / 18.0, 5.0 / 18.0)
+        );
+        // The unary minus and the operators in the scripts are not spaced.
+        assert_eq!(get_operator_spacing("\u{2212}", false, style), (0.0, 0.0));
+        assert_eq!(get_operator_spacing("+", true, style.script()), (0.0, 0.0));
+        assert_eq!(get_operator_spacing(",", false, style), (0.0, 3.0 / 18.0));
+    }
+
+    #[test]
+    fn render_formula() {
+        let glyphs = MathLayout {
+            width: 8.0,
+            ascent: 12.0,
+            descent: 4.0,
+            items: vec![MathItem::Glyphs {
+                text: "1".to_string(),
+                x: 0.0,
+                y: -12.0,
+                font_size: 11.36,
+            }],
+        };
+        // The fraction 1/1 whose bar is on the axis 4px above the baseline.
+        let layout = MathLayout {
+            width: 10.0,
+            ascent: 30.0,
+            descent: 20.0,
+            items: [
+                glyphs.clone().translate(1.0, -18.0),
+                glyphs.translate(1.0, 16.0),
+                vec![MathItem::Rule {
+                    x: 0.0,
+                    y: -4.5,
+                    width: 10.0,
+                    height: 1.0,
+                }],
+            ]
+            .concat(),
+        };
+        let objects = layout.to_render_objects(
+            100.0,
+            50.0,
+            &["serif".to_string()],
+            "normal",
+            (0.0, 0.0, 0.0, 1.0),
+        );
+        let RenderObject::Text { x, y, .. } = &objects[0] else {
+            panic!();
+        };
+        assert_eq!((*x, *y), (101.0, 50.0));
+        let RenderObject::Text { y, .. } = &objects[1] else {
+            panic!();
+        };
+        assert_eq!(*y, 84.0);
+        let RenderObject::Rect { y, width, .. } = &objects[2] else {
+            panic!();
+        };
+        assert_eq!((*y, *width), (75.5, 10.0));
+    }
+}
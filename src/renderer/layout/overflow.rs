@@ -0,0 +1,188 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::renderer::html::dom::DomNode;
+use crate::renderer::layout::box_model::BoxNode;
+use crate::renderer::layout::geometry::Rect;
+use crate::renderer::layout::positioned::get_border_box;
+
+/// A scroll container laid out in the page, whose contents are moved by its scroll position.
+/// https://drafts.csswg.org/css-overflow/#scroll-container
+#[derive(Debug, Clone)]
+pub struct ScrollBox {
+    pub node: Rc<RefCell<DomNode>>,
+    /// The padding box of the box, which is the viewport of its contents.
+    pub scrollport: Rect,
+    /// The part of the scrollport which is not clipped by the ancestors, where the box can be scrolled.
+    pub visible_scrollport: Option<Rect>,
+    /// The scroll position (x, y) by which the contents are moved.
+    pub offset: (f32, f32),
+    /// The largest scroll position (x, y), where the scrollport reaches the end of the scrollable overflow
+    /// area.
+    pub max_offset: (f32, f32),
+    /// Whether the user can scroll the box on the (x, y) axes, i.e. it's not `overflow: hidden` on it.
+    pub is_user_scrollable: (bool, bool),
+}
+
+impl ScrollBox {
+    /// Returns the scroll position after scrolling by (dx, dy) on the axes which the user can scroll,
+    /// which is kept within the scrollable overflow area.
+    pub fn get_scrolled_offset(&self, dx: f32, dy: f32) -> (f32, f32) {
+        let scroll = |offset: f32, delta: f32, max: f32, is_scrollable: bool| {
+            if is_scrollable {
+                (offset + delta).clamp(0.0, max)
+            } else {
+                offset
+            }
+        };
+        (
+            scroll(
+                self.offset.0,
+                dx,
+                self.max_offset.0,
+                self.is_user_scrollable.0,
+            ),
+            scroll(
+                self.offset.1,
+                dy,
+                self.max_offset.1,
+                self.is_user_scrollable.1,
+            ),
+        )
+    }
+}
+
+/// Returns whether the box clips its contents to its padding box, i.e. its `overflow` is not `visible`.
+pub fn clips_overflow(node: &BoxNode) -> bool {
+    node.get_element_style_node()
+        .is_some_and(|n| n.borrow().style.overflow.clips())
+}
+
+/// Returns the padding box of the laid out box, to which its contents are clipped.
+pub fn get_padding_box(node: &BoxNode) -> Rect {
+    let border = &node.get_layout_info().used_values.border;
+    get_border_box(node).expand((
+        -border.top as f64,
+        -border.right as f64,
+        -border.bottom as f64,
+        -border.left as f64,
+    ))
+}
+
+/// Returns the area to which the contents of the box are clipped, where `clip` is the area to which the box
+/// itself is clipped by its ancestors. The contents of the box which is entirely clipped are clipped to an
+/// empty area.
+pub fn get_contents_clip(node: &BoxNode, clip: Option<Rect>) -> Option<Rect> {
+    if !clips_overflow(node) {
+        return clip;
+    }
+    let padding_box = get_padding_box(node);
+    Some(
+        clip.map_or(Some(padding_box), |clip| clip.intersect(&padding_box))
+            .unwrap_or(Rect::new(padding_box.x, padding_box.y, 0.0, 0.0)),
+    )
+}
+
+/// Returns the scrollable overflow area of the box, which covers its padding box and the border boxes of
+/// its descendants. The descendants of the boxes which clip their contents are not included.
+/// todo: Exclude the positioned boxes whose containing blocks are outside the box.
+/// https://drafts.csswg.org/css-overflow/#scrollable
+pub fn get_scrollable_overflow(node: &BoxNode) -> Rect {
+    fn helper(node: &BoxNode, area: &mut Rect) {
+        for child in node.get_children() {
+            let child = child.borrow();
+            *area = area.union(&get_border_box(&child));
+            if !clips_overflow(&child) {
+                helper(&child, area);
+            }
+        }
+    }
+
+    let mut area = get_padding_box(node);
+    helper(node, &mut area);
+    area
+}
+
+/// Moves the contents of the scroll containers by their scroll positions, which are kept within their
+/// scrollable overflow areas, and returns the scroll containers in tree order. The outer scroll containers
+/// are scrolled before the ones inside them, so the scrollports of the inner ones are where they are seen.
+/// https://drafts.csswg.org/cssom-view/#scrolling
+pub fn scroll_boxes(root: &Rc<RefCell<BoxNode>>) -> Vec<ScrollBox> {
+    fn helper(node: &Rc<RefCell<BoxNode>>, clip: Option<Rect>, scroll_boxes: &mut Vec<ScrollBox>) {
+        let mut clip = clip;
+        if let Some(style_node) = node.borrow().get_element_style_node() {
+            let style_node = style_node.borrow();
+            let overflow = &style_node.style.overflow;
+            if overflow.clips() {
+                let scrollport = get_padding_box(&node.borrow());
+                let visible_scrollport = match clip {
+                    Some(clip) => scrollport.intersect(&clip),
+                    None => Some(scrollport),
+                };
+                if overflow.is_scroll_container() {
+                    let area = get_scrollable_overflow(&node.borrow());
+                    let max_offset = (
+                        (area.x + area.width - scrollport.x - scrollport.width).max(0.0) as f32,
+                        (area.y + area.height - scrollport.y - scrollport.height).max(0.0) as f32,
+                    );
+                    let (x, y) = style_node.dom_node.borrow().scroll_offset;
+                    let offset = (x.clamp(0.0, max_offset.0), y.clamp(0.0, max_offset.1));
+                    for child in node.borrow().get_children() {
+                        child.borrow_mut().translate(-offset.0, -offset.1);
+                    }
+                    scroll_boxes.push(ScrollBox {
+                        node: Rc::clone(&style_node.dom_node),
+                        scrollport,
+                        visible_scrollport,
+                        offset,
+                        max_offset,
+                        is_user_scrollable: (
+                            overflow.is_user_scrollable(true),
+                            overflow.is_user_scrollable(false),
+                        ),
+                    });
+                }
+                // The contents of the box which is entirely clipped are not seen at all.
+                clip = Some(visible_scrollport.unwrap_or(Rect::new(
+                    scrollport.x,
+                    scrollport.y,
+                    0.0,
+                    0.0,
+                )));
+            }
+        }
+        for child in node.borrow().get_children() {
+            helper(child, clip, scroll_boxes);
+        }
+    }
+
+    let mut scroll_boxes = Vec::new();
+    helper(root, None, &mut scroll_boxes);
+    scroll_boxes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scroll_within_overflow() {
+        let scroll_box = ScrollBox {
+            node: Rc::new(RefCell::new(DomNode::default())),
+            scrollport: Rect::new(0.0, 0.0, 100.0, 100.0),
+            visible_scrollport: Some(Rect::new(0.0, 0.0, 100.0, 100.0)),
+            offset: (0.0, 30.0),
+            max_offset: (0.0, 50.0),
+            is_user_scrollable: (true, true),
+        };
+        assert_eq!(scroll_box.get_scrolled_offset(10.0, 10.0), (0.0, 40.0));
+        assert_eq!(scroll_box.get_scrolled_offset(0.0, 40.0), (0.0, 50.0));
+        assert_eq!(scroll_box.get_scrolled_offset(0.0, -40.0), (0.0, 0.0));
+
+        let hidden = ScrollBox {
+            is_user_scrollable: (false, false),
+            ..scroll_box
+        };
+        assert_eq!(hidden.get_scrolled_offset(0.0, 10.0), (0.0, 30.0));
+    }
+}
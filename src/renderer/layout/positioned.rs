@@ -0,0 +1,384 @@
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::renderer::layout::box_model::{BoxNode, BoxPosition, BoxSize, LayoutInfo};
+use crate::renderer::layout::geometry::Rect;
+use crate::renderer::style::property::CssValue;
+use crate::renderer::style::style_model::ComputedStyle;
+
+/// A range of the render objects painted by a box which moves differently from the rest of the page when
+/// the viewport is scrolled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScrollLayer {
+    pub range: Range<usize>,
+    pub kind: ScrollLayerKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScrollLayerKind {
+    /// A fixed positioned box, which is laid out in the viewport at the origin of the canvas and stays
+    /// there while the page is scrolled.
+    Fixed,
+    /// A sticky positioned box, which is shifted to keep `insets` (top, right, bottom, left) from the edges
+    /// of the viewport while its border box `rect` stays within `bounds`, the content box of its parent.
+    /// https://drafts.csswg.org/css-position/#stickypos-insets
+    Sticky {
+        rect: Rect,
+        bounds: Rect,
+        insets: [Option<f64>; 4],
+    },
+}
+
+impl ScrollLayer {
+    /// Returns the offset by which the objects of the layer are moved when `viewport` (x, y, width, height)
+    /// is the visible area of the canvas.
+    pub fn get_offset(&self, viewport: (f64, f64, f64, f64)) -> (f64, f64) {
+        let (vx, vy, vw, vh) = viewport;
+        match &self.kind {
+            ScrollLayerKind::Fixed => (vx, vy),
+            ScrollLayerKind::Sticky {
+                rect,
+                bounds,
+                insets: [top, right, bottom, left],
+            } => (
+                get_sticky_offset(
+                    (rect.x, rect.width),
+                    (bounds.x, bounds.width),
+                    (vx, vw),
+                    (*left, *right),
+                ),
+                get_sticky_offset(
+                    (rect.y, rect.height),
+                    (bounds.y, bounds.height),
+                    (vy, vh),
+                    (*top, *bottom),
+                ),
+            ),
+        }
+    }
+}
+
+/// Returns the offset of the sticky positioned box on an axis, which keeps the box at the start inset from
+/// the start edge of the viewport, or else at the end inset from the end edge, without moving it out of its
+/// bounds. Each range is given as (start, size).
+fn get_sticky_offset(
+    (start, size): (f64, f64),
+    (bounds_start, bounds_size): (f64, f64),
+    (viewport_start, viewport_size): (f64, f64),
+    (inset_start, inset_end): (Option<f64>, Option<f64>),
+) -> f64 {
+    if let Some(inset) = inset_start {
+        let offset = viewport_start + inset - start;
+        if offset > 0.0 {
+            return offset
+                .min(bounds_start + bounds_size - start - size)
+                .max(0.0);
+        }
+    }
+    if let Some(inset) = inset_end {
+        let offset = viewport_start + viewport_size - inset - (start + size);
+        if offset < 0.0 {
+            return offset.max(bounds_start - start).min(0.0);
+        }
+    }
+    0.0
+}
+
+/// Returns whether the box is absolutely positioned, in which case it's skipped while its siblings are laid
+/// out and laid out later by `layout_positioned_boxes()`.
+pub fn is_out_of_flow(node: &BoxNode) -> bool {
+    node.get_element_style_node()
+        .is_some_and(|n| n.borrow().style.position.is_absolutely_positioned())
+}
+
+/// Records the static position of the out-of-flow box, which is where its margin box would be placed if
+/// it were in flow: below the previous sibling in a block formatting context, after it in an inline
+/// formatting context, or else at the start of the content box of the parent. It's placed there if its
+/// insets are `auto`.
+/// https://drafts.csswg.org/css-position/#staticpos-rect
+pub fn set_static_position(
+    node: &Rc<RefCell<BoxNode>>,
+    parent: &LayoutInfo,
+    prev_sibling: Option<&LayoutInfo>,
+    is_inline: bool,
+) {
+    let content_x = parent.pos.x + parent.used_values.padding.left;
+    let content_y = parent.pos.y + parent.used_values.padding.top;
+    let pos = match prev_sibling {
+        Some(info) if is_inline => BoxPosition {
+            x: info.get_expanded_pos().x + info.get_expanded_size().width,
+            y: info.get_expanded_pos().y,
+        },
+        Some(info) => BoxPosition {
+            x: content_x,
+            y: info.get_expanded_pos().y + info.get_expanded_size().height,
+        },
+        None => BoxPosition {
+            x: content_x,
+            y: content_y,
+        },
+    };
+    node.borrow_mut().get_layout_info_mut().pos = pos;
+}
+
+/// Applies the offsets of the relatively positioned boxes, and lays out the absolutely positioned boxes
+/// in their containing blocks, which are the padding boxes of their nearest positioned ancestors, or the
+/// viewport (the initial containing block for `absolute`) for the `fixed` ones and the others. The boxes
+/// are visited in tree order, so the boxes inside them are moved with them.
+/// https://drafts.csswg.org/css-position/#positioning-scheme
+pub fn layout_positioned_boxes(root: &Rc<RefCell<BoxNode>>, viewport: Rect) {
+    fn helper(node: &Rc<RefCell<BoxNode>>, parent_content: Rect, abs_cb: Rect, viewport: Rect) {
+        // The anonymous boxes and the texts are not positioned.
+        let Some(style_node) = node.borrow().get_element_style_node() else {
+            for child in node.borrow().get_children() {
+                helper(child, parent_content, abs_cb, viewport);
+            }
+            return;
+        };
+        let style = &style_node.borrow().style;
+        let position = &style.position;
+        if position.is_absolutely_positioned() {
+            let cb = if position.is_fixed() {
+                viewport
+            } else {
+                abs_cb
+            };
+            layout_absolute(node, style, cb);
+        } else if position.is_relative() {
+            // https://drafts.csswg.org/css-position/#relpos-insets
+            let [top, right, bottom, left] = style
+                .inset
+                .to_px(parent_content.width as f32, parent_content.height as f32);
+            let dx = left.or(right.map(|right| -right)).unwrap_or(0.0);
+            let dy = top.or(bottom.map(|bottom| -bottom)).unwrap_or(0.0);
+            node.borrow_mut().translate(dx, dy);
+        }
+
+        let node = node.borrow();
+        let layout_info = node.get_layout_info();
+        let content = get_content_box(&node);
+        let abs_cb = if position.is_positioned() {
+            content.expand((
+                layout_info.used_values.padding.top as f64,
+                layout_info.used_values.padding.right as f64,
+                layout_info.used_values.padding.bottom as f64,
+                layout_info.used_values.padding.left as f64,
+            ))
+        } else {
+            abs_cb
+        };
+        for child in node.get_children() {
+            helper(child, content, abs_cb, viewport);
+        }
+    }
+
+    helper(root, viewport, viewport, viewport);
+}
+
+/// Lays out the absolutely positioned box in its containing block `cb`. The width is the one between the
+/// left and right insets if both are set, or else shrinks to fit the contents. The box is laid out at the
+/// start of the containing block, and then moved to its insets or its static position.
+/// https://drafts.csswg.org/css-position/#abspos-layout
+fn layout_absolute(node: &Rc<RefCell<BoxNode>>, style: &ComputedStyle, cb: Rect) {
+    let static_pos = node.borrow().get_layout_info().pos;
+    let [top, right, bottom, left] = style.inset.to_px(cb.width as f32, cb.height as f32);
+    let (x, width) = match (left, right) {
+        (Some(left), Some(right)) => (cb.x as f32 + left, cb.width as f32 - left - right),
+        _ => (cb.x as f32, cb.width as f32),
+    };
+    node.borrow_mut().layout(
+        &LayoutInfo {
+            size: BoxSize {
+                width: width.max(0.0),
+                height: cb.height as f32,
+            },
+            pos: BoxPosition { x, y: cb.y as f32 },
+            ..Default::default()
+        },
+        None,
+        None,
+    );
+
+    let is_width_auto = style.width.size == CssValue::Ident("auto".to_string());
+    if let BoxNode::BlockBox(block) = &mut *node.borrow_mut() {
+        // The flow layout gives the leftover width to the margins, while the ones of the absolutely
+        // positioned box are only the specified ones, where `auto` is zero.
+        let resolve = |margin: &CssValue| match margin {
+            CssValue::Ident(_) => 0.0,
+            _ => margin.resolve_px(cb.width as f32).unwrap_or(0.0),
+        };
+        block.layout_info.used_values.margin.left = resolve(&style.margin.left);
+        block.layout_info.used_values.margin.right = resolve(&style.margin.right);
+        if is_width_auto && (left.is_none() || right.is_none()) {
+            block.shrink_to_fit(cb.width as f32);
+        }
+        // The `auto` height is the one between the top and bottom insets if both are set.
+        if let (Some(top), Some(bottom), CssValue::Ident(_)) = (top, bottom, &style.height.size) {
+            let margin = &block.layout_info.used_values.margin;
+            block.layout_info.size.height =
+                (cb.height as f32 - top - bottom - margin.top - margin.bottom).max(0.0);
+        }
+    }
+
+    let margin_box = {
+        let node = node.borrow();
+        let margin = &node.get_layout_info().used_values.margin;
+        get_border_box(&node).expand((
+            margin.top as f64,
+            margin.right as f64,
+            margin.bottom as f64,
+            margin.left as f64,
+        ))
+    };
+    let target_x = match (left, right) {
+        (Some(left), _) => cb.x + left as f64,
+        (None, Some(right)) => cb.x + cb.width - right as f64 - margin_box.width,
+        (None, None) => static_pos.x as f64,
+    };
+    let target_y = match (top, bottom) {
+        (Some(top), _) => cb.y + top as f64,
+        (None, Some(bottom)) => cb.y + cb.height - bottom as f64 - margin_box.height,
+        (None, None) => static_pos.y as f64,
+    };
+    node.borrow_mut().translate(
+        (target_x - margin_box.x) as f32,
+        (target_y - margin_box.y) as f32,
+    );
+}
+
+/// Returns the scroll layer of the objects painted by the child from `start`, if it's fixed or sticky
+/// positioned. `parent` is the layout of the box which the sticky positioned box stays within.
+pub fn get_scroll_layer(
+    child: &BoxNode,
+    parent: &LayoutInfo,
+    start: usize,
+    end: usize,
+    viewport: Rect,
+) -> Option<ScrollLayer> {
+    let style_node = child.get_element_style_node()?;
+    let style = &style_node.borrow().style;
+    let kind = if style.position.is_fixed() {
+        ScrollLayerKind::Fixed
+    } else if style.position.is_sticky() {
+        let insets = style
+            .inset
+            .to_px(viewport.width as f32, viewport.height as f32)
+            .map(|inset| inset.map(f64::from));
+        let used_values = &parent.used_values;
+        ScrollLayerKind::Sticky {
+            rect: get_border_box(child),
+            bounds: Rect::new(
+                (parent.pos.x + used_values.padding.left) as f64,
+                (parent.pos.y + used_values.padding.top) as f64,
+                parent.get_content_width() as f64,
+                (parent.size.height
+                    - used_values.padding.top
+                    - used_values.padding.bottom
+                    - used_values.border.top
+                    - used_values.border.bottom) as f64,
+            ),
+            insets,
+        }
+    } else {
+        return None;
+    };
+    Some(ScrollLayer {
+        range: start..end,
+        kind,
+    })
+}
+
+/// Returns the border box of the laid out box. The position of a block box is the one of its padding box,
+/// and its size is the one of its border box, while the ones of the other boxes are of their content boxes.
+pub fn get_border_box(node: &BoxNode) -> Rect {
+    let LayoutInfo {
+        size,
+        pos,
+        used_values,
+    } = node.get_layout_info();
+    match node {
+        BoxNode::BlockBox(_) => Rect::new(
+            (pos.x - used_values.border.left) as f64,
+            (pos.y - used_values.border.top) as f64,
+            size.width as f64,
+            size.height as f64,
+        ),
+        _ => get_content_box(node).expand((
+            (used_values.padding.top + used_values.border.top) as f64,
+            (used_values.padding.right + used_values.border.right) as f64,
+            (used_values.padding.bottom + used_values.border.bottom) as f64,
+            (used_values.padding.left + used_values.border.left) as f64,
+        )),
+    }
+}
+
+fn get_content_box(node: &BoxNode) -> Rect {
+    let LayoutInfo {
+        size,
+        pos,
+        used_values,
+    } = node.get_layout_info();
+    match node {
+        BoxNode::BlockBox(_) => Rect::new(
+            (pos.x + used_values.padding.left) as f64,
+            (pos.y + used_values.padding.top) as f64,
+            node.get_layout_info().get_content_width() as f64,
+            (size.height
+                - used_values.padding.top
+                - used_values.padding.bottom
+                - used_values.border.top
+                - used_values.border.bottom) as f64,
+        ),
+        _ => Rect::new(
+            pos.x as f64,
+            pos.y as f64,
+            size.width as f64,
+            size.height as f64,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_scroll_offsets() {
+        assert_eq!(
+            ScrollLayer {
+                range: 0..1,
+                kind: ScrollLayerKind::Fixed,
+            }
+            .get_offset((0.0, 120.0, 800.0, 600.0)),
+            (0.0, 120.0)
+        );
+
+        // A header stuck to the top of a section from 100 to 500.
+        let sticky = ScrollLayer {
+            range: 0..1,
+            kind: ScrollLayerKind::Sticky {
+                rect: Rect::new(0.0, 100.0, 800.0, 50.0),
+                bounds: Rect::new(0.0, 100.0, 800.0, 400.0),
+                insets: [Some(10.0), None, None, None],
+            },
+        };
+        assert_eq!(sticky.get_offset((0.0, 0.0, 800.0, 600.0)), (0.0, 0.0));
+        assert_eq!(sticky.get_offset((0.0, 200.0, 800.0, 600.0)), (0.0, 110.0));
+        // It stops at the bottom of the section.
+        assert_eq!(sticky.get_offset((0.0, 1000.0, 800.0, 600.0)), (0.0, 350.0));
+
+        // A footer stuck to the bottom of the viewport until the section is scrolled into view.
+        let sticky = ScrollLayer {
+            range: 0..1,
+            kind: ScrollLayerKind::Sticky {
+                rect: Rect::new(0.0, 950.0, 800.0, 50.0),
+                bounds: Rect::new(0.0, 600.0, 800.0, 400.0),
+                insets: [None, None, Some(0.0), None],
+            },
+        };
+        assert_eq!(sticky.get_offset((0.0, 0.0, 800.0, 600.0)), (0.0, -350.0));
+        assert_eq!(sticky.get_offset((0.0, 300.0, 800.0, 600.0)), (0.0, -100.0));
+        assert_eq!(sticky.get_offset((0.0, 500.0, 800.0, 600.0)), (0.0, 0.0));
+    }
+}
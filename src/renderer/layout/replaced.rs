@@ -0,0 +1,195 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::renderer::html::dom::{Element, NodeType};
+use crate::renderer::layout::box_model::{BoxSize, LayoutBox, LayoutInfo};
+use crate::renderer::layout::math::MathLayout;
+use crate::renderer::style::property::display::DisplayOutside;
+use crate::renderer::style::property::CssValue;
+use crate::renderer::style::style_model::RenderNode;
+use crate::renderer::svg;
+
+/// The height of the media controls exposed by the user agent, which is the same as Chromium.
+const MEDIA_CONTROLS_HEIGHT: f32 = 54.0;
+
+/// The size of images whose dimensions are unknown, which is the same as the broken image icon of Chromium.
+const UNKNOWN_IMAGE_SIZE: f32 = 16.0;
+
+/// A box of a replaced element, whose content is outside the scope of the CSS formatting model.
+/// Currently, images, inline SVG images and media elements are supported, and media elements are painted as
+/// placeholders. MathML formulas are also laid out as replaced boxes, since their contents are laid out by
+/// the MathML layout instead of CSS.
+/// https://www.w3.org/TR/css-display-3/#replaced-element
+#[derive(Debug)]
+pub struct ReplacedBox {
+    pub style_node: Rc<RefCell<RenderNode>>,
+    pub layout_info: LayoutInfo,
+    /// The layout of the formula if the element is a `math` element.
+    pub math: Option<MathLayout>,
+}
+
+impl LayoutBox for ReplacedBox {
+    #[tracing::instrument(skip_all)]
+    fn layout(
+        &mut self,
+        containing_block_info: &LayoutInfo,
+        _: Option<LayoutInfo>,
+        prev_sibling_info: Option<LayoutInfo>,
+    ) {
+        self.calc_used_values(containing_block_info);
+        self.layout_info.size = self.calc_used_size();
+        self.calc_pos(containing_block_info, prev_sibling_info);
+    }
+
+    // The contents of replaced elements are not laid out by CSS.
+    fn layout_children(&mut self, _: &LayoutInfo) {}
+}
+
+impl ReplacedBox {
+    /// Returns whether the node is rendered as a replaced element.
+    /// https://html.spec.whatwg.org/multipage/rendering.html#replaced-elements
+    pub fn is_replaced_element(style_node: &RenderNode) -> bool {
+        match &style_node.dom_node.borrow().node_type {
+            NodeType::Element(elm) => style_node.pseudo_element.is_none() && Self::is_replaced(elm),
+            _ => false,
+        }
+    }
+
+    /// Returns whether the element is a replaced element, whose content is outside the scope of CSS.
+    /// https://drafts.csswg.org/css-display/#replaced-element
+    pub fn is_replaced(elm: &Element) -> bool {
+        elm.is_media_element() || matches!(elm.tag_name.as_str(), "img" | "svg" | "math")
+    }
+
+    pub fn get_display_type(&self) -> DisplayOutside {
+        self.style_node.borrow().get_display_type()
+    }
+
+    fn calc_used_values(&mut self, containing_block_info: &LayoutInfo) {
+        let margin = self.style_node.borrow().style.margin.clone();
+        let cb_width = containing_block_info.get_content_width();
+        let to_px = |value: &CssValue| match value {
+            CssValue::Ident(v) if v == "auto" => 0.0,
+            CssValue::Length(..) | CssValue::Percentage(_) | CssValue::Calc(_) => {
+                value.resolve_px(cb_width).unwrap()
+            }
+            _ => unreachable!(),
+        };
+        self.layout_info.used_values.margin.top = to_px(&margin.top);
+        self.layout_info.used_values.margin.right = to_px(&margin.right);
+        self.layout_info.used_values.margin.bottom = to_px(&margin.bottom);
+        self.layout_info.used_values.margin.left = to_px(&margin.left);
+    }
+
+    /// Returns the used width and height of the content box. The image and media resources are not fetched yet,
+    /// so the elements have no natural dimensions, and the default object size is used for `auto` values.
+    /// https://www.w3.org/TR/CSS22/visudet.html#inline-replaced-width
+    /// https://www.w3.org/TR/CSS22/visudet.html#inline-replaced-height
+    fn calc_used_size(&self) -> BoxSize {
+        let style_node = self.style_node.borrow();
+        let NodeType::Element(elm) = &style_node.dom_node.borrow().node_type else {
+            unreachable!()
+        };
+
+        // The `width` and `height` attributes of the img and video elements are presentational hints.
+        // https://html.spec.whatwg.org/multipage/rendering.html#dimRendering
+        let get_dimension = |value: &CssValue, attr: &str| match value {
+            CssValue::Length(..) => Some(value.to_px().unwrap()),
+            _ if matches!(elm.tag_name.as_str(), "img" | "video") => elm
+                .get_attribute(attr)
+                .and_then(|v| v.trim().parse::<f32>().ok())
+                .filter(|v| *v >= 0.0),
+            // The `width` and `height` attributes of the svg element are mapped to the properties.
+            // https://www.w3.org/TR/SVG2/geometry.html#Sizing
+            _ if elm.tag_name == "svg" => elm
+                .get_attribute(attr)
+                .and_then(svg::parse_length)
+                .filter(|v| *v >= 0.0)
+                .map(|v| v as f32),
+            _ => None,
+        };
+        let default_size = match elm.tag_name.as_str() {
+            "math" => {
+                let math = self.math.as_ref().unwrap();
+                BoxSize {
+                    width: math.width as f32,
+                    height: (math.ascent + math.descent) as f32,
+                }
+            }
+            "img" => BoxSize {
+                width: UNKNOWN_IMAGE_SIZE,
+                height: UNKNOWN_IMAGE_SIZE,
+            },
+            "video" | "svg" => BoxSize {
+                width: 300.0,
+                height: 150.0,
+            },
+            _ => BoxSize {
+                width: 300.0,
+                height: MEDIA_CONTROLS_HEIGHT,
+            },
+        };
+
+        let width = get_dimension(&style_node.style.width.size, "width");
+        let height = get_dimension(&style_node.style.height.size, "height");
+        // The missing dimension of the svg element is derived from the aspect ratio of its viewBox.
+        // https://www.w3.org/TR/SVG2/coords.html#SizingSVGInCSS
+        let (width, height) = match (width, height) {
+            (Some(w), None) if elm.tag_name == "svg" => {
+                (Some(w), svg::get_aspect_ratio(elm).map(|r| w / r as f32))
+            }
+            (None, Some(h)) if elm.tag_name == "svg" => {
+                (svg::get_aspect_ratio(elm).map(|r| h * r as f32), Some(h))
+            }
+            dimensions => dimensions,
+        };
+        BoxSize {
+            width: width.unwrap_or(default_size.width),
+            height: height.unwrap_or(default_size.height),
+        }
+    }
+
+    fn calc_pos(
+        &mut self,
+        containing_block_info: &LayoutInfo,
+        prev_sibling_info: Option<LayoutInfo>,
+    ) {
+        let used_values = self.layout_info.used_values.clone();
+        match self.get_display_type() {
+            // Block-level replaced elements are stacked vertically like block boxes.
+            // https://www.w3.org/TR/CSS22/visudet.html#block-replaced-width
+            DisplayOutside::Block => {
+                self.layout_info.pos.x = containing_block_info.pos.x
+                    + containing_block_info.used_values.padding.left
+                    + used_values.margin.left
+                    + used_values.border.left
+                    + used_values.padding.left;
+                self.layout_info.pos.y = used_values.margin.top
+                    + used_values.border.top
+                    + used_values.padding.top
+                    + if let Some(info) = prev_sibling_info {
+                        info.get_expanded_pos().y + info.get_expanded_size().height
+                    } else {
+                        containing_block_info.pos.y + containing_block_info.used_values.padding.top
+                    };
+            }
+            // Inline-level replaced elements are placed next to the previous sibling like inline boxes.
+            // https://www.w3.org/TR/CSS22/visuren.html#inline-formatting
+            DisplayOutside::Inline => {
+                let start_x = if let Some(info) = prev_sibling_info {
+                    info.get_expanded_pos().x + info.get_expanded_size().width
+                } else {
+                    containing_block_info.pos.x
+                };
+                self.layout_info.pos.x = start_x
+                    + used_values.margin.left
+                    + used_values.border.left
+                    + used_values.padding.left;
+                self.layout_info.pos.y = containing_block_info.pos.y
+                    + used_values.margin.top
+                    + used_values.border.top
+                    + used_values.padding.top;
+            }
+        }
+    }
+}
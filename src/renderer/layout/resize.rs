@@ -0,0 +1,231 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::renderer::html::dom::DomNode;
+use crate::renderer::layout::box_model::{BoxTree, ElementBoxes};
+use crate::renderer::layout::geometry::Rect;
+
+/// The box whose size is observed.
+/// https://drafts.csswg.org/resize-observer/#enumdef-resizeobserverboxoptions
+#[allow(dead_code, clippy::enum_variant_names)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeObserverBoxOptions {
+    BorderBox,
+    #[default]
+    ContentBox,
+    /// The content box in device pixels.
+    DevicePixelContentBox,
+}
+
+/// The size of a box in the writing mode of the element, which is always horizontal for now.
+/// https://drafts.csswg.org/resize-observer/#resizeobserversize
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ResizeObserverSize {
+    pub inline_size: f64,
+    pub block_size: f64,
+}
+
+impl ResizeObserverSize {
+    fn from_rect(rect: &Rect, scale: f64) -> Self {
+        Self {
+            inline_size: rect.width * scale,
+            block_size: rect.height * scale,
+        }
+    }
+}
+
+/// The new sizes of an observed element.
+/// todo: Add the content rect and the sizes of the other boxes, which are only read by scripts.
+/// https://drafts.csswg.org/resize-observer/#resize-observer-entry-interface
+#[derive(Debug, Clone)]
+pub struct ResizeObserverEntry {
+    pub target: Rc<RefCell<DomNode>>,
+    pub content_box_size: ResizeObserverSize,
+}
+
+/// A receiver of the entries of a resize observer, such as a script callback or an overlay which follows
+/// the boxes of the elements.
+pub trait ResizeObserverCallback: fmt::Debug {
+    fn on_resize(&mut self, entries: &[ResizeObserverEntry]);
+}
+
+#[derive(Debug)]
+struct ResizeObservation {
+    target: Rc<RefCell<DomNode>>,
+    observed_box: ResizeObserverBoxOptions,
+    last_reported_size: ResizeObserverSize,
+}
+
+impl ResizeObservation {
+    fn compute_size(&self, boxes: &ElementBoxes, device_pixel_ratio: f64) -> ResizeObserverSize {
+        match self.observed_box {
+            ResizeObserverBoxOptions::BorderBox => {
+                ResizeObserverSize::from_rect(&boxes.border_box, 1.0)
+            }
+            ResizeObserverBoxOptions::ContentBox => {
+                ResizeObserverSize::from_rect(&boxes.content_box, 1.0)
+            }
+            ResizeObserverBoxOptions::DevicePixelContentBox => {
+                ResizeObserverSize::from_rect(&boxes.content_box, device_pixel_ratio)
+            }
+        }
+    }
+}
+
+/// Observes the sizes of the boxes of the target elements, and delivers the changes to the callback after
+/// layout.
+/// https://drafts.csswg.org/resize-observer/#resize-observer-interface
+#[derive(Debug)]
+pub struct ResizeObserver {
+    callback: Rc<RefCell<dyn ResizeObserverCallback>>,
+    observations: Vec<ResizeObservation>,
+}
+
+impl ResizeObserver {
+    pub fn new(callback: Rc<RefCell<dyn ResizeObserverCallback>>) -> Self {
+        Self {
+            callback,
+            observations: Vec::new(),
+        }
+    }
+
+    /// Starts observing the box of the target, replacing the observation of the target if it exists.
+    /// Elements which are not rendered have the size of zero, so the first notification is delivered
+    /// once the target is rendered.
+    /// https://drafts.csswg.org/resize-observer/#dom-resizeobserver-observe
+    pub fn observe(
+        &mut self,
+        target: &Rc<RefCell<DomNode>>,
+        observed_box: ResizeObserverBoxOptions,
+    ) {
+        self.unobserve(target);
+        self.observations.push(ResizeObservation {
+            target: Rc::clone(target),
+            observed_box,
+            last_reported_size: ResizeObserverSize::default(),
+        });
+    }
+
+    /// https://drafts.csswg.org/resize-observer/#dom-resizeobserver-unobserve
+    pub fn unobserve(&mut self, target: &Rc<RefCell<DomNode>>) {
+        self.observations.retain(|o| !Rc::ptr_eq(&o.target, target));
+    }
+
+    /// https://drafts.csswg.org/resize-observer/#dom-resizeobserver-disconnect
+    pub fn disconnect(&mut self) {
+        self.observations.clear();
+    }
+
+    /// Delivers the entries of the targets whose observed sizes have changed since they were last reported,
+    /// and returns whether any entry is delivered. `get_boxes` returns the boxes of a target, or `None` if
+    /// it is not rendered.
+    /// https://drafts.csswg.org/resize-observer/#broadcast-active-resize-observations
+    pub fn broadcast_active_observations(
+        &mut self,
+        device_pixel_ratio: f64,
+        get_boxes: impl Fn(&Rc<RefCell<DomNode>>) -> Option<ElementBoxes>,
+    ) -> bool {
+        let mut entries = Vec::new();
+        for observation in &mut self.observations {
+            let boxes = get_boxes(&observation.target).unwrap_or_default();
+            let size = observation.compute_size(&boxes, device_pixel_ratio);
+            if size == observation.last_reported_size {
+                continue;
+            }
+            entries.push(ResizeObserverEntry {
+                target: Rc::clone(&observation.target),
+                content_box_size: ResizeObserverSize::from_rect(&boxes.content_box, 1.0),
+            });
+            observation.last_reported_size = size;
+        }
+        if entries.is_empty() {
+            return false;
+        }
+        self.callback.borrow_mut().on_resize(&entries);
+        true
+    }
+}
+
+/// The resize observers of a document, which are notified in the order of their creation after each
+/// layout. Internal consumers are registered here in the same way as the observers created by scripts.
+#[derive(Debug, Default)]
+pub struct ResizeObservers {
+    observers: Vec<Rc<RefCell<ResizeObserver>>>,
+}
+
+impl ResizeObservers {
+    pub fn register(&mut self, observer: &Rc<RefCell<ResizeObserver>>) {
+        self.observers.push(Rc::clone(observer));
+    }
+
+    /// Notifies the observers of the size changes in the laid-out box tree.
+    /// https://html.spec.whatwg.org/multipage/webappapis.html#event-loop-processing-model
+    pub fn notify(&self, box_tree: &BoxTree, device_pixel_ratio: f64) {
+        for observer in &self.observers {
+            observer
+                .borrow_mut()
+                .broadcast_active_observations(device_pixel_ratio, |target| {
+                    box_tree.get_element_boxes(target)
+                });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::dom::{Element, NodeType};
+
+    #[derive(Debug, Default)]
+    struct EntryRecorder {
+        sizes: Vec<Vec<f64>>,
+    }
+
+    impl ResizeObserverCallback for EntryRecorder {
+        fn on_resize(&mut self, entries: &[ResizeObserverEntry]) {
+            self.sizes.push(
+                entries
+                    .iter()
+                    .map(|e| e.content_box_size.inline_size)
+                    .collect(),
+            );
+        }
+    }
+
+    #[test]
+    fn observe_resizes() {
+        let div = Rc::new(RefCell::new(DomNode::new(NodeType::Element(Element {
+            tag_name: "div".to_string(),
+            attributes: Vec::new(),
+        }))));
+        let recorder = Rc::new(RefCell::new(EntryRecorder::default()));
+        let mut observer = ResizeObserver::new(Rc::clone(&recorder) as _);
+        observer.observe(&div, ResizeObserverBoxOptions::ContentBox);
+
+        let boxes = |width: f64, padding: f64| ElementBoxes {
+            content_box: Rect::new(padding, 0.0, width, 10.0),
+            padding_box: Rect::new(0.0, 0.0, width + padding * 2.0, 10.0),
+            border_box: Rect::new(0.0, 0.0, width + padding * 2.0, 10.0),
+        };
+        // Nothing is delivered while the target is not rendered.
+        assert!(!observer.broadcast_active_observations(1.0, |_| None));
+        assert!(observer.broadcast_active_observations(1.0, |_| Some(boxes(100.0, 0.0))));
+        assert!(!observer.broadcast_active_observations(1.0, |_| Some(boxes(100.0, 0.0))));
+        // The content box doesn't change when only the padding changes.
+        assert!(!observer.broadcast_active_observations(1.0, |_| Some(boxes(100.0, 5.0))));
+
+        // The border box observation is reported with the size of the content box, which hasn't changed.
+        observer.observe(&div, ResizeObserverBoxOptions::BorderBox);
+        assert!(observer.broadcast_active_observations(1.0, |_| Some(boxes(100.0, 5.0))));
+        assert!(observer.broadcast_active_observations(1.0, |_| Some(boxes(80.0, 5.0))));
+        assert!(observer.broadcast_active_observations(1.0, |_| None));
+
+        observer.disconnect();
+        assert!(!observer.broadcast_active_observations(1.0, |_| Some(boxes(50.0, 5.0))));
+        assert_eq!(
+            recorder.borrow().sizes,
+            [vec![100.0], vec![100.0], vec![80.0], vec![0.0]]
+        );
+    }
+}
@@ -0,0 +1,459 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::renderer::layout::box_model::{BoxNode, LayoutInfo};
+use crate::renderer::layout::geometry::Rect;
+use crate::renderer::layout::overflow::{
+    clips_overflow, get_contents_clip, get_padding_box, ScrollBox,
+};
+use crate::renderer::layout::positioned::{get_scroll_layer, ScrollLayer, ScrollLayerKind};
+use crate::renderer::style::style_model::ComputedStyle;
+use crate::renderer::RenderObject;
+
+/// A box which is painted apart from the boxes in flow of its stacking context, i.e. a positioned box or a
+/// box which creates its own stacking context.
+struct StackedBox {
+    node: Rc<RefCell<BoxNode>>,
+    /// The layout of the parent, which the sticky positioned box stays within.
+    parent: LayoutInfo,
+    /// The stack level, which is 0 for `z-index: auto`.
+    level: i32,
+    is_stacking_context: bool,
+    /// The area to which the box is clipped by the boxes between it and its stacking context.
+    /// todo: Don't clip the absolutely positioned boxes whose containing blocks are outside the clipping
+    /// boxes.
+    clip: Option<Rect>,
+}
+
+/// The layers in which the boxes in flow of a stacking context are painted, between the stacking contexts
+/// with negative stack levels and the positioned boxes. The boxes in each layer are painted in tree order.
+/// https://drafts.csswg.org/css2/#painting-order
+#[derive(Clone, Copy, PartialEq)]
+enum Layer {
+    /// The backgrounds of the block-level boxes.
+    BlockBackgrounds,
+    /// The floats, each of which is painted as if it created a stacking context.
+    Floats,
+    /// The inline-level contents, i.e. the texts, the inline boxes, the replaced boxes and the atomic
+    /// inline-level boxes, the last of which are painted as if they created stacking contexts.
+    InlineContents,
+}
+
+const LAYERS: [Layer; 3] = [
+    Layer::BlockBackgrounds,
+    Layer::Floats,
+    Layer::InlineContents,
+];
+
+/// The thickness of the scrollbar indicators, which are overlaid on the contents of the scroll containers
+/// without taking space from them.
+const SCROLLBAR_WIDTH: f64 = 6.0;
+/// The minimum length of the thumbs of the scrollbar indicators.
+const SCROLLBAR_MIN_THUMB_LENGTH: f64 = 16.0;
+
+/// Returns whether the box of the style creates a stacking context, in which the boxes inside it are
+/// painted together in front of or behind the other boxes.
+/// https://drafts.csswg.org/css-position-4/#stacking-context
+pub fn creates_stacking_context(style: &ComputedStyle) -> bool {
+    let position = &style.position;
+    (position.is_positioned() && style.z_index.get_level().is_some())
+        || position.is_fixed()
+        || position.is_sticky()
+        || style.opacity.is_translucent()
+}
+
+/// Returns the render objects of the box tree in the painting order of the stacking contexts, and the
+/// ranges of the fixed and sticky positioned boxes in them. The scrollbars of the scroll containers are
+/// painted by their scroll positions in `scroll_boxes`.
+pub fn paint_box_tree(
+    root: &Rc<RefCell<BoxNode>>,
+    scroll_boxes: &[ScrollBox],
+    viewport_width: i32,
+    viewport_height: i32,
+) -> (Vec<RenderObject>, Vec<ScrollLayer>) {
+    let mut painter = Painter {
+        objects: Vec::new(),
+        scroll_layers: Vec::new(),
+        scroll_boxes,
+        viewport_width,
+        viewport_height,
+    };
+    // The root element always creates a stacking context.
+    painter.paint_stacked(&StackedBox {
+        node: Rc::clone(root),
+        parent: root.borrow().get_layout_info().clone(),
+        level: 0,
+        is_stacking_context: true,
+        clip: None,
+    });
+    (painter.objects, painter.scroll_layers)
+}
+
+struct Painter<'a> {
+    objects: Vec<RenderObject>,
+    scroll_layers: Vec<ScrollLayer>,
+    scroll_boxes: &'a [ScrollBox],
+    viewport_width: i32,
+    viewport_height: i32,
+}
+
+impl Painter<'_> {
+    /// Paints the stacked box, whose objects are made translucent by its opacity, and form a scroll layer if
+    /// it's fixed or sticky positioned.
+    /// todo: Composite the translucent boxes as groups so that their overlapping objects are not seen
+    /// through each other, and make the images translucent.
+    fn paint_stacked(&mut self, stacked: &StackedBox) {
+        if let Some(clip) = stacked.clip {
+            self.push_clip(clip);
+        }
+        let start = self.objects.len();
+        if stacked.is_stacking_context {
+            self.paint_stacking_context(&stacked.node);
+        } else {
+            self.paint_atomically(&stacked.node.borrow());
+        }
+
+        let node = stacked.node.borrow();
+        if let Some(style_node) = node.get_element_style_node() {
+            let alpha = style_node.borrow().style.opacity.get_alpha();
+            if alpha < 1.0 {
+                for object in &mut self.objects[start..] {
+                    object.apply_opacity(alpha);
+                }
+            }
+        }
+        let viewport = Rect::new(
+            0.0,
+            0.0,
+            self.viewport_width as f64,
+            self.viewport_height as f64,
+        );
+        if let Some(layer) =
+            get_scroll_layer(&node, &stacked.parent, start, self.objects.len(), viewport)
+        {
+            // The fixed positioned boxes inside a fixed positioned box are moved with it.
+            if layer.kind == ScrollLayerKind::Fixed {
+                self.scroll_layers.retain(|l| {
+                    l.kind != ScrollLayerKind::Fixed || l.range.start < layer.range.start
+                });
+            }
+            self.scroll_layers.push(layer);
+        }
+        if stacked.clip.is_some() {
+            self.objects.push(RenderObject::EndClip);
+        }
+    }
+
+    /// Paints the box and the boxes in its stacking context: the background of the box, the stacking
+    /// contexts with negative stack levels, the layers of the boxes in flow, the positioned boxes and the
+    /// stacking contexts with the stack level 0 in tree order, and then the stacking contexts with positive
+    /// stack levels. The stacking contexts with the same stack level are painted in tree order.
+    /// https://drafts.csswg.org/css2/#painting-order
+    fn paint_stacking_context(&mut self, node: &Rc<RefCell<BoxNode>>) {
+        let mut stacked_boxes = Vec::new();
+        collect_stacked_boxes(&node.borrow(), &mut stacked_boxes);
+        stacked_boxes.sort_by_key(|stacked| stacked.level);
+        let (negative, non_negative): (Vec<_>, Vec<_>) = stacked_boxes
+            .into_iter()
+            .partition(|stacked| stacked.level < 0);
+
+        node.borrow().to_render_objects(
+            &mut self.objects,
+            self.viewport_width,
+            self.viewport_height,
+        );
+        self.paint_contents(&node.borrow(), |painter| {
+            for stacked in &negative {
+                painter.paint_stacked(stacked);
+            }
+            for layer in LAYERS {
+                painter.paint_layer(&node.borrow(), layer);
+            }
+            for stacked in &non_negative {
+                painter.paint_stacked(stacked);
+            }
+        });
+    }
+
+    /// Paints the box and the layers of the boxes in flow inside it as if it created a stacking context,
+    /// except that the stacked boxes inside it are painted by its stacking context. The positioned boxes
+    /// with `z-index: auto`, the floats and the atomic inline-level boxes are painted in this way.
+    fn paint_atomically(&mut self, node: &BoxNode) {
+        node.to_render_objects(&mut self.objects, self.viewport_width, self.viewport_height);
+        self.paint_contents(node, |painter| {
+            for layer in LAYERS {
+                painter.paint_layer(node, layer);
+            }
+        });
+    }
+
+    /// Paints the objects of the layer in the boxes in flow inside the box in tree order. The floats and
+    /// the atomic inline-level boxes are painted atomically in their layers, and the stacked boxes are
+    /// skipped.
+    fn paint_layer(&mut self, node: &BoxNode, layer: Layer) {
+        for child in node.get_children() {
+            let child = child.borrow();
+            if get_stack_level(&child).is_some() {
+                continue;
+            }
+            if is_float(&child) || is_atomic_inline(&child) {
+                let child_layer = if is_float(&child) {
+                    Layer::Floats
+                } else {
+                    Layer::InlineContents
+                };
+                if layer == child_layer {
+                    self.paint_atomically(&child);
+                }
+                continue;
+            }
+            let is_block_box = matches!(&*child, BoxNode::BlockBox(_));
+            if is_block_box == (layer == Layer::BlockBackgrounds) && layer != Layer::Floats {
+                child.to_render_objects(
+                    &mut self.objects,
+                    self.viewport_width,
+                    self.viewport_height,
+                );
+            }
+            // The scrollbars are painted over the contents in the last layer.
+            if layer == Layer::InlineContents {
+                self.paint_contents(&child, |painter| painter.paint_layer(&child, layer));
+            } else {
+                self.clip_contents(&child, |painter| painter.paint_layer(&child, layer));
+            }
+        }
+    }
+
+    /// Paints the contents of the box with `paint`, which are clipped to its padding box if its overflow
+    /// is not visible, and then its scrollbars if it's a scroll container.
+    /// https://drafts.csswg.org/css-overflow-3/#overflow-properties
+    fn paint_contents(&mut self, node: &BoxNode, paint: impl FnOnce(&mut Self)) {
+        self.clip_contents(node, paint);
+        if clips_overflow(node) {
+            self.paint_scrollbars(node);
+        }
+    }
+
+    /// Paints the contents of the box with `paint`, which are clipped to its padding box if its overflow
+    /// is not visible. The clip is left out if nothing is painted in it.
+    fn clip_contents(&mut self, node: &BoxNode, paint: impl FnOnce(&mut Self)) {
+        if !clips_overflow(node) {
+            paint(self);
+            return;
+        }
+        let start = self.objects.len();
+        self.push_clip(get_padding_box(node));
+        paint(self);
+        if self.objects.len() == start + 1 {
+            self.objects.pop();
+        } else {
+            self.objects.push(RenderObject::EndClip);
+        }
+    }
+
+    fn push_clip(&mut self, clip: Rect) {
+        self.objects.push(RenderObject::Clip {
+            x: clip.x,
+            y: clip.y,
+            width: clip.width,
+            height: clip.height,
+        });
+    }
+
+    /// Paints the scrollbar indicators of the scroll container at the right and the bottom edges of its
+    /// scrollport, whose thumbs show the part of the scrollable overflow area seen in the scrollport.
+    /// todo: Make the scrollbars draggable.
+    fn paint_scrollbars(&mut self, node: &BoxNode) {
+        let Some(style_node) = node.get_element_style_node() else {
+            return;
+        };
+        let style_node = style_node.borrow();
+        let Some(scroll_box) = self
+            .scroll_boxes
+            .iter()
+            .find(|scroll_box| Rc::ptr_eq(&scroll_box.node, &style_node.dom_node))
+        else {
+            return;
+        };
+        let scrollport = scroll_box.scrollport;
+        for is_horizontal in [true, false] {
+            let (offset, max_offset) = if is_horizontal {
+                (scroll_box.offset.0, scroll_box.max_offset.0)
+            } else {
+                (scroll_box.offset.1, scroll_box.max_offset.1)
+            };
+            if !style_node
+                .style
+                .overflow
+                .has_scrollbar(is_horizontal, max_offset > 0.0)
+            {
+                continue;
+            }
+            let track = if is_horizontal {
+                Rect::new(
+                    scrollport.x,
+                    scrollport.y + scrollport.height - SCROLLBAR_WIDTH,
+                    scrollport.width,
+                    SCROLLBAR_WIDTH,
+                )
+            } else {
+                Rect::new(
+                    scrollport.x + scrollport.width - SCROLLBAR_WIDTH,
+                    scrollport.y,
+                    SCROLLBAR_WIDTH,
+                    scrollport.height,
+                )
+            };
+            let track_length = if is_horizontal {
+                track.width
+            } else {
+                track.height
+            };
+            let max_offset = max_offset as f64;
+            let thumb_length = (track_length * track_length / (track_length + max_offset))
+                .max(SCROLLBAR_MIN_THUMB_LENGTH)
+                .min(track_length);
+            let thumb_start = if max_offset > 0.0 {
+                (track_length - thumb_length) * offset as f64 / max_offset
+            } else {
+                0.0
+            };
+            let thumb = if is_horizontal {
+                Rect::new(track.x + thumb_start, track.y, thumb_length, track.height)
+            } else {
+                Rect::new(track.x, track.y + thumb_start, track.width, thumb_length)
+            };
+            for (rect, color) in [
+                (track, (0.0, 0.0, 0.0, 0.05)),
+                (thumb, (0.0, 0.0, 0.0, 0.4)),
+            ] {
+                let radius = SCROLLBAR_WIDTH / 2.0;
+                self.objects.push(RenderObject::Rect {
+                    x: rect.x,
+                    y: rect.y,
+                    width: rect.width,
+                    height: rect.height,
+                    color,
+                    border_radius: (radius, radius, radius, radius),
+                });
+            }
+        }
+    }
+}
+
+/// Collects the stacked boxes in the stacking context of the box in tree order. The boxes inside the
+/// positioned boxes with `z-index: auto` are in the same stacking context, while the ones inside the
+/// other stacking contexts are not.
+/// The stacked boxes inside the boxes which clip their overflow are clipped with them.
+fn collect_stacked_boxes(node: &BoxNode, stacked_boxes: &mut Vec<StackedBox>) {
+    fn helper(node: &BoxNode, clip: Option<Rect>, stacked_boxes: &mut Vec<StackedBox>) {
+        for child in node.get_children() {
+            let child_node = child.borrow();
+            if let Some((level, is_stacking_context)) = get_stack_level(&child_node) {
+                stacked_boxes.push(StackedBox {
+                    node: Rc::clone(child),
+                    parent: node.get_layout_info().clone(),
+                    level,
+                    is_stacking_context,
+                    clip,
+                });
+                if is_stacking_context {
+                    continue;
+                }
+            }
+            let clip = get_contents_clip(&child_node, clip);
+            helper(&child_node, clip, stacked_boxes);
+        }
+    }
+
+    helper(node, None, stacked_boxes);
+}
+
+fn is_float(node: &BoxNode) -> bool {
+    node.get_element_style_node()
+        .is_some_and(|style_node| style_node.borrow().style.float.is_floating())
+}
+
+fn is_atomic_inline(node: &BoxNode) -> bool {
+    matches!(node, BoxNode::BlockBox(block) if block.is_atomic_inline())
+}
+
+/// Returns the stack level of the box and whether it creates a stacking context if it's a stacked box.
+/// The boxes which create stacking contexts without being positioned, e.g. the translucent ones, are
+/// painted as the positioned ones with `z-index: 0`.
+/// https://drafts.csswg.org/css-color/#transparency
+fn get_stack_level(node: &BoxNode) -> Option<(i32, bool)> {
+    let style_node = node.get_element_style_node()?;
+    let style = &style_node.borrow().style;
+    let is_stacking_context = creates_stacking_context(style);
+    if !is_stacking_context && !style.position.is_positioned() {
+        return None;
+    }
+    let level = if style.position.is_positioned() {
+        style.z_index.get_level().unwrap_or(0)
+    } else {
+        0
+    };
+    Some((level, is_stacking_context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::layout::box_model::BoxTree;
+
+    /// Returns the colors of the backgrounds painted for the HTML document, which has no texts.
+    fn paint_backgrounds(html: &str) -> Vec<(f64, f64, f64, f64)> {
+        BoxTree::from_html(html, 800, 600)
+            .to_render_objects(800, 600)
+            .list
+            .into_iter()
+            .filter_map(|object| match object {
+                RenderObject::Rect { color, .. } => Some(color),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn paint_in_stacking_order() {
+        const RED: (f64, f64, f64, f64) = (1.0, 0.0, 0.0, 1.0);
+        const LIME: (f64, f64, f64, f64) = (0.0, 1.0, 0.0, 1.0);
+        const BLUE: (f64, f64, f64, f64) = (0.0, 0.0, 1.0, 1.0);
+        const YELLOW: (f64, f64, f64, f64) = (1.0, 1.0, 0.0, 1.0);
+        const BLACK: (f64, f64, f64, f64) = (0.0, 0.0, 0.0, 1.0);
+
+        // The positive stack levels over the positioned boxes, which are over the floats, which are over the
+        // block backgrounds, which are over the negative stack levels.
+        let html = "<html><body>\
+            <div style='position: relative; z-index: 1; height: 10px; background-color: black'></div>\
+            <div style='position: relative; height: 10px; background-color: yellow'></div>\
+            <div style='float: left; width: 10px; height: 10px; background-color: lime'></div>\
+            <div style='height: 10px; background-color: red'></div>\
+            <div style='position: relative; z-index: -1; height: 10px; background-color: blue'></div>\
+            </body></html>";
+        assert_eq!(paint_backgrounds(html), [BLUE, RED, LIME, YELLOW, BLACK]);
+
+        // The stack levels are compared in the same stacking context, in which the ones with the same
+        // level are painted in tree order.
+        let html = "<html><body>\
+            <div style='position: relative; z-index: 2; height: 10px; background-color: yellow'>\
+              <div style='position: relative; z-index: -5; height: 10px; background-color: red'></div>\
+            </div>\
+            <div style='position: relative; z-index: 1; height: 10px; background-color: black'></div>\
+            <div style='position: relative; z-index: 1; height: 10px; background-color: blue'></div>\
+            </body></html>";
+        assert_eq!(paint_backgrounds(html), [BLACK, BLUE, YELLOW, RED]);
+
+        // The positioned boxes inside a float are painted in the stacking context of the float, and the
+        // blocks inside it are painted with it.
+        let html = "<html><body>\
+            <div style='float: left; width: 20px; background-color: lime'>\
+              <div style='position: relative; height: 10px; background-color: yellow'></div>\
+              <div style='height: 10px; background-color: blue'></div>\
+            </div>\
+            <div style='height: 10px; background-color: red'></div>\
+            </body></html>";
+        assert_eq!(paint_backgrounds(html), [RED, LIME, BLUE, YELLOW]);
+    }
+}
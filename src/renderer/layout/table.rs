@@ -0,0 +1,230 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk4::pango;
+
+use crate::renderer::layout::block::AnonymousBox;
+use crate::renderer::layout::box_model::{BoxNode, BoxPosition, BoxSize, LayoutInfo, UsedValues};
+use crate::renderer::style::property::display::{DisplayInside, DisplayInternal};
+use crate::renderer::style::property::{CssValue, DisplayOutside, DisplayProp};
+use crate::renderer::style::style_model::ComputedStyle;
+
+/// Generates the missing anonymous table boxes around the children of the box, so that the cells are in
+/// rows and the rows are in row groups or tables.
+/// todo: Generate the anonymous tables around the inline-level cells as `inline-table`.
+/// https://www.w3.org/TR/CSS22/tables.html#anonymous-boxes
+pub fn fix_up_table_children(
+    display: &DisplayProp,
+    style: &ComputedStyle,
+    children: Vec<Rc<RefCell<BoxNode>>>,
+    draw_ctx: &pango::Context,
+) -> Vec<Rc<RefCell<BoxNode>>> {
+    let is_table_container = display.is_table() || display.is_table_row_group();
+    let children = if is_table_container || display.is_table_row() {
+        // 1. The whitespace between the boxes of a table, its row groups and rows is not rendered.
+        children
+            .into_iter()
+            .filter(|child| !is_whitespace(&child.borrow()))
+            .collect()
+    } else {
+        children
+    };
+
+    if display.is_table_row() {
+        // 2. The children of a row other than cells are wrapped in an anonymous cell.
+        return wrap_runs(
+            children,
+            style,
+            get_internal_display(DisplayInternal::TableCell),
+            |d| !d.is_table_cell(),
+            draw_ctx,
+        );
+    }
+    if is_table_container {
+        // 3. The children of a table or a row group other than rows are wrapped in an anonymous row.
+        let children = wrap_runs(
+            children,
+            style,
+            get_internal_display(DisplayInternal::TableRow),
+            |d| {
+                !d.is_table_row()
+                    && !(display.is_table()
+                        && (d.is_table_row_group()
+                            || d.internal == Some(DisplayInternal::TableCaption)))
+            },
+            draw_ctx,
+        );
+        for child in &children {
+            if let BoxNode::AnonymousBox(anon_box) = &mut *child.borrow_mut() {
+                if anon_box.style.display.is_table_row() {
+                    let grandchildren = std::mem::take(&mut anon_box.children);
+                    anon_box.children = fix_up_table_children(
+                        &anon_box.style.display,
+                        style,
+                        grandchildren,
+                        draw_ctx,
+                    );
+                }
+            }
+        }
+        return children;
+    }
+
+    // 4. The cells out of rows are wrapped in an anonymous row, and the rows out of tables in an anonymous
+    // table.
+    let children = wrap_runs(
+        children,
+        style,
+        get_internal_display(DisplayInternal::TableRow),
+        DisplayProp::is_table_cell,
+        draw_ctx,
+    );
+    let table_display = DisplayProp {
+        outside: DisplayOutside::Block,
+        inside: DisplayInside::Table,
+        ..Default::default()
+    };
+    wrap_runs(
+        children,
+        style,
+        table_display,
+        |d| d.is_table_row() || d.is_table_row_group(),
+        draw_ctx,
+    )
+}
+
+/// Lays out the cells of the row side by side, and returns the height of the row, to which the cells are
+/// stretched. The cells with a length in `width` take it, and the others share the rest equally.
+/// todo: Implement the automatic table layout, `colspan`, `rowspan` and `border-spacing`.
+/// https://www.w3.org/TR/CSS22/tables.html#fixed-table-layout
+pub fn layout_table_row(children: &[Rc<RefCell<BoxNode>>], row_info: &LayoutInfo) -> f32 {
+    let row_width = row_info.get_content_width();
+    let widths = children
+        .iter()
+        .map(|child| match &*child.borrow() {
+            BoxNode::BlockBox(b) => {
+                let width = &b.style_node.borrow().style.width.size;
+                match width {
+                    CssValue::Length(..) | CssValue::Percentage(_) | CssValue::Calc(_) => {
+                        let used_values = &b.layout_info.used_values;
+                        width.resolve_px(row_width).ok().map(|width| {
+                            width
+                                + used_values.padding.left
+                                + used_values.padding.right
+                                + used_values.border.left
+                                + used_values.border.right
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let auto_count = widths.iter().filter(|w| w.is_none()).count();
+    let auto_width = if auto_count == 0 {
+        0.0
+    } else {
+        ((row_width - widths.iter().flatten().sum::<f32>()) / auto_count as f32).max(0.0)
+    };
+
+    let mut x = row_info.pos.x + row_info.used_values.padding.left;
+    let y = row_info.pos.y + row_info.used_values.padding.top;
+    let mut row_height: f32 = 0.0;
+    for (child, width) in children.iter().zip(&widths) {
+        let width = width.unwrap_or(auto_width);
+        let slot_info = LayoutInfo {
+            size: BoxSize { width, height: 0.0 },
+            pos: BoxPosition { x, y },
+            used_values: UsedValues {
+                width: Some(width),
+                ..Default::default()
+            },
+        };
+        child.borrow_mut().layout(&slot_info, None, None);
+        row_height = row_height.max(child.borrow().get_layout_info().get_expanded_size().height);
+        x += width;
+    }
+
+    for child in children {
+        let mut child = child.borrow_mut();
+        let layout_info = child.get_layout_info_mut();
+        let extra = layout_info.get_expanded_size().height - layout_info.size.height;
+        layout_info.size.height = row_height - extra;
+    }
+    row_height
+}
+
+/// Returns whether the box is only whitespace, i.e. a text of whitespace or an anonymous box of them.
+fn is_whitespace(node: &BoxNode) -> bool {
+    match node {
+        BoxNode::Text(t) => t
+            .style_node
+            .borrow()
+            .dom_node
+            .borrow()
+            .get_inside_text()
+            .is_some_and(|text| text.chars().all(char::is_whitespace)),
+        BoxNode::AnonymousBox(AnonymousBox { children, .. }) => {
+            children.iter().all(|child| is_whitespace(&child.borrow()))
+        }
+        _ => false,
+    }
+}
+
+/// Wraps each run of the consecutive children that `needs_wrapping` in an anonymous box of `display`.
+fn wrap_runs(
+    children: Vec<Rc<RefCell<BoxNode>>>,
+    style: &ComputedStyle,
+    display: DisplayProp,
+    needs_wrapping: impl Fn(&DisplayProp) -> bool,
+    draw_ctx: &pango::Context,
+) -> Vec<Rc<RefCell<BoxNode>>> {
+    let mut ret = Vec::new();
+    let mut run = Vec::new();
+    for child in children {
+        if needs_wrapping(&child.borrow().get_display()) {
+            run.push(child);
+            continue;
+        }
+        if !run.is_empty() {
+            ret.push(new_anonymous_box(
+                style,
+                &display,
+                std::mem::take(&mut run),
+                draw_ctx,
+            ));
+        }
+        ret.push(child);
+    }
+    if !run.is_empty() {
+        ret.push(new_anonymous_box(style, &display, run, draw_ctx));
+    }
+    ret
+}
+
+/// Returns the display of the anonymous internal table box, which is block-level in the box tree.
+fn get_internal_display(internal: DisplayInternal) -> DisplayProp {
+    DisplayProp {
+        outside: DisplayOutside::Block,
+        internal: Some(internal),
+        ..Default::default()
+    }
+}
+
+/// Returns the anonymous box of `display` around the children, which inherits the style of the parent.
+fn new_anonymous_box(
+    style: &ComputedStyle,
+    display: &DisplayProp,
+    children: Vec<Rc<RefCell<BoxNode>>>,
+    draw_ctx: &pango::Context,
+) -> Rc<RefCell<BoxNode>> {
+    let mut style = style.clone();
+    style.display = display.clone();
+    Rc::new(RefCell::new(BoxNode::AnonymousBox(AnonymousBox {
+        style: Box::new(style),
+        layout_info: LayoutInfo::default(),
+        children,
+        draw_ctx: draw_ctx.clone(),
+    })))
+}
@@ -1,86 +1,238 @@
 use std::cell::RefCell;
-use std::cmp::max_by;
 use std::rc::Rc;
 
-use anyhow::Result;
 use gtk4::pango;
-use regex::Regex;
+use gtk4::pango::prelude::FontExt as _;
 
+use crate::renderer::html::dom::DomNode;
+use crate::renderer::html::editing::Composition;
+use crate::renderer::html::spellcheck::{get_dictionary, is_spellchecked};
+use crate::renderer::layout::bidi::{get_bidi_class, BidiClass};
 use crate::renderer::layout::box_model::{LayoutBox, LayoutInfo};
-use crate::renderer::style::property::{CssValue, DisplayOutside};
+use crate::renderer::layout::font::SelectedFont;
+use crate::renderer::layout::geometry::Rect;
+use crate::renderer::style::property::{CssValue, TextDecorationProp, WhiteSpaceProp};
 use crate::renderer::style::style_model::RenderNode;
+use crate::renderer::{PathSegment, RenderObject};
 
 #[derive(Debug)]
 pub struct Text {
     pub style_node: Rc<RefCell<RenderNode>>,
     pub layout_info: LayoutInfo,
     pub draw_ctx: pango::Context,
+    /// The fragments of the text in the line boxes into which it's wrapped in the last layout.
+    pub lines: Vec<TextLine>,
+    /// The text decorations of the boxes which the text is in, from the outermost.
+    pub decorations: Vec<TextDecorationProp>,
+}
+
+/// A fragment of the text in a line box, which is painted at its own position and kept to debug the wrapping
+/// with `--dump lines`.
+/// https://drafts.csswg.org/css-inline-3/#line-boxes
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLine {
+    pub text: String,
+    /// The top left corner of the content area of the fragment, i.e. of the glyphs.
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// The distance from the top of the content area to the baseline.
+    pub ascent: f32,
+    /// The byte offset in the text after the white space processing at which the fragment starts, i.e. the
+    /// break opportunity used before the line, which is `None` for the first fragment.
+    pub break_offset: Option<usize>,
+    /// The embedding level of the fragment, which is `None` if the text isn't ordered by the bidirectional
+    /// algorithm.
+    pub level: Option<u8>,
+}
+
+impl TextLine {
+    pub fn get_baseline(&self) -> f32 {
+        self.y + self.ascent
+    }
+
+    /// Returns the text of the fragment wrapped in the override characters of its direction if needed,
+    /// since the fragment is already ordered visually in the line and must not be reordered when painted.
+    pub fn get_display_text(&self) -> String {
+        self.to_display_text(&self.text)
+    }
+
+    /// Wraps the part of the text of the fragment in the override characters of its direction if needed.
+    fn to_display_text(&self, text: &str) -> String {
+        use BidiClass::*;
+        match self.level {
+            Some(level) if level % 2 == 1 => format!("\u{202E}{}\u{202C}", text),
+            Some(_)
+                if text
+                    .chars()
+                    .any(|c| matches!(get_bidi_class(c), R | Al | An)) =>
+            {
+                format!("\u{202D}{}\u{202C}", text)
+            }
+            _ => text.to_string(),
+        }
+    }
+}
+
+/// A misspelled word in the text, which is underlined with a wavy line and whose suggestions are offered
+/// from the context menu.
+#[derive(Debug, Clone)]
+pub struct MisspelledWord {
+    /// The text node which the word is in.
+    pub node: Rc<RefCell<DomNode>>,
+    /// The index of the word in the misspelled words of the data of the text node, with which the word is
+    /// replaced. The words are assumed not to be split across the lines.
+    pub index: usize,
+    pub word: String,
+    /// The area of the glyphs of the word on the canvas.
+    pub rect: Rect,
+    pub baseline: f64,
+}
+
+/// A fragment of an editable text, in which the caret is placed by hit testing and painted.
+/// todo: Map the offsets in the text whose white space collapses to the ones in the data of the node, which
+/// are assumed to be the same.
+#[derive(Debug, Clone)]
+pub struct EditableText {
+    /// The text node which the fragment is in.
+    pub node: Rc<RefCell<DomNode>>,
+    /// The offset in the data of the node at which the fragment starts, in characters.
+    pub offset: usize,
+    /// The horizontal positions of the caret on the canvas at the offsets in the fragment, from its start to
+    /// its end.
+    pub caret_positions: Vec<f64>,
+    /// The area of the line of the fragment on the canvas.
+    pub rect: Rect,
+    pub baseline: f64,
+    pub font: SelectedFont,
+    /// 0.0 <= (r, g, b, a) <= 1.0
+    pub color: (f64, f64, f64, f64),
+}
+
+impl EditableText {
+    /// Returns whether the caret at the offset in the data of the node is in the fragment.
+    pub fn contains(&self, node: &Rc<RefCell<DomNode>>, offset: usize) -> bool {
+        Rc::ptr_eq(&self.node, node)
+            && (self.offset..self.offset + self.caret_positions.len()).contains(&offset)
+    }
+
+    /// Returns the offset in the data of the node of the caret nearest to the horizontal position.
+    pub fn get_offset_at(&self, x: f64) -> usize {
+        let i = self
+            .caret_positions
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (*a - x).abs().total_cmp(&(*b - x).abs()))
+            .map_or(0, |(i, _)| i);
+        self.offset + i
+    }
+
+    /// Returns the objects drawing the caret at the offset in the data of the node, and the text being
+    /// composed there with an underline, in whose middle the caret is placed at the cursor of the input
+    /// method. The caret is painted in the color of the text, which is `caret-color: auto`.
+    /// todo: Lay out the composed text in the line instead of painting it over the text after the caret.
+    /// https://drafts.csswg.org/css-ui/#caret-color
+    pub fn get_caret_objects(
+        &self,
+        offset: usize,
+        composition: Option<&Composition>,
+        is_caret_visible: bool,
+        draw_ctx: &pango::Context,
+    ) -> Vec<RenderObject> {
+        let Some(&x) = self.caret_positions.get(offset.saturating_sub(self.offset)) else {
+            return Vec::new();
+        };
+        let mut objects = Vec::new();
+        let mut caret_x = x;
+        if let Some(composition) = composition {
+            let extents = self.font.measure(draw_ctx, &composition.text);
+            let top = self.baseline - extents.ascent as f64;
+            objects.push(RenderObject::Text {
+                text: composition.text.clone(),
+                x,
+                y: top,
+                font_family: self.font.families.clone(),
+                font_size: self.font.size as f64,
+                font_weight: self.font.get_weight_name(),
+                font_style: self.font.get_style_name(),
+                color: self.color,
+            });
+            let metrics = self.font.get_decoration_metrics(draw_ctx);
+            objects.extend(draw_decoration_line(
+                (
+                    x,
+                    self.baseline - metrics.underline_position as f64,
+                    extents.width as f64,
+                ),
+                metrics.underline_thickness.max(1.0) as f64,
+                "solid",
+                self.color,
+            ));
+            let before_cursor = composition
+                .text
+                .chars()
+                .take(composition.cursor)
+                .collect::<String>();
+            caret_x += self.font.measure(draw_ctx, &before_cursor).width as f64;
+        }
+        if is_caret_visible {
+            objects.push(RenderObject::Rect {
+                x: caret_x,
+                y: self.rect.y,
+                width: CARET_WIDTH,
+                height: self.rect.height,
+                color: self.color,
+                border_radius: (0.0, 0.0, 0.0, 0.0),
+            });
+        }
+        objects
+    }
+}
+
+/// The width of the caret, which is the one of the text cursors of GTK.
+const CARET_WIDTH: f64 = 1.0;
+
+/// The color of the wavy lines under the misspelled words.
+const SPELLING_ERROR_COLOR: (f64, f64, f64, f64) = (1.0, 0.0, 0.0, 1.0);
+
+/// A part of a fragment painted with the same families of the font, whose top is placed so that its
+/// baseline is aligned with the one of the fragment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSegment {
+    pub text: String,
+    pub x: f32,
+    pub y: f32,
+    pub families: Vec<String>,
+}
+
+/// A run of the glyphs shaped with the same font in a line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRun {
+    pub text: String,
+    pub font: String,
+    pub advance: f32,
 }
 
 impl LayoutBox for Text {
+    #[tracing::instrument(skip_all)]
     fn layout(
         &mut self,
         containing_block_info: &LayoutInfo,
         parent_info: Option<LayoutInfo>,
         prev_sibling_info: Option<LayoutInfo>,
     ) {
-        self.calc_used_values();
-        self.calc_width_and_height(containing_block_info);
+        self.calc_used_values(containing_block_info);
         self.calc_pos(containing_block_info, parent_info, prev_sibling_info);
+        self.layout_single_line();
     }
 
     fn layout_children(&mut self, _: &LayoutInfo) {}
 }
 
 impl Text {
-    /// Removes unnecessary whitespace from the text.
-    /// https://developer.mozilla.org/en-US/docs/Web/API/Document_Object_Model/Whitespace
-    pub fn trim_text(&mut self, is_first_child: bool, is_last_child: bool) -> Result<()> {
-        let text = self
-            .style_node
-            .borrow()
-            .dom_node
-            .borrow()
-            .get_inside_text()
-            .unwrap();
-
-        let text = Regex::new(r"[ \t]*\n[ \t]*")?
-            // 1. All spaces and tabs immediately before and after a line break are ignored.
-            .replace_all(&text, "\n")
-            // 2. All tab characters are converted to space characters.
-            .replace("\t", " ")
-            // 3. All line breaks are transformed to spaces.
-            .replace("\n", " ");
-
-        let text = Regex::new(r" +")?
-            // 4. Any space immediately following another space (even across two separate inline elements) is ignored.
-            .replace_all(&text, " ");
-
-        let text = match self.style_node.borrow().get_display_type() {
-            // 5. All spaces at the beginning and end of the block box are removed.
-            DisplayOutside::Block => text.trim(),
-            // 5'. Sequences of spaces at the beginning and end of an element are removed.
-            DisplayOutside::Inline => {
-                if is_first_child {
-                    text.trim_start()
-                } else if is_last_child {
-                    text.trim_end()
-                } else {
-                    &text
-                }
-            }
-        };
-
-        self.style_node
-            .borrow_mut()
-            .dom_node
-            .borrow_mut()
-            .set_inside_text(text);
-
-        Ok(())
-    }
-
-    fn calc_used_values(&mut self) {
+    fn calc_used_values(&mut self, containing_block_info: &LayoutInfo) {
+        let cb_width = containing_block_info.get_content_width();
         [
             (
                 self.layout_info.used_values.margin.left,
@@ -103,8 +255,9 @@ impl Text {
         .for_each(|(used_margin, comp_margin)| {
             *used_margin = match comp_margin {
                 CssValue::Ident(v) if v == "auto" => 0.0,
-                CssValue::Length(..) => comp_margin.to_px().unwrap(),
-                CssValue::Percentage(_) => unimplemented!(),
+                CssValue::Length(..) | CssValue::Percentage(_) | CssValue::Calc(_) => {
+                    comp_margin.resolve_px(cb_width).unwrap()
+                }
                 _ => unreachable!(),
             };
         });
@@ -134,110 +287,555 @@ impl Text {
             + self.layout_info.used_values.padding.top;
     }
 
-    fn calc_width_and_height(&mut self, containing_block_info: &LayoutInfo) {
-        let font_size = self.style_node.borrow().style.font_size.to_px().unwrap();
-        let font_family = self
-            .style_node
-            .borrow()
-            .style
-            .font_family
-            .to_name_list()
-            .unwrap();
-        let font_weight = &self
+    /// Lays out the whole text in a line from the position of the box, which is the case where it's laid out
+    /// outside of the line boxes of a block container, e.g. in the base of a ruby.
+    fn layout_single_line(&mut self) {
+        let white_space = self.style_node.borrow().style.white_space.clone();
+        let text = collapse_white_space(&self.get_text(), &white_space, &mut true);
+        let text = self
             .style_node
             .borrow()
             .style
-            .font_weight
-            .to_name()
-            .unwrap();
-        let font_desc = pango::FontDescription::from_string(&format!(
-            "{} {} {}px",
-            font_family.join(", "),
-            font_weight,
-            font_size
-        ));
-
-        let max_line_width = self.wrap_text(&font_desc, containing_block_info);
-        let height = self
-            .style_node
+            .text_transform
+            .apply(&text, None);
+        let text = if white_space.collapses_spaces() {
+            text.trim_end_matches(' ').to_string()
+        } else {
+            text
+        };
+        let extents = self.get_selected_font().measure(&self.draw_ctx, &text);
+        self.set_lines(vec![TextLine {
+            text,
+            x: self.layout_info.pos.x,
+            y: self.layout_info.pos.y,
+            width: extents.width,
+            height: extents.height,
+            ascent: extents.ascent,
+            break_offset: None,
+            level: None,
+        }]);
+    }
+
+    /// Sets the fragments of the laid out text, whose bounding box is the box of the text.
+    pub fn set_lines(&mut self, lines: Vec<TextLine>) {
+        let (left, top, right, bottom) = lines.iter().fold(
+            (
+                f32::INFINITY,
+                f32::INFINITY,
+                f32::NEG_INFINITY,
+                f32::NEG_INFINITY,
+            ),
+            |(left, top, right, bottom), line| {
+                (
+                    left.min(line.x),
+                    top.min(line.y),
+                    right.max(line.x + line.width),
+                    bottom.max(line.y + line.height),
+                )
+            },
+        );
+        if !lines.is_empty() {
+            self.layout_info.pos.x = left;
+            self.layout_info.pos.y = top;
+            self.layout_info.size.width = right - left;
+            self.layout_info.size.height = bottom - top;
+        }
+        self.lines = lines;
+    }
+
+    /// Returns the text of the node as it is, whose white space is processed when it's laid out.
+    pub fn get_text(&self) -> String {
+        self.style_node
             .borrow()
             .dom_node
             .borrow()
             .get_inside_text()
             .unwrap()
-            .split('\n')
-            .map(|line| {
-                let layout = pango::Layout::new(&self.draw_ctx);
-                layout.set_font_description(Some(&font_desc));
-                layout.set_text(line);
-                layout.size().1 as f32 / pango::SCALE as f32
+    }
+
+    /// Returns the runs of the glyphs into which the line is shaped, with their advance widths. Each segment
+    /// of the line in a family of the fallback is shaped separately.
+    pub fn get_runs(&self, line: &str) -> Vec<TextRun> {
+        let font = self.get_selected_font();
+        let mut runs = Vec::new();
+        for segment in font.split_by_fallback(&self.draw_ctx, line) {
+            let text = &line[segment.range];
+            let layout = pango::Layout::new(&self.draw_ctx);
+            layout.set_font_description(Some(&font.to_description_in(segment.family)));
+            layout.set_text(text);
+            let Some(layout_line) = layout.line_readonly(0) else {
+                continue;
+            };
+            runs.extend(layout_line.runs().iter().map(|run| {
+                let item = run.item();
+                let start = item.offset() as usize;
+                let end = start + item.length() as usize;
+                TextRun {
+                    text: text.get(start..end).unwrap_or_default().to_string(),
+                    font: item.analysis().font().describe().to_string(),
+                    advance: run.glyph_string().width() as f32 / pango::SCALE as f32,
+                }
+            }));
+        }
+        runs
+    }
+
+    /// Returns the segments of the fragment painted with the families of the fallback, which are placed from
+    /// the right in a right-to-left fragment since it's already ordered visually.
+    pub fn get_segments(&self, line: &TextLine) -> Vec<TextSegment> {
+        let font = self.get_selected_font();
+        let segments = font.split_by_fallback(&self.draw_ctx, &line.text);
+        if segments.len() <= 1 {
+            let family = segments.first().and_then(|segment| segment.family);
+            return vec![TextSegment {
+                text: line.get_display_text(),
+                x: line.x,
+                y: line.y,
+                families: font.get_families_in(family),
+            }];
+        }
+        let is_rtl = line.level.is_some_and(|level| level % 2 == 1);
+        let mut offset = 0.0;
+        segments
+            .into_iter()
+            .map(|segment| {
+                let text = &line.text[segment.range];
+                let extents = font.measure(&self.draw_ctx, text);
+                let x = if is_rtl {
+                    line.x + line.width - offset - extents.width
+                } else {
+                    line.x + offset
+                };
+                offset += extents.width;
+                TextSegment {
+                    text: line.to_display_text(text),
+                    x,
+                    y: line.get_baseline() - extents.ascent,
+                    families: font.get_families_in(segment.family),
+                }
             })
-            .sum::<f32>();
+            .collect()
+    }
 
-        self.layout_info.size.width = max_line_width as f32;
-        self.layout_info.size.height = height;
+    /// Returns the lines of the text decorations over the fragment, which are the underlines and the
+    /// overlines painted before the text and the line-throughs painted after it. The positions and the
+    /// thicknesses of the lines are given by the font of the text.
+    /// todo: Skip the ink of the glyphs, and align the underlines of a decorating box across its texts.
+    /// https://drafts.csswg.org/css-text-decor/#painting-order
+    pub fn get_decoration_objects(
+        &self,
+        line: &TextLine,
+    ) -> (Vec<RenderObject>, Vec<RenderObject>) {
+        let (mut before, mut after) = (Vec::new(), Vec::new());
+        if self.decorations.is_empty() || line.width == 0.0 {
+            return (before, after);
+        }
+        let metrics = self
+            .get_selected_font()
+            .get_decoration_metrics(&self.draw_ctx);
+        let baseline = line.get_baseline();
+        for decoration in &self.decorations {
+            let color = decoration.color.to_color().unwrap().to_unit_rgba();
+            let style = decoration.style.to_name().unwrap();
+            let thickness = |from_font: f32| match &decoration.thickness {
+                CssValue::Ident(_) => from_font,
+                thickness => thickness.to_px().unwrap(),
+            };
+            for kind in &decoration.line {
+                let (top, thickness, objects) = match kind.to_name().unwrap().as_str() {
+                    "underline" => (
+                        baseline - metrics.underline_position,
+                        thickness(metrics.underline_thickness),
+                        &mut before,
+                    ),
+                    "overline" => (line.y, thickness(metrics.underline_thickness), &mut before),
+                    "line-through" => {
+                        let from_font = metrics.strikethrough_thickness;
+                        let thickness = thickness(from_font);
+                        (
+                            baseline - metrics.strikethrough_position
+                                + (from_font - thickness) / 2.0,
+                            thickness,
+                            &mut after,
+                        )
+                    }
+                    _ => continue,
+                };
+                objects.extend(draw_decoration_line(
+                    (line.x as f64, top as f64, line.width as f64),
+                    thickness.max(1.0) as f64,
+                    &style,
+                    color,
+                ));
+            }
+        }
+        (before, after)
     }
 
-    /// Wraps the text by inserting line breaks at appropriate places to fit the width
-    /// of the containing block, and returns the maximum line width and the number of lines.
-    ///
-    /// This implementation is quite simple and doesn't take into account the line box system
-    /// and Unicode line-break rules. It also assumes that a text node is the only child of
-    /// a block-level box (if it isn't, the line breaks won't work properly).
-    /// https://drafts.csswg.org/css-text/#line-breaking
-    fn wrap_text(
-        &mut self,
-        font_desc: &pango::FontDescription,
-        containing_block_info: &LayoutInfo,
-    ) -> f64 {
-        let text = self
+    /// Returns the misspelled words in the lines of the text if its spelling is checked with the dictionary
+    /// of the process.
+    /// todo: Find the words in the fragments ordered from right to left.
+    pub fn get_misspelled_words(&self) -> Vec<MisspelledWord> {
+        let node = Rc::clone(&self.style_node.borrow().dom_node);
+        let Some(dictionary) = get_dictionary().filter(|_| is_spellchecked(&node)) else {
+            return Vec::new();
+        };
+        let font = self.get_selected_font();
+        let mut words = Vec::new();
+        let mut count = 0;
+        for line in &self.lines {
+            let ranges = dictionary.find_misspelled_words(&line.text);
+            let first_index = count;
+            count += ranges.len();
+            if line.level.is_some_and(|level| level % 2 == 1) {
+                continue;
+            }
+            for (i, range) in ranges.into_iter().enumerate() {
+                let x = line.x
+                    + font
+                        .measure(&self.draw_ctx, &line.text[..range.start])
+                        .width;
+                let width = font
+                    .measure(&self.draw_ctx, &line.text[range.clone()])
+                    .width;
+                words.push(MisspelledWord {
+                    node: Rc::clone(&node),
+                    index: first_index + i,
+                    word: line.text[range].to_string(),
+                    rect: Rect::new(x as f64, line.y as f64, width as f64, line.height as f64),
+                    baseline: line.get_baseline() as f64,
+                });
+            }
+        }
+        words
+    }
+
+    /// Returns the objects drawing the wavy lines under the misspelled words of the text.
+    pub fn get_spelling_error_objects(&self) -> Vec<RenderObject> {
+        let words = self.get_misspelled_words();
+        if words.is_empty() {
+            return Vec::new();
+        }
+        let metrics = self
+            .get_selected_font()
+            .get_decoration_metrics(&self.draw_ctx);
+        words
+            .iter()
+            .flat_map(|word| {
+                draw_decoration_line(
+                    (
+                        word.rect.x,
+                        word.baseline - metrics.underline_position as f64,
+                        word.rect.width,
+                    ),
+                    metrics.underline_thickness.max(1.0) as f64,
+                    "wavy",
+                    SPELLING_ERROR_COLOR,
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the fragments of the text in the lines if it's editable, where the caret can be placed.
+    /// todo: Place the caret in the fragments ordered from right to left.
+    pub fn get_editable_texts(&self) -> Vec<EditableText> {
+        let node = Rc::clone(&self.style_node.borrow().dom_node);
+        if !DomNode::is_editable(&node) {
+            return Vec::new();
+        }
+        let data = self.get_text();
+        let font = self.get_selected_font();
+        let color = self
             .style_node
             .borrow()
-            .dom_node
-            .borrow()
-            .get_inside_text()
-            .unwrap();
-        let mut new_text = String::new();
-        let mut curr_width = 0.0;
-        let mut max_line_width = 0.0;
-
+            .style
+            .color
+            .to_color()
+            .unwrap()
+            .to_unit_rgba();
         let layout = pango::Layout::new(&self.draw_ctx);
-        layout.set_font_description(Some(font_desc));
-        layout.set_text(" ");
-        let space_width = layout.size().0 as f64 / pango::SCALE as f64;
-
-        text.split(' ').for_each(|word| {
-            let layout = pango::Layout::new(&self.draw_ctx);
-            layout.set_font_description(Some(font_desc));
-            layout.set_text(word);
-            let word_width = layout.size().0 as f64 / pango::SCALE as f64;
-            curr_width += word_width;
-            if curr_width as f32 >= containing_block_info.used_values.width.unwrap() {
-                curr_width -= word_width;
-                if new_text.ends_with(' ') {
-                    new_text.pop();
-                    curr_width -= space_width;
+        layout.set_font_description(Some(&font.to_description()));
+        self.lines
+            .iter()
+            .filter(|line| line.level.is_none_or(|level| level % 2 == 0))
+            .map(|line| {
+                layout.set_text(&line.text);
+                let caret_positions = line
+                    .text
+                    .char_indices()
+                    .map(|(i, _)| i)
+                    .chain(std::iter::once(line.text.len()))
+                    .map(|i| {
+                        line.x as f64
+                            + layout.index_to_pos(i as i32).x() as f64 / pango::SCALE as f64
+                    })
+                    .collect();
+                let offset = line
+                    .break_offset
+                    .and_then(|offset| data.get(..offset))
+                    .map_or(0, |before| before.chars().count());
+                EditableText {
+                    node: Rc::clone(&node),
+                    offset,
+                    caret_positions,
+                    rect: Rect::new(
+                        line.x as f64,
+                        line.y as f64,
+                        line.width as f64,
+                        line.height as f64,
+                    ),
+                    baseline: line.get_baseline() as f64,
+                    font: font.clone(),
+                    color,
                 }
-                new_text.push_str(format!("\n{word} ").as_str());
-                max_line_width =
-                    max_by(max_line_width, curr_width, |a, b| a.partial_cmp(b).unwrap());
-                curr_width = word_width;
+            })
+            .collect()
+    }
+
+    /// Returns the font selected for the computed style of the text.
+    pub fn get_selected_font(&self) -> SelectedFont {
+        SelectedFont::select(&self.draw_ctx, &self.style_node.borrow().style)
+    }
+
+    /// Returns whether the text is only the white space which collapses away, e.g. between block-level boxes.
+    pub fn is_collapsible_white_space(&self) -> bool {
+        let white_space = &self.style_node.borrow().style.white_space;
+        white_space.collapses_spaces()
+            && self.get_text().chars().all(|c| {
+                matches!(c, ' ' | '\t') || (c == '\n' && !white_space.preserves_line_breaks())
+            })
+    }
+}
+
+/// Returns the objects drawing a line of a text decoration in the style, whose left, top and width are
+/// `(x, y, width)`.
+fn draw_decoration_line(
+    (x, y, width): (f64, f64, f64),
+    thickness: f64,
+    style: &str,
+    color: (f64, f64, f64, f64),
+) -> Vec<RenderObject> {
+    let rect = |x: f64, y: f64, width: f64| RenderObject::Rect {
+        x,
+        y,
+        width,
+        height: thickness,
+        color,
+        border_radius: (0.0, 0.0, 0.0, 0.0),
+    };
+    // The dots and the dashes are repeated with the gaps as long as the thickness.
+    let dashes = |length: f64| {
+        let period = length + thickness;
+        (0..(width / period).ceil() as usize)
+            .map(|i| {
+                rect(
+                    x + i as f64 * period,
+                    y,
+                    length.min(width - i as f64 * period),
+                )
+            })
+            .collect()
+    };
+    match style {
+        "double" => vec![rect(x, y, width), rect(x, y + thickness * 2.0, width)],
+        "dotted" => dashes(thickness),
+        "dashed" => dashes(thickness * 3.0),
+        "wavy" => {
+            // The half waves are the cubic Bézier curves whose control points are 4/3 of the amplitude away
+            // from the center, which are repeated and clipped to the width.
+            let (amplitude, half_wave) = (thickness * 1.5, thickness * 3.0);
+            let center = y + thickness / 2.0;
+            let mut segments = vec![PathSegment::MoveTo(x, center)];
+            let mut start = x;
+            let mut direction = -1.0;
+            while start < x + width {
+                let control = center + direction * amplitude * 4.0 / 3.0;
+                segments.push(PathSegment::CurveTo(
+                    start + half_wave / 3.0,
+                    control,
+                    start + half_wave * 2.0 / 3.0,
+                    control,
+                    start + half_wave,
+                    center,
+                ));
+                start += half_wave;
+                direction = -direction;
+            }
+            vec![RenderObject::Path {
+                segments,
+                fill: None,
+                even_odd: false,
+                stroke: Some(color),
+                stroke_width: thickness,
+                clip: (
+                    x,
+                    center - amplitude - thickness,
+                    width,
+                    (amplitude + thickness) * 2.0,
+                ),
+            }]
+        }
+        _ => vec![rect(x, y, width)],
+    }
+}
+
+/// Collapses the white space of the text by `white-space` before it's laid out in the line boxes. The spaces and
+/// tabs around the newlines are removed, the newlines are turned into spaces unless they're preserved, and the
+/// spaces following another collapsible space collapse, even across the boundaries of the inline boxes, for
+/// which `follows_space` is whether the text follows a collapsible space and is updated for the next text.
+/// The spaces at the starts and the ends of the lines are removed later while the lines are laid out.
+/// todo: Remove the newlines between the East Asian characters, and support `tab-size`.
+/// https://drafts.csswg.org/css-text/#white-space-phase-1
+pub fn collapse_white_space(
+    text: &str,
+    white_space: &WhiteSpaceProp,
+    follows_space: &mut bool,
+) -> String {
+    if !white_space.collapses_spaces() {
+        if !text.is_empty() {
+            *follows_space = false;
+        }
+        return text.to_string();
+    }
+    let segments = text.split('\n').collect::<Vec<_>>();
+    let last = segments.len() - 1;
+    let text = segments
+        .iter()
+        .enumerate()
+        .map(|(i, segment)| {
+            let segment = if i > 0 {
+                segment.trim_start_matches([' ', '\t'])
+            } else {
+                segment
+            };
+            if i < last {
+                segment.trim_end_matches([' ', '\t'])
             } else {
-                new_text.push_str(format!("{word} ").as_str());
-                curr_width += space_width;
+                segment
             }
+        })
+        .collect::<Vec<_>>()
+        .join(if white_space.preserves_line_breaks() {
+            "\n"
+        } else {
+            " "
         });
-        if new_text.ends_with(' ') {
-            new_text.pop();
-            curr_width -= space_width;
+
+    let mut collapsed = String::with_capacity(text.len());
+    for c in text.chars() {
+        let c = if c == '\t' { ' ' } else { c };
+        if c == ' ' && *follows_space {
+            continue;
         }
-        max_line_width = max_by(max_line_width, curr_width, |a, b| a.partial_cmp(b).unwrap());
-        self.style_node
-            .borrow()
-            .dom_node
-            .borrow_mut()
-            .set_inside_text(&new_text);
+        // The spaces after a preserved newline are at the start of a line, where they're removed.
+        *follows_space = matches!(c, ' ' | '\n');
+        collapsed.push(c);
+    }
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::cssom::ComponentValue;
+    use crate::renderer::css::token::CssToken;
+    use crate::renderer::html::dom::NodeType;
+    use crate::renderer::layout::font::FontFaceDescriptor;
+    use crate::renderer::style::property::{CssProperty, FontStyle};
 
-        max_line_width
+    #[test]
+    fn collapse_spaces() {
+        let collapse = |text: &str, keyword: &str, follows_space: bool| {
+            let white_space = WhiteSpaceProp::parse(&[ComponentValue::PreservedToken(
+                CssToken::Ident(keyword.to_string()),
+            )])
+            .unwrap();
+            let mut follows_space = follows_space;
+            let text = collapse_white_space(text, &white_space, &mut follows_space);
+            (text, follows_space)
+        };
+        assert_eq!(
+            collapse("  a \t b  \n\n  c ", "normal", false),
+            (" a b c ".to_string(), true)
+        );
+        // The space at the start collapses with the one before the text.
+        assert_eq!(
+            collapse(" a\tb", "nowrap", true),
+            ("a b".to_string(), false)
+        );
+        assert_eq!(
+            collapse("a  \n  b\n", "pre-line", false),
+            ("a\nb\n".to_string(), true)
+        );
+        assert_eq!(
+            collapse("  a \n b", "pre", true),
+            ("  a \n b".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn draw_decoration_styles() {
+        let color = (0.0, 0.0, 0.0, 1.0);
+        let rects = |objects: Vec<RenderObject>| {
+            objects
+                .into_iter()
+                .map(|object| match object {
+                    RenderObject::Rect { x, y, width, .. } => (x, y, width),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            rects(draw_decoration_line(
+                (10.0, 20.0, 30.0),
+                2.0,
+                "double",
+                color
+            )),
+            vec![(10.0, 20.0, 30.0), (10.0, 24.0, 30.0)]
+        );
+        // The last dash is cut at the end of the line.
+        assert_eq!(
+            rects(draw_decoration_line((0.0, 0.0, 20.0), 2.0, "dashed", color)),
+            vec![(0.0, 0.0, 6.0), (8.0, 0.0, 6.0), (16.0, 0.0, 4.0)]
+        );
+        let wavy = draw_decoration_line((0.0, 10.0, 20.0), 2.0, "wavy", color);
+        let [RenderObject::Path { segments, clip, .. }] = wavy.as_slice() else {
+            unreachable!()
+        };
+        assert_eq!(segments.len(), 5);
+        assert_eq!(*clip, (0.0, 6.0, 20.0, 10.0));
+    }
+
+    #[test]
+    fn place_caret_in_editable_text() {
+        let node = Rc::new(RefCell::new(DomNode::new(NodeType::Text(
+            "ab cd".to_string(),
+        ))));
+        let text = EditableText {
+            node: Rc::clone(&node),
+            offset: 3,
+            caret_positions: vec![10.0, 18.0, 26.0],
+            rect: Rect::new(10.0, 20.0, 16.0, 16.0),
+            baseline: 32.0,
+            font: SelectedFont {
+                families: vec!["serif".to_string()],
+                face: FontFaceDescriptor {
+                    weight: 400.0,
+                    style: FontStyle::Normal,
+                    stretch: 100.0,
+                },
+                size: 16.0,
+            },
+            color: (0.0, 0.0, 0.0, 1.0),
+        };
+        assert_eq!(text.get_offset_at(0.0), 3);
+        assert_eq!(text.get_offset_at(21.0), 4);
+        assert_eq!(text.get_offset_at(23.0), 5);
+        assert!(text.contains(&node, 5) && !text.contains(&node, 2));
+        let other = Rc::new(RefCell::new(DomNode::new(NodeType::Text(
+            "ab cd".to_string(),
+        ))));
+        assert!(!text.contains(&other, 4));
     }
 }
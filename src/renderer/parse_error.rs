@@ -0,0 +1,79 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::renderer::source::SourcePosition;
+
+/// A parse error reported by a tokenizer or a parser. Parse errors are not fatal, and the parsers
+/// recover from them in the ways defined in the specifications.
+/// - https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+/// - https://www.w3.org/TR/css-syntax-3/#error-handling
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseErrorReport {
+    /// The code of the error, such as `unexpected-null-character`.
+    pub code: String,
+    /// Additional information about the error, such as the token that caused it.
+    pub detail: Option<String>,
+    /// The position of the error, if the input is a source text rather than tokens.
+    pub position: Option<SourcePosition>,
+    /// The source text around the position of the error.
+    pub context: Option<String>,
+}
+
+impl fmt::Display for ParseErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(position) = &self.position {
+            write!(f, "{}: ", position)?;
+        }
+        write!(f, "{} parse error", self.code)?;
+        if let Some(detail) = &self.detail {
+            write!(f, " ({})", detail)?;
+        }
+        if let Some(context) = &self.context {
+            write!(f, " near {:?}", context)?;
+        }
+        fmt::Result::Ok(())
+    }
+}
+
+/// A sink for the parse errors reported by the tokenizers and the parsers.
+pub trait ParseErrorHandler: fmt::Debug {
+    fn handle(&mut self, error: ParseErrorReport);
+}
+
+/// The handler shared between a tokenizer and the parser consuming its tokens.
+pub type SharedParseErrorHandler = Rc<RefCell<dyn ParseErrorHandler>>;
+
+/// Prints parse errors to the standard error. This is the default handler.
+#[derive(Debug, Default)]
+pub struct StderrParseErrorHandler;
+
+impl ParseErrorHandler for StderrParseErrorHandler {
+    fn handle(&mut self, error: ParseErrorReport) {
+        eprintln!("{}", error);
+    }
+}
+
+impl StderrParseErrorHandler {
+    pub fn new_shared() -> SharedParseErrorHandler {
+        Rc::new(RefCell::new(Self))
+    }
+}
+
+/// Collects parse errors in the order they are reported.
+#[derive(Debug, Default)]
+pub struct ParseErrorCollector {
+    errors: Vec<ParseErrorReport>,
+}
+
+impl ParseErrorHandler for ParseErrorCollector {
+    fn handle(&mut self, error: ParseErrorReport) {
+        self.errors.push(error);
+    }
+}
+
+impl ParseErrorCollector {
+    pub fn take_errors(&mut self) -> Vec<ParseErrorReport> {
+        std::mem::take(&mut self.errors)
+    }
+}
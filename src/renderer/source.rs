@@ -0,0 +1,180 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+/// The maximum number of characters shown before and after a position in the context of it.
+const CONTEXT_RADIUS: usize = 30;
+
+/// A position in the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourcePosition {
+    /// The byte offset from the start of the source text.
+    pub offset: usize,
+    /// The 1-based line number.
+    pub line: usize,
+    /// The 1-based column number, which counts characters rather than bytes.
+    pub column: usize,
+}
+
+impl Default for SourcePosition {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl fmt::Display for SourcePosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl SourcePosition {
+    /// Returns the position of `relative`, which is relative to this position, in the enclosing source text.
+    fn resolve(&self, relative: SourcePosition) -> SourcePosition {
+        SourcePosition {
+            offset: self.offset + relative.offset,
+            line: self.line + relative.line - 1,
+            column: if relative.line == 1 {
+                self.column + relative.column - 1
+            } else {
+                relative.column
+            },
+        }
+    }
+}
+
+/// A range in the source text. The end is exclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourceSpan {
+    pub start: SourcePosition,
+    pub end: SourcePosition,
+}
+
+impl fmt::Display for SourceSpan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// Computes the positions of characters in a source text. Positions are mostly requested in ascending order,
+/// so the computation is resumed from the last requested position.
+#[derive(Debug, Default)]
+pub struct SourceLocator {
+    /// The position of the start of the source text in the enclosing one, e.g. a style sheet in an HTML document.
+    start: SourcePosition,
+    index: usize,
+    line_start: usize,
+    position: SourcePosition,
+    /// Whether the start of the source text has been discarded, in which case the computation can't be restarted.
+    discarded: bool,
+}
+
+impl SourceLocator {
+    pub fn new(start: SourcePosition) -> Self {
+        Self {
+            start,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the position of the character at `index`.
+    pub fn position(&mut self, chars: &VecDeque<char>, index: usize) -> SourcePosition {
+        let mut index = index.min(chars.len());
+        if index < self.index {
+            if self.discarded {
+                index = self.index;
+            } else {
+                *self = Self::new(self.start);
+            }
+        }
+        for (i, c) in chars.range(self.index..index).enumerate() {
+            self.position.offset += c.len_utf8();
+            if *c == '\n' {
+                self.position.line += 1;
+                self.line_start = self.index + i + 1;
+            }
+        }
+        self.index = index;
+        self.position.column = index - self.line_start + 1;
+        self.start.resolve(self.position)
+    }
+
+    /// Updates the state after the first `count` characters are removed from the source text.
+    /// The positions of the removed characters can't be computed after this.
+    pub fn discard(&mut self, chars: &VecDeque<char>, count: usize) {
+        if self.index < count {
+            self.position(chars, count);
+        }
+        self.index -= count;
+        self.line_start = self.line_start.saturating_sub(count);
+        self.discarded = true;
+    }
+
+    /// Returns the span from the character at `start` to the character before `end`.
+    pub fn span(&mut self, chars: &VecDeque<char>, start: usize, end: usize) -> SourceSpan {
+        SourceSpan {
+            start: self.position(chars, start),
+            end: self.position(chars, end),
+        }
+    }
+
+    /// Returns the position of the character at `index` and the source text around it.
+    pub fn locate(&mut self, chars: &VecDeque<char>, index: usize) -> (SourcePosition, String) {
+        let position = self.position(chars, index);
+        let index = self.index;
+        let context_start = self.line_start.max(index.saturating_sub(CONTEXT_RADIUS));
+        let context = chars
+            .range(context_start..)
+            .take(index - context_start + CONTEXT_RADIUS)
+            .take_while(|c| **c != '\n')
+            .collect::<String>();
+        (position, context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate() {
+        let chars = "ab\ncdé\nf".chars().collect::<VecDeque<_>>();
+        let mut locator = SourceLocator::default();
+        let position = |offset, line, column| SourcePosition {
+            offset,
+            line,
+            column,
+        };
+
+        assert_eq!(
+            locator.locate(&chars, 1),
+            (position(1, 1, 2), "ab".to_string())
+        );
+        assert_eq!(
+            locator.locate(&chars, 5),
+            (position(5, 2, 3), "cdé".to_string())
+        );
+        assert_eq!(
+            locator.locate(&chars, 7),
+            (position(8, 3, 1), "f".to_string())
+        );
+        // The position after the end of the input is clamped.
+        assert_eq!(
+            locator.locate(&chars, 100),
+            (position(9, 3, 2), "f".to_string())
+        );
+        // Requesting a preceding position restarts the computation.
+        assert_eq!(
+            locator.locate(&chars, 0),
+            (position(0, 1, 1), "ab".to_string())
+        );
+
+        // Positions in an embedded source text are resolved in the enclosing one.
+        let mut locator = SourceLocator::new(position(10, 2, 5));
+        assert_eq!(locator.position(&chars, 1), position(11, 2, 6));
+        assert_eq!(locator.position(&chars, 4), position(14, 3, 2));
+    }
+}
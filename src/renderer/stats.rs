@@ -0,0 +1,179 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+use anyhow::Result;
+
+use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::css::media::MediaEnvironment;
+use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::html::dom::{DomNode, NodeType};
+
+/// The statistics of the stages of the rendering pipeline for a document, with which one can check that
+/// the stages do as much work as expected on the document, e.g. how many elements each rule matches.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PipelineStats {
+    /// The numbers of the HTML tokens by type.
+    pub html_tokens: Vec<(&'static str, usize)>,
+    /// The numbers of the CSS tokens in the `style` elements by type.
+    /// todo: Count the tokens of the external style sheets too.
+    pub css_tokens: BTreeMap<&'static str, usize>,
+    pub elements: usize,
+    /// The numbers of the style sheets, the style rules, the selectors and the declarations, including the
+    /// ones of the user agent.
+    pub style_sheets: usize,
+    pub rules: usize,
+    pub selectors: usize,
+    pub declarations: usize,
+    /// The selector list of each style rule of the document which applies to the media, and the number of
+    /// elements it matches.
+    pub rule_matches: Vec<(String, usize)>,
+    /// The number of times the boxes are laid out, which is larger than the number of the boxes if some of
+    /// them are laid out more than once, e.g. to measure them.
+    pub box_layouts: usize,
+}
+
+impl PipelineStats {
+    /// Collects the statistics of the parsing and the selector matching of the document. The first style
+    /// sheet must be the one of the user agent, whose rules are not listed in `rule_matches`.
+    pub fn collect(
+        document: &Rc<RefCell<DomNode>>,
+        html_tokens: Vec<(&'static str, usize)>,
+        style_sheets: &[StyleSheet],
+        media: &MediaEnvironment,
+    ) -> Result<Self> {
+        let mut stats = Self {
+            html_tokens,
+            style_sheets: style_sheets.len(),
+            ..Default::default()
+        };
+
+        for node in DomNode::get_descendants(document) {
+            let NodeType::Element(elm) = &node.borrow().node_type else {
+                continue;
+            };
+            stats.elements += 1;
+            if elm.tag_name != "style" {
+                continue;
+            }
+            let css = node
+                .borrow()
+                .children
+                .iter()
+                .filter_map(|child| match &child.borrow().node_type {
+                    NodeType::Text(text) => Some(text.clone()),
+                    _ => None,
+                })
+                .collect::<String>();
+            for token in CssTokenizer::new(&css).tokenize()? {
+                *stats.css_tokens.entry(token.get_type_name()).or_default() += 1;
+            }
+        }
+
+        let elements = DomNode::get_descendants(document)
+            .filter(|node| matches!(node.borrow().node_type, NodeType::Element(_)))
+            .collect::<Vec<_>>();
+        for (i, style_sheet) in style_sheets.iter().enumerate() {
+            for rule in style_sheet.get_all_style_rules() {
+                stats.rules += 1;
+                stats.selectors += rule.selectors.len();
+                stats.declarations += rule.declarations.len();
+            }
+            if i == 0 {
+                continue;
+            }
+            for rule in style_sheet.get_style_rules(media) {
+                let matched = elements
+                    .iter()
+                    .filter(|node| rule.get_matched_selectors(node, None).is_some())
+                    .count();
+                let selectors = rule
+                    .selectors
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                stats.rule_matches.push((selectors, matched));
+            }
+        }
+        Ok(stats)
+    }
+}
+
+impl fmt::Display for PipelineStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "html-tokens: {}",
+            format_counts(self.html_tokens.iter().copied())
+        )?;
+        writeln!(
+            f,
+            "css-tokens: {}",
+            format_counts(self.css_tokens.iter().map(|(k, v)| (*k, *v)))
+        )?;
+        writeln!(f, "elements: {}", self.elements)?;
+        writeln!(f, "style-sheets: {}", self.style_sheets)?;
+        writeln!(f, "rules: {}", self.rules)?;
+        writeln!(f, "selectors: {}", self.selectors)?;
+        writeln!(f, "declarations: {}", self.declarations)?;
+        writeln!(f, "matched-elements-per-rule:")?;
+        for (selectors, matched) in &self.rule_matches {
+            writeln!(f, "  {} {}", matched, selectors)?;
+        }
+        writeln!(f, "box-layouts: {}", self.box_layouts)
+    }
+}
+
+fn format_counts<'a>(counts: impl Iterator<Item = (&'a str, usize)>) -> String {
+    counts
+        .map(|(name, count)| format!("{}={}", name, count))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::get_ua_style_sheet;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    #[test]
+    fn collect_stats() {
+        let html = "<!DOCTYPE html><style>p { color: red; } .a, div { margin: 0; }</style><p class=a>x</p><p>y";
+        let mut parser = HtmlParser::new(HtmlTokenizer::new(html));
+        let (document, style_sheets) = parser.parse().unwrap();
+        let style_sheets = std::iter::once(get_ua_style_sheet().unwrap())
+            .chain(style_sheets)
+            .collect::<Vec<_>>();
+        let stats = PipelineStats::collect(
+            &document,
+            parser.get_token_counts(),
+            &style_sheets,
+            &MediaEnvironment::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            stats.html_tokens,
+            [
+                ("doctype", 1),
+                ("start-tag", 3),
+                ("end-tag", 2),
+                ("comment", 0),
+                ("character", 42),
+                ("eof", 1)
+            ]
+        );
+        assert_eq!(stats.css_tokens["ident"], 6);
+        assert_eq!(stats.css_tokens["{"], 2);
+        // html, head, style, body, and the two paragraphs.
+        assert_eq!(stats.elements, 6);
+        assert_eq!(
+            stats.rule_matches,
+            [("p".to_string(), 2), (".a, div".to_string(), 1)]
+        );
+    }
+}
@@ -1,2 +1,4 @@
+pub mod forced_colors;
+pub mod invalidation;
 pub mod property;
 pub mod style_model;
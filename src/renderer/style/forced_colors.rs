@@ -0,0 +1,139 @@
+use crate::renderer::style::property::color::Color;
+use crate::renderer::style::property::CssValue;
+use crate::renderer::style::style_model::ComputedStyle;
+
+/// The palette of the system colors which replace the author colors in the forced colors mode. This is a
+/// high-contrast palette with light text on a dark background.
+/// todo: Follow the palette of the OS, and support the system color keywords in style sheets.
+/// https://drafts.csswg.org/css-color/#css-system-colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemPalette {
+    /// The background of the documents.
+    pub canvas: (u8, u8, u8),
+    /// The text on `canvas`.
+    pub canvas_text: (u8, u8, u8),
+    /// The text of unvisited links.
+    pub link_text: (u8, u8, u8),
+}
+
+impl Default for SystemPalette {
+    fn default() -> Self {
+        Self {
+            canvas: (0, 0, 0),
+            canvas_text: (255, 255, 255),
+            link_text: (255, 255, 0),
+        }
+    }
+}
+
+impl ComputedStyle {
+    /// Replaces the colors of the element with the system colors, unless `forced-color-adjust` opts it out.
+    /// The background keeps its alpha channel, so transparent backgrounds stay transparent, except for the
+    /// root element, which paints the canvas.
+    /// https://drafts.csswg.org/css-color-adjust/#forced-colors-properties
+    pub fn apply_forced_colors(
+        &mut self,
+        palette: &SystemPalette,
+        parent_style: Option<&ComputedStyle>,
+        is_link: bool,
+        is_root: bool,
+    ) {
+        if self.forced_color_adjust.is_preserve_parent_color() {
+            if let Some(parent_style) = parent_style {
+                self.color = parent_style.color.clone();
+            }
+            return;
+        }
+        if !self.forced_color_adjust.is_auto() {
+            return;
+        }
+
+        let color = |(r, g, b): (u8, u8, u8), a: f32| CssValue::Color(Color::new(r, g, b, a));
+        let text = if is_link {
+            palette.link_text
+        } else {
+            palette.canvas_text
+        };
+        self.color.value = color(text, 1.0);
+        let alpha = match self.background_color.value {
+            _ if is_root => 1.0,
+            CssValue::Color(Color { a, .. }) => a,
+            _ => 0.0,
+        };
+        self.background_color.value = color(palette.canvas, alpha);
+        self.border.border_color.value = color(text, 1.0);
+        self.text_decoration.color.value = color(text, 1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::renderer::css::get_ua_style_sheet;
+    use crate::renderer::css::media::MediaEnvironment;
+    use crate::renderer::html::dom::DocumentTree;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    #[test]
+    fn force_colors() {
+        let html = "<html><head><style>p { color: red; background-color: #ff000080; } \
+            .none { forced-color-adjust: none; } .preserve { forced-color-adjust: preserve-parent-color; color: blue; }</style>\
+            <style media=\"(forced-colors: active)\">div { color: green; }</style></head>\
+            <body><p>a<a href=\"/\">link</a></p><p class=\"none\">b<span>c</span></p>\
+            <p class=\"preserve\">d</p><div>e</div></body></html>";
+        let media = MediaEnvironment {
+            forced_colors: true,
+            ..Default::default()
+        };
+        let (root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html))
+            .set_media_environment(media)
+            .parse()
+            .unwrap();
+        let style_sheets = std::iter::once(get_ua_style_sheet().unwrap())
+            .chain(style_sheets)
+            .collect::<Vec<_>>();
+        let render_tree = DocumentTree::build(root)
+            .unwrap()
+            .to_render_tree(style_sheets, &media)
+            .unwrap();
+
+        let mut colors = Vec::new();
+        let mut stack = vec![Rc::clone(&render_tree.root)];
+        while let Some(node) = stack.pop() {
+            let style = &node.borrow().style;
+            colors.push(format!(
+                "{} {} {}",
+                node.borrow().dom_node.borrow().node_type,
+                style.color,
+                style.background_color
+            ));
+            stack.extend(node.borrow().children.iter().rev().cloned());
+        }
+
+        // The colors are replaced except for the elements which opt out. The root paints the canvas, and
+        // the style sheets for the forced colors mode are applied but overridden.
+        assert_eq!(
+            colors,
+            [
+                "Document black transparent",
+                "Elem( tag: <html> ) white black",
+                "Elem( tag: <body> ) white rgba(0, 0, 0, 0)",
+                "Elem( tag: <p> ) white rgba(0, 0, 0, 0.5019608)",
+                "Text( a ) white rgba(0, 0, 0, 0.5019608)",
+                "Elem( tag: <a>, attr: [\"href\"=\"/\"] ) yellow rgba(0, 0, 0, 0)",
+                "Text( link ) yellow rgba(0, 0, 0, 0)",
+                "Elem( tag: <p>, attr: [\"class\"=\"none\"] ) red rgba(255, 0, 0, 0.5019608)",
+                "Text( b ) red rgba(255, 0, 0, 0.5019608)",
+                "Elem( tag: <span> ) red rgba(0, 0, 0, 0)",
+                "Text( c ) red rgba(0, 0, 0, 0)",
+                "Elem( tag: <p>, attr: [\"class\"=\"preserve\"] ) white rgba(255, 0, 0, 0.5019608)",
+                "Text( d ) white rgba(255, 0, 0, 0.5019608)",
+                "Elem( tag: <div> ) white rgba(0, 0, 0, 0)",
+                "Text( e ) white rgba(0, 0, 0, 0)",
+            ]
+        );
+    }
+}
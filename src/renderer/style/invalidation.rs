@@ -0,0 +1,307 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::css::selector::{Combinator, Selector, SimpleSelector};
+use crate::renderer::html::dom::{DomNode, NodeType};
+
+/// The range of elements whose styles can change when an element enters or leaves a user action state, e.g.
+/// the hover state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidationScope {
+    /// Only the hovered element itself (e.g. `a:hover`).
+    Itself,
+    /// The descendants of the hovered element (e.g. `div:hover p`, `ul:hover > li`).
+    Descendants,
+    /// The following siblings of the hovered element and their descendants (e.g. `h1:hover + p`).
+    Siblings,
+}
+
+/// A map from the compound selectors containing a user action pseudo-class (e.g. `:hover`) to the scopes of
+/// elements that can be affected. This is used to restyle only the affected elements when the hovered
+/// element changes, instead of matching all the selectors against the whole document on every mouse event.
+/// https://chromium.googlesource.com/chromium/src/+/main/third_party/blink/renderer/core/css/style-invalidation.md
+#[derive(Debug, Default)]
+pub struct UserActionInvalidationMap {
+    /// The pseudo-class without the colon, e.g. `hover`.
+    pseudo_class: String,
+    /// Each entry is a pair of the compound selector without the pseudo-class that the element entering
+    /// or leaving the state must match, and the scope of the invalidation.
+    entries: Vec<(Vec<SimpleSelector>, InvalidationScope)>,
+}
+
+impl UserActionInvalidationMap {
+    pub fn build(style_sheets: &[StyleSheet], pseudo_class: &str) -> Self {
+        let mut map = Self {
+            pseudo_class: pseudo_class.to_string(),
+            ..Default::default()
+        };
+        for style_sheet in style_sheets {
+            // The rules in all the `@media` rules are included, since the environment may change.
+            for rule in style_sheet.get_all_style_rules() {
+                for selector in &rule.selectors {
+                    map.add_selector(selector);
+                }
+            }
+        }
+        map
+    }
+
+    /// Returns `true` if no style rule depends on the state, in which case mouse movement never needs a restyle.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the elements that need to be restyled when the hovered element changes from `old_hovered` to `new_hovered`.
+    /// An element is in the hover state if it or one of its descendants is designated by the pointing device,
+    /// so the elements that actually change their hover state are the ones in only one of the two ancestor chains.
+    /// https://www.w3.org/TR/selectors-4/#the-hover-pseudo
+    #[allow(dead_code)]
+    pub fn get_invalidated_nodes(
+        &self,
+        old_hovered: Option<&Rc<RefCell<DomNode>>>,
+        new_hovered: Option<&Rc<RefCell<DomNode>>>,
+    ) -> Vec<Rc<RefCell<DomNode>>> {
+        let old_chain = old_hovered.map_or_else(Vec::new, get_inclusive_ancestors);
+        let new_chain = new_hovered.map_or_else(Vec::new, get_inclusive_ancestors);
+        let changed = old_chain
+            .iter()
+            .filter(|node| !new_chain.iter().any(|n| Rc::ptr_eq(n, node)))
+            .chain(
+                new_chain
+                    .iter()
+                    .filter(|node| !old_chain.iter().any(|n| Rc::ptr_eq(n, node))),
+            )
+            .cloned()
+            .collect::<Vec<_>>();
+        self.get_invalidated_nodes_by_changes(&changed)
+    }
+
+    /// Returns the elements that need to be restyled when the elements enter or leave the state.
+    pub fn get_invalidated_nodes_by_changes(
+        &self,
+        changed: &[Rc<RefCell<DomNode>>],
+    ) -> Vec<Rc<RefCell<DomNode>>> {
+        let mut invalidated = Vec::new();
+        if self.is_empty() {
+            return invalidated;
+        }
+
+        // The element which left the state may have been removed, in which case it is not in the tree of the
+        // others and not restyled.
+        let Some(root) = changed.last().map(DomNode::get_root) else {
+            return invalidated;
+        };
+
+        for node in changed {
+            if !matches!(node.borrow().node_type, NodeType::Element(_))
+                || !Rc::ptr_eq(&DomNode::get_root(node), &root)
+            {
+                continue;
+            }
+            for (compound, scope) in &self.entries {
+                if !compound.iter().all(|selector| selector.matches(node)) {
+                    continue;
+                }
+                match scope {
+                    InvalidationScope::Itself => push_node(&mut invalidated, node),
+                    InvalidationScope::Descendants => push_descendants(&mut invalidated, node),
+                    InvalidationScope::Siblings => {
+                        let mut sibling = node.borrow().next_sibling.clone();
+                        while let Some(s) = sibling {
+                            push_node(&mut invalidated, &s);
+                            push_descendants(&mut invalidated, &s);
+                            sibling = s.borrow().next_sibling.clone();
+                        }
+                    }
+                }
+            }
+        }
+
+        invalidated
+    }
+
+    fn add_selector(&mut self, selector: &Selector) {
+        // The selector is right-associative, so the compound selectors are visited from left to right,
+        // and the combinators that follow the current compound selector are collected on the way.
+        let mut compounds = Vec::new();
+        let mut current = selector;
+        loop {
+            match current {
+                Selector::Simple(simple_selectors) => {
+                    compounds.push((simple_selectors, None));
+                    break;
+                }
+                Selector::Complex(left, combinator, right) => {
+                    let Selector::Simple(simple_selectors) = left.as_ref() else {
+                        unreachable!();
+                    };
+                    compounds.push((simple_selectors, Some(combinator)));
+                    current = right;
+                }
+            }
+        }
+
+        let is_state = |selector: &SimpleSelector| matches!(selector, SimpleSelector::PseudoClass(name) if name.eq_ignore_ascii_case(&self.pseudo_class));
+        for (i, (simple_selectors, _)) in compounds.iter().enumerate() {
+            if !simple_selectors.iter().any(is_state) {
+                continue;
+            }
+            let following_combinators = compounds[i..]
+                .iter()
+                .filter_map(|(_, combinator)| *combinator)
+                .collect::<Vec<_>>();
+            let scope = if following_combinators.is_empty() {
+                InvalidationScope::Itself
+            } else if following_combinators
+                .iter()
+                .any(|c| matches!(c, Combinator::Plus | Combinator::Tilde))
+            {
+                InvalidationScope::Siblings
+            } else {
+                InvalidationScope::Descendants
+            };
+            let compound = simple_selectors
+                .iter()
+                .filter(|s| !is_state(s))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !self
+                .entries
+                .iter()
+                .any(|(c, s)| *c == compound && *s == scope)
+            {
+                self.entries.push((compound, scope));
+            }
+        }
+    }
+}
+
+fn get_inclusive_ancestors(node: &Rc<RefCell<DomNode>>) -> Vec<Rc<RefCell<DomNode>>> {
+    let mut ancestors = vec![Rc::clone(node)];
+    let mut current = Rc::clone(node);
+    loop {
+        let Some(parent) = DomNode::get_parent(&current) else {
+            break;
+        };
+        ancestors.push(Rc::clone(&parent));
+        current = parent;
+    }
+    ancestors
+}
+
+fn push_node(nodes: &mut Vec<Rc<RefCell<DomNode>>>, node: &Rc<RefCell<DomNode>>) {
+    if !nodes.iter().any(|n| Rc::ptr_eq(n, node)) {
+        nodes.push(Rc::clone(node));
+    }
+}
+
+fn push_descendants(nodes: &mut Vec<Rc<RefCell<DomNode>>>, node: &Rc<RefCell<DomNode>>) {
+    for child in &node.borrow().children {
+        push_node(nodes, child);
+        push_descendants(nodes, child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::parser::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+    use crate::renderer::html::dom::DocumentTree;
+    use crate::renderer::html::dom::NodeType;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    fn get_elm_by_id(tree: &DocumentTree, id: &str) -> Rc<RefCell<DomNode>> {
+        tree.get_dfs_iter()
+            .find(|node| match &node.borrow().node_type {
+                NodeType::Element(elm) => elm.get_attribute("id") == Some(id),
+                _ => false,
+            })
+            .unwrap()
+    }
+
+    fn get_ids(nodes: &[Rc<RefCell<DomNode>>]) -> Vec<String> {
+        nodes
+            .iter()
+            .filter_map(|node| match &node.borrow().node_type {
+                NodeType::Element(elm) => elm.get_attribute("id").map(|id| id.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn build_hover_invalidation_map() {
+        let css = "a:hover { color: red; } div:hover p { color: blue; } h1:hover + p, .x { color: green; } p { color: black; }";
+        let style_sheet = CssParser::new(&CssTokenizer::new(css).tokenize().unwrap())
+            .parse()
+            .unwrap();
+        let map = UserActionInvalidationMap::build(&[style_sheet], "hover");
+        assert_eq!(
+            map.entries,
+            vec![
+                (
+                    vec![SimpleSelector::Type {
+                        namespace_prefix: None,
+                        name: "a".to_string()
+                    }],
+                    InvalidationScope::Itself
+                ),
+                (
+                    vec![SimpleSelector::Type {
+                        namespace_prefix: None,
+                        name: "div".to_string()
+                    }],
+                    InvalidationScope::Descendants
+                ),
+                (
+                    vec![SimpleSelector::Type {
+                        namespace_prefix: None,
+                        name: "h1".to_string()
+                    }],
+                    InvalidationScope::Siblings
+                ),
+            ]
+        );
+
+        let style_sheet =
+            CssParser::new(&CssTokenizer::new("p { color: black; }").tokenize().unwrap())
+                .parse()
+                .unwrap();
+        assert!(UserActionInvalidationMap::build(&[style_sheet], "hover").is_empty());
+    }
+
+    #[test]
+    fn get_invalidated_nodes() {
+        let html = "<html><head></head><body><div id=\"d\"><p id=\"p1\"><a id=\"a\">link</a></p></div><h1 id=\"h\">title</h1><p id=\"p2\">text</p></body></html>";
+        let tree =
+            DocumentTree::build(HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap().0)
+                .unwrap();
+        let css =
+            "a:hover { color: red; } div:hover p { color: blue; } h1:hover + p { color: green; }";
+        let map = UserActionInvalidationMap::build(
+            &[CssParser::new(&CssTokenizer::new(css).tokenize().unwrap())
+                .parse()
+                .unwrap()],
+            "hover",
+        );
+
+        let a = get_elm_by_id(&tree, "a");
+        let h = get_elm_by_id(&tree, "h");
+
+        // Entering the hover state on <a> also puts <p> and <div> in the hover state.
+        assert_eq!(
+            get_ids(&map.get_invalidated_nodes(None, Some(&a))),
+            vec!["a", "p1"]
+        );
+        // Moving from <a> to <h1> leaves <a>, <p> and <div>, and enters <h1>.
+        assert_eq!(
+            get_ids(&map.get_invalidated_nodes(Some(&a), Some(&h))),
+            vec!["a", "p1", "p2"]
+        );
+        // Moving within the same element invalidates nothing.
+        assert!(map.get_invalidated_nodes(Some(&h), Some(&h)).is_empty());
+    }
+}
@@ -1,28 +1,78 @@
 pub mod border;
 pub mod border_radius;
+pub mod box_sizing;
+pub mod calc;
 pub mod color;
+pub mod content;
+pub mod direction;
 pub mod display;
+pub mod float;
+pub mod font;
 pub mod font_family;
 pub mod font_size;
+pub mod font_stretch;
+pub mod font_style;
 pub mod font_weight;
+pub mod forced_color_adjust;
 pub mod height;
+pub mod line_height;
+pub mod list_style;
 pub mod margin;
+pub mod min_max_size;
+pub mod opacity;
+pub mod overflow;
+pub mod overflow_wrap;
 pub mod padding;
+pub mod position;
+pub mod ruby_position;
+pub mod text_align;
 pub mod text_decoration;
+pub mod text_indent;
+pub mod text_overflow;
+pub mod text_transform;
+pub mod unicode_bidi;
+pub mod vertical_align;
+pub mod white_space;
 pub mod width;
+pub mod word_break;
 
 pub use border::BorderProp;
 pub use border_radius::BorderRadiusProp;
+pub use box_sizing::BoxSizingProp;
+pub use calc::CalcNode;
 pub use color::{BackGroundColorProp, ColorProp};
+pub use content::{ContentProp, CounterIncrementProp, CounterResetProp};
+pub use direction::DirectionProp;
 pub use display::{DisplayBox, DisplayOutside, DisplayProp};
+pub use float::FloatProp;
+pub use font::FontShorthand;
 pub use font_family::FontFamilyProp;
 pub use font_size::FontSizeProp;
+pub use font_stretch::FontStretchProp;
+pub use font_style::{FontStyle, FontStyleProp};
 pub use font_weight::FontWeightProp;
+pub use forced_color_adjust::ForcedColorAdjustProp;
 pub use height::HeightProp;
+pub use line_height::LineHeightProp;
+pub use list_style::ListStyleTypeProp;
 pub use margin::{MarginBlockProp, MarginProp};
+pub use min_max_size::{MaxSizeProp, MinSizeProp};
+pub use opacity::OpacityProp;
+pub use overflow::OverflowProp;
+pub use overflow_wrap::OverflowWrapProp;
 pub use padding::PaddingProp;
+pub use position::{InsetProp, PositionProp, ZIndexProp};
+pub use ruby_position::RubyPositionProp;
+pub use text_align::TextAlignProp;
 pub use text_decoration::TextDecorationProp;
+pub use text_indent::TextIndentProp;
+pub use text_overflow::TextOverflowProp;
+pub use text_transform::TextTransformProp;
+pub use unicode_bidi::UnicodeBidiProp;
+pub use vertical_align::VerticalAlignProp;
+pub use white_space::WhiteSpaceProp;
 pub use width::WidthProp;
+pub use word_break::WordBreakProp;
 
 use std::fmt;
 use std::iter::Peekable;
@@ -30,19 +80,82 @@ use std::iter::Peekable;
 use anyhow::{bail, Result};
 
 use crate::renderer::css::cssom::ComponentValue;
-use crate::renderer::css::token::{CssToken, NumericType};
-use crate::renderer::style::property::color::rgb_to_name;
+use crate::renderer::css::media::MediaEnvironment;
+use crate::renderer::css::parser::CssParser;
+use crate::renderer::css::token::{CssToken, CssTokenizer, NumericType};
+use crate::renderer::style::property::calc::{is_math_function, parse_math_function};
+use crate::renderer::style::property::color::Color;
 use crate::renderer::style::style_model::SpecifiedStyle;
 
 pub trait CssProperty {
     fn parse(values: &[ComponentValue]) -> Result<Self>
     where
         Self: Sized;
-    fn compute(&mut self, current_style: Option<&SpecifiedStyle>) -> Result<&Self>
+    fn compute(
+        &mut self,
+        current_style: Option<&SpecifiedStyle>,
+        context: &LengthContext,
+    ) -> Result<&Self>
     where
         Self: Sized;
 }
 
+/// The sizes against which the relative lengths are converted to pixels at computed value time. The
+/// percentages are kept until used value time, where they are resolved against the containing block.
+/// https://drafts.csswg.org/css-values-4/#relative-lengths
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LengthContext {
+    /// The computed font size of the element, or of its parent while `font-size` itself is computed.
+    pub font_size: f32,
+    /// The computed font size of the root element, which is `None` while the root element itself is
+    /// computed. `rem` is then relative to the initial font size in `font-size`, and to the font size of the
+    /// element in the other properties.
+    pub root_font_size: Option<f32>,
+    pub viewport_width: f32,
+    pub viewport_height: f32,
+}
+
+impl Default for LengthContext {
+    fn default() -> Self {
+        Self::new(&MediaEnvironment::default())
+    }
+}
+
+impl LengthContext {
+    /// Creates the context of the root element in the viewport of the environment.
+    pub fn new(media: &MediaEnvironment) -> Self {
+        Self {
+            font_size: font_size::MEDIUM,
+            root_font_size: None,
+            viewport_width: media.viewport_width as f32,
+            viewport_height: media.viewport_height as f32,
+        }
+    }
+
+    /// Converts the length in the unit to pixels.
+    pub fn to_px(self, value: f32, unit: &LengthUnit) -> f32 {
+        match unit {
+            LengthUnit::AbsoluteLengthUnit(unit) => unit.to_px(value),
+            LengthUnit::RelativeLengthUnit(unit) => match unit {
+                RelativeLengthUnit::Em => value * self.font_size,
+                // The glyphs are not measured while the styles are computed, so `ex` and `ch` are assumed
+                // to be 0.5em, which is the fallback of the spec.
+                // https://drafts.csswg.org/css-values-4/#ex
+                RelativeLengthUnit::Ex | RelativeLengthUnit::Ch => value * self.font_size / 2.0,
+                RelativeLengthUnit::Rem => value * self.root_font_size.unwrap_or(font_size::MEDIUM),
+                RelativeLengthUnit::Vw => value * self.viewport_width / 100.0,
+                RelativeLengthUnit::Vh => value * self.viewport_height / 100.0,
+                RelativeLengthUnit::Vmin => {
+                    value * self.viewport_width.min(self.viewport_height) / 100.0
+                }
+                RelativeLengthUnit::Vmax => {
+                    value * self.viewport_width.max(self.viewport_height) / 100.0
+                }
+            },
+        }
+    }
+}
+
 /// https://www.w3.org/TR/css-values-3/
 #[allow(dead_code)]
 #[derive(Clone, Debug, PartialEq)]
@@ -54,10 +167,12 @@ pub enum CssValue {
     Dimension(f32, String),
     Percentage(f32),
     Length(f32, LengthUnit),
-    Color { r: u8, g: u8, b: u8, a: f32 },
+    Color(Color),
     HexColor(String),
     AbsoluteSize(AbsoluteSize),
     RelativeSize(RelativeSize),
+    /// A math function which can't be simplified to a single value until its percentages are resolved.
+    Calc(CalcNode),
 }
 
 impl fmt::Display for CssValue {
@@ -70,21 +185,15 @@ impl fmt::Display for CssValue {
             CssValue::Dimension(value, unit) => write!(f, "{}{}", value, unit),
             CssValue::Percentage(value) => write!(f, "{}%", value),
             CssValue::Length(value, unit) => write!(f, "{}{}", value, unit),
-            CssValue::Color { r, g, b, a } => {
-                if *a == 1.0 {
-                    let name = rgb_to_name(*r, *g, *b);
-                    if name.is_some() {
-                        write!(f, "{}", name.unwrap())
-                    } else {
-                        write!(f, "rgb({}, {}, {})", r, g, b)
-                    }
-                } else {
-                    write!(f, "rgba({}, {}, {}, {})", r, g, b, a)
-                }
-            }
+            CssValue::Color(color) => write!(f, "{}", color),
             CssValue::HexColor(color) => write!(f, "{}", color),
             CssValue::AbsoluteSize(size) => write!(f, "{:?}", size),
             CssValue::RelativeSize(size) => write!(f, "{:?}", size),
+            CssValue::Calc(node) => match node {
+                CalcNode::Min(_) | CalcNode::Max(_) | CalcNode::Clamp(..) => write!(f, "{}", node),
+                CalcNode::Sum(_) => write!(f, "calc{}", node),
+                _ => write!(f, "calc({})", node),
+            },
         }
     }
 }
@@ -102,10 +211,33 @@ impl CssValue {
         }
     }
 
-    /// Converts the color value to rgba. R: 0-255, G: 0-255, B: 0-255, A: 0.0-1.0
-    pub fn to_rgba(&self) -> Result<(u8, u8, u8, f32)> {
-        if let CssValue::Color { r, g, b, a } = self {
-            Ok((*r, *g, *b, *a))
+    /// Returns the computed value of the length, percentage or math function value, whose lengths are
+    /// converted to pixels. The other values are returned as they are.
+    pub fn compute_length(&self, context: &LengthContext) -> Result<CssValue> {
+        match self {
+            CssValue::Length(value, unit) => Ok(CssValue::Length(
+                context.to_px(*value, unit),
+                LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
+            )),
+            CssValue::Calc(node) => node.to_computed_value(context),
+            _ => Ok(self.clone()),
+        }
+    }
+
+    /// Converts the computed length, percentage or math function value to pixels, where the percentages are
+    /// relative to `percentage_basis`.
+    pub fn resolve_px(&self, percentage_basis: f32) -> Result<f32> {
+        match self {
+            CssValue::Percentage(value) => Ok(value / 100.0 * percentage_basis),
+            CssValue::Calc(node) => node.resolve(percentage_basis),
+            _ => self.to_px(),
+        }
+    }
+
+    /// Returns the computed color value.
+    pub fn to_color(&self) -> Result<Color> {
+        if let CssValue::Color(color) = self {
+            Ok(*color)
         } else {
             bail!("Expected color value but found: {:?}", self);
         }
@@ -161,6 +293,22 @@ pub enum AbsoluteLengthUnit {
     Px,
 }
 
+impl AbsoluteLengthUnit {
+    /// Converts the length in this unit to CSS pixels, where 1in is 96px.
+    /// https://www.w3.org/TR/css-values-3/#absolute-lengths
+    pub fn to_px(&self, value: f32) -> f32 {
+        match self {
+            Self::Cm => value * 96.0 / 2.54,
+            Self::Mm => value * 96.0 / 25.4,
+            Self::Q => value * 96.0 / 101.6,
+            Self::In => value * 96.0,
+            Self::Pc => value * 16.0,
+            Self::Pt => value * 96.0 / 72.0,
+            Self::Px => value,
+        }
+    }
+}
+
 /// https://www.w3.org/TR/css-fonts-3/#absolute-size-value
 #[derive(Clone, Debug, PartialEq)]
 pub enum AbsoluteSize {
@@ -182,13 +330,19 @@ pub enum RelativeSize {
 
 // <length-percentage> =
 //   <length>      |
-//   <percentage>
+//   <percentage>  |
+//   <calc()>
 pub fn parse_length_percentage_type<I>(values: &mut Peekable<I>) -> Result<CssValue>
 where
     I: Iterator<Item = ComponentValue>,
 {
     match values.peek() {
         Some(v) => match v {
+            v if is_math_function(v) => {
+                let node = parse_math_function(v)?;
+                values.next();
+                Ok(CssValue::Calc(node))
+            }
             ComponentValue::PreservedToken(CssToken::Dimension(..) | CssToken::Number(..)) => {
                 parse_length_type(values)
             }
@@ -276,7 +430,7 @@ where
                         size,
                         LengthUnit::RelativeLengthUnit(RelativeLengthUnit::Vmax),
                     )),
-                    _ => unimplemented!(),
+                    _ => bail!("Unknown length unit: {}", unit),
                 }
             }
             ComponentValue::PreservedToken(CssToken::Number(NumericType::Integer(0))) => Ok(
@@ -288,6 +442,22 @@ where
     }
 }
 
+/// Parses the length outside of the style sheets, e.g. in an attribute or a media feature, and converts it to
+/// pixels in the context. Returns `None` if the string is not a single length.
+pub fn parse_length_px(value: &str, context: &LengthContext) -> Option<f32> {
+    let tokens = CssTokenizer::new(value).tokenize().ok()?;
+    let mut values = CssParser::new(&tokens)
+        .parse_list_of_component_values()
+        .into_iter()
+        .filter(|v| *v != ComponentValue::PreservedToken(CssToken::Whitespace))
+        .peekable();
+    let length = parse_length_type(&mut values).ok()?;
+    if values.next().is_some() {
+        return None;
+    }
+    length.compute_length(context).ok()?.to_px().ok()
+}
+
 pub fn parse_percentage_type<I>(values: &mut Peekable<I>) -> Result<CssValue>
 where
     I: Iterator<Item = ComponentValue>,
@@ -302,3 +472,21 @@ where
         None => bail!("Expected percentage value but found none"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_lengths_in_strings() {
+        let context = LengthContext::default();
+        assert_eq!(parse_length_px("12px", &context), Some(12.0));
+        assert_eq!(parse_length_px(" 1in ", &context), Some(96.0));
+        assert_eq!(parse_length_px("2em", &context), Some(32.0));
+        assert_eq!(parse_length_px("0", &context), Some(0.0));
+        assert_eq!(parse_length_px("12", &context), None);
+        assert_eq!(parse_length_px("12foo", &context), None);
+        assert_eq!(parse_length_px("12px 3px", &context), None);
+        assert_eq!(parse_length_px("", &context), None);
+    }
+}
@@ -5,7 +5,9 @@ use anyhow::{Ok, Result};
 use crate::renderer::css::cssom::ComponentValue;
 use crate::renderer::layout::box_model::Edge;
 use crate::renderer::style::property::color::ColorProp;
-use crate::renderer::style::property::{AbsoluteLengthUnit, CssProperty, CssValue, LengthUnit};
+use crate::renderer::style::property::{
+    AbsoluteLengthUnit, CssProperty, CssValue, LengthContext, LengthUnit,
+};
 use crate::renderer::style::style_model::SpecifiedStyle;
 
 // The values of these properties are not clearly defined in the CSS specification.
@@ -69,8 +71,12 @@ impl CssProperty for BorderProp {
 
     // todo: proper implementation
     #[allow(unused_variables)]
-    fn compute(&mut self, current_style: Option<&SpecifiedStyle>) -> Result<&Self> {
-        self.border_color.compute(current_style)?;
+    fn compute(
+        &mut self,
+        current_style: Option<&SpecifiedStyle>,
+        context: &LengthContext,
+    ) -> Result<&Self> {
+        self.border_color.compute(current_style, context)?;
         self.border_width = BorderWidthProp {
             top: CssValue::Length(0.0, LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px)),
             right: CssValue::Length(0.0, LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px)),
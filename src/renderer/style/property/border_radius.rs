@@ -4,10 +4,9 @@ use anyhow::{bail, Ok, Result};
 
 use crate::renderer::css::cssom::ComponentValue;
 use crate::renderer::css::token::CssToken;
-use crate::renderer::style::property::font_size::{self, FontSizeProp};
 use crate::renderer::style::property::{
-    parse_length_percentage_type, AbsoluteLengthUnit, CssProperty, CssValue, LengthUnit,
-    RelativeLengthUnit,
+    parse_length_percentage_type, AbsoluteLengthUnit, CssProperty, CssValue, LengthContext,
+    LengthUnit,
 };
 use crate::renderer::style::style_model::SpecifiedStyle;
 
@@ -107,35 +106,24 @@ impl CssProperty for BorderRadiusProp {
         }
     }
 
-    fn compute(&mut self, current_style: Option<&SpecifiedStyle>) -> Result<&Self> {
-        self.top_left = Self::compute_top(&self.top_left, current_style)?;
-        self.top_right = Self::compute_top(&self.top_right, current_style)?;
-        self.bottom_right = Self::compute_top(&self.bottom_right, current_style)?;
-        self.bottom_left = Self::compute_top(&self.bottom_left, current_style)?;
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, context: &LengthContext) -> Result<&Self> {
+        self.top_left = Self::compute_top(&self.top_left, context)?;
+        self.top_right = Self::compute_top(&self.top_right, context)?;
+        self.bottom_right = Self::compute_top(&self.bottom_right, context)?;
+        self.bottom_left = Self::compute_top(&self.bottom_left, context)?;
         Ok(self)
     }
 }
 
 impl BorderRadiusProp {
-    fn compute_top(value: &CssValue, current_style: Option<&SpecifiedStyle>) -> Result<CssValue> {
-        let current_font_size = current_style.and_then(|s| s.font_size.as_ref());
-        let current_font_size = match current_font_size {
-            Some(FontSizeProp {
-                size: CssValue::Length(size, LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px)),
-            }) => size,
-            None => &font_size::MEDIUM,
-            _ => bail!("Invalid font-size value: {:?}", current_font_size),
-        };
+    fn compute_top(value: &CssValue, context: &LengthContext) -> Result<CssValue> {
         match &value {
-            CssValue::Length(size, unit) => match unit {
-                LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px) => Ok(value.clone()),
-                LengthUnit::RelativeLengthUnit(RelativeLengthUnit::Em) => Ok(CssValue::Length(
-                    size * current_font_size,
-                    LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
-                )),
-                _ => unimplemented!(),
-            },
+            CssValue::Length(..) => value.compute_length(context),
             CssValue::Percentage(_) => unimplemented!(),
+            CssValue::Calc(_) => match value.compute_length(context)? {
+                CssValue::Calc(_) => bail!("Unsupported percentage in {}", value),
+                value => Ok(value),
+            },
             _ => bail!("Invalid padding value: {:?}", &value),
         }
     }
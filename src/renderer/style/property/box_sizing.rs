@@ -0,0 +1,62 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// https://drafts.csswg.org/css-sizing/#box-sizing
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoxSizingProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for BoxSizingProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for BoxSizingProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("content-box".to_string()),
+        }
+    }
+}
+
+impl CssProperty for BoxSizingProp {
+    // box-sizing =
+    //   content-box  |
+    //   border-box
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (keywords.next(), keywords.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(value))), None) => {
+                let value = value.to_ascii_lowercase();
+                match value.as_str() {
+                    "content-box" | "border-box" => Ok(Self {
+                        value: CssValue::Ident(value),
+                    }),
+                    _ => bail!("Invalid box-sizing value: {:?}", value),
+                }
+            }
+            _ => bail!("Invalid box-sizing declaration: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl BoxSizingProp {
+    /// Returns whether the sizes of the box include its paddings and borders.
+    pub fn is_border_box(&self) -> bool {
+        self.value == CssValue::Ident("border-box".to_string())
+    }
+}
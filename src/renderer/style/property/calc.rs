@@ -0,0 +1,387 @@
+use std::fmt;
+use std::iter::Peekable;
+
+use anyhow::{bail, ensure, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::{CssToken, NumericType};
+use crate::renderer::style::property::{
+    parse_length_type, AbsoluteLengthUnit, CssValue, LengthContext, LengthUnit,
+};
+
+/// A node of the calculation tree of a math function, which is kept until the percentages in it are resolved
+/// at used value time.
+/// https://drafts.csswg.org/css-values-4/#calculation-tree
+#[derive(Clone, Debug, PartialEq)]
+pub enum CalcNode {
+    Number(f32),
+    Length(f32, LengthUnit),
+    Percentage(f32),
+    Sum(Vec<CalcNode>),
+    Negate(Box<CalcNode>),
+    Product(Box<CalcNode>, Box<CalcNode>),
+    /// The division, whose divisor is always a number.
+    Quotient(Box<CalcNode>, Box<CalcNode>),
+    Min(Vec<CalcNode>),
+    Max(Vec<CalcNode>),
+    /// (minimum, central, maximum)
+    Clamp(Box<CalcNode>, Box<CalcNode>, Box<CalcNode>),
+}
+
+/// The type of a calculation, where percentages are resolved against lengths, e.g. `<length-percentage>`.
+/// https://drafts.csswg.org/css-values-4/#determine-the-type-of-a-calculation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CalcType {
+    Number,
+    Length,
+}
+
+impl fmt::Display for CalcNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |nodes: &[CalcNode]| {
+            nodes
+                .iter()
+                .map(|node| node.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            CalcNode::Number(n) => write!(f, "{}", n),
+            CalcNode::Length(n, unit) => write!(f, "{}{}", n, unit),
+            CalcNode::Percentage(n) => write!(f, "{}%", n),
+            CalcNode::Sum(terms) => {
+                write!(f, "({}", terms[0])?;
+                for term in &terms[1..] {
+                    match term {
+                        CalcNode::Negate(term) => write!(f, " - {}", term)?,
+                        _ => write!(f, " + {}", term)?,
+                    }
+                }
+                write!(f, ")")
+            }
+            CalcNode::Negate(node) => write!(f, "(-1 * {})", node),
+            CalcNode::Product(lhs, rhs) => write!(f, "{} * {}", lhs, rhs),
+            CalcNode::Quotient(lhs, rhs) => write!(f, "{} / {}", lhs, rhs),
+            CalcNode::Min(nodes) => write!(f, "min({})", join(nodes)),
+            CalcNode::Max(nodes) => write!(f, "max({})", join(nodes)),
+            CalcNode::Clamp(min, val, max) => write!(f, "clamp({}, {}, {})", min, val, max),
+        }
+    }
+}
+
+impl CalcNode {
+    /// Returns the type of the calculation, or an error if the types of the operands don't match.
+    pub fn get_type(&self) -> Result<CalcType> {
+        let same_type = |nodes: &[CalcNode]| -> Result<CalcType> {
+            let ty = nodes[0].get_type()?;
+            for node in &nodes[1..] {
+                ensure!(
+                    node.get_type()? == ty,
+                    "Mismatched types in the calculation"
+                );
+            }
+            Ok(ty)
+        };
+        match self {
+            CalcNode::Number(_) => Ok(CalcType::Number),
+            CalcNode::Length(..) | CalcNode::Percentage(_) => Ok(CalcType::Length),
+            CalcNode::Negate(node) => node.get_type(),
+            CalcNode::Sum(nodes) | CalcNode::Min(nodes) | CalcNode::Max(nodes) => same_type(nodes),
+            CalcNode::Clamp(min, val, max) => {
+                same_type(&[*min.clone(), *val.clone(), *max.clone()])
+            }
+            // At least one side of a multiplication must be a number.
+            CalcNode::Product(lhs, rhs) => match (lhs.get_type()?, rhs.get_type()?) {
+                (CalcType::Number, ty) | (ty, CalcType::Number) => Ok(ty),
+                _ => bail!("Cannot multiply two lengths"),
+            },
+            CalcNode::Quotient(lhs, rhs) => {
+                ensure!(
+                    rhs.get_type()? == CalcType::Number,
+                    "The divisor must be a number"
+                );
+                lhs.get_type()
+            }
+        }
+    }
+
+    /// Returns whether the calculation contains percentages, which are resolved at used value time.
+    pub fn has_percentage(&self) -> bool {
+        match self {
+            CalcNode::Number(_) | CalcNode::Length(..) => false,
+            CalcNode::Percentage(_) => true,
+            CalcNode::Negate(node) => node.has_percentage(),
+            CalcNode::Sum(nodes) | CalcNode::Min(nodes) | CalcNode::Max(nodes) => {
+                nodes.iter().any(CalcNode::has_percentage)
+            }
+            CalcNode::Product(lhs, rhs) | CalcNode::Quotient(lhs, rhs) => {
+                lhs.has_percentage() || rhs.has_percentage()
+            }
+            CalcNode::Clamp(min, val, max) => {
+                min.has_percentage() || val.has_percentage() || max.has_percentage()
+            }
+        }
+    }
+
+    /// Converts the lengths to pixels in the context, which is done at computed value time.
+    pub fn compute(&self, context: &LengthContext) -> Result<CalcNode> {
+        let compute_all = |nodes: &[CalcNode]| {
+            nodes
+                .iter()
+                .map(|node| node.compute(context))
+                .collect::<Result<Vec<_>>>()
+        };
+        let compute_box = |node: &CalcNode| node.compute(context).map(Box::new);
+        Ok(match self {
+            CalcNode::Length(n, unit) => CalcNode::Length(
+                context.to_px(*n, unit),
+                LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
+            ),
+            CalcNode::Number(_) | CalcNode::Percentage(_) => self.clone(),
+            CalcNode::Sum(nodes) => CalcNode::Sum(compute_all(nodes)?),
+            CalcNode::Min(nodes) => CalcNode::Min(compute_all(nodes)?),
+            CalcNode::Max(nodes) => CalcNode::Max(compute_all(nodes)?),
+            CalcNode::Negate(node) => CalcNode::Negate(compute_box(node)?),
+            CalcNode::Product(lhs, rhs) => CalcNode::Product(compute_box(lhs)?, compute_box(rhs)?),
+            CalcNode::Quotient(lhs, rhs) => {
+                CalcNode::Quotient(compute_box(lhs)?, compute_box(rhs)?)
+            }
+            CalcNode::Clamp(min, val, max) => {
+                CalcNode::Clamp(compute_box(min)?, compute_box(val)?, compute_box(max)?)
+            }
+        })
+    }
+
+    /// Returns the computed value of the math function, which is simplified to a length in pixels if it
+    /// doesn't contain percentages.
+    pub fn to_computed_value(&self, context: &LengthContext) -> Result<CssValue> {
+        let node = self.compute(context)?;
+        if node.has_percentage() {
+            Ok(CssValue::Calc(node))
+        } else {
+            Ok(CssValue::Length(
+                node.resolve(0.0)?,
+                LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
+            ))
+        }
+    }
+
+    /// Evaluates the calculation whose lengths are in pixels, where the percentages are relative to
+    /// `percentage_basis`.
+    /// https://drafts.csswg.org/css-values-4/#calc-computed-value
+    pub fn resolve(&self, percentage_basis: f32) -> Result<f32> {
+        let resolve_all = |nodes: &[CalcNode]| {
+            nodes
+                .iter()
+                .map(|node| node.resolve(percentage_basis))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(match self {
+            CalcNode::Number(n) => *n,
+            CalcNode::Length(n, LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px)) => *n,
+            CalcNode::Length(_, unit) => bail!("Expected px unit but found: {:?}", unit),
+            CalcNode::Percentage(n) => n / 100.0 * percentage_basis,
+            CalcNode::Sum(nodes) => resolve_all(nodes)?.into_iter().sum(),
+            CalcNode::Negate(node) => -node.resolve(percentage_basis)?,
+            CalcNode::Product(lhs, rhs) => {
+                lhs.resolve(percentage_basis)? * rhs.resolve(percentage_basis)?
+            }
+            CalcNode::Quotient(lhs, rhs) => {
+                lhs.resolve(percentage_basis)? / rhs.resolve(percentage_basis)?
+            }
+            CalcNode::Min(nodes) => resolve_all(nodes)?
+                .into_iter()
+                .fold(f32::INFINITY, f32::min),
+            CalcNode::Max(nodes) => resolve_all(nodes)?
+                .into_iter()
+                .fold(f32::NEG_INFINITY, f32::max),
+            // The minimum wins over the maximum if they conflict.
+            CalcNode::Clamp(min, val, max) => val
+                .resolve(percentage_basis)?
+                .min(max.resolve(percentage_basis)?)
+                .max(min.resolve(percentage_basis)?),
+        })
+    }
+}
+
+/// Returns whether the component value is a math function supported by `parse_math_function`.
+pub fn is_math_function(value: &ComponentValue) -> bool {
+    matches!(
+        value,
+        ComponentValue::Function { name, .. }
+            if matches!(name.to_ascii_lowercase().as_str(), "calc" | "min" | "max" | "clamp")
+    )
+}
+
+/// Parses a `calc()`, `min()`, `max()` or `clamp()` function into a calculation tree.
+/// https://drafts.csswg.org/css-values-4/#calc-syntax
+pub fn parse_math_function(value: &ComponentValue) -> Result<CalcNode> {
+    let ComponentValue::Function { name, values } = value else {
+        bail!("Expected a math function but found: {:?}", value);
+    };
+    let args = values
+        .split(|v| *v == ComponentValue::PreservedToken(CssToken::Comma))
+        .map(parse_sum)
+        .collect::<Result<Vec<_>>>()?;
+    let node = match (name.to_ascii_lowercase().as_str(), &args[..]) {
+        ("calc", [arg]) => arg.clone(),
+        ("min", [_, ..]) => CalcNode::Min(args),
+        ("max", [_, ..]) => CalcNode::Max(args),
+        ("clamp", [min, val, max]) => CalcNode::Clamp(
+            Box::new(min.clone()),
+            Box::new(val.clone()),
+            Box::new(max.clone()),
+        ),
+        _ => bail!("Invalid arguments of {}(): {:?}", name, values),
+    };
+    node.get_type()?;
+    Ok(node)
+}
+
+// <calc-sum> = <calc-product> [ [ '+' | '-' ] <calc-product> ]*
+fn parse_sum(values: &[ComponentValue]) -> Result<CalcNode> {
+    let mut values = values
+        .iter()
+        .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+        .cloned()
+        .peekable();
+    let mut terms = vec![parse_product(&mut values)?];
+    while let Some(v) = values.next() {
+        match v {
+            ComponentValue::PreservedToken(CssToken::Delim('+')) => {
+                terms.push(parse_product(&mut values)?)
+            }
+            ComponentValue::PreservedToken(CssToken::Delim('-')) => {
+                terms.push(CalcNode::Negate(Box::new(parse_product(&mut values)?)))
+            }
+            _ => bail!("Expected '+' or '-' in the calculation but found: {:?}", v),
+        }
+    }
+    Ok(if terms.len() == 1 {
+        terms.pop().unwrap()
+    } else {
+        CalcNode::Sum(terms)
+    })
+}
+
+// <calc-product> = <calc-value> [ [ '*' | '/' ] <calc-value> ]*
+fn parse_product<I>(values: &mut Peekable<I>) -> Result<CalcNode>
+where
+    I: Iterator<Item = ComponentValue>,
+{
+    let mut node = parse_value(values)?;
+    loop {
+        match values.peek() {
+            Some(ComponentValue::PreservedToken(CssToken::Delim('*'))) => {
+                values.next();
+                node = CalcNode::Product(Box::new(node), Box::new(parse_value(values)?));
+            }
+            Some(ComponentValue::PreservedToken(CssToken::Delim('/'))) => {
+                values.next();
+                node = CalcNode::Quotient(Box::new(node), Box::new(parse_value(values)?));
+            }
+            _ => return Ok(node),
+        }
+    }
+}
+
+// <calc-value> = <number> | <dimension> | <percentage> | ( <calc-sum> )
+fn parse_value<I>(values: &mut Peekable<I>) -> Result<CalcNode>
+where
+    I: Iterator<Item = ComponentValue>,
+{
+    match values.peek() {
+        Some(ComponentValue::PreservedToken(CssToken::Number(n))) => {
+            let n = match n {
+                NumericType::Integer(n) => *n as f32,
+                NumericType::Number(n) => *n,
+            };
+            values.next();
+            Ok(CalcNode::Number(n))
+        }
+        Some(ComponentValue::PreservedToken(CssToken::Percentage(n))) => {
+            let n = *n;
+            values.next();
+            Ok(CalcNode::Percentage(n))
+        }
+        Some(ComponentValue::PreservedToken(CssToken::Dimension(..))) => {
+            match parse_length_type(values)? {
+                CssValue::Length(n, unit) => Ok(CalcNode::Length(n, unit)),
+                v => bail!("Expected length value but found: {:?}", v),
+            }
+        }
+        Some(ComponentValue::SimpleBlock {
+            associated_token: CssToken::OpenParenthesis,
+            values: block,
+        }) => {
+            let node = parse_sum(block)?;
+            values.next();
+            Ok(node)
+        }
+        Some(v) if is_math_function(v) => {
+            let node = parse_math_function(v)?;
+            values.next();
+            Ok(node)
+        }
+        v => bail!("Expected a value in the calculation but found: {:?}", v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::parser::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+
+    fn parse(css: &str) -> Result<CalcNode> {
+        let values = CssParser::new(&CssTokenizer::new(css).tokenize().unwrap())
+            .parse_list_of_component_values();
+        parse_math_function(&values[0])
+    }
+
+    #[test]
+    fn evaluate_math_functions() {
+        let context = |font_size| LengthContext {
+            font_size,
+            ..Default::default()
+        };
+        let node = parse("calc(100% - 20px)").unwrap();
+        assert!(node.has_percentage());
+        assert_eq!(
+            node.compute(&context(16.0))
+                .unwrap()
+                .resolve(300.0)
+                .unwrap(),
+            280.0
+        );
+
+        // The relative lengths are resolved with the font size, and the nested functions are evaluated.
+        let node = parse("calc((2em + 4px) * 2 - max(10px, 1rem) / 2)").unwrap();
+        assert!(!node.has_percentage());
+        assert_eq!(
+            node.compute(&context(10.0)).unwrap().resolve(0.0).unwrap(),
+            40.0
+        );
+        let node = parse("calc(50vw - 1rem)").unwrap();
+        let context = LengthContext {
+            root_font_size: Some(20.0),
+            viewport_width: 800.0,
+            ..context(10.0)
+        };
+        assert_eq!(node.compute(&context).unwrap().resolve(0.0).unwrap(), 380.0);
+
+        let node = parse("clamp(100px, 50%, 200px)").unwrap();
+        let node = node.compute(&context).unwrap();
+        assert_eq!(node.resolve(100.0).unwrap(), 100.0);
+        assert_eq!(node.resolve(300.0).unwrap(), 150.0);
+        assert_eq!(node.resolve(1000.0).unwrap(), 200.0);
+        assert_eq!(parse("min(10px, 5%)").unwrap().resolve(100.0).unwrap(), 5.0);
+
+        // The operands of '+' and '-' must have the same type, and lengths can't be multiplied together.
+        assert!(parse("calc(10px + 2)").is_err());
+        assert!(parse("calc(10px * 2px)").is_err());
+        assert!(parse("calc(10px / 2px)").is_err());
+        assert!(parse("clamp(1px, 2px)").is_err());
+        assert!(parse("calc(10px 2px)").is_err());
+    }
+}
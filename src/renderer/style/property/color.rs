@@ -3,11 +3,101 @@ use std::iter::Peekable;
 
 use anyhow::{anyhow, bail, ensure, Ok, Result};
 
+use crate::renderer::color_management::{ColorTransform, RgbColorSpace};
 use crate::renderer::css::cssom::ComponentValue;
 use crate::renderer::css::token::{CssToken, NumericType};
-use crate::renderer::style::property::{CssProperty, CssValue};
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
 use crate::renderer::style::style_model::SpecifiedStyle;
 
+/// The resolved `<color>` value in sRGB, to which all the color syntaxes are converted when the values are
+/// computed.
+/// https://www.w3.org/TR/css-color-4/#resolving-color-values
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    /// 0.0 <= a <= 1.0
+    pub a: f32,
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.a == 1.0 {
+            match rgb_to_name(self.r, self.g, self.b) {
+                Some(name) => write!(f, "{}", name),
+                None => write!(f, "rgb({}, {}, {})", self.r, self.g, self.b),
+            }
+        } else {
+            write!(f, "rgba({}, {}, {}, {})", self.r, self.g, self.b, self.a)
+        }
+    }
+}
+
+impl Color {
+    pub const BLACK: Self = Self::new(0, 0, 0, 1.0);
+    pub const TRANSPARENT: Self = Self::new(0, 0, 0, 0.0);
+
+    pub const fn new(r: u8, g: u8, b: u8, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Returns the color whose channels are in [0.0, 1.0], clamping the ones out of the range.
+    fn from_unit_rgba([r, g, b]: [f64; 3], a: f64) -> Self {
+        let to_u8 = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Self::new(to_u8(r), to_u8(g), to_u8(b), a.clamp(0.0, 1.0) as f32)
+    }
+
+    /// Returns the color of the `<named-color>` or `transparent`, case-insensitively.
+    /// https://www.w3.org/TR/css-color-4/#named-colors
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        if name == "transparent" {
+            return Some(Self::TRANSPARENT);
+        }
+        NAMED_COLORS
+            .binary_search_by_key(&name.as_str(), |(n, _)| n)
+            .ok()
+            .map(|i| {
+                let (r, g, b) = NAMED_COLORS[i].1;
+                Self::new(r, g, b, 1.0)
+            })
+    }
+
+    /// Returns the color of the hex digits of `<hex-color>`, which are 3, 4, 6 or 8 digits long.
+    /// https://www.w3.org/TR/css-color-4/#hex-notation
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        ensure!(
+            hex.is_ascii() && [3, 4, 6, 8].contains(&hex.len()),
+            "Invalid hex color: {}",
+            hex
+        );
+        let digits = if hex.len() <= 4 {
+            hex.chars().flat_map(|c| [c, c]).collect::<String>()
+        } else {
+            hex.to_string()
+        };
+        let channel = |i: usize| u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16);
+        let a = if digits.len() == 8 { channel(3)? } else { 255 };
+        Ok(Self::new(
+            channel(0)?,
+            channel(1)?,
+            channel(2)?,
+            a as f32 / 255.0,
+        ))
+    }
+
+    /// Returns (r, g, b, a) in [0.0, 1.0], which is used to paint the color.
+    pub fn to_unit_rgba(self) -> (f64, f64, f64, f64) {
+        (
+            self.r as f64 / 255.0,
+            self.g as f64 / 255.0,
+            self.b as f64 / 255.0,
+            self.a as f64,
+        )
+    }
+}
+
 /// https://developer.mozilla.org/en-US/docs/Web/CSS/color
 #[derive(Clone, Debug, PartialEq)]
 pub struct ColorProp {
@@ -23,12 +113,7 @@ impl fmt::Display for ColorProp {
 impl Default for ColorProp {
     fn default() -> Self {
         ColorProp {
-            value: CssValue::Color {
-                r: 0,
-                g: 0,
-                b: 0,
-                a: 1.0,
-            },
+            value: CssValue::Color(Color::BLACK),
         }
     }
 }
@@ -42,57 +127,20 @@ impl CssProperty for ColorProp {
         })
     }
 
-    fn compute(&mut self, current_color: Option<&SpecifiedStyle>) -> Result<&Self> {
+    fn compute(
+        &mut self,
+        current_color: Option<&SpecifiedStyle>,
+        _: &LengthContext,
+    ) -> Result<&Self> {
         let current_color = current_color.and_then(|v| v.color.as_ref());
-        match &self.value {
-            CssValue::Ident(name) => match name.to_ascii_lowercase().as_str() {
-                "currentcolor" => {
-                    if let Some(curr) = current_color {
-                        self.value = curr.value.clone();
-                    } else {
-                        self.value = CssValue::Color {
-                            r: 0,
-                            g: 0,
-                            b: 0,
-                            a: 1.0,
-                        };
-                    }
-                }
-                "transparent" => {
-                    self.value = CssValue::Color {
-                        r: 0,
-                        g: 0,
-                        b: 0,
-                        a: 0.0,
-                    };
-                }
-                _ => {
-                    if let Some((r, g, b)) = name_to_rgb(name) {
-                        self.value = CssValue::Color { r, g, b, a: 1.0 };
-                    } else {
-                        bail!("Failed to compute color: {}", name);
-                    }
-                }
-            },
-            CssValue::HexColor(hex) => {
-                let (r, g, b, a) = hex_to_rgba(hex)?;
-                self.value = CssValue::Color {
-                    r,
-                    g,
-                    b,
-                    a: a as f32 / 255.0,
-                };
-            }
-            CssValue::Color { .. } => {}
-            _ => bail!("Failed to compute color: {:?}", self.value),
-        }
+        self.value = CssValue::Color(compute_color(&self.value, current_color)?);
         Ok(self)
     }
 }
 
 impl ColorProp {
-    pub fn to_rgba(&self) -> Result<(u8, u8, u8, f32)> {
-        self.value.to_rgba()
+    pub fn to_color(&self) -> Result<Color> {
+        self.value.to_color()
     }
 }
 
@@ -125,56 +173,38 @@ impl CssProperty for BackGroundColorProp {
         })
     }
 
-    fn compute(&mut self, current_style: Option<&SpecifiedStyle>) -> Result<&Self> {
-        match &self.value {
-            CssValue::Ident(name) => match name.to_ascii_lowercase().as_str() {
-                "currentcolor" => {
-                    if let Some(curr) = current_style.and_then(|v| v.color.as_ref()) {
-                        self.value = curr.value.clone();
-                    } else {
-                        self.value = CssValue::Color {
-                            r: 0,
-                            g: 0,
-                            b: 0,
-                            a: 1.0,
-                        };
-                    }
-                }
-                "transparent" => {
-                    self.value = CssValue::Color {
-                        r: 0,
-                        g: 0,
-                        b: 0,
-                        a: 0.0,
-                    };
-                }
-                _ => {
-                    if let Some((r, g, b)) = name_to_rgb(name) {
-                        self.value = CssValue::Color { r, g, b, a: 1.0 };
-                    } else {
-                        bail!("Failed to compute color: {}", name);
-                    }
-                }
-            },
-            CssValue::HexColor(hex) => {
-                let (r, g, b, a) = hex_to_rgba(hex)?;
-                self.value = CssValue::Color {
-                    r,
-                    g,
-                    b,
-                    a: a as f32 / 255.0,
-                };
-            }
-            CssValue::Color { .. } => {}
-            _ => bail!("Failed to compute color: {:?}", self.value),
-        }
+    fn compute(
+        &mut self,
+        current_style: Option<&SpecifiedStyle>,
+        _: &LengthContext,
+    ) -> Result<&Self> {
+        let current_color = current_style.and_then(|v| v.color.as_ref());
+        self.value = CssValue::Color(compute_color(&self.value, current_color)?);
         Ok(self)
     }
 }
 
 impl BackGroundColorProp {
-    pub fn to_rgba(&self) -> Result<(u8, u8, u8, f32)> {
-        self.value.to_rgba()
+    pub fn to_color(&self) -> Result<Color> {
+        self.value.to_color()
+    }
+}
+
+/// Resolves the specified `<color>` value, where `currentColor` is the value of `color` of
+/// `current_color`, or black if it is absent.
+/// https://www.w3.org/TR/css-color-4/#resolving-color-values
+fn compute_color(value: &CssValue, current_color: Option<&ColorProp>) -> Result<Color> {
+    match value {
+        CssValue::Ident(name) if name.eq_ignore_ascii_case("currentColor") => match current_color {
+            Some(curr) => compute_color(&curr.value, None),
+            None => Ok(Color::BLACK),
+        },
+        CssValue::Ident(name) => {
+            Color::from_name(name).ok_or_else(|| anyhow!("Failed to compute color: {}", name))
+        }
+        CssValue::HexColor(hex) => Color::from_hex(hex),
+        CssValue::Color(color) => Ok(*color),
+        _ => bail!("Failed to compute color: {:?}", value),
     }
 }
 
@@ -247,22 +277,12 @@ where
     match values.next() {
         Some(v) => match v {
             ComponentValue::PreservedToken(CssToken::Ident(v)) => {
-                match v.to_ascii_lowercase().as_str() {
-                    "black" => Ok(CssValue::Ident("black".to_string())),
-                    "gray" => Ok(CssValue::Ident("gray".to_string())),
-                    "white" => Ok(CssValue::Ident("white".to_string())),
-                    "red" => Ok(CssValue::Ident("red".to_string())),
-                    "purple" => Ok(CssValue::Ident("purple".to_string())),
-                    "blueviolet" => Ok(CssValue::Ident("blueviolet".to_string())),
-                    "green" => Ok(CssValue::Ident("green".to_string())),
-                    "yellowgreen" => Ok(CssValue::Ident("yellowgreen".to_string())),
-                    "yellow" => Ok(CssValue::Ident("yellow".to_string())),
-                    "blue" => Ok(CssValue::Ident("blue".to_string())),
-                    "aqua" => Ok(CssValue::Ident("aqua".to_string())),
-                    "orange" => Ok(CssValue::Ident("orange".to_string())),
-                    "brown" => Ok(CssValue::Ident("brown".to_string())),
-                    _ => unimplemented!(),
-                }
+                ensure!(
+                    Color::from_name(&v).is_some(),
+                    "Unsupported color name: {}",
+                    v
+                );
+                Ok(CssValue::Ident(v.to_ascii_lowercase()))
             }
             _ => bail!("Invalid color value: {:?}", v),
         },
@@ -280,41 +300,21 @@ where
     {}
     match values.next() {
         Some(v) => match v {
-            ComponentValue::PreservedToken(CssToken::Hash(v, _)) => match v.len() {
-                3 => {
-                    let r = u8::from_str_radix(&v[0..1].repeat(2), 16)?;
-                    let g = u8::from_str_radix(&v[1..2].repeat(2), 16)?;
-                    let b = u8::from_str_radix(&v[2..3].repeat(2), 16)?;
-                    Ok(CssValue::HexColor(format!("{:02x}{:02x}{:02x}", r, g, b)))
-                }
-                4 => {
-                    let r = u8::from_str_radix(&v[0..1].repeat(2), 16)?;
-                    let g = u8::from_str_radix(&v[1..2].repeat(2), 16)?;
-                    let b = u8::from_str_radix(&v[2..3].repeat(2), 16)?;
-                    let a = u8::from_str_radix(&v[3..4].repeat(2), 16)?;
+            ComponentValue::PreservedToken(CssToken::Hash(v, _)) => {
+                // The hex digits are normalized to the 6- or 8-digit lowercase form.
+                let Color { r, g, b, a } = Color::from_hex(&v)?;
+                if v.len() == 4 || v.len() == 8 {
                     Ok(CssValue::HexColor(format!(
                         "{:02x}{:02x}{:02x}{:02x}",
-                        r, g, b, a
+                        r,
+                        g,
+                        b,
+                        (a * 255.0).round() as u8
                     )))
-                }
-                6 => {
-                    let r = u8::from_str_radix(&v[0..2], 16)?;
-                    let g = u8::from_str_radix(&v[2..4], 16)?;
-                    let b = u8::from_str_radix(&v[4..6], 16)?;
+                } else {
                     Ok(CssValue::HexColor(format!("{:02x}{:02x}{:02x}", r, g, b)))
                 }
-                8 => {
-                    let r = u8::from_str_radix(&v[0..2], 16)?;
-                    let g = u8::from_str_radix(&v[2..4], 16)?;
-                    let b = u8::from_str_radix(&v[4..6], 16)?;
-                    let a = u8::from_str_radix(&v[6..8], 16)?;
-                    Ok(CssValue::HexColor(format!(
-                        "{:02x}{:02x}{:02x}{:02x}",
-                        r, g, b, a
-                    )))
-                }
-                _ => bail!("Invalid hex color: {}", v),
-            },
+            }
             _ => bail!("Invalid color value: {:?}", v),
         },
         None => bail!("Expected color value but found nothing"),
@@ -332,6 +332,7 @@ where
 //   <oklab()>  |
 //   <oklch()>  |
 //   <color()>
+/// todo: Support lab(), lch(), oklab() and oklch().
 fn parse_color_function_type<I>(values: &mut Peekable<I>) -> Result<CssValue>
 where
     I: Iterator<Item = ComponentValue>,
@@ -342,12 +343,12 @@ where
     {}
     match values.next() {
         Some(v) => match v {
-            ComponentValue::Function { name, values } => match name.as_str() {
-                "rgb" | "rgba" => {
-                    let mut args = values.iter().cloned().peekable();
-                    parse_color_rgb_function_type(&mut args)
-                }
-                _ => unimplemented!(),
+            ComponentValue::Function { name, values } => match name.to_ascii_lowercase().as_str() {
+                "rgb" | "rgba" => parse_color_rgb_function_type(&values),
+                "hsl" | "hsla" => parse_color_hsl_function_type(&values),
+                "hwb" => parse_color_hwb_function_type(&values),
+                "color" => parse_color_color_function_type(&values),
+                _ => bail!("Unsupported color function: {}", name),
             },
             _ => bail!("Invalid color value: {:?}", v),
         },
@@ -355,6 +356,133 @@ where
     }
 }
 
+/// The arguments of rgb(), hsl() and hwb(), which are either comma-separated in the legacy syntax or
+/// space-separated with `/` before the alpha in the modern syntax.
+struct ColorFunctionArgs<'a> {
+    channels: [&'a ComponentValue; 3],
+    alpha: Option<&'a ComponentValue>,
+    is_legacy: bool,
+}
+
+impl<'a> ColorFunctionArgs<'a> {
+    fn parse(values: &'a [ComponentValue], allows_legacy: bool) -> Result<Self> {
+        let values = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+            .collect::<Vec<_>>();
+        let is_comma = |v: &&ComponentValue| **v == ComponentValue::PreservedToken(CssToken::Comma);
+        let is_legacy = values.iter().any(is_comma);
+        let args = if is_legacy {
+            ensure!(allows_legacy, "Unexpected comma in color function");
+            // The values and the commas must alternate.
+            ensure!(
+                values.len() % 2 == 1
+                    && values
+                        .iter()
+                        .enumerate()
+                        .all(|(i, v)| is_comma(v) == (i % 2 == 1)),
+                "Invalid comma-separated color function"
+            );
+            values.into_iter().step_by(2).collect::<Vec<_>>()
+        } else {
+            match values.as_slice() {
+                [_, _, _] => values,
+                [c1, c2, c3, ComponentValue::PreservedToken(CssToken::Delim('/')), alpha] => {
+                    vec![*c1, *c2, *c3, *alpha]
+                }
+                _ => bail!("Invalid color function"),
+            }
+        };
+        let (channels, alpha) = match args[..] {
+            [c1, c2, c3] => ([c1, c2, c3], None),
+            [c1, c2, c3, alpha] => ([c1, c2, c3], Some(alpha)),
+            _ => bail!("Invalid number of arguments in color function"),
+        };
+        if is_legacy {
+            ensure!(
+                channels.iter().chain(&alpha).all(|v| !is_none(v)),
+                "`none` is not allowed in the legacy color syntax"
+            );
+        }
+        Ok(Self {
+            channels,
+            alpha,
+            is_legacy,
+        })
+    }
+
+    /// Returns the alpha in [0.0, 1.0], which is opaque if it is omitted.
+    fn get_alpha(&self) -> Result<f64> {
+        let Some(alpha) = self.alpha else {
+            return Ok(1.0);
+        };
+        let alpha = match alpha {
+            ComponentValue::PreservedToken(CssToken::Percentage(v)) => *v as f64 / 100.0,
+            _ => to_number(alpha)?,
+        };
+        Ok(alpha.clamp(0.0, 1.0))
+    }
+}
+
+/// `none` is the missing component, which is treated as zero when the color is converted.
+/// https://www.w3.org/TR/css-color-4/#missing
+fn is_none(value: &ComponentValue) -> bool {
+    matches!(value, ComponentValue::PreservedToken(CssToken::Ident(v)) if v.eq_ignore_ascii_case("none"))
+}
+
+/// Returns the `<number>` or `none` value.
+fn to_number(value: &ComponentValue) -> Result<f64> {
+    match value {
+        ComponentValue::PreservedToken(CssToken::Number(NumericType::Integer(v))) => Ok(*v as f64),
+        ComponentValue::PreservedToken(CssToken::Number(NumericType::Number(v))) => Ok(*v as f64),
+        _ if is_none(value) => Ok(0.0),
+        _ => bail!("Invalid number in color function: {:?}", value),
+    }
+}
+
+/// Returns the `<percentage>`, `<number>` or `none` value in [0.0, 1.0], where the number is the same as
+/// the percentage.
+fn to_fraction(value: &ComponentValue) -> Result<f64> {
+    let value = match value {
+        ComponentValue::PreservedToken(CssToken::Percentage(v)) => *v as f64,
+        _ => to_number(value)?,
+    };
+    Ok((value / 100.0).clamp(0.0, 1.0))
+}
+
+/// Returns the `<hue>` or `none` value in degrees, which is in [0.0, 360.0).
+/// https://www.w3.org/TR/css-color-4/#hue-syntax
+fn to_hue(value: &ComponentValue) -> Result<f64> {
+    let degrees = match value {
+        ComponentValue::PreservedToken(CssToken::Dimension(number, unit)) => {
+            let number = match number {
+                NumericType::Integer(v) => *v as f64,
+                NumericType::Number(v) => *v as f64,
+            };
+            match unit.to_ascii_lowercase().as_str() {
+                "deg" => number,
+                "grad" => number * 0.9,
+                "rad" => number.to_degrees(),
+                "turn" => number * 360.0,
+                _ => bail!("Invalid angle unit in color function: {}", unit),
+            }
+        }
+        _ => to_number(value)?,
+    };
+    Ok(degrees.rem_euclid(360.0))
+}
+
+/// Converts the HSL color to sRGB, where the saturation and the lightness are in [0.0, 1.0].
+/// https://www.w3.org/TR/css-color-4/#hsl-to-rgb
+fn hsl_to_rgb(hue: f64, saturation: f64, lightness: f64) -> [f64; 3] {
+    let f = |n: f64| {
+        let k = (n + hue / 30.0) % 12.0;
+        let a = saturation * lightness.min(1.0 - lightness);
+        lightness - a * (k - 3.0).min(9.0 - k).clamp(-1.0, 1.0)
+    };
+    [f(0.0), f(8.0), f(4.0)]
+}
+
 // rgb() = [ <legacy-rgb-syntax> | <modern-rgb-syntax> ]
 // rgba() = [ <legacy-rgba-syntax> | <modern-rgba-syntax> ]
 // <legacy-rgb-syntax> =   rgb( <percentage>#{3} , <alpha-value>? ) |
@@ -367,243 +495,379 @@ where
 // <modern-rgba-syntax> = rgba(
 //   [ <number> | <percentage> | none]{3}
 //   [ / [<alpha-value> | none] ]?  )
+/// The channels out of the range are clamped.
 /// https://www.w3.org/TR/css-color-4/#rgb-functions
-fn parse_color_rgb_function_type<I>(values: &mut Peekable<I>) -> Result<CssValue>
-where
-    I: Iterator<Item = ComponentValue>,
-{
-    let mut is_legacy_syntax = false;
-    while values
-        .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-        .is_some()
-    {}
+fn parse_color_rgb_function_type(values: &[ComponentValue]) -> Result<CssValue> {
+    let args = ColorFunctionArgs::parse(values, true)?;
+    let is_percentage =
+        |v: &ComponentValue| matches!(v, ComponentValue::PreservedToken(CssToken::Percentage(_)));
+    if args.is_legacy {
+        ensure!(
+            args.channels.iter().all(|v| is_percentage(v))
+                || !args.channels.iter().any(|v| is_percentage(v)),
+            "The channels of the legacy rgb function must be all numbers or all percentages"
+        );
+    }
 
-    let r = values
-        .next_if(|v| {
-            matches!(
-                v,
-                ComponentValue::PreservedToken(CssToken::Number(
-                    NumericType::Integer(_) | NumericType::Number(_)
-                ))
-            )
-        })
-        .ok_or_else(|| anyhow!("Invalid rgb function"))?;
-    while values
-        .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-        .is_some()
-    {}
-    if values
-        .next_if_eq(&ComponentValue::PreservedToken(CssToken::Comma))
-        .is_some()
-    {
-        is_legacy_syntax = true;
-        while values
-            .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-            .is_some()
-        {}
-    }
-
-    let g = values
-        .next_if(|v| {
-            matches!(
-                v,
-                ComponentValue::PreservedToken(CssToken::Number(
-                    NumericType::Integer(_) | NumericType::Number(_)
-                ))
-            )
-        })
-        .ok_or_else(|| anyhow!("Invalid rgb function"))?;
-    while values
-        .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-        .is_some()
-    {}
-    if values
-        .next_if_eq(&ComponentValue::PreservedToken(CssToken::Comma))
-        .is_some()
-    {
-        if !is_legacy_syntax {
-            bail!("Invalid rgb function");
-        }
-        while values
-            .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-            .is_some()
-        {}
-    }
-
-    let b = values
-        .next_if(|v| {
-            matches!(
-                v,
-                ComponentValue::PreservedToken(CssToken::Number(
-                    NumericType::Integer(_) | NumericType::Number(_)
-                ))
-            )
-        })
-        .ok_or_else(|| anyhow!("Invalid rgb function"))?;
-    while values
-        .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-        .is_some()
-    {}
+    let mut rgb = [0.0; 3];
+    for (value, channel) in rgb.iter_mut().zip(args.channels) {
+        *value = match channel {
+            ComponentValue::PreservedToken(CssToken::Percentage(v)) => *v as f64 / 100.0,
+            _ => to_number(channel)? / 255.0,
+        };
+    }
+    Ok(CssValue::Color(Color::from_unit_rgba(
+        rgb,
+        args.get_alpha()?,
+    )))
+}
 
-    let is_separator_present = if is_legacy_syntax {
-        if values
-            .next_if_eq(&ComponentValue::PreservedToken(CssToken::Comma))
-            .is_some()
-        {
-            while values
-                .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-                .is_some()
-            {}
-            true
-        } else {
-            false
-        }
-    } else if values
-        .next_if_eq(&ComponentValue::PreservedToken(CssToken::Delim('/')))
-        .is_some()
-    {
-        while values
-            .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-            .is_some()
-        {}
-        true
+// hsl() = [ <legacy-hsl-syntax> | <modern-hsl-syntax> ]
+// hsla() = [ <legacy-hsla-syntax> | <modern-hsla-syntax> ]
+// <modern-hsl-syntax> = hsl(
+//     [<hue> | none]
+//     [<percentage> | <number> | none]
+//     [<percentage> | <number> | none]
+//     [ / [<alpha-value> | none] ]? )
+// <legacy-hsl-syntax> = hsl( <hue>, <percentage>, <percentage>, <alpha-value>? )
+/// https://www.w3.org/TR/css-color-4/#the-hsl-notation
+fn parse_color_hsl_function_type(values: &[ComponentValue]) -> Result<CssValue> {
+    let args = ColorFunctionArgs::parse(values, true)?;
+    let [hue, saturation, lightness] = args.channels;
+    if args.is_legacy {
+        ensure!(
+            [saturation, lightness]
+                .iter()
+                .all(|v| matches!(v, ComponentValue::PreservedToken(CssToken::Percentage(_)))),
+            "The saturation and the lightness of the legacy hsl function must be percentages"
+        );
+    }
+    let rgb = hsl_to_rgb(
+        to_hue(hue)?,
+        to_fraction(saturation)?,
+        to_fraction(lightness)?,
+    );
+    Ok(CssValue::Color(Color::from_unit_rgba(
+        rgb,
+        args.get_alpha()?,
+    )))
+}
+
+// hwb() = hwb(
+//   [<hue> | none]
+//   [<percentage> | <number> | none]
+//   [<percentage> | <number> | none]
+//   [ / [<alpha-value> | none] ]? )
+/// https://www.w3.org/TR/css-color-4/#the-hwb-notation
+fn parse_color_hwb_function_type(values: &[ComponentValue]) -> Result<CssValue> {
+    let args = ColorFunctionArgs::parse(values, false)?;
+    let [hue, whiteness, blackness] = args.channels;
+    let (hue, whiteness, blackness) = (
+        to_hue(hue)?,
+        to_fraction(whiteness)?,
+        to_fraction(blackness)?,
+    );
+    // The whiteness and the blackness are normalized so that their sum doesn't exceed 100%.
+    // https://www.w3.org/TR/css-color-4/#hwb-to-rgb
+    let rgb = if whiteness + blackness >= 1.0 {
+        [whiteness / (whiteness + blackness); 3]
     } else {
-        false
+        hsl_to_rgb(hue, 1.0, 0.5).map(|v| v * (1.0 - whiteness - blackness) + whiteness)
     };
+    Ok(CssValue::Color(Color::from_unit_rgba(
+        rgb,
+        args.get_alpha()?,
+    )))
+}
 
-    let a = match values.next() {
-        Some(ComponentValue::PreservedToken(CssToken::Number(NumericType::Number(v)))) => {
-            ensure!(
-                (0.0..=1.0).contains(&v),
-                "Invalid value for alpha in rgb function"
-            );
-            v
+// color() = color( <colorspace-params> [ / [ <alpha-value> | none ] ]? )
+// <colorspace-params> = [ <predefined-rgb-params> | <xyz-params>]
+// <predefined-rgb-params> = <predefined-rgb> [ <number> | <percentage> | none ]{3}
+/// The color is converted to the color space of the output surface here, since the computed colors are
+/// in sRGB.
+/// todo: Support the other predefined color spaces and keep the colors out of the sRGB gamut.
+/// https://www.w3.org/TR/css-color-4/#color-function
+fn parse_color_color_function_type(values: &[ComponentValue]) -> Result<CssValue> {
+    let values = values
+        .iter()
+        .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+        .collect::<Vec<_>>();
+    let (space, channels, alpha) = match values[..] {
+        [ComponentValue::PreservedToken(CssToken::Ident(space)), c1, c2, c3] => {
+            (space, [c1, c2, c3], None)
         }
-        Some(_) => bail!("Invalid rgb function"),
-        None => {
-            if is_separator_present {
-                bail!("Invalid rgb function");
-            }
-            1.0
+        [ComponentValue::PreservedToken(CssToken::Ident(space)), c1, c2, c3, ComponentValue::PreservedToken(CssToken::Delim('/')), alpha] => {
+            (space, [c1, c2, c3], Some(alpha))
         }
+        _ => bail!("Invalid color function"),
     };
+    let space = RgbColorSpace::from_name(space)
+        .ok_or_else(|| anyhow!("Unsupported color space in color function: {}", space))?;
 
-    let r = match r {
-        ComponentValue::PreservedToken(CssToken::Number(NumericType::Integer(r))) => {
-            ensure!(
-                (0..=255).contains(&r),
-                "Invalid value for red in rgb function"
-            );
-            r as u8
-        }
-        ComponentValue::PreservedToken(CssToken::Number(NumericType::Number(r))) => {
-            ensure!(
-                (0.0..=255.0).contains(&r),
-                "Invalid value for red in rgb function"
-            );
-            r.round() as u8
-        }
-        _ => bail!("Invalid rgb function"),
-    };
-    let g = match g {
-        ComponentValue::PreservedToken(CssToken::Number(NumericType::Integer(g))) => {
-            ensure!(
-                (0..=255).contains(&g),
-                "Invalid value for green in rgb function"
-            );
-            g as u8
-        }
-        ComponentValue::PreservedToken(CssToken::Number(NumericType::Number(g))) => {
-            ensure!(
-                (0.0..=255.0).contains(&g),
-                "Invalid value for green in rgb function"
-            );
-            g.round() as u8
+    // `none` is treated as zero when the color is converted.
+    // https://www.w3.org/TR/css-color-4/#missing
+    let to_number = |v: &ComponentValue| -> Result<f64> {
+        match v {
+            ComponentValue::PreservedToken(CssToken::Number(NumericType::Integer(v))) => {
+                Ok(*v as f64)
+            }
+            ComponentValue::PreservedToken(CssToken::Number(NumericType::Number(v))) => {
+                Ok(*v as f64)
+            }
+            ComponentValue::PreservedToken(CssToken::Percentage(v)) => Ok(*v as f64 / 100.0),
+            ComponentValue::PreservedToken(CssToken::Ident(v))
+                if v.eq_ignore_ascii_case("none") =>
+            {
+                Ok(0.0)
+            }
+            _ => bail!("Invalid value in color function: {:?}", v),
         }
-        _ => bail!("Invalid rgb function"),
     };
-    let b = match b {
-        ComponentValue::PreservedToken(CssToken::Number(NumericType::Integer(b))) => {
-            ensure!(
-                (0..=255).contains(&b),
-                "Invalid value for blue in rgb function"
-            );
-            b as u8
-        }
-        ComponentValue::PreservedToken(CssToken::Number(NumericType::Number(b))) => {
-            ensure!(
-                (0.0..=255.0).contains(&b),
-                "Invalid value for blue in rgb function"
-            );
-            b.round() as u8
-        }
-        _ => bail!("Invalid rgb function"),
+    let mut rgb = [0.0; 3];
+    for (value, channel) in rgb.iter_mut().zip(channels) {
+        *value = to_number(channel)?;
+    }
+    let [r, g, b] = ColorTransform::new(&space, &RgbColorSpace::OUTPUT)?.convert(rgb);
+    let a = match alpha {
+        Some(alpha) => to_number(alpha)?.clamp(0.0, 1.0),
+        None => 1.0,
     };
 
-    Ok(CssValue::Color { r, g, b, a })
-}
-
-fn name_to_rgb(name: &str) -> Option<(u8, u8, u8)> {
-    match name.to_ascii_lowercase().as_str() {
-        "black" => Some((0, 0, 0)),
-        "gray" => Some((128, 128, 128)),
-        "white" => Some((255, 255, 255)),
-        "red" => Some((255, 0, 0)),
-        "purple" => Some((128, 0, 128)),
-        "blueviolet" => Some((138, 43, 226)),
-        "green" => Some((0, 128, 0)),
-        "yellowgreen" => Some((154, 205, 50)),
-        "yellow" => Some((255, 255, 0)),
-        "blue" => Some((0, 0, 255)),
-        "aqua" => Some((0, 255, 255)),
-        "orange" => Some((255, 165, 0)),
-        "brown" => Some((165, 42, 42)),
-        _ => None,
-    }
-}
-
-pub fn rgb_to_name(r: u8, g: u8, b: u8) -> Option<String> {
-    match (r, g, b) {
-        (0, 0, 0) => Some("black".to_string()),
-        (128, 128, 128) => Some("gray".to_string()),
-        (255, 255, 255) => Some("white".to_string()),
-        (255, 0, 0) => Some("red".to_string()),
-        (128, 0, 128) => Some("purple".to_string()),
-        (138, 43, 226) => Some("blueviolet".to_string()),
-        (0, 128, 0) => Some("green".to_string()),
-        (154, 205, 50) => Some("yellowgreen".to_string()),
-        (255, 255, 0) => Some("yellow".to_string()),
-        (0, 0, 255) => Some("blue".to_string()),
-        (0, 255, 255) => Some("aqua".to_string()),
-        (255, 165, 0) => Some("orange".to_string()),
-        (165, 42, 42) => Some("brown".to_string()),
-        _ => None,
-    }
-}
-
-fn hex_to_rgba(hex: &str) -> Result<(u8, u8, u8, u8)> {
-    match hex.len() {
-        6 => {
-            let r = u8::from_str_radix(&hex[0..2], 16)?;
-            let g = u8::from_str_radix(&hex[2..4], 16)?;
-            let b = u8::from_str_radix(&hex[4..6], 16)?;
-            Ok((r, g, b, 255))
-        }
-        8 => {
-            let r = u8::from_str_radix(&hex[0..2], 16)?;
-            let g = u8::from_str_radix(&hex[2..4], 16)?;
-            let b = u8::from_str_radix(&hex[4..6], 16)?;
-            let a = u8::from_str_radix(&hex[6..8], 16)?;
-            Ok((r, g, b, a))
-        }
-        _ => bail!("Invalid hex color: {}", hex),
-    }
+    Ok(CssValue::Color(Color::from_unit_rgba([r, g, b], a)))
+}
+
+/// The named colors, sorted by name for the binary search.
+/// https://www.w3.org/TR/css-color-4/#named-colors
+const NAMED_COLORS: [(&str, (u8, u8, u8)); 148] = [
+    ("aliceblue", (240, 248, 255)),
+    ("antiquewhite", (250, 235, 215)),
+    ("aqua", (0, 255, 255)),
+    ("aquamarine", (127, 255, 212)),
+    ("azure", (240, 255, 255)),
+    ("beige", (245, 245, 220)),
+    ("bisque", (255, 228, 196)),
+    ("black", (0, 0, 0)),
+    ("blanchedalmond", (255, 235, 205)),
+    ("blue", (0, 0, 255)),
+    ("blueviolet", (138, 43, 226)),
+    ("brown", (165, 42, 42)),
+    ("burlywood", (222, 184, 135)),
+    ("cadetblue", (95, 158, 160)),
+    ("chartreuse", (127, 255, 0)),
+    ("chocolate", (210, 105, 30)),
+    ("coral", (255, 127, 80)),
+    ("cornflowerblue", (100, 149, 237)),
+    ("cornsilk", (255, 248, 220)),
+    ("crimson", (220, 20, 60)),
+    ("cyan", (0, 255, 255)),
+    ("darkblue", (0, 0, 139)),
+    ("darkcyan", (0, 139, 139)),
+    ("darkgoldenrod", (184, 134, 11)),
+    ("darkgray", (169, 169, 169)),
+    ("darkgreen", (0, 100, 0)),
+    ("darkgrey", (169, 169, 169)),
+    ("darkkhaki", (189, 183, 107)),
+    ("darkmagenta", (139, 0, 139)),
+    ("darkolivegreen", (85, 107, 47)),
+    ("darkorange", (255, 140, 0)),
+    ("darkorchid", (153, 50, 204)),
+    ("darkred", (139, 0, 0)),
+    ("darksalmon", (233, 150, 122)),
+    ("darkseagreen", (143, 188, 143)),
+    ("darkslateblue", (72, 61, 139)),
+    ("darkslategray", (47, 79, 79)),
+    ("darkslategrey", (47, 79, 79)),
+    ("darkturquoise", (0, 206, 209)),
+    ("darkviolet", (148, 0, 211)),
+    ("deeppink", (255, 20, 147)),
+    ("deepskyblue", (0, 191, 255)),
+    ("dimgray", (105, 105, 105)),
+    ("dimgrey", (105, 105, 105)),
+    ("dodgerblue", (30, 144, 255)),
+    ("firebrick", (178, 34, 34)),
+    ("floralwhite", (255, 250, 240)),
+    ("forestgreen", (34, 139, 34)),
+    ("fuchsia", (255, 0, 255)),
+    ("gainsboro", (220, 220, 220)),
+    ("ghostwhite", (248, 248, 255)),
+    ("gold", (255, 215, 0)),
+    ("goldenrod", (218, 165, 32)),
+    ("gray", (128, 128, 128)),
+    ("green", (0, 128, 0)),
+    ("greenyellow", (173, 255, 47)),
+    ("grey", (128, 128, 128)),
+    ("honeydew", (240, 255, 240)),
+    ("hotpink", (255, 105, 180)),
+    ("indianred", (205, 92, 92)),
+    ("indigo", (75, 0, 130)),
+    ("ivory", (255, 255, 240)),
+    ("khaki", (240, 230, 140)),
+    ("lavender", (230, 230, 250)),
+    ("lavenderblush", (255, 240, 245)),
+    ("lawngreen", (124, 252, 0)),
+    ("lemonchiffon", (255, 250, 205)),
+    ("lightblue", (173, 216, 230)),
+    ("lightcoral", (240, 128, 128)),
+    ("lightcyan", (224, 255, 255)),
+    ("lightgoldenrodyellow", (250, 250, 210)),
+    ("lightgray", (211, 211, 211)),
+    ("lightgreen", (144, 238, 144)),
+    ("lightgrey", (211, 211, 211)),
+    ("lightpink", (255, 182, 193)),
+    ("lightsalmon", (255, 160, 122)),
+    ("lightseagreen", (32, 178, 170)),
+    ("lightskyblue", (135, 206, 250)),
+    ("lightslategray", (119, 136, 153)),
+    ("lightslategrey", (119, 136, 153)),
+    ("lightsteelblue", (176, 196, 222)),
+    ("lightyellow", (255, 255, 224)),
+    ("lime", (0, 255, 0)),
+    ("limegreen", (50, 205, 50)),
+    ("linen", (250, 240, 230)),
+    ("magenta", (255, 0, 255)),
+    ("maroon", (128, 0, 0)),
+    ("mediumaquamarine", (102, 205, 170)),
+    ("mediumblue", (0, 0, 205)),
+    ("mediumorchid", (186, 85, 211)),
+    ("mediumpurple", (147, 112, 219)),
+    ("mediumseagreen", (60, 179, 113)),
+    ("mediumslateblue", (123, 104, 238)),
+    ("mediumspringgreen", (0, 250, 154)),
+    ("mediumturquoise", (72, 209, 204)),
+    ("mediumvioletred", (199, 21, 133)),
+    ("midnightblue", (25, 25, 112)),
+    ("mintcream", (245, 255, 250)),
+    ("mistyrose", (255, 228, 225)),
+    ("moccasin", (255, 228, 181)),
+    ("navajowhite", (255, 222, 173)),
+    ("navy", (0, 0, 128)),
+    ("oldlace", (253, 245, 230)),
+    ("olive", (128, 128, 0)),
+    ("olivedrab", (107, 142, 35)),
+    ("orange", (255, 165, 0)),
+    ("orangered", (255, 69, 0)),
+    ("orchid", (218, 112, 214)),
+    ("palegoldenrod", (238, 232, 170)),
+    ("palegreen", (152, 251, 152)),
+    ("paleturquoise", (175, 238, 238)),
+    ("palevioletred", (219, 112, 147)),
+    ("papayawhip", (255, 239, 213)),
+    ("peachpuff", (255, 218, 185)),
+    ("peru", (205, 133, 63)),
+    ("pink", (255, 192, 203)),
+    ("plum", (221, 160, 221)),
+    ("powderblue", (176, 224, 230)),
+    ("purple", (128, 0, 128)),
+    ("rebeccapurple", (102, 51, 153)),
+    ("red", (255, 0, 0)),
+    ("rosybrown", (188, 143, 143)),
+    ("royalblue", (65, 105, 225)),
+    ("saddlebrown", (139, 69, 19)),
+    ("salmon", (250, 128, 114)),
+    ("sandybrown", (244, 164, 96)),
+    ("seagreen", (46, 139, 87)),
+    ("seashell", (255, 245, 238)),
+    ("sienna", (160, 82, 45)),
+    ("silver", (192, 192, 192)),
+    ("skyblue", (135, 206, 235)),
+    ("slateblue", (106, 90, 205)),
+    ("slategray", (112, 128, 144)),
+    ("slategrey", (112, 128, 144)),
+    ("snow", (255, 250, 250)),
+    ("springgreen", (0, 255, 127)),
+    ("steelblue", (70, 130, 180)),
+    ("tan", (210, 180, 140)),
+    ("teal", (0, 128, 128)),
+    ("thistle", (216, 191, 216)),
+    ("tomato", (255, 99, 71)),
+    ("turquoise", (64, 224, 208)),
+    ("violet", (238, 130, 238)),
+    ("wheat", (245, 222, 179)),
+    ("white", (255, 255, 255)),
+    ("whitesmoke", (245, 245, 245)),
+    ("yellow", (255, 255, 0)),
+    ("yellowgreen", (154, 205, 50)),
+];
+
+/// Returns the name of the color if it has one, which is the first one in alphabetical order if it has
+/// several, e.g. `aqua` rather than `cyan`.
+pub fn rgb_to_name(r: u8, g: u8, b: u8) -> Option<&'static str> {
+    NAMED_COLORS
+        .iter()
+        .find(|(_, rgb)| *rgb == (r, g, b))
+        .map(|(name, _)| *name)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::renderer::css::token::HashType;
+    use crate::renderer::css::parser::CssParser;
+    use crate::renderer::css::token::{CssTokenizer, HashType};
+
+    fn compute(css: &str) -> Result<Color> {
+        let values =
+            CssParser::new(&CssTokenizer::new(css).tokenize()?).parse_list_of_component_values();
+        let current_color = ColorProp {
+            value: CssValue::Ident("teal".to_string()),
+        };
+        compute_color(&ColorProp::parse(&values)?.value, Some(&current_color))
+    }
+
+    #[test]
+    fn compute_color_syntaxes() {
+        assert_eq!(
+            compute("RebeccaPurple").unwrap(),
+            Color::new(102, 51, 153, 1.0)
+        );
+        assert_eq!(compute("transparent").unwrap(), Color::TRANSPARENT);
+        assert_eq!(
+            compute("currentcolor").unwrap(),
+            Color::new(0, 128, 128, 1.0)
+        );
+        assert_eq!(
+            compute("#f008").unwrap(),
+            Color::new(255, 0, 0, 136.0 / 255.0)
+        );
+        assert!(compute("#ff00f").is_err());
+        assert!(compute("reddish").is_err());
+
+        // The percentages and the values out of the range are clamped.
+        assert_eq!(
+            compute("rgb(100% 50% none / 25%)").unwrap(),
+            Color::new(255, 128, 0, 0.25)
+        );
+        assert_eq!(
+            compute("rgba(300, -10, 0, 2)").unwrap(),
+            Color::new(255, 0, 0, 1.0)
+        );
+        assert!(compute("rgb(255, 50%, 0)").is_err());
+        assert!(compute("rgb(255, 0 0)").is_err());
+        assert!(compute("rgb(255, 0, none)").is_err());
+
+        assert_eq!(
+            compute("hsl(120 100% 25%)").unwrap(),
+            Color::new(0, 128, 0, 1.0)
+        );
+        assert_eq!(
+            compute("hsla(0.5turn, 100%, 50%, 0.5)").unwrap(),
+            Color::new(0, 255, 255, 0.5)
+        );
+        assert_eq!(
+            compute("hsl(-120deg 100 50)").unwrap(),
+            Color::new(0, 0, 255, 1.0)
+        );
+        assert!(compute("hsl(0, 100, 50)").is_err());
+
+        assert_eq!(compute("hwb(0 0% 0%)").unwrap(), Color::new(255, 0, 0, 1.0));
+        assert_eq!(
+            compute("hwb(90 60% 60%)").unwrap(),
+            Color::new(128, 128, 128, 1.0)
+        );
+        assert!(compute("hwb(0, 0%, 0%)").is_err());
+
+        assert_eq!(Color::new(0, 255, 255, 1.0).to_string(), "aqua");
+        assert_eq!(Color::new(1, 2, 3, 1.0).to_string(), "rgb(1, 2, 3)");
+    }
 
     #[test]
     fn parse_named_color() {
@@ -651,12 +915,7 @@ mod tests {
             }])
             .unwrap(),
             ColorProp {
-                value: CssValue::Color {
-                    r: 255,
-                    g: 0,
-                    b: 0,
-                    a: 1.0
-                }
+                value: CssValue::Color(Color::new(255, 0, 0, 1.0))
             }
         );
         assert_eq!(
@@ -676,12 +935,7 @@ mod tests {
             }])
             .unwrap(),
             ColorProp {
-                value: CssValue::Color {
-                    r: 255,
-                    g: 0,
-                    b: 0,
-                    a: 0.5
-                }
+                value: CssValue::Color(Color::new(255, 0, 0, 0.5))
             }
         );
 
@@ -703,12 +957,7 @@ mod tests {
             }])
             .unwrap(),
             ColorProp {
-                value: CssValue::Color {
-                    r: 255,
-                    g: 0,
-                    b: 0,
-                    a: 0.5
-                }
+                value: CssValue::Color(Color::new(255, 0, 0, 0.5))
             }
         );
 
@@ -728,12 +977,7 @@ mod tests {
             }])
             .unwrap(),
             ColorProp {
-                value: CssValue::Color {
-                    r: 10,
-                    g: 5,
-                    b: 200,
-                    a: 0.9
-                }
+                value: CssValue::Color(Color::new(10, 5, 200, 0.9))
             }
         );
     }
@@ -860,4 +1104,70 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn parse_color_function() {
+        let color = |values: Vec<CssToken>| {
+            ColorProp::parse(&[ComponentValue::Function {
+                name: "color".to_string(),
+                values: values
+                    .into_iter()
+                    .map(ComponentValue::PreservedToken)
+                    .collect(),
+            }])
+            .map(|prop| prop.value)
+        };
+        let ident = |v: &str| CssToken::Ident(v.to_string());
+        let number = |v: f32| CssToken::Number(NumericType::Number(v));
+
+        // Display P3 colors are converted to sRGB, and the ones out of its gamut are clipped.
+        assert_eq!(
+            color(vec![
+                ident("display-p3"),
+                CssToken::Whitespace,
+                number(0.8),
+                CssToken::Whitespace,
+                CssToken::Percentage(40.0),
+                CssToken::Whitespace,
+                number(0.3),
+            ])
+            .unwrap(),
+            CssValue::Color(Color::new(219, 94, 68, 1.0))
+        );
+        assert_eq!(
+            color(vec![
+                ident("display-p3"),
+                CssToken::Whitespace,
+                CssToken::Number(NumericType::Integer(1)),
+                CssToken::Whitespace,
+                ident("none"),
+                CssToken::Whitespace,
+                number(0.0),
+                CssToken::Whitespace,
+                CssToken::Delim('/'),
+                CssToken::Whitespace,
+                number(0.5),
+            ])
+            .unwrap(),
+            CssValue::Color(Color::new(255, 0, 0, 0.5))
+        );
+        assert_eq!(
+            color(vec![
+                ident("srgb-linear"),
+                number(0.5),
+                number(0.5),
+                number(0.5)
+            ])
+            .unwrap(),
+            CssValue::Color(Color::new(188, 188, 188, 1.0))
+        );
+        assert!(color(vec![
+            ident("rec2020"),
+            number(1.0),
+            number(0.0),
+            number(0.0)
+        ])
+        .is_err());
+        assert!(color(vec![ident("srgb"), number(1.0), number(0.0)]).is_err());
+    }
 }
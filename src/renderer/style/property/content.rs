@@ -0,0 +1,354 @@
+use std::fmt;
+
+use anyhow::{bail, ensure, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::{CssToken, NumericType};
+use crate::renderer::style::property::{CssProperty, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// The item of the `content` property, which is replaced with text when the pseudo-element is generated.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ContentItem {
+    String(String),
+    /// `attr(<name>)`, which is the value of the attribute of the originating element.
+    Attr(String),
+    /// `counter(<name>, <counter-style>?)`, which is the value of the innermost counter with the name.
+    Counter(String, CounterStyle),
+}
+
+impl fmt::Display for ContentItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::String(s) => write!(f, "{:?}", s),
+            Self::Attr(name) => write!(f, "attr({})", name),
+            Self::Counter(name, CounterStyle::Decimal) => write!(f, "counter({})", name),
+            Self::Counter(name, style) => write!(f, "counter({}, {})", name, style),
+        }
+    }
+}
+
+/// The predefined counter styles with which the counters are represented.
+/// todo: Support the other counter styles and `@counter-style` rules.
+/// https://drafts.csswg.org/css-counter-styles/#predefined-counters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CounterStyle {
+    Decimal,
+    LowerAlpha,
+    UpperAlpha,
+    LowerRoman,
+    UpperRoman,
+    None,
+}
+
+impl fmt::Display for CounterStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Decimal => write!(f, "decimal"),
+            Self::LowerAlpha => write!(f, "lower-alpha"),
+            Self::UpperAlpha => write!(f, "upper-alpha"),
+            Self::LowerRoman => write!(f, "lower-roman"),
+            Self::UpperRoman => write!(f, "upper-roman"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
+impl CounterStyle {
+    pub fn parse(name: &str) -> Result<Self> {
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "decimal" => Self::Decimal,
+            "lower-alpha" | "lower-latin" => Self::LowerAlpha,
+            "upper-alpha" | "upper-latin" => Self::UpperAlpha,
+            "lower-roman" => Self::LowerRoman,
+            "upper-roman" => Self::UpperRoman,
+            "none" => Self::None,
+            _ => bail!("Unsupported counter style: {}", name),
+        })
+    }
+
+    /// Returns the representation of the counter value. The values out of the range of the style are
+    /// represented in `decimal`.
+    /// https://drafts.csswg.org/css-counter-styles/#generate-a-counter
+    pub fn format(&self, value: i32) -> String {
+        match self {
+            Self::LowerAlpha | Self::UpperAlpha if value > 0 => {
+                // https://drafts.csswg.org/css-counter-styles/#alphabetic-system
+                let mut value = value as u32;
+                let mut letters = Vec::new();
+                while value > 0 {
+                    value -= 1;
+                    letters.push(char::from(b'a' + (value % 26) as u8));
+                    value /= 26;
+                }
+                let letters = letters.into_iter().rev().collect::<String>();
+                if *self == Self::UpperAlpha {
+                    letters.to_ascii_uppercase()
+                } else {
+                    letters
+                }
+            }
+            Self::LowerRoman | Self::UpperRoman if (1..4000).contains(&value) => {
+                // https://drafts.csswg.org/css-counter-styles/#additive-system
+                const SYMBOLS: [(i32, &str); 13] = [
+                    (1000, "M"),
+                    (900, "CM"),
+                    (500, "D"),
+                    (400, "CD"),
+                    (100, "C"),
+                    (90, "XC"),
+                    (50, "L"),
+                    (40, "XL"),
+                    (10, "X"),
+                    (9, "IX"),
+                    (5, "V"),
+                    (4, "IV"),
+                    (1, "I"),
+                ];
+                let mut value = value;
+                let mut numeral = String::new();
+                for (weight, symbol) in SYMBOLS {
+                    while value >= weight {
+                        numeral.push_str(symbol);
+                        value -= weight;
+                    }
+                }
+                if *self == Self::LowerRoman {
+                    numeral.to_ascii_lowercase()
+                } else {
+                    numeral
+                }
+            }
+            Self::None => String::new(),
+            _ => value.to_string(),
+        }
+    }
+}
+
+/// The `content` property, which is applied only to the `::before` and `::after` pseudo-elements for now.
+/// The items are `None` for `normal` and `none`, with which no pseudo-element is generated.
+/// todo: Support the images, the quotes and `counters()`.
+/// https://drafts.csswg.org/css-content/#content-property
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ContentProp {
+    pub items: Option<Vec<ContentItem>>,
+}
+
+impl fmt::Display for ContentProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.items {
+            Some(items) => write!(
+                f,
+                "{}",
+                items
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            None => write!(f, "none"),
+        }
+    }
+}
+
+impl CssProperty for ContentProp {
+    // content =
+    //   normal | none | [ <string> | attr() | <counter> ]+
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let values = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+            .collect::<Vec<_>>();
+        if let [ComponentValue::PreservedToken(CssToken::Ident(ident))] = values.as_slice() {
+            if ident.eq_ignore_ascii_case("normal") || ident.eq_ignore_ascii_case("none") {
+                return Ok(Self { items: None });
+            }
+        }
+        ensure!(!values.is_empty(), "The content value is empty.");
+
+        let items = values
+            .into_iter()
+            .map(|v| match v {
+                ComponentValue::PreservedToken(CssToken::String(s)) => {
+                    Ok(ContentItem::String(s.clone()))
+                }
+                ComponentValue::Function { name, values } => {
+                    let args = values
+                        .iter()
+                        .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+                        .collect::<Vec<_>>();
+                    match (name.to_ascii_lowercase().as_str(), args.as_slice()) {
+                        ("attr", [ComponentValue::PreservedToken(CssToken::Ident(name))]) => {
+                            Ok(ContentItem::Attr(name.clone()))
+                        }
+                        ("counter", [ComponentValue::PreservedToken(CssToken::Ident(name))]) => {
+                            Ok(ContentItem::Counter(name.clone(), CounterStyle::Decimal))
+                        }
+                        (
+                            "counter",
+                            [ComponentValue::PreservedToken(CssToken::Ident(name)), ComponentValue::PreservedToken(CssToken::Comma), ComponentValue::PreservedToken(CssToken::Ident(style))],
+                        ) => Ok(ContentItem::Counter(
+                            name.clone(),
+                            CounterStyle::parse(style)?,
+                        )),
+                        _ => bail!("Unsupported content function: {:?}", v),
+                    }
+                }
+                _ => bail!("Invalid content value: {:?}", v),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { items: Some(items) })
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+/// The `counter-reset` property, which creates the counters with the initial values.
+/// https://drafts.csswg.org/css-lists/#counter-reset
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CounterResetProp {
+    pub counters: Vec<(String, i32)>,
+}
+
+impl fmt::Display for CounterResetProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format_counters(&self.counters))
+    }
+}
+
+impl CssProperty for CounterResetProp {
+    // counter-reset =
+    //   [ <counter-name> <integer>? ]+ | none
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        Ok(Self {
+            counters: parse_counters(values, 0)?,
+        })
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+/// The `counter-increment` property, which adds the values to the counters.
+/// https://drafts.csswg.org/css-lists/#increment-and-set
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CounterIncrementProp {
+    pub counters: Vec<(String, i32)>,
+}
+
+impl fmt::Display for CounterIncrementProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format_counters(&self.counters))
+    }
+}
+
+impl CssProperty for CounterIncrementProp {
+    // counter-increment =
+    //   [ <counter-name> <integer>? ]+ | none
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        Ok(Self {
+            counters: parse_counters(values, 1)?,
+        })
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+/// Parses the list of the counter names with the optional integers, which default to `default_value`.
+fn parse_counters(values: &[ComponentValue], default_value: i32) -> Result<Vec<(String, i32)>> {
+    let mut values = values
+        .iter()
+        .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+        .peekable();
+    let mut counters = Vec::new();
+    while let Some(v) = values.next() {
+        let ComponentValue::PreservedToken(CssToken::Ident(name)) = v else {
+            bail!("Expected a counter name but found {:?}", v);
+        };
+        if name.eq_ignore_ascii_case("none") {
+            ensure!(
+                counters.is_empty() && values.peek().is_none(),
+                "`none` must be the only value."
+            );
+            break;
+        }
+        let value = match values.peek() {
+            Some(ComponentValue::PreservedToken(CssToken::Number(NumericType::Integer(i)))) => {
+                values.next();
+                *i
+            }
+            _ => default_value,
+        };
+        counters.push((name.clone(), value));
+    }
+    Ok(counters)
+}
+
+fn format_counters(counters: &[(String, i32)]) -> String {
+    if counters.is_empty() {
+        return "none".to_string();
+    }
+    counters
+        .iter()
+        .map(|(name, value)| format!("{} {}", name, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::parser::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+
+    fn parse_values(css: &str) -> Vec<ComponentValue> {
+        CssParser::new(&CssTokenizer::new(css).tokenize().unwrap()).parse_list_of_component_values()
+    }
+
+    #[test]
+    fn parse_content_and_counters() {
+        let content =
+            ContentProp::parse(&parse_values("\"a\" attr(title) counter(x, lower-alpha)")).unwrap();
+        assert_eq!(
+            content.items.unwrap(),
+            [
+                ContentItem::String("a".to_string()),
+                ContentItem::Attr("title".to_string()),
+                ContentItem::Counter("x".to_string(), CounterStyle::LowerAlpha)
+            ]
+        );
+        assert_eq!(
+            ContentProp::parse(&parse_values("normal")).unwrap().items,
+            None
+        );
+        assert!(ContentProp::parse(&parse_values("open-quote")).is_err());
+        assert!(ContentProp::parse(&parse_values("counter(x, 1)")).is_err());
+
+        assert_eq!(
+            CounterResetProp::parse(&parse_values("a b -2"))
+                .unwrap()
+                .counters,
+            [("a".to_string(), 0), ("b".to_string(), -2)]
+        );
+        assert_eq!(
+            CounterIncrementProp::parse(&parse_values("a"))
+                .unwrap()
+                .counters,
+            [("a".to_string(), 1)]
+        );
+        assert!(CounterIncrementProp::parse(&parse_values("none a")).is_err());
+
+        let format = |style: CounterStyle| [1, 27, 1994, 0].map(|v| style.format(v));
+        assert_eq!(format(CounterStyle::LowerAlpha), ["a", "aa", "bxr", "0"]);
+        assert_eq!(
+            format(CounterStyle::UpperRoman),
+            ["I", "XXVII", "MCMXCIV", "0"]
+        );
+        assert_eq!(format(CounterStyle::Decimal), ["1", "27", "1994", "0"]);
+    }
+}
@@ -0,0 +1,79 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// The inline base direction of the texts, which is the paragraph embedding level of a block container and
+/// the direction of the embeddings and the overrides of an inline box.
+/// https://drafts.csswg.org/css-writing-modes/#direction
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirectionProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for DirectionProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for DirectionProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("ltr".to_string()),
+        }
+    }
+}
+
+impl CssProperty for DirectionProp {
+    // direction =
+    //   ltr | rtl
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (keywords.next(), keywords.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(value))), None) => {
+                let value = value.to_ascii_lowercase();
+                match value.as_str() {
+                    "ltr" | "rtl" => Ok(Self {
+                        value: CssValue::Ident(value),
+                    }),
+                    _ => bail!("Invalid direction value: {:?}", value),
+                }
+            }
+            _ => bail!("Invalid direction declaration: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl DirectionProp {
+    pub fn is_rtl(&self) -> bool {
+        matches!(&self.value, CssValue::Ident(v) if v == "rtl")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_direction() {
+        let parse = |keyword: &str| {
+            DirectionProp::parse(&[ComponentValue::PreservedToken(CssToken::Ident(
+                keyword.to_string(),
+            ))])
+        };
+        assert!(parse("RTL").unwrap().is_rtl());
+        assert!(!DirectionProp::default().is_rtl());
+        assert!(parse("auto").is_err());
+    }
+}
@@ -1,16 +1,16 @@
+use anyhow::{bail, ensure, Result};
 use std::fmt;
-use std::iter::Peekable;
-
-use anyhow::{bail, Result};
 
 use crate::renderer::css::cssom::ComponentValue;
 use crate::renderer::css::token::CssToken;
-use crate::renderer::style::property::{CssProperty, CssValue};
+use crate::renderer::style::property::{CssProperty, LengthContext};
 use crate::renderer::style::style_model::SpecifiedStyle;
 
 #[derive(Clone, Debug, Copy, PartialEq)]
 pub enum DisplayInside {
     Flow,
+    /// The box establishes a new block formatting context, e.g. `inline-block`.
+    FlowRoot,
     Table,
 }
 
@@ -26,17 +26,74 @@ pub enum DisplayBox {
     Contents,
 }
 
+/// The layout-specific display types of the internal boxes of tables, which are block-level in the box tree.
+/// https://drafts.csswg.org/css-display/#layout-specific-display
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisplayInternal {
+    TableRowGroup,
+    TableHeaderGroup,
+    TableFooterGroup,
+    TableRow,
+    TableCell,
+    TableColumnGroup,
+    TableColumn,
+    TableCaption,
+}
+
+/// The keywords of the internal display types, in the same order as `DisplayInternal`.
+const INTERNAL_KEYWORDS: [(&str, DisplayInternal); 8] = [
+    ("table-row-group", DisplayInternal::TableRowGroup),
+    ("table-header-group", DisplayInternal::TableHeaderGroup),
+    ("table-footer-group", DisplayInternal::TableFooterGroup),
+    ("table-row", DisplayInternal::TableRow),
+    ("table-cell", DisplayInternal::TableCell),
+    ("table-column-group", DisplayInternal::TableColumnGroup),
+    ("table-column", DisplayInternal::TableColumn),
+    ("table-caption", DisplayInternal::TableCaption),
+];
+
 /// https://drafts.csswg.org/css-display/#the-display-properties
 #[derive(Clone, Debug, PartialEq)]
 pub struct DisplayProp {
     pub inside: DisplayInside,
     pub outside: DisplayOutside,
     pub display_box: Option<DisplayBox>,
+    /// Whether the box generates a `::marker` box, i.e. `list-item`.
+    pub is_list_item: bool,
+    pub internal: Option<DisplayInternal>,
 }
 
 impl fmt::Display for DisplayProp {
+    // The shortest serialization of the value, e.g. `inline-block` rather than `inline flow-root`.
+    // https://drafts.csswg.org/css-display/#display-value-summary
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self.outside)
+        if let Some(display_box) = &self.display_box {
+            return match display_box {
+                DisplayBox::None => write!(f, "none"),
+                DisplayBox::Contents => write!(f, "contents"),
+            };
+        }
+        if let Some(internal) = self.internal {
+            let (keyword, _) = INTERNAL_KEYWORDS
+                .iter()
+                .find(|(_, v)| *v == internal)
+                .unwrap();
+            return write!(f, "{}", keyword);
+        }
+        let value = match (self.outside, self.inside, self.is_list_item) {
+            (DisplayOutside::Block, DisplayInside::Flow, false) => "block",
+            (DisplayOutside::Inline, DisplayInside::Flow, false) => "inline",
+            (DisplayOutside::Block, DisplayInside::FlowRoot, false) => "flow-root",
+            (DisplayOutside::Inline, DisplayInside::FlowRoot, false) => "inline-block",
+            (DisplayOutside::Block, DisplayInside::Table, _) => "table",
+            (DisplayOutside::Inline, DisplayInside::Table, _) => "inline-table",
+            (DisplayOutside::Block, DisplayInside::Flow, true) => "list-item",
+            (DisplayOutside::Inline, DisplayInside::Flow, true) => "inline list-item",
+            (DisplayOutside::Block, DisplayInside::FlowRoot, true) => "flow-root list-item",
+            (DisplayOutside::Inline, DisplayInside::FlowRoot, true) => "inline flow-root list-item",
+        };
+        write!(f, "{}", value)
     }
 }
 
@@ -46,153 +103,157 @@ impl Default for DisplayProp {
             inside: DisplayInside::Flow,
             outside: DisplayOutside::Inline,
             display_box: None,
+            is_list_item: false,
+            internal: None,
         }
     }
 }
 
 impl CssProperty for DisplayProp {
+    // display =
+    //   [ <display-outside> || <display-inside> ]  |
+    //   <display-listitem>                         |
+    //   <display-internal>                         |
+    //   <display-box>                              |
+    //   <display-legacy>
+    // todo: Support `flex`, `grid`, `ruby` and `math`.
     fn parse(values: &[ComponentValue]) -> Result<Self> {
-        let mut values = values.iter().cloned().peekable();
-        let mut ret = Self {
-            inside: DisplayInside::Flow,
-            outside: DisplayOutside::Inline,
-            display_box: None,
-        };
+        let keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+            .map(|v| match v {
+                ComponentValue::PreservedToken(CssToken::Ident(ident)) => {
+                    Ok(ident.to_ascii_lowercase())
+                }
+                _ => bail!("Invalid display value: {:?}", v),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut ret = Self::default();
 
-        if let Some(ComponentValue::PreservedToken(CssToken::Ident(ident))) = values.peek() {
-            match ident.as_str() {
-                "flow" | "table" | "block" | "inline" => {
-                    let mut is_inside_parsed = false;
-                    let mut is_outside_parsed = false;
-
-                    while values.peek().is_some() {
-                        while values
-                            .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-                            .is_some()
-                        {}
-                        if let Some(ComponentValue::PreservedToken(CssToken::Ident(ident))) =
-                            values.peek()
-                        {
-                            match ident.as_str() {
-                                "flow" | "table" => {
-                                    if is_inside_parsed {
-                                        bail!("Inside display value is already parsed");
-                                    }
-                                    match parse_display_inside_type(&mut values)? {
-                                        CssValue::Ident(v) => match v.as_str() {
-                                            "flow" => ret.inside = DisplayInside::Flow,
-                                            "table" => ret.inside = DisplayInside::Table,
-                                            _ => unimplemented!(),
-                                        },
-                                        _ => unreachable!(),
-                                    }
-                                    is_inside_parsed = true;
-                                }
-                                "block" | "inline" => {
-                                    if is_outside_parsed {
-                                        bail!("Outside display value is already parsed");
-                                    }
-                                    match parse_display_outside_type(&mut values)? {
-                                        CssValue::Ident(v) => match v.as_str() {
-                                            "block" => ret.outside = DisplayOutside::Block,
-                                            "inline" => ret.outside = DisplayOutside::Inline,
-                                            _ => unimplemented!(),
-                                        },
-                                        _ => unreachable!(),
-                                    }
-                                    is_outside_parsed = true;
-                                }
-                                _ => unimplemented!(),
-                            }
-                        }
-                    }
+        if let [keyword] = keywords.as_slice() {
+            match keyword.as_str() {
+                "none" => {
+                    ret.display_box = Some(DisplayBox::None);
+                    return Ok(ret);
+                }
+                "contents" => {
+                    ret.display_box = Some(DisplayBox::Contents);
+                    return Ok(ret);
                 }
-                "none" | "contents" => match parse_display_box_type(&mut values)? {
-                    CssValue::Ident(v) => match v.as_str() {
-                        "none" => ret.display_box = Some(DisplayBox::None),
-                        "contents" => ret.display_box = Some(DisplayBox::Contents),
-                        _ => bail!("Invalid display box value: {:?}", v),
-                    },
-                    _ => bail!("Invalid display box value"),
-                },
-                _ => unimplemented!(),
+                // https://drafts.csswg.org/css-display/#legacy-display
+                "inline-block" => {
+                    ret.inside = DisplayInside::FlowRoot;
+                    return Ok(ret);
+                }
+                "inline-table" => {
+                    ret.inside = DisplayInside::Table;
+                    return Ok(ret);
+                }
+                _ => {}
+            }
+            if let Some((_, internal)) = INTERNAL_KEYWORDS.iter().find(|(k, _)| k == keyword) {
+                // The internal table boxes are laid out like block boxes.
+                ret.outside = DisplayOutside::Block;
+                ret.internal = Some(*internal);
+                return Ok(ret);
             }
         }
 
+        let mut outside = None;
+        let mut inside = None;
+        for keyword in &keywords {
+            match keyword.as_str() {
+                "block" | "inline" if outside.is_none() => {
+                    outside = Some(if keyword == "block" {
+                        DisplayOutside::Block
+                    } else {
+                        DisplayOutside::Inline
+                    });
+                }
+                "flow" | "flow-root" | "table" if inside.is_none() => {
+                    inside = Some(match keyword.as_str() {
+                        "flow" => DisplayInside::Flow,
+                        "flow-root" => DisplayInside::FlowRoot,
+                        _ => DisplayInside::Table,
+                    });
+                }
+                "list-item" if !ret.is_list_item => ret.is_list_item = true,
+                _ => bail!("Invalid display value: {:?}", keywords),
+            }
+        }
+        ensure!(!keywords.is_empty(), "The display value is empty");
+        ensure!(
+            !ret.is_list_item || inside != Some(DisplayInside::Table),
+            "A list item must be a flow or flow-root box: {:?}",
+            keywords
+        );
+        ret.inside = inside.unwrap_or(DisplayInside::Flow);
+        // The outer display type defaults to `block` if only the inner one or `list-item` is given.
+        ret.outside = outside.unwrap_or(DisplayOutside::Block);
         Ok(ret)
     }
 
-    fn compute(&mut self, _: Option<&SpecifiedStyle>) -> Result<&Self> {
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
         Ok(self)
     }
 }
 
-fn parse_display_inside_type<I>(values: &mut Peekable<I>) -> Result<CssValue>
-where
-    I: Iterator<Item = ComponentValue>,
-{
-    while values
-        .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-        .is_some()
-    {}
-    match values.next() {
-        Some(v) => match v {
-            ComponentValue::PreservedToken(CssToken::Ident(v)) => {
-                match v.to_ascii_lowercase().as_str() {
-                    "flow" => Ok(CssValue::Ident("flow".to_string())),
-                    "table" => Ok(CssValue::Ident("table".to_string())),
-                    _ => unimplemented!(),
-                }
-            }
-            _ => bail!("Invalid inside display value: {:?}", v),
-        },
-        None => bail!("Expected inside display value but found none"),
+impl DisplayProp {
+    /// Returns whether the box contains block-level boxes or establishes an inline formatting context
+    /// for its contents, i.e. it's not an inline box.
+    /// https://drafts.csswg.org/css-display/#block-container
+    pub fn is_block_container(&self) -> bool {
+        self.outside == DisplayOutside::Block || self.inside != DisplayInside::Flow
     }
-}
 
-fn parse_display_outside_type<I>(values: &mut Peekable<I>) -> Result<CssValue>
-where
-    I: Iterator<Item = ComponentValue>,
-{
-    while values
-        .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-        .is_some()
-    {}
-    match values.next() {
-        Some(v) => match v {
-            ComponentValue::PreservedToken(CssToken::Ident(v)) => {
-                match v.to_ascii_lowercase().as_str() {
-                    "block" => Ok(CssValue::Ident("block".to_string())),
-                    "inline" => Ok(CssValue::Ident("inline".to_string())),
-                    _ => unimplemented!(),
-                }
-            }
-            _ => bail!("Invalid outside display value: {:?}", v),
-        },
-        None => bail!("Expected outside display value but found none"),
+    /// Returns whether the box is an inline-level box which is laid out as a single unit, e.g.
+    /// `inline-block`.
+    /// https://drafts.csswg.org/css-display/#atomic-inline
+    pub fn is_atomic_inline(&self) -> bool {
+        self.outside == DisplayOutside::Inline && self.inside != DisplayInside::Flow
     }
-}
 
-fn parse_display_box_type<I>(values: &mut Peekable<I>) -> Result<CssValue>
-where
-    I: Iterator<Item = ComponentValue>,
-{
-    while values
-        .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
-        .is_some()
-    {}
-    match values.next() {
-        Some(v) => match v {
-            ComponentValue::PreservedToken(CssToken::Ident(v)) => {
-                match v.to_ascii_lowercase().as_str() {
-                    "none" => Ok(CssValue::Ident("none".to_string())),
-                    "contents" => Ok(CssValue::Ident("contents".to_string())),
-                    _ => unimplemented!(),
-                }
-            }
-            _ => bail!("Invalid outside display value: {:?}", v),
-        },
-        None => bail!("Expected outside display value but found none"),
+    /// Makes the box block-level, as the absolutely positioned boxes are. The internal table boxes become
+    /// block boxes.
+    /// https://drafts.csswg.org/css-display/#blockify
+    pub fn blockify(&mut self) {
+        self.outside = DisplayOutside::Block;
+        self.internal = None;
+    }
+
+    /// Returns whether the box generates no box, i.e. `none`, or the columns of a table, which are not
+    /// rendered but whose properties would apply to their cells.
+    /// todo: Apply the backgrounds and the widths of the columns to the cells.
+    pub fn is_not_rendered(&self) -> bool {
+        self.display_box == Some(DisplayBox::None)
+            || matches!(
+                self.internal,
+                Some(DisplayInternal::TableColumn | DisplayInternal::TableColumnGroup)
+            )
+    }
+
+    pub fn is_table(&self) -> bool {
+        self.inside == DisplayInside::Table
+    }
+
+    /// Returns whether the box is a row group of a table, e.g. `table-header-group`.
+    pub fn is_table_row_group(&self) -> bool {
+        matches!(
+            self.internal,
+            Some(
+                DisplayInternal::TableRowGroup
+                    | DisplayInternal::TableHeaderGroup
+                    | DisplayInternal::TableFooterGroup
+            )
+        )
+    }
+
+    pub fn is_table_row(&self) -> bool {
+        self.internal == Some(DisplayInternal::TableRow)
+    }
+
+    pub fn is_table_cell(&self) -> bool {
+        self.internal == Some(DisplayInternal::TableCell)
     }
 }
 
@@ -237,6 +298,39 @@ mod tests {
         assert_eq!(display.display_box, Some(DisplayBox::Contents));
     }
 
+    #[test]
+    fn serialize_display() {
+        let serialize = |value: &str| {
+            let values = value
+                .split(' ')
+                .flat_map(|keyword| {
+                    [
+                        ComponentValue::PreservedToken(CssToken::Ident(keyword.to_string())),
+                        ComponentValue::PreservedToken(CssToken::Whitespace),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            DisplayProp::parse(&values).map(|display| display.to_string())
+        };
+        assert_eq!(serialize("inline flow-root").unwrap(), "inline-block");
+        assert_eq!(serialize("Inline-Block").unwrap(), "inline-block");
+        assert_eq!(serialize("flow list-item").unwrap(), "list-item");
+        assert_eq!(serialize("list-item inline").unwrap(), "inline list-item");
+        assert_eq!(serialize("table").unwrap(), "table");
+        assert_eq!(serialize("inline-table").unwrap(), "inline-table");
+        assert_eq!(serialize("table-cell").unwrap(), "table-cell");
+        assert_eq!(serialize("flow").unwrap(), "block");
+        assert!(serialize("table list-item").is_err());
+        assert!(serialize("table-cell block").is_err());
+        assert!(serialize("flex").is_err());
+
+        let display = DisplayProp::parse(&[ComponentValue::PreservedToken(CssToken::Ident(
+            "inline-block".to_string(),
+        ))])
+        .unwrap();
+        assert!(display.is_atomic_inline() && display.is_block_container());
+    }
+
     #[test]
     #[should_panic]
     fn parse_invalid_display() {
@@ -0,0 +1,64 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// https://www.w3.org/TR/CSS22/visuren.html#float-position
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloatProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for FloatProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for FloatProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("none".to_string()),
+        }
+    }
+}
+
+impl CssProperty for FloatProp {
+    // float =
+    //   left | right | none
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (keywords.next(), keywords.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(value))), None) => {
+                let value = value.to_ascii_lowercase();
+                match value.as_str() {
+                    "left" | "right" | "none" => Ok(Self {
+                        value: CssValue::Ident(value),
+                    }),
+                    _ => bail!("Invalid float value: {:?}", value),
+                }
+            }
+            _ => bail!("Invalid float declaration: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl FloatProp {
+    pub fn is_floating(&self) -> bool {
+        self.value != CssValue::Ident("none".to_string())
+    }
+
+    pub fn is_right(&self) -> bool {
+        self.value == CssValue::Ident("right".to_string())
+    }
+}
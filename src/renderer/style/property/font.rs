@@ -0,0 +1,149 @@
+use anyhow::{bail, ensure, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::font_stretch::get_keyword_stretch;
+use crate::renderer::style::property::{
+    CssProperty, FontFamilyProp, FontSizeProp, FontStretchProp, FontStyleProp, FontWeightProp,
+    LineHeightProp,
+};
+
+/// The longhands of the `font` shorthand, where the ones omitted in it are set to their initial values.
+/// todo: Support `font-variant` and the system font keywords, e.g. `caption`.
+/// https://drafts.csswg.org/css-fonts/#font-prop
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FontShorthand {
+    pub style: FontStyleProp,
+    pub weight: FontWeightProp,
+    pub stretch: FontStretchProp,
+    pub size: FontSizeProp,
+    pub family: FontFamilyProp,
+    pub line_height: LineHeightProp,
+}
+
+impl FontShorthand {
+    // font =
+    //   [ <'font-style'> || <font-variant-css2> || <'font-weight'> || <font-width-css3> ]?
+    //   <'font-size'> [ / <'line-height'> ]? <'font-family'>#
+    pub fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut font = Self::default();
+        let mut values = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+            .peekable();
+        let (mut has_style, mut has_weight, mut has_stretch, mut has_variant) =
+            (false, false, false, false);
+
+        // The keywords before the size, each of which can appear at most once in any order. `normal` sets
+        // none of them, since it's the initial value of all of them.
+        for _ in 0..4 {
+            let Some(value) = values.peek() else {
+                break;
+            };
+            match value {
+                ComponentValue::PreservedToken(CssToken::Ident(ident)) => {
+                    let ident = ident.to_ascii_lowercase();
+                    match ident.as_str() {
+                        "normal" => {}
+                        "italic" | "oblique" if !has_style => {
+                            let mut style = vec![values.next().unwrap().clone()];
+                            if ident == "oblique" {
+                                if let Some(angle) = values.next_if(|v| {
+                                    matches!(
+                                        v,
+                                        ComponentValue::PreservedToken(CssToken::Dimension(..))
+                                    )
+                                }) {
+                                    style.push(angle.clone());
+                                }
+                            }
+                            font.style = FontStyleProp::parse(&style)?;
+                            has_style = true;
+                            continue;
+                        }
+                        "bold" | "bolder" | "lighter" if !has_weight => {
+                            font.weight = FontWeightProp::parse(std::slice::from_ref(*value))?;
+                            has_weight = true;
+                        }
+                        "small-caps" if !has_variant => has_variant = true,
+                        _ if !has_stretch && get_keyword_stretch(&ident).is_some() => {
+                            font.stretch = FontStretchProp::parse(std::slice::from_ref(*value))?;
+                            has_stretch = true;
+                        }
+                        _ => break,
+                    }
+                }
+                ComponentValue::PreservedToken(CssToken::Number(_)) if !has_weight => {
+                    font.weight = FontWeightProp::parse(std::slice::from_ref(*value))?;
+                    has_weight = true;
+                }
+                _ => break,
+            }
+            values.next();
+        }
+
+        let Some(size) = values.next() else {
+            bail!("The font size is missing in the font shorthand");
+        };
+        font.size = FontSizeProp::parse(std::slice::from_ref(size))?;
+        if values
+            .next_if_eq(&&ComponentValue::PreservedToken(CssToken::Delim('/')))
+            .is_some()
+        {
+            let Some(line_height) = values.next() else {
+                bail!("The line height is missing after '/' in the font shorthand");
+            };
+            font.line_height = LineHeightProp::parse(std::slice::from_ref(line_height))?;
+        }
+
+        let family = values.cloned().collect::<Vec<_>>();
+        ensure!(
+            !family.is_empty(),
+            "The font family is missing in the font shorthand"
+        );
+        font.family = FontFamilyProp::parse(&family)?;
+        Ok(font)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::parser::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+    use crate::renderer::style::property::{CssValue, FontStyle};
+
+    fn parse(css: &str) -> Result<FontShorthand> {
+        FontShorthand::parse(
+            &CssParser::new(&CssTokenizer::new(css).tokenize().unwrap())
+                .parse_list_of_component_values(),
+        )
+    }
+
+    #[test]
+    fn parse_font_shorthand() {
+        let font = parse("italic condensed 600 12px/1.5 \"DejaVu Sans\", serif").unwrap();
+        assert_eq!(font.style.style, FontStyle::Italic);
+        assert_eq!(font.weight.weight, CssValue::Number(600.0));
+        assert_eq!(font.stretch.stretch, 75.0);
+        assert_eq!(font.size.to_px().unwrap(), 12.0);
+        assert_eq!(font.family.to_string(), "DejaVu Sans, serif");
+        assert_eq!(font.line_height.value, CssValue::Number(1.5));
+
+        // The omitted longhands are reset to their initial values.
+        let font = parse("normal bold large monospace").unwrap();
+        assert_eq!(font.style, FontStyleProp::default());
+        assert_eq!(font.weight.to_number().unwrap(), 700.0);
+        assert_eq!(font.stretch, FontStretchProp::default());
+        assert_eq!(font.line_height, LineHeightProp::default());
+        assert_eq!(
+            parse("oblique 20deg 1em serif").unwrap().style.style,
+            FontStyle::Oblique(20.0)
+        );
+
+        assert!(parse("bold serif").is_err());
+        assert!(parse("12px").is_err());
+        assert!(parse("bold bold 12px serif").is_err());
+        assert!(parse("italic 12px /").is_err());
+    }
+}
@@ -4,8 +4,9 @@ use anyhow::{bail, Ok, Result};
 
 use crate::renderer::css::cssom::ComponentValue;
 use crate::renderer::css::token::CssToken;
-use crate::renderer::style::property::{CssProperty, CssValue};
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
 use crate::renderer::style::style_model::SpecifiedStyle;
+use crate::renderer::text_rendering::get_text_rendering_options;
 
 /// https://developer.mozilla.org/en-US/docs/Web/CSS/font-family
 #[derive(Clone, Debug, PartialEq)]
@@ -71,7 +72,17 @@ impl CssProperty for FontFamilyProp {
         Ok(Self { family })
     }
 
-    fn compute(&mut self, _: Option<&SpecifiedStyle>) -> Result<&Self> {
+    /// The generic families are resolved to the families chosen by the user, if any.
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        let options = get_text_rendering_options();
+        for family in &mut self.family {
+            if let CssValue::Ident(name) | CssValue::String(name) = family {
+                let resolved = options.resolve_family(name).to_string();
+                if resolved != *name {
+                    *family = CssValue::String(resolved);
+                }
+            }
+        }
         Ok(self)
     }
 }
@@ -7,9 +7,10 @@ use crate::renderer::css::cssom::ComponentValue;
 use crate::renderer::css::token::CssToken;
 use crate::renderer::style::property::{
     parse_length_percentage_type, AbsoluteLengthUnit, AbsoluteSize, CssProperty, CssValue,
-    LengthUnit, RelativeLengthUnit, RelativeSize,
+    LengthContext, LengthUnit, RelativeSize,
 };
 use crate::renderer::style::style_model::SpecifiedStyle;
+use crate::renderer::text_rendering::get_text_rendering_options;
 
 pub const SMALL: f32 = 13.0;
 pub const MEDIUM: f32 = 16.0;
@@ -65,13 +66,9 @@ impl CssProperty for FontSizeProp {
         }
     }
 
-    fn compute(&mut self, parent_style: Option<&SpecifiedStyle>) -> Result<&Self> {
-        let parent_px = match parent_style.and_then(|s| s.font_size.as_ref()) {
-            Some(FontSizeProp {
-                size: CssValue::Length(size, LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px)),
-            }) => *size,
-            _ => MEDIUM,
-        };
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, context: &LengthContext) -> Result<&Self> {
+        // The relative lengths are relative to the font size of the parent.
+        let parent_px = context.font_size;
 
         match &self.size {
             CssValue::AbsoluteSize(size) => match size {
@@ -96,30 +93,26 @@ impl CssProperty for FontSizeProp {
                 _ => unimplemented!(),
             },
             CssValue::RelativeSize(_) => unimplemented!(),
-            CssValue::Length(size, unit) => match unit {
-                LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px) => {
-                    self.size = CssValue::Length(
-                        *size,
-                        LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
-                    );
-                }
-                LengthUnit::RelativeLengthUnit(RelativeLengthUnit::Em) => {
-                    self.size = CssValue::Length(
-                        size * parent_px,
-                        LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
-                    );
-                }
-                _ => unimplemented!(),
-            },
+            CssValue::Length(..) => self.size = self.size.compute_length(context)?,
             CssValue::Percentage(size) => {
                 self.size = CssValue::Length(
                     size / 100.0 * parent_px,
                     LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
                 );
             }
+            CssValue::Calc(node) => {
+                self.size = CssValue::Length(
+                    node.compute(context)?.resolve(parent_px)?,
+                    LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
+                );
+            }
             _ => bail!("Invalid font-size value: {:?}", self.size),
         }
 
+        let minimum_size = get_text_rendering_options().minimum_font_size;
+        if let CssValue::Length(size, _) = &mut self.size {
+            *size = size.max(minimum_size);
+        }
         Ok(self)
     }
 }
@@ -175,6 +168,7 @@ where
 mod tests {
     use super::*;
     use crate::renderer::css::token::NumericType;
+    use crate::renderer::style::property::RelativeLengthUnit;
 
     #[test]
     fn parse_size() {
@@ -0,0 +1,98 @@
+use std::fmt;
+
+use anyhow::{anyhow, bail, ensure, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// The keywords of `font-stretch` with their percentages of the normal width, and the stretch options of the
+/// font description of pango.
+/// https://drafts.csswg.org/css-fonts/#font-stretch-prop
+/// https://docs.gtk.org/Pango/type_func.FontDescription.from_string.html
+const KEYWORDS: [(&str, f32, &str); 9] = [
+    ("ultra-condensed", 50.0, "Ultra-Condensed"),
+    ("extra-condensed", 62.5, "Extra-Condensed"),
+    ("condensed", 75.0, "Condensed"),
+    ("semi-condensed", 87.5, "Semi-Condensed"),
+    ("normal", 100.0, ""),
+    ("semi-expanded", 112.5, "Semi-Expanded"),
+    ("expanded", 125.0, "Expanded"),
+    ("extra-expanded", 150.0, "Extra-Expanded"),
+    ("ultra-expanded", 200.0, "Ultra-Expanded"),
+];
+
+/// The width of the glyphs as a percentage of the normal width.
+/// https://drafts.csswg.org/css-fonts/#font-stretch-prop
+#[derive(Clone, Debug, PartialEq)]
+pub struct FontStretchProp {
+    pub stretch: f32,
+}
+
+impl fmt::Display for FontStretchProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match KEYWORDS
+            .iter()
+            .find(|(_, stretch, _)| *stretch == self.stretch)
+        {
+            Some((keyword, _, _)) => write!(f, "{}", keyword),
+            None => write!(f, "{}%", self.stretch),
+        }
+    }
+}
+
+impl Default for FontStretchProp {
+    fn default() -> Self {
+        Self { stretch: 100.0 }
+    }
+}
+
+impl CssProperty for FontStretchProp {
+    // font-stretch =
+    //   normal | <percentage [0,∞]> | ultra-condensed | extra-condensed | condensed | semi-condensed |
+    //   semi-expanded | expanded | extra-expanded | ultra-expanded
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let values = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+            .collect::<Vec<_>>();
+        match values.as_slice() {
+            [ComponentValue::PreservedToken(CssToken::Ident(keyword))] => Ok(Self {
+                stretch: get_keyword_stretch(keyword)
+                    .ok_or_else(|| anyhow!("Invalid font-stretch value: {}", keyword))?,
+            }),
+            [ComponentValue::PreservedToken(CssToken::Percentage(stretch))] => {
+                ensure!(
+                    *stretch >= 0.0,
+                    "The font-stretch percentage must not be negative: {}",
+                    stretch
+                );
+                Ok(Self { stretch: *stretch })
+            }
+            _ => bail!("Invalid font-stretch value: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+/// Returns the percentage of the keyword of `font-stretch`, if it is one.
+pub fn get_keyword_stretch(keyword: &str) -> Option<f32> {
+    KEYWORDS
+        .iter()
+        .find(|(name, _, _)| name.eq_ignore_ascii_case(keyword))
+        .map(|(_, stretch, _)| *stretch)
+}
+
+/// Returns the stretch option of the font description of pango closest to the percentage, which is empty
+/// for the normal width.
+pub fn get_stretch_name(stretch: f32) -> &'static str {
+    KEYWORDS
+        .iter()
+        .min_by(|a, b| (a.1 - stretch).abs().total_cmp(&(b.1 - stretch).abs()))
+        .map(|(_, _, name)| *name)
+        .unwrap()
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+
+use anyhow::{bail, ensure, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::{CssToken, NumericType};
+use crate::renderer::style::property::{CssProperty, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// The default angle of `oblique`.
+pub const OBLIQUE_ANGLE: f32 = 14.0;
+
+/// The slant of the glyphs. The angle of `oblique` is kept, but any oblique face matches it for now.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+    Oblique(f32),
+}
+
+impl fmt::Display for FontStyle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Normal => write!(f, "normal"),
+            Self::Italic => write!(f, "italic"),
+            Self::Oblique(angle) if *angle == OBLIQUE_ANGLE => write!(f, "oblique"),
+            Self::Oblique(angle) => write!(f, "oblique {}deg", angle),
+        }
+    }
+}
+
+impl FontStyle {
+    /// Returns the style option of the font description of pango, which is empty for `normal`.
+    /// https://docs.gtk.org/Pango/type_func.FontDescription.from_string.html
+    pub fn to_name(self) -> &'static str {
+        match self {
+            Self::Normal => "",
+            Self::Italic => "Italic",
+            Self::Oblique(_) => "Oblique",
+        }
+    }
+}
+
+/// https://drafts.csswg.org/css-fonts/#font-style-prop
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FontStyleProp {
+    pub style: FontStyle,
+}
+
+impl fmt::Display for FontStyleProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.style)
+    }
+}
+
+impl CssProperty for FontStyleProp {
+    // font-style =
+    //   normal                        |
+    //   italic                        |
+    //   oblique <angle [-90deg,90deg]>?
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let values = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+            .collect::<Vec<_>>();
+        let style = match values.as_slice() {
+            [ComponentValue::PreservedToken(CssToken::Ident(style))] => {
+                match style.to_ascii_lowercase().as_str() {
+                    "normal" => FontStyle::Normal,
+                    "italic" => FontStyle::Italic,
+                    "oblique" => FontStyle::Oblique(OBLIQUE_ANGLE),
+                    _ => bail!("Invalid font-style value: {:?}", style),
+                }
+            }
+            [ComponentValue::PreservedToken(CssToken::Ident(style)), ComponentValue::PreservedToken(CssToken::Dimension(angle, unit))]
+                if style.eq_ignore_ascii_case("oblique") =>
+            {
+                FontStyle::Oblique(parse_angle(angle, unit)?)
+            }
+            _ => bail!("Invalid font-style value: {:?}", values),
+        };
+        Ok(Self { style })
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+/// Parses the angle of `oblique` in degrees.
+/// https://drafts.csswg.org/css-values/#angles
+fn parse_angle(value: &NumericType, unit: &str) -> Result<f32> {
+    let value = match value {
+        NumericType::Integer(i) => *i as f32,
+        NumericType::Number(n) => *n,
+    };
+    let degrees = match unit.to_ascii_lowercase().as_str() {
+        "deg" => value,
+        "grad" => value * 0.9,
+        "rad" => value.to_degrees(),
+        "turn" => value * 360.0,
+        _ => bail!("Invalid angle unit: {}", unit),
+    };
+    ensure!(
+        (-90.0..=90.0).contains(&degrees),
+        "The oblique angle must be between -90deg and 90deg: {}",
+        degrees
+    );
+    Ok(degrees)
+}
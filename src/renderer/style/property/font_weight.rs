@@ -5,7 +5,7 @@ use anyhow::{bail, Ok, Result};
 
 use crate::renderer::css::cssom::ComponentValue;
 use crate::renderer::css::token::{CssToken, NumericType};
-use crate::renderer::style::property::{CssProperty, CssValue};
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
 use crate::renderer::style::style_model::SpecifiedStyle;
 
 /// https://developer.mozilla.org/en-US/docs/Web/CSS/font-weight
@@ -52,7 +52,11 @@ impl CssProperty for FontWeightProp {
         }
     }
 
-    fn compute(&mut self, parent_style: Option<&SpecifiedStyle>) -> Result<&Self> {
+    fn compute(
+        &mut self,
+        parent_style: Option<&SpecifiedStyle>,
+        _: &LengthContext,
+    ) -> Result<&Self> {
         let parent_weight = parent_style.and_then(|s| s.font_weight.as_ref());
         let parent_weight = match parent_weight {
             Some(FontWeightProp {
@@ -93,33 +97,42 @@ impl CssProperty for FontWeightProp {
 
 impl FontWeightProp {
     pub fn to_name(&self) -> Result<String> {
-        // https://developer.mozilla.org/en-US/docs/Web/CSS/font-weight#common_weight_name_mapping
-        // https://docs.gtk.org/Pango/type_func.FontDescription.from_string.html
-        let weight = match &self.weight {
+        Ok(get_weight_name(self.to_number()?)?.to_string())
+    }
+
+    /// Returns the numeric weight, where `normal` is 400 and `bold` is 700.
+    pub fn to_number(&self) -> Result<f32> {
+        match &self.weight {
             CssValue::Ident(weight) => match weight.as_str() {
-                "normal" => "Regular",
-                "bold" => "Bold",
-                _ => bail!("Invalid font weight: {:?}", weight),
-            },
-            CssValue::Number(weight) => match weight {
-                0.0..150.0 => "Thin",
-                150.0..250.0 => "Extra Light",
-                250.0..350.0 => "Light",
-                350.0..450.0 => "Regular",
-                450.0..550.0 => "Medium",
-                550.0..650.0 => "Semi-Bold",
-                650.0..750.0 => "Bold",
-                750.0..850.0 => "Extra-Bold",
-                850.0..950.0 => "Black",
-                950.0.. => "Extra-Black",
+                "normal" => Ok(400.0),
+                "bold" => Ok(700.0),
                 _ => bail!("Invalid font weight: {:?}", weight),
             },
+            CssValue::Number(weight) => Ok(*weight),
             _ => bail!("Invalid font weight: {:?}", self.weight),
-        };
-        Ok(weight.to_string())
+        }
     }
 }
 
+/// Returns the weight option of the font description of pango for the numeric weight.
+pub fn get_weight_name(weight: f32) -> Result<&'static str> {
+    // https://developer.mozilla.org/en-US/docs/Web/CSS/font-weight#common_weight_name_mapping
+    // https://docs.gtk.org/Pango/type_func.FontDescription.from_string.html
+    Ok(match weight {
+        0.0..150.0 => "Thin",
+        150.0..250.0 => "Extra Light",
+        250.0..350.0 => "Light",
+        350.0..450.0 => "Regular",
+        450.0..550.0 => "Medium",
+        550.0..650.0 => "Semi-Bold",
+        650.0..750.0 => "Bold",
+        750.0..850.0 => "Extra-Bold",
+        850.0..950.0 => "Black",
+        950.0.. => "Extra-Black",
+        _ => bail!("Invalid font weight: {:?}", weight),
+    })
+}
+
 // <font-weight-absolute> =
 //   normal             |
 //   bold               |
@@ -139,6 +152,11 @@ where
             {
                 Ok(CssValue::Number(*n))
             }
+            ComponentValue::PreservedToken(CssToken::Number(NumericType::Integer(i)))
+                if (1..=1000).contains(i) =>
+            {
+                Ok(CssValue::Number(*i as f32))
+            }
             _ => bail!("Expected number between 1 and 1000 but found: {:?}", v),
         },
         None => bail!("Expected <font-weight-absolute> but found none"),
@@ -0,0 +1,68 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// https://drafts.csswg.org/css-color-adjust/#forced-color-adjust-prop
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForcedColorAdjustProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for ForcedColorAdjustProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for ForcedColorAdjustProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("auto".to_string()),
+        }
+    }
+}
+
+impl CssProperty for ForcedColorAdjustProp {
+    // forced-color-adjust =
+    //   auto                   |
+    //   none                   |
+    //   preserve-parent-color
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut values = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (values.next(), values.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(value))), None) => {
+                let value = value.to_ascii_lowercase();
+                match value.as_str() {
+                    "auto" | "none" | "preserve-parent-color" => Ok(Self {
+                        value: CssValue::Ident(value),
+                    }),
+                    _ => bail!("Invalid forced-color-adjust value: {}", value),
+                }
+            }
+            _ => bail!("Invalid forced-color-adjust value: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl ForcedColorAdjustProp {
+    /// Returns whether the colors of the element are replaced in the forced colors mode.
+    pub fn is_auto(&self) -> bool {
+        self.value == CssValue::Ident("auto".to_string())
+    }
+
+    /// Returns whether the element keeps the color inherited from its parent in the forced colors mode.
+    pub fn is_preserve_parent_color(&self) -> bool {
+        self.value == CssValue::Ident("preserve-parent-color".to_string())
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::{CssToken, NumericType};
+use crate::renderer::style::property::{
+    parse_length_percentage_type, AbsoluteLengthUnit, CssProperty, CssValue, LengthContext,
+    LengthUnit,
+};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// The height of the line boxes which the inline boxes take up, where the half of the difference from the
+/// height of the font is added above and below the glyphs as the half-leading.
+/// https://www.w3.org/TR/CSS22/visudet.html#propdef-line-height
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineHeightProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for LineHeightProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for LineHeightProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("normal".to_string()),
+        }
+    }
+}
+
+impl CssProperty for LineHeightProp {
+    // line-height =
+    //   normal                     |
+    //   <number [0,∞]>             |
+    //   <length-percentage [0,∞]>
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut tokens = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+            .cloned()
+            .peekable();
+        let value = match tokens.peek() {
+            Some(ComponentValue::PreservedToken(CssToken::Ident(value)))
+                if value.eq_ignore_ascii_case("normal") =>
+            {
+                tokens.next();
+                CssValue::Ident("normal".to_string())
+            }
+            Some(ComponentValue::PreservedToken(CssToken::Number(number))) => {
+                let number = match number {
+                    NumericType::Integer(n) => *n as f32,
+                    NumericType::Number(n) => *n,
+                };
+                tokens.next();
+                CssValue::Number(number)
+            }
+            _ => parse_length_percentage_type(&mut tokens)?,
+        };
+        if tokens.next().is_some() {
+            bail!("Invalid line-height declaration: {:?}", values);
+        }
+        match value {
+            CssValue::Number(v) | CssValue::Length(v, _) | CssValue::Percentage(v) if v < 0.0 => {
+                bail!("Negative line-height: {:?}", value)
+            }
+            _ => Ok(Self { value }),
+        }
+    }
+
+    // The lengths and the percentages compute to absolute lengths, while the numbers are inherited as they
+    // are, so that the line height of the descendants follows their own font sizes.
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, context: &LengthContext) -> Result<&Self> {
+        match self.value {
+            CssValue::Length(..) | CssValue::Calc(_) => {
+                self.value = self.value.compute_length(context)?;
+                if let CssValue::Calc(_) = self.value {
+                    self.value = CssValue::Length(
+                        self.value.resolve_px(context.font_size)?,
+                        LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
+                    );
+                }
+            }
+            CssValue::Percentage(percentage) => {
+                self.value = CssValue::Length(
+                    percentage / 100.0 * context.font_size,
+                    LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
+                );
+            }
+            _ => {}
+        }
+        Ok(self)
+    }
+}
+
+impl LineHeightProp {
+    /// Returns the used line height in pixels for the font size, or `None` for `normal`, which is the
+    /// height of the font.
+    pub fn to_px(&self, font_size: f32) -> Option<f32> {
+        match self.value {
+            CssValue::Number(number) => Some(number * font_size),
+            CssValue::Length(..) => self.value.to_px().ok(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::parser::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+
+    fn parse(css: &str) -> Result<LineHeightProp> {
+        LineHeightProp::parse(
+            &CssParser::new(&CssTokenizer::new(css).tokenize().unwrap())
+                .parse_list_of_component_values(),
+        )
+    }
+
+    #[test]
+    fn compute_line_height() {
+        let context = LengthContext {
+            font_size: 20.0,
+            ..Default::default()
+        };
+        let compute = |css: &str| {
+            let mut line_height = parse(css).unwrap();
+            line_height.compute(None, &context).unwrap();
+            line_height
+        };
+
+        assert_eq!(compute("normal").to_px(20.0), None);
+        // The numbers are multiplied by the font sizes of the elements which inherit them.
+        assert_eq!(compute("1.5").value, CssValue::Number(1.5));
+        assert_eq!(compute("1.5").to_px(10.0), Some(15.0));
+        assert_eq!(compute("150%").to_px(10.0), Some(30.0));
+        assert_eq!(compute("2em").to_px(10.0), Some(40.0));
+        assert_eq!(compute("24px").to_px(10.0), Some(24.0));
+
+        assert!(parse("-1").is_err());
+        assert!(parse("1.5 2").is_err());
+        assert!(parse("tall").is_err());
+    }
+}
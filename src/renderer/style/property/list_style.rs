@@ -0,0 +1,120 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::content::CounterStyle;
+use crate::renderer::style::property::{CssProperty, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// The marker of a list item, which is a symbol, the value of the `list-item` counter in a counter style,
+/// or a string.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListStyleType {
+    Disc,
+    Circle,
+    Square,
+    /// `none` is `CounterStyle::None`.
+    Counter(CounterStyle),
+    String(String),
+}
+
+/// https://drafts.csswg.org/css-lists/#text-markers
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListStyleTypeProp {
+    pub value: ListStyleType,
+}
+
+impl fmt::Display for ListStyleTypeProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.value {
+            ListStyleType::Disc => write!(f, "disc"),
+            ListStyleType::Circle => write!(f, "circle"),
+            ListStyleType::Square => write!(f, "square"),
+            ListStyleType::Counter(style) => write!(f, "{}", style),
+            ListStyleType::String(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+impl Default for ListStyleTypeProp {
+    fn default() -> Self {
+        Self {
+            value: ListStyleType::Disc,
+        }
+    }
+}
+
+impl CssProperty for ListStyleTypeProp {
+    // list-style-type =
+    //   <counter-style> | <string> | none
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let values = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+            .collect::<Vec<_>>();
+        let value = match values.as_slice() {
+            [ComponentValue::PreservedToken(CssToken::Ident(name))] => {
+                match name.to_ascii_lowercase().as_str() {
+                    "disc" => ListStyleType::Disc,
+                    "circle" => ListStyleType::Circle,
+                    "square" => ListStyleType::Square,
+                    _ => ListStyleType::Counter(CounterStyle::parse(name)?),
+                }
+            }
+            [ComponentValue::PreservedToken(CssToken::String(s))] => {
+                ListStyleType::String(s.clone())
+            }
+            _ => bail!("Invalid list-style-type value: {:?}", values),
+        };
+        Ok(Self { value })
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl ListStyleTypeProp {
+    /// Returns the text of the marker for the value of the `list-item` counter, or `None` for `none`. The
+    /// counter styles are followed by their suffix `. ` and the symbols by a space.
+    /// https://drafts.csswg.org/css-counter-styles/#simple-symbolic
+    pub fn get_marker_text(&self, value: i32) -> Option<String> {
+        match &self.value {
+            ListStyleType::Disc => Some("• ".to_string()),
+            ListStyleType::Circle => Some("◦ ".to_string()),
+            ListStyleType::Square => Some("▪ ".to_string()),
+            ListStyleType::Counter(CounterStyle::None) => None,
+            ListStyleType::Counter(style) => Some(format!("{}. ", style.format(value))),
+            ListStyleType::String(s) => Some(s.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::parser::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+
+    fn parse_values(css: &str) -> Vec<ComponentValue> {
+        CssParser::new(&CssTokenizer::new(css).tokenize().unwrap()).parse_list_of_component_values()
+    }
+
+    #[test]
+    fn get_marker_texts() {
+        let get_marker_text = |css: &str, value: i32| {
+            ListStyleTypeProp::parse(&parse_values(css))
+                .unwrap()
+                .get_marker_text(value)
+        };
+        assert_eq!(get_marker_text("disc", 1), Some("• ".to_string()));
+        assert_eq!(get_marker_text("Square", 1), Some("▪ ".to_string()));
+        assert_eq!(get_marker_text("decimal", 3), Some("3. ".to_string()));
+        assert_eq!(get_marker_text("upper-roman", 4), Some("IV. ".to_string()));
+        assert_eq!(get_marker_text("\"-\"", 2), Some("-".to_string()));
+        assert_eq!(get_marker_text("none", 1), None);
+        assert!(ListStyleTypeProp::parse(&parse_values("disc circle")).is_err());
+    }
+}
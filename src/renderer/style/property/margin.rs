@@ -5,10 +5,9 @@ use anyhow::{bail, Ok, Result};
 
 use crate::renderer::css::cssom::ComponentValue;
 use crate::renderer::css::token::CssToken;
-use crate::renderer::style::property::font_size::{self, FontSizeProp};
 use crate::renderer::style::property::{
-    parse_length_percentage_type, AbsoluteLengthUnit, CssProperty, CssValue, LengthUnit,
-    RelativeLengthUnit,
+    parse_length_percentage_type, AbsoluteLengthUnit, CssProperty, CssValue, LengthContext,
+    LengthUnit,
 };
 use crate::renderer::style::style_model::SpecifiedStyle;
 
@@ -96,25 +95,17 @@ impl CssProperty for MarginProp {
         }
     }
 
-    fn compute(&mut self, current_style: Option<&SpecifiedStyle>) -> Result<&Self> {
-        self.top = Self::compute_top(&self.top, current_style)?;
-        self.right = Self::compute_top(&self.right, current_style)?;
-        self.bottom = Self::compute_top(&self.bottom, current_style)?;
-        self.left = Self::compute_top(&self.left, current_style)?;
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, context: &LengthContext) -> Result<&Self> {
+        self.top = Self::compute_top(&self.top, context)?;
+        self.right = Self::compute_top(&self.right, context)?;
+        self.bottom = Self::compute_top(&self.bottom, context)?;
+        self.left = Self::compute_top(&self.left, context)?;
         Ok(self)
     }
 }
 
 impl MarginProp {
-    fn compute_top(value: &CssValue, current_style: Option<&SpecifiedStyle>) -> Result<CssValue> {
-        let current_font_size = current_style.and_then(|s| s.font_size.as_ref());
-        let current_font_size = match current_font_size {
-            Some(FontSizeProp {
-                size: CssValue::Length(size, LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px)),
-            }) => size,
-            None => &font_size::MEDIUM,
-            _ => bail!("Invalid font-size value: {:?}", current_font_size),
-        };
+    fn compute_top(value: &CssValue, context: &LengthContext) -> Result<CssValue> {
         match &value {
             CssValue::Ident(v) => {
                 if v != "auto" {
@@ -122,15 +113,8 @@ impl MarginProp {
                 }
                 Ok(value.clone())
             }
-            CssValue::Length(size, unit) => match unit {
-                LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px) => Ok(value.clone()),
-                LengthUnit::RelativeLengthUnit(RelativeLengthUnit::Em) => Ok(CssValue::Length(
-                    size * current_font_size,
-                    LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
-                )),
-                _ => unimplemented!(),
-            },
-            CssValue::Percentage(_) => unimplemented!(),
+            CssValue::Length(..) | CssValue::Calc(_) => value.compute_length(context),
+            CssValue::Percentage(_) => Ok(value.clone()),
             _ => bail!("Invalid margin value: {:?}", &value),
         }
     }
@@ -189,33 +173,18 @@ impl CssProperty for MarginBlockProp {
         }
     }
 
-    fn compute(&mut self, current_style: Option<&SpecifiedStyle>) -> Result<&Self> {
-        self.start = Self::compute_top(&self.start, current_style)?;
-        self.end = Self::compute_top(&self.end, current_style)?;
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, context: &LengthContext) -> Result<&Self> {
+        self.start = Self::compute_top(&self.start, context)?;
+        self.end = Self::compute_top(&self.end, context)?;
         Ok(self)
     }
 }
 
 impl MarginBlockProp {
-    fn compute_top(value: &CssValue, current_style: Option<&SpecifiedStyle>) -> Result<CssValue> {
-        let current_font_size = current_style.and_then(|s| s.font_size.as_ref());
-        let current_font_size = match current_font_size {
-            Some(FontSizeProp {
-                size: CssValue::Length(size, LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px)),
-            }) => size,
-            None => &font_size::MEDIUM,
-            _ => bail!("Invalid font-size value: {:?}", current_font_size),
-        };
+    fn compute_top(value: &CssValue, context: &LengthContext) -> Result<CssValue> {
         match &value {
-            CssValue::Length(size, unit) => match unit {
-                LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px) => Ok(value.clone()),
-                LengthUnit::RelativeLengthUnit(RelativeLengthUnit::Em) => Ok(CssValue::Length(
-                    size * current_font_size,
-                    LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
-                )),
-                _ => unimplemented!(),
-            },
-            CssValue::Percentage(_) => unimplemented!(),
+            CssValue::Length(..) | CssValue::Calc(_) => value.compute_length(context),
+            CssValue::Percentage(_) => Ok(value.clone()),
             _ => bail!("Invalid margin value: {:?}", &value),
         }
     }
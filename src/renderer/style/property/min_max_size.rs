@@ -0,0 +1,181 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{
+    parse_length_percentage_type, CssProperty, CssValue, LengthContext,
+};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// The `min-width` and `min-height` properties, which are the lower limits of the sizes of the box.
+/// https://drafts.csswg.org/css-sizing/#min-size-properties
+#[derive(Clone, Debug, PartialEq)]
+pub struct MinSizeProp {
+    pub size: CssValue,
+}
+
+impl fmt::Display for MinSizeProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.size)
+    }
+}
+
+impl Default for MinSizeProp {
+    fn default() -> Self {
+        Self {
+            size: CssValue::Ident("auto".to_string()),
+        }
+    }
+}
+
+impl CssProperty for MinSizeProp {
+    // min-width =
+    //   auto                       |
+    //   <length-percentage [0,∞]>  |
+    //   min-content                |
+    //   max-content                |
+    //   fit-content( <length-percentage [0,∞]> )
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        // todo: implement the intrinsic sizes
+        Ok(Self {
+            size: parse_size_limit(values, "auto")?,
+        })
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, context: &LengthContext) -> Result<&Self> {
+        if let CssValue::Length(..) | CssValue::Calc(_) = self.size {
+            self.size = self.size.compute_length(context)?;
+        }
+        Ok(self)
+    }
+}
+
+impl MinSizeProp {
+    /// Returns the used minimum size in pixels, which is 0 for `auto`. The percentages are relative to
+    /// `percentage_basis`, and treated as 0 if it's indefinite.
+    /// https://drafts.csswg.org/css-sizing/#valdef-width-auto
+    pub fn to_px(&self, percentage_basis: Option<f32>) -> f32 {
+        resolve_size_limit(&self.size, percentage_basis).unwrap_or(0.0)
+    }
+}
+
+/// The `max-width` and `max-height` properties, which are the upper limits of the sizes of the box.
+/// https://drafts.csswg.org/css-sizing/#max-size-properties
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaxSizeProp {
+    pub size: CssValue,
+}
+
+impl fmt::Display for MaxSizeProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.size)
+    }
+}
+
+impl Default for MaxSizeProp {
+    fn default() -> Self {
+        Self {
+            size: CssValue::Ident("none".to_string()),
+        }
+    }
+}
+
+impl CssProperty for MaxSizeProp {
+    // max-width =
+    //   none                       |
+    //   <length-percentage [0,∞]>  |
+    //   min-content                |
+    //   max-content                |
+    //   fit-content( <length-percentage [0,∞]> )
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        // todo: implement the intrinsic sizes
+        Ok(Self {
+            size: parse_size_limit(values, "none")?,
+        })
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, context: &LengthContext) -> Result<&Self> {
+        if let CssValue::Length(..) | CssValue::Calc(_) = self.size {
+            self.size = self.size.compute_length(context)?;
+        }
+        Ok(self)
+    }
+}
+
+impl MaxSizeProp {
+    /// Returns the used maximum size in pixels, or `None` if the size is unlimited. The percentages are
+    /// relative to `percentage_basis`, and treated as `none` if it's indefinite.
+    pub fn to_px(&self, percentage_basis: Option<f32>) -> Option<f32> {
+        resolve_size_limit(&self.size, percentage_basis)
+    }
+}
+
+/// Parses the value of the min or max size property, where `keyword` is the one which doesn't limit the size.
+fn parse_size_limit(values: &[ComponentValue], keyword: &str) -> Result<CssValue> {
+    let mut tokens = values
+        .iter()
+        .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+        .cloned()
+        .peekable();
+    let size = match tokens.peek() {
+        Some(ComponentValue::PreservedToken(CssToken::Ident(value)))
+            if value.eq_ignore_ascii_case(keyword) =>
+        {
+            tokens.next();
+            CssValue::Ident(keyword.to_string())
+        }
+        _ => parse_length_percentage_type(&mut tokens)?,
+    };
+    if tokens.next().is_some() {
+        bail!("Invalid size declaration: {:?}", values);
+    }
+    match size {
+        CssValue::Length(value, _) | CssValue::Percentage(value) if value < 0.0 => {
+            bail!("Negative size: {:?}", size)
+        }
+        _ => Ok(size),
+    }
+}
+
+fn resolve_size_limit(size: &CssValue, percentage_basis: Option<f32>) -> Option<f32> {
+    match (size, percentage_basis) {
+        (CssValue::Ident(_), _) | (CssValue::Percentage(_) | CssValue::Calc(_), None) => None,
+        (_, Some(basis)) => size.resolve_px(basis).ok(),
+        (_, None) => size.to_px().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::token::NumericType;
+
+    #[test]
+    fn parse_size_limits() {
+        let px = |v: f32| {
+            ComponentValue::PreservedToken(CssToken::Dimension(
+                NumericType::Number(v),
+                "px".to_string(),
+            ))
+        };
+        let ident = |v: &str| ComponentValue::PreservedToken(CssToken::Ident(v.to_string()));
+
+        let min = MinSizeProp::parse(&[ident("AUTO")]).unwrap();
+        assert_eq!(min, MinSizeProp::default());
+        assert_eq!(min.to_px(Some(100.0)), 0.0);
+        let min = MinSizeProp::parse(&[ComponentValue::PreservedToken(CssToken::Percentage(50.0))])
+            .unwrap();
+        assert_eq!(min.to_px(Some(300.0)), 150.0);
+        assert_eq!(min.to_px(None), 0.0);
+        assert!(MinSizeProp::parse(&[ident("none")]).is_err());
+        assert!(MinSizeProp::parse(&[px(-1.0)]).is_err());
+
+        let max = MaxSizeProp::parse(&[px(20.0)]).unwrap();
+        assert_eq!(max.to_px(None), Some(20.0));
+        assert_eq!(MaxSizeProp::default().to_px(Some(100.0)), None);
+        assert!(MaxSizeProp::parse(&[ident("auto")]).is_err());
+        assert!(MaxSizeProp::parse(&[px(1.0), px(2.0)]).is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::{CssToken, NumericType};
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// https://drafts.csswg.org/css-color/#transparency
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpacityProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for OpacityProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for OpacityProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Number(1.0),
+        }
+    }
+}
+
+impl CssProperty for OpacityProp {
+    // opacity =
+    //   <opacity-value>
+    //
+    // <opacity-value> =
+    //   <number>      |
+    //   <percentage>
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        let value = match (keywords.next(), keywords.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Number(number))), None) => {
+                match number {
+                    NumericType::Integer(n) => *n as f32,
+                    NumericType::Number(n) => *n,
+                }
+            }
+            (Some(ComponentValue::PreservedToken(CssToken::Percentage(percentage))), None) => {
+                percentage / 100.0
+            }
+            _ => bail!("Invalid opacity declaration: {:?}", values),
+        };
+        Ok(Self {
+            value: CssValue::Number(value),
+        })
+    }
+
+    // The computed value is clamped to [0, 1].
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        if let CssValue::Number(value) = self.value {
+            self.value = CssValue::Number(value.clamp(0.0, 1.0));
+        }
+        Ok(self)
+    }
+}
+
+impl OpacityProp {
+    /// Returns the opacity in [0.0, 1.0].
+    pub fn get_alpha(&self) -> f64 {
+        match self.value {
+            CssValue::Number(value) => value.clamp(0.0, 1.0) as f64,
+            _ => 1.0,
+        }
+    }
+
+    /// Returns whether the element is painted translucently, which creates a stacking context.
+    pub fn is_translucent(&self) -> bool {
+        self.get_alpha() < 1.0
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// The `overflow-x` and `overflow-y` properties, which are set together by the `overflow` shorthand.
+/// https://drafts.csswg.org/css-overflow/#overflow-properties
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverflowProp {
+    pub x: CssValue,
+    pub y: CssValue,
+}
+
+impl fmt::Display for OverflowProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.x, self.y)
+    }
+}
+
+impl Default for OverflowProp {
+    fn default() -> Self {
+        Self {
+            x: CssValue::Ident("visible".to_string()),
+            y: CssValue::Ident("visible".to_string()),
+        }
+    }
+}
+
+impl CssProperty for OverflowProp {
+    // overflow =
+    //   [ visible | hidden | clip | scroll | auto ]{1,2}
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+            .map(|v| match v {
+                ComponentValue::PreservedToken(CssToken::Ident(value)) => {
+                    let value = value.to_ascii_lowercase();
+                    match value.as_str() {
+                        "visible" | "hidden" | "clip" | "scroll" | "auto" => {
+                            Ok(CssValue::Ident(value))
+                        }
+                        _ => bail!("Invalid overflow value: {:?}", value),
+                    }
+                }
+                _ => bail!("Invalid overflow declaration: {:?}", values),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        match keywords.as_slice() {
+            [value] => Ok(Self {
+                x: value.clone(),
+                y: value.clone(),
+            }),
+            [x, y] => Ok(Self {
+                x: x.clone(),
+                y: y.clone(),
+            }),
+            _ => bail!("Invalid overflow declaration: {:?}", values),
+        }
+    }
+
+    // If one of the axes scrolls, `visible` and `clip` of the other one compute to `auto` and `hidden`.
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        let is_scrollable = |value: &CssValue| !is(value, "visible") && !is(value, "clip");
+        if is_scrollable(&self.x) || is_scrollable(&self.y) {
+            for value in [&mut self.x, &mut self.y] {
+                if is(value, "visible") {
+                    *value = CssValue::Ident("auto".to_string());
+                } else if is(value, "clip") {
+                    *value = CssValue::Ident("hidden".to_string());
+                }
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl OverflowProp {
+    /// Returns whether the contents of the box are clipped to its padding box.
+    pub fn clips(&self) -> bool {
+        !is(&self.x, "visible") || !is(&self.y, "visible")
+    }
+
+    /// Returns whether the contents of the box are clipped in the inline direction.
+    pub fn clips_inline(&self) -> bool {
+        !is(&self.x, "visible")
+    }
+
+    /// Returns whether the box is a scroll container, whose contents can be scrolled, at least
+    /// programmatically. It establishes a block formatting context for its contents.
+    /// https://drafts.csswg.org/css-overflow/#scroll-container
+    pub fn is_scroll_container(&self) -> bool {
+        self.clips() && !is(&self.x, "clip") && !is(&self.y, "clip")
+    }
+
+    /// Returns whether the user can scroll the contents of the box on the axis.
+    pub fn is_user_scrollable(&self, is_horizontal: bool) -> bool {
+        let value = if is_horizontal { &self.x } else { &self.y };
+        is(value, "scroll") || is(value, "auto")
+    }
+
+    /// Returns whether the box shows the scrollbar on the axis, which is always shown for `scroll` and
+    /// only if the contents overflow the box for `auto`.
+    pub fn has_scrollbar(&self, is_horizontal: bool, overflows: bool) -> bool {
+        let value = if is_horizontal { &self.x } else { &self.y };
+        is(value, "scroll") || (is(value, "auto") && overflows)
+    }
+}
+
+fn is(value: &CssValue, keyword: &str) -> bool {
+    *value == CssValue::Ident(keyword.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compute_overflow() {
+        let ident = |v: &str| ComponentValue::PreservedToken(CssToken::Ident(v.to_string()));
+        let context = LengthContext::default();
+
+        let mut overflow = OverflowProp::parse(&[
+            ident("Visible"),
+            ComponentValue::PreservedToken(CssToken::Whitespace),
+            ident("scroll"),
+        ])
+        .unwrap();
+        overflow.compute(None, &context).unwrap();
+        assert_eq!(overflow.x, CssValue::Ident("auto".to_string()));
+        assert!(overflow.is_scroll_container());
+        assert!(!overflow.has_scrollbar(true, false));
+        assert!(overflow.has_scrollbar(false, false));
+
+        let mut overflow = OverflowProp::parse(&[ident("clip")]).unwrap();
+        overflow.compute(None, &context).unwrap();
+        assert!(overflow.clips());
+        assert!(!overflow.is_scroll_container());
+        assert!(!OverflowProp::default().clips());
+
+        assert!(OverflowProp::parse(&[ident("overlay")]).is_err());
+        assert!(OverflowProp::parse(&[ident("auto"), ident("auto"), ident("auto")]).is_err());
+    }
+}
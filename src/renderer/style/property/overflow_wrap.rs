@@ -0,0 +1,81 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// Whether the lines may break within an otherwise unbreakable word to prevent it from overflowing the
+/// line. `word-wrap` is the legacy name of the property.
+/// https://drafts.csswg.org/css-text/#overflow-wrap-property
+#[derive(Clone, Debug, PartialEq)]
+pub struct OverflowWrapProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for OverflowWrapProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for OverflowWrapProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("normal".to_string()),
+        }
+    }
+}
+
+impl CssProperty for OverflowWrapProp {
+    // overflow-wrap =
+    //   normal | break-word | anywhere
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (keywords.next(), keywords.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(value))), None) => {
+                let value = value.to_ascii_lowercase();
+                match value.as_str() {
+                    "normal" | "break-word" | "anywhere" => Ok(Self {
+                        value: CssValue::Ident(value),
+                    }),
+                    _ => bail!("Invalid overflow-wrap value: {:?}", value),
+                }
+            }
+            _ => bail!("Invalid overflow-wrap declaration: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl OverflowWrapProp {
+    /// Returns whether an overflowing word may break at an arbitrary point.
+    pub fn breaks_words(&self) -> bool {
+        matches!(&self.value, CssValue::Ident(v) if v != "normal")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_overflow_wrap() {
+        let parse = |keyword: &str| {
+            OverflowWrapProp::parse(&[ComponentValue::PreservedToken(CssToken::Ident(
+                keyword.to_string(),
+            ))])
+        };
+        assert!(parse("break-word").unwrap().breaks_words());
+        assert!(parse("anywhere").unwrap().breaks_words());
+        assert!(!OverflowWrapProp::default().breaks_words());
+        assert!(parse("break-all").is_err());
+    }
+}
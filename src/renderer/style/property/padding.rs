@@ -6,10 +6,9 @@ use anyhow::{bail, Ok, Result};
 use crate::renderer::css::cssom::ComponentValue;
 use crate::renderer::css::token::CssToken;
 use crate::renderer::layout::box_model::Edge;
-use crate::renderer::style::property::font_size::{self, FontSizeProp};
 use crate::renderer::style::property::{
-    parse_length_percentage_type, AbsoluteLengthUnit, CssProperty, CssValue, LengthUnit,
-    RelativeLengthUnit,
+    parse_length_percentage_type, AbsoluteLengthUnit, CssProperty, CssValue, LengthContext,
+    LengthUnit,
 };
 use crate::renderer::style::style_model::SpecifiedStyle;
 
@@ -97,35 +96,24 @@ impl CssProperty for PaddingProp {
         }
     }
 
-    fn compute(&mut self, current_style: Option<&SpecifiedStyle>) -> Result<&Self> {
-        self.top = Self::compute_top(&self.top, current_style)?;
-        self.right = Self::compute_top(&self.right, current_style)?;
-        self.bottom = Self::compute_top(&self.bottom, current_style)?;
-        self.left = Self::compute_top(&self.left, current_style)?;
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, context: &LengthContext) -> Result<&Self> {
+        self.top = Self::compute_top(&self.top, context)?;
+        self.right = Self::compute_top(&self.right, context)?;
+        self.bottom = Self::compute_top(&self.bottom, context)?;
+        self.left = Self::compute_top(&self.left, context)?;
         Ok(self)
     }
 }
 
 impl PaddingProp {
-    fn compute_top(value: &CssValue, current_style: Option<&SpecifiedStyle>) -> Result<CssValue> {
-        let current_font_size = current_style.and_then(|s| s.font_size.as_ref());
-        let current_font_size = match current_font_size {
-            Some(FontSizeProp {
-                size: CssValue::Length(size, LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px)),
-            }) => size,
-            None => &font_size::MEDIUM,
-            _ => bail!("Invalid font-size value: {:?}", current_font_size),
-        };
+    fn compute_top(value: &CssValue, context: &LengthContext) -> Result<CssValue> {
         match &value {
-            CssValue::Length(size, unit) => match unit {
-                LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px) => Ok(value.clone()),
-                LengthUnit::RelativeLengthUnit(RelativeLengthUnit::Em) => Ok(CssValue::Length(
-                    size * current_font_size,
-                    LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
-                )),
-                _ => unimplemented!(),
-            },
+            CssValue::Length(..) => value.compute_length(context),
             CssValue::Percentage(_) => unimplemented!(),
+            CssValue::Calc(_) => match value.compute_length(context)? {
+                CssValue::Calc(_) => bail!("Unsupported percentage in {}", value),
+                value => Ok(value),
+            },
             _ => bail!("Invalid padding value: {:?}", &value),
         }
     }
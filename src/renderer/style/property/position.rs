@@ -0,0 +1,323 @@
+use std::fmt;
+use std::iter::Peekable;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::{CssToken, NumericType};
+use crate::renderer::style::property::{
+    parse_length_percentage_type, CssProperty, CssValue, LengthContext,
+};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// https://drafts.csswg.org/css-position/#position-property
+#[derive(Clone, Debug, PartialEq)]
+pub struct PositionProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for PositionProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for PositionProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("static".to_string()),
+        }
+    }
+}
+
+impl CssProperty for PositionProp {
+    // position =
+    //   static | relative | absolute | sticky | fixed
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (keywords.next(), keywords.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(value))), None) => {
+                let value = value.to_ascii_lowercase();
+                match value.as_str() {
+                    "static" | "relative" | "absolute" | "sticky" | "fixed" => Ok(Self {
+                        value: CssValue::Ident(value),
+                    }),
+                    _ => bail!("Invalid position value: {:?}", value),
+                }
+            }
+            _ => bail!("Invalid position declaration: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl PositionProp {
+    fn is(&self, keyword: &str) -> bool {
+        self.value == CssValue::Ident(keyword.to_string())
+    }
+
+    /// Returns whether the box is positioned, i.e. its insets apply and it's the containing block of its
+    /// absolutely positioned descendants.
+    pub fn is_positioned(&self) -> bool {
+        !self.is("static")
+    }
+
+    /// Returns whether the box is taken out of flow, i.e. `absolute` or `fixed`.
+    /// https://drafts.csswg.org/css-position/#absolute-position
+    pub fn is_absolutely_positioned(&self) -> bool {
+        self.is("absolute") || self.is("fixed")
+    }
+
+    pub fn is_relative(&self) -> bool {
+        self.is("relative")
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        self.is("fixed")
+    }
+
+    pub fn is_sticky(&self) -> bool {
+        self.is("sticky")
+    }
+}
+
+/// The `top`, `right`, `bottom` and `left` properties, which are set together by the `inset` shorthand.
+/// https://drafts.csswg.org/css-position/#insets
+#[derive(Clone, Debug, PartialEq)]
+pub struct InsetProp {
+    pub top: CssValue,
+    pub right: CssValue,
+    pub bottom: CssValue,
+    pub left: CssValue,
+}
+
+impl fmt::Display for InsetProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.top, self.right, self.bottom, self.left
+        )
+    }
+}
+
+impl Default for InsetProp {
+    fn default() -> Self {
+        Self {
+            top: CssValue::Ident("auto".to_string()),
+            right: CssValue::Ident("auto".to_string()),
+            bottom: CssValue::Ident("auto".to_string()),
+            left: CssValue::Ident("auto".to_string()),
+        }
+    }
+}
+
+impl CssProperty for InsetProp {
+    // inset =
+    //   <'top'>{1,4}
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut values = values.iter().cloned().peekable();
+        let mut trbl = vec![];
+        while values.peek().is_some() {
+            while values
+                .next_if_eq(&ComponentValue::PreservedToken(CssToken::Whitespace))
+                .is_some()
+            {}
+            if values.peek().is_some() {
+                trbl.push(parse_top_type(&mut values)?);
+            }
+        }
+        let (top, right, bottom, left) = match trbl.as_slice() {
+            [v] => (v, v, v, v),
+            [top, right] => (top, right, top, right),
+            [top, right, bottom] => (top, right, bottom, right),
+            [top, right, bottom, left] => (top, right, bottom, left),
+            _ => bail!("Invalid inset declaration: {:?}", trbl),
+        };
+        Ok(Self {
+            top: top.clone(),
+            right: right.clone(),
+            bottom: bottom.clone(),
+            left: left.clone(),
+        })
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, context: &LengthContext) -> Result<&Self> {
+        for value in [
+            &mut self.top,
+            &mut self.right,
+            &mut self.bottom,
+            &mut self.left,
+        ] {
+            if let CssValue::Length(..) | CssValue::Calc(_) = value {
+                *value = value.compute_length(context)?;
+            }
+        }
+        Ok(self)
+    }
+}
+
+impl InsetProp {
+    /// Returns the used values of the insets in pixels as (top, right, bottom, left), where `auto` is
+    /// `None`. The percentages are relative to the size of the containing block.
+    pub fn to_px(&self, cb_width: f32, cb_height: f32) -> [Option<f32>; 4] {
+        let resolve = |value: &CssValue, basis: f32| match value {
+            CssValue::Ident(_) => None,
+            _ => value.resolve_px(basis).ok(),
+        };
+        [
+            resolve(&self.top, cb_height),
+            resolve(&self.right, cb_width),
+            resolve(&self.bottom, cb_height),
+            resolve(&self.left, cb_width),
+        ]
+    }
+}
+
+/// The stack level of a positioned box in its stacking context, where `auto` doesn't create a new stacking
+/// context.
+/// https://drafts.csswg.org/css2/#z-index
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZIndexProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for ZIndexProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for ZIndexProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("auto".to_string()),
+        }
+    }
+}
+
+impl CssProperty for ZIndexProp {
+    // z-index =
+    //   auto       |
+    //   <integer>
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (keywords.next(), keywords.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(value))), None)
+                if value.eq_ignore_ascii_case("auto") =>
+            {
+                Ok(Self::default())
+            }
+            (
+                Some(ComponentValue::PreservedToken(CssToken::Number(NumericType::Integer(value)))),
+                None,
+            ) => Ok(Self {
+                value: CssValue::Integer(*value),
+            }),
+            _ => bail!("Invalid z-index declaration: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl ZIndexProp {
+    /// Returns the stack level, or `None` if it's `auto`.
+    pub fn get_level(&self) -> Option<i32> {
+        match self.value {
+            CssValue::Integer(level) => Some(level),
+            _ => None,
+        }
+    }
+}
+
+// <top> =
+//   auto                 |
+//   <length-percentage>  |
+//   <anchor()>           |
+//   <anchor-size()>
+fn parse_top_type<I>(values: &mut Peekable<I>) -> Result<CssValue>
+where
+    I: Iterator<Item = ComponentValue>,
+{
+    match values.peek() {
+        Some(ComponentValue::PreservedToken(CssToken::Ident(value)))
+            if value.eq_ignore_ascii_case("auto") =>
+        {
+            values.next();
+            Ok(CssValue::Ident("auto".to_string()))
+        }
+        _ => parse_length_percentage_type(values),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::style::property::{AbsoluteLengthUnit, LengthUnit};
+
+    #[test]
+    fn parse_inset() {
+        let px = |v: f32| {
+            ComponentValue::PreservedToken(CssToken::Dimension(
+                NumericType::Number(v),
+                "px".to_string(),
+            ))
+        };
+        let auto = ComponentValue::PreservedToken(CssToken::Ident("auto".to_string()));
+        let inset = InsetProp::parse(&[
+            px(10.0),
+            ComponentValue::PreservedToken(CssToken::Whitespace),
+            auto,
+            ComponentValue::PreservedToken(CssToken::Whitespace),
+            ComponentValue::PreservedToken(CssToken::Percentage(50.0)),
+        ])
+        .unwrap();
+        assert_eq!(
+            inset,
+            InsetProp {
+                top: CssValue::Length(10.0, LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px)),
+                right: CssValue::Ident("auto".to_string()),
+                bottom: CssValue::Percentage(50.0),
+                left: CssValue::Ident("auto".to_string()),
+            }
+        );
+        assert_eq!(
+            inset.to_px(200.0, 100.0),
+            [Some(10.0), None, Some(50.0), None]
+        );
+
+        assert!(
+            PositionProp::parse(&[ComponentValue::PreservedToken(CssToken::Ident(
+                "Sticky".to_string()
+            ))])
+            .unwrap()
+            .is_sticky()
+        );
+        assert!(PositionProp::parse(&[px(1.0)]).is_err());
+
+        let z_index = |token| ZIndexProp::parse(&[ComponentValue::PreservedToken(token)]);
+        assert_eq!(
+            z_index(CssToken::Number(NumericType::Integer(-2)))
+                .unwrap()
+                .get_level(),
+            Some(-2)
+        );
+        assert_eq!(
+            z_index(CssToken::Ident("AUTO".to_string()))
+                .unwrap()
+                .get_level(),
+            None
+        );
+        assert!(z_index(CssToken::Number(NumericType::Number(1.5))).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// https://drafts.csswg.org/css-ruby/#rubypos
+#[derive(Clone, Debug, PartialEq)]
+pub struct RubyPositionProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for RubyPositionProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for RubyPositionProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("alternate".to_string()),
+        }
+    }
+}
+
+impl CssProperty for RubyPositionProp {
+    // ruby-position =
+    //   [ alternate || [ over | under ] ] |
+    //   inter-character
+    // todo: Support `inter-character`, which requires the vertical writing mode.
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+            .map(|v| match v {
+                ComponentValue::PreservedToken(CssToken::Ident(value)) => {
+                    Ok(value.to_ascii_lowercase())
+                }
+                _ => bail!("Invalid ruby-position value: {:?}", v),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let value = match keywords
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            ["alternate"] | ["alternate", "over"] | ["over", "alternate"] => "alternate",
+            ["alternate", "under"] | ["under", "alternate"] => "alternate under",
+            ["over"] => "over",
+            ["under"] => "under",
+            _ => bail!("Invalid ruby-position value: {:?}", keywords),
+        };
+        Ok(Self {
+            value: CssValue::Ident(value.to_string()),
+        })
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl RubyPositionProp {
+    /// Returns whether the annotations are placed under the base text. With `alternate`, the first level of
+    /// annotations is placed over it, which is the only level supported for now.
+    /// todo: Alternate the positions of the nested levels of annotations.
+    pub fn is_under(&self) -> bool {
+        self.value == CssValue::Ident("under".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ruby_position() {
+        let parse = |keywords: &[&str]| {
+            let values = keywords
+                .iter()
+                .flat_map(|k| {
+                    [
+                        ComponentValue::PreservedToken(CssToken::Ident(k.to_string())),
+                        ComponentValue::PreservedToken(CssToken::Whitespace),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            RubyPositionProp::parse(&values)
+        };
+        assert!(parse(&["under"]).unwrap().is_under());
+        assert!(!parse(&["over", "alternate"]).unwrap().is_under());
+        assert!(!parse(&["Under", "alternate"]).unwrap().is_under());
+        assert!(parse(&["over", "under"]).is_err());
+        assert!(parse(&["inter-character"]).is_err());
+    }
+}
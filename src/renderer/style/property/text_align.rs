@@ -0,0 +1,98 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// How the inline-level contents are aligned horizontally in the line boxes.
+/// todo: Support `match-parent` and `text-align-last`.
+/// https://drafts.csswg.org/css-text/#text-align-property
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextAlignProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for TextAlignProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for TextAlignProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("start".to_string()),
+        }
+    }
+}
+
+impl CssProperty for TextAlignProp {
+    // text-align =
+    //   start | end | left | right | center | justify
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (keywords.next(), keywords.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(value))), None) => {
+                let value = value.to_ascii_lowercase();
+                match value.as_str() {
+                    "start" | "end" | "left" | "right" | "center" | "justify" => Ok(Self {
+                        value: CssValue::Ident(value),
+                    }),
+                    _ => bail!("Invalid text-align value: {:?}", value),
+                }
+            }
+            _ => bail!("Invalid text-align declaration: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl TextAlignProp {
+    /// Returns whether the spaces between the words are stretched to fill the lines, except the last one.
+    pub fn is_justified(&self) -> bool {
+        matches!(&self.value, CssValue::Ident(v) if v == "justify")
+    }
+
+    /// Returns the ratio of the free space of a line placed before its contents, where the lines of the
+    /// justified texts which aren't stretched are aligned to the start.
+    pub fn get_free_space_ratio(&self, is_rtl: bool) -> f32 {
+        let CssValue::Ident(value) = &self.value else {
+            unreachable!()
+        };
+        match (value.as_str(), is_rtl) {
+            ("left", _) | ("start" | "justify", false) | ("end", true) => 0.0,
+            ("center", _) => 0.5,
+            _ => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_text_align() {
+        let parse = |keyword: &str| {
+            TextAlignProp::parse(&[ComponentValue::PreservedToken(CssToken::Ident(
+                keyword.to_string(),
+            ))])
+        };
+        let default = TextAlignProp::default();
+        assert_eq!(default.get_free_space_ratio(false), 0.0);
+        assert_eq!(default.get_free_space_ratio(true), 1.0);
+        assert_eq!(parse("Center").unwrap().get_free_space_ratio(true), 0.5);
+        assert_eq!(parse("end").unwrap().get_free_space_ratio(false), 1.0);
+        let justify = parse("justify").unwrap();
+        assert!(justify.is_justified() && justify.get_free_space_ratio(true) == 1.0);
+        assert!(parse("match-parent").is_err());
+    }
+}
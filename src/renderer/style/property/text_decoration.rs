@@ -5,32 +5,42 @@ use std::iter::Peekable;
 use anyhow::{bail, Ok, Result};
 
 use crate::renderer::css::cssom::ComponentValue;
-use crate::renderer::css::token::CssToken;
+use crate::renderer::css::token::{CssToken, NumericType};
+use crate::renderer::style::property::calc::is_math_function;
 use crate::renderer::style::property::color::{parse_color_type, ColorProp};
-use crate::renderer::style::property::{CssProperty, CssValue};
+use crate::renderer::style::property::{
+    parse_length_percentage_type, AbsoluteLengthUnit, CssProperty, CssValue, LengthContext,
+    LengthUnit,
+};
 use crate::renderer::style::style_model::SpecifiedStyle;
 
-// todo: add TextDecorationColor, TextDecorationLine, TextDecorationStyle structs for each member
-/// https://developer.mozilla.org/en-US/docs/Web/CSS/text-decoration
+/// The lines drawn over the texts of the box and of its in-flow descendants, whose longhands
+/// `text-decoration-line`, `text-decoration-style`, `text-decoration-color` and `text-decoration-thickness`
+/// are the members.
+/// todo: Support `text-underline-position`, `text-underline-offset` and `text-decoration-skip-ink`.
+/// https://drafts.csswg.org/css-text-decor/#text-decoration-property
 #[derive(Clone, Debug, PartialEq)]
 pub struct TextDecorationProp {
     pub color: ColorProp,
     pub line: Vec<CssValue>,
     pub style: CssValue,
+    /// `auto`, `from-font`, or a length whose percentage is relative to 1em.
+    pub thickness: CssValue,
 }
 
 impl fmt::Display for TextDecorationProp {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{} {} {}",
+            "{} {} {} {}",
             self.color,
             self.line
                 .iter()
                 .map(|v| v.to_string())
                 .collect::<Vec<_>>()
                 .join(" "),
-            self.style
+            self.style,
+            self.thickness
         )
     }
 }
@@ -43,27 +53,24 @@ impl Default for TextDecorationProp {
             },
             line: vec![CssValue::Ident("none".to_string())],
             style: CssValue::Ident("solid".to_string()),
+            thickness: CssValue::Ident("auto".to_string()),
         }
     }
 }
 
 impl CssProperty for TextDecorationProp {
     // text-decoration =
-    //   <'text-decoration-line'>   ||
-    //   <'text-decoration-style'>  ||
+    //   <'text-decoration-line'>      ||
+    //   <'text-decoration-thickness'> ||
+    //   <'text-decoration-style'>     ||
     //   <'text-decoration-color'>
     fn parse(values: &[ComponentValue]) -> Result<Self> {
         let mut values = values.iter().cloned().peekable();
-        let mut ret = Self {
-            color: ColorProp {
-                value: CssValue::Ident("currentColor".to_string()),
-            },
-            line: vec![CssValue::Ident("none".to_string())],
-            style: CssValue::Ident("solid".to_string()),
-        };
+        let mut ret = Self::default();
         let mut is_color_parsed = false;
         let mut is_line_parsed = false;
         let mut is_style_parsed = false;
+        let mut is_thickness_parsed = false;
 
         while values.peek().is_some() {
             while values
@@ -87,6 +94,14 @@ impl CssProperty for TextDecorationProp {
                             ret.style = parse_text_decoration_style_type(&mut values)?;
                             is_style_parsed = true;
                         }
+                        "auto" | "from-font" => {
+                            if is_thickness_parsed {
+                                bail!("text-decoration-thickness is already parsed");
+                            }
+                            ret.thickness = CssValue::Ident(ident.to_string());
+                            values.next();
+                            is_thickness_parsed = true;
+                        }
                         _ => {
                             if is_color_parsed {
                                 bail!("text-decoration-color is already parsed");
@@ -98,6 +113,25 @@ impl CssProperty for TextDecorationProp {
                         }
                     }
                 }
+                Some(ComponentValue::PreservedToken(
+                    CssToken::Dimension(..)
+                    | CssToken::Percentage(..)
+                    | CssToken::Number(NumericType::Integer(0)),
+                )) => {
+                    if is_thickness_parsed {
+                        bail!("text-decoration-thickness is already parsed");
+                    }
+                    ret.thickness = parse_length_percentage_type(&mut values)?;
+                    is_thickness_parsed = true;
+                }
+                Some(v) if is_math_function(v) => {
+                    if is_thickness_parsed {
+                        bail!("text-decoration-thickness is already parsed");
+                    }
+                    ret.thickness = parse_length_percentage_type(&mut values)?;
+                    is_thickness_parsed = true;
+                }
+                None => {}
                 _ => {
                     if is_color_parsed {
                         bail!("text-decoration-color is already parsed");
@@ -113,12 +147,33 @@ impl CssProperty for TextDecorationProp {
         Ok(ret)
     }
 
-    fn compute(&mut self, current_style: Option<&SpecifiedStyle>) -> Result<&Self> {
-        self.color.compute(current_style)?;
+    fn compute(
+        &mut self,
+        current_style: Option<&SpecifiedStyle>,
+        context: &LengthContext,
+    ) -> Result<&Self> {
+        self.color.compute(current_style, context)?;
+        if !matches!(self.thickness, CssValue::Ident(_)) {
+            self.thickness = CssValue::Length(
+                self.thickness
+                    .compute_length(context)?
+                    .resolve_px(context.font_size)?,
+                LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
+            );
+        }
         Ok(self)
     }
 }
 
+impl TextDecorationProp {
+    /// Returns whether the box draws any lines.
+    pub fn has_lines(&self) -> bool {
+        self.line
+            .iter()
+            .any(|line| !matches!(line, CssValue::Ident(v) if v == "none"))
+    }
+}
+
 // <text-decoration-line> =
 //   none                                                |
 //   [ underline || overline || line-through || blink ]
@@ -206,6 +261,7 @@ where
 mod tests {
     use super::*;
     use crate::renderer::css::token::NumericType;
+    use crate::renderer::style::property::color::Color;
 
     #[test]
     fn parse_line() {
@@ -227,7 +283,8 @@ mod tests {
                     CssValue::Ident("overline".to_string()),
                     CssValue::Ident("line-through".to_string())
                 ],
-                style: CssValue::Ident("solid".to_string())
+                style: CssValue::Ident("solid".to_string()),
+                thickness: CssValue::Ident("auto".to_string())
             }
         );
     }
@@ -244,7 +301,8 @@ mod tests {
                     value: CssValue::Ident("currentColor".to_string())
                 },
                 line: vec![CssValue::Ident("none".to_string())],
-                style: CssValue::Ident("dotted".to_string())
+                style: CssValue::Ident("dotted".to_string()),
+                thickness: CssValue::Ident("auto".to_string())
             }
         );
     }
@@ -270,7 +328,8 @@ mod tests {
                     CssValue::Ident("underline".to_string()),
                     CssValue::Ident("overline".to_string())
                 ],
-                style: CssValue::Ident("dotted".to_string())
+                style: CssValue::Ident("dotted".to_string()),
+                thickness: CssValue::Ident("auto".to_string())
             }
         );
 
@@ -296,22 +355,47 @@ mod tests {
             TextDecorationProp::parse(&values).unwrap(),
             TextDecorationProp {
                 color: ColorProp {
-                    value: CssValue::Color {
-                        r: 255,
-                        g: 0,
-                        b: 0,
-                        a: 1.0
-                    }
+                    value: CssValue::Color(Color::new(255, 0, 0, 1.0))
                 },
                 line: vec![
                     CssValue::Ident("underline".to_string()),
                     CssValue::Ident("overline".to_string())
                 ],
-                style: CssValue::Ident("dotted".to_string())
+                style: CssValue::Ident("dotted".to_string()),
+                thickness: CssValue::Ident("auto".to_string())
             }
         );
     }
 
+    #[test]
+    fn compute_thickness() {
+        let context = LengthContext {
+            font_size: 20.0,
+            root_font_size: None,
+            viewport_width: 0.0,
+            viewport_height: 0.0,
+        };
+        let values = vec![
+            ComponentValue::PreservedToken(CssToken::Ident("line-through".to_string())),
+            ComponentValue::PreservedToken(CssToken::Whitespace),
+            ComponentValue::PreservedToken(CssToken::Percentage(10.0)),
+        ];
+        let mut decoration = TextDecorationProp::parse(&values).unwrap();
+        assert!(decoration.has_lines());
+        decoration.compute(None, &context).unwrap();
+        assert_eq!(decoration.thickness.to_px().unwrap(), 2.0);
+
+        let values = vec![ComponentValue::PreservedToken(CssToken::Ident(
+            "from-font".to_string(),
+        ))];
+        let decoration = TextDecorationProp::parse(&values).unwrap();
+        assert!(!decoration.has_lines());
+        assert_eq!(
+            decoration.thickness,
+            CssValue::Ident("from-font".to_string())
+        );
+    }
+
     #[test]
     #[should_panic]
     fn parse_invalid_text_decoration_prop() {
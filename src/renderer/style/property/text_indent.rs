@@ -0,0 +1,75 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{
+    parse_length_percentage_type, AbsoluteLengthUnit, CssProperty, CssValue, LengthContext,
+    LengthUnit,
+};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// The indentation of the first line box of a block container from its start edge, where the percentages
+/// are relative to the width of the block container.
+/// todo: Support `hanging` and `each-line`.
+/// https://drafts.csswg.org/css-text/#text-indent-property
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextIndentProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for TextIndentProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for TextIndentProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Length(0.0, LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px)),
+        }
+    }
+}
+
+impl CssProperty for TextIndentProp {
+    // text-indent =
+    //   <length-percentage>
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut tokens = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+            .cloned()
+            .peekable();
+        let value = parse_length_percentage_type(&mut tokens)?;
+        if tokens.next().is_some() {
+            bail!("Invalid text-indent declaration: {:?}", values);
+        }
+        Ok(Self { value })
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, context: &LengthContext) -> Result<&Self> {
+        self.value = self.value.compute_length(context)?;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_text_indent() {
+        let indent =
+            TextIndentProp::parse(&[ComponentValue::PreservedToken(CssToken::Percentage(10.0))])
+                .unwrap();
+        assert_eq!(indent.value.resolve_px(300.0).unwrap(), 30.0);
+        assert!(
+            TextIndentProp::parse(&[ComponentValue::PreservedToken(CssToken::Ident(
+                "hanging".to_string()
+            ))])
+            .is_err()
+        );
+    }
+}
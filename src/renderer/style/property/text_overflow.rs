@@ -0,0 +1,82 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// How the contents overflowing the end edges of the line boxes are rendered, when the block container clips
+/// its inline overflow.
+/// todo: Support the strings and the values of the start edges.
+/// https://drafts.csswg.org/css-overflow/#text-overflow
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextOverflowProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for TextOverflowProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for TextOverflowProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("clip".to_string()),
+        }
+    }
+}
+
+impl CssProperty for TextOverflowProp {
+    // text-overflow =
+    //   clip | ellipsis
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (keywords.next(), keywords.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(value))), None) => {
+                let value = value.to_ascii_lowercase();
+                match value.as_str() {
+                    "clip" | "ellipsis" => Ok(Self {
+                        value: CssValue::Ident(value),
+                    }),
+                    _ => bail!("Invalid text-overflow value: {:?}", value),
+                }
+            }
+            _ => bail!("Invalid text-overflow declaration: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl TextOverflowProp {
+    /// Returns whether the overflowing contents are replaced with an ellipsis.
+    pub fn is_ellipsis(&self) -> bool {
+        matches!(&self.value, CssValue::Ident(v) if v == "ellipsis")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_text_overflow() {
+        let parse = |keyword: &str| {
+            TextOverflowProp::parse(&[ComponentValue::PreservedToken(CssToken::Ident(
+                keyword.to_string(),
+            ))])
+        };
+        assert!(parse("Ellipsis").unwrap().is_ellipsis());
+        assert!(!parse("clip").unwrap().is_ellipsis());
+        assert!(!TextOverflowProp::default().is_ellipsis());
+        assert!(parse("fade").is_err());
+    }
+}
@@ -0,0 +1,109 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// The case of the letters in the texts, which is transformed before they're laid out.
+/// todo: Support `full-width`, `full-size-kana` and the language-sensitive mappings.
+/// https://drafts.csswg.org/css-text/#text-transform-property
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextTransformProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for TextTransformProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for TextTransformProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("none".to_string()),
+        }
+    }
+}
+
+impl CssProperty for TextTransformProp {
+    // text-transform =
+    //   none | capitalize | uppercase | lowercase
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (keywords.next(), keywords.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(value))), None) => {
+                let value = value.to_ascii_lowercase();
+                match value.as_str() {
+                    "none" | "capitalize" | "uppercase" | "lowercase" => Ok(Self {
+                        value: CssValue::Ident(value),
+                    }),
+                    _ => bail!("Invalid text-transform value: {:?}", value),
+                }
+            }
+            _ => bail!("Invalid text-transform declaration: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl TextTransformProp {
+    /// Transforms the case of the text, which follows `previous` if the text continues from another one.
+    /// The first letters of the words are the letters which don't follow a letter or a digit.
+    pub fn apply(&self, text: &str, previous: Option<char>) -> String {
+        let CssValue::Ident(value) = &self.value else {
+            unreachable!()
+        };
+        match value.as_str() {
+            "uppercase" => text.to_uppercase(),
+            "lowercase" => text.to_lowercase(),
+            "capitalize" => {
+                let mut previous = previous;
+                let mut transformed = String::with_capacity(text.len());
+                for c in text.chars() {
+                    if c.is_alphabetic() && !previous.is_some_and(|p| p.is_alphanumeric()) {
+                        transformed.extend(c.to_uppercase());
+                    } else {
+                        transformed.push(c);
+                    }
+                    previous = Some(c);
+                }
+                transformed
+            }
+            _ => text.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_text() {
+        let parse = |keyword: &str| {
+            TextTransformProp::parse(&[ComponentValue::PreservedToken(CssToken::Ident(
+                keyword.to_string(),
+            ))])
+            .unwrap()
+        };
+        assert_eq!(parse("uppercase").apply("straße", None), "STRASSE");
+        assert_eq!(parse("LOWERCASE").apply("ÀB", None), "àb");
+        let capitalize = parse("capitalize");
+        assert_eq!(
+            capitalize.apply("hello wORLD (élan) 3d", None),
+            "Hello WORLD (Élan) 3d"
+        );
+        assert_eq!(capitalize.apply("ing done", Some('k')), "ing Done");
+        assert_eq!(TextTransformProp::default().apply("abc", None), "abc");
+        assert!(TextTransformProp::parse(&[]).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// How an element opens an embedding or an isolate, or overrides the directions of the characters, in the
+/// bidirectional algorithm.
+/// https://drafts.csswg.org/css-writing-modes/#unicode-bidi
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnicodeBidiProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for UnicodeBidiProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for UnicodeBidiProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("normal".to_string()),
+        }
+    }
+}
+
+impl CssProperty for UnicodeBidiProp {
+    // unicode-bidi =
+    //   normal | embed | isolate | bidi-override | isolate-override | plaintext
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (keywords.next(), keywords.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(value))), None) => {
+                let value = value.to_ascii_lowercase();
+                match value.as_str() {
+                    "normal" | "embed" | "isolate" | "bidi-override" | "isolate-override"
+                    | "plaintext" => Ok(Self {
+                        value: CssValue::Ident(value),
+                    }),
+                    _ => bail!("Invalid unicode-bidi value: {:?}", value),
+                }
+            }
+            _ => bail!("Invalid unicode-bidi declaration: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl UnicodeBidiProp {
+    pub fn as_str(&self) -> &str {
+        match &self.value {
+            CssValue::Ident(v) => v,
+            _ => "normal",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_unicode_bidi() {
+        let parse = |keyword: &str| {
+            UnicodeBidiProp::parse(&[ComponentValue::PreservedToken(CssToken::Ident(
+                keyword.to_string(),
+            ))])
+        };
+        assert_eq!(
+            parse("Isolate-Override").unwrap().as_str(),
+            "isolate-override"
+        );
+        assert_eq!(UnicodeBidiProp::default().as_str(), "normal");
+        assert!(parse("override").is_err());
+    }
+}
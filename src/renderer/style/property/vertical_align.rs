@@ -0,0 +1,70 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{
+    parse_length_percentage_type, CssProperty, CssValue, LengthContext,
+};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// The vertical position of an inline-level box in the line box, relative to the baseline of its parent or
+/// to the line box itself for `top` and `bottom`.
+/// https://www.w3.org/TR/CSS22/visudet.html#propdef-vertical-align
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerticalAlignProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for VerticalAlignProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for VerticalAlignProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("baseline".to_string()),
+        }
+    }
+}
+
+impl CssProperty for VerticalAlignProp {
+    // vertical-align =
+    //   baseline | sub | super | text-top | text-bottom | middle | top | bottom |
+    //   <length-percentage>
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut tokens = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace))
+            .cloned()
+            .peekable();
+        let value = match tokens.peek() {
+            Some(ComponentValue::PreservedToken(CssToken::Ident(value))) => {
+                let value = value.to_ascii_lowercase();
+                match value.as_str() {
+                    "baseline" | "sub" | "super" | "text-top" | "text-bottom" | "middle"
+                    | "top" | "bottom" => {
+                        tokens.next();
+                        CssValue::Ident(value)
+                    }
+                    _ => bail!("Invalid vertical-align value: {:?}", value),
+                }
+            }
+            _ => parse_length_percentage_type(&mut tokens)?,
+        };
+        if tokens.next().is_some() {
+            bail!("Invalid vertical-align declaration: {:?}", values);
+        }
+        Ok(Self { value })
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, context: &LengthContext) -> Result<&Self> {
+        if let CssValue::Length(..) | CssValue::Calc(_) = self.value {
+            self.value = self.value.compute_length(context)?;
+        }
+        Ok(self)
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// Whether and how the white space in the texts is collapsed, and whether the lines wrap.
+/// todo: Support `break-spaces` and the longhands `white-space-collapse` and `text-wrap-mode`.
+/// https://drafts.csswg.org/css-text/#white-space-property
+#[derive(Clone, Debug, PartialEq)]
+pub struct WhiteSpaceProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for WhiteSpaceProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for WhiteSpaceProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("normal".to_string()),
+        }
+    }
+}
+
+impl CssProperty for WhiteSpaceProp {
+    // white-space =
+    //   normal | pre | pre-wrap | nowrap | pre-line
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (keywords.next(), keywords.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(value))), None) => {
+                let value = value.to_ascii_lowercase();
+                match value.as_str() {
+                    "normal" | "pre" | "pre-wrap" | "nowrap" | "pre-line" => Ok(Self {
+                        value: CssValue::Ident(value),
+                    }),
+                    _ => bail!("Invalid white-space value: {:?}", value),
+                }
+            }
+            _ => bail!("Invalid white-space declaration: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl WhiteSpaceProp {
+    fn is(&self, keywords: &[&str]) -> bool {
+        matches!(&self.value, CssValue::Ident(v) if keywords.contains(&v.as_str()))
+    }
+
+    /// Returns whether the sequences of spaces and tabs collapse into a space.
+    pub fn collapses_spaces(&self) -> bool {
+        self.is(&["normal", "nowrap", "pre-line"])
+    }
+
+    /// Returns whether the segment breaks, i.e. the newlines, are preserved as forced line breaks.
+    pub fn preserves_line_breaks(&self) -> bool {
+        self.is(&["pre", "pre-wrap", "pre-line"])
+    }
+
+    /// Returns whether the lines wrap at the soft wrap opportunities.
+    pub fn wraps(&self) -> bool {
+        self.is(&["normal", "pre-wrap", "pre-line"])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_white_space() {
+        let parse = |keyword: &str| {
+            WhiteSpaceProp::parse(&[ComponentValue::PreservedToken(CssToken::Ident(
+                keyword.to_string(),
+            ))])
+        };
+        let pre = parse("PRE").unwrap();
+        assert!(!pre.collapses_spaces() && pre.preserves_line_breaks() && !pre.wraps());
+        let pre_line = parse("pre-line").unwrap();
+        assert!(pre_line.collapses_spaces() && pre_line.preserves_line_breaks());
+        assert!(!parse("nowrap").unwrap().wraps());
+        assert!(WhiteSpaceProp::default().wraps());
+        assert!(parse("break-spaces").is_err());
+    }
+}
@@ -1,13 +1,11 @@
 use std::fmt;
 
-use anyhow::{bail, Result};
+use anyhow::Result;
 
 use crate::renderer::css::cssom::ComponentValue;
 use crate::renderer::css::token::CssToken;
-use crate::renderer::style::property::font_size::{self, FontSizeProp};
 use crate::renderer::style::property::{
-    parse_length_percentage_type, AbsoluteLengthUnit, CssProperty, CssValue, LengthUnit,
-    RelativeLengthUnit,
+    parse_length_percentage_type, CssProperty, CssValue, LengthContext,
 };
 use crate::renderer::style::style_model::SpecifiedStyle;
 
@@ -57,36 +55,16 @@ impl CssProperty for WidthProp {
         }
     }
 
-    fn compute(&mut self, current_style: Option<&SpecifiedStyle>) -> Result<&Self> {
-        let current_font_size = current_style.and_then(|s| s.font_size.as_ref());
-        let current_font_size = match current_font_size {
-            Some(FontSizeProp {
-                size: CssValue::Length(size, LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px)),
-            }) => size,
-            None => &font_size::MEDIUM,
-            _ => bail!("Invalid font-size value: {:?}", current_font_size),
-        };
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, context: &LengthContext) -> Result<&Self> {
         match &self.size {
             CssValue::Ident(v) => {
                 if v != "auto" {
                     unimplemented!()
                 }
             }
-            CssValue::Length(size, unit) => match unit {
-                LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px) => {
-                    self.size = CssValue::Length(
-                        *size,
-                        LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
-                    );
-                }
-                LengthUnit::RelativeLengthUnit(RelativeLengthUnit::Em) => {
-                    self.size = CssValue::Length(
-                        size * current_font_size,
-                        LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px),
-                    );
-                }
-                _ => unimplemented!(),
-            },
+            CssValue::Length(..) | CssValue::Calc(_) => {
+                self.size = self.size.compute_length(context)?;
+            }
             CssValue::Percentage(_) => {}
             _ => unimplemented!(),
         }
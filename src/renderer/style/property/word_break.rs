@@ -0,0 +1,86 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+
+use crate::renderer::css::cssom::ComponentValue;
+use crate::renderer::css::token::CssToken;
+use crate::renderer::style::property::{CssProperty, CssValue, LengthContext};
+use crate::renderer::style::style_model::SpecifiedStyle;
+
+/// Where the lines may break within the words.
+/// todo: Support `break-word` and `auto-phrase`.
+/// https://drafts.csswg.org/css-text/#word-break-property
+#[derive(Clone, Debug, PartialEq)]
+pub struct WordBreakProp {
+    pub value: CssValue,
+}
+
+impl fmt::Display for WordBreakProp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Default for WordBreakProp {
+    fn default() -> Self {
+        Self {
+            value: CssValue::Ident("normal".to_string()),
+        }
+    }
+}
+
+impl CssProperty for WordBreakProp {
+    // word-break =
+    //   normal | break-all | keep-all
+    fn parse(values: &[ComponentValue]) -> Result<Self> {
+        let mut keywords = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (keywords.next(), keywords.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(value))), None) => {
+                let value = value.to_ascii_lowercase();
+                match value.as_str() {
+                    "normal" | "break-all" | "keep-all" => Ok(Self {
+                        value: CssValue::Ident(value),
+                    }),
+                    _ => bail!("Invalid word-break value: {:?}", value),
+                }
+            }
+            _ => bail!("Invalid word-break declaration: {:?}", values),
+        }
+    }
+
+    fn compute(&mut self, _: Option<&SpecifiedStyle>, _: &LengthContext) -> Result<&Self> {
+        Ok(self)
+    }
+}
+
+impl WordBreakProp {
+    /// Returns whether the lines may break between any two typographic letter units.
+    pub fn breaks_all(&self) -> bool {
+        matches!(&self.value, CssValue::Ident(v) if v == "break-all")
+    }
+
+    /// Returns whether the lines may not break between the letters, even in the CJK texts.
+    pub fn keeps_all(&self) -> bool {
+        matches!(&self.value, CssValue::Ident(v) if v == "keep-all")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_word_break() {
+        let parse = |keyword: &str| {
+            WordBreakProp::parse(&[ComponentValue::PreservedToken(CssToken::Ident(
+                keyword.to_string(),
+            ))])
+        };
+        assert!(parse("Break-All").unwrap().breaks_all());
+        assert!(parse("keep-all").unwrap().keeps_all());
+        assert!(!WordBreakProp::default().breaks_all());
+        assert!(parse("anywhere").is_err());
+    }
+}
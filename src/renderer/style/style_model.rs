@@ -1,4 +1,5 @@
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::default::Default;
 use std::fmt;
 use std::rc::Rc;
@@ -7,14 +8,30 @@ use anyhow::{anyhow, Context, Result};
 use gtk4::pango;
 use indexmap::IndexMap;
 
-use crate::renderer::css::cssom::{ComponentValue, Declaration, Rule, StyleSheet};
+use crate::renderer::css::cssom::{CascadeOrigin, ComponentValue, Declaration, StyleSheet};
+use crate::renderer::css::get_ua_style_sheet;
+use crate::renderer::css::media::MediaEnvironment;
+use crate::renderer::css::parser::CssParser;
 use crate::renderer::css::selector::Selector;
-use crate::renderer::html::dom::{DocumentTree, DomNode, NodeType};
+use crate::renderer::css::token::{CssToken, CssTokenizer};
+use crate::renderer::html::dom::{DocumentTree, DomNode, Element, NodeType};
+use crate::renderer::html::interaction::get_summary_for_details;
+use crate::renderer::html::mutation::clear_style_dirty_flags;
 use crate::renderer::layout::box_model::BoxTree;
+use crate::renderer::layout::replaced::ReplacedBox;
+use crate::renderer::style::forced_colors::SystemPalette;
+use crate::renderer::style::property::content::ContentItem;
+use crate::renderer::style::property::font_size;
 use crate::renderer::style::property::{
-    BackGroundColorProp, BorderProp, BorderRadiusProp, ColorProp, CssProperty, DisplayBox,
-    DisplayOutside, DisplayProp, FontFamilyProp, FontSizeProp, FontWeightProp, HeightProp,
-    MarginBlockProp, MarginProp, PaddingProp, TextDecorationProp, WidthProp,
+    BackGroundColorProp, BorderProp, BorderRadiusProp, BoxSizingProp, ColorProp, ContentProp,
+    CounterIncrementProp, CounterResetProp, CssProperty, CssValue, DirectionProp, DisplayBox,
+    DisplayOutside, DisplayProp, FloatProp, FontFamilyProp, FontShorthand, FontSizeProp,
+    FontStretchProp, FontStyleProp, FontWeightProp, ForcedColorAdjustProp, HeightProp, InsetProp,
+    LengthContext, LineHeightProp, ListStyleTypeProp, MarginBlockProp, MarginProp, MaxSizeProp,
+    MinSizeProp, OpacityProp, OverflowProp, OverflowWrapProp, PaddingProp, PositionProp,
+    RubyPositionProp, TextAlignProp, TextDecorationProp, TextIndentProp, TextOverflowProp,
+    TextTransformProp, UnicodeBidiProp, VerticalAlignProp, WhiteSpaceProp, WidthProp,
+    WordBreakProp, ZIndexProp,
 };
 use crate::utils::PrintableTree;
 
@@ -24,15 +41,76 @@ pub struct RenderTree {
 }
 
 impl RenderTree {
-    pub fn build(document_tree: &DocumentTree, style_sheets: Vec<StyleSheet>) -> Result<Self> {
+    /// Builds the render tree with all the styles computed from scratch.
+    #[tracing::instrument(skip_all)]
+    pub fn build(
+        document_tree: &DocumentTree,
+        style_sheets: Vec<StyleSheet>,
+        media: &MediaEnvironment,
+    ) -> Result<Self> {
+        Self::build_with_kept_styles(document_tree, &style_sheets, media, None)
+    }
+
+    /// Builds the render tree reusing the styles kept in `styles` by the last restyle, which must be for the
+    /// same style sheets and media, and keeps the new styles in it instead.
+    /// Only the elements whose styles are invalidated by the dirty flags since then and their subtrees are
+    /// cascaded again, and the flags are cleared.
+    #[tracing::instrument(skip_all)]
+    pub fn restyle(
+        document_tree: &DocumentTree,
+        style_sheets: Vec<StyleSheet>,
+        media: &MediaEnvironment,
+        styles: &mut StyleCache,
+    ) -> Result<Self> {
+        let kept_styles = KeptStyles {
+            previous: styles,
+            current: RefCell::default(),
+        };
+        let tree =
+            Self::build_with_kept_styles(document_tree, &style_sheets, media, Some(&kept_styles))?;
+        *styles = kept_styles.current.into_inner();
+        clear_style_dirty_flags(&document_tree.root);
+        Ok(tree)
+    }
+
+    fn build_with_kept_styles(
+        document_tree: &DocumentTree,
+        style_sheets: &[StyleSheet],
+        media: &MediaEnvironment,
+        kept_styles: Option<&KeptStyles>,
+    ) -> Result<Self> {
         Ok(Self {
             root: Rc::new(RefCell::new(
-                RenderNode::build(Rc::clone(&document_tree.root), &style_sheets, None)?
-                    .context("Failed to build the render tree.")?,
+                RenderNode::build(
+                    Rc::clone(&document_tree.root),
+                    style_sheets,
+                    None,
+                    media,
+                    kept_styles,
+                )?
+                .context("Failed to build the render tree.")?,
             )),
         })
     }
 
+    /// Returns the rendered elements in tree order with their computed styles. The elements which are
+    /// `display: none` or `display: contents`, or in the ones which are `display: none`, generate no render
+    /// nodes, and so they are not included.
+    pub fn get_element_styles(&self) -> Vec<(Rc<RefCell<DomNode>>, ComputedStyle)> {
+        let mut styles = Vec::new();
+        let mut stack = vec![Rc::clone(&self.root)];
+        while let Some(node) = stack.pop() {
+            let node = node.borrow();
+            let is_element = matches!(node.dom_node.borrow().node_type, NodeType::Element(_));
+            if is_element && node.pseudo_element.is_none() {
+                styles.push((Rc::clone(&node.dom_node), node.style.clone()));
+            }
+            stack.extend(node.children.iter().rev().cloned());
+        }
+        styles
+    }
+
+    #[tracing::instrument(skip_all)]
     pub fn to_box_tree(&self, draw_ctx: &pango::Context) -> Result<BoxTree> {
         BoxTree::build(self, draw_ctx)
     }
@@ -80,71 +158,432 @@ impl fmt::Display for RenderTree {
 
 impl PrintableTree for RenderTree {}
 
+/// The computed styles of the elements and their pseudo-elements kept by the last restyle, which are reused
+/// for the elements whose styles are not invalidated since. The styles are keyed by the addresses of the
+/// elements with the names of the pseudo-elements, or empty names for the elements themselves, and the
+/// elements are kept alive so that their addresses are not reused.
+#[derive(Debug, Default)]
+pub struct StyleCache {
+    styles: HashMap<StyleKey, (Rc<RefCell<DomNode>>, Option<ComputedStyle>)>,
+}
+
+type StyleKey = (*const RefCell<DomNode>, String);
+
+/// The styles kept by the last restyle, and the ones kept by the current restyle.
+struct KeptStyles<'a> {
+    previous: &'a StyleCache,
+    current: RefCell<StyleCache>,
+}
+
 #[derive(Debug)]
 pub struct RenderNode {
+    /// The node, or the originating element of the pseudo-element.
     pub dom_node: Rc<RefCell<DomNode>>,
+    /// The name of the pseudo-element, e.g. `before`, if the node is generated for the pseudo-element. Its
+    /// generated content is in a text node which is not in the DOM.
+    pub pseudo_element: Option<String>,
     pub style: ComputedStyle,
     pub children: Vec<Rc<RefCell<Self>>>,
 }
 
+/// The counters in scope, as `(name, value)` from the outermost. The counters created by an element are in
+/// scope for the element, its following siblings, and their descendants.
+/// https://drafts.csswg.org/css-lists/#creating-a-counter
+type Counters = Vec<(String, i32)>;
+
+/// The shadow tree being built, which is used to assign the children of the shadow host to the slots in the tree.
+/// https://dom.spec.whatwg.org/#shadow-trees
+struct ShadowScope<'a> {
+    host: Rc<RefCell<DomNode>>,
+    /// The style sheets of the tree the shadow host is in.
+    host_style_sheets: &'a [StyleSheet],
+    /// The scope of the tree the shadow host is in, if the shadow host is also in a shadow tree.
+    host_scope: Option<&'a ShadowScope<'a>>,
+}
+
 impl RenderNode {
-    pub fn build(
+    /// Builds the render tree of the node.
+    #[tracing::instrument(skip_all)]
+    fn build(
         node: Rc<RefCell<DomNode>>,
-        style_sheets: &Vec<StyleSheet>,
+        style_sheets: &[StyleSheet],
         parent_style: Option<ComputedStyle>,
+        media: &MediaEnvironment,
+        kept_styles: Option<&KeptStyles>,
     ) -> Result<Option<Self>> {
+        let mut counters = Counters::new();
+        let env = StyleEnvironment {
+            media,
+            root_font_size: None,
+            kept_styles,
+            restyles: false,
+        };
+        Ok(
+            Self::build_nodes(node, style_sheets, parent_style, None, env, &mut counters)?
+                .into_iter()
+                .next(),
+        )
+    }
+
+    /// Builds the render nodes for the node in the flat tree, in which the children of shadow hosts are
+    /// replaced with their shadow trees, and the slots are replaced with their assigned nodes.
+    /// This returns no nodes if the node is not rendered, and multiple nodes if the node is `display: contents`.
+    /// The colors are replaced with the system colors here if the environment forces them.
+    /// The `::before` and `::after` pseudo-elements of the elements are generated as their first and last
+    /// children, and `counters` are updated in tree order to generate their contents.
+    /// https://drafts.csswg.org/css-scoping/#flat-tree
+    fn build_nodes(
+        node: Rc<RefCell<DomNode>>,
+        style_sheets: &[StyleSheet],
+        parent_style: Option<ComputedStyle>,
+        scope: Option<&ShadowScope>,
+        env: StyleEnvironment,
+        counters: &mut Counters,
+    ) -> Result<Vec<Self>> {
         // Omit nodes that are not rendered.
         match &node.borrow().node_type {
             NodeType::DocumentType(_) | NodeType::Comment(_) => {
-                return Ok(None);
+                return Ok(Vec::new());
             }
             _ => {}
         }
 
-        let computed_style = match &node.borrow().node_type {
-            NodeType::Element(_) => {
-                // https://www.w3.org/TR/css-cascade-3/#value-stages
-                apply_filtering(Rc::clone(&node), style_sheets)
-                    .apply_cascading()
-                    .apply_defaulting(&parent_style)?
-                    .apply_computing()
-            }
-            NodeType::Text(_) => {
-                if parent_style.is_some() {
-                    let mut style = parent_style.as_ref().unwrap().clone();
-                    style.display.outside = DisplayOutside::Inline;
+        let is_element = matches!(&node.borrow().node_type, NodeType::Element(_));
+        let kept_style = if is_element {
+            env.get_kept_style(&node, "").flatten()
+        } else {
+            None
+        };
+        let is_restyled = is_element && kept_style.is_none();
+        let computed_style = if let Some(style) = kept_style {
+            style
+        } else {
+            match &node.borrow().node_type {
+                NodeType::Element(elm) => {
+                    // https://www.w3.org/TR/css-cascade-3/#value-stages
+                    let mut style = apply_filtering(&node, style_sheets, env.media, None)
+                        .apply_cascading()
+                        .apply_defaulting(&parent_style)?
+                        .apply_computing(&env.get_length_context(parent_style.as_ref()));
+                    if env.media.forced_colors {
+                        let is_link = matches!(elm.tag_name.as_str(), "a" | "area")
+                            && elm.get_attribute("href").is_some();
+                        let is_root = node
+                            .borrow()
+                            .parent
+                            .as_ref()
+                            .and_then(|p| p.upgrade())
+                            .is_some_and(|p| matches!(p.borrow().node_type, NodeType::Document(_)));
+                        style.apply_forced_colors(
+                            &SystemPalette::default(),
+                            parent_style.as_ref(),
+                            is_link,
+                            is_root,
+                        );
+                    }
                     style
-                } else {
-                    unreachable!()
                 }
+                NodeType::Text(_) => {
+                    if parent_style.is_some() {
+                        let mut style = parent_style.as_ref().unwrap().clone();
+                        // The parent may be `display: contents` or a block container, which is not applied
+                        // to the text.
+                        style.display = DisplayProp::default();
+                        style
+                    } else {
+                        unreachable!()
+                    }
+                }
+                _ => ComputedStyle::default(),
             }
-            _ => ComputedStyle::default(),
         };
+        if is_element {
+            env.keep_style(&node, "", Some(&computed_style));
+        }
 
         // All elements with a value of none for the display property and their descendants are not rendered.
         // Some elements such as <meta>, <title>, <script>, <style> are marked as `none` in the UA style sheet.
         // https://developer.mozilla.org/en-US/docs/Web/CSS/display#none
-        if computed_style.display.display_box == Some(DisplayBox::None) {
-            return Ok(None);
+        if computed_style.display.is_not_rendered() {
+            return Ok(Vec::new());
         }
 
-        let child_nodes = node
-            .borrow()
-            .children
-            .iter()
-            .map(|child| Self::build(Rc::clone(child), style_sheets, Some(computed_style.clone())))
-            .collect::<Result<Vec<_>>>()?
-            .into_iter()
-            // Skip the children that are not rendered.
-            .filter(|child| child.is_some())
-            .map(|child| Rc::new(RefCell::new(child.unwrap())))
-            .collect::<Vec<_>>();
+        // The first element styled is the root element, whose font size `rem` is relative to.
+        // The styles of the descendants and the pseudo-elements depend on the style of the node, so they are
+        // restyled with it.
+        let env = StyleEnvironment {
+            root_font_size: env
+                .root_font_size
+                .or_else(|| computed_style.font_size.to_px().ok()),
+            restyles: env.restyles || is_restyled || node.borrow().dirty.subtree_style,
+            ..env
+        };
+
+        let is_slot =
+            matches!(&node.borrow().node_type, NodeType::Element(elm) if elm.tag_name == "slot");
+        let assigned_nodes = match scope {
+            Some(scope) if is_slot => DomNode::find_slottables(&scope.host, &node),
+            _ => Vec::new(),
+        };
+        let shadow_root = node.borrow().shadow_root.clone();
+
+        if matches!(&node.borrow().node_type, NodeType::Element(_)) {
+            apply_counters(
+                counters,
+                &computed_style.counter_reset,
+                &computed_style.counter_increment,
+            );
+            // List items increment the `list-item` counter implicitly unless it's incremented explicitly.
+            // https://drafts.csswg.org/css-lists/#declaring-a-list-item
+            if computed_style.display.is_list_item
+                && !computed_style
+                    .counter_increment
+                    .counters
+                    .iter()
+                    .any(|(name, _)| name == "list-item")
+            {
+                apply_counters(
+                    counters,
+                    &CounterResetProp::default(),
+                    &CounterIncrementProp {
+                        counters: vec![("list-item".to_string(), 1)],
+                    },
+                );
+            }
+        }
+        // The counters created by the descendants are out of scope after the element.
+        let counters_len = counters.len();
+
+        let mut child_nodes = Vec::new();
+        if computed_style.display.is_list_item {
+            child_nodes.extend(Self::build_marker(
+                &node,
+                style_sheets,
+                &computed_style,
+                env,
+                counters,
+            )?);
+        }
+        child_nodes.extend(Self::build_pseudo_element(
+            &node,
+            "before",
+            style_sheets,
+            &computed_style,
+            env,
+            counters,
+        )?);
+        if let (false, Some(scope)) = (assigned_nodes.is_empty(), scope) {
+            // The assigned nodes are in the tree of the shadow host, so they are styled with its style sheets.
+            // If no nodes are assigned, the children of the slot are rendered as the fallback content.
+            for assigned_node in assigned_nodes {
+                child_nodes.extend(Self::build_nodes(
+                    assigned_node,
+                    scope.host_style_sheets,
+                    Some(computed_style.clone()),
+                    scope.host_scope,
+                    env,
+                    counters,
+                )?);
+            }
+        } else if let Some(shadow_root) = shadow_root {
+            // The style sheets of the document don't apply to the shadow tree, except for the UA style sheet.
+            // https://drafts.csswg.org/css-scoping/#shadow-cascading
+            let shadow_style_sheets = std::iter::once(get_ua_style_sheet())
+                .chain(get_shadow_style_sheets(&shadow_root))
+                .collect::<Result<Vec<_>>>()?;
+            let shadow_scope = ShadowScope {
+                host: Rc::clone(&node),
+                host_style_sheets: style_sheets,
+                host_scope: scope,
+            };
+            // The mutations of the shadow trees don't set the dirty flags, so they are always restyled.
+            let env = StyleEnvironment {
+                restyles: true,
+                ..env
+            };
+            for child in &shadow_root.borrow().children {
+                child_nodes.extend(Self::build_nodes(
+                    Rc::clone(child),
+                    &shadow_style_sheets,
+                    Some(computed_style.clone()),
+                    Some(&shadow_scope),
+                    env,
+                    counters,
+                )?);
+            }
+        } else {
+            // Only the summary of a closed `details` element is rendered.
+            // https://html.spec.whatwg.org/multipage/rendering.html#the-details-and-summary-elements
+            let is_closed_details = matches!(&node.borrow().node_type,
+                NodeType::Element(elm) if elm.tag_name == "details" && elm.get_attribute("open").is_none());
+            let summary = is_closed_details
+                .then(|| get_summary_for_details(&node))
+                .flatten();
+            for child in node.borrow().children.iter().filter(|child| {
+                !is_closed_details || summary.as_ref().is_some_and(|s| Rc::ptr_eq(s, child))
+            }) {
+                child_nodes.extend(Self::build_nodes(
+                    Rc::clone(child),
+                    style_sheets,
+                    Some(computed_style.clone()),
+                    scope,
+                    env,
+                    counters,
+                )?);
+            }
+        }
+        child_nodes.extend(Self::build_pseudo_element(
+            &node,
+            "after",
+            style_sheets,
+            &computed_style,
+            env,
+            counters,
+        )?);
+        counters.truncate(counters_len);
+
+        // The element itself does not generate any boxes, and its children are rendered in its place.
+        // https://drafts.csswg.org/css-display/#valdef-display-contents
+        if computed_style.display.display_box == Some(DisplayBox::Contents) {
+            return Ok(child_nodes);
+        }
 
-        Ok(Some(Self {
+        Ok(vec![Self {
             dom_node: Rc::clone(&node),
+            pseudo_element: None,
             style: computed_style,
-            children: child_nodes,
-        }))
+            children: child_nodes
+                .into_iter()
+                .map(|child| Rc::new(RefCell::new(child)))
+                .collect(),
+        }])
+    }
+
+    /// Builds the render nodes for the pseudo-element of the element if its `content` is not `none`, whose
+    /// children are the text of the content. Replaced elements have no `::before` and `::after`.
+    /// todo: Generate the other pseudo-elements, e.g. `::first-letter`.
+    /// https://drafts.csswg.org/css-pseudo/#generated-content
+    fn build_pseudo_element(
+        node: &Rc<RefCell<DomNode>>,
+        name: &str,
+        style_sheets: &[StyleSheet],
+        parent_style: &ComputedStyle,
+        env: StyleEnvironment,
+        counters: &mut Counters,
+    ) -> Result<Vec<Self>> {
+        let elm = match &node.borrow().node_type {
+            NodeType::Element(elm) if !ReplacedBox::is_replaced(elm) => elm.clone(),
+            _ => return Ok(Vec::new()),
+        };
+        let Some(style) =
+            Self::compute_pseudo_style(node, name, style_sheets, parent_style, env, false)?
+        else {
+            return Ok(Vec::new());
+        };
+        let Some(items) = &style.content.items else {
+            return Ok(Vec::new());
+        };
+        if style.display.is_not_rendered() {
+            return Ok(Vec::new());
+        }
+
+        apply_counters(counters, &style.counter_reset, &style.counter_increment);
+        let text = get_content_text(items, &elm, counters);
+        Ok(Self::new_pseudo_element(node, name, style, text))
+    }
+
+    /// Builds the render nodes for the `::marker` pseudo-element of the list item, whose text is its
+    /// `content` or the marker of `list-style-type` for the value of the `list-item` counter. The marker is
+    /// placed inline at the start of the list item.
+    /// todo: Place the markers outside the list items for `list-style-position: outside`.
+    /// https://drafts.csswg.org/css-lists/#marker-pseudo
+    fn build_marker(
+        node: &Rc<RefCell<DomNode>>,
+        style_sheets: &[StyleSheet],
+        parent_style: &ComputedStyle,
+        env: StyleEnvironment,
+        counters: &Counters,
+    ) -> Result<Vec<Self>> {
+        let elm = match &node.borrow().node_type {
+            NodeType::Element(elm) => elm.clone(),
+            _ => return Ok(Vec::new()),
+        };
+        let Some(mut style) =
+            Self::compute_pseudo_style(node, "marker", style_sheets, parent_style, env, true)?
+        else {
+            return Ok(Vec::new());
+        };
+        let text = match &style.content.items {
+            Some(items) => get_content_text(items, &elm, counters),
+            None => match style
+                .list_style_type
+                .get_marker_text(get_counter_value(counters, "list-item"))
+            {
+                Some(text) => text,
+                None => return Ok(Vec::new()),
+            },
+        };
+        // The marker box is an inline box regardless of its `display`.
+        style.display = DisplayProp::default();
+        Ok(Self::new_pseudo_element(node, "marker", style, text))
+    }
+
+    /// Returns the computed style of the pseudo-element of the element, or `None` if no rules apply to it
+    /// unless `is_always_styled`.
+    fn compute_pseudo_style(
+        node: &Rc<RefCell<DomNode>>,
+        name: &str,
+        style_sheets: &[StyleSheet],
+        parent_style: &ComputedStyle,
+        env: StyleEnvironment,
+        is_always_styled: bool,
+    ) -> Result<Option<ComputedStyle>> {
+        if let Some(style) = env.get_kept_style(node, name) {
+            env.keep_style(node, name, style.as_ref());
+            return Ok(style);
+        }
+        let declared_values = apply_filtering(node, style_sheets, env.media, Some(name));
+        if declared_values.values.is_empty() && !is_always_styled {
+            env.keep_style(node, name, None);
+            return Ok(None);
+        }
+        let mut style = declared_values
+            .apply_cascading()
+            .apply_defaulting(&Some(parent_style.clone()))?
+            .apply_computing(&env.get_length_context(Some(parent_style)));
+        if env.media.forced_colors {
+            style.apply_forced_colors(&SystemPalette::default(), Some(parent_style), false, false);
+        }
+        env.keep_style(node, name, Some(&style));
+        Ok(Some(style))
+    }
+
+    /// Returns the render node of the pseudo-element of the element with its text as the only child, or
+    /// only the text if the pseudo-element is `display: contents`.
+    fn new_pseudo_element(
+        node: &Rc<RefCell<DomNode>>,
+        name: &str,
+        style: ComputedStyle,
+        text: String,
+    ) -> Vec<Self> {
+        let mut text_style = style.clone();
+        text_style.display = DisplayProp::default();
+        let text_node = Self {
+            dom_node: Rc::new(RefCell::new(DomNode::new(NodeType::Text(text)))),
+            pseudo_element: None,
+            style: text_style,
+            children: Vec::new(),
+        };
+
+        if style.display.display_box == Some(DisplayBox::Contents) {
+            return vec![text_node];
+        }
+        vec![Self {
+            dom_node: Rc::clone(node),
+            pseudo_element: Some(name.to_string()),
+            style,
+            children: vec![Rc::new(RefCell::new(text_node))],
+        }]
     }
 
     pub fn get_display_type(&self) -> DisplayOutside {
@@ -152,10 +591,111 @@ impl RenderNode {
     }
 }
 
+/// The environment in which the nodes are styled.
+#[derive(Clone, Copy)]
+struct StyleEnvironment<'a> {
+    media: &'a MediaEnvironment,
+    /// The computed font size of the root element, which is `None` until the root element is styled.
+    root_font_size: Option<f32>,
+    /// The styles kept by the last restyle, which are `None` if all the styles are computed from scratch.
+    kept_styles: Option<&'a KeptStyles<'a>>,
+    /// Whether the node must be restyled regardless of its dirty flags, since an ancestor is restyled.
+    restyles: bool,
+}
+
+impl StyleEnvironment<'_> {
+    /// Returns the style of the element or its pseudo-element kept by the last restyle, or `None` if it must
+    /// be computed again.
+    fn get_kept_style(
+        &self,
+        node: &Rc<RefCell<DomNode>>,
+        pseudo_element: &str,
+    ) -> Option<Option<ComputedStyle>> {
+        let kept_styles = self.kept_styles?;
+        if self.restyles || node.borrow().dirty.style {
+            return None;
+        }
+        kept_styles
+            .previous
+            .styles
+            .get(&(Rc::as_ptr(node), pseudo_element.to_string()))
+            .map(|(_, style)| style.clone())
+    }
+
+    /// Keeps the style of the element or its pseudo-element for the next restyle.
+    fn keep_style(
+        &self,
+        node: &Rc<RefCell<DomNode>>,
+        pseudo_element: &str,
+        style: Option<&ComputedStyle>,
+    ) {
+        if let Some(kept_styles) = self.kept_styles {
+            kept_styles.current.borrow_mut().styles.insert(
+                (Rc::as_ptr(node), pseudo_element.to_string()),
+                (Rc::clone(node), style.cloned()),
+            );
+        }
+    }
+
+    /// Returns the context in which the lengths of the child of the node with `parent_style` are computed.
+    fn get_length_context(&self, parent_style: Option<&ComputedStyle>) -> LengthContext {
+        LengthContext {
+            font_size: parent_style
+                .and_then(|s| s.font_size.to_px().ok())
+                .unwrap_or(font_size::MEDIUM),
+            root_font_size: self.root_font_size,
+            ..LengthContext::new(self.media)
+        }
+    }
+}
+
+/// Returns the text generated by the items of `content` for the element.
+fn get_content_text(items: &[ContentItem], elm: &Element, counters: &Counters) -> String {
+    items
+        .iter()
+        .map(|item| match item {
+            ContentItem::String(s) => s.clone(),
+            ContentItem::Attr(name) => elm.get_attribute(name).unwrap_or_default().to_string(),
+            ContentItem::Counter(name, counter_style) => {
+                counter_style.format(get_counter_value(counters, name))
+            }
+        })
+        .collect()
+}
+
+/// Returns the value of the innermost counter with the name in scope, which is zero if there is none.
+fn get_counter_value(counters: &Counters, name: &str) -> i32 {
+    counters
+        .iter()
+        .rev()
+        .find(|(n, _)| n == name)
+        .map_or(0, |(_, value)| *value)
+}
+
+/// Creates the counters with `counter-reset` and then increments them with `counter-increment`. The counters
+/// which are incremented but not in scope are created with zero.
+/// https://drafts.csswg.org/css-lists/#auto-numbering
+fn apply_counters(
+    counters: &mut Counters,
+    counter_reset: &CounterResetProp,
+    counter_increment: &CounterIncrementProp,
+) {
+    counters.extend(counter_reset.counters.iter().cloned());
+    for (name, value) in &counter_increment.counters {
+        match counters.iter_mut().rev().find(|(n, _)| n == name) {
+            Some((_, counter)) => *counter += value,
+            None => counters.push((name.clone(), *value)),
+        }
+    }
+}
+
 impl fmt::Display for RenderNode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self.dom_node.borrow().node_type {
-            NodeType::Element(elm) => write!(f, "{}, Computed( {})", elm, self.style),
+            NodeType::Element(elm) => match &self.pseudo_element {
+                Some(name) => write!(f, "{}::{}, Computed( {})", elm, name, self.style),
+                None => write!(f, "{}, Computed( {})", elm, self.style),
+            },
             NodeType::Text(_) => write!(
                 f,
                 "{:?}, Computed( {})",
@@ -167,34 +707,104 @@ impl fmt::Display for RenderNode {
     }
 }
 
-/// Returns all declared values that match the node.
+/// Returns the style sheets of the `style` elements in the shadow tree, in tree order.
+/// https://drafts.csswg.org/cssom/#documentorshadowroot-document-or-shadow-root-css-style-sheets
+fn get_shadow_style_sheets(shadow_root: &Rc<RefCell<DomNode>>) -> Vec<Result<StyleSheet>> {
+    let mut style_sheets = Vec::new();
+    for child in &shadow_root.borrow().children {
+        if let NodeType::Element(elm) = &child.borrow().node_type {
+            if elm.tag_name == "style" {
+                let css = child
+                    .borrow()
+                    .children
+                    .iter()
+                    .filter_map(|n| n.borrow().get_inside_text())
+                    .collect::<String>();
+                style_sheets.push(
+                    CssTokenizer::new(&css)
+                        .tokenize()
+                        .and_then(|tokens| CssParser::new(&tokens).parse()),
+                );
+                continue;
+            }
+        }
+        style_sheets.extend(get_shadow_style_sheets(child));
+    }
+    style_sheets
+}
+
+/// Returns all declared values that match the node, or its pseudo-element if `pseudo_element` is given.
+/// The rules in the `@media` rules apply only if their conditions match the environment.
 /// https://www.w3.org/TR/css-cascade-3/#filtering
-fn apply_filtering(node: Rc<RefCell<DomNode>>, style_sheets: &[StyleSheet]) -> DeclaredStyle {
+#[tracing::instrument(skip_all)]
+fn apply_filtering(
+    node: &Rc<RefCell<DomNode>>,
+    style_sheets: &[StyleSheet],
+    media: &MediaEnvironment,
+    pseudo_element: Option<&str>,
+) -> DeclaredStyle {
     let mut declared_values = DeclaredStyle::new();
 
     // As for the order of appearance in the subsequent cascading stage, the declarations from style sheets independently
     // linked by the originating document are treated as if they were concatenated in linking order, as determined by the host document language.
-    style_sheets.iter().for_each(|style_sheet| {
-        style_sheet.rules.iter().for_each(|rule| {
-            let selectors = rule.get_matched_selectors(Rc::clone(&node));
-            if selectors.is_some() {
-                let Rule::QualifiedRule(qualified_rule) = rule else {
-                    unreachable!();
-                };
-                for selector in selectors.unwrap() {
-                    declared_values.add(selector, &qualified_rule.declarations);
-                }
-            }
+    style_sheets
+        .iter()
+        .enumerate()
+        .for_each(|(style_sheet_index, style_sheet)| {
+            style_sheet
+                .get_style_rules(media)
+                .into_iter()
+                .enumerate()
+                .for_each(|(rule_index, rule)| {
+                    let selectors = rule.get_matched_selectors(node, pseudo_element);
+                    if selectors.is_some() {
+                        for selector in selectors.unwrap() {
+                            declared_values.add(
+                                selector,
+                                &rule.declarations,
+                                style_sheet.origin,
+                                (style_sheet_index, rule_index),
+                            );
+                        }
+                    }
+                });
         });
-    });
+
+    // The declarations in the `style` attribute belong to the author origin.
+    // https://drafts.csswg.org/css-style-attr/#interpret
+    let style = match &node.borrow().node_type {
+        NodeType::Element(elm) if pseudo_element.is_none() => {
+            elm.get_attribute("style").map(str::to_string)
+        }
+        _ => None,
+    };
+    if let Some(style) = style {
+        match CssTokenizer::new(&style).tokenize() {
+            Ok(tokens) => declared_values
+                .add_style_attribute(&CssParser::new(&tokens).parse_list_of_declarations()),
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
 
     declared_values
 }
 
+/// The position of a rule in the style sheets, as `(style sheet index, rule index)`.
+/// The rule with the greater value appears later in the style sheets.
+/// https://www.w3.org/TR/css-cascade-3/#cascade-order
+pub type SourceOrder = (usize, usize);
+
 /// https://www.w3.org/TR/css-cascade-3/#declared
 #[derive(Debug)]
 pub struct DeclaredStyle {
-    pub values: Vec<(Selector, Vec<Declaration>)>,
+    /// The declarations with the selectors through which they apply, where `None` means the declarations
+    /// are in the `style` attribute of the element.
+    pub values: Vec<(
+        Option<Selector>,
+        Vec<Declaration>,
+        CascadeOrigin,
+        SourceOrder,
+    )>,
 }
 
 impl DeclaredStyle {
@@ -202,38 +812,98 @@ impl DeclaredStyle {
         Self { values: Vec::new() }
     }
 
-    pub fn add(&mut self, selector: Selector, declarations: &[Declaration]) {
-        self.values.push((selector, declarations.to_vec()));
+    pub fn add(
+        &mut self,
+        selector: Selector,
+        declarations: &[Declaration],
+        origin: CascadeOrigin,
+        source_order: SourceOrder,
+    ) {
+        self.values
+            .push((Some(selector), declarations.to_vec(), origin, source_order));
+    }
+
+    /// Adds the declarations in the `style` attribute of the element, which win over the ones of any
+    /// selector in the author origin.
+    /// https://drafts.csswg.org/css-cascade-5/#style-attr
+    pub fn add_style_attribute(&mut self, declarations: &[Declaration]) {
+        self.values.push((
+            None,
+            declarations.to_vec(),
+            CascadeOrigin::Author,
+            SourceOrder::default(),
+        ));
     }
 
     /// Returns the cascaded values, which are the declared values that "win" the cascade.
     /// There is at most one cascaded value per property per element.
-    /// https://www.w3.org/TR/css-cascade-3/#cascading
+    /// https://drafts.csswg.org/css-cascade-5/#cascade-sort
+    #[tracing::instrument(skip_all)]
     pub fn apply_cascading(&self) -> CascadedStyle {
-        // Vec<(index, declaration, specificity)>
+        // Vec<(origin and importance, (element-attached, specificity), (source order, declaration index), declaration, origin)>
         let mut sorted_list = self
             .values
             .iter()
-            .enumerate()
-            // This function assumes that the element with the lower index is the one that appears earlier in the stylesheets.
-            .map(|(index, (selector, declarations))| {
-                (index, declarations.clone(), selector.calc_specificity())
+            .flat_map(|(selector, declarations, origin, source_order)| {
+                let specificity = match selector {
+                    Some(selector) => (false, selector.calc_specificity()),
+                    None => (true, Default::default()),
+                };
+                declarations
+                    .iter()
+                    .enumerate()
+                    .map(move |(i, declaration)| {
+                        (
+                            origin.get_precedence(declaration.important),
+                            specificity,
+                            (*source_order, i),
+                            declaration,
+                            *origin,
+                        )
+                    })
             })
             .collect::<Vec<_>>();
 
-        // Sort by specificity and then by index in descending order. If the specificity is the same,
-        // the order of the declarations in the stylesheet is preserved (the last declared style gets precedence).
-        sorted_list.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| b.0.cmp(&a.0)));
+        // Sort by origin and importance, element-attached or not, specificity, and then order of appearance
+        // in descending order.
+        // If all of them are the same, the declaration that appears later in the style sheets gets precedence.
+        sorted_list.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| b.1.cmp(&a.1))
+                .then_with(|| b.2.cmp(&a.2))
+        });
 
         // Determine the winning (highest-priority) declarations.
         let mut cascaded_values = CascadedStyle::new();
-        for declarations in sorted_list.iter().map(|(_, declarations, _)| declarations) {
-            for declaration in declarations {
-                // The higher-priority declarations are placed first in the table,
-                // and declarations placed later in the table that have lower-priority
-                // with the same name are ignored.
-                cascaded_values.add(&declaration.name, &declaration.value);
+        // The properties whose winning declarations are `revert`, with the origins of the declarations.
+        let mut reverted = HashMap::new();
+        for (_, _, _, declaration, origin) in sorted_list {
+            let name = declaration.name.as_str();
+            if cascaded_values.values.contains_key(name)
+                || reverted.get(name).is_some_and(|o| origin >= *o)
+            {
+                continue;
             }
+            // `revert` rolls the cascade back as if its origin and the later ones had no declarations of the
+            // property.
+            // https://drafts.csswg.org/css-cascade-5/#default
+            if CssWideKeyword::parse(&declaration.value) == Some(CssWideKeyword::Revert) {
+                reverted.insert(name, origin);
+                continue;
+            }
+            // The higher-priority declarations are placed first in the table,
+            // and declarations placed later in the table that have lower-priority
+            // with the same name are ignored.
+            cascaded_values.add(name, &declaration.value);
+        }
+        // The properties reverted in all the origins are left `revert`, which then acts as `unset`.
+        for name in reverted.into_keys() {
+            cascaded_values.add(
+                name,
+                &[ComponentValue::PreservedToken(CssToken::Ident(
+                    "revert".to_string(),
+                ))],
+            );
         }
 
         cascaded_values
@@ -260,8 +930,11 @@ impl CascadedStyle {
             .or_insert_with(|| values.to_vec());
     }
 
-    /// Returns the specified values. All properties are set to their initial values or inherited values.
+    /// Returns the specified values. The properties without cascaded values are set to the computed values
+    /// of the parent if they are inherited properties, and to their initial values otherwise, and so are the
+    /// ones whose cascaded values are the CSS-wide keywords.
     /// https://www.w3.org/TR/css-cascade-3/#defaulting
+    #[tracing::instrument(skip_all)]
     pub fn apply_defaulting(&self, parent_style: &Option<ComputedStyle>) -> Result<SpecifiedStyle> {
         let mut specified_values = SpecifiedStyle::new();
 
@@ -271,12 +944,44 @@ impl CascadedStyle {
             specified_values.inherit(parent_style.as_ref().unwrap());
         }
 
-        specified_values.set_from(self);
+        specified_values.set_from(self, parent_style.as_ref());
 
         Ok(specified_values)
     }
 }
 
+/// The keywords which all the properties accept, and which set the properties to the initial values or the
+/// inherited values instead of their own values. `revert-layer` is the same as `revert` since there are no
+/// cascade layers.
+/// https://drafts.csswg.org/css-cascade-5/#defaulting-keywords
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CssWideKeyword {
+    Initial,
+    Inherit,
+    Unset,
+    Revert,
+}
+
+impl CssWideKeyword {
+    fn parse(values: &[ComponentValue]) -> Option<Self> {
+        let mut values = values
+            .iter()
+            .filter(|v| **v != ComponentValue::PreservedToken(CssToken::Whitespace));
+        match (values.next(), values.next()) {
+            (Some(ComponentValue::PreservedToken(CssToken::Ident(ident))), None) => {
+                match ident.to_ascii_lowercase().as_str() {
+                    "initial" => Some(Self::Initial),
+                    "inherit" => Some(Self::Inherit),
+                    "unset" => Some(Self::Unset),
+                    "revert" | "revert-layer" => Some(Self::Revert),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
 /// https://www.w3.org/TR/css-cascade-3/#specified
 #[derive(Clone, Debug, Default)]
 pub struct SpecifiedStyle {
@@ -286,6 +991,8 @@ pub struct SpecifiedStyle {
     pub font_family: Option<FontFamilyProp>,
     pub font_size: Option<FontSizeProp>,
     pub font_weight: Option<FontWeightProp>,
+    pub font_style: Option<FontStyleProp>,
+    pub font_stretch: Option<FontStretchProp>,
     pub text_decoration: Option<TextDecorationProp>,
     pub margin: Option<MarginProp>,
     pub margin_block: Option<MarginBlockProp>,
@@ -293,7 +1000,35 @@ pub struct SpecifiedStyle {
     pub padding: Option<PaddingProp>,
     pub width: Option<WidthProp>,
     pub height: Option<HeightProp>,
+    pub box_sizing: Option<BoxSizingProp>,
+    pub min_width: Option<MinSizeProp>,
+    pub max_width: Option<MaxSizeProp>,
+    pub min_height: Option<MinSizeProp>,
+    pub max_height: Option<MaxSizeProp>,
     pub border_radius: Option<BorderRadiusProp>,
+    pub forced_color_adjust: Option<ForcedColorAdjustProp>,
+    pub ruby_position: Option<RubyPositionProp>,
+    pub line_height: Option<LineHeightProp>,
+    pub vertical_align: Option<VerticalAlignProp>,
+    pub white_space: Option<WhiteSpaceProp>,
+    pub word_break: Option<WordBreakProp>,
+    pub overflow_wrap: Option<OverflowWrapProp>,
+    pub direction: Option<DirectionProp>,
+    pub unicode_bidi: Option<UnicodeBidiProp>,
+    pub text_align: Option<TextAlignProp>,
+    pub text_indent: Option<TextIndentProp>,
+    pub text_transform: Option<TextTransformProp>,
+    pub text_overflow: Option<TextOverflowProp>,
+    pub content: Option<ContentProp>,
+    pub counter_reset: Option<CounterResetProp>,
+    pub counter_increment: Option<CounterIncrementProp>,
+    pub list_style_type: Option<ListStyleTypeProp>,
+    pub position: Option<PositionProp>,
+    pub float: Option<FloatProp>,
+    pub inset: Option<InsetProp>,
+    pub z_index: Option<ZIndexProp>,
+    pub opacity: Option<OpacityProp>,
+    pub overflow: Option<OverflowProp>,
 }
 
 impl SpecifiedStyle {
@@ -310,6 +1045,8 @@ impl SpecifiedStyle {
         self.font_family = Some(FontFamilyProp::default());
         self.font_size = Some(FontSizeProp::default());
         self.font_weight = Some(FontWeightProp::default());
+        self.font_style = Some(FontStyleProp::default());
+        self.font_stretch = Some(FontStretchProp::default());
         self.text_decoration = Some(TextDecorationProp::default());
         self.margin = Some(MarginProp::default());
         self.margin_block = Some(MarginBlockProp::default());
@@ -317,7 +1054,35 @@ impl SpecifiedStyle {
         self.padding = Some(PaddingProp::default());
         self.width = Some(WidthProp::default());
         self.height = Some(HeightProp::default());
+        self.box_sizing = Some(BoxSizingProp::default());
+        self.min_width = Some(MinSizeProp::default());
+        self.max_width = Some(MaxSizeProp::default());
+        self.min_height = Some(MinSizeProp::default());
+        self.max_height = Some(MaxSizeProp::default());
         self.border_radius = Some(BorderRadiusProp::default());
+        self.forced_color_adjust = Some(ForcedColorAdjustProp::default());
+        self.ruby_position = Some(RubyPositionProp::default());
+        self.line_height = Some(LineHeightProp::default());
+        self.vertical_align = Some(VerticalAlignProp::default());
+        self.white_space = Some(WhiteSpaceProp::default());
+        self.word_break = Some(WordBreakProp::default());
+        self.overflow_wrap = Some(OverflowWrapProp::default());
+        self.direction = Some(DirectionProp::default());
+        self.unicode_bidi = Some(UnicodeBidiProp::default());
+        self.text_align = Some(TextAlignProp::default());
+        self.text_indent = Some(TextIndentProp::default());
+        self.text_transform = Some(TextTransformProp::default());
+        self.text_overflow = Some(TextOverflowProp::default());
+        self.content = Some(ContentProp::default());
+        self.counter_reset = Some(CounterResetProp::default());
+        self.counter_increment = Some(CounterIncrementProp::default());
+        self.list_style_type = Some(ListStyleTypeProp::default());
+        self.position = Some(PositionProp::default());
+        self.float = Some(FloatProp::default());
+        self.inset = Some(InsetProp::default());
+        self.z_index = Some(ZIndexProp::default());
+        self.opacity = Some(OpacityProp::default());
+        self.overflow = Some(OverflowProp::default());
     }
 
     /// Sets the inherited values for all "inherited properties".
@@ -327,10 +1092,25 @@ impl SpecifiedStyle {
         self.font_family = Some(parent_values.font_family.clone());
         self.font_size = Some(parent_values.font_size.clone());
         self.font_weight = Some(parent_values.font_weight.clone());
+        self.font_style = Some(parent_values.font_style.clone());
+        self.font_stretch = Some(parent_values.font_stretch.clone());
+        self.forced_color_adjust = Some(parent_values.forced_color_adjust.clone());
+        self.ruby_position = Some(parent_values.ruby_position.clone());
+        self.line_height = Some(parent_values.line_height.clone());
+        self.white_space = Some(parent_values.white_space.clone());
+        self.word_break = Some(parent_values.word_break.clone());
+        self.overflow_wrap = Some(parent_values.overflow_wrap.clone());
+        self.direction = Some(parent_values.direction.clone());
+        self.text_align = Some(parent_values.text_align.clone());
+        self.text_indent = Some(parent_values.text_indent.clone());
+        self.text_transform = Some(parent_values.text_transform.clone());
+        self.list_style_type = Some(parent_values.list_style_type.clone());
     }
 
-    // Assumes that the computed values have been initialized and inherited.
-    pub fn set_from(&mut self, cascaded_values: &CascadedStyle) {
+    /// Sets the values of the cascaded properties, where `parent` is the computed style of the parent that the
+    /// CSS-wide keywords inherit from. The invalid values are ignored.
+    /// Assumes that the computed values have been initialized and inherited.
+    pub fn set_from(&mut self, cascaded_values: &CascadedStyle, parent: Option<&ComputedStyle>) {
         let mut cascaded_values = cascaded_values.values.clone();
         // The higher priority styles are placed first in the values, so
         // it must be reversed to process according to the priority.
@@ -338,45 +1118,69 @@ impl SpecifiedStyle {
         cascaded_values.reverse();
         for (name, values) in &cascaded_values {
             match name.as_str() {
-                "background-color" => {
-                    if let Ok(v) = BackGroundColorProp::parse(values) {
-                        self.background_color = Some(v);
-                    }
-                }
+                "background-color" => Self::set_property(
+                    &mut self.background_color,
+                    values,
+                    parent.map(|p| &p.background_color),
+                    false,
+                ),
                 "color" => {
-                    if let Ok(v) = ColorProp::parse(values) {
-                        self.color = Some(v);
-                    }
+                    Self::set_property(&mut self.color, values, parent.map(|p| &p.color), true)
                 }
                 "display" => {
-                    if let Ok(v) = DisplayProp::parse(values) {
-                        self.display = Some(v);
-                    }
+                    Self::set_property(&mut self.display, values, parent.map(|p| &p.display), false)
                 }
-                "font-family" => {
-                    if let Ok(v) = FontFamilyProp::parse(values) {
-                        self.font_family = Some(v);
-                    }
+                "font-family" => Self::set_property(
+                    &mut self.font_family,
+                    values,
+                    parent.map(|p| &p.font_family),
+                    true,
+                ),
+                "font-size" => Self::set_property(
+                    &mut self.font_size,
+                    values,
+                    parent.map(|p| &p.font_size),
+                    true,
+                ),
+                "font-weight" => Self::set_property(
+                    &mut self.font_weight,
+                    values,
+                    parent.map(|p| &p.font_weight),
+                    true,
+                ),
+                "font-style" => Self::set_property(
+                    &mut self.font_style,
+                    values,
+                    parent.map(|p| &p.font_style),
+                    true,
+                ),
+                "font-stretch" => Self::set_property(
+                    &mut self.font_stretch,
+                    values,
+                    parent.map(|p| &p.font_stretch),
+                    true,
+                ),
+                "font" => self.set_font(values, parent),
+                "text-decoration" => Self::set_property(
+                    &mut self.text_decoration,
+                    values,
+                    parent.map(|p| &p.text_decoration),
+                    false,
+                ),
+                "text-decoration-line" => {
+                    self.set_text_decoration_longhand(values, parent, |d| &mut d.line)
                 }
-                "font-size" => {
-                    if let Ok(v) = FontSizeProp::parse(values) {
-                        self.font_size = Some(v);
-                    }
+                "text-decoration-style" => {
+                    self.set_text_decoration_longhand(values, parent, |d| &mut d.style)
                 }
-                "font-weight" => {
-                    if let Ok(v) = FontWeightProp::parse(values) {
-                        self.font_weight = Some(v);
-                    }
+                "text-decoration-color" => {
+                    self.set_text_decoration_longhand(values, parent, |d| &mut d.color)
                 }
-                "text-decoration" => {
-                    if let Ok(v) = TextDecorationProp::parse(values) {
-                        self.text_decoration = Some(v);
-                    }
+                "text-decoration-thickness" => {
+                    self.set_text_decoration_longhand(values, parent, |d| &mut d.thickness)
                 }
                 "margin" => {
-                    if let Ok(v) = MarginProp::parse(values) {
-                        self.margin = Some(v);
-                    }
+                    Self::set_property(&mut self.margin, values, parent.map(|p| &p.margin), false);
                     // Assume that the margin-block-start and margin-block-end values
                     // are the same as the margin-top and margin-bottom values.
                     // todo: Handle the direction of the text.
@@ -386,9 +1190,12 @@ impl SpecifiedStyle {
                         self.margin.as_ref().unwrap().bottom.clone();
                 }
                 "margin-block" => {
-                    if let Ok(v) = MarginBlockProp::parse(values) {
-                        self.margin_block = Some(v);
-                    }
+                    Self::set_property(
+                        &mut self.margin_block,
+                        values,
+                        parent.map(|p| &p.margin_block),
+                        false,
+                    );
                     // Assume that the margin-block-start and margin-block-end values
                     // are the same as the margin-top and margin-bottom values.
                     // todo: Handle the direction of the text.
@@ -398,43 +1205,337 @@ impl SpecifiedStyle {
                         self.margin_block.as_ref().unwrap().end.clone();
                 }
                 "border" => {
-                    if let Ok(v) = BorderProp::parse(values) {
-                        self.border = Some(v);
-                    }
+                    Self::set_property(&mut self.border, values, parent.map(|p| &p.border), false)
                 }
                 "padding" => {
-                    if let Ok(v) = PaddingProp::parse(values) {
-                        self.padding = Some(v);
-                    }
+                    Self::set_property(&mut self.padding, values, parent.map(|p| &p.padding), false)
                 }
                 "width" => {
-                    if let Ok(v) = WidthProp::parse(values) {
-                        self.width = Some(v);
-                    }
+                    Self::set_property(&mut self.width, values, parent.map(|p| &p.width), false)
                 }
                 "height" => {
-                    if let Ok(v) = HeightProp::parse(values) {
-                        self.height = Some(v);
-                    }
+                    Self::set_property(&mut self.height, values, parent.map(|p| &p.height), false)
                 }
-                "border-radius" => {
-                    if let Ok(v) = BorderRadiusProp::parse(values) {
-                        self.border_radius = Some(v);
-                    }
+                "box-sizing" => Self::set_property(
+                    &mut self.box_sizing,
+                    values,
+                    parent.map(|p| &p.box_sizing),
+                    false,
+                ),
+                "min-width" => Self::set_property(
+                    &mut self.min_width,
+                    values,
+                    parent.map(|p| &p.min_width),
+                    false,
+                ),
+                "max-width" => Self::set_property(
+                    &mut self.max_width,
+                    values,
+                    parent.map(|p| &p.max_width),
+                    false,
+                ),
+                "min-height" => Self::set_property(
+                    &mut self.min_height,
+                    values,
+                    parent.map(|p| &p.min_height),
+                    false,
+                ),
+                "max-height" => Self::set_property(
+                    &mut self.max_height,
+                    values,
+                    parent.map(|p| &p.max_height),
+                    false,
+                ),
+                "border-radius" => Self::set_property(
+                    &mut self.border_radius,
+                    values,
+                    parent.map(|p| &p.border_radius),
+                    false,
+                ),
+                "forced-color-adjust" => Self::set_property(
+                    &mut self.forced_color_adjust,
+                    values,
+                    parent.map(|p| &p.forced_color_adjust),
+                    true,
+                ),
+                "ruby-position" => Self::set_property(
+                    &mut self.ruby_position,
+                    values,
+                    parent.map(|p| &p.ruby_position),
+                    true,
+                ),
+                "line-height" => Self::set_property(
+                    &mut self.line_height,
+                    values,
+                    parent.map(|p| &p.line_height),
+                    true,
+                ),
+                "vertical-align" => Self::set_property(
+                    &mut self.vertical_align,
+                    values,
+                    parent.map(|p| &p.vertical_align),
+                    false,
+                ),
+                "white-space" => Self::set_property(
+                    &mut self.white_space,
+                    values,
+                    parent.map(|p| &p.white_space),
+                    true,
+                ),
+                "word-break" => Self::set_property(
+                    &mut self.word_break,
+                    values,
+                    parent.map(|p| &p.word_break),
+                    true,
+                ),
+                "overflow-wrap" | "word-wrap" => Self::set_property(
+                    &mut self.overflow_wrap,
+                    values,
+                    parent.map(|p| &p.overflow_wrap),
+                    true,
+                ),
+                "direction" => Self::set_property(
+                    &mut self.direction,
+                    values,
+                    parent.map(|p| &p.direction),
+                    true,
+                ),
+                "unicode-bidi" => Self::set_property(
+                    &mut self.unicode_bidi,
+                    values,
+                    parent.map(|p| &p.unicode_bidi),
+                    false,
+                ),
+                "text-align" => Self::set_property(
+                    &mut self.text_align,
+                    values,
+                    parent.map(|p| &p.text_align),
+                    true,
+                ),
+                "text-indent" => Self::set_property(
+                    &mut self.text_indent,
+                    values,
+                    parent.map(|p| &p.text_indent),
+                    true,
+                ),
+                "text-transform" => Self::set_property(
+                    &mut self.text_transform,
+                    values,
+                    parent.map(|p| &p.text_transform),
+                    true,
+                ),
+                "text-overflow" => Self::set_property(
+                    &mut self.text_overflow,
+                    values,
+                    parent.map(|p| &p.text_overflow),
+                    false,
+                ),
+                "content" => {
+                    Self::set_property(&mut self.content, values, parent.map(|p| &p.content), false)
                 }
+                "counter-reset" => Self::set_property(
+                    &mut self.counter_reset,
+                    values,
+                    parent.map(|p| &p.counter_reset),
+                    false,
+                ),
+                "counter-increment" => Self::set_property(
+                    &mut self.counter_increment,
+                    values,
+                    parent.map(|p| &p.counter_increment),
+                    false,
+                ),
+                "list-style-type" => Self::set_property(
+                    &mut self.list_style_type,
+                    values,
+                    parent.map(|p| &p.list_style_type),
+                    true,
+                ),
+                "position" => Self::set_property(
+                    &mut self.position,
+                    values,
+                    parent.map(|p| &p.position),
+                    false,
+                ),
+                "float" => {
+                    Self::set_property(&mut self.float, values, parent.map(|p| &p.float), false)
+                }
+                "inset" => {
+                    Self::set_property(&mut self.inset, values, parent.map(|p| &p.inset), false)
+                }
+                "top" => self.set_inset_side(values, parent, |inset| &mut inset.top),
+                "right" => self.set_inset_side(values, parent, |inset| &mut inset.right),
+                "bottom" => self.set_inset_side(values, parent, |inset| &mut inset.bottom),
+                "left" => self.set_inset_side(values, parent, |inset| &mut inset.left),
+                "z-index" => {
+                    Self::set_property(&mut self.z_index, values, parent.map(|p| &p.z_index), false)
+                }
+                "opacity" => {
+                    Self::set_property(&mut self.opacity, values, parent.map(|p| &p.opacity), false)
+                }
+                "overflow" => Self::set_property(
+                    &mut self.overflow,
+                    values,
+                    parent.map(|p| &p.overflow),
+                    false,
+                ),
+                "overflow-x" => self.set_overflow_axis(values, parent, |overflow| &mut overflow.x),
+                "overflow-y" => self.set_overflow_axis(values, parent, |overflow| &mut overflow.y),
                 _ => {}
             }
         }
     }
 
-    /// Converts the relative values to absolute values.
+    /// Sets the longhands of the `font` shorthand, to which a CSS-wide keyword in it applies too.
+    /// https://drafts.csswg.org/css-cascade-5/#shorthand
+    fn set_font(&mut self, values: &[ComponentValue], parent: Option<&ComputedStyle>) {
+        if CssWideKeyword::parse(values).is_some() {
+            Self::set_property(
+                &mut self.font_style,
+                values,
+                parent.map(|p| &p.font_style),
+                true,
+            );
+            Self::set_property(
+                &mut self.font_weight,
+                values,
+                parent.map(|p| &p.font_weight),
+                true,
+            );
+            Self::set_property(
+                &mut self.font_stretch,
+                values,
+                parent.map(|p| &p.font_stretch),
+                true,
+            );
+            Self::set_property(
+                &mut self.font_size,
+                values,
+                parent.map(|p| &p.font_size),
+                true,
+            );
+            Self::set_property(
+                &mut self.font_family,
+                values,
+                parent.map(|p| &p.font_family),
+                true,
+            );
+            Self::set_property(
+                &mut self.line_height,
+                values,
+                parent.map(|p| &p.line_height),
+                true,
+            );
+        } else if let Ok(font) = FontShorthand::parse(values) {
+            self.font_style = Some(font.style);
+            self.font_weight = Some(font.weight);
+            self.font_stretch = Some(font.stretch);
+            self.font_size = Some(font.size);
+            self.font_family = Some(font.family);
+            self.line_height = Some(font.line_height);
+        }
+    }
+
+    /// Sets one of the longhands of the `inset` shorthand, whose single value is parsed as the shorthand.
+    fn set_inset_side(
+        &mut self,
+        values: &[ComponentValue],
+        parent: Option<&ComputedStyle>,
+        side: fn(&mut InsetProp) -> &mut CssValue,
+    ) {
+        let mut inset = None;
+        Self::set_property(&mut inset, values, parent.map(|p| &p.inset), false);
+        if let (Some(mut inset), Some(current)) = (inset, self.inset.as_mut()) {
+            *side(current) = side(&mut inset).clone();
+        }
+    }
+
+    /// Sets a longhand of `text-decoration`, which takes only the values of its member in the shorthand.
+    fn set_text_decoration_longhand<T: Clone>(
+        &mut self,
+        values: &[ComponentValue],
+        parent: Option<&ComputedStyle>,
+        member: fn(&mut TextDecorationProp) -> &mut T,
+    ) {
+        let mut decoration = None;
+        Self::set_property(
+            &mut decoration,
+            values,
+            parent.map(|p| &p.text_decoration),
+            false,
+        );
+        let Some(mut decoration) = decoration else {
+            return;
+        };
+        let mut others = decoration.clone();
+        *member(&mut others) = member(&mut TextDecorationProp::default()).clone();
+        if others != TextDecorationProp::default() && CssWideKeyword::parse(values).is_none() {
+            return;
+        }
+        if let Some(current) = self.text_decoration.as_mut() {
+            *member(current) = member(&mut decoration).clone();
+        }
+    }
+
+    /// Sets a longhand of `overflow`, which takes a single keyword of the shorthand.
+    fn set_overflow_axis(
+        &mut self,
+        values: &[ComponentValue],
+        parent: Option<&ComputedStyle>,
+        axis: fn(&mut OverflowProp) -> &mut CssValue,
+    ) {
+        let mut overflow = None;
+        Self::set_property(&mut overflow, values, parent.map(|p| &p.overflow), false);
+        if let (Some(mut overflow), Some(current)) = (overflow, self.overflow.as_mut()) {
+            *axis(current) = axis(&mut overflow).clone();
+        }
+    }
+
+    /// Sets the property to the value parsed from `values`, or to the value of the CSS-wide keyword in them,
+    /// where `unset` and `revert` inherit the value of the parent only if the property is inherited.
+    /// https://drafts.csswg.org/css-cascade-5/#defaulting-keywords
+    fn set_property<T: CssProperty + Clone + Default>(
+        prop: &mut Option<T>,
+        values: &[ComponentValue],
+        parent_value: Option<&T>,
+        is_inherited: bool,
+    ) {
+        let inherited_value = || parent_value.cloned().unwrap_or_default();
+        let value = match CssWideKeyword::parse(values) {
+            Some(CssWideKeyword::Initial) => Some(T::default()),
+            Some(CssWideKeyword::Inherit) => Some(inherited_value()),
+            Some(CssWideKeyword::Unset | CssWideKeyword::Revert) if is_inherited => {
+                Some(inherited_value())
+            }
+            Some(CssWideKeyword::Unset | CssWideKeyword::Revert) => Some(T::default()),
+            None => T::parse(values).ok(),
+        };
+        if value.is_some() {
+            *prop = value;
+        }
+    }
+
+    /// Converts the relative values to absolute values. The lengths in `font-size` are relative to the font
+    /// size in `context`, which is the one of the parent, and the other lengths are relative to the computed
+    /// font size of the element.
     /// https://www.w3.org/TR/css-cascade-3/#computed
-    pub fn apply_computing(&self) -> ComputedStyle {
+    #[tracing::instrument(skip_all)]
+    pub fn apply_computing(&self, context: &LengthContext) -> ComputedStyle {
         let mut v = self.clone();
 
-        Self::compute_earlier(&mut v, self);
+        Self::compute_earlier(&mut v, self, context);
+        let font_size = v
+            .font_size
+            .as_ref()
+            .and_then(|s| s.to_px().ok())
+            .unwrap_or(font_size::MEDIUM);
+        let context = LengthContext {
+            font_size,
+            root_font_size: Some(context.root_font_size.unwrap_or(font_size)),
+            ..*context
+        };
         let earlier_style = v.clone();
-        Self::compute_later(&mut v, &earlier_style);
+        Self::compute_later(&mut v, &earlier_style, &context);
 
         ComputedStyle {
             background_color: v.background_color.unwrap(),
@@ -443,6 +1544,8 @@ impl SpecifiedStyle {
             font_family: v.font_family.unwrap(),
             font_size: v.font_size.unwrap(),
             font_weight: v.font_weight.unwrap(),
+            font_style: v.font_style.unwrap(),
+            font_stretch: v.font_stretch.unwrap(),
             text_decoration: v.text_decoration.unwrap(),
             margin: v.margin.unwrap(),
             margin_block: v.margin_block.unwrap(),
@@ -450,38 +1553,111 @@ impl SpecifiedStyle {
             padding: v.padding.unwrap(),
             width: v.width.unwrap(),
             height: v.height.unwrap(),
+            box_sizing: v.box_sizing.unwrap(),
+            min_width: v.min_width.unwrap(),
+            max_width: v.max_width.unwrap(),
+            min_height: v.min_height.unwrap(),
+            max_height: v.max_height.unwrap(),
             border_radius: v.border_radius.unwrap(),
+            forced_color_adjust: v.forced_color_adjust.unwrap(),
+            ruby_position: v.ruby_position.unwrap(),
+            line_height: v.line_height.unwrap(),
+            vertical_align: v.vertical_align.unwrap(),
+            white_space: v.white_space.unwrap(),
+            word_break: v.word_break.unwrap(),
+            overflow_wrap: v.overflow_wrap.unwrap(),
+            direction: v.direction.unwrap(),
+            unicode_bidi: v.unicode_bidi.unwrap(),
+            text_align: v.text_align.unwrap(),
+            text_indent: v.text_indent.unwrap(),
+            text_transform: v.text_transform.unwrap(),
+            text_overflow: v.text_overflow.unwrap(),
+            content: v.content.unwrap(),
+            counter_reset: v.counter_reset.unwrap(),
+            counter_increment: v.counter_increment.unwrap(),
+            list_style_type: v.list_style_type.unwrap(),
+            position: v.position.unwrap(),
+            float: v.float.unwrap(),
+            inset: v.inset.unwrap(),
+            z_index: v.z_index.unwrap(),
+            opacity: v.opacity.unwrap(),
+            overflow: v.overflow.unwrap(),
         }
     }
 
     /// Computes the properties whose values are used to compute other properties.
-    fn compute_earlier(v: &mut Self, initialized_style: &Self) {
-        Self::compute_property(&mut v.color, Some(initialized_style));
-        Self::compute_property(&mut v.font_size, Some(initialized_style));
-        Self::compute_property(&mut v.display, None);
+    fn compute_earlier(v: &mut Self, initialized_style: &Self, context: &LengthContext) {
+        Self::compute_property(&mut v.color, Some(initialized_style), context);
+        Self::compute_property(&mut v.font_size, Some(initialized_style), context);
+        Self::compute_property(&mut v.display, None, context);
+        Self::compute_property(&mut v.position, None, context);
+        Self::compute_property(&mut v.float, None, context);
+        // The absolutely positioned boxes are block-level and don't float.
+        // https://drafts.csswg.org/css-position/#abspos-layout
+        if v.position.as_ref().unwrap().is_absolutely_positioned() {
+            v.display.as_mut().unwrap().blockify();
+            v.float = Some(FloatProp::default());
+        }
+        // The floats are block-level.
+        // https://www.w3.org/TR/CSS22/visuren.html#dis-pos-flo
+        if v.float.as_ref().unwrap().is_floating() {
+            v.display.as_mut().unwrap().blockify();
+        }
     }
 
     /// Computes the properties that require some computed values.
-    fn compute_later(v: &mut Self, earlier_style: &Self) {
-        Self::compute_property(&mut v.background_color, Some(earlier_style));
-        Self::compute_property(&mut v.font_family, Some(earlier_style));
-        Self::compute_property(&mut v.font_weight, Some(earlier_style));
-        Self::compute_property(&mut v.text_decoration, Some(earlier_style));
-        Self::compute_property(&mut v.margin, Some(earlier_style));
-        Self::compute_property(&mut v.margin_block, Some(earlier_style));
-        Self::compute_property(&mut v.border, Some(earlier_style));
-        Self::compute_property(&mut v.padding, Some(earlier_style));
-        Self::compute_property(&mut v.width, Some(earlier_style));
-        Self::compute_property(&mut v.height, Some(earlier_style));
-        Self::compute_property(&mut v.border_radius, Some(earlier_style));
-    }
-
-    fn compute_property(prop: &mut Option<impl CssProperty>, current_style: Option<&Self>) {
+    fn compute_later(v: &mut Self, earlier_style: &Self, context: &LengthContext) {
+        Self::compute_property(&mut v.background_color, Some(earlier_style), context);
+        Self::compute_property(&mut v.font_family, Some(earlier_style), context);
+        Self::compute_property(&mut v.font_weight, Some(earlier_style), context);
+        Self::compute_property(&mut v.font_style, Some(earlier_style), context);
+        Self::compute_property(&mut v.font_stretch, Some(earlier_style), context);
+        Self::compute_property(&mut v.text_decoration, Some(earlier_style), context);
+        Self::compute_property(&mut v.margin, Some(earlier_style), context);
+        Self::compute_property(&mut v.margin_block, Some(earlier_style), context);
+        Self::compute_property(&mut v.border, Some(earlier_style), context);
+        Self::compute_property(&mut v.padding, Some(earlier_style), context);
+        Self::compute_property(&mut v.width, Some(earlier_style), context);
+        Self::compute_property(&mut v.height, Some(earlier_style), context);
+        Self::compute_property(&mut v.box_sizing, Some(earlier_style), context);
+        Self::compute_property(&mut v.min_width, Some(earlier_style), context);
+        Self::compute_property(&mut v.max_width, Some(earlier_style), context);
+        Self::compute_property(&mut v.min_height, Some(earlier_style), context);
+        Self::compute_property(&mut v.max_height, Some(earlier_style), context);
+        Self::compute_property(&mut v.border_radius, Some(earlier_style), context);
+        Self::compute_property(&mut v.forced_color_adjust, Some(earlier_style), context);
+        Self::compute_property(&mut v.ruby_position, Some(earlier_style), context);
+        Self::compute_property(&mut v.line_height, Some(earlier_style), context);
+        Self::compute_property(&mut v.vertical_align, Some(earlier_style), context);
+        Self::compute_property(&mut v.white_space, Some(earlier_style), context);
+        Self::compute_property(&mut v.word_break, Some(earlier_style), context);
+        Self::compute_property(&mut v.overflow_wrap, Some(earlier_style), context);
+        Self::compute_property(&mut v.direction, Some(earlier_style), context);
+        Self::compute_property(&mut v.unicode_bidi, Some(earlier_style), context);
+        Self::compute_property(&mut v.text_align, Some(earlier_style), context);
+        Self::compute_property(&mut v.text_indent, Some(earlier_style), context);
+        Self::compute_property(&mut v.text_transform, Some(earlier_style), context);
+        Self::compute_property(&mut v.text_overflow, Some(earlier_style), context);
+        Self::compute_property(&mut v.content, Some(earlier_style), context);
+        Self::compute_property(&mut v.counter_reset, Some(earlier_style), context);
+        Self::compute_property(&mut v.counter_increment, Some(earlier_style), context);
+        Self::compute_property(&mut v.list_style_type, Some(earlier_style), context);
+        Self::compute_property(&mut v.inset, Some(earlier_style), context);
+        Self::compute_property(&mut v.z_index, Some(earlier_style), context);
+        Self::compute_property(&mut v.opacity, Some(earlier_style), context);
+        Self::compute_property(&mut v.overflow, Some(earlier_style), context);
+    }
+
+    fn compute_property(
+        prop: &mut Option<impl CssProperty>,
+        current_style: Option<&Self>,
+        context: &LengthContext,
+    ) {
         if let Err(e) = prop
             .as_mut()
             .context(anyhow!("Uninitialized property detected while computing."))
             .unwrap()
-            .compute(current_style)
+            .compute(current_style, context)
         {
             eprintln!("{e}");
         }
@@ -497,6 +1673,8 @@ pub struct ComputedStyle {
     pub font_family: FontFamilyProp,
     pub font_size: FontSizeProp,
     pub font_weight: FontWeightProp,
+    pub font_style: FontStyleProp,
+    pub font_stretch: FontStretchProp,
     pub text_decoration: TextDecorationProp,
     pub margin: MarginProp,
     pub margin_block: MarginBlockProp,
@@ -504,26 +1682,417 @@ pub struct ComputedStyle {
     pub padding: PaddingProp,
     pub width: WidthProp,
     pub height: HeightProp,
+    pub box_sizing: BoxSizingProp,
+    pub min_width: MinSizeProp,
+    pub max_width: MaxSizeProp,
+    pub min_height: MinSizeProp,
+    pub max_height: MaxSizeProp,
     pub border_radius: BorderRadiusProp,
+    pub forced_color_adjust: ForcedColorAdjustProp,
+    pub ruby_position: RubyPositionProp,
+    pub line_height: LineHeightProp,
+    pub vertical_align: VerticalAlignProp,
+    pub white_space: WhiteSpaceProp,
+    pub word_break: WordBreakProp,
+    pub overflow_wrap: OverflowWrapProp,
+    pub direction: DirectionProp,
+    pub unicode_bidi: UnicodeBidiProp,
+    pub text_align: TextAlignProp,
+    pub text_indent: TextIndentProp,
+    pub text_transform: TextTransformProp,
+    pub text_overflow: TextOverflowProp,
+    pub content: ContentProp,
+    pub counter_reset: CounterResetProp,
+    pub counter_increment: CounterIncrementProp,
+    pub list_style_type: ListStyleTypeProp,
+    pub position: PositionProp,
+    pub float: FloatProp,
+    pub inset: InsetProp,
+    pub z_index: ZIndexProp,
+    pub opacity: OpacityProp,
+    pub overflow: OverflowProp,
+}
+
+impl ComputedStyle {
+    /// Returns the names and the serialized values of the properties, which are shown in the dumps and
+    /// returned to the DevTools clients.
+    /// https://drafts.csswg.org/cssom/#dom-window-getcomputedstyle
+    pub fn get_declarations(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("background-color", self.background_color.to_string()),
+            ("color", self.color.to_string()),
+            ("display", self.display.to_string()),
+            ("font-family", self.font_family.to_string()),
+            ("font-size", self.font_size.to_string()),
+            ("font-weight", self.font_weight.to_string()),
+            ("font-style", self.font_style.to_string()),
+            ("font-stretch", self.font_stretch.to_string()),
+            ("text-decoration", self.text_decoration.to_string()),
+            ("margin", self.margin.to_string()),
+            ("margin-block", self.margin_block.to_string()),
+            ("border", self.border.to_string()),
+            ("padding", self.padding.to_string()),
+            ("width", self.width.to_string()),
+            ("height", self.height.to_string()),
+            ("box-sizing", self.box_sizing.to_string()),
+            ("min-width", self.min_width.to_string()),
+            ("max-width", self.max_width.to_string()),
+            ("min-height", self.min_height.to_string()),
+            ("max-height", self.max_height.to_string()),
+            ("border-radius", self.border_radius.to_string()),
+            ("forced-color-adjust", self.forced_color_adjust.to_string()),
+            ("ruby-position", self.ruby_position.to_string()),
+            ("line-height", self.line_height.to_string()),
+            ("vertical-align", self.vertical_align.to_string()),
+            ("white-space", self.white_space.to_string()),
+            ("word-break", self.word_break.to_string()),
+            ("overflow-wrap", self.overflow_wrap.to_string()),
+            ("direction", self.direction.to_string()),
+            ("unicode-bidi", self.unicode_bidi.to_string()),
+            ("text-align", self.text_align.to_string()),
+            ("text-indent", self.text_indent.to_string()),
+            ("text-transform", self.text_transform.to_string()),
+            ("text-overflow", self.text_overflow.to_string()),
+            ("content", self.content.to_string()),
+            ("counter-reset", self.counter_reset.to_string()),
+            ("counter-increment", self.counter_increment.to_string()),
+            ("list-style-type", self.list_style_type.to_string()),
+            ("position", self.position.to_string()),
+            ("float", self.float.to_string()),
+            ("inset", self.inset.to_string()),
+            ("z-index", self.z_index.to_string()),
+            ("opacity", self.opacity.to_string()),
+            ("overflow", self.overflow.to_string()),
+        ]
+    }
 }
 
 impl fmt::Display for ComputedStyle {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut style_str = String::new();
-        style_str.push_str(&format!("background-color: {}; ", self.background_color));
-        style_str.push_str(&format!("color: {}; ", self.color));
-        style_str.push_str(&format!("display: {}; ", self.display));
-        style_str.push_str(&format!("font-family: {}; ", self.font_family));
-        style_str.push_str(&format!("font-size: {}; ", self.font_size));
-        style_str.push_str(&format!("font-weight: {}; ", self.font_weight));
-        style_str.push_str(&format!("text-decoration: {}; ", self.text_decoration));
-        style_str.push_str(&format!("margin: {}; ", self.margin));
-        style_str.push_str(&format!("margin-block: {}; ", self.margin_block));
-        style_str.push_str(&format!("border: {}; ", self.border));
-        style_str.push_str(&format!("padding: {}; ", self.padding));
-        style_str.push_str(&format!("width: {}; ", self.width));
-        style_str.push_str(&format!("height: {}; ", self.height));
-        style_str.push_str(&format!("border-radius: {}", self.border_radius));
-        write!(f, "{}", style_str)
+        let declarations = self
+            .get_declarations()
+            .into_iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect::<Vec<_>>();
+        write!(f, "{}", declarations.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::selector::{SelectorParser, Specificity};
+    use crate::renderer::html::mutation::invalidate_style;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    #[test]
+    fn build_flat_tree_with_slots() {
+        let html = "<html><head><style>p { color: blue; }</style></head><body><div><template shadowrootmode=\"open\"><style>p { color: red; }</style><p><slot name=\"x\"></slot></p><slot>fallback</slot><slot name=\"y\">fallback</slot></template><p slot=\"x\">light</p>text</div></body></html>";
+        let (root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let style_sheets = std::iter::once(get_ua_style_sheet().unwrap())
+            .chain(style_sheets)
+            .collect::<Vec<_>>();
+        let render_tree = DocumentTree::build(root)
+            .unwrap()
+            .to_render_tree(style_sheets, &MediaEnvironment::default())
+            .unwrap();
+
+        let mut nodes = Vec::new();
+        let mut stack = vec![Rc::clone(&render_tree.root)];
+        while let Some(node) = stack.pop() {
+            nodes.push((
+                node.borrow().dom_node.borrow().node_type.to_string(),
+                node.borrow().style.color.to_string(),
+            ));
+            stack.extend(node.borrow().children.iter().rev().cloned());
+        }
+
+        // The shadow tree is styled only with its own style sheet, while the slotted nodes are styled with the document's.
+        // The default slot is assigned the text, and the slot without assigned nodes shows its fallback content.
+        let expected = [
+            ("Document", "black"),
+            ("Elem( tag: <html> )", "black"),
+            ("Elem( tag: <body> )", "black"),
+            ("Elem( tag: <div> )", "black"),
+            ("Elem( tag: <p> )", "red"),
+            ("Elem( tag: <p>, attr: [\"slot\"=\"x\"] )", "blue"),
+            ("Text( light )", "blue"),
+            ("Text( text )", "black"),
+            ("Text( fallback )", "black"),
+        ]
+        .map(|(node, color)| (node.to_string(), color.to_string()));
+        assert_eq!(nodes, expected);
+    }
+
+    #[test]
+    fn cascade_by_origin_and_importance() {
+        let parse = |css: &str, origin| {
+            let mut style_sheet = CssParser::new(&CssTokenizer::new(css).tokenize().unwrap())
+                .parse()
+                .unwrap();
+            style_sheet.origin = origin;
+            style_sheet
+        };
+        let style_sheets = [
+            parse(
+                "p { color: red !important; display: block; font-size: 10px; }",
+                CascadeOrigin::UserAgent,
+            ),
+            parse(
+                "p { display: inline !important; font-size: 20px; }",
+                CascadeOrigin::User,
+            ),
+            parse(
+                "#a { color: blue !important; display: none ! IMPORTANT; font-size: 40px; width: 2px; }\
+                p { width: 1px !important; font-size: 30px; margin: 1px; margin: 2px; }",
+                CascadeOrigin::Author,
+            ),
+        ];
+        let mut declared_values = DeclaredStyle::new();
+        for (i, style_sheet) in style_sheets.iter().enumerate() {
+            for (j, rule) in style_sheet.get_all_style_rules().into_iter().enumerate() {
+                declared_values.add(
+                    rule.selectors[0].clone(),
+                    &rule.declarations,
+                    style_sheet.origin,
+                    (i, j),
+                );
+            }
+        }
+        let cascaded_values = declared_values.apply_cascading();
+        let get_value = |name: &str| {
+            cascaded_values.values[name]
+                .iter()
+                .map(|v| format!("{:?}", v))
+                .collect::<String>()
+        };
+        let expected_value = |css: &str| {
+            CssParser::new(&CssTokenizer::new(css).tokenize().unwrap())
+                .parse_list_of_component_values()
+                .iter()
+                .map(|v| format!("{:?}", v))
+                .collect::<String>()
+        };
+
+        // The important declarations of the user agent and the user win over the ones of the author, while
+        // the normal declarations of the author win over the ones of the user and the user agent.
+        assert_eq!(get_value("color"), expected_value("red"));
+        assert_eq!(get_value("display"), expected_value("inline"));
+        assert_eq!(get_value("font-size"), expected_value("40px"));
+        // The importance wins over the specificity, and the later declaration wins in the same rule.
+        assert_eq!(get_value("width"), expected_value("1px"));
+        assert_eq!(get_value("margin"), expected_value("2px"));
+    }
+
+    #[test]
+    fn apply_css_wide_keywords() {
+        let html = "<html><head><style>body { color: red; margin: 3px; } div { color: blue; margin: 5px; font-weight: bold; }\
+            #a { color: inherit; margin: inherit; } #b { color: initial; font-weight: unset; margin: unset; } #c { font-weight: normal; color: revert; }</style></head>\
+            <body><div id=\"a\">a</div><div id=\"b\">b</div><p>p</p><h1 id=\"c\" style=\"font-weight: revert\">c</h1></body></html>";
+        let (root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let style_sheets = std::iter::once(get_ua_style_sheet().unwrap())
+            .chain(style_sheets)
+            .collect::<Vec<_>>();
+        let styles = DocumentTree::build(root)
+            .unwrap()
+            .to_render_tree(style_sheets, &MediaEnvironment::default())
+            .unwrap()
+            .get_element_styles()
+            .into_iter()
+            .map(|(_, style)| {
+                style
+                    .get_declarations()
+                    .into_iter()
+                    .collect::<HashMap<_, _>>()
+            })
+            .collect::<Vec<_>>();
+        let get = |i: usize, name: &str| styles[i][name].clone();
+
+        // html, body, div#a, div#b, p, h1#c
+        assert_eq!(styles.len(), 6);
+        // `inherit` takes the computed value of the parent even for the properties which are not inherited.
+        assert_eq!(get(2, "color"), "red");
+        assert_eq!(get(2, "margin"), get(1, "margin"));
+        // `initial` and `unset` of the properties which are not inherited reset them to the initial values.
+        assert_eq!(get(3, "color"), "black");
+        assert_eq!(get(3, "margin"), get(0, "margin"));
+        assert_eq!(get(3, "font-weight"), get(1, "font-weight"));
+        // `revert` rolls back to the user agent style sheet, and acts as `unset` if it sets nothing.
+        assert_eq!(get(5, "font-weight"), get(2, "font-weight"));
+        assert_ne!(get(5, "font-weight"), get(1, "font-weight"));
+        assert_eq!(get(5, "color"), "red");
+    }
+
+    #[test]
+    fn generate_pseudo_elements() {
+        let html = "<html><head><style>ol { counter-reset: item; } li { counter-increment: item; }\
+            li::before { content: counter(item, upper-roman) \". \"; color: red; } li:first-child::before { content: none; }\
+            a:after { content: \" (\" attr(href) \")\" } img::before { content: \"x\"; }</style></head>\
+            <body><ol><li>a</li><li>b</li><li>c</li></ol><ol><li>d</li><li>e</li></ol><a href=\"/x\" style=\"content: 'y'\">link</a><img></body></html>";
+        let (root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let style_sheets = std::iter::once(get_ua_style_sheet().unwrap())
+            .chain(style_sheets)
+            .collect::<Vec<_>>();
+        let render_tree = DocumentTree::build(root)
+            .unwrap()
+            .to_render_tree(style_sheets, &MediaEnvironment::default())
+            .unwrap();
+
+        let mut generated = Vec::new();
+        let mut stack = vec![Rc::clone(&render_tree.root)];
+        while let Some(node) = stack.pop() {
+            if let Some(name) = &node.borrow().pseudo_element {
+                let text = node.borrow().children[0]
+                    .borrow()
+                    .dom_node
+                    .borrow()
+                    .get_inside_text()
+                    .unwrap()
+                    .to_string();
+                generated.push((name.clone(), text, node.borrow().style.color.to_string()));
+            }
+            stack.extend(node.borrow().children.iter().rev().cloned());
+        }
+
+        // The counters are scoped to each list, and the replaced elements have no pseudo-elements.
+        // The list items have the markers of the implicit `list-item` counter before `::before`.
+        let expected = [
+            ("marker", "1. ", "black"),
+            ("marker", "2. ", "black"),
+            ("before", "II. ", "red"),
+            ("marker", "3. ", "black"),
+            ("before", "III. ", "red"),
+            ("marker", "1. ", "black"),
+            ("marker", "2. ", "black"),
+            ("before", "II. ", "red"),
+            ("after", " (/x)", "rgb(0, 0, 238)"),
+        ]
+        .map(|(name, text, color)| (name.to_string(), text.to_string(), color.to_string()));
+        assert_eq!(generated, expected);
+        assert_eq!(
+            SelectorParser::parse_str("li:first-child::before, a:after")
+                .unwrap()
+                .iter()
+                .map(|selector| (selector.to_string(), selector.calc_specificity()))
+                .collect::<Vec<_>>(),
+            [
+                ("li:first-child::before".to_string(), Specificity(0, 1, 2)),
+                ("a::after".to_string(), Specificity(0, 0, 2))
+            ]
+        );
+        assert!(SelectorParser::parse_str("p::before span").is_err());
+    }
+
+    #[test]
+    fn apply_style_attribute() {
+        use crate::renderer::style::property::{AbsoluteLengthUnit, CssValue, LengthUnit};
+
+        let html = "<html><head><style>#a { color: red; font-weight: bold !important; } p { margin: 1px; }</style></head>\
+            <body><p id=\"a\" style=\"color: green; font-weight: normal; margin: 2px !important\">a</p></body></html>";
+        let (root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let style_sheets = std::iter::once(get_ua_style_sheet().unwrap())
+            .chain(style_sheets)
+            .collect::<Vec<_>>();
+        let render_tree = DocumentTree::build(root)
+            .unwrap()
+            .to_render_tree(style_sheets, &MediaEnvironment::default())
+            .unwrap();
+        let body = Rc::clone(&render_tree.root.borrow().children[0].borrow().children[0]);
+        let p = Rc::clone(&body.borrow().children[0]);
+        let style = &p.borrow().style;
+
+        // The `style` attribute wins over the ID selector, but not over its important declarations.
+        assert_eq!(style.color.to_string(), "green");
+        assert_eq!(style.font_weight.to_string(), "bold");
+        assert_eq!(
+            style.margin.top,
+            CssValue::Length(2.0, LengthUnit::AbsoluteLengthUnit(AbsoluteLengthUnit::Px))
+        );
+    }
+
+    #[test]
+    fn compute_relative_lengths() {
+        use crate::renderer::style::property::CssValue;
+
+        let html = "<html style=\"font-size: 20px; width: 2rem\"><body><div style=\"font-size: 2em; width: 10rem; height: 50vh; margin: 1em calc(10vmin - 1ex)\">\
+            <p style=\"font-size: 50%; width: 25vmax; padding: 2ch\">a</p></div></body></html>";
+        let (root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let style_sheets = std::iter::once(get_ua_style_sheet().unwrap())
+            .chain(style_sheets)
+            .collect::<Vec<_>>();
+        let media = MediaEnvironment {
+            viewport_width: 800,
+            viewport_height: 600,
+            ..Default::default()
+        };
+        let render_tree = DocumentTree::build(root)
+            .unwrap()
+            .to_render_tree(style_sheets, &media)
+            .unwrap();
+        let html = Rc::clone(&render_tree.root.borrow().children[0]);
+        let body = Rc::clone(&html.borrow().children[0]);
+        let div = Rc::clone(&body.borrow().children[0]);
+        let p = Rc::clone(&div.borrow().children[0]);
+        let px = |value: &CssValue| value.to_px().unwrap();
+
+        // `rem` in the root element is relative to its own font size except in `font-size`.
+        assert_eq!(px(&html.borrow().style.width.size), 40.0);
+        let div = &div.borrow().style;
+        assert_eq!(div.font_size.to_px().unwrap(), 40.0);
+        assert_eq!(px(&div.width.size), 200.0);
+        assert_eq!(px(&div.height.size), 300.0);
+        assert_eq!(px(&div.margin.top), 40.0);
+        // `ex` is assumed to be 0.5em.
+        assert_eq!(px(&div.margin.right), 40.0);
+        let p = &p.borrow().style;
+        assert_eq!(p.font_size.to_px().unwrap(), 20.0);
+        assert_eq!(px(&p.width.size), 200.0);
+        assert_eq!(px(&p.padding.left), 20.0);
+    }
+
+    #[test]
+    fn restyle_invalidated_subtrees() {
+        let html = "<html><head><style>div:hover { color: red; } div:hover::before { content: \"x\"; }</style></head><body><div id=a><p>a</p></div><div id=b><p>b</p></div></body></html>";
+        let (root, style_sheets) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let style_sheets = std::iter::once(get_ua_style_sheet().unwrap())
+            .chain(style_sheets)
+            .collect::<Vec<_>>();
+        let tree = DocumentTree::build(root).unwrap();
+        let media = MediaEnvironment::default();
+        let mut styles = StyleCache::default();
+        tree.restyle_render_tree(style_sheets.clone(), &media, &mut styles)
+            .unwrap();
+
+        // Both elements are hovered, but only the style of `a` is invalidated.
+        let a = tree.get_element_by_id("a").unwrap();
+        let b = tree.get_element_by_id("b").unwrap();
+        a.borrow_mut().state.hover = true;
+        b.borrow_mut().state.hover = true;
+        invalidate_style(&a);
+        let render_tree = tree
+            .restyle_render_tree(style_sheets, &media, &mut styles)
+            .unwrap();
+
+        let colors = render_tree
+            .get_element_styles()
+            .into_iter()
+            .filter(|(node, _)| {
+                matches!(&node.borrow().node_type, NodeType::Element(elm) if ["div", "p"].contains(&elm.tag_name.as_str()))
+            })
+            .map(|(_, style)| style.color.to_string())
+            .collect::<Vec<_>>();
+        // The descendant of `a` inherits its new color, while the sibling `b` and its subtree keep the old ones.
+        assert_eq!(colors, ["red", "red", "black", "black"]);
+        let pseudo_elements = Rc::clone(&render_tree.root)
+            .borrow()
+            .children
+            .iter()
+            .flat_map(|html| html.borrow().children.clone())
+            .flat_map(|body| body.borrow().children.clone())
+            .map(|div| div.borrow().children[0].borrow().pseudo_element.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(pseudo_elements, [Some("before".to_string()), None]);
+        assert!(!a.borrow().dirty.style);
     }
 }
@@ -0,0 +1,630 @@
+pub mod path;
+pub mod transform;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use anyhow::Result;
+
+use crate::renderer::css::parser::CssParser;
+use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::html::dom::{DomNode, Element, NodeType};
+use crate::renderer::layout::geometry::Rect;
+use crate::renderer::style::property::color::ColorProp;
+use crate::renderer::style::property::{parse_length_px, CssProperty, LengthContext};
+use crate::renderer::RenderObject;
+use path::{arc_to_curves, ellipse_to_path, parse_number_list, parse_path_data, PathSegment};
+use transform::Transform;
+
+/// 0.0 <= (r, g, b, a) <= 1.0
+pub type Rgba = (f64, f64, f64, f64);
+
+/// The maximum depth of the nested `use` elements, which stops the references to their own ancestors.
+const MAX_USE_DEPTH: usize = 16;
+
+/// The properties of an SVG element which affect how its shapes are painted.
+/// https://www.w3.org/TR/SVG2/painting.html
+#[derive(Debug, Clone, Copy)]
+struct SvgStyle {
+    /// `None` if the shapes are not filled.
+    fill: Option<Rgba>,
+    /// `None` if the shapes are not stroked.
+    stroke: Option<Rgba>,
+    stroke_width: f64,
+    fill_opacity: f64,
+    stroke_opacity: f64,
+    /// The opacity of the element multiplied by the ones of its ancestors.
+    /// todo: Composite the groups as a whole, so that their overlapping shapes don't show through.
+    opacity: f64,
+    /// Whether the fill rule is `evenodd` instead of `nonzero`.
+    even_odd: bool,
+    /// The value of `currentColor`.
+    color: Rgba,
+    display_none: bool,
+}
+
+impl SvgStyle {
+    /// Returns the initial style, where the shapes are filled with black and not stroked.
+    fn new(color: Rgba) -> Self {
+        Self {
+            fill: Some((0.0, 0.0, 0.0, 1.0)),
+            stroke: None,
+            stroke_width: 1.0,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+            opacity: 1.0,
+            even_odd: false,
+            color,
+            display_none: false,
+        }
+    }
+
+    /// Returns the style of the element, which inherits this style. The declarations in the `style` attribute
+    /// take precedence over the presentation attributes, and the invalid values are ignored.
+    /// https://www.w3.org/TR/SVG2/styling.html#PresentationAttributes
+    /// todo: Apply the style sheets of the document to the SVG elements too.
+    fn cascade(&self, elm: &Element) -> Self {
+        let mut style = *self;
+        let declarations = elm
+            .attributes
+            .iter()
+            .filter(|(name, _)| name != "style")
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.trim().to_string()))
+            .chain(
+                get_attr(elm, "style")
+                    .unwrap_or_default()
+                    .split(';')
+                    .filter_map(|declaration| declaration.split_once(':'))
+                    .map(|(name, value)| {
+                        (name.trim().to_ascii_lowercase(), value.trim().to_string())
+                    }),
+            )
+            .collect::<Vec<_>>();
+
+        // The color is applied first, since `currentColor` in the other properties refers to it.
+        for (_, value) in declarations.iter().filter(|(name, _)| name == "color") {
+            if let Ok(color) = parse_color(value) {
+                style.color = color;
+            }
+        }
+        for (name, value) in &declarations {
+            let number = || value.parse::<f64>().ok().filter(|v| v.is_finite());
+            match name.as_str() {
+                "fill" => style.fill = parse_paint(value, style.color).unwrap_or(style.fill),
+                "stroke" => style.stroke = parse_paint(value, style.color).unwrap_or(style.stroke),
+                "stroke-width" => {
+                    if let Some(width) = parse_length(value).filter(|v| *v >= 0.0) {
+                        style.stroke_width = width;
+                    }
+                }
+                "fill-opacity" => style.fill_opacity = number().unwrap_or(1.0).clamp(0.0, 1.0),
+                "stroke-opacity" => style.stroke_opacity = number().unwrap_or(1.0).clamp(0.0, 1.0),
+                "opacity" => style.opacity = self.opacity * number().unwrap_or(1.0).clamp(0.0, 1.0),
+                "fill-rule" => match value.as_str() {
+                    "evenodd" => style.even_odd = true,
+                    "nonzero" => style.even_odd = false,
+                    _ => {}
+                },
+                "display" => style.display_none = value == "none",
+                _ => {}
+            }
+        }
+        style
+    }
+}
+
+/// The state inherited by the descendants of an SVG element while it is rendered.
+#[derive(Debug, Clone, Copy)]
+struct RenderContext {
+    /// The transform from the user space of the element to the canvas.
+    transform: Transform,
+    style: SvgStyle,
+    /// The area of the canvas which the shapes are clipped to, which is the nearest viewport.
+    clip: Rect,
+    /// The size of the nearest viewport in the user space, which is the default size of the nested viewports.
+    viewport_size: (f64, f64),
+    use_depth: usize,
+}
+
+/// Renders the `svg` element into the paths to paint in the area of the canvas. `color` is the computed
+/// `color` of the element, which is the value of `currentColor`.
+/// https://www.w3.org/TR/SVG2/render.html
+pub fn render_svg(svg: &Rc<RefCell<DomNode>>, viewport: Rect, color: Rgba) -> Vec<RenderObject> {
+    let mut objects = Vec::new();
+    let NodeType::Element(elm) = &svg.borrow().node_type else {
+        return objects;
+    };
+    let ctx = RenderContext {
+        transform: Transform::IDENTITY,
+        style: SvgStyle::new(color).cascade(elm),
+        clip: viewport,
+        viewport_size: (viewport.width, viewport.height),
+        use_depth: 0,
+    };
+    if !ctx.style.display_none {
+        render_viewport(svg, elm, viewport, &ctx, &mut objects);
+    }
+    objects
+}
+
+/// Renders the children of an `svg` or a `symbol` element, whose viewBox is fitted into the viewport, which is
+/// in the user space of `ctx`.
+/// https://www.w3.org/TR/SVG2/coords.html#EstablishingANewSVGViewport
+fn render_viewport(
+    node: &Rc<RefCell<DomNode>>,
+    elm: &Element,
+    viewport: Rect,
+    ctx: &RenderContext,
+    objects: &mut Vec<RenderObject>,
+) {
+    if viewport.width <= 0.0 || viewport.height <= 0.0 {
+        return;
+    }
+    let (x1, y1) = ctx.transform.apply(viewport.x, viewport.y);
+    let (x2, y2) = ctx
+        .transform
+        .apply(viewport.x + viewport.width, viewport.y + viewport.height);
+    // The viewport is clipped to its bounding box on the canvas, since it is rotated rarely.
+    let Some(clip) =
+        Rect::new(x1.min(x2), y1.min(y2), (x2 - x1).abs(), (y2 - y1).abs()).intersect(&ctx.clip)
+    else {
+        return;
+    };
+    let view_box = get_view_box(elm);
+    let ctx = RenderContext {
+        transform: ctx
+            .transform
+            .multiply(&Transform::translate(viewport.x, viewport.y))
+            .multiply(&get_view_box_transform(
+                elm,
+                viewport.width,
+                viewport.height,
+            )),
+        clip,
+        viewport_size: view_box.map_or((viewport.width, viewport.height), |v| (v.width, v.height)),
+        ..*ctx
+    };
+    for child in node.borrow().children.iter() {
+        render_element(child, &ctx, objects);
+    }
+}
+
+fn render_element(
+    node: &Rc<RefCell<DomNode>>,
+    ctx: &RenderContext,
+    objects: &mut Vec<RenderObject>,
+) {
+    let NodeType::Element(elm) = &node.borrow().node_type else {
+        return;
+    };
+    let style = ctx.style.cascade(elm);
+    if style.display_none {
+        return;
+    }
+    let transform = get_attr(elm, "transform")
+        .and_then(|value| Transform::parse(value).ok())
+        .map_or(ctx.transform, |t| ctx.transform.multiply(&t));
+    let ctx = RenderContext {
+        transform,
+        style,
+        ..*ctx
+    };
+    let get_length = |name: &str| get_attr(elm, name).and_then(parse_length);
+
+    let segments = match elm.tag_name.as_str() {
+        "g" | "a" => {
+            for child in node.borrow().children.iter() {
+                render_element(child, &ctx, objects);
+            }
+            return;
+        }
+        "svg" => {
+            let viewport = Rect::new(
+                get_length("x").unwrap_or(0.0),
+                get_length("y").unwrap_or(0.0),
+                get_length("width").unwrap_or(ctx.viewport_size.0),
+                get_length("height").unwrap_or(ctx.viewport_size.1),
+            );
+            render_viewport(node, elm, viewport, &ctx, objects);
+            return;
+        }
+        "use" => {
+            render_use(node, elm, &ctx, objects);
+            return;
+        }
+        "path" => parse_path_data(get_attr(elm, "d").unwrap_or_default()),
+        "rect" => {
+            let (x, y) = (
+                get_length("x").unwrap_or(0.0),
+                get_length("y").unwrap_or(0.0),
+            );
+            let (width, height) = (
+                get_length("width").unwrap_or(0.0),
+                get_length("height").unwrap_or(0.0),
+            );
+            // If only one of the radii is given, the other is the same.
+            // https://www.w3.org/TR/SVG2/geometry.html#RX
+            let (rx, ry) = match (get_length("rx"), get_length("ry")) {
+                (Some(rx), Some(ry)) => (rx, ry),
+                (Some(r), None) | (None, Some(r)) => (r, r),
+                (None, None) => (0.0, 0.0),
+            };
+            if width > 0.0 && height > 0.0 {
+                rect_to_path(
+                    x,
+                    y,
+                    width,
+                    height,
+                    rx.clamp(0.0, width / 2.0),
+                    ry.clamp(0.0, height / 2.0),
+                )
+            } else {
+                Vec::new()
+            }
+        }
+        "circle" => match get_length("r").filter(|r| *r > 0.0) {
+            Some(r) => ellipse_to_path(
+                get_length("cx").unwrap_or(0.0),
+                get_length("cy").unwrap_or(0.0),
+                r,
+                r,
+            ),
+            None => Vec::new(),
+        },
+        "ellipse" => match (get_length("rx"), get_length("ry")) {
+            (Some(rx), Some(ry)) if rx > 0.0 && ry > 0.0 => ellipse_to_path(
+                get_length("cx").unwrap_or(0.0),
+                get_length("cy").unwrap_or(0.0),
+                rx,
+                ry,
+            ),
+            _ => Vec::new(),
+        },
+        "line" => vec![
+            PathSegment::MoveTo(
+                get_length("x1").unwrap_or(0.0),
+                get_length("y1").unwrap_or(0.0),
+            ),
+            PathSegment::LineTo(
+                get_length("x2").unwrap_or(0.0),
+                get_length("y2").unwrap_or(0.0),
+            ),
+        ],
+        "polyline" | "polygon" => {
+            // The points are rendered up to the error, and an odd number of coordinates drops the last one.
+            // https://www.w3.org/TR/SVG2/shapes.html#PolylineElement
+            let points = parse_points(get_attr(elm, "points").unwrap_or_default());
+            let mut segments = points
+                .chunks_exact(2)
+                .enumerate()
+                .map(|(i, point)| match i {
+                    0 => PathSegment::MoveTo(point[0], point[1]),
+                    _ => PathSegment::LineTo(point[0], point[1]),
+                })
+                .collect::<Vec<_>>();
+            if elm.tag_name == "polygon" && !segments.is_empty() {
+                segments.push(PathSegment::ClosePath);
+            }
+            segments
+        }
+        // The other elements, such as `defs` and `symbol`, are not rendered directly.
+        _ => return,
+    };
+    push_path(&segments, &ctx, objects);
+}
+
+/// Renders the element referenced by the `use` element, which inherits the style of the `use` element.
+/// https://www.w3.org/TR/SVG2/struct.html#UseElement
+fn render_use(
+    node: &Rc<RefCell<DomNode>>,
+    elm: &Element,
+    ctx: &RenderContext,
+    objects: &mut Vec<RenderObject>,
+) {
+    if ctx.use_depth >= MAX_USE_DEPTH {
+        return;
+    }
+    // todo: Support the references to the external resources.
+    let Some(id) = get_attr(elm, "href")
+        .or_else(|| get_attr(elm, "xlink:href"))
+        .and_then(|href| href.trim().strip_prefix('#'))
+    else {
+        return;
+    };
+    let Some(target) = DomNode::get_element_by_id_in_tree(node, id) else {
+        return;
+    };
+    let get_length = |name: &str| get_attr(elm, name).and_then(parse_length);
+    let (x, y) = (
+        get_length("x").unwrap_or(0.0),
+        get_length("y").unwrap_or(0.0),
+    );
+    let ctx = RenderContext {
+        transform: ctx.transform.multiply(&Transform::translate(x, y)),
+        use_depth: ctx.use_depth + 1,
+        ..*ctx
+    };
+
+    let target_ref = target.borrow();
+    let NodeType::Element(target_elm) = &target_ref.node_type else {
+        return;
+    };
+    if target_elm.tag_name == "symbol" {
+        let style = ctx.style.cascade(target_elm);
+        if style.display_none {
+            return;
+        }
+        // The symbol is rendered like a nested `svg` element, whose size is given by the `use` element.
+        let viewport = Rect::new(
+            0.0,
+            0.0,
+            get_length("width")
+                .or_else(|| get_attr(target_elm, "width").and_then(parse_length))
+                .unwrap_or(ctx.viewport_size.0),
+            get_length("height")
+                .or_else(|| get_attr(target_elm, "height").and_then(parse_length))
+                .unwrap_or(ctx.viewport_size.1),
+        );
+        let ctx = RenderContext { style, ..ctx };
+        render_viewport(&target, target_elm, viewport, &ctx, objects);
+    } else {
+        drop(target_ref);
+        render_element(&target, &ctx, objects);
+    }
+}
+
+/// Adds the path in the user space of `ctx` as a render object, if it is painted.
+fn push_path(segments: &[PathSegment], ctx: &RenderContext, objects: &mut Vec<RenderObject>) {
+    let style = &ctx.style;
+    let fill = style
+        .fill
+        .map(|(r, g, b, a)| (r, g, b, a * style.fill_opacity * style.opacity))
+        .filter(|color| color.3 > 0.0);
+    let stroke = style
+        .stroke
+        .map(|(r, g, b, a)| (r, g, b, a * style.stroke_opacity * style.opacity))
+        .filter(|color| color.3 > 0.0 && style.stroke_width > 0.0);
+    if segments.is_empty() || (fill.is_none() && stroke.is_none()) {
+        return;
+    }
+    objects.push(RenderObject::Path {
+        segments: segments
+            .iter()
+            .map(|segment| segment.map_points(|x, y| ctx.transform.apply(x, y)))
+            .collect(),
+        fill,
+        even_odd: style.even_odd,
+        stroke,
+        stroke_width: style.stroke_width * ctx.transform.get_scale(),
+        clip: (ctx.clip.x, ctx.clip.y, ctx.clip.width, ctx.clip.height),
+    });
+}
+
+/// Returns the path of the rectangle, whose corners are rounded by the radii.
+fn rect_to_path(x: f64, y: f64, width: f64, height: f64, rx: f64, ry: f64) -> Vec<PathSegment> {
+    let (right, bottom) = (x + width, y + height);
+    if rx == 0.0 || ry == 0.0 {
+        return vec![
+            PathSegment::MoveTo(x, y),
+            PathSegment::LineTo(right, y),
+            PathSegment::LineTo(right, bottom),
+            PathSegment::LineTo(x, bottom),
+            PathSegment::ClosePath,
+        ];
+    }
+    let corner =
+        |from: (f64, f64), to: (f64, f64)| arc_to_curves(from, (rx, ry), 0.0, false, true, to);
+    let mut segments = vec![
+        PathSegment::MoveTo(x + rx, y),
+        PathSegment::LineTo(right - rx, y),
+    ];
+    segments.extend(corner((right - rx, y), (right, y + ry)));
+    segments.push(PathSegment::LineTo(right, bottom - ry));
+    segments.extend(corner((right, bottom - ry), (right - rx, bottom)));
+    segments.push(PathSegment::LineTo(x + rx, bottom));
+    segments.extend(corner((x + rx, bottom), (x, bottom - ry)));
+    segments.push(PathSegment::LineTo(x, y + ry));
+    segments.extend(corner((x, y + ry), (x + rx, y)));
+    segments.push(PathSegment::ClosePath);
+    segments
+}
+
+/// Parses the coordinates of the `points` attribute up to the first error.
+fn parse_points(value: &str) -> Vec<f64> {
+    let mut scanner = path::NumberScanner::new(value);
+    let mut points = Vec::new();
+    while scanner.peek().is_some() {
+        match scanner.next_number() {
+            Ok(number) => points.push(number),
+            Err(_) => break,
+        }
+    }
+    points
+}
+
+/// Returns the value of the attribute, whose name is matched case-insensitively, since the HTML parser
+/// lowercases the names of the attributes such as `viewBox`.
+fn get_attr<'a>(elm: &'a Element, name: &str) -> Option<&'a str> {
+    elm.attributes
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Parses the length in the user units, which are the numbers without units. The font-relative units are
+/// relative to the initial font size.
+/// todo: Support the percentages, and the font size of the element.
+pub fn parse_length(value: &str) -> Option<f64> {
+    match value.trim().parse::<f64>() {
+        Ok(v) => Some(v),
+        Err(_) => parse_length_px(value, &LengthContext::default()).map(f64::from),
+    }
+    .filter(|v| v.is_finite())
+}
+
+/// Parses the paint of the `fill` and `stroke` properties, where `None` is `none`.
+/// https://www.w3.org/TR/SVG2/painting.html#SpecifyingPaint
+fn parse_paint(value: &str, current_color: Rgba) -> Result<Option<Rgba>> {
+    // The paint servers are not supported yet, so the fallback color is used, which is `none` if it is omitted.
+    // todo: Support the gradients and the patterns.
+    if let Some(rest) = value.strip_prefix("url(") {
+        let fallback = rest.split_once(')').map_or("", |(_, v)| v.trim());
+        return match fallback {
+            "" => Ok(None),
+            _ => parse_paint(fallback, current_color),
+        };
+    }
+    match value.to_ascii_lowercase().as_str() {
+        "none" => Ok(None),
+        "currentcolor" => Ok(Some(current_color)),
+        _ => parse_color(value).map(Some),
+    }
+}
+
+fn parse_color(value: &str) -> Result<Rgba> {
+    let values = CssParser::new(&CssTokenizer::new(value.trim()).tokenize()?)
+        .parse_list_of_component_values();
+    Ok(ColorProp::parse(&values)?
+        .compute(None, &LengthContext::default())?
+        .to_color()?
+        .to_unit_rgba())
+}
+
+/// Returns the viewBox of the element, which is ignored if its width or height is not positive.
+/// https://www.w3.org/TR/SVG2/coords.html#ViewBoxAttribute
+fn get_view_box(elm: &Element) -> Option<Rect> {
+    match parse_number_list(get_attr(elm, "viewBox")?).ok()?[..] {
+        [x, y, width, height] if width > 0.0 && height > 0.0 => {
+            Some(Rect::new(x, y, width, height))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the width divided by the height of the viewBox of the element, which is used as the aspect ratio
+/// of the `svg` element whose width or height is not given.
+/// https://www.w3.org/TR/SVG2/coords.html#SizingSVGInCSS
+pub fn get_aspect_ratio(elm: &Element) -> Option<f64> {
+    get_view_box(elm).map(|view_box| view_box.width / view_box.height)
+}
+
+/// Returns the transform which fits the viewBox of the element into the viewport of the size, following
+/// `preserveAspectRatio`.
+/// https://www.w3.org/TR/SVG2/coords.html#ComputingAViewportsTransform
+fn get_view_box_transform(elm: &Element, width: f64, height: f64) -> Transform {
+    let Some(view_box) = get_view_box(elm) else {
+        return Transform::IDENTITY;
+    };
+    let mut params = get_attr(elm, "preserveAspectRatio")
+        .unwrap_or_default()
+        .split_ascii_whitespace();
+    let align = params.next().unwrap_or("xMidYMid");
+    let slice = params.next() == Some("slice");
+
+    let (mut sx, mut sy) = (width / view_box.width, height / view_box.height);
+    let (align_x, align_y) = if align == "none" {
+        ("Min", "Min")
+    } else {
+        let scale = if slice { sx.max(sy) } else { sx.min(sy) };
+        (sx, sy) = (scale, scale);
+        (
+            align.get(1..4).unwrap_or("Mid"),
+            align.get(5..8).unwrap_or("Mid"),
+        )
+    };
+    let offset = |align: &str, space: f64| match align {
+        "Min" => 0.0,
+        "Max" => space,
+        _ => space / 2.0,
+    };
+    Transform::new(
+        sx,
+        0.0,
+        0.0,
+        sy,
+        offset(align_x, width - view_box.width * sx) - view_box.x * sx,
+        offset(align_y, height - view_box.height * sy) - view_box.y * sy,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+
+    #[test]
+    fn render_inline_svg() {
+        let html = "<html><body><svg viewBox=\"0 0 24 24\" fill=\"currentColor\">\
+            <defs><symbol id=\"dot\" viewBox=\"0 0 2 2\"><circle cx=\"1\" cy=\"1\" r=\"1\"/></symbol></defs>\
+            <g transform=\"translate(2 2)\" style=\"stroke: red; stroke-width: 2\">\
+            <rect width=\"10\" height=\"4\" fill=\"none\"/></g>\
+            <path d=\"M0 0h4v4z\" opacity=\"0.5\" display=\"none\"/>\
+            <use href=\"#dot\" x=\"12\" y=\"12\" width=\"12\" height=\"12\" fill=\"#00f\"/>\
+            </svg></body></html>";
+        let (document, _) = HtmlParser::new(HtmlTokenizer::new(html)).parse().unwrap();
+        let svg = DomNode::get_descendants(&document)
+            .find(|node| matches!(&node.borrow().node_type, NodeType::Element(e) if e.tag_name == "svg"))
+            .unwrap();
+        // The viewBox is scaled by 2 and centered vertically in the viewport.
+        let objects = render_svg(
+            &svg,
+            Rect::new(100.0, 50.0, 48.0, 64.0),
+            (0.0, 0.5, 0.0, 1.0),
+        );
+        assert_eq!(objects.len(), 2);
+
+        let RenderObject::Path {
+            segments,
+            fill,
+            stroke,
+            stroke_width,
+            clip,
+            ..
+        } = &objects[0]
+        else {
+            panic!();
+        };
+        assert_eq!(segments[0], PathSegment::MoveTo(104.0, 62.0));
+        assert_eq!(segments[2], PathSegment::LineTo(124.0, 70.0));
+        assert_eq!(*fill, None);
+        assert_eq!(*stroke, Some((1.0, 0.0, 0.0, 1.0)));
+        assert_eq!(*stroke_width, 4.0);
+        assert_eq!(*clip, (100.0, 50.0, 48.0, 64.0));
+
+        // The circle in the symbol is fitted into the area given by the `use` element.
+        let RenderObject::Path {
+            segments,
+            fill,
+            stroke,
+            clip,
+            ..
+        } = &objects[1]
+        else {
+            panic!();
+        };
+        assert_eq!(segments[0], PathSegment::MoveTo(148.0, 94.0));
+        assert_eq!(*fill, Some((0.0, 0.0, 1.0, 1.0)));
+        assert_eq!(*stroke, None);
+        assert_eq!(*clip, (124.0, 82.0, 24.0, 24.0));
+
+        assert_eq!(
+            get_view_box_transform(
+                &Element {
+                    tag_name: "svg".to_string(),
+                    attributes: vec![
+                        ("viewbox".to_string(), "0 0 10 10".to_string()),
+                        (
+                            "preserveaspectratio".to_string(),
+                            "xMinYMax slice".to_string()
+                        ),
+                    ],
+                },
+                20.0,
+                40.0
+            )
+            .apply(10.0, 10.0),
+            (40.0, 40.0)
+        );
+    }
+}
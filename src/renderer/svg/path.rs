@@ -0,0 +1,387 @@
+use std::f64::consts::PI;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use anyhow::{bail, Context, Result};
+
+/// A segment of a path, whose coordinates are absolute. All the curves are converted to cubic Bézier curves,
+/// since they are transformed by affine transforms without changing their shapes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+    MoveTo(f64, f64),
+    LineTo(f64, f64),
+    /// (x1, y1, x2, y2, x, y), where (x1, y1) and (x2, y2) are the control points.
+    CurveTo(f64, f64, f64, f64, f64, f64),
+    ClosePath,
+}
+
+impl PathSegment {
+    /// Returns the segment whose points are mapped by `f`.
+    pub fn map_points(&self, f: impl Fn(f64, f64) -> (f64, f64)) -> Self {
+        match *self {
+            Self::MoveTo(x, y) => {
+                let (x, y) = f(x, y);
+                Self::MoveTo(x, y)
+            }
+            Self::LineTo(x, y) => {
+                let (x, y) = f(x, y);
+                Self::LineTo(x, y)
+            }
+            Self::CurveTo(x1, y1, x2, y2, x, y) => {
+                let ((x1, y1), (x2, y2), (x, y)) = (f(x1, y1), f(x2, y2), f(x, y));
+                Self::CurveTo(x1, y1, x2, y2, x, y)
+            }
+            Self::ClosePath => Self::ClosePath,
+        }
+    }
+}
+
+/// Reads the numbers and the flags in the path data and the other SVG attributes. The numbers may be
+/// separated by whitespace, a comma, or nothing if the next number starts with a sign or a dot.
+/// https://www.w3.org/TR/SVG2/paths.html#PathDataBNF
+pub struct NumberScanner<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> NumberScanner<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.chars().peekable(),
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self
+            .chars
+            .next_if(|c| c.is_ascii_whitespace() || *c == ',')
+            .is_some()
+        {}
+    }
+
+    /// Returns the next non-separator character without consuming it.
+    pub fn peek(&mut self) -> Option<char> {
+        self.skip_separators();
+        self.chars.peek().copied()
+    }
+
+    pub fn next_char(&mut self) -> Option<char> {
+        self.skip_separators();
+        self.chars.next()
+    }
+
+    pub fn next_number(&mut self) -> Result<f64> {
+        self.skip_separators();
+        let mut number = String::new();
+        if let Some(sign) = self.chars.next_if(|c| matches!(c, '+' | '-')) {
+            number.push(sign);
+        }
+        let mut has_dot = false;
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_ascii_digit() || (*c == '.' && !has_dot))
+        {
+            has_dot |= c == '.';
+            number.push(c);
+        }
+        if let Some(e) = self.chars.next_if(|c| matches!(c, 'e' | 'E')) {
+            number.push(e);
+            if let Some(sign) = self.chars.next_if(|c| matches!(c, '+' | '-')) {
+                number.push(sign);
+            }
+            while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit()) {
+                number.push(c);
+            }
+        }
+        number
+            .parse()
+            .with_context(|| format!("Invalid number: {:?}", number))
+    }
+
+    /// Reads a flag of an arc, which may not be followed by a separator.
+    fn next_flag(&mut self) -> Result<bool> {
+        match self.next_char() {
+            Some('0') => Ok(false),
+            Some('1') => Ok(true),
+            c => bail!("Invalid flag: {:?}", c),
+        }
+    }
+}
+
+/// Parses a list of numbers, such as the `viewBox` and the `points` attributes.
+pub fn parse_number_list(input: &str) -> Result<Vec<f64>> {
+    let mut scanner = NumberScanner::new(input);
+    let mut numbers = Vec::new();
+    while scanner.peek().is_some() {
+        numbers.push(scanner.next_number()?);
+    }
+    Ok(numbers)
+}
+
+/// Parses the path data. If there is an error, the path is rendered up to the segment before it.
+/// https://www.w3.org/TR/SVG2/paths.html#PathDataErrorHandling
+pub fn parse_path_data(d: &str) -> Vec<PathSegment> {
+    let mut segments = Vec::new();
+    let _ = parse_path_data_into(d, &mut segments);
+    segments
+}
+
+fn parse_path_data_into(d: &str, segments: &mut Vec<PathSegment>) -> Result<()> {
+    let mut scanner = NumberScanner::new(d);
+    let (mut x, mut y) = (0.0, 0.0);
+    let (mut start_x, mut start_y) = (0.0, 0.0);
+    // The control point of the previous curve, which is reflected by the smooth curve commands.
+    let mut prev_cubic_control: Option<(f64, f64)> = None;
+    let mut prev_quad_control: Option<(f64, f64)> = None;
+    let mut command = None;
+
+    while let Some(c) = scanner.peek() {
+        if c.is_ascii_alphabetic() {
+            scanner.next_char();
+            command = Some(c);
+        } else if command.is_none() {
+            bail!("The path data must start with a command.");
+        }
+        if segments.is_empty() && !matches!(command, Some('M' | 'm')) {
+            bail!("The path data must start with a moveto command.");
+        }
+        let Some(cmd) = command else {
+            unreachable!();
+        };
+        let is_relative = cmd.is_ascii_lowercase();
+        let (dx, dy) = if is_relative { (x, y) } else { (0.0, 0.0) };
+        let mut cubic_control = None;
+        let mut quad_control = None;
+
+        match cmd.to_ascii_uppercase() {
+            'M' => {
+                (x, y) = (scanner.next_number()? + dx, scanner.next_number()? + dy);
+                (start_x, start_y) = (x, y);
+                segments.push(PathSegment::MoveTo(x, y));
+                // The coordinate pairs after the first one are the implicit line-to commands.
+                command = Some(if is_relative { 'l' } else { 'L' });
+            }
+            'L' => {
+                (x, y) = (scanner.next_number()? + dx, scanner.next_number()? + dy);
+                segments.push(PathSegment::LineTo(x, y));
+            }
+            'H' => {
+                x = scanner.next_number()? + dx;
+                segments.push(PathSegment::LineTo(x, y));
+            }
+            'V' => {
+                y = scanner.next_number()? + dy;
+                segments.push(PathSegment::LineTo(x, y));
+            }
+            'C' | 'S' => {
+                let (x1, y1) = if cmd.eq_ignore_ascii_case(&'C') {
+                    (scanner.next_number()? + dx, scanner.next_number()? + dy)
+                } else {
+                    prev_cubic_control.map_or((x, y), |(cx, cy)| (2.0 * x - cx, 2.0 * y - cy))
+                };
+                let (x2, y2) = (scanner.next_number()? + dx, scanner.next_number()? + dy);
+                (x, y) = (scanner.next_number()? + dx, scanner.next_number()? + dy);
+                segments.push(PathSegment::CurveTo(x1, y1, x2, y2, x, y));
+                cubic_control = Some((x2, y2));
+            }
+            'Q' | 'T' => {
+                let (qx, qy) = if cmd.eq_ignore_ascii_case(&'Q') {
+                    (scanner.next_number()? + dx, scanner.next_number()? + dy)
+                } else {
+                    prev_quad_control.map_or((x, y), |(cx, cy)| (2.0 * x - cx, 2.0 * y - cy))
+                };
+                let (end_x, end_y) = (scanner.next_number()? + dx, scanner.next_number()? + dy);
+                // The quadratic curve is raised to the cubic one.
+                segments.push(PathSegment::CurveTo(
+                    x + 2.0 / 3.0 * (qx - x),
+                    y + 2.0 / 3.0 * (qy - y),
+                    end_x + 2.0 / 3.0 * (qx - end_x),
+                    end_y + 2.0 / 3.0 * (qy - end_y),
+                    end_x,
+                    end_y,
+                ));
+                (x, y) = (end_x, end_y);
+                quad_control = Some((qx, qy));
+            }
+            'A' => {
+                let (rx, ry) = (scanner.next_number()?, scanner.next_number()?);
+                let rotation = scanner.next_number()?;
+                let (large_arc, sweep) = (scanner.next_flag()?, scanner.next_flag()?);
+                let (end_x, end_y) = (scanner.next_number()? + dx, scanner.next_number()? + dy);
+                segments.extend(arc_to_curves(
+                    (x, y),
+                    (rx, ry),
+                    rotation,
+                    large_arc,
+                    sweep,
+                    (end_x, end_y),
+                ));
+                (x, y) = (end_x, end_y);
+            }
+            'Z' => {
+                segments.push(PathSegment::ClosePath);
+                (x, y) = (start_x, start_y);
+                // A number after the close path command is an error.
+                command = None;
+            }
+            _ => bail!("Invalid path command: {}", cmd),
+        }
+        prev_cubic_control = cubic_control;
+        prev_quad_control = quad_control;
+    }
+    Ok(())
+}
+
+/// Converts the elliptical arc to cubic Bézier curves, each of which spans at most 90 degrees.
+/// https://www.w3.org/TR/SVG2/implnote.html#ArcImplementationNotes
+pub fn arc_to_curves(
+    (x1, y1): (f64, f64),
+    (rx, ry): (f64, f64),
+    rotation: f64,
+    large_arc: bool,
+    sweep: bool,
+    (x2, y2): (f64, f64),
+) -> Vec<PathSegment> {
+    if (x1, y1) == (x2, y2) {
+        return Vec::new();
+    }
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if rx == 0.0 || ry == 0.0 {
+        return vec![PathSegment::LineTo(x2, y2)];
+    }
+
+    // Compute the center of the ellipse.
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let (mid_x, mid_y) = ((x1 - x2) / 2.0, (y1 - y2) / 2.0);
+    let x1p = cos * mid_x + sin * mid_y;
+    let y1p = -sin * mid_x + cos * mid_y;
+    // The radii are scaled up if they are too small to reach the end point.
+    let lambda = (x1p / rx).powi(2) + (y1p / ry).powi(2);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let numerator = (rx * ry).powi(2) - (rx * y1p).powi(2) - (ry * x1p).powi(2);
+    let denominator = (rx * y1p).powi(2) + (ry * x1p).powi(2);
+    let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+    let coef = sign * (numerator / denominator).max(0.0).sqrt();
+    let (cxp, cyp) = (coef * rx * y1p / ry, -coef * ry * x1p / rx);
+    let cx = cos * cxp - sin * cyp + (x1 + x2) / 2.0;
+    let cy = sin * cxp + cos * cyp + (y1 + y2) / 2.0;
+
+    let angle =
+        |(ux, uy): (f64, f64), (vx, vy): (f64, f64)| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+    let start = ((x1p - cxp) / rx, (y1p - cyp) / ry);
+    let end = ((-x1p - cxp) / rx, (-y1p - cyp) / ry);
+    let theta = angle((1.0, 0.0), start);
+    let mut delta_theta = angle(start, end);
+    if !sweep && delta_theta > 0.0 {
+        delta_theta -= 2.0 * PI;
+    } else if sweep && delta_theta < 0.0 {
+        delta_theta += 2.0 * PI;
+    }
+
+    let count = (delta_theta.abs() / (PI / 2.0)).ceil().max(1.0) as usize;
+    let delta = delta_theta / count as f64;
+    let t = 4.0 / 3.0 * (delta / 4.0).tan();
+    let to_point = |ux: f64, uy: f64| {
+        (
+            cx + rx * ux * cos - ry * uy * sin,
+            cy + rx * ux * sin + ry * uy * cos,
+        )
+    };
+    (0..count)
+        .map(|i| {
+            let (sin1, cos1) = (theta + delta * i as f64).sin_cos();
+            let (sin2, cos2) = (theta + delta * (i + 1) as f64).sin_cos();
+            let (c1x, c1y) = to_point(cos1 - t * sin1, sin1 + t * cos1);
+            let (c2x, c2y) = to_point(cos2 + t * sin2, sin2 - t * cos2);
+            let (x, y) = to_point(cos2, sin2);
+            PathSegment::CurveTo(c1x, c1y, c2x, c2y, x, y)
+        })
+        .collect()
+}
+
+/// Returns the path of the ellipse, which starts at the rightmost point and goes clockwise.
+pub fn ellipse_to_path(cx: f64, cy: f64, rx: f64, ry: f64) -> Vec<PathSegment> {
+    let mut segments = vec![PathSegment::MoveTo(cx + rx, cy)];
+    segments.extend(arc_to_curves(
+        (cx + rx, cy),
+        (rx, ry),
+        0.0,
+        false,
+        true,
+        (cx - rx, cy),
+    ));
+    segments.extend(arc_to_curves(
+        (cx - rx, cy),
+        (rx, ry),
+        0.0,
+        false,
+        true,
+        (cx + rx, cy),
+    ));
+    segments.push(PathSegment::ClosePath);
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_path() {
+        use PathSegment::*;
+
+        assert_eq!(
+            parse_path_data("M10-20l5.5.5h10V0zm1 1 2 2"),
+            [
+                MoveTo(10.0, -20.0),
+                LineTo(15.5, -19.5),
+                LineTo(25.5, -19.5),
+                LineTo(25.5, 0.0),
+                ClosePath,
+                MoveTo(11.0, -19.0),
+                LineTo(13.0, -17.0),
+            ]
+        );
+        // The smooth curves reflect the control point of the previous curve.
+        let round = |v: f64| (v * 1e6).round() / 1e6;
+        assert_eq!(
+            parse_path_data("M0,0 C0,10 10,10 10,0 S20,-10 20,0 Q30,0 30,10 T30,30")
+                .iter()
+                .map(|segment| segment.map_points(|x, y| (round(x), round(y))))
+                .collect::<Vec<_>>(),
+            [
+                MoveTo(0.0, 0.0),
+                CurveTo(0.0, 10.0, 10.0, 10.0, 10.0, 0.0),
+                CurveTo(10.0, -10.0, 20.0, -10.0, 20.0, 0.0),
+                CurveTo(26.666667, 0.0, 30.0, 3.333333, 30.0, 10.0),
+                CurveTo(30.0, 16.666667, 30.0, 23.333333, 30.0, 30.0),
+            ]
+        );
+        // The path is rendered up to the error.
+        assert_eq!(
+            parse_path_data("M 0 0 L 10 10 L 20"),
+            [MoveTo(0.0, 0.0), LineTo(10.0, 10.0)]
+        );
+        assert!(parse_path_data("L 10 10").is_empty());
+
+        // The half circle is split into two curves, and the flags need no separators.
+        let segments = parse_path_data("M0 0a5 5 0 1010 0");
+        assert_eq!(segments.len(), 3);
+        let PathSegment::CurveTo(.., x, y) = segments[1] else {
+            panic!();
+        };
+        assert!((x - 5.0).abs() < 1e-9 && (y - 5.0).abs() < 1e-9);
+        let PathSegment::CurveTo(.., x, y) = segments[2] else {
+            panic!();
+        };
+        assert!((x - 10.0).abs() < 1e-9 && y.abs() < 1e-9);
+
+        assert_eq!(
+            parse_number_list("0 0,24 -1.5e1").unwrap(),
+            [0.0, 0.0, 24.0, -15.0]
+        );
+        assert!(parse_number_list("0 a").is_err());
+    }
+}
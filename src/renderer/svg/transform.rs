@@ -0,0 +1,138 @@
+use anyhow::{bail, ensure, Result};
+
+use crate::renderer::svg::path::parse_number_list;
+
+/// A 2D affine transform, which maps (x, y) to (ax + cy + e, bx + dy + f).
+/// https://www.w3.org/TR/css-transforms-1/#svg-transform
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+
+    pub const fn new(a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> Self {
+        Self { a, b, c, d, e, f }
+    }
+
+    pub fn translate(tx: f64, ty: f64) -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, tx, ty)
+    }
+
+    pub fn scale(sx: f64, sy: f64) -> Self {
+        Self::new(sx, 0.0, 0.0, sy, 0.0, 0.0)
+    }
+
+    /// Returns the clockwise rotation by the angle in degrees, since the y-axis points down.
+    pub fn rotate(angle: f64) -> Self {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Self::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// Returns the transform which applies `other` first and then this transform.
+    pub fn multiply(&self, other: &Self) -> Self {
+        Self::new(
+            self.a * other.a + self.c * other.b,
+            self.b * other.a + self.d * other.b,
+            self.a * other.c + self.c * other.d,
+            self.b * other.c + self.d * other.d,
+            self.a * other.e + self.c * other.f + self.e,
+            self.b * other.e + self.d * other.f + self.f,
+        )
+    }
+
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        )
+    }
+
+    /// Returns the factor by which the lengths such as the stroke width are scaled. The lengths are scaled
+    /// by the geometric mean of the scales of the axes, since the strokes are not skewed for now.
+    pub fn get_scale(&self) -> f64 {
+        (self.a * self.d - self.b * self.c).abs().sqrt()
+    }
+
+    /// Parses the value of the `transform` attribute, which is a list of the transform functions applied
+    /// from right to left.
+    /// https://www.w3.org/TR/css-transforms-1/#svg-syntax
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut transform = Self::IDENTITY;
+        let mut rest = value.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        while !rest.is_empty() {
+            let Some((name, after_name)) = rest.split_once('(') else {
+                bail!("Invalid transform: {}", value);
+            };
+            let Some((args, after_args)) = after_name.split_once(')') else {
+                bail!("Invalid transform: {}", value);
+            };
+            let args = parse_number_list(args)?;
+            let function = match (name.trim(), &args[..]) {
+                ("matrix", &[a, b, c, d, e, f]) => Self::new(a, b, c, d, e, f),
+                ("translate", &[tx]) => Self::translate(tx, 0.0),
+                ("translate", &[tx, ty]) => Self::translate(tx, ty),
+                ("scale", &[s]) => Self::scale(s, s),
+                ("scale", &[sx, sy]) => Self::scale(sx, sy),
+                ("rotate", &[angle]) => Self::rotate(angle),
+                // The rotation around the point.
+                ("rotate", &[angle, cx, cy]) => Self::translate(cx, cy)
+                    .multiply(&Self::rotate(angle))
+                    .multiply(&Self::translate(-cx, -cy)),
+                ("skewX", &[angle]) => Self::new(1.0, 0.0, angle.to_radians().tan(), 1.0, 0.0, 0.0),
+                ("skewY", &[angle]) => Self::new(1.0, angle.to_radians().tan(), 0.0, 1.0, 0.0, 0.0),
+                _ => bail!("Invalid transform function: {}", name),
+            };
+            transform = transform.multiply(&function);
+            rest = after_args.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        }
+        ensure!(
+            transform.a.is_finite() && transform.d.is_finite(),
+            "Invalid transform: {}",
+            value
+        );
+        Ok(transform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_transforms() {
+        let apply = |value: &str, x: f64, y: f64| {
+            let (x, y) = Transform::parse(value).unwrap().apply(x, y);
+            ((x * 1e6).round() / 1e6, (y * 1e6).round() / 1e6)
+        };
+        assert_eq!(apply("", 1.0, 2.0), (1.0, 2.0));
+        assert_eq!(apply("translate(10)", 1.0, 2.0), (11.0, 2.0));
+        // The rightmost function is applied first.
+        assert_eq!(apply("translate(10, 20) scale(2)", 1.0, 2.0), (12.0, 24.0));
+        assert_eq!(apply("scale(2),translate(10 20)", 1.0, 2.0), (22.0, 44.0));
+        assert_eq!(apply("rotate(90)", 1.0, 0.0), (0.0, 1.0));
+        assert_eq!(apply("rotate(180 5 5)", 0.0, 0.0), (10.0, 10.0));
+        assert_eq!(apply("matrix(1 0 0 1 3 4)", 0.0, 0.0), (3.0, 4.0));
+        assert_eq!(apply("skewX(45)", 0.0, 1.0), (1.0, 1.0));
+        assert_eq!(
+            Transform::parse("scale(2 3)").unwrap().get_scale(),
+            6.0_f64.sqrt()
+        );
+
+        assert!(Transform::parse("translate(1, 2").is_err());
+        assert!(Transform::parse("rotate(1, 2)").is_err());
+        assert!(Transform::parse("perspective(1)").is_err());
+    }
+}
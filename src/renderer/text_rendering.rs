@@ -0,0 +1,169 @@
+use std::fmt;
+use std::sync::RwLock;
+
+use anyhow::{bail, Context, Result};
+
+/// The options of the text rendering of the current process, which are set from the command line and the
+/// settings page, and which apply to the pages rendered after they are set.
+static OPTIONS: RwLock<Option<TextRenderingOptions>> = RwLock::new(None);
+
+/// How the edges of the glyphs are smoothed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Antialiasing {
+    /// The default of the font backend, which usually follows the system settings.
+    #[default]
+    System,
+    None,
+    Grayscale,
+    Subpixel,
+}
+
+impl fmt::Display for Antialiasing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::System => write!(f, "system"),
+            Self::None => write!(f, "none"),
+            Self::Grayscale => write!(f, "grayscale"),
+            Self::Subpixel => write!(f, "subpixel"),
+        }
+    }
+}
+
+/// How strongly the outlines of the glyphs are fitted to the pixel grid. The glyph metrics are also
+/// rounded to whole pixels unless the hinting is `None`, so this affects the layout too.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Hinting {
+    /// The default of the font backend, which usually follows the system settings.
+    #[default]
+    System,
+    None,
+    Slight,
+    Medium,
+    Full,
+}
+
+impl fmt::Display for Hinting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::System => write!(f, "system"),
+            Self::None => write!(f, "none"),
+            Self::Slight => write!(f, "slight"),
+            Self::Medium => write!(f, "medium"),
+            Self::Full => write!(f, "full"),
+        }
+    }
+}
+
+/// The user preferences of how the text is rendered. The output in CLI mode doesn't depend on the fonts
+/// configured on the system if all of them are set explicitly.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TextRenderingOptions {
+    pub antialiasing: Antialiasing,
+    pub hinting: Hinting,
+    /// The families used for the generic families `serif`, `sans-serif` and `monospace`, which are left to
+    /// fontconfig if they are `None`.
+    /// https://drafts.csswg.org/css-fonts/#generic-font-families
+    pub serif_family: Option<String>,
+    pub sans_serif_family: Option<String>,
+    pub monospace_family: Option<String>,
+    /// The smallest computed `font-size` in pixels, below which the text is enlarged for legibility. The
+    /// relative sizes of the descendants are relative to the enlarged size.
+    pub minimum_font_size: f32,
+}
+
+impl TextRenderingOptions {
+    /// Changes the option with the name used on the settings page, e.g. `antialiasing=subpixel` or
+    /// `serif-font=Noto Serif`. The families are reset to the defaults with empty values.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let family = || Some(value.to_string()).filter(|v| !v.is_empty());
+        match name {
+            "antialiasing" => {
+                self.antialiasing = match value {
+                    "system" => Antialiasing::System,
+                    "none" => Antialiasing::None,
+                    "grayscale" => Antialiasing::Grayscale,
+                    "subpixel" => Antialiasing::Subpixel,
+                    _ => bail!("Invalid antialiasing: {}", value),
+                }
+            }
+            "hinting" => {
+                self.hinting = match value {
+                    "system" => Hinting::System,
+                    "none" => Hinting::None,
+                    "slight" => Hinting::Slight,
+                    "medium" => Hinting::Medium,
+                    "full" => Hinting::Full,
+                    _ => bail!("Invalid hinting: {}", value),
+                }
+            }
+            "serif-font" => self.serif_family = family(),
+            "sans-serif-font" => self.sans_serif_family = family(),
+            "monospace-font" => self.monospace_family = family(),
+            "minimum-font-size" => {
+                let size = value
+                    .parse::<f32>()
+                    .with_context(|| format!("Invalid minimum font size: {}", value))?;
+                if !(0.0..=72.0).contains(&size) {
+                    bail!("The minimum font size must be between 0 and 72: {}", value);
+                }
+                self.minimum_font_size = size;
+            }
+            _ => bail!("Unknown text rendering option: {}", name),
+        }
+        Ok(())
+    }
+
+    /// Returns the family used for the family name, which is the configured family if it is a generic
+    /// family, or the name itself otherwise.
+    pub fn resolve_family<'a>(&'a self, name: &'a str) -> &'a str {
+        let family = match name.to_ascii_lowercase().as_str() {
+            "serif" => &self.serif_family,
+            "sans-serif" => &self.sans_serif_family,
+            "monospace" => &self.monospace_family,
+            _ => &None,
+        };
+        family.as_deref().unwrap_or(name)
+    }
+}
+
+/// Replaces the options of the text rendering of the process.
+pub fn set_text_rendering_options(options: TextRenderingOptions) {
+    *OPTIONS.write().unwrap() = Some(options);
+}
+
+/// Returns the options of the text rendering of the process, which are the defaults until they are set.
+pub fn get_text_rendering_options() -> TextRenderingOptions {
+    OPTIONS.read().unwrap().clone().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_options() {
+        let mut options = TextRenderingOptions::default();
+        options.set("antialiasing", "subpixel").unwrap();
+        options.set("hinting", "none").unwrap();
+        options.set("monospace-font", "DejaVu Sans Mono").unwrap();
+        options.set("minimum-font-size", "9.5").unwrap();
+        assert_eq!(
+            options,
+            TextRenderingOptions {
+                antialiasing: Antialiasing::Subpixel,
+                hinting: Hinting::None,
+                monospace_family: Some("DejaVu Sans Mono".to_string()),
+                minimum_font_size: 9.5,
+                ..Default::default()
+            }
+        );
+        assert!(options.set("hinting", "strong").is_err());
+        assert!(options.set("minimum-font-size", "-1").is_err());
+
+        assert_eq!(options.resolve_family("Monospace"), "DejaVu Sans Mono");
+        assert_eq!(options.resolve_family("serif"), "serif");
+        assert_eq!(options.resolve_family("Arial"), "Arial");
+        options.set("monospace-font", "").unwrap();
+        assert_eq!(options.resolve_family("monospace"), "monospace");
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use anyhow::{bail, Context, Result};
+
+use crate::renderer::{escape_string, TextRenderingOptions};
+
+/// The URL of the page which lists the stored settings.
+pub const SETTINGS_PAGE_URL: &str = "about:settings";
+pub const MIN_ZOOM: f64 = 0.25;
+pub const MAX_ZOOM: f64 = 5.0;
+
+/// The preferences of an origin, which are applied when a navigation to it commits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SiteSettings {
+    pub zoom: f64,
+    /// todo: Render the pages in reader mode.
+    pub reader_mode: bool,
+    pub images_disabled: bool,
+    /// todo: Enable the scripting flag of the parser unless this is set, once scripts are executed. Until
+    /// then, the pages are always parsed as if scripting is disabled, e.g. `noscript` elements are rendered.
+    pub scripts_disabled: bool,
+    /// todo: Neither send nor store the cookies of the origin if this is set, once cookies are supported.
+    pub cookies_disabled: bool,
+}
+
+impl Default for SiteSettings {
+    fn default() -> Self {
+        Self {
+            zoom: 1.0,
+            reader_mode: false,
+            images_disabled: false,
+            scripts_disabled: false,
+            cookies_disabled: false,
+        }
+    }
+}
+
+/// A feature which is allowed for all origins by default, and which can be blocked for each origin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    Scripts,
+    Images,
+    Cookies,
+}
+
+impl Permission {
+    pub const ALL: [Self; 3] = [Self::Scripts, Self::Images, Self::Cookies];
+
+    pub fn get_label(&self) -> &'static str {
+        match self {
+            Self::Scripts => "JavaScript",
+            Self::Images => "Images",
+            Self::Cookies => "Cookies",
+        }
+    }
+}
+
+impl SiteSettings {
+    /// Returns the settings with the zoom level changed by `factor`, which is clamped to the
+    /// supported range.
+    pub fn zoomed_by(&self, factor: f64) -> Self {
+        Self {
+            zoom: (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM),
+            ..*self
+        }
+    }
+
+    pub fn is_allowed(&self, permission: Permission) -> bool {
+        !match permission {
+            Permission::Scripts => self.scripts_disabled,
+            Permission::Images => self.images_disabled,
+            Permission::Cookies => self.cookies_disabled,
+        }
+    }
+
+    /// Returns the settings with the permission allowed or blocked.
+    pub fn with_permission(&self, permission: Permission, allowed: bool) -> Self {
+        let mut settings = *self;
+        *match permission {
+            Permission::Scripts => &mut settings.scripts_disabled,
+            Permission::Images => &mut settings.images_disabled,
+            Permission::Cookies => &mut settings.cookies_disabled,
+        } = !allowed;
+        settings
+    }
+}
+
+/// A small store of per-origin settings, which is written to a file each time it is changed.
+///
+/// Each line of the file is an origin followed by its settings, e.g.
+/// `http://example.com zoom=1.5 reader_mode=false images_disabled=true scripts_disabled=false cookies_disabled=true`.
+/// The file can be edited by hand while the browser is closed, where the omitted settings are the default
+/// ones. Origins with the default settings are not stored.
+#[derive(Debug, Default)]
+pub struct SettingsStore {
+    /// The store is kept only in memory if this is `None`.
+    path: Option<PathBuf>,
+    sites: BTreeMap<String, SiteSettings>,
+}
+
+impl SettingsStore {
+    /// Opens the store at `path`, which is created when the settings are first changed.
+    pub fn open(path: &Path) -> Result<Self> {
+        let sites = match fs::read_to_string(path) {
+            Ok(text) => parse_settings(&text)
+                .with_context(|| format!("Failed to parse {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            sites,
+        })
+    }
+
+    /// Returns the default location of the store, which is `$XDG_CONFIG_HOME/pentas/site_settings`
+    /// or `$HOME/.config/pentas/site_settings`.
+    pub fn get_default_path() -> Option<PathBuf> {
+        let config_dir = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_dir.join("pentas").join("site_settings"))
+    }
+
+    pub fn get(&self, origin: &str) -> SiteSettings {
+        self.sites.get(origin).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, origin: &str, settings: SiteSettings) -> Result<()> {
+        if settings == SiteSettings::default() {
+            self.sites.remove(origin);
+        } else {
+            self.sites.insert(origin.to_string(), settings);
+        }
+        self.save()
+    }
+
+    pub fn clear(&mut self, origin: &str) -> Result<()> {
+        self.sites.remove(origin);
+        self.save()
+    }
+
+    pub fn clear_all(&mut self) -> Result<()> {
+        self.sites.clear();
+        self.save()
+    }
+
+    /// Returns the HTML of the settings page, which lists the stored settings and shows whether the forced
+    /// colors mode is on and the options of the text rendering, neither of which is stored.
+    pub fn to_html(&self, forced_colors: bool, text_rendering: &TextRenderingOptions) -> String {
+        let mut html = String::from(
+            "<html><head><title>Site settings</title></head><body><h1>Site settings</h1>",
+        );
+        if self.sites.is_empty() {
+            html.push_str("<p>No site has its own settings.</p>");
+        } else {
+            html.push_str(
+                "<table><tr><th>Origin</th><th>Zoom</th><th>Reader mode</th><th>Images</th><th>JavaScript</th><th>Cookies</th></tr>",
+            );
+            for (origin, settings) in &self.sites {
+                let _ = write!(
+                    html,
+                    "<tr><td>{}</td><td>{}%</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape_string(origin, false),
+                    (settings.zoom * 100.0).round(),
+                    if settings.reader_mode { "on" } else { "off" },
+                    get_permission_state(settings, Permission::Images),
+                    get_permission_state(settings, Permission::Scripts),
+                    get_permission_state(settings, Permission::Cookies),
+                );
+            }
+            html.push_str("</table>");
+        }
+        let _ = write!(
+            html,
+            "<p>Forced colors: {1}. Open {0}?forced-colors={2} to turn them {3}.</p>",
+            SETTINGS_PAGE_URL,
+            if forced_colors { "on" } else { "off" },
+            if forced_colors { "none" } else { "active" },
+            if forced_colors { "off" } else { "on" },
+        );
+        let family = |family: &Option<String>| {
+            family
+                .as_deref()
+                .map_or("system".to_string(), |family| escape_string(family, false))
+        };
+        let _ = write!(
+            html,
+            "<p>Text rendering: antialiasing {1}, hinting {2}, serif font {3}, sans-serif font {4}, monospace font {5}, minimum font size {6}px. Open {0}?&lt;option&gt;=&lt;value&gt; to change them, e.g. {0}?antialiasing=subpixel, {0}?hinting=slight, {0}?serif-font=Noto Serif or {0}?minimum-font-size=9.</p>",
+            SETTINGS_PAGE_URL,
+            text_rendering.antialiasing,
+            text_rendering.hinting,
+            family(&text_rendering.serif_family),
+            family(&text_rendering.sans_serif_family),
+            family(&text_rendering.monospace_family),
+            text_rendering.minimum_font_size,
+        );
+        let _ = write!(
+            html,
+            "<p>Open {0}?clear=&lt;origin&gt; to clear the settings of an origin, or {0}?clear to clear all of them.</p></body></html>",
+            SETTINGS_PAGE_URL
+        );
+        html
+    }
+
+    /// Writes the store to a temporary file first, so that the store is not broken by exiting while writing
+    /// it.
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let mut text = String::new();
+        for (origin, settings) in &self.sites {
+            let _ = writeln!(
+                text,
+                "{} zoom={} reader_mode={} images_disabled={} scripts_disabled={} cookies_disabled={}",
+                origin,
+                settings.zoom,
+                settings.reader_mode,
+                settings.images_disabled,
+                settings.scripts_disabled,
+                settings.cookies_disabled
+            );
+        }
+        let mut temp_path = path.clone().into_os_string();
+        temp_path.push(".tmp");
+        fs::write(&temp_path, text)
+            .and_then(|_| fs::rename(&temp_path, path))
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Returns the origin of the URL in the form of `scheme://host[:port]`, omitting the default port.
+/// URLs without a scheme are treated as HTTP ones, as the toolbar does.
+/// https://html.spec.whatwg.org/multipage/browsers.html#ascii-serialisation-of-an-origin
+pub fn get_origin(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("http", url));
+    let scheme = scheme.to_ascii_lowercase();
+    let default_port = match scheme.as_str() {
+        "http" => 80,
+        "https" => 443,
+        _ => return None,
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let (host, port) = match authority.split_once(':') {
+        Some((host, port)) => (host, port.parse::<u16>().ok()?),
+        None => (authority, default_port),
+    };
+    if host.is_empty() {
+        return None;
+    }
+    let host = host.to_ascii_lowercase();
+    if port == default_port {
+        Some(format!("{}://{}", scheme, host))
+    } else {
+        Some(format!("{}://{}:{}", scheme, host, port))
+    }
+}
+
+fn parse_settings(text: &str) -> Result<BTreeMap<String, SiteSettings>> {
+    let mut sites = BTreeMap::new();
+    for (i, line) in text.lines().enumerate() {
+        let mut fields = line.split_whitespace();
+        let Some(origin) = fields.next() else {
+            continue;
+        };
+        let mut settings = SiteSettings::default();
+        for field in fields {
+            let Some((name, value)) = field.split_once('=') else {
+                bail!("Invalid setting \"{}\" at line {}", field, i + 1);
+            };
+            let invalid_value = || format!("Invalid value of {} at line {}", name, i + 1);
+            match name {
+                "zoom" => {
+                    settings.zoom = value
+                        .parse::<f64>()
+                        .with_context(invalid_value)?
+                        .clamp(MIN_ZOOM, MAX_ZOOM)
+                }
+                "reader_mode" => {
+                    settings.reader_mode = value.parse().with_context(invalid_value)?
+                }
+                "images_disabled" => {
+                    settings.images_disabled = value.parse().with_context(invalid_value)?
+                }
+                "scripts_disabled" => {
+                    settings.scripts_disabled = value.parse().with_context(invalid_value)?
+                }
+                "cookies_disabled" => {
+                    settings.cookies_disabled = value.parse().with_context(invalid_value)?
+                }
+                // Settings added by later versions are ignored.
+                _ => {}
+            }
+        }
+        sites.insert(origin.to_string(), settings);
+    }
+    Ok(sites)
+}
+
+fn get_permission_state(settings: &SiteSettings, permission: Permission) -> &'static str {
+    if settings.is_allowed(permission) {
+        "allowed"
+    } else {
+        "blocked"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origins() {
+        assert_eq!(
+            get_origin("example.com/a/b").as_deref(),
+            Some("http://example.com")
+        );
+        assert_eq!(
+            get_origin("HTTPS://Example.com:443/?q").as_deref(),
+            Some("https://example.com")
+        );
+        assert_eq!(
+            get_origin("http://localhost:8000").as_deref(),
+            Some("http://localhost:8000")
+        );
+        assert_eq!(get_origin(SETTINGS_PAGE_URL), None);
+        assert_eq!(get_origin("file:///index.html"), None);
+    }
+
+    #[test]
+    fn persist_settings() {
+        let path = env::temp_dir().join(format!("pentas-site-settings-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut store = SettingsStore::open(&path).unwrap();
+        assert_eq!(store.get("http://example.com"), SiteSettings::default());
+        let settings = SiteSettings {
+            images_disabled: true,
+            ..SiteSettings::default().zoomed_by(1.5)
+        };
+        store.set("http://example.com", settings).unwrap();
+        store
+            .set(
+                "http://localhost:8000",
+                SiteSettings::default().zoomed_by(100.0),
+            )
+            .unwrap();
+        // Origins with the default settings are not stored.
+        store
+            .set("https://example.org", SiteSettings::default())
+            .unwrap();
+
+        let mut store = SettingsStore::open(&path).unwrap();
+        assert_eq!(store.get("http://example.com"), settings);
+        assert_eq!(store.get("http://localhost:8000").zoom, MAX_ZOOM);
+        let text_rendering = TextRenderingOptions {
+            serif_family: Some("A&B".to_string()),
+            ..Default::default()
+        };
+        let html = store.to_html(false, &text_rendering);
+        assert!(html.contains("hinting system, serif font A&amp;B, sans-serif font system"));
+        assert!(
+            html.contains("<td>http://example.com</td><td>150%</td><td>off</td><td>blocked</td>")
+        );
+        assert!(html.contains("Forced colors: off."));
+        assert!(!html.contains("example.org"));
+
+        store.clear("http://example.com").unwrap();
+        let mut store = SettingsStore::open(&path).unwrap();
+        assert_eq!(store.get("http://example.com"), SiteSettings::default());
+        assert_eq!(store.get("http://localhost:8000").zoom, MAX_ZOOM);
+        store.clear_all().unwrap();
+        assert!(SettingsStore::open(&path).unwrap().sites.is_empty());
+
+        fs::write(&path, "http://example.com zoom=large\n").unwrap();
+        assert!(SettingsStore::open(&path).is_err());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn toggle_permissions() {
+        let path = env::temp_dir().join(format!("pentas-permissions-{}", std::process::id()));
+        // The settings which are not written are the default ones.
+        fs::write(&path, "http://example.com scripts_disabled=true\n").unwrap();
+        let mut store = SettingsStore::open(&path).unwrap();
+        let settings = store.get("http://example.com");
+        assert!(!settings.is_allowed(Permission::Scripts));
+        assert!(
+            settings.is_allowed(Permission::Images) && settings.is_allowed(Permission::Cookies)
+        );
+
+        let settings = settings
+            .with_permission(Permission::Cookies, false)
+            .with_permission(Permission::Scripts, true);
+        assert!(settings.cookies_disabled && !settings.scripts_disabled);
+        store.set("http://example.com", settings).unwrap();
+        assert_eq!(
+            SettingsStore::open(&path)
+                .unwrap()
+                .get("http://example.com"),
+            settings
+        );
+        assert!(store
+            .to_html(false, &TextRenderingOptions::default())
+            .contains("<td>allowed</td><td>allowed</td><td>blocked</td>"));
+        let _ = fs::remove_file(&path);
+    }
+}
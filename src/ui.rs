@@ -1,31 +1,14 @@
+mod accessibility;
+mod browser;
 mod painter;
+mod print;
+mod screenshot;
 mod widgets;
 
-use gtk4::prelude::*;
-use gtk4::{gio, glib, Application};
+pub use browser::Browser;
+pub use painter::set_font_options;
+pub use print::print_pages;
+pub use screenshot::{load_png, paint_to_pixels, save_png};
 
-use crate::app::VerbosityLevel;
-use widgets::window::Window;
-
-const GTK_APP_ID: &str = "app.pentas";
 pub const DEFAULT_WINDOW_WIDTH: i32 = 1200;
 pub const DEFAULT_WINDOW_HEIGHT: i32 = 800;
-
-pub fn show_ui(verbosity: VerbosityLevel) -> glib::ExitCode {
-    gio::resources_register_include!("pentas.gresource").expect("Failed to register resources.");
-    let app = Application::builder().application_id(GTK_APP_ID).build();
-
-    app.connect_activate(move |app| {
-        build_ui(app, verbosity);
-    });
-    // https://github.com/gtk-rs/gtk4-rs/issues/1626
-    app.run_with_args::<glib::GString>(&[])
-}
-
-fn build_ui(app: &Application, verbosity: VerbosityLevel) {
-    let window = Window::new(app);
-    window.set_title(Some("pentas"));
-    window.set_default_size(DEFAULT_WINDOW_WIDTH, DEFAULT_WINDOW_HEIGHT);
-    window.set_verbosity(verbosity);
-    window.present();
-}
@@ -0,0 +1,39 @@
+use gtk4::prelude::*;
+
+#[cfg(feature = "gtk_v4_14")]
+use crate::renderer::Politeness;
+use crate::renderer::{AccessibilityBackend, Announcement};
+
+/// Sends the announcements of the live regions to screen readers through the accessibility API of GTK, e.g.
+/// AT-SPI on Linux. Before GTK 4.14, which added the announcements, the accessible description of the widget
+/// is replaced instead, which screen readers read out when the widget has the focus.
+/// https://docs.gtk.org/gtk4/method.Accessible.announce.html
+#[derive(Debug)]
+pub struct AccessibleAnnouncer {
+    widget: gtk4::Widget,
+}
+
+impl AccessibleAnnouncer {
+    pub fn new(widget: &impl IsA<gtk4::Widget>) -> Self {
+        Self {
+            widget: widget.clone().upcast(),
+        }
+    }
+}
+
+impl AccessibilityBackend for AccessibleAnnouncer {
+    #[cfg(feature = "gtk_v4_14")]
+    fn announce(&self, announcement: &Announcement) {
+        let priority = match announcement.politeness {
+            Politeness::Polite => gtk4::AccessibleAnnouncementPriority::Medium,
+            Politeness::Assertive => gtk4::AccessibleAnnouncementPriority::High,
+        };
+        self.widget.announce(&announcement.text, priority);
+    }
+
+    #[cfg(not(feature = "gtk_v4_14"))]
+    fn announce(&self, announcement: &Announcement) {
+        self.widget
+            .update_property(&[gtk4::accessible::Property::Description(&announcement.text)]);
+    }
+}
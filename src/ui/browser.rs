@@ -0,0 +1,144 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use anyhow::{bail, Context, Result};
+use gtk4::prelude::*;
+use gtk4::{gio, glib, style_context_add_provider_for_display, Application};
+
+use crate::app::VerbosityLevel;
+use crate::devtools::DevToolsServer;
+use crate::net::cache::close_shared_cache;
+use crate::renderer::{cancel_parser_threads, ColorScheme, MediaEnvironment};
+use crate::ui::widgets::window::Window;
+
+const GTK_APP_ID: &str = "app.pentas";
+
+/// The browser, which owns the top-level windows. Each window has its own history and site settings,
+/// while the options given on the command line are shared by all of them.
+pub struct Browser {
+    verbosity: VerbosityLevel,
+    media: Cell<MediaEnvironment>,
+    /// The remote debugging server, whose commands are run on the page shown in the first window.
+    devtools: Cell<Option<DevToolsServer>>,
+    windows: RefCell<Vec<Window>>,
+}
+
+impl Browser {
+    pub fn new(
+        verbosity: VerbosityLevel,
+        media: MediaEnvironment,
+        devtools: Option<DevToolsServer>,
+    ) -> Rc<Self> {
+        Rc::new(Self {
+            verbosity,
+            media: Cell::new(media),
+            devtools: Cell::new(devtools),
+            windows: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Runs the application with a blank window until all the windows are closed, and then shuts down.
+    /// New windows are opened with Ctrl+N, or with a page from the context menu of a link.
+    pub fn run(self: &Rc<Self>) -> Result<()> {
+        gio::resources_register_include!("pentas.gresource")
+            .expect("Failed to register resources.");
+        let app = Application::builder().application_id(GTK_APP_ID).build();
+
+        let browser = Rc::downgrade(self);
+        app.connect_startup(move |app| {
+            if let Some(browser) = browser.upgrade() {
+                browser.on_startup(app);
+            }
+        });
+        let browser = Rc::downgrade(self);
+        app.connect_activate(move |app| {
+            if let Some(browser) = browser.upgrade() {
+                browser.open_window(app, "");
+            }
+        });
+        // https://github.com/gtk-rs/gtk4-rs/issues/1626
+        let exit_code = app.run_with_args::<glib::GString>(&[]);
+        self.shutdown()?;
+        if exit_code != glib::ExitCode::SUCCESS {
+            bail!("The application exited with {:?}", exit_code);
+        }
+        Ok(())
+    }
+
+    /// Flushes the state stored on disk after all the windows are closed. The parser threads of the pages
+    /// which were still loading and their fetches are canceled and joined first, so that nothing is
+    /// written to the cache after it is closed. The site settings are written whenever they change, and
+    /// the history is kept only in memory.
+    /// todo: Flush the cookies and the session once they are stored.
+    fn shutdown(&self) -> Result<()> {
+        self.windows.borrow_mut().clear();
+        cancel_parser_threads();
+        close_shared_cache().context("Failed to close the cache")
+    }
+
+    fn on_startup(self: &Rc<Self>, app: &Application) {
+        // The preferred color scheme follows the theme of GTK.
+        if gtk4::Settings::default().is_some_and(|s| s.is_gtk_application_prefer_dark_theme()) {
+            self.media.set(MediaEnvironment {
+                color_scheme: ColorScheme::Dark,
+                ..self.media.get()
+            });
+        }
+
+        let provider = gtk4::CssProvider::new();
+        provider.load_from_data(
+            "window {
+                background-color: #ffffff;
+            }",
+        );
+        style_context_add_provider_for_display(
+            &gtk4::gdk::Display::default().unwrap(),
+            &provider,
+            gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
+
+        // The action takes the URL of the page to open in the new window, which is blank if it's empty.
+        let new_window = gio::SimpleAction::new("new-window", Some(glib::VariantTy::STRING));
+        let browser = Rc::downgrade(self);
+        new_window.connect_activate(glib::clone!(
+            #[weak]
+            app,
+            move |_, query| {
+                let (Some(browser), Some(query)) =
+                    (browser.upgrade(), query.and_then(|q| q.get::<String>()))
+                else {
+                    return;
+                };
+                browser.open_window(&app, &query);
+            }
+        ));
+        app.add_action(&new_window);
+        app.set_accels_for_action("app.new-window('')", &["<Ctrl>n"]);
+    }
+
+    /// Opens a new window, which loads the page of `query` if it's not empty.
+    fn open_window(self: &Rc<Self>, app: &Application, query: &str) {
+        let media = self.media.get();
+        let window = Window::new(app);
+        window.set_title(Some("pentas"));
+        window.set_default_size(media.viewport_width, media.viewport_height);
+        window.set_verbosity(self.verbosity);
+        window.set_media_environment(media);
+        if let Some(devtools) = self.devtools.take() {
+            window.serve_devtools(devtools);
+        }
+        // The window is dropped from the browser when it's closed, after which the application exits if
+        // it was the last one.
+        let browser = Rc::downgrade(self);
+        window.connect_destroy(move |window| {
+            if let Some(browser) = browser.upgrade() {
+                browser.windows.borrow_mut().retain(|w| w != window);
+            }
+        });
+        self.windows.borrow_mut().push(window.clone());
+        window.present();
+        if !query.is_empty() {
+            window.load(query);
+        }
+    }
+}
@@ -1,10 +1,96 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use gtk4::gdk::prelude::GdkCairoContextExt as _;
+use gtk4::gdk_pixbuf::Pixbuf;
 use gtk4::prelude::*;
-use gtk4::{cairo, pango, DrawingArea};
-use pangocairo::functions::show_layout;
+use gtk4::{cairo, gdk, gio, glib, pango, DrawingArea};
+use indexmap::IndexMap;
+use pangocairo::functions::{context_set_font_options, show_layout};
+
+use crate::net::fetch::ResourceFetcher;
+use crate::net::scheduler::{ResourcePriority, ResourceScheduler};
+use crate::net::url::resolve_url;
+use crate::renderer::color_management::{ColorTransform, RgbColorSpace};
+use crate::renderer::{
+    get_text_rendering_options, Antialiasing, AreaShape, Hinting, PathSegment, RenderObject,
+    TextRenderingOptions,
+};
+
+/// The maximum number of the decoded images kept in `IMAGES`, of which the least recently decoded one is
+/// dropped first.
+const MAX_CACHED_IMAGES: usize = 64;
 
-use crate::renderer::RenderObject;
+/// The URL of an image, the size in pixels at which it's decoded, and whether its aspect ratio is kept.
+type ImageKey = (String, i32, i32, bool);
+
+thread_local! {
+    /// The images decoded and converted to the output color space, which are reused by the following paints
+    /// instead of decoding them and transforming their pixels every time. The images which failed to load
+    /// are kept as `None`.
+    static IMAGES: RefCell<IndexMap<ImageKey, (RgbColorSpace, Option<Pixbuf>)>> =
+        RefCell::new(IndexMap::new());
+
+    /// The base URL of the page whose images are fetched, and the bodies of the images fetched by
+    /// `fetch_images()` by their URLs as written in the page. The images which failed to load are kept as
+    /// `None`.
+    static IMAGE_DATA: RefCell<(String, HashMap<String, Option<glib::Bytes>>)> = RefCell::default();
+}
 
-pub fn paint(canvas: &DrawingArea, objects: &[RenderObject], cairo_ctx: &cairo::Context) {
+/// Fetches the images of the objects which are not fetched yet, whose URLs are resolved against `base_url`.
+/// The images in `viewport` (x, y, width, height) are fetched first, and the lazily loaded ones are not
+/// fetched until they are resumed. The fetched images are dropped once the ones of a page with another
+/// base URL are fetched.
+pub fn fetch_images(
+    objects: &[RenderObject],
+    base_url: &str,
+    fetcher: &dyn ResourceFetcher,
+    viewport: (f64, f64, f64, f64),
+) {
+    IMAGE_DATA.with_borrow_mut(|(current_base_url, data)| {
+        if current_base_url != base_url {
+            *current_base_url = base_url.to_string();
+            data.clear();
+            // The decoded images are keyed by the URLs in the page too.
+            IMAGES.with_borrow_mut(IndexMap::clear);
+        }
+        let mut scheduler = ResourceScheduler::default();
+        for object in objects {
+            let url = match object {
+                RenderObject::Image { src: Some(src), .. } if object.should_fetch() => src,
+                RenderObject::Media {
+                    poster: Some(poster),
+                    ..
+                } => poster,
+                _ => continue,
+            };
+            if !data.contains_key(url) {
+                scheduler.enqueue(url, ResourcePriority::BelowTheFoldImage);
+            }
+        }
+        scheduler.prioritize_visible_images(objects, viewport);
+        for (url, response) in scheduler.fetch_all(|url| fetcher.fetch(&resolve_url(base_url, url)))
+        {
+            if let Err(e) = &response {
+                eprintln!("{:#}", e);
+            }
+            // The image painted as an empty frame before it was fetched is decoded again.
+            IMAGES.with_borrow_mut(|images| images.retain(|key, _| key.0 != url));
+            data.insert(url, response.ok().map(glib::Bytes::from_owned));
+        }
+    });
+}
+
+/// Paints the objects. The images are painted as empty frames if `images_disabled` is true, or until they
+/// are fetched. The images are converted to the `output` color space of the surface.
+pub fn paint(
+    canvas: &DrawingArea,
+    objects: &[RenderObject],
+    cairo_ctx: &cairo::Context,
+    images_disabled: bool,
+    output: &RgbColorSpace,
+) {
+    let text_rendering = get_text_rendering_options();
     for object in objects.iter() {
         match object {
             RenderObject::Text {
@@ -14,68 +100,32 @@ pub fn paint(canvas: &DrawingArea, objects: &[RenderObject], cairo_ctx: &cairo::
                 font_family,
                 font_size,
                 font_weight,
+                font_style,
                 color,
-                decoration_color,
-                decoration_line,
-                decoration_style,
             } => {
                 cairo_ctx.move_to(*x, *y);
 
                 let pango_ctx = canvas.create_pango_context();
+                set_font_options(&pango_ctx, &text_rendering);
                 let layout = pango::Layout::new(&pango_ctx);
                 let attrs = pango::AttrList::new();
 
                 // https://docs.gtk.org/Pango/struct.Color.html
-                let font_color = (
+                attrs.insert(pango::AttrColor::new_foreground(
                     (color.0 * 65535.0) as u16,
                     (color.1 * 65535.0) as u16,
                     (color.2 * 65535.0) as u16,
-                );
-                let deco_color = (
-                    (decoration_color.0 * 65535.0) as u16,
-                    (decoration_color.1 * 65535.0) as u16,
-                    (decoration_color.2 * 65535.0) as u16,
-                );
-
-                attrs.insert(pango::AttrColor::new_foreground(
-                    font_color.0,
-                    font_color.1,
-                    font_color.2,
                 ));
-                if decoration_line.contains(&"underline".to_string()) {
-                    attrs.insert(pango::AttrColor::new_underline_color(
-                        deco_color.0,
-                        deco_color.1,
-                        deco_color.2,
-                    ));
-                    if decoration_style.eq_ignore_ascii_case("double") {
-                        attrs.insert(pango::AttrInt::new_underline(pango::Underline::Double));
-                    } else {
-                        attrs.insert(pango::AttrInt::new_underline(pango::Underline::Single));
-                    }
-                }
-                if decoration_line.contains(&"overline".to_string()) {
-                    attrs.insert(pango::AttrColor::new_overline_color(
-                        deco_color.0,
-                        deco_color.1,
-                        deco_color.2,
-                    ));
-                    attrs.insert(pango::AttrInt::new_overline(pango::Overline::Single));
-                }
-                if decoration_line.contains(&"line-through".to_string()) {
-                    attrs.insert(pango::AttrColor::new_strikethrough_color(
-                        deco_color.0,
-                        deco_color.1,
-                        deco_color.2,
-                    ));
-                    attrs.insert(pango::AttrInt::new_strikethrough(true));
-                }
+                attrs.insert(pango::AttrInt::new_foreground_alpha(
+                    (color.3 * 65535.0) as u16,
+                ));
 
                 layout.set_text(text);
                 layout.set_font_description(Some(&pango::FontDescription::from_string(&format!(
-                    "{} {} {}px",
+                    "{} {} {} {}px",
                     font_family.join(", "),
                     font_weight,
+                    font_style,
                     font_size
                 ))));
                 layout.set_attributes(Some(&attrs));
@@ -96,7 +146,7 @@ pub fn paint(canvas: &DrawingArea, objects: &[RenderObject], cairo_ctx: &cairo::
                     border_radius.3,
                 );
 
-                cairo_ctx.set_source_rgb(color.0, color.1, color.2);
+                cairo_ctx.set_source_rgba(color.0, color.1, color.2, color.3);
                 if (top_left_r, top_right_r, bottom_right_r, bottom_left_r) == (0.0, 0.0, 0.0, 0.0)
                 {
                     cairo_ctx.rectangle(*x, *y, *width, *height);
@@ -151,6 +201,344 @@ pub fn paint(canvas: &DrawingArea, objects: &[RenderObject], cairo_ctx: &cairo::
                     let _ = cairo_ctx.fill();
                 }
             }
+            RenderObject::Media {
+                x,
+                y,
+                width,
+                height,
+                is_video,
+                poster,
+                controls,
+            } => {
+                if *is_video {
+                    cairo_ctx.set_source_rgb(0.0, 0.0, 0.0);
+                    cairo_ctx.rectangle(*x, *y, *width, *height);
+                    let _ = cairo_ctx.fill();
+
+                    if let Some(pixbuf) = poster
+                        .as_ref()
+                        .filter(|_| !images_disabled)
+                        .and_then(|url| load_image(url, *width, *height, true, output))
+                    {
+                        // The poster frame is centered in the box, keeping its aspect ratio.
+                        // https://html.spec.whatwg.org/multipage/rendering.html#video-object-fit
+                        let poster_x = x + (width - pixbuf.width() as f64) / 2.0;
+                        let poster_y = y + (height - pixbuf.height() as f64) / 2.0;
+                        cairo_ctx.set_source_pixbuf(&pixbuf, poster_x, poster_y);
+                        cairo_ctx.rectangle(
+                            poster_x,
+                            poster_y,
+                            pixbuf.width() as f64,
+                            pixbuf.height() as f64,
+                        );
+                        let _ = cairo_ctx.fill();
+                    }
+                }
+
+                if *controls {
+                    paint_media_controls(cairo_ctx, *x, *y, *width, *height);
+                }
+            }
+            RenderObject::Image {
+                x,
+                y,
+                width,
+                height,
+                src,
+                ..
+            } => {
+                let should_fetch = !images_disabled && object.should_fetch();
+                if let Some(pixbuf) = src
+                    .as_ref()
+                    .filter(|_| should_fetch)
+                    .and_then(|url| load_image(url, *width, *height, false, output))
+                {
+                    cairo_ctx.set_source_pixbuf(&pixbuf, *x, *y);
+                    cairo_ctx.rectangle(*x, *y, *width, *height);
+                    let _ = cairo_ctx.fill();
+                } else {
+                    // Images which can't be loaded are painted as empty frames.
+                    cairo_ctx.set_source_rgb(0.6, 0.6, 0.6);
+                    cairo_ctx.set_line_width(1.0);
+                    cairo_ctx.rectangle(x + 0.5, y + 0.5, width - 1.0, height - 1.0);
+                    let _ = cairo_ctx.stroke();
+                }
+            }
+            RenderObject::Path {
+                segments,
+                fill,
+                even_odd,
+                stroke,
+                stroke_width,
+                clip: (clip_x, clip_y, clip_width, clip_height),
+            } => {
+                let _ = cairo_ctx.save();
+                cairo_ctx.rectangle(*clip_x, *clip_y, *clip_width, *clip_height);
+                cairo_ctx.clip();
+
+                for segment in segments {
+                    match *segment {
+                        PathSegment::MoveTo(x, y) => cairo_ctx.move_to(x, y),
+                        PathSegment::LineTo(x, y) => cairo_ctx.line_to(x, y),
+                        PathSegment::CurveTo(x1, y1, x2, y2, x, y) => {
+                            cairo_ctx.curve_to(x1, y1, x2, y2, x, y)
+                        }
+                        PathSegment::ClosePath => cairo_ctx.close_path(),
+                    }
+                }
+                if let Some((r, g, b, a)) = fill {
+                    cairo_ctx.set_source_rgba(*r, *g, *b, *a);
+                    cairo_ctx.set_fill_rule(if *even_odd {
+                        cairo::FillRule::EvenOdd
+                    } else {
+                        cairo::FillRule::Winding
+                    });
+                    let _ = cairo_ctx.fill_preserve();
+                }
+                if let Some((r, g, b, a)) = stroke {
+                    cairo_ctx.set_source_rgba(*r, *g, *b, *a);
+                    cairo_ctx.set_line_width(*stroke_width);
+                    let _ = cairo_ctx.stroke_preserve();
+                }
+                cairo_ctx.new_path();
+                let _ = cairo_ctx.restore();
+            }
+            RenderObject::Clip {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let _ = cairo_ctx.save();
+                cairo_ctx.rectangle(*x, *y, *width, *height);
+                cairo_ctx.clip();
+            }
+            RenderObject::EndClip => {
+                let _ = cairo_ctx.restore();
+            }
         }
     }
 }
+
+/// Loads the local image at the size, and converts its colors from its embedded ICC profile to the color
+/// space of the output, so that the photos in wide-gamut color spaces are not painted washed out.
+/// Images without a profile are assumed to be in sRGB.
+/// https://www.w3.org/TR/css-color-4/#untagged
+/// Applies the antialiasing and the hinting of the options to the context with which the text is laid out
+/// or painted. The context keeps the options of the system if neither of them is set.
+pub fn set_font_options(pango_ctx: &pango::Context, options: &TextRenderingOptions) {
+    if (options.antialiasing, options.hinting) == (Antialiasing::System, Hinting::System) {
+        return;
+    }
+    let Ok(mut font_options) = cairo::FontOptions::new() else {
+        return;
+    };
+    font_options.set_antialias(match options.antialiasing {
+        Antialiasing::System => cairo::Antialias::Default,
+        Antialiasing::None => cairo::Antialias::None,
+        Antialiasing::Grayscale => cairo::Antialias::Gray,
+        Antialiasing::Subpixel => cairo::Antialias::Subpixel,
+    });
+    font_options.set_hint_style(match options.hinting {
+        Hinting::System => cairo::HintStyle::Default,
+        Hinting::None => cairo::HintStyle::None,
+        Hinting::Slight => cairo::HintStyle::Slight,
+        Hinting::Medium => cairo::HintStyle::Medium,
+        Hinting::Full => cairo::HintStyle::Full,
+    });
+    // The unhinted metrics keep the fractional advances, so that the layout doesn't depend on the pixel grid.
+    font_options.set_hint_metrics(match options.hinting {
+        Hinting::System => cairo::HintMetrics::Default,
+        Hinting::None => cairo::HintMetrics::Off,
+        _ => cairo::HintMetrics::On,
+    });
+    context_set_font_options(pango_ctx, Some(&font_options));
+}
+
+/// Returns the color space of the ICC profile which colord assigns to the monitor, or `None` if colord is
+/// not running or the monitor has no profile, in which case the monitor is assumed to be sRGB.
+/// https://www.freedesktop.org/software/colord/gtk-doc/ref-dbus.html
+pub fn get_monitor_color_space(monitor: &gdk::Monitor) -> Option<RgbColorSpace> {
+    let connector = monitor.connector()?;
+    let bus = gio::bus_get_sync(gio::BusType::System, gio::Cancellable::NONE).ok()?;
+    let get_proxy = |path: &str, interface: &str| {
+        gio::DBusProxy::new_sync(
+            &bus,
+            gio::DBusProxyFlags::NONE,
+            None,
+            Some("org.freedesktop.ColorManager"),
+            path,
+            interface,
+            gio::Cancellable::NONE,
+        )
+        .ok()
+    };
+    // The devices of the monitors are found by the names of their connectors.
+    let (device,) = get_proxy(
+        "/org/freedesktop/ColorManager",
+        "org.freedesktop.ColorManager",
+    )?
+    .call_sync(
+        "FindDeviceByProperty",
+        Some(&("XRANDR_name", connector.as_str()).to_variant()),
+        gio::DBusCallFlags::NONE,
+        1000,
+        gio::Cancellable::NONE,
+    )
+    .ok()?
+    .get::<(glib::variant::ObjectPath,)>()?;
+    // The default profile of the device comes first.
+    let profiles = get_proxy(device.as_str(), "org.freedesktop.ColorManager.Device")?
+        .cached_property("Profiles")?
+        .get::<Vec<glib::variant::ObjectPath>>()?;
+    let filename = get_proxy(
+        profiles.first()?.as_str(),
+        "org.freedesktop.ColorManager.Profile",
+    )?
+    .cached_property("Filename")?
+    .get::<String>()?;
+    RgbColorSpace::from_icc_profile(&std::fs::read(filename).ok()?).ok()
+}
+
+/// Returns the image decoded at the size and converted to the output color space, which is cached until
+/// it's decoded for another color space.
+fn load_image(
+    url: &str,
+    width: f64,
+    height: f64,
+    preserve_aspect_ratio: bool,
+    output: &RgbColorSpace,
+) -> Option<Pixbuf> {
+    let key = (
+        url.to_string(),
+        width as i32,
+        height as i32,
+        preserve_aspect_ratio,
+    );
+    if let Some((space, pixbuf)) = IMAGES.with_borrow(|images| images.get(&key).cloned()) {
+        if space == *output {
+            return pixbuf;
+        }
+    }
+    let pixbuf = decode_image(url, width, height, preserve_aspect_ratio, output);
+    IMAGES.with_borrow_mut(|images| {
+        if images.len() >= MAX_CACHED_IMAGES && !images.contains_key(&key) {
+            images.shift_remove_index(0);
+        }
+        images.insert(key, (output.clone(), pixbuf.clone()));
+    });
+    pixbuf
+}
+
+/// Decodes the image fetched by `fetch_images()`, and converts it from the color space of its embedded ICC profile, or sRGB if it has
+/// none, to the output color space.
+fn decode_image(
+    url: &str,
+    width: f64,
+    height: f64,
+    preserve_aspect_ratio: bool,
+    output: &RgbColorSpace,
+) -> Option<Pixbuf> {
+    let bytes = IMAGE_DATA.with_borrow(|(_, data)| data.get(url).cloned().flatten())?;
+    let pixbuf = Pixbuf::from_stream_at_scale(
+        &gio::MemoryInputStream::from_bytes(&bytes),
+        width as i32,
+        height as i32,
+        preserve_aspect_ratio,
+        gio::Cancellable::NONE,
+    )
+    .ok()?;
+    // The PNG and JPEG loaders of GdkPixbuf expose the profile as a base64 string.
+    let source = pixbuf
+        .option("icc-profile")
+        .and_then(|profile| RgbColorSpace::from_icc_profile(&glib::base64_decode(&profile)).ok())
+        .unwrap_or(RgbColorSpace::SRGB);
+    let Some(transform) = ColorTransform::new(&source, output)
+        .ok()
+        .filter(|_| source != *output)
+    else {
+        return Some(pixbuf);
+    };
+    let mut pixels = pixbuf.read_pixel_bytes().to_vec();
+    transform.transform_pixels(
+        &mut pixels,
+        pixbuf.width() as usize,
+        pixbuf.n_channels() as usize,
+        pixbuf.rowstride() as usize,
+    );
+    Some(Pixbuf::from_bytes(
+        &glib::Bytes::from_owned(pixels),
+        pixbuf.colorspace(),
+        pixbuf.has_alpha(),
+        pixbuf.bits_per_sample(),
+        pixbuf.width(),
+        pixbuf.height(),
+        pixbuf.rowstride(),
+    ))
+}
+
+/// Paints the focus ring of an image map area. `x` and `y` are the position of the image.
+pub fn paint_area_focus_ring(
+    cairo_ctx: &cairo::Context,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    shape: &AreaShape,
+) {
+    cairo_ctx.set_source_rgb(0.0, 0.37, 0.8);
+    cairo_ctx.set_line_width(2.0);
+    cairo_ctx.set_dash(&[4.0, 2.0], 0.0);
+    match shape {
+        AreaShape::Circle {
+            x: cx,
+            y: cy,
+            radius,
+        } => {
+            cairo_ctx.new_sub_path();
+            cairo_ctx.arc(x + cx, y + cy, *radius, 0.0, 2.0 * std::f64::consts::PI);
+        }
+        AreaShape::Polygon(points) => {
+            for (i, (px, py)) in points.iter().enumerate() {
+                if i == 0 {
+                    cairo_ctx.move_to(x + px, y + py);
+                } else {
+                    cairo_ctx.line_to(x + px, y + py);
+                }
+            }
+            cairo_ctx.close_path();
+        }
+        AreaShape::Default | AreaShape::Rectangle { .. } => {
+            let (left, top, w, h) = shape.get_bounding_box(width, height);
+            cairo_ctx.rectangle(x + left, y + top, w, h);
+        }
+    }
+    let _ = cairo_ctx.stroke();
+    cairo_ctx.set_dash(&[], 0.0);
+}
+
+/// Paints the media controls at the bottom of the box, which only consist of a play button and a timeline.
+fn paint_media_controls(cairo_ctx: &cairo::Context, x: f64, y: f64, width: f64, height: f64) {
+    const CONTROLS_HEIGHT: f64 = 54.0;
+    let bar_height = CONTROLS_HEIGHT.min(height);
+    let bar_y = y + height - bar_height;
+
+    cairo_ctx.set_source_rgb(0.94, 0.95, 0.96);
+    cairo_ctx.rectangle(x, bar_y, width, bar_height);
+    let _ = cairo_ctx.fill();
+
+    // play button
+    cairo_ctx.set_source_rgb(0.0, 0.0, 0.0);
+    cairo_ctx.move_to(x + 16.0, bar_y + bar_height * 0.35);
+    cairo_ctx.line_to(x + 16.0, bar_y + bar_height * 0.65);
+    cairo_ctx.line_to(x + 16.0 + bar_height * 0.26, bar_y + bar_height * 0.5);
+    cairo_ctx.close_path();
+    let _ = cairo_ctx.fill();
+
+    // timeline
+    if width > 64.0 {
+        cairo_ctx.set_source_rgb(0.6, 0.6, 0.6);
+        cairo_ctx.rectangle(x + 48.0, bar_y + bar_height * 0.5 - 2.0, width - 64.0, 4.0);
+        let _ = cairo_ctx.fill();
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::Context as _;
+use gtk4::prelude::*;
+use gtk4::{DrawingArea, PageSetup, PaperSize, PrintOperation, PrintOperationAction, Unit, Window};
+
+use crate::renderer::color_management::RgbColorSpace;
+use crate::renderer::{PageSettings, RenderObjects};
+use crate::ui::painter::paint;
+
+/// The number of points in a CSS pixel, since 1in is 72pt and 96px.
+const POINTS_PER_PX: f64 = 0.75;
+
+/// Prints the objects rendered for printing, splitting them into the pages of the settings. The print dialog
+/// is shown on the window, or the pages are exported to the PDF file without the dialog if `export_path` is
+/// given.
+/// todo: Wait for the lazily loaded images, which are not fetched yet when the pages are painted.
+pub fn print_pages(
+    objects: &RenderObjects,
+    page_settings: &PageSettings,
+    parent: Option<&Window>,
+    export_path: Option<&str>,
+) -> anyhow::Result<()> {
+    let to_points = |px: f32| px as f64 * POINTS_PER_PX;
+    let page_setup = PageSetup::new();
+    page_setup.set_paper_size(&PaperSize::new_custom(
+        "pentas",
+        "pentas",
+        to_points(page_settings.width),
+        to_points(page_settings.height),
+        Unit::Points,
+    ));
+    let (top, right, bottom, left) = page_settings.margin;
+    page_setup.set_top_margin(to_points(top), Unit::Points);
+    page_setup.set_right_margin(to_points(right), Unit::Points);
+    page_setup.set_bottom_margin(to_points(bottom), Unit::Points);
+    page_setup.set_left_margin(to_points(left), Unit::Points);
+
+    let operation = PrintOperation::new();
+    operation.set_default_page_setup(Some(&page_setup));
+    // The origin of the drawing is at the corner of the page area, inside the margins.
+    operation.set_use_full_page(false);
+    operation.set_unit(Unit::Points);
+    operation.set_n_pages(page_settings.get_page_count(objects.max_height) as i32);
+
+    let canvas = DrawingArea::new();
+    let list = objects.list.clone();
+    let (page_width, page_height) = page_settings.get_page_area();
+    let (page_width, page_height) = (page_width as f64, page_height as f64);
+    operation.connect_draw_page(move |_, context, page_number| {
+        let ctx = context.cairo_context();
+        ctx.scale(POINTS_PER_PX, POINTS_PER_PX);
+        ctx.rectangle(0.0, 0.0, page_width, page_height);
+        ctx.clip();
+        let top = page_number as f64 * page_height;
+        ctx.translate(0.0, -top);
+        paint(&canvas, &list, &ctx, false, &RgbColorSpace::SRGB);
+    });
+
+    let action = match export_path {
+        Some(path) => {
+            operation.set_export_filename(path);
+            PrintOperationAction::Export
+        }
+        None => PrintOperationAction::PrintDialog,
+    };
+    operation
+        .run(action, parent)
+        .context("Failed to print the page")?;
+    Ok(())
+}
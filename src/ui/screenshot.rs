@@ -0,0 +1,63 @@
+use std::path::Path;
+
+use anyhow::Context as _;
+use gtk4::prelude::*;
+use gtk4::{cairo, gdk, gio, glib, DrawingArea};
+
+use crate::renderer::color_management::RgbColorSpace;
+use crate::renderer::RenderObjects;
+use crate::ui::painter::paint;
+
+/// Paints the objects on a white canvas of the size without a window, and returns the pixels in the layout
+/// of cairo's ARGB32 format, i.e. premultiplied BGRA on little-endian machines, in rows of `width * 4` bytes.
+pub fn paint_to_pixels(
+    objects: &RenderObjects,
+    width: i32,
+    height: i32,
+) -> anyhow::Result<Vec<u8>> {
+    let mut surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+    {
+        let ctx = cairo::Context::new(&surface)?;
+        ctx.set_source_rgb(1.0, 1.0, 1.0);
+        ctx.paint()?;
+        paint(
+            &DrawingArea::new(),
+            &objects.list,
+            &ctx,
+            false,
+            &RgbColorSpace::SRGB,
+        );
+    }
+    surface.flush();
+    let stride = surface.stride() as usize;
+    let data = surface.data()?;
+    Ok(data
+        .chunks(stride)
+        .flat_map(|row| &row[..width as usize * 4])
+        .copied()
+        .collect())
+}
+
+/// Writes the pixels returned by `paint_to_pixels()` to a PNG file.
+pub fn save_png(pixels: &[u8], width: i32, height: i32, path: &Path) -> anyhow::Result<()> {
+    gdk::MemoryTexture::new(
+        width,
+        height,
+        gdk::MemoryFormat::B8g8r8a8Premultiplied,
+        &glib::Bytes::from(pixels),
+        width as usize * 4,
+    )
+    .save_to_png(path)
+    .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Reads a PNG file into the pixels in the same layout as `paint_to_pixels()`, with the size of the image.
+pub fn load_png(path: &Path) -> anyhow::Result<(Vec<u8>, (i32, i32))> {
+    let texture = gdk::Texture::from_file(&gio::File::for_path(path))
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let (width, height) = (texture.width(), texture.height());
+    let mut pixels = vec![0; width as usize * height as usize * 4];
+    // The pixels are downloaded in the format of `GDK_MEMORY_DEFAULT`, which is premultiplied BGRA.
+    texture.download(&mut pixels, width as usize * 4);
+    Ok((pixels, (width, height)))
+}
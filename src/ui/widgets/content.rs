@@ -1,27 +1,61 @@
+use std::time::Duration;
 use std::vec;
 
-use gtk4::glib;
+use anyhow::Context as _;
 use gtk4::prelude::*;
 use gtk4::subclass::prelude::ObjectSubclassIsExt;
+use gtk4::{gdk, gio, glib, pango};
+use serde_json::{json, Value};
 
 use crate::app::VerbosityLevel;
-use crate::net::http::HttpClient;
-use crate::renderer::get_render_objects;
+use crate::devtools::{DevToolsCommand, DevToolsServer};
+use crate::net::cache::{get_shared_cache, lock, CACHE_PAGE_URL};
+use crate::net::fetch::{fetch_http, post_http, Cancellable, DefaultFetcher};
+use crate::net::url::{resolve_url, QueryString};
+use crate::renderer::color_management::RgbColorSpace;
+use crate::renderer::{
+    decode_html, get_text_rendering_options, get_timer_delay, is_caret_visible,
+    set_text_rendering_options, Activation, Editor, FormSubmission, MediaEnvironment, PageMetadata,
+    ParsedDocument, PendingDocument, RenderObjects, SharePreview, VisibilityState,
+    CARET_BLINK_TIMEOUT,
+};
+use crate::settings::{get_origin, Permission, SiteSettings, SETTINGS_PAGE_URL};
+use crate::ui::accessibility::AccessibleAnnouncer;
+use crate::ui::painter::{fetch_images, get_monitor_color_space, paint};
+use crate::ui::{print_pages, set_font_options};
+
+/// The factor by which the zoom level is changed with Ctrl+Plus and Ctrl+Minus.
+const ZOOM_STEP: f64 = 1.1;
+
+/// The distance in pixels by which the scroll containers are scrolled for a step of the mouse wheel.
+/// todo: Scroll by the deltas as they are for the touchpads, which report them in pixels.
+const SCROLL_STEP: f64 = 40.0;
+
+/// The interval at which the document parsed on the worker thread is checked for completion.
+const PARSE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The interval at which the commands of the remote debugging clients are checked.
+const DEVTOOLS_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 mod imp {
-    use std::cell::RefCell;
+    use std::cell::{Cell, RefCell};
     use std::sync::OnceLock;
 
     use glib::subclass::InitializingObject;
     use gtk4::glib::subclass::Signal;
     use gtk4::prelude::*;
     use gtk4::subclass::prelude::*;
-    use gtk4::{glib, CompositeTemplate};
+    use gtk4::{gdk, glib, CompositeTemplate};
 
     use crate::app::VerbosityLevel;
     use crate::history::History;
-    use crate::renderer::RenderObjects;
-    use crate::ui::painter::paint;
+    use crate::renderer::color_management::RgbColorSpace;
+    use crate::renderer::{
+        AreaIndex, Editor, MediaEnvironment, ParsedDocument, RenderObject, RenderObjects,
+        VisibilityState,
+    };
+    use crate::settings::{SettingsStore, SiteSettings};
+    use crate::ui::painter::{paint, paint_area_focus_ring};
 
     // "/pentas" is just a prefix. See resouces.gresource.xml
     #[derive(Debug, CompositeTemplate, Default)]
@@ -36,6 +70,44 @@ mod imp {
 
         pub history: RefCell<History>,
         pub verbosity: RefCell<VerbosityLevel>,
+        /// The environment in which the pages are rendered, e.g. whether the colors are forced.
+        pub media: RefCell<MediaEnvironment>,
+        /// The document of the current page, which is rendered again when the `@media` rules which apply to
+        /// it change. This is `None` after traversing the history to another document.
+        pub document: RefCell<Option<ParsedDocument>>,
+        /// The image map area which has the keyboard focus.
+        pub focused_area: RefCell<Option<AreaIndex>>,
+        pub settings: RefCell<SettingsStore>,
+        /// The settings of the origin of the current page.
+        pub site_settings: RefCell<SiteSettings>,
+        /// The timer of the navigation scheduled by the `meta` refresh of the current page.
+        pub refresh_timer: RefCell<Option<glib::SourceId>>,
+        /// The URL of the `meta` refresh whose timer fired while the page was hidden, which is navigated to
+        /// once the page is visible again.
+        pub deferred_refresh: RefCell<Option<String>>,
+        pub visibility: Cell<VisibilityState>,
+        /// The timer which polls the document of the navigation being parsed on the worker thread.
+        pub parse_timer: RefCell<Option<glib::SourceId>>,
+        /// The editor of the focused editing host, into which the text is typed and pasted.
+        /// todo: Focus the form fields too.
+        pub editor: RefCell<Option<Editor>>,
+        /// The keys typed into the canvas, which are passed to the input method while an editor is focused.
+        pub keys: gtk4::EventControllerKey,
+        /// The input method with which the text is composed into the editor, e.g. the CJK text.
+        pub im_context: gtk4::IMMulticontext,
+        /// Whether the input method has started a composition, whose text is committed as an undo step of
+        /// its own.
+        pub is_composing: Cell<bool>,
+        /// The monotonic time of the last input into the editor in microseconds, which the caret blinks from.
+        pub last_input_time: Cell<i64>,
+        pub is_caret_visible: Cell<bool>,
+        /// The callback which blinks the caret on the frame clock until it stops blinking.
+        pub caret_blink: RefCell<Option<gtk4::TickCallbackId>>,
+        /// The position of the pointer on the canvas, or `None` if it's outside the canvas.
+        pub pointer: Cell<Option<(f64, f64)>>,
+        /// The color space of the monitor which the window is on, to which the images are converted. This is
+        /// `None` if the monitor has no color profile, in which case it's assumed to be sRGB.
+        pub output_color_space: RefCell<Option<RgbColorSpace>>,
     }
 
     #[glib::object_subclass]
@@ -46,6 +118,18 @@ mod imp {
 
         fn class_init(klass: &mut Self::Class) {
             klass.bind_template();
+            // The suggestion chosen in the context menu, with the position of the misspelled word.
+            klass.install_action(
+                "content.replace-misspelled-word",
+                Some(&<(String, f64, f64)>::static_variant_type()),
+                |obj, _, parameter| {
+                    if let Some((replacement, x, y)) =
+                        parameter.and_then(|p| p.get::<(String, f64, f64)>())
+                    {
+                        obj.replace_misspelled_word(x, y, &replacement);
+                    }
+                },
+            );
         }
 
         fn instance_init(obj: &InitializingObject<Self>) {
@@ -62,55 +146,48 @@ mod imp {
                 #[strong]
                 obj,
                 move |_, ctx, _, _| {
+                    let site_settings = *obj.imp().site_settings.borrow();
                     // Adjust the width and the height of the canvas for scrolling.
                     // Note: Each time the canvas is resized, this closure is called.
-                    if obj.imp().canvas.width()
-                        != obj
-                            .imp()
-                            .history
-                            .borrow()
-                            .get_current()
-                            .unwrap()
-                            .objects
-                            .max_width
-                            .round() as i32
-                    {
-                        obj.imp().canvas.set_width_request(
-                            obj.imp()
-                                .history
-                                .borrow()
-                                .get_current()
-                                .unwrap()
-                                .objects
-                                .max_width
-                                .round() as i32,
-                        );
+                    let (width, height) = {
+                        let history = obj.imp().history.borrow();
+                        let objects = &history.get_current().unwrap().objects;
+                        let zoom = site_settings.zoom as f32;
+                        (
+                            (objects.max_width * zoom).round() as i32,
+                            (objects.max_height * zoom).round() as i32 + 5,
+                        )
+                    };
+                    if obj.imp().canvas.width() != width {
+                        obj.imp().canvas.set_width_request(width);
                     }
-                    if obj.imp().canvas.height()
-                        != obj
-                            .imp()
-                            .history
-                            .borrow()
-                            .get_current()
-                            .unwrap()
-                            .objects
-                            .max_height
-                            .round() as i32
-                            + 5
-                    {
-                        obj.imp().canvas.set_height_request(
-                            obj.imp()
-                                .history
-                                .borrow()
-                                .get_current()
-                                .unwrap()
-                                .objects
-                                .max_height
-                                .round() as i32
-                                + 5,
+                    if obj.imp().canvas.height() != height {
+                        obj.imp().canvas.set_height_request(height);
+                    }
+
+                    // The visible area of the canvas in the coordinates of the page.
+                    let viewport = {
+                        let zoom = site_settings.zoom;
+                        let (h, v) = (
+                            obj.imp().viewport.hadjustment(),
+                            obj.imp().viewport.vadjustment(),
                         );
+                        (
+                            h.as_ref().map_or(0.0, |h| h.value()) / zoom,
+                            v.as_ref().map_or(0.0, |v| v.value()) / zoom,
+                            h.as_ref().map_or(width as f64, |h| h.page_size()) / zoom,
+                            v.as_ref().map_or(height as f64, |v| v.page_size()) / zoom,
+                        )
+                    };
+
+                    if !site_settings.images_disabled {
+                        obj.update_lazy_loading(viewport);
+                        obj.fetch_images(viewport);
                     }
 
+                    // The page is zoomed without relayout.
+                    ctx.scale(site_settings.zoom, site_settings.zoom);
+                    // The fixed and sticky boxes are moved along with the scroll position.
                     paint(
                         &obj.imp().canvas.get(),
                         &obj.imp()
@@ -119,11 +196,157 @@ mod imp {
                             .get_current()
                             .unwrap()
                             .objects
-                            .list,
+                            .get_scrolled_list(viewport),
                         ctx,
-                    )
+                        site_settings.images_disabled,
+                        obj.imp()
+                            .output_color_space
+                            .borrow()
+                            .as_ref()
+                            .unwrap_or(&RgbColorSpace::SRGB),
+                    );
+
+                    if let Some((i, j)) = *obj.imp().focused_area.borrow() {
+                        if let Some(RenderObject::Image {
+                            x,
+                            y,
+                            width,
+                            height,
+                            areas,
+                            ..
+                        }) = obj
+                            .imp()
+                            .history
+                            .borrow()
+                            .get_current()
+                            .unwrap()
+                            .objects
+                            .list
+                            .get(i)
+                        {
+                            paint_area_focus_ring(ctx, *x, *y, *width, *height, &areas[j].shape);
+                        }
+                    }
+                    obj.paint_caret(ctx);
                 }
             ));
+            // The images are painted in the color space of the monitor which the window is on.
+            self.canvas.connect_realize(glib::clone!(
+                #[strong]
+                obj,
+                move |_| obj.watch_output_color_space()
+            ));
+
+            // Image map areas are activated by clicks, and can be focused with the keyboard.
+            self.canvas.set_focusable(true);
+            let click = gtk4::GestureClick::new();
+            click.connect_pressed(glib::clone!(
+                #[strong]
+                obj,
+                move |_, _, x, y| obj.on_canvas_press(x, y)
+            ));
+            click.connect_released(glib::clone!(
+                #[strong]
+                obj,
+                move |_, _, x, y| obj.on_canvas_click(x, y)
+            ));
+            self.canvas.add_controller(click);
+            // The primary selection is pasted with a middle click, as is conventional on X11.
+            let middle_click = gtk4::GestureClick::new();
+            middle_click.set_button(gdk::BUTTON_MIDDLE);
+            middle_click.connect_released(glib::clone!(
+                #[strong]
+                obj,
+                move |_, _, _, _| obj.paste_from_clipboard(&obj.primary_clipboard())
+            ));
+            self.canvas.add_controller(middle_click);
+            // The hyperlinks can be opened in a new window from the context menu.
+            let secondary_click = gtk4::GestureClick::new();
+            secondary_click.set_button(gdk::BUTTON_SECONDARY);
+            secondary_click.connect_pressed(glib::clone!(
+                #[strong]
+                obj,
+                move |_, _, x, y| obj.on_canvas_context_menu(x, y)
+            ));
+            self.canvas.add_controller(secondary_click);
+            // The element under the pointer is in the hover state, which is matched by `:hover`.
+            let motion = gtk4::EventControllerMotion::new();
+            motion.connect_motion(glib::clone!(
+                #[strong]
+                obj,
+                move |_, x, y| obj.on_canvas_motion(Some((x, y)))
+            ));
+            motion.connect_leave(glib::clone!(
+                #[strong]
+                obj,
+                move |_| obj.on_canvas_motion(None)
+            ));
+            self.canvas.add_controller(motion);
+            // The scroll containers under the pointer are scrolled before the page.
+            let scroll =
+                gtk4::EventControllerScroll::new(gtk4::EventControllerScrollFlags::BOTH_AXES);
+            scroll.connect_scroll(glib::clone!(
+                #[strong]
+                obj,
+                move |_, dx, dy| obj.on_canvas_scroll(dx, dy)
+            ));
+            self.canvas.add_controller(scroll);
+            self.keys.connect_key_pressed(glib::clone!(
+                #[strong]
+                obj,
+                move |_, key, _, state| obj.on_canvas_key_press(key, state)
+            ));
+            self.canvas.add_controller(self.keys.clone());
+            // The text is typed into the focused editor through the input method, which shows the text being
+            // composed as a preedit until a candidate is committed.
+            self.im_context.set_client_widget(Some(&*self.canvas));
+            self.im_context.connect_preedit_start(glib::clone!(
+                #[strong]
+                obj,
+                move |_| obj.imp().is_composing.set(true)
+            ));
+            self.im_context.connect_preedit_changed(glib::clone!(
+                #[strong]
+                obj,
+                move |im_context| obj.on_preedit_changed(im_context)
+            ));
+            self.im_context.connect_commit(glib::clone!(
+                #[strong]
+                obj,
+                move |_, text| obj.on_im_commit(text)
+            ));
+
+            // The lazily loaded images are fetched as they approach the viewport on scrolling.
+            if let Some(vadjustment) = self.viewport.vadjustment() {
+                vadjustment.connect_value_changed(glib::clone!(
+                    #[strong]
+                    obj,
+                    move |_| obj.imp().paint()
+                ));
+            }
+            // The page size of the adjustments is the size of the viewport, which changes on resize.
+            for adjustment in [self.viewport.hadjustment(), self.viewport.vadjustment()]
+                .into_iter()
+                .flatten()
+            {
+                adjustment.connect_notify_local(
+                    Some("page-size"),
+                    glib::clone!(
+                        #[strong]
+                        obj,
+                        move |_, _| obj.on_viewport_resize()
+                    ),
+                );
+            }
+
+            if let Some(path) = SettingsStore::get_default_path() {
+                match SettingsStore::open(&path) {
+                    Ok(store) => {
+                        self.settings.replace(store);
+                    }
+                    Err(e) => eprintln!("{:#}", e),
+                }
+            }
 
             // The initial history is a blank page.
             self.history.borrow_mut().add(
@@ -132,16 +355,44 @@ mod imp {
                     list: vec![],
                     max_width: self.canvas.width() as f32,
                     max_height: self.canvas.height() as f32,
+                    ..Default::default()
                 },
             );
         }
 
+        /// Cancels the navigation being parsed and the scheduled refresh when the window is closed, so that
+        /// nothing is loaded into the closed window.
+        fn dispose(&self) {
+            if let Some(timer) = self.parse_timer.take() {
+                timer.remove();
+            }
+            if let Some(timer) = self.refresh_timer.take() {
+                timer.remove();
+            }
+        }
+
         fn signals() -> &'static [glib::subclass::Signal] {
             static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
             SIGNALS.get_or_init(|| {
-                vec![Signal::builder("history-updated")
-                    .param_types([glib::Type::STRING, glib::Type::BOOL, glib::Type::BOOL])
-                    .build()]
+                vec![
+                    Signal::builder("history-updated")
+                        .param_types([glib::Type::STRING, glib::Type::BOOL, glib::Type::BOOL])
+                        .build(),
+                    // Emitted with the state of the entry when the history is traversed within a page.
+                    // https://html.spec.whatwg.org/multipage/nav-history-apis.html#event-popstate
+                    Signal::builder("popstate")
+                        .param_types([glib::Type::STRING])
+                        .build(),
+                    // Emitted with the new title when the title of the current page changes, e.g. when its
+                    // `title` element is changed.
+                    Signal::builder("title-changed")
+                        .param_types([glib::Type::STRING])
+                        .build(),
+                    // Emitted with whether the page has an origin, to which the settings apply.
+                    Signal::builder("site-settings-changed")
+                        .param_types([glib::Type::BOOL])
+                        .build(),
+                ]
             })
         }
     }
@@ -150,8 +401,12 @@ mod imp {
     impl BoxImpl for ContentArea {}
 
     impl ContentArea {
-        /// Paints all added objects.
+        /// Paints all added objects. The hidden page is not painted, and it is painted once it is visible
+        /// again.
         pub fn paint(&self) {
+            if self.visibility.get() == VisibilityState::Hidden {
+                return;
+            }
             self.canvas.queue_draw();
         }
     }
@@ -168,60 +423,150 @@ impl ContentArea {
         self.imp().verbosity.replace(verbosity);
     }
 
+    pub fn set_media_environment(&self, media: MediaEnvironment) {
+        self.imp().media.replace(media);
+    }
+
     pub fn on_toolbar_entry_activate(&self, query: &str) {
-        // todo: Add a proper URL parser.
-        let url = query
-            .trim_start_matches("http://")
-            .trim_start_matches("https://");
-        let mut url = url.split('/');
-        let (host, port) = if let Some(hp) = url.by_ref().next() {
-            let hp = hp.split(':').collect::<Vec<&str>>();
-            let host = hp.first().unwrap();
-            if let Some(port) = hp.get(1) {
-                if let Ok(port) = port.parse::<u16>() {
-                    (*host, port)
-                } else {
-                    return;
-                }
-            } else {
-                (*host, 80)
-            }
-        } else {
+        if query.starts_with(SETTINGS_PAGE_URL) {
+            self.open_settings_page(query);
+            return;
+        }
+        if query.starts_with(CACHE_PAGE_URL) {
+            self.open_cache_page(query);
             return;
+        }
+
+        // HTTPS is not supported, so it is fetched over HTTP.
+        let html = match fetch_http(&query.replacen("https://", "http://", 1)) {
+            Ok(response) => decode_html(&response.body, response.get_header("Content-Type")),
+            Err(e) => {
+                eprintln!("{:#}", e);
+                return;
+            }
         };
-        let path = if let Some(path) = url.by_ref().next() {
-            format!("/{}", path)
-        } else {
-            "/".to_string()
+        self.commit_navigation(query, &html);
+    }
+
+    /// Shows the stored site settings. The settings of an origin are cleared with
+    /// `about:settings?clear=<origin>`, and all of them are cleared with `about:settings?clear`.
+    /// The forced colors mode is turned on and off with `about:settings?forced-colors=active` and
+    /// `about:settings?forced-colors=none`, which applies to the pages loaded after it, and so do the options
+    /// of the text rendering, e.g. `about:settings?hinting=none`.
+    fn open_settings_page(&self, query: &str) {
+        let params = QueryString::parse(&query[SETTINGS_PAGE_URL.len()..]);
+        let param = params.get_pairs().first();
+        let result = match param.map(|(name, value)| (name.as_str(), value.as_str())) {
+            Some(("forced-colors", mode)) => {
+                self.imp().media.borrow_mut().forced_colors = mode == "active";
+                Ok(())
+            }
+            Some(("clear", "")) => self.imp().settings.borrow_mut().clear_all(),
+            Some(("clear", origin)) => {
+                let origin = get_origin(origin).unwrap_or_else(|| origin.to_string());
+                self.imp().settings.borrow_mut().clear(&origin)
+            }
+            Some((name, value)) => {
+                let mut options = get_text_rendering_options();
+                options
+                    .set(name, value)
+                    .map(|_| set_text_rendering_options(options))
+            }
+            None => Ok(()),
         };
+        if let Err(e) = result {
+            eprintln!("{:#}", e);
+        }
+        let forced_colors = self.imp().media.borrow().forced_colors;
+        let html = self
+            .imp()
+            .settings
+            .borrow()
+            .to_html(forced_colors, &get_text_rendering_options());
+        self.commit_navigation(SETTINGS_PAGE_URL, &html);
+    }
+
+    /// Shows the resources in the disk cache, which are cleared with `about:cache?clear`.
+    fn open_cache_page(&self, query: &str) {
+        let params = QueryString::parse(&query[CACHE_PAGE_URL.len()..]);
+        let html = match get_shared_cache() {
+            Some(cache) => {
+                let mut cache = lock(cache);
+                if params.get("clear").is_some() {
+                    if let Err(e) = cache.clear() {
+                        eprintln!("{:#}", e);
+                    }
+                }
+                cache.to_html()
+            }
+            None => "<html><head><title>Cache</title></head><body><h1>Cache</h1><p>The cache is disabled since its location is unknown.</p></body></html>".to_string(),
+        };
+        self.commit_navigation(CACHE_PAGE_URL, &html);
+    }
 
-        let client = HttpClient::new(host, port);
-        let headers = vec![
-            // HTTP/1.1 client must contain Host header.
-            // https://datatracker.ietf.org/doc/html/rfc9112#section-3.2
-            ("Host", host),
-            // ("User-Agent", "pentas"),
-            // todo: Remove this header and handle Content-Length in the client.
-            ("Connection", "close"),
-        ];
-        let html = match client.send_request("GET", &path, &headers, None) {
-            Ok(response) => response.body,
+    /// Parses the page on the worker thread, and commits the navigation once it is rendered. The navigation
+    /// whose page is still being parsed is canceled.
+    fn commit_navigation(&self, query: &str, html: &str) {
+        self.cancel_pending_navigation();
+        let pending = match PendingDocument::parse(html, query, *self.imp().media.borrow()) {
+            Ok(pending) => pending,
             Err(e) => {
-                eprintln!("{}", e);
+                eprintln!("{:#}", e);
                 return;
             }
         };
+        let obj = self.downgrade();
+        let query = query.to_string();
+        let timer = glib::timeout_add_local(PARSE_POLL_INTERVAL, move || {
+            let Some(obj) = obj.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            let imp = obj.imp();
+            let Some(result) = pending.poll() else {
+                return glib::ControlFlow::Continue;
+            };
+            // The source is removed when `Break` is returned.
+            imp.parse_timer.take();
+            let result = result.and_then(|document| {
+                // https://html.spec.whatwg.org/multipage/browsing-the-web.html#scroll-to-the-fragment-identifier
+                if let Some((_, fragment)) = query.split_once('#') {
+                    document.set_target(fragment);
+                }
+                let objects = document.render(
+                    &imp.media.borrow(),
+                    imp.canvas.width(),
+                    imp.canvas.height(),
+                    &obj.create_draw_context(),
+                    *imp.verbosity.borrow(),
+                )?;
+                Ok((document, objects))
+            });
+            match result {
+                Ok((document, objects)) => {
+                    obj.show_page(&query, &objects);
+                    imp.document.replace(Some(document));
+                }
+                Err(e) => eprintln!("{:#}", e),
+            }
+            glib::ControlFlow::Break
+        });
+        self.imp().parse_timer.replace(Some(timer));
+    }
 
-        let objects = get_render_objects(
-            &html,
-            self.imp().canvas.width(),
-            self.imp().canvas.height(),
-            &self.imp().canvas.create_pango_context(),
-            *self.imp().verbosity.borrow(),
-        )
-        .unwrap();
+    /// Stops polling the page being parsed, which closes its parser thread.
+    fn cancel_pending_navigation(&self) {
+        if let Some(timer) = self.imp().parse_timer.take() {
+            timer.remove();
+        }
+    }
 
-        self.imp().history.borrow_mut().add(query, &objects);
+    /// Shows the rendered page and adds it to the history.
+    fn show_page(&self, query: &str, objects: &RenderObjects) {
+        self.imp().focused_area.replace(None);
+        self.blur_editor();
+        self.apply_site_settings(query);
+        self.schedule_refresh(query, &objects.metadata);
+        self.imp().history.borrow_mut().add(query, objects);
         self.emit_by_name::<()>(
             "history-updated",
             &[
@@ -234,9 +579,37 @@ impl ContentArea {
         self.imp().paint();
     }
 
+    /// Returns the summary of the current page used for bookmarking and sharing it.
+    pub fn get_share_preview(&self) -> Option<SharePreview> {
+        self.imp()
+            .history
+            .borrow()
+            .get_current()
+            .map(|entry| entry.objects.metadata.get_share_preview())
+    }
+
+    pub fn get_site_settings(&self) -> SiteSettings {
+        *self.imp().site_settings.borrow()
+    }
+
+    /// Allows or blocks the feature for the origin of the current page, e.g. from the site-info popover.
+    pub fn set_site_permission(&self, permission: Permission, allowed: bool) {
+        self.update_site_settings(|settings| settings.with_permission(permission, allowed));
+    }
+
     pub fn on_backward_button_click(&self) {
         if self.imp().history.borrow().is_rewindable() {
+            self.imp().focused_area.replace(None);
+            let from_document_id = self
+                .imp()
+                .history
+                .borrow()
+                .get_current()
+                .unwrap()
+                .document_id;
             let history = self.imp().history.borrow_mut().rewind().unwrap().clone();
+            self.on_traverse(from_document_id);
+            self.apply_site_settings(&history.query);
             self.emit_by_name::<()>(
                 "history-updated",
                 &[
@@ -251,7 +624,17 @@ impl ContentArea {
 
     pub fn on_forward_button_click(&self) {
         if self.imp().history.borrow().is_forwardable() {
+            self.imp().focused_area.replace(None);
+            let from_document_id = self
+                .imp()
+                .history
+                .borrow()
+                .get_current()
+                .unwrap()
+                .document_id;
             let history = self.imp().history.borrow_mut().forward().unwrap().clone();
+            self.on_traverse(from_document_id);
+            self.apply_site_settings(&history.query);
             self.emit_by_name::<()>(
                 "history-updated",
                 &[
@@ -263,4 +646,962 @@ impl ContentArea {
             self.imp().paint();
         }
     }
+
+    /// Fires the `popstate` event if the traversal stayed in the same document, which happens between the
+    /// entries added by `push_state()`. Otherwise, the scheduled refresh of the previous page is canceled.
+    fn on_traverse(&self, from_document_id: u64) {
+        self.cancel_pending_navigation();
+        self.blur_editor();
+        let state = self
+            .imp()
+            .history
+            .borrow()
+            .get_pop_state(from_document_id)
+            .map(|state| state.map(|s| s.to_string()));
+        match state {
+            Some(state) => self.emit_by_name::<()>("popstate", &[&state]),
+            None => {
+                self.cancel_refresh();
+                self.imp().document.take();
+            }
+        }
+    }
+
+    /// Updates the size of the viewport in the environment, and renders the current page again if the
+    /// `@media` rules which apply to it change.
+    /// https://drafts.csswg.org/cssom-view/#evaluate-media-queries-and-report-changes
+    fn on_viewport_resize(&self) {
+        let imp = self.imp();
+        let (Some(hadjustment), Some(vadjustment)) =
+            (imp.viewport.hadjustment(), imp.viewport.vadjustment())
+        else {
+            return;
+        };
+        let zoom = imp.site_settings.borrow().zoom;
+        let old = *imp.media.borrow();
+        let new = MediaEnvironment {
+            viewport_width: (hadjustment.page_size() / zoom).round() as i32,
+            viewport_height: (vadjustment.page_size() / zoom).round() as i32,
+            ..old
+        };
+        if new == old || new.viewport_width <= 0 || new.viewport_height <= 0 {
+            return;
+        }
+        imp.media.replace(new);
+
+        let result = match &*imp.document.borrow() {
+            Some(document) if document.is_affected_by_media_change(&old, &new) => document.render(
+                &new,
+                imp.canvas.width(),
+                imp.canvas.height(),
+                &self.create_draw_context(),
+                *imp.verbosity.borrow(),
+            ),
+            _ => return,
+        };
+        match result {
+            Ok(objects) => {
+                self.set_current_objects(&objects);
+                imp.paint();
+            }
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+
+    /// Runs the commands of the remote debugging clients on the current page, which are polled on the main loop
+    /// as long as the content area is alive.
+    pub fn serve_devtools(&self, devtools: DevToolsServer) {
+        let obj = self.downgrade();
+        glib::timeout_add_local(DEVTOOLS_POLL_INTERVAL, move || {
+            let Some(obj) = obj.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            while let Some(request) = devtools.try_recv() {
+                let result = obj.run_devtools_command(&request.command);
+                request.reply(result);
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+
+    fn run_devtools_command(&self, command: &DevToolsCommand) -> anyhow::Result<Value> {
+        if let DevToolsCommand::Navigate { url } = command {
+            self.on_toolbar_entry_activate(url);
+            return Ok(json!({}));
+        }
+        if let DevToolsCommand::CaptureScreenshot = command {
+            let png = self.capture_screenshot()?;
+            return Ok(json!({ "data": glib::base64_encode(&png).as_str() }));
+        }
+        if let DevToolsCommand::Click { selector } = command {
+            self.click(selector)?;
+            return Ok(json!({}));
+        }
+        if let DevToolsCommand::SetTitle { title } = command {
+            self.set_title(title)?;
+            return Ok(json!({}));
+        }
+        if let DevToolsCommand::PushState { state, url } = command {
+            self.push_state(state.as_deref(), url.as_deref())?;
+            return Ok(json!({}));
+        }
+        if let DevToolsCommand::ReplaceState { state, url } = command {
+            self.replace_state(state.as_deref(), url.as_deref())?;
+            return Ok(json!({}));
+        }
+        let document = self.imp().document.borrow();
+        let document = document.as_ref().context("No document is loaded")?;
+        Ok(match command {
+            DevToolsCommand::GetDocument => {
+                json!({ "html": document.serialize()?, "title": document.get_title() })
+            }
+            DevToolsCommand::QuerySelectorAll { selector } => {
+                json!({ "nodes": document.query_selector_all(selector)? })
+            }
+            DevToolsCommand::GetComputedStyle { selector } => {
+                let style = document
+                    .get_computed_style(selector, &self.imp().media.borrow())?
+                    .into_iter()
+                    .map(|(name, value)| json!({ "name": name, "value": value }))
+                    .collect::<Vec<_>>();
+                json!({ "computedStyle": style })
+            }
+            _ => unreachable!(),
+        })
+    }
+
+    /// Clicks the first element on the current page which matches the selector list, and then renders the
+    /// page again, follows the hyperlink or submits the form.
+    fn click(&self, selectors: &str) -> anyhow::Result<()> {
+        let imp = self.imp();
+        let activation = imp
+            .document
+            .borrow()
+            .as_ref()
+            .context("No document is loaded")?
+            .activate(selectors)?;
+        match activation {
+            Activation::None => {}
+            Activation::Restyle => self.restyle()?,
+            Activation::FollowHyperlink(href) => self.follow_hyperlink(&href),
+            Activation::SubmitForm(submission) => self.submit_form(&submission),
+        }
+        Ok(())
+    }
+
+    /// Prints the current page with the print dialog, rendering it again with the `print` media type.
+    /// https://html.spec.whatwg.org/multipage/timers-and-user-prompts.html#printing
+    fn print(&self) -> anyhow::Result<()> {
+        let imp = self.imp();
+        let (page_settings, objects) = imp
+            .document
+            .borrow()
+            .as_ref()
+            .context("No document is loaded")?
+            .render_for_print(
+                &imp.media.borrow(),
+                &self.create_draw_context(),
+                *imp.verbosity.borrow(),
+            )?;
+        let window = self.root().and_downcast::<gtk4::Window>();
+        print_pages(&objects, &page_settings, window.as_ref(), None)
+    }
+
+    /// Returns the context with which the text of the pages is laid out.
+    fn create_draw_context(&self) -> pango::Context {
+        let draw_ctx = self.imp().canvas.create_pango_context();
+        set_font_options(&draw_ctx, &get_text_rendering_options());
+        draw_ctx
+    }
+
+    /// Renders the current page again after the states or the attributes of its elements change.
+    fn restyle(&self) -> anyhow::Result<()> {
+        let imp = self.imp();
+        let objects = imp
+            .document
+            .borrow()
+            .as_ref()
+            .context("No document is loaded")?
+            .render(
+                &imp.media.borrow(),
+                imp.canvas.width(),
+                imp.canvas.height(),
+                &self.create_draw_context(),
+                *imp.verbosity.borrow(),
+            )?;
+        self.set_current_objects(&objects);
+        imp.paint();
+        Ok(())
+    }
+
+    /// Replaces the objects of the current page with the ones rendered again, and notifies the window if
+    /// its title changes. The changes of the live regions are announced, and the caret area is updated if the
+    /// editing host of the focused editor is resized.
+    fn set_current_objects(&self, objects: &RenderObjects) {
+        let is_title_changed = self.imp().history.borrow_mut().set_current_objects(objects);
+        if is_title_changed {
+            self.emit_by_name::<()>("title-changed", &[&self.get_title()]);
+        }
+        let imp = self.imp();
+        let is_host_resized = match imp.document.borrow().as_ref() {
+            Some(document) => {
+                document.update_live_regions(&AccessibleAnnouncer::new(&imp.canvas.get()));
+                document.take_editing_host_resize()
+            }
+            None => false,
+        };
+        if is_host_resized {
+            self.update_caret_area();
+        }
+    }
+
+    /// Returns the title of the current page shown in the window.
+    pub fn get_title(&self) -> String {
+        self.imp()
+            .history
+            .borrow()
+            .get_current()
+            .map(|entry| entry.get_title())
+            .unwrap_or_default()
+    }
+
+    /// Sets the title of the current page, and renders it again to update its metadata.
+    /// https://html.spec.whatwg.org/multipage/dom.html#document.title
+    fn set_title(&self, title: &str) -> anyhow::Result<()> {
+        self.imp()
+            .document
+            .borrow()
+            .as_ref()
+            .context("No document is loaded")?
+            .set_title(title)?;
+        self.restyle()
+    }
+
+    /// Updates the user action states of the elements of the current page, and renders it again if any
+    /// style depends on the changed states, in which case only the invalidated elements are restyled.
+    fn update_user_action(&self, update: impl FnOnce(&ParsedDocument) -> bool) {
+        let needs_restyle = self.imp().document.borrow().as_ref().is_some_and(update);
+        if needs_restyle {
+            if let Err(e) = self.restyle() {
+                eprintln!("{:#}", e);
+            }
+        }
+    }
+
+    /// Moves the hover state to the element under the pointer, or clears it if the pointer leaves the
+    /// canvas.
+    fn on_canvas_motion(&self, pos: Option<(f64, f64)>) {
+        self.imp().pointer.set(pos);
+        let zoom = self.imp().site_settings.borrow().zoom;
+        let pos = pos.map(|(x, y)| (x / zoom, y / zoom));
+        self.update_user_action(|document| document.on_pointer_move(pos));
+    }
+
+    /// Scrolls the scroll container under the pointer by the deltas of the scroll event. The event is left
+    /// to the page if no scroll container is scrolled by it.
+    fn on_canvas_scroll(&self, dx: f64, dy: f64) -> glib::Propagation {
+        let Some((x, y)) = self.imp().pointer.get() else {
+            return glib::Propagation::Proceed;
+        };
+        let zoom = self.imp().site_settings.borrow().zoom;
+        let mut scrolled = false;
+        self.update_user_action(|document| {
+            scrolled = document.scroll_by(x / zoom, y / zoom, dx * SCROLL_STEP, dy * SCROLL_STEP);
+            scrolled
+        });
+        if scrolled {
+            glib::Propagation::Stop
+        } else {
+            glib::Propagation::Proceed
+        }
+    }
+
+    /// Makes the element under the pointer active and focuses it while the button is pressed. The caret is
+    /// placed at the pointer if it's over an editable text.
+    fn on_canvas_press(&self, x: f64, y: f64) {
+        let zoom = self.imp().site_settings.borrow().zoom;
+        self.update_user_action(|document| document.on_pointer_down(x / zoom, y / zoom));
+        self.focus_editor_at(x / zoom, y / zoom);
+    }
+
+    /// Places the caret at the position of the document in the editor of its editing host, which is focused,
+    /// or blurs the editor if the position is not in an editable text.
+    fn focus_editor_at(&self, x: f64, y: f64) {
+        let imp = self.imp();
+        let position = imp
+            .document
+            .borrow()
+            .as_ref()
+            .and_then(|document| document.get_caret_position(x, y));
+        let Some((node, offset)) = position else {
+            self.blur_editor();
+            return;
+        };
+        {
+            let mut editor = imp.editor.borrow_mut();
+            if !editor.as_mut().is_some_and(|e| e.set_caret(&node, offset)) {
+                *editor = Editor::with_caret(&node, offset);
+            }
+            if editor.is_none() {
+                return;
+            }
+            if let Some(document) = imp.document.borrow().as_ref() {
+                document.observe_editing_host(editor.as_ref());
+            }
+        }
+        // The composition is canceled when the caret is moved by the pointer.
+        imp.im_context.reset();
+        imp.is_composing.set(false);
+        imp.keys.set_im_context(Some(&imp.im_context));
+        imp.im_context.focus_in();
+        self.on_editor_input(false);
+    }
+
+    /// Blurs the focused editor, which stops the input method and the blinking of the caret.
+    fn blur_editor(&self) {
+        let imp = self.imp();
+        if imp.editor.take().is_none() {
+            return;
+        }
+        if let Some(document) = imp.document.borrow().as_ref() {
+            document.observe_editing_host(None);
+        }
+        imp.im_context.reset();
+        imp.im_context.focus_out();
+        imp.is_composing.set(false);
+        imp.keys.set_im_context(None::<&gtk4::IMContext>);
+        if let Some(caret_blink) = imp.caret_blink.take() {
+            caret_blink.remove();
+        }
+        imp.paint();
+    }
+
+    /// Renders the page again if the text of the focused editor is changed, and shows the caret at its new
+    /// position, restarting its blinking.
+    fn on_editor_input(&self, is_edited: bool) {
+        if is_edited {
+            if let Err(e) = self.restyle() {
+                eprintln!("{:#}", e);
+            }
+        }
+        self.restart_caret_blink();
+        self.update_caret_area();
+        self.imp().paint();
+    }
+
+    /// Shows the caret, and blinks it on the frame clock from now until it stops blinking after a while
+    /// without input.
+    fn restart_caret_blink(&self) {
+        let imp = self.imp();
+        imp.last_input_time.set(glib::monotonic_time());
+        imp.is_caret_visible.set(true);
+        if imp.caret_blink.borrow().is_some() {
+            return;
+        }
+        let caret_blink = imp.canvas.add_tick_callback(glib::clone!(
+            #[weak(rename_to = obj)]
+            self,
+            #[upgrade_or]
+            glib::ControlFlow::Break,
+            move |_, clock| {
+                let imp = obj.imp();
+                let elapsed = (clock.frame_time() - imp.last_input_time.get()).max(0) as u64;
+                let elapsed = Duration::from_micros(elapsed);
+                let is_visible = is_caret_visible(elapsed);
+                if imp.is_caret_visible.replace(is_visible) != is_visible {
+                    imp.paint();
+                }
+                if elapsed >= CARET_BLINK_TIMEOUT {
+                    // The callback is removed when `Break` is returned.
+                    imp.caret_blink.take();
+                    glib::ControlFlow::Break
+                } else {
+                    glib::ControlFlow::Continue
+                }
+            }
+        ));
+        imp.caret_blink.replace(Some(caret_blink));
+    }
+
+    /// Tells the input method the position of the caret on the canvas, next to which the candidates are
+    /// shown.
+    fn update_caret_area(&self) {
+        let imp = self.imp();
+        let area = imp.editor.borrow().as_ref().and_then(|editor| {
+            imp.document
+                .borrow()
+                .as_ref()
+                .and_then(|document| document.get_caret_area(editor))
+        });
+        if let Some(area) = area {
+            let zoom = imp.site_settings.borrow().zoom;
+            imp.im_context.set_cursor_location(&gdk::Rectangle::new(
+                (area.x * zoom).round() as i32,
+                (area.y * zoom).round() as i32,
+                1,
+                (area.height * zoom).ceil() as i32,
+            ));
+        }
+    }
+
+    /// Takes the output color space from the monitor which the window is on, and updates it when the window
+    /// moves to another monitor.
+    fn watch_output_color_space(&self) {
+        let Some(surface) = self.native().and_then(|native| native.surface()) else {
+            return;
+        };
+        let update = glib::clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |monitor: &gdk::Monitor| {
+                *this.imp().output_color_space.borrow_mut() = get_monitor_color_space(monitor);
+                this.imp().canvas.queue_draw();
+            }
+        );
+        if let Some(monitor) = surface.display().monitor_at_surface(&surface) {
+            update(&monitor);
+        }
+        surface.connect_enter_monitor(move |_, monitor| update(monitor));
+    }
+
+    /// Resumes the lazily loaded images of the current page which approach `viewport`, and renders it again
+    /// after the current draw to fetch them.
+    fn update_lazy_loading(&self, viewport: (f64, f64, f64, f64)) {
+        let is_resumed = self
+            .imp()
+            .document
+            .borrow()
+            .as_ref()
+            .is_some_and(|document| document.update_lazy_loading(viewport));
+        if is_resumed {
+            let obj = self.downgrade();
+            glib::idle_add_local_once(move || {
+                if let Some(obj) = obj.upgrade() {
+                    if let Err(e) = obj.restyle() {
+                        eprintln!("{:#}", e);
+                    }
+                }
+            });
+        }
+    }
+
+    /// Fetches the images of the current page which are painted in `viewport`, or which are about to be.
+    fn fetch_images(&self, viewport: (f64, f64, f64, f64)) {
+        let history = self.imp().history.borrow();
+        let current = history.get_current().unwrap();
+        fetch_images(
+            &current.objects.get_scrolled_list(viewport),
+            &get_base_url(&current.query, &current.objects.metadata),
+            &DefaultFetcher::new(&current.query, Cancellable::default()),
+            viewport,
+        );
+    }
+
+    /// Paints the caret of the focused editor and the text being composed at it over the page.
+    fn paint_caret(&self, ctx: &gtk4::cairo::Context) {
+        let imp = self.imp();
+        let objects = match (&*imp.editor.borrow(), &*imp.document.borrow()) {
+            (Some(editor), Some(document)) => document.get_caret_objects(
+                editor,
+                imp.is_caret_visible.get(),
+                &self.create_draw_context(),
+            ),
+            _ => return,
+        };
+        paint(
+            &imp.canvas.get(),
+            &objects,
+            ctx,
+            false,
+            imp.output_color_space
+                .borrow()
+                .as_ref()
+                .unwrap_or(&RgbColorSpace::SRGB),
+        );
+    }
+
+    /// Updates the composition of the focused editor with the preedit text of the input method.
+    /// https://w3c.github.io/uievents/#event-type-compositionupdate
+    fn on_preedit_changed(&self, im_context: &gtk4::IMMulticontext) {
+        let (text, _, cursor) = im_context.preedit_string();
+        match self.imp().editor.borrow_mut().as_mut() {
+            Some(editor) => editor.update_composition(&text, cursor.max(0) as usize),
+            None => return,
+        }
+        self.on_editor_input(false);
+    }
+
+    /// Inserts the text committed by the input method into the focused editor, which ends the composition
+    /// if any.
+    fn on_im_commit(&self, text: &str) {
+        let is_composed = self.imp().is_composing.take();
+        match self.imp().editor.borrow_mut().as_mut() {
+            Some(editor) if is_composed => editor.commit_composition(text),
+            Some(editor) => editor.insert_text(text),
+            None => return,
+        }
+        self.on_editor_input(true);
+    }
+
+    /// Moves the caret of the focused editor with the arrow keys, deletes the text with Backspace and
+    /// Delete, and undoes and redoes the edits with Ctrl+Z and Ctrl+Shift+Z. Returns `None` if the key is
+    /// not handled by the editor.
+    /// https://w3c.github.io/editing/docs/execCommand/#editing-commands
+    fn on_editor_key_press(
+        &self,
+        key: gdk::Key,
+        state: gdk::ModifierType,
+    ) -> Option<glib::Propagation> {
+        let is_edited = {
+            let mut editor = self.imp().editor.borrow_mut();
+            let editor = editor.as_mut()?;
+            match (key, state.contains(gdk::ModifierType::CONTROL_MASK)) {
+                (gdk::Key::Left | gdk::Key::KP_Left, false) => {
+                    editor.move_backward();
+                    false
+                }
+                (gdk::Key::Right | gdk::Key::KP_Right, false) => {
+                    editor.move_forward();
+                    false
+                }
+                (gdk::Key::Up | gdk::Key::KP_Up, false) => {
+                    editor.move_up();
+                    false
+                }
+                (gdk::Key::Down | gdk::Key::KP_Down, false) => {
+                    editor.move_down();
+                    false
+                }
+                (gdk::Key::BackSpace, false) => {
+                    editor.delete_backward();
+                    true
+                }
+                (gdk::Key::Delete | gdk::Key::KP_Delete, false) => {
+                    editor.delete_forward();
+                    true
+                }
+                (gdk::Key::z, true) => editor.undo(),
+                (gdk::Key::Z, true) => editor.redo(),
+                _ => return None,
+            }
+        };
+        self.on_editor_input(is_edited);
+        Some(glib::Propagation::Stop)
+    }
+
+    /// Returns the visible area of the page as it is painted, encoded in PNG.
+    /// https://chromedevtools.github.io/devtools-protocol/tot/Page/#method-captureScreenshot
+    fn capture_screenshot(&self) -> anyhow::Result<Vec<u8>> {
+        let viewport = self.imp().viewport.get();
+        let snapshot = gtk4::Snapshot::new();
+        gtk4::WidgetPaintable::new(Some(&viewport)).snapshot(
+            &snapshot,
+            viewport.width() as f64,
+            viewport.height() as f64,
+        );
+        let node = snapshot.to_node().context("Nothing is painted")?;
+        let renderer = self
+            .native()
+            .and_then(|native| native.renderer())
+            .context("The window is not realized")?;
+        let texture = renderer.render_texture(node, None);
+        gdk::pixbuf_get_from_texture(&texture)
+            .context("Failed to download the screenshot")?
+            .save_to_bufferv("png", &[])
+            .context("Failed to encode the screenshot")
+    }
+
+    /// Adds a history entry for the current page without loading it again, as `history.pushState()`
+    /// does. `url` is resolved against the URL of the current page, and must have the same origin.
+    pub fn push_state(&self, state: Option<&str>, url: Option<&str>) -> anyhow::Result<()> {
+        self.imp().history.borrow_mut().push_state(state, url)?;
+        self.on_history_state_changed();
+        Ok(())
+    }
+
+    /// Replaces the current history entry in the same way as `push_state()`, as `history.replaceState()`
+    /// does.
+    pub fn replace_state(&self, state: Option<&str>, url: Option<&str>) -> anyhow::Result<()> {
+        self.imp().history.borrow_mut().replace_state(state, url)?;
+        self.on_history_state_changed();
+        Ok(())
+    }
+
+    /// Updates the URL bar and the navigation buttons after the history is changed by the page.
+    fn on_history_state_changed(&self) {
+        let query = self
+            .imp()
+            .history
+            .borrow()
+            .get_current()
+            .unwrap()
+            .query
+            .clone();
+        self.emit_by_name::<()>(
+            "history-updated",
+            &[
+                &query,
+                &self.imp().history.borrow().is_rewindable(),
+                &self.imp().history.borrow().is_forwardable(),
+            ],
+        );
+    }
+
+    /// Schedules the navigation declared by the `meta` refresh of the page, replacing the one of the
+    /// previous page. The page is reloaded if the refresh has no URL.
+    /// https://html.spec.whatwg.org/multipage/semantics.html#shared-declarative-refresh-steps
+    fn schedule_refresh(&self, query: &str, metadata: &PageMetadata) {
+        self.cancel_refresh();
+        let Some(refresh) = &metadata.refresh else {
+            return;
+        };
+        let url = match &refresh.url {
+            Some(url) => resolve_url(&get_base_url(query, metadata), url),
+            None => query.to_string(),
+        };
+        let delay = get_timer_delay(
+            Duration::from_secs(refresh.delay.into()),
+            self.imp().visibility.get(),
+        );
+        let timer = glib::timeout_add_local_once(
+            delay,
+            glib::clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move || {
+                    // The source is removed after it is dispatched.
+                    obj.imp().refresh_timer.take();
+                    if obj.imp().visibility.get() == VisibilityState::Hidden {
+                        obj.imp().deferred_refresh.replace(Some(url));
+                    } else {
+                        obj.on_toolbar_entry_activate(&url);
+                    }
+                }
+            ),
+        );
+        self.imp().refresh_timer.replace(Some(timer));
+    }
+
+    fn cancel_refresh(&self) {
+        if let Some(timer) = self.imp().refresh_timer.take() {
+            timer.remove();
+        }
+        self.imp().deferred_refresh.take();
+    }
+
+    /// Updates whether the page is visible to the user. When the page becomes visible, it is painted, and
+    /// the navigation deferred while it was hidden is run.
+    /// https://html.spec.whatwg.org/multipage/interaction.html#update-the-visibility-state
+    pub fn set_visibility_state(&self, visibility: VisibilityState) {
+        let imp = self.imp();
+        if imp.visibility.replace(visibility) == visibility || visibility == VisibilityState::Hidden
+        {
+            return;
+        }
+        imp.paint();
+        if let Some(url) = imp.deferred_refresh.take() {
+            self.on_toolbar_entry_activate(&url);
+        }
+    }
+
+    /// Applies the stored settings of the origin of the page to which the navigation commits.
+    fn apply_site_settings(&self, query: &str) {
+        let origin = get_origin(query);
+        let settings = origin
+            .as_ref()
+            .map(|origin| self.imp().settings.borrow().get(origin))
+            .unwrap_or_default();
+        self.imp().site_settings.replace(settings);
+        self.emit_by_name::<()>("site-settings-changed", &[&origin.is_some()]);
+    }
+
+    /// Changes the settings of the origin of the current page, and stores them.
+    fn update_site_settings(&self, update: impl FnOnce(SiteSettings) -> SiteSettings) {
+        let query = self
+            .imp()
+            .history
+            .borrow()
+            .get_current()
+            .unwrap()
+            .query
+            .clone();
+        let Some(origin) = get_origin(&query) else {
+            return;
+        };
+        let settings = update(*self.imp().site_settings.borrow());
+        self.imp().site_settings.replace(settings);
+        if let Err(e) = self.imp().settings.borrow_mut().set(&origin, settings) {
+            eprintln!("{:#}", e);
+        }
+        self.emit_by_name::<()>("site-settings-changed", &[&true]);
+        self.imp().paint();
+    }
+
+    /// Follows the hyperlink of the image map area at the clicked point, if any.
+    /// https://html.spec.whatwg.org/multipage/image-maps.html#image-map-processing-model
+    fn on_canvas_click(&self, x: f64, y: f64) {
+        self.imp().canvas.grab_focus();
+        self.update_user_action(ParsedDocument::on_pointer_up);
+        if let Some(href) = self.get_hyperlink_at(x, y) {
+            self.follow_hyperlink(&href);
+        }
+    }
+
+    /// Shows the context menu at the clicked point. The menu of a misspelled word has the suggestions with
+    /// which it's replaced, and the menu of a hyperlink opens its page in a new window.
+    fn on_canvas_context_menu(&self, x: f64, y: f64) {
+        let zoom = self.imp().site_settings.borrow().zoom;
+        let (doc_x, doc_y) = (x / zoom, y / zoom);
+        let suggestions = self
+            .imp()
+            .document
+            .borrow()
+            .as_ref()
+            .and_then(|document| document.get_spelling_suggestions(doc_x, doc_y));
+        let menu = gio::Menu::new();
+        if let Some(suggestions) = suggestions {
+            for suggestion in &suggestions {
+                let item = gio::MenuItem::new(Some(suggestion), None);
+                item.set_action_and_target_value(
+                    Some("content.replace-misspelled-word"),
+                    Some(&(suggestion.as_str(), doc_x, doc_y).to_variant()),
+                );
+                menu.append_item(&item);
+            }
+            if suggestions.is_empty() {
+                // The item without an action is shown as disabled.
+                menu.append(Some("No suggestions"), None);
+            }
+        } else if let Some(href) = self.get_hyperlink_at(x, y) {
+            let item = gio::MenuItem::new(Some("Open in new window"), None);
+            item.set_action_and_target_value(
+                Some("app.new-window"),
+                Some(&self.resolve_hyperlink(&href).to_variant()),
+            );
+            menu.append_item(&item);
+        } else {
+            return;
+        }
+
+        let popover = gtk4::PopoverMenu::from_model(Some(&menu));
+        popover.set_parent(&*self.imp().canvas);
+        popover.set_has_arrow(false);
+        popover.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+        popover.connect_closed(|popover| {
+            // The popover is unparented after the item is activated, which happens after it's closed.
+            glib::idle_add_local_once(glib::clone!(
+                #[weak]
+                popover,
+                move || popover.unparent()
+            ));
+        });
+        popover.popup();
+    }
+
+    /// Replaces the misspelled word at the point of the document with the suggestion, and renders the page
+    /// again.
+    fn replace_misspelled_word(&self, x: f64, y: f64, replacement: &str) {
+        let result = self
+            .imp()
+            .document
+            .borrow()
+            .as_ref()
+            .context("No document is loaded")
+            .and_then(|document| document.replace_misspelled_word(x, y, replacement));
+        match result {
+            Ok(true) => {
+                if let Err(e) = self.restyle() {
+                    eprintln!("{:#}", e);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+
+    /// Returns the URL of the image map area at the point of the canvas, if any, as written in `href`.
+    fn get_hyperlink_at(&self, x: f64, y: f64) -> Option<String> {
+        let zoom = self.imp().site_settings.borrow().zoom;
+        let history = self.imp().history.borrow();
+        let objects = &history.get_current().unwrap().objects;
+        objects
+            .find_area_at(x / zoom, y / zoom)
+            .and_then(|index| objects.get_area(index))
+            .and_then(|area| area.href.clone())
+    }
+
+    /// Reads the text of the clipboard, and pastes it into the focused editor once it is read.
+    fn paste_from_clipboard(&self, clipboard: &gdk::Clipboard) {
+        clipboard.read_text_async(
+            None::<&gtk4::gio::Cancellable>,
+            glib::clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |result| match result {
+                    Ok(Some(text)) => obj.paste_text(&text),
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Failed to read the clipboard: {}", e),
+                }
+            ),
+        );
+    }
+
+    /// Pastes the text into the focused editor, if any.
+    /// https://w3c.github.io/clipboard-apis/#paste-action
+    pub fn paste_text(&self, text: &str) {
+        match self.imp().editor.borrow_mut().as_mut() {
+            Some(editor) => editor.paste(text),
+            None => return,
+        }
+        self.on_editor_input(true);
+    }
+
+    /// Moves the focus between the image map areas with Tab and Shift+Tab, and follows the hyperlink
+    /// of the focused area with Enter or Space.
+    /// The zoom level of the origin is changed with Ctrl+Plus, Ctrl+Minus and Ctrl+0, images are
+    /// blocked or allowed with Ctrl+Shift+I, and the clipboard is pasted with Ctrl+V. The keys for editing
+    /// are handled first while an editor is focused.
+    /// https://html.spec.whatwg.org/multipage/interaction.html#sequential-focus-navigation
+    fn on_canvas_key_press(&self, key: gdk::Key, state: gdk::ModifierType) -> glib::Propagation {
+        if let Some(propagation) = self.on_editor_key_press(key, state) {
+            return propagation;
+        }
+        if state.contains(gdk::ModifierType::CONTROL_MASK) {
+            match key {
+                gdk::Key::plus | gdk::Key::equal | gdk::Key::KP_Add => {
+                    self.update_site_settings(|settings| settings.zoomed_by(ZOOM_STEP));
+                }
+                gdk::Key::minus | gdk::Key::KP_Subtract => {
+                    self.update_site_settings(|settings| settings.zoomed_by(1.0 / ZOOM_STEP));
+                }
+                gdk::Key::_0 | gdk::Key::KP_0 => {
+                    self.update_site_settings(|settings| SiteSettings {
+                        zoom: SiteSettings::default().zoom,
+                        ..settings
+                    });
+                }
+                gdk::Key::I => {
+                    self.update_site_settings(|settings| SiteSettings {
+                        images_disabled: !settings.images_disabled,
+                        ..settings
+                    });
+                }
+                gdk::Key::v | gdk::Key::V => {
+                    self.paste_from_clipboard(&self.clipboard());
+                }
+                gdk::Key::p | gdk::Key::P => {
+                    if let Err(e) = self.print() {
+                        eprintln!("{:#}", e);
+                    }
+                }
+                _ => return glib::Propagation::Proceed,
+            }
+            return glib::Propagation::Stop;
+        }
+
+        let focused = *self.imp().focused_area.borrow();
+        match key {
+            gdk::Key::Tab | gdk::Key::ISO_Left_Tab => {
+                let focusable_areas = self
+                    .imp()
+                    .history
+                    .borrow()
+                    .get_current()
+                    .unwrap()
+                    .objects
+                    .get_focusable_areas();
+                let current = focused.and_then(|f| focusable_areas.iter().position(|a| *a == f));
+                let next = match (key == gdk::Key::Tab, current) {
+                    (true, None) => focusable_areas.first(),
+                    (true, Some(i)) => focusable_areas.get(i + 1),
+                    (false, None) => focusable_areas.last(),
+                    (false, Some(i)) => i.checked_sub(1).and_then(|i| focusable_areas.get(i)),
+                };
+                self.imp().focused_area.replace(next.copied());
+                self.imp().paint();
+                // When the focus leaves the last area, it moves to the next widget.
+                if next.is_some() {
+                    glib::Propagation::Stop
+                } else {
+                    glib::Propagation::Proceed
+                }
+            }
+            gdk::Key::Return | gdk::Key::KP_Enter | gdk::Key::space => {
+                let href = focused.and_then(|index| {
+                    self.imp()
+                        .history
+                        .borrow()
+                        .get_current()
+                        .unwrap()
+                        .objects
+                        .get_area(index)
+                        .and_then(|area| area.href.clone())
+                });
+                match href {
+                    Some(href) => {
+                        self.follow_hyperlink(&href);
+                        glib::Propagation::Stop
+                    }
+                    None => glib::Propagation::Proceed,
+                }
+            }
+            _ => glib::Propagation::Proceed,
+        }
+    }
+
+    fn follow_hyperlink(&self, href: &str) {
+        self.on_toolbar_entry_activate(&self.resolve_hyperlink(href));
+    }
+
+    /// Navigates to the action of the submitted form, which is the current page if it is omitted.
+    /// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#form-submission-algorithm
+    fn submit_form(&self, submission: &FormSubmission) {
+        let action_url = match submission.get_action() {
+            "" => self
+                .imp()
+                .history
+                .borrow()
+                .get_current()
+                .unwrap()
+                .query
+                .clone(),
+            action => self.resolve_hyperlink(action),
+        };
+        let url = submission.get_url(&action_url);
+        let FormSubmission::Post {
+            content_type, body, ..
+        } = submission
+        else {
+            self.on_toolbar_entry_activate(&url);
+            return;
+        };
+        // HTTPS is not supported, so it is submitted over HTTP.
+        match post_http(&url.replacen("https://", "http://", 1), content_type, body) {
+            Ok(response) => {
+                let html = decode_html(&response.body, response.get_header("Content-Type"));
+                self.commit_navigation(&url, &html);
+            }
+            Err(e) => eprintln!("{:#}", e),
+        }
+    }
+
+    /// Resolves the URL of the hyperlink against the base URL of the current page.
+    fn resolve_hyperlink(&self, href: &str) -> String {
+        let history = self.imp().history.borrow();
+        let current = history.get_current().unwrap();
+        resolve_url(
+            &get_base_url(&current.query, &current.objects.metadata),
+            href,
+        )
+    }
+}
+
+/// Returns the URL against which the relative URLs in the page are resolved, which is the one of the
+/// `base` element if any, or the URL of the page itself.
+/// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#document-base-url
+fn get_base_url(query: &str, metadata: &PageMetadata) -> String {
+    match &metadata.base {
+        Some(base) => resolve_url(query, base),
+        None => query.to_string(),
+    }
 }
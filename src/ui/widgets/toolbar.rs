@@ -2,14 +2,19 @@ use gtk4::prelude::*;
 use gtk4::subclass::prelude::*;
 use gtk4::{glib, template_callbacks};
 
+use crate::settings::{Permission, SiteSettings};
+
 mod imp {
+    use std::cell::{Cell, RefCell};
     use std::sync::OnceLock;
 
     use glib::subclass::InitializingObject;
     use gtk4::glib::subclass::Signal;
     use gtk4::prelude::*;
     use gtk4::subclass::prelude::*;
-    use gtk4::{glib, Button, CompositeTemplate, Entry};
+    use gtk4::{glib, Button, CheckButton, CompositeTemplate, Entry, EntryIconPosition, Popover};
+
+    use crate::settings::Permission;
 
     // "/pentas" is just a prefix. See resouces.gresource.xml
     #[derive(Debug, CompositeTemplate, Default)]
@@ -21,6 +26,14 @@ mod imp {
         pub forward_button: TemplateChild<Button>,
         #[template_child]
         pub entry: TemplateChild<Entry>,
+        /// The site-info popover of the entry, with which the features of the origin of the current page
+        /// are allowed and blocked.
+        pub site_info: RefCell<Option<Popover>>,
+        /// The check buttons of `Permission::ALL` in the popover.
+        pub permission_buttons: RefCell<Vec<CheckButton>>,
+        /// Whether the check buttons are being updated to the settings, during which their toggles are not
+        /// emitted.
+        pub is_syncing: Cell<bool>,
     }
 
     #[glib::object_subclass]
@@ -56,6 +69,47 @@ mod imp {
                 .style_context()
                 .add_provider(&provider, gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION);
             self.entry.set_hexpand(true);
+
+            let popover = Popover::new();
+            let list = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
+            list.append(&gtk4::Label::new(Some("Allowed on this site")));
+            for (i, permission) in Permission::ALL.iter().enumerate() {
+                let button = CheckButton::with_label(permission.get_label());
+                let obj = self.obj().downgrade();
+                button.connect_toggled(move |button| {
+                    let Some(obj) = obj.upgrade() else {
+                        return;
+                    };
+                    if !obj.imp().is_syncing.get() {
+                        obj.emit_by_name::<()>(
+                            "site-permission-toggled",
+                            &[&(i as u32), &button.is_active()],
+                        );
+                    }
+                });
+                list.append(&button);
+                self.permission_buttons.borrow_mut().push(button);
+            }
+            popover.set_child(Some(&list));
+            popover.set_parent(&*self.entry);
+            self.entry
+                .set_primary_icon_tooltip_text(Some("Site information"));
+            let weak_popover = popover.downgrade();
+            self.entry.connect_icon_press(move |entry, position| {
+                if let (Some(popover), EntryIconPosition::Primary) =
+                    (weak_popover.upgrade(), position)
+                {
+                    popover.set_pointing_to(Some(&entry.icon_area(position)));
+                    popover.popup();
+                }
+            });
+            self.site_info.replace(Some(popover));
+        }
+
+        fn dispose(&self) {
+            if let Some(popover) = self.site_info.take() {
+                popover.unparent();
+            }
         }
 
         fn signals() -> &'static [glib::subclass::Signal] {
@@ -71,6 +125,11 @@ mod imp {
                     Signal::builder("forward-button-clicked")
                         .param_types([glib::Type::STRING])
                         .build(),
+                    // Emitted with the index of the permission in `Permission::ALL` and whether it is
+                    // allowed.
+                    Signal::builder("site-permission-toggled")
+                        .param_types([glib::Type::U32, glib::Type::BOOL])
+                        .build(),
                 ]
             })
         }
@@ -103,6 +162,25 @@ impl Toolbar {
         self.emit_by_name::<()>("toolbar-entry-activated", &[&self.imp().entry.text()]);
     }
 
+    /// Shows the settings of the origin of the current page in the site-info popover. The icon of the
+    /// popover is hidden for the pages without origins, such as the internal pages.
+    pub fn set_site_settings(&self, settings: Option<SiteSettings>) {
+        let imp = self.imp();
+        imp.entry
+            .set_primary_icon_name(settings.map(|_| "dialog-information-symbolic"));
+        let Some(settings) = settings else {
+            if let Some(popover) = imp.site_info.borrow().as_ref() {
+                popover.popdown();
+            }
+            return;
+        };
+        imp.is_syncing.set(true);
+        for (button, permission) in imp.permission_buttons.borrow().iter().zip(Permission::ALL) {
+            button.set_active(settings.is_allowed(permission));
+        }
+        imp.is_syncing.set(false);
+    }
+
     pub fn on_history_update(
         &self,
         query: &str,
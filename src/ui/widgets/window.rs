@@ -1,16 +1,21 @@
 use gtk4::glib::{self, Object};
+use gtk4::prelude::GtkWindowExt as _;
 use gtk4::subclass::prelude::ObjectSubclassIsExt;
 use gtk4::{gio, Application};
 
 use crate::app::VerbosityLevel;
+use crate::devtools::DevToolsServer;
+use crate::renderer::MediaEnvironment;
 
 mod imp {
     use glib::subclass::InitializingObject;
     use gtk4::glib::closure_local;
     use gtk4::prelude::*;
     use gtk4::subclass::prelude::*;
-    use gtk4::{glib, style_context_add_provider_for_display, CompositeTemplate};
+    use gtk4::{gdk, glib, CompositeTemplate};
 
+    use crate::renderer::VisibilityState;
+    use crate::settings::Permission;
     use crate::ui::widgets::content::ContentArea;
     use crate::ui::widgets::toolbar::Toolbar;
 
@@ -43,18 +48,6 @@ mod imp {
         fn constructed(&self) {
             self.parent_constructed();
 
-            let provider = gtk4::CssProvider::new();
-            provider.load_from_data(
-                "window {
-                    background-color: #ffffff;
-                }",
-            );
-            style_context_add_provider_for_display(
-                &gtk4::gdk::Display::default().unwrap(),
-                &provider,
-                gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
-            );
-
             let content_area = self.content_area.get();
             self.toolbar.connect_closure(
                 "toolbar-entry-activated",
@@ -79,11 +72,28 @@ mod imp {
                     content_area.on_forward_button_click();
                 }),
             );
+            let content_area = self.content_area.get();
+            self.toolbar.connect_closure(
+                "site-permission-toggled",
+                false,
+                closure_local!(move |_: Toolbar, index: u32, allowed: bool| {
+                    content_area.set_site_permission(Permission::ALL[index as usize], allowed);
+                }),
+            );
             let toolbar = self.toolbar.get();
+            self.content_area.connect_closure(
+                "site-settings-changed",
+                false,
+                closure_local!(move |content_area: ContentArea, has_origin: bool| {
+                    toolbar.set_site_settings(has_origin.then(|| content_area.get_site_settings()));
+                }),
+            );
+            let toolbar = self.toolbar.get();
+            let window = self.obj().downgrade();
             self.content_area.connect_closure(
                 "history-updated",
                 false,
-                closure_local!(move |_: ContentArea,
+                closure_local!(move |content_area: ContentArea,
                                      query: String,
                                      is_history_rewindable: bool,
                                      is_history_forwardable: bool| {
@@ -92,8 +102,50 @@ mod imp {
                         is_history_rewindable,
                         is_history_forwardable,
                     );
+                    if let Some(window) = window.upgrade() {
+                        window.set_page_title(&content_area.get_title());
+                    }
+                }),
+            );
+            let window = self.obj().downgrade();
+            self.content_area.connect_closure(
+                "title-changed",
+                false,
+                closure_local!(move |_: ContentArea, title: String| {
+                    if let Some(window) = window.upgrade() {
+                        window.set_page_title(&title);
+                    }
                 }),
             );
+
+            // The page is hidden while the window is unmapped or minimized, during which its work is throttled.
+            let content_area = self.content_area.get();
+            self.obj().connect_map(move |_| {
+                content_area.set_visibility_state(VisibilityState::Visible);
+            });
+            let content_area = self.content_area.get();
+            self.obj().connect_unmap(move |_| {
+                content_area.set_visibility_state(VisibilityState::Hidden);
+            });
+            let content_area = self.content_area.get();
+            self.obj().connect_realize(move |window| {
+                let Some(toplevel) = window
+                    .surface()
+                    .and_then(|surface| surface.dynamic_cast::<gdk::Toplevel>().ok())
+                else {
+                    return;
+                };
+                let content_area = content_area.clone();
+                toplevel.connect_state_notify(move |toplevel| {
+                    content_area.set_visibility_state(
+                        if toplevel.state().contains(gdk::ToplevelState::MINIMIZED) {
+                            VisibilityState::Hidden
+                        } else {
+                            VisibilityState::Visible
+                        },
+                    );
+                });
+            });
         }
     }
 
@@ -117,4 +169,22 @@ impl Window {
     pub fn set_verbosity(&self, verbosity: VerbosityLevel) {
         self.imp().content_area.set_verbosity(verbosity);
     }
+
+    pub fn set_media_environment(&self, media: MediaEnvironment) {
+        self.imp().content_area.set_media_environment(media);
+    }
+
+    pub fn serve_devtools(&self, devtools: DevToolsServer) {
+        self.imp().content_area.serve_devtools(devtools);
+    }
+
+    /// Loads the page of the query as if it were entered in the toolbar.
+    pub fn load(&self, query: &str) {
+        self.imp().content_area.on_toolbar_entry_activate(query);
+    }
+
+    /// Sets the title of the page as the window title, or the name of the browser if the page has none.
+    fn set_page_title(&self, title: &str) {
+        self.set_title(Some(if title.is_empty() { "pentas" } else { title }));
+    }
 }
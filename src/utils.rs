@@ -93,6 +93,29 @@ where
     pub fn get_last_consumed(&self) -> Option<&I> {
         self.buf.get(self.pos - 1)
     }
+
+    /// Returns the index of the next item to be consumed.
+    pub fn get_pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Returns all the items, including the consumed ones.
+    pub fn get_items(&self) -> &VecDeque<I> {
+        &self.buf
+    }
+
+    /// Appends items to the end of the buffer.
+    pub fn extend(&mut self, items: impl IntoIterator<Item = I>) {
+        self.buf.extend(items);
+    }
+
+    /// Removes the first `count` consumed items from the buffer, so the indices of the remaining items
+    /// are decreased by `count`.
+    pub fn discard_consumed(&mut self, count: usize) {
+        let count = count.min(self.pos).min(self.buf.len());
+        self.buf.drain(..count);
+        self.pos -= count;
+    }
 }
 
 impl<I> Iterator for TokenIterator<I>